@@ -0,0 +1,314 @@
+//! Headless runs of the pipeline steps, for scripts and CI.
+//!
+//! Every step is a subcommand using the same code as the app. Progress goes
+//! to stderr and the result to stdout; with `--json` both are JSON, one
+//! progress event per line and a single result object, so a script can
+//! follow a run and pick up the produced files.
+//!
+//! ```text
+//! videonova-cli --json pipeline https://youtu.be/... --to ru -o out/
+//! videonova-cli translate out/talk.vtt --to de --to-name German
+//! videonova-cli serve --bind 0.0.0.0:8787 --token secret   # with the server feature
+//! ```
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use videonova_lib::utils::cookies::CookieSource;
+use videonova_lib::utils::headless::{self, EventSink, HeadlessEvent, MergeInputs, PipelineRequest, TargetLanguage, Voice};
+use videonova_lib::utils::tools;
+
+#[derive(Parser)]
+#[command(name = "videonova-cli", version, about = "Translate and dub videos without the app window")]
+struct Cli {
+    /// Print progress and the result as JSON
+    #[arg(long, global = true)]
+    json: bool,
+    /// OpenAI API key for transcription, translation and speech
+    #[arg(long, global = true, env = "OPENAI_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Download the video and audio of a URL
+    Download {
+        url: String,
+        #[command(flatten)]
+        source: SourceArgs,
+    },
+    /// Transcribe audio into a VTT file
+    Transcribe {
+        audio: PathBuf,
+        /// Output directory, defaults to the directory of the audio
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+        /// Language of the speech, detected when omitted
+        #[arg(long)]
+        language: Option<String>,
+    },
+    /// Translate a VTT file
+    Translate {
+        vtt: PathBuf,
+        #[command(flatten)]
+        target: TargetArgs,
+        /// Output directory, defaults to the directory of the VTT
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Speak a translated VTT file into a WAV timed to its cues
+    Tts {
+        vtt: PathBuf,
+        /// Output WAV, defaults to the VTT path with a .wav extension
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Original audio, for the background track and loudness
+        #[arg(long)]
+        original_audio: Option<PathBuf>,
+        #[command(flatten)]
+        voice: VoiceArgs,
+    },
+    /// Merge the video with the dubbed audio and both subtitle tracks
+    Merge {
+        #[arg(long)]
+        video: PathBuf,
+        /// Dubbed audio written by `tts`
+        #[arg(long)]
+        audio: PathBuf,
+        #[arg(long)]
+        original_audio: PathBuf,
+        #[arg(long)]
+        original_vtt: PathBuf,
+        #[arg(long)]
+        translated_vtt: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Language code of the original
+        #[arg(long)]
+        from: String,
+        #[command(flatten)]
+        target: TargetArgs,
+    },
+    /// Run all steps: download, transcribe, translate, tts and merge
+    Pipeline {
+        url: String,
+        #[command(flatten)]
+        source: SourceArgs,
+        #[command(flatten)]
+        target: TargetArgs,
+        #[command(flatten)]
+        voice: VoiceArgs,
+    },
+    /// Accept jobs over HTTP and run them one after another
+    #[cfg(feature = "server")]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8787")]
+        bind: std::net::SocketAddr,
+        /// Bearer token every request must carry; required beyond localhost
+        #[arg(long, env = "VIDEONOVA_SERVER_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+        /// Directory the jobs work in
+        #[arg(long, default_value = "videonova-jobs")]
+        data_dir: PathBuf,
+        #[arg(long)]
+        cookies: Option<PathBuf>,
+        #[arg(long)]
+        cookies_from_browser: Option<String>,
+    },
+}
+
+#[derive(clap::Args)]
+struct SourceArgs {
+    #[arg(short, long, default_value = ".")]
+    output_dir: PathBuf,
+    /// Netscape cookies.txt for videos that need a login
+    #[arg(long, conflicts_with = "cookies_from_browser")]
+    cookies: Option<PathBuf>,
+    /// Take the cookies from a browser profile (chrome, firefox, ...)
+    #[arg(long)]
+    cookies_from_browser: Option<String>,
+}
+
+impl SourceArgs {
+    fn cookie_source(&self) -> CookieSource {
+        cookie_source(&self.cookies, &self.cookies_from_browser)
+    }
+}
+
+fn cookie_source(file: &Option<PathBuf>, browser: &Option<String>) -> CookieSource {
+    match (file, browser) {
+        (Some(file), _) => CookieSource::File(file.clone()),
+        (None, Some(browser)) => CookieSource::Browser(browser.clone()),
+        (None, None) => CookieSource::Anonymous,
+    }
+}
+
+#[derive(clap::Args)]
+struct TargetArgs {
+    /// Language code to translate into
+    #[arg(long)]
+    to: String,
+    /// Language name used in prompts and track titles, defaults to the code
+    #[arg(long)]
+    to_name: Option<String>,
+}
+
+impl TargetArgs {
+    fn language(&self) -> TargetLanguage {
+        TargetLanguage { code: self.to.clone(), name: self.to_name.clone() }
+    }
+}
+
+#[derive(clap::Args)]
+struct VoiceArgs {
+    #[arg(long, default_value = "ash")]
+    voice: String,
+    #[arg(long, default_value = "tts-1-hd")]
+    model: String,
+    #[arg(long, default_value_t = 1.0)]
+    speed: f32,
+}
+
+impl VoiceArgs {
+    fn voice(&self) -> Voice {
+        Voice { voice: self.voice.clone(), model: self.model.clone(), speed: self.speed }
+    }
+}
+
+/// Prints the events of a run to stderr
+fn reporter(json: bool) -> EventSink {
+    Arc::new(move |event: HeadlessEvent| {
+        if json {
+            eprintln!("{}", serde_json::to_string(&event).unwrap_or_default());
+            return;
+        }
+        match event {
+            HeadlessEvent::Step { step } => eprintln!("==> {}", step),
+            HeadlessEvent::Progress { step, update } => {
+                let status = update.get("status").and_then(Value::as_str);
+                let progress = update.get("progress").and_then(Value::as_f64);
+                match (status, progress) {
+                    (Some(status), Some(progress)) => eprintln!("[{}] {:>3.0}% {}", step, progress, status),
+                    _ => eprintln!("[{}] {}", step, update.get("type").unwrap_or(&update)),
+                }
+            }
+        }
+    })
+}
+
+fn api_key(cli: &Cli) -> Result<&str> {
+    cli.api_key
+        .as_deref()
+        .filter(|key| !key.trim().is_empty())
+        .ok_or_else(|| anyhow!("An OpenAI API key is required, pass --api-key or set OPENAI_API_KEY"))
+}
+
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn display(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+async fn run(cli: &Cli) -> Result<Value> {
+    let sink = reporter(cli.json);
+    tools::init_tools(None).await?;
+
+    match &cli.command {
+        Command::Download { url, source } => {
+            let (info, files) = headless::download(&sink, url, &source.output_dir, &source.cookie_source()).await?;
+            Ok(json!({
+                "title": info.title,
+                "video_path": display(&files.video_path),
+                "audio_path": display(&files.audio_path),
+            }))
+        }
+        Command::Transcribe { audio, output_dir, language } => {
+            let output_dir = output_dir.clone().unwrap_or_else(|| parent_dir(audio));
+            let vtt = headless::transcribe(&sink, api_key(cli)?, audio, &output_dir, language.clone()).await?;
+            Ok(json!({ "vtt_path": display(&vtt) }))
+        }
+        Command::Translate { vtt, target, output_dir } => {
+            let output_dir = output_dir.clone().unwrap_or_else(|| parent_dir(vtt));
+            let translated = headless::translate(&sink, api_key(cli)?, vtt, &output_dir, &target.language()).await?;
+            Ok(json!({ "translated_vtt_path": display(&translated) }))
+        }
+        Command::Tts { vtt, output, original_audio, voice } => {
+            let output = output.clone().unwrap_or_else(|| vtt.with_extension("wav"));
+            let (wav, retimed) =
+                headless::tts(&sink, api_key(cli)?, vtt, &output, original_audio.as_deref(), &voice.voice()).await?;
+            Ok(json!({
+                "audio_path": display(&wav),
+                "retimed_vtt_path": retimed.as_deref().map(display),
+            }))
+        }
+        Command::Merge { video, audio, original_audio, original_vtt, translated_vtt, output, from, target } => {
+            let inputs = MergeInputs {
+                video,
+                dubbed_audio: audio,
+                original_audio,
+                original_vtt,
+                translated_vtt,
+                metadata: Default::default(),
+            };
+            let merged = headless::merge(&sink, inputs, output, from, &target.language()).await?;
+            Ok(json!({ "output_path": display(&merged) }))
+        }
+        Command::Pipeline { url, source, target, voice } => {
+            let request = PipelineRequest { url: url.clone(), target: target.language(), voice: voice.voice() };
+            let output =
+                headless::pipeline(&sink, api_key(cli)?, &request, &source.output_dir, &source.cookie_source()).await?;
+            Ok(serde_json::to_value(output)?)
+        }
+        #[cfg(feature = "server")]
+        Command::Serve { bind, token, data_dir, cookies, cookies_from_browser } => {
+            use videonova_lib::utils::server::http::{serve, ServerConfig};
+            serve(ServerConfig {
+                bind: *bind,
+                token: token.clone(),
+                data_dir: data_dir.clone(),
+                api_key: cli.api_key.clone(),
+                cookies: cookie_source(cookies, cookies_from_browser),
+            })
+            .await?;
+            Ok(Value::Null)
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    // Logs stay quiet unless asked for, stderr is for the progress
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+        .target(env_logger::Target::Stderr)
+        .init();
+
+    match run(&cli).await {
+        Ok(result) if cli.json => println!("{}", json!({ "ok": true, "result": result })),
+        Ok(result) => {
+            if let Value::Object(fields) = result {
+                for (key, value) in fields.iter().filter(|(_, value)| !value.is_null()) {
+                    println!("{}: {}", key, value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string()));
+                }
+            }
+        }
+        Err(e) => {
+            if cli.json {
+                println!("{}", json!({ "ok": false, "error": e.to_string() }));
+            } else {
+                eprintln!("error: {}", e);
+            }
+            std::process::exit(1);
+        }
+    }
+}
@@ -1,30 +1,77 @@
 use log::{error, info, warn};
-use reqwest;
 use serde::Serialize;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::thread;
-use tauri::Emitter;
-use tokio::sync::mpsc;
+use tauri::{Emitter, Listener, Manager};
+use tokio::sync::{mpsc, oneshot};
 use serde_json::json;
 use std::path::Path;
 use tokio_util::sync::CancellationToken;
 use tauri_plugin_opener::OpenerExt;
-use crate::utils::tts::tts::{synchronizer::{SyncConfig, process_sync}, ProgressUpdate, TtsConfig, AudioProcessingConfig};
+use crate::utils::tts::tts::{synchronizer::{HumanNarration, SyncConfig, process_sync}, ProgressUpdate, TtsConfig, AudioProcessingConfig};
+use crate::utils::tts::tts::loudness::LoudnessTarget;
+use crate::utils::tts::tts::progress::ProgressObservers;
+use crate::utils::tts::tts::provider::{self, SpeechProvider};
+use crate::utils::audio_probe;
+use crate::utils::cancellation;
+use crate::utils::channels;
+use crate::utils::chapters;
+use crate::utils::conditioning::TranscriptionConditioning;
+use crate::utils::transcript_cleanup::TranscriptCleanup;
+use crate::utils::confidence;
+use crate::utils::diagnose;
 use crate::utils::common::{sanitize_filename, check_file_exists_and_valid};
-use crate::utils::merge::{self, MergeProgress};
+use crate::utils::emitter;
+use crate::utils::diarization::{self, DiarizationSettings};
+use crate::utils::events::{self, StepProgress};
+use crate::utils::fish_speech::{self, ClonedVoice};
+use crate::utils::jobs;
+use crate::utils::karaoke::{self, KaraokeFormat};
+use crate::utils::logger;
+use crate::utils::job_state::{self, JobState};
+use crate::utils::library;
+use crate::utils::merge::{self, AudioFormat, MergeProgress, MergeStyle, OutputContainer, OutputMode};
+use crate::utils::narration::{self, NarrationClip};
+use crate::utils::openai_connection::{self, OpenAiAuth, OpenAiConnection};
+use crate::utils::perf_stats;
+use crate::utils::pipeline_job::{self, PipelineJob, PipelineStatus};
+use crate::utils::piper;
+use crate::utils::profiles;
+use crate::utils::progress::{self, PipelineStep, PipelineProgressTracker};
+use crate::utils::publish;
+use crate::utils::queue::{self, JobQueue, JobRequest};
+use crate::utils::quota;
+use crate::utils::remote;
+use crate::utils::regenerate;
+use crate::utils::review;
+use crate::utils::scrub::ScrubOptions;
+use crate::utils::headless::PipelineRequest;
+use crate::utils::server::{ProcessingServer, RemoteJob, SubmitJob};
+use crate::utils::settings;
+use crate::utils::source_language;
+use crate::utils::split;
+use crate::utils::subtitles::{self, SpeakerLabels, SubtitleSource};
+use crate::utils::subtitle_optimizer::{self, ReadabilitySettings};
+use crate::utils::timing_report::{self, TimingCollector, TimingReport};
+use crate::utils::optimizer_report::{self, DecisionCollector, OptimizerReport};
+use crate::utils::advanced_config::{self, AdvancedConfigStatus};
+use crate::utils::notify;
 use crate::utils::transcribe;
 use crate::utils::translate;
-use crate::utils::youtube::{self, DownloadProgress, VideoInfo};
-use crate::utils::tts::tts::soundtouch;
-
-#[derive(Clone, Serialize)]
-pub struct DownloadState {
-    progress: DownloadProgress,
-    #[serde(skip)]
-    #[allow(dead_code)]
-    progress_sender: mpsc::Sender<DownloadProgress>,
-}
+use crate::utils::validation::{CommandError, Validate};
+use crate::utils::voices;
+use crate::utils::youtube::{self, VideoInfo};
+use crate::utils::tts::tts::timestretch::StretchSettings;
+use crate::utils::tts::tts::language_speed::{self, SpeedProfile};
+use crate::utils::tts::tts::lexicon::{Lexicon, LexiconEntry};
+use crate::utils::tts::normalize::{self, NormalizationOptions, Normalizer};
+use crate::utils::tts::tts::timeline::{FitStrategy, OverlapPolicy, SentenceMerging};
+use crate::utils::tts::tts::vad::VoiceActivity;
+use crate::utils::tts::tts::vtt::{self, SubtitleFormat};
+use crate::utils::tts::tts::audio::RenderedAudio;
+use crate::utils::tts::tts::demucs;
+use std::collections::HashMap;
 
 #[derive(Serialize)]
 pub struct TranscriptionResult {
@@ -40,10 +87,26 @@ pub struct TranslationResult {
 #[derive(Serialize)]
 pub struct TTSResult {
     audio_path: String,
+    timing_report: TimingReport,
+    /// None when the speech of an earlier run was reused
+    optimizer_report: Option<OptimizerReport>,
+    /// Translated subtitles moved to where the speech plays, when the speech pushed cues later
+    retimed_vtt_path: Option<String>,
+}
+
+/// Where the synchronizer saves the translated subtitles retimed to the speech
+fn retimed_subtitles_path(tts_output: &str) -> PathBuf {
+    Path::new(tts_output).with_extension("retimed.vtt")
+}
+
+fn existing_retimed_subtitles(tts_output: &str) -> Option<String> {
+    let path = retimed_subtitles_path(tts_output);
+    path.exists().then(|| path.to_string_lossy().to_string())
 }
 
 #[derive(Serialize)]
 pub struct ProcessVideoResult {
+    job_id: String,
     video_path: String,
     audio_path: String,
     transcription_path: String,
@@ -51,6 +114,10 @@ pub struct ProcessVideoResult {
     tts_path: String,
     final_path: String,
     merged_path: String,
+    /// Parts of the output when it was split under the size cap, final_path is then their playlist
+    output_parts: Vec<String>,
+    /// Translated subtitles exported next to the output, see `set_subtitle_export`
+    subtitle_path: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -59,15 +126,6 @@ pub struct MergeResult {
     output_dir: String,
 }
 
-#[derive(Debug)]
-pub enum Step {
-    Download { url: String },
-    Transcribe,
-    Translate,
-    GenerateSpeech,
-    Merge,
-}
-
 /// Get information about a YouTube video
 #[tauri::command]
 pub async fn get_video_info(window: tauri::Window, url: String) -> Result<VideoInfo, String> {
@@ -76,28 +134,48 @@ pub async fn get_video_info(window: tauri::Window, url: String) -> Result<VideoI
         .map_err(|e| e.to_string())
 }
 
+/// Use an exported cookies.txt for YouTube instead of browser cookies
+#[tauri::command]
+pub async fn import_youtube_cookies(app_handle: tauri::AppHandle, path: String) -> Result<(), String> {
+    youtube::import_cookies_file(&app_handle, Path::new(&path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 /// Start downloading a YouTube video
 #[tauri::command]
 pub async fn download_video(
     window: tauri::Window,
     url: String,
     output_dir: String,
+) -> Result<serde_json::Value, String> {
+    download_media(window, url, output_dir, None, CancellationToken::new(), None).await
+}
+
+/// Download a video or a start/end section of it, stopping yt-dlp once the token is cancelled
+async fn download_media(
+    window: tauri::Window,
+    url: String,
+    output_dir: String,
+    section: Option<(f64, f64)>,
+    cancellation_token: CancellationToken,
+    job_id: Option<&str>,
 ) -> Result<serde_json::Value, String> {
     let (tx, mut rx) = mpsc::channel(32);
     let output_dir = PathBuf::from(output_dir);
-    let cancellation_token = CancellationToken::new();
     
     // Spawn task to handle progress updates
     let window_clone = window.clone();
+    let job_id = job_id.map(str::to_string);
     tokio::spawn(async move {
         while let Some(progress) = rx.recv().await {
-            if let Err(e) = window_clone.emit("download-progress", progress) {
+            if let Err(e) = emitter::emit_for_job(&window_clone, "download-progress", job_id.as_deref(), progress) {
                 error!("Failed to emit progress: {}", e);
             }
         }
     });
     
-    match youtube::download_video(&url, &output_dir, Some(tx), cancellation_token, &window).await {
+    match youtube::download_video(&url, &output_dir, section, Some(tx), cancellation_token, &window).await {
         Ok(result) => Ok(result.to_frontend_response()),
         Err(e) => Err(e.to_string()),
     }
@@ -111,18 +189,37 @@ pub async fn transcribe_audio(
     api_key: String,
     language: Option<String>,
     window: tauri::Window,
+) -> Result<TranscriptionResult, String> {
+    transcribe_audio_file(audio_path, output_path, api_key, language, None, window).await
+}
+
+/// Transcribe an audio file, tagging the progress with the pipeline job it belongs to
+async fn transcribe_audio_file(
+    audio_path: String,
+    output_path: String,
+    api_key: String,
+    language: Option<String>,
+    job_id: Option<&str>,
+    window: tauri::Window,
 ) -> Result<TranscriptionResult, String> {
     // Create progress channel
     let (tx, mut rx) = mpsc::channel::<transcribe::TranscriptionProgress>(32);
 
     // Clone window handle for the progress monitoring task
     let progress_window = window.clone();
+    let job_id = job_id.map(str::to_string);
 
     // Spawn progress monitoring task
     let monitoring_task = tokio::spawn(async move {
         while let Some(progress) = rx.recv().await {
+            // Early cues of long media can be reviewed while the rest transcribes
+            if let Some(partial) = &progress.partial
+                && let Err(e) = emitter::emit(&progress_window, "partial-subtitles-available", partial)
+            {
+                eprintln!("Failed to emit partial subtitles: {}", e);
+            }
             // Emit progress event to frontend
-            if let Err(e) = progress_window.emit("transcription-progress", progress) {
+            if let Err(e) = emitter::emit_for_job(&progress_window, "transcription-progress", job_id.as_deref(), progress) {
                 eprintln!("Failed to emit transcription progress: {}", e);
             }
         }
@@ -133,13 +230,26 @@ pub async fn transcribe_audio(
     tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
 
     // Start transcription
+    let options = transcribe::TranscriptionOptions {
+        language,
+        conditioning: load_transcription_conditioning(window.app_handle()),
+        cleanup: load_transcript_cleanup(window.app_handle()),
+        voice_activity: load_voice_activity(window.app_handle()),
+    };
     let audio_file = PathBuf::from(audio_path);
     let output_dir = PathBuf::from(output_path);
 
-    let result_path =
-        transcribe::transcribe_audio(&audio_file, &output_dir, &api_key, language, Some(tx))
-            .await
-            .map_err(|e| e.to_string())?;
+    let result_path = transcribe::transcribe_audio(&audio_file, &output_dir, &api_key, &options, Some(tx))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Dialog gets a speaker per cue, so each speaker can be dubbed by their own voice
+    let diarization = load_diarization(window.app_handle());
+    if diarization.enabled
+        && let Err(e) = diarization::diarize(&audio_file, &result_path, &diarization).await
+    {
+        warn!("Speaker diarization failed, the subtitles stay untagged: {}", e);
+    }
 
     // Подождем завершения задачи мониторинга (она должна завершиться
     // после закрытия канала tx при завершении transcribe_audio)
@@ -155,7 +265,7 @@ pub async fn validate_openai_key(api_key: String) -> Result<bool, String> {
     info!("Beginning OpenAI API key validation");
 
     // Create a client with detailed debug information
-    let client = reqwest::Client::builder()
+    let client = openai_connection::client_builder()
         .timeout(std::time::Duration::from_secs(10))
         .user_agent("videonova-tts-client/1.0")
         .build()
@@ -168,8 +278,8 @@ pub async fn validate_openai_key(api_key: String) -> Result<bool, String> {
     
     let request_start = std::time::Instant::now();
     let response = client
-        .get("https://api.openai.com/v1/models")
-        .header("Authorization", format!("Bearer {}", api_key))
+        .get(openai_connection::endpoint("/models"))
+        .openai_auth(&api_key)
         .send()
         .await;
     let request_duration = request_start.elapsed();
@@ -215,6 +325,7 @@ pub async fn validate_openai_key(api_key: String) -> Result<bool, String> {
 
 /// Translate VTT file to target language using OpenAI GPT-4o-mini
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn translate_vtt(
     vtt_path: String,
     output_path: String,
@@ -222,8 +333,49 @@ pub async fn translate_vtt(
     target_language: String,
     target_language_code: String,
     api_key: String,
+    encoding: Option<String>,
     window: tauri::Window,
 ) -> Result<TranslationResult, String> {
+    info!("Translating {} from {} to {}", vtt_path, source_language, target_language);
+    let request = VttTranslation {
+        vtt_path,
+        encoding,
+        output_path,
+        target_language,
+        target_language_code,
+        api_key,
+        ..VttTranslation::default()
+    };
+    translate_vtt_file(request, window).await
+}
+
+/// A VTT file to translate, see [`translate_vtt_file`]
+#[derive(Default)]
+struct VttTranslation<'a> {
+    vtt_path: String,
+    /// Overrides the detected charset of the source
+    encoding: Option<String>,
+    output_path: String,
+    /// Name of the target language
+    target_language: String,
+    target_language_code: String,
+    api_key: String,
+    cancel: Option<&'a CancellationToken>,
+    job_id: Option<&'a str>,
+}
+
+/// Translate a VTT file, stopping between batches once the token is cancelled
+async fn translate_vtt_file(request: VttTranslation<'_>, window: tauri::Window) -> Result<TranslationResult, String> {
+    let VttTranslation {
+        vtt_path,
+        encoding,
+        output_path,
+        target_language,
+        target_language_code,
+        api_key,
+        cancel,
+        job_id,
+    } = request;
     info!("Starting VTT translation to {}", target_language);
     
     // Create progress channel
@@ -231,12 +383,13 @@ pub async fn translate_vtt(
 
     // Clone window handle for the progress monitoring task
     let progress_window = window.clone();
+    let job_id = job_id.map(str::to_string);
 
     // Spawn progress monitoring task
     let monitoring_task = tokio::spawn(async move {
         while let Some(progress) = rx.recv().await {
             // Emit progress event to frontend
-            if let Err(e) = progress_window.emit("translation-progress", progress) {
+            if let Err(e) = emitter::emit_for_job(&progress_window, "translation-progress", job_id.as_deref(), progress) {
                 error!("Failed to emit translation progress: {}", e);
             }
         }
@@ -250,12 +403,18 @@ pub async fn translate_vtt(
     let vtt_file = PathBuf::from(vtt_path);
     let output_dir = PathBuf::from(output_path);
 
+    let target = translate::TranslationTarget {
+        target_language_code: &target_language_code,
+        target_language_name: &target_language,
+        api_key: &api_key,
+        options: &default_translation_options(window.app_handle()),
+    };
     let result_path = translate::translate_vtt(
         &vtt_file,
+        encoding.as_deref(),
         &output_dir,
-        &target_language_code,
-        &target_language,
-        &api_key,
+        &target,
+        cancel,
         Some(tx),
     )
     .await
@@ -276,6 +435,167 @@ pub async fn translate_vtt(
     })
 }
 
+const TRANSLATION_SCRUBBING_KEY: &str = "translation-scrubbing";
+
+fn load_translation_scrubbing(app_handle: &tauri::AppHandle) -> ScrubOptions {
+    settings::get(app_handle, TRANSLATION_SCRUBBING_KEY).unwrap_or_default()
+}
+
+/// Get where OpenAI requests go: the API base URL and the proxy
+#[tauri::command]
+pub async fn get_openai_connection() -> Result<OpenAiConnection, String> {
+    Ok(openai_connection::current())
+}
+
+/// Set an OpenAI-compatible base URL and a proxy for transcription, translation and speech
+#[tauri::command]
+pub async fn set_openai_connection(app_handle: tauri::AppHandle, connection: OpenAiConnection) -> Result<(), String> {
+    connection.validate().map_err(|e| e.to_string())?;
+    settings::set(&app_handle, openai_connection::SETTINGS_KEY, &connection).await.map_err(|e| e.to_string())?;
+    openai_connection::apply(connection);
+    Ok(())
+}
+
+const TRANSLATION_GLOSSARY_KEY: &str = "translation-glossary";
+
+fn load_translation_glossary(app_handle: &tauri::AppHandle) -> Vec<translate::GlossaryEntry> {
+    settings::get(app_handle, TRANSLATION_GLOSSARY_KEY).unwrap_or_default()
+}
+
+/// Translation options of jobs that don't pass their own
+fn default_translation_options(app_handle: &tauri::AppHandle) -> translate::TranslationOptions {
+    translate::TranslationOptions {
+        glossary: load_translation_glossary(app_handle),
+        scrub: load_translation_scrubbing(app_handle),
+        ..Default::default()
+    }
+}
+
+/// Get the glossary and do-not-translate terms of every translation
+#[tauri::command]
+pub async fn get_translation_glossary(app_handle: tauri::AppHandle) -> Result<Vec<translate::GlossaryEntry>, String> {
+    Ok(load_translation_glossary(&app_handle))
+}
+
+/// Set the glossary; an entry without a translation keeps its term untranslated
+#[tauri::command]
+pub async fn set_translation_glossary(
+    app_handle: tauri::AppHandle,
+    glossary: Vec<translate::GlossaryEntry>,
+) -> Result<(), String> {
+    let glossary: Vec<translate::GlossaryEntry> =
+        glossary.into_iter().filter(|entry| !entry.term.trim().is_empty()).collect();
+    settings::set(&app_handle, TRANSLATION_GLOSSARY_KEY, &glossary).await.map_err(|e| e.to_string())
+}
+
+const PRONUNCIATION_LEXICON_KEY: &str = "pronunciation-lexicon";
+
+fn load_pronunciation_lexicon(app_handle: &tauri::AppHandle) -> Vec<LexiconEntry> {
+    settings::get(app_handle, PRONUNCIATION_LEXICON_KEY).unwrap_or_default()
+}
+
+/// Get the pronunciations substituted for terms before they are spoken
+#[tauri::command]
+pub async fn get_pronunciation_lexicon(app_handle: tauri::AppHandle) -> Result<Vec<LexiconEntry>, String> {
+    Ok(load_pronunciation_lexicon(&app_handle))
+}
+
+/// Set the pronunciation lexicon; an entry without a language applies to every language
+#[tauri::command]
+pub async fn set_pronunciation_lexicon(app_handle: tauri::AppHandle, lexicon: Vec<LexiconEntry>) -> Result<(), String> {
+    let lexicon: Vec<LexiconEntry> = lexicon
+        .into_iter()
+        .filter(|entry| !entry.term.trim().is_empty() && !entry.pronunciation.trim().is_empty())
+        .collect();
+    settings::set(&app_handle, PRONUNCIATION_LEXICON_KEY, &lexicon).await.map_err(|e| e.to_string())
+}
+
+const TEXT_NORMALIZATION_KEY: &str = "text-normalization";
+
+/// Normalization options by language code; languages without an entry use the defaults
+fn load_text_normalization(app_handle: &tauri::AppHandle) -> HashMap<String, NormalizationOptions> {
+    settings::get(app_handle, TEXT_NORMALIZATION_KEY).unwrap_or_default()
+}
+
+/// Get how numbers, units and abbreviations are spelled out per language before synthesis
+#[tauri::command]
+pub async fn get_text_normalization(app_handle: tauri::AppHandle) -> Result<HashMap<String, NormalizationOptions>, String> {
+    Ok(load_text_normalization(&app_handle))
+}
+
+/// Set the normalization options per language code
+#[tauri::command]
+pub async fn set_text_normalization(
+    app_handle: tauri::AppHandle,
+    normalization: HashMap<String, NormalizationOptions>,
+) -> Result<(), String> {
+    let normalization: HashMap<String, NormalizationOptions> =
+        normalization.into_iter().map(|(language, options)| (language.to_lowercase(), options)).collect();
+    settings::set(&app_handle, TEXT_NORMALIZATION_KEY, &normalization).await.map_err(|e| e.to_string())
+}
+
+/// Get what is masked in transcripts before they are sent for translation
+#[tauri::command]
+pub async fn get_translation_scrubbing(app_handle: tauri::AppHandle) -> Result<ScrubOptions, String> {
+    Ok(load_translation_scrubbing(&app_handle))
+}
+
+/// Set what is masked in transcripts before they are sent for translation
+#[tauri::command]
+pub async fn set_translation_scrubbing(app_handle: tauri::AppHandle, options: ScrubOptions) -> Result<(), String> {
+    settings::set(&app_handle, TRANSLATION_SCRUBBING_KEY, &options).await.map_err(|e| e.to_string())
+}
+
+/// Translate a local VTT or SRT file on its own, without a video. Returns the
+/// path of the translated file, which keeps the format of the source.
+#[tauri::command]
+pub async fn translate_subtitle_file(
+    request: translate::SubtitleFileRequest,
+    window: tauri::Window,
+) -> Result<String, CommandError> {
+    request.validate()?;
+    let translate::SubtitleFileRequest {
+        input_path,
+        output_path,
+        target_language,
+        target_language_code,
+        api_key,
+        options,
+        encoding,
+    } = request;
+    let (tx, mut rx) = mpsc::channel::<translate::TranslationProgress>(32);
+    let progress_window = window.clone();
+    let monitoring_task = tokio::spawn(async move {
+        while let Some(progress) = rx.recv().await {
+            if let Err(e) = emitter::emit(&progress_window, "translation-progress", progress) {
+                error!("Failed to emit translation progress: {}", e);
+            }
+        }
+    });
+
+    let output_path = output_path.filter(|path| !path.trim().is_empty()).map(PathBuf::from);
+    let options = options.unwrap_or_else(|| default_translation_options(window.app_handle()));
+    let target = translate::TranslationTarget {
+        target_language_code: &target_language_code,
+        target_language_name: &target_language,
+        api_key: &api_key,
+        options: &options,
+    };
+    let result = translate::translate_subtitle_file(
+        Path::new(&input_path),
+        encoding.as_deref(),
+        output_path.as_deref(),
+        &target,
+        Some(tx),
+    )
+    .await;
+    let _ = monitoring_task.await;
+
+    Ok(result
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())?)
+}
+
 struct TauriProgressObserver {
     window: tauri::Window,
 }
@@ -286,17 +606,78 @@ impl TauriProgressObserver {
     }
 }
 
-/// Enhanced TTS function with detailed logging for troubleshooting
-async fn enhanced_tts_with_logging(
-    video_path: &str,
-    audio_path: &str,
-    original_vtt_path: &str,
-    translated_vtt_path: &str,
-    output_path: &str,
-    api_key: &str,
+/// Everything `enhanced_tts_with_logging` needs, resolved from the settings
+/// by `synthesize_speech`
+struct TtsRun<'a> {
+    video_path: &'a str,
+    audio_path: &'a str,
+    original_vtt_path: &'a str,
+    translated_vtt_path: &'a str,
+    subtitle_encoding: Option<String>,
+    output_path: &'a str,
+    api_key: &'a str,
+    speed_profile: SpeedProfile,
+    overlap_policy: OverlapPolicy,
+    fit_strategy: FitStrategy,
+    stretch: StretchSettings,
+    loudness: LoudnessTarget,
+    lip_sync: bool,
+    sentence_merging: SentenceMerging,
+    voice_activity: VoiceActivity,
+    lexicon: Lexicon,
+    normalizer: Normalizer,
+    speech_to_speech: Option<String>,
+    engine: String,
+    voice: String,
+    speaker_voices: HashMap<String, String>,
+    narration: Vec<HumanNarration>,
+    instrumental: Option<PathBuf>,
+    stream_to: Option<oneshot::Sender<RenderedAudio>>,
     observer: TauriProgressObserver,
-) -> Result<String, String> {
+    timing: Arc<TimingCollector>,
+    decisions: Arc<DecisionCollector>,
+    cancel: CancellationToken,
+    job_id: Option<String>,
+}
+
+/// Enhanced TTS function with detailed logging for troubleshooting
+async fn enhanced_tts_with_logging(run: TtsRun<'_>) -> Result<String, String> {
+    let TtsRun {
+        video_path,
+        audio_path,
+        original_vtt_path,
+        translated_vtt_path,
+        subtitle_encoding,
+        output_path,
+        api_key,
+        speed_profile,
+        overlap_policy,
+        fit_strategy,
+        stretch,
+        loudness,
+        lip_sync,
+        sentence_merging,
+        voice_activity,
+        lexicon,
+        normalizer,
+        speech_to_speech,
+        engine,
+        voice,
+        speaker_voices,
+        narration,
+        instrumental,
+        stream_to,
+        observer,
+        timing,
+        decisions,
+        cancel,
+        job_id,
+    } = run;
     info!("Starting enhanced TTS with detailed logging");
+    info!(
+        "Speed profile: TTS speed {:.2}, tempo {:.2}-{:.2}",
+        speed_profile.tts_speed, speed_profile.min_tempo, speed_profile.max_tempo
+    );
     
     // Log file sizes and existence for debugging
     for (path, desc) in [
@@ -346,10 +727,14 @@ async fn enhanced_tts_with_logging(
     
     // Clone all the values we need to pass to the thread
     let translated_vtt_path_clone = translated_vtt_path.to_string();
+    let original_vtt_path_clone = original_vtt_path.to_string();
     let api_key_clone = api_key.to_string();
     let output_path_clone = output_path.to_string();
     let audio_path_clone = audio_path.to_string();
     let window_clone = observer.window.clone();
+    let streaming = stream_to.is_some();
+    let cancel_clone = cancel.clone();
+    let retimed_vtt_path = retimed_subtitles_path(output_path);
     
     // Spawn a new thread to run the TTS synchronization
     thread::spawn(move || {
@@ -375,18 +760,9 @@ async fn enhanced_tts_with_logging(
                                 ProgressUpdate::TTSGeneration { current, total } => {
                                     // Reduce the TTS generation range to leave room for vocal removal and mixing
                                     let progress = 10.0 + 40.0 * (*current as f32 / *total as f32);
-                                    (progress, format!("Генерация TTS"), Some(*current as i32), Some(*total as i32))
+                                    (progress, "Генерация TTS".to_string(), Some(*current as i32), Some(*total as i32))
                                 },
                                 ProgressUpdate::ProcessingFragment { index, total, step } => {
-                                    // Limit detailed step information
-                                    let simplified_step = if step.contains("Удаление вокала") {
-                                        "Удаление вокала"
-                                    } else if step.contains("Длительность") {
-                                        "Обработка аудио"
-                                    } else {
-                                        &step
-                                    };
-                                    
                                     // For vocal removal specifically, make it finish at 85%
                                     let progress = if step.contains("Удаление вокала") {
                                         // Remap to 50-85%
@@ -396,16 +772,20 @@ async fn enhanced_tts_with_logging(
                                         60.0 + 30.0 * (*index as f32 / *total as f32)
                                     };
                                     
-                                    (progress, format!("Обработка аудио"), Some(*index as i32), Some(*total as i32))
+                                    (progress, "Обработка аудио".to_string(), Some(*index as i32), Some(*total as i32))
                                 },
+                                // Goes into the reports or is too fine-grained for the progress bar
+                                ProgressUpdate::SegmentTiming { .. }
+                                | ProgressUpdate::CueDecision { .. }
+                                | ProgressUpdate::StreamingChunk { .. } => continue,
                                 ProgressUpdate::MergingFragments => (90.0, "Формирование результата".to_string(), None, None),
-                                ProgressUpdate::Normalizing { using_original } => (95.0, "Нормализация громкости".to_string(), None, None),
+                                ProgressUpdate::Normalizing { .. } => (95.0, "Нормализация громкости".to_string(), None, None),
                                 ProgressUpdate::Encoding => (98.0, "Сохранение результата".to_string(), None, None),
                                 ProgressUpdate::Finished => (100.0, "TTS готов".to_string(), None, None),
                             };
                             
                             // Убедимся, что прогресс в диапазоне 0-100
-                            let mut normalized_progress = progress.max(0.0).min(100.0);
+                            let mut normalized_progress = progress.clamp(0.0, 100.0);
                             
                             // Never decrease progress (except for new starts)
                             if normalized_progress < highest_progress && normalized_progress > 1.0 {
@@ -438,65 +818,149 @@ async fn enhanced_tts_with_logging(
                             
                             // Отправляем обновления только если нужно
                             if should_send {
-                                // Создаем объект прогресса
-                                let progress_json = json!({
-                                    "step": "TTS Generation",
-                                    "step_progress": normalized_progress,
-                                    "total_progress": normalized_progress,
-                                    "details": status,
-                                    "current_segment": current,
-                                    "total_segments": total,
-                                    "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64,
-                                    "status": status,  // явно добавим статус для UI
-                                    "progress": normalized_progress  // Явно добавляем поле progress для совместимости с интерфейсом прогресса
-                                });
+                                // Общий прогресс задачи считает PipelineProgressTracker
+                                let progress = StepProgress {
+                                    status,
+                                    progress: normalized_progress,
+                                    current_segment: current,
+                                    total_segments: total,
+                                };
                                 
                                 // Всегда логгируем прогресс для отладки
-                                info!("TTS progress: {:.1}%, status={}", normalized_progress, status);
+                                info!("TTS progress: {:.1}%, status={}", normalized_progress, progress.status);
                                 
                                 // Отправляем событие
-                                if let Err(e) = progress_window.emit("tts-progress", progress_json.clone()) {
+                                if let Err(e) = emitter::emit_for_job(&progress_window, "tts-progress", job_id.as_deref(), progress) {
                                     error!("Failed to emit TTS progress: {}", e);
                                 }
                             }
                         }
                     });
                     
-                    // Set up the configuration for our TTS library
-                    let vtt_path = Path::new(&translated_vtt_path_clone);
+                    // Set up the configuration for our TTS library. Speech-to-speech
+                    // dubs the original cues and writes the translated VTT itself.
+                    let vtt_path = if speech_to_speech.is_some() {
+                        Path::new(&original_vtt_path_clone)
+                    } else {
+                        Path::new(&translated_vtt_path_clone)
+                    };
                     let output_wav_path = Path::new(&output_path_clone);
                     let original_audio = Some(Path::new(&audio_path_clone));
                     
                     // Create TTS configuration with sensible defaults
                     let tts_config = TtsConfig {
                         model: "tts-1-hd".to_string(),
-                        voice: "ash".to_string(),
-                        speed: 1.0,
+                        voice,
+                        speed: speed_profile.tts_speed,
                     };
                     
                     // Create audio processing configuration with sensible defaults
                     let audio_config = AudioProcessingConfig {
                         window_size: 512,
                         hop_size: 256,
-                        target_peak_level: 0.8,
+                        loudness,
                         voice_to_instrumental_ratio: 0.6,
                         instrumental_boost: 1.5,
+                        max_tempo: speed_profile.max_tempo,
+                        min_tempo: speed_profile.min_tempo,
+                        stretch,
+                        overlap_policy,
+                        fit_strategy,
+                        lip_sync,
+                        sentence_merging,
+                        voice_activity,
+                        ..AudioProcessingConfig::default()
                     };
                     
+                    // Segments the speech-to-speech model can't handle go through translation and TTS
+                    let registry = provider::registry();
+                    let create = |voice: &str| {
+                        let params = provider::EngineParams {
+                            api_key: &api_key_clone,
+                            config: TtsConfig { voice: voice.to_string(), ..tts_config.clone() },
+                            target_language: speech_to_speech.as_deref(),
+                        };
+                        if speech_to_speech.is_some() {
+                            registry
+                                .create(&engine, &params)
+                                .and_then(|primary| Ok(vec![primary, registry.create("openai-tts", &params)?]))
+                                .map(|chain| Arc::new(provider::Fallback::new(chain)) as Arc<dyn SpeechProvider>)
+                        } else {
+                            registry.create(&engine, &params)
+                        }
+                    };
+                    // Speakers without a voice of their own speak with the job's voice
+                    let speech_provider = create(&tts_config.voice).and_then(|default| {
+                        if speaker_voices.is_empty() {
+                            return Ok(default);
+                        }
+                        let mut speakers = HashMap::new();
+                        for (speaker, voice) in &speaker_voices {
+                            speakers.insert(speaker.clone(), create(voice)?);
+                        }
+                        Ok(Arc::new(provider::SpeakerVoices::new(default, speakers)) as Arc<dyn SpeechProvider>)
+                    });
+                    // Speech-to-speech speaks the original audio, there is no text to respell
+                    let speech_provider = speech_provider.map(|speech_provider| {
+                        if speech_to_speech.is_some() || (lexicon.is_empty() && !normalizer.is_active()) {
+                            return speech_provider;
+                        }
+                        let pronounced = provider::Pronounced::new(speech_provider, Arc::new(lexicon), Arc::new(normalizer));
+                        Arc::new(pronounced) as Arc<dyn SpeechProvider>
+                    });
+                    let speech_provider = match speech_provider {
+                        Ok(speech_provider) => Some(speech_provider),
+                        Err(e) => {
+                            let _ = tx.send(Err(format!("Failed to create speech engine: {}", e))).await;
+                            return;
+                        }
+                    };
+                    let spoken_vtt_path = speech_to_speech.as_ref().map(|_| Path::new(&translated_vtt_path_clone));
+
+                    // Per-segment timings and cue decisions are collected for the reports
+                    let observers = Arc::new(ProgressObservers::new());
+                    observers.register(timing);
+                    observers.register(decisions);
+
                     // Create the sync configuration
                     let sync_config = SyncConfig {
                         api_key: &api_key_clone,
                         vtt_path,
+                        // The encoding chosen by the user is that of the translated subtitles
+                        subtitle_encoding: if speech_to_speech.is_some() { None } else { subtitle_encoding.as_deref() },
                         output_wav: output_wav_path,
                         original_audio_path: original_audio,
                         progress_sender: Some(progress_tx),
+                        observers: Some(observers),
                         tts_config,
                         audio_config,
+                        stream_to,
+                        speech_provider,
+                        spoken_vtt_path,
+                        narration,
+                        retimed_vtt_path: Some(&retimed_vtt_path),
+                        instrumental_path: instrumental.as_deref(),
                     };
                     
                     // Run the TTS synchronization
                     info!("Starting TTS synchronization with video duration: {:.2}s", video_duration);
-                    match process_sync(sync_config).await {
+                    // Dropping the synchronization stops the TTS requests in flight
+                    let sync_result = tokio::select! {
+                        result = process_sync(sync_config) => Some(result),
+                        _ = cancel_clone.cancelled() => None,
+                    };
+                    let Some(sync_result) = sync_result else {
+                        warn!("TTS process cancelled");
+                        let _ = tx.send(Err(cancellation::CANCELLED.to_string())).await;
+                        progress_task.abort();
+                        return;
+                    };
+                    match sync_result {
+                        Ok(()) if streaming => {
+                            // The audio was handed over in memory, there is no file to check
+                            info!("TTS process completed successfully, audio streamed to merge");
+                            let _ = tx.send(Ok(output_path_clone.clone())).await;
+                        },
                         Ok(()) => {
                             info!("TTS process completed successfully!");
                             info!("Generated TTS output file: {}", output_path_clone);
@@ -513,6 +977,15 @@ async fn enhanced_tts_with_logging(
                                         let _ = tx.send(Err(error_msg)).await;
                                         return;
                                     }
+
+                                    // A truncated mix is cheaper to spot here than in the merged video
+                                    match audio_probe::native_duration(std::path::Path::new(&output_path_clone)) {
+                                        Some(audio_duration) if audio_duration + 1.0 < video_duration => {
+                                            warn!("Generated audio is {:.2}s long, the video is {:.2}s", audio_duration, video_duration);
+                                        },
+                                        Some(audio_duration) => info!("Generated audio duration: {:.2}s", audio_duration),
+                                        None => warn!("Could not read the duration of the generated audio"),
+                                    }
                                     
                                     let _ = tx.send(Ok(output_path_clone.clone())).await;
                                 },
@@ -545,10 +1018,15 @@ async fn enhanced_tts_with_logging(
     
     // Wait for the result from the spawned thread
     // Add a timeout to prevent hanging indefinitely
-    match tokio::time::timeout(
-        std::time::Duration::from_secs(600), // 10 minute timeout
-        rx.recv()
-    ).await {
+    let received = tokio::select! {
+        received = tokio::time::timeout(
+            std::time::Duration::from_secs(600), // 10 minute timeout
+            rx.recv()
+        ) => received,
+        // Audio processing may not notice right away, the thread finishes on its own
+        _ = cancel.cancelled() => return Err(cancellation::CANCELLED.to_string()),
+    };
+    match received {
         Ok(Some(result)) => result,
         Ok(None) => {
             error!("TTS process channel closed unexpectedly");
@@ -563,27 +1041,9 @@ async fn enhanced_tts_with_logging(
 
 // Helper function to get video duration
 async fn get_video_duration(video_path: &str) -> Result<f64, String> {
-    use tokio::process::Command;
-    
-    // Using ffprobe to get video duration
-    let output = Command::new("ffprobe")
-        .args([
-            "-v", "error",
-            "-show_entries", "format=duration",
-            "-of", "default=noprint_wrappers=1:nokey=1",
-            video_path
-        ])
-        .output()
+    audio_probe::duration(std::path::Path::new(video_path))
         .await
-        .map_err(|e| format!("Failed to execute ffprobe: {}", e))?;
-    
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("ffprobe error: {}", stderr));
-    }
-    
-    let duration_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    duration_str.parse::<f64>().map_err(|e| format!("Failed to parse duration: {}", e))
+        .map_err(|e| e.to_string())
 }
 
 /// Helper function to copy a file to the output path
@@ -593,12 +1053,12 @@ async fn copy_to_output_path(source: &str, destination: &str) -> Result<(), Stri
     let dest_path = std::path::Path::new(destination);
     
     // Ensure parent directories exist
-    if let Some(parent) = dest_path.parent() {
-        if !parent.exists() {
-            tokio::fs::create_dir_all(parent)
-                .await
-                .map_err(|e| format!("Failed to create parent directories: {}", e))?;
-        }
+    if let Some(parent) = dest_path.parent()
+        && !parent.exists()
+    {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create parent directories: {}", e))?;
     }
     
     // Check if destination is a directory
@@ -625,6 +1085,7 @@ async fn copy_to_output_path(source: &str, destination: &str) -> Result<(), Stri
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn generate_speech(
     video_path: String,
     audio_path: String,
@@ -632,45 +1093,114 @@ pub async fn generate_speech(
     translated_vtt_path: String,
     output_path: String,
     api_key: String,
+    target_language: Option<String>,
+    encoding: Option<String>,
     window: tauri::Window,
 ) -> Result<TTSResult, String> {
+    let inputs = SpeechInputs {
+        video_path,
+        audio_path,
+        original_vtt_path,
+        translated_vtt_path,
+        subtitle_encoding: encoding,
+        output_path,
+        api_key,
+        target_language,
+        ..Default::default()
+    };
+    synthesize_speech(inputs, window).await
+}
+
+/// What `synthesize_speech` works from. The optional parts fall back to the
+/// settings or to a plain run.
+#[derive(Default)]
+struct SpeechInputs<'a> {
+    video_path: String,
+    audio_path: String,
+    original_vtt_path: String,
+    translated_vtt_path: String,
+    subtitle_encoding: Option<String>,
+    output_path: String,
+    api_key: String,
+    target_language: Option<String>,
+    /// Name of the target language, set when speech is translated to speech
+    speech_to_speech: Option<String>,
+    fit_strategy: Option<FitStrategy>,
+    voice: Option<String>,
+    narration: Vec<HumanNarration>,
+    /// The background of the original already separated for another language
+    instrumental: Option<PathBuf>,
+    /// Receives the result in memory instead of it being written to `output_path`
+    stream_to: Option<oneshot::Sender<RenderedAudio>>,
+    job_id: Option<&'a str>,
+    cancel: Option<&'a CancellationToken>,
+}
+
+/// Generate the synchronized speech track
+async fn synthesize_speech(inputs: SpeechInputs<'_>, window: tauri::Window) -> Result<TTSResult, String> {
+    let SpeechInputs {
+        video_path,
+        audio_path,
+        original_vtt_path,
+        translated_vtt_path,
+        subtitle_encoding,
+        output_path,
+        api_key,
+        target_language,
+        speech_to_speech,
+        fit_strategy,
+        voice,
+        narration,
+        instrumental,
+        stream_to,
+        job_id,
+        cancel,
+    } = inputs;
     info!("Starting TTS generation with synchronization");
+
+    // Speech-to-speech has its own switch, otherwise the engine from the settings speaks
+    let engine = if speech_to_speech.is_some() {
+        "openai-speech-to-speech".to_string()
+    } else {
+        load_tts_engine(window.app_handle())
+    };
+    let capabilities = provider::registry()
+        .get(&engine)
+        .ok_or_else(|| format!("Unknown speech engine: {}", engine))?;
+    info!("Speech engine: {}", capabilities.name);
     
     // Validate the API key first before proceeding
-    info!("Validating OpenAI API key before TTS generation");
-    if api_key.trim().is_empty() {
-        error!("OpenAI API key is empty");
-        return Err("OpenAI API key is required for TTS generation".to_string());
-    }
-    
-    // Additional validation by making a test request to the OpenAI API
-    match validate_openai_key(api_key.clone()).await {
-        Ok(true) => info!("OpenAI API key validated successfully"),
-        Ok(false) => {
-            error!("Invalid OpenAI API key: Authentication failed");
-            return Err("OpenAI API key validation failed. Please check your API key and ensure it has access to TTS services.".to_string());
-        },
-        Err(e) => {
-            error!("OpenAI API key validation error: {}", e);
-            return Err(format!("Failed to validate OpenAI API key: {}. Please check your internet connection and try again.", e));
+    if capabilities.requires_api_key {
+        info!("Validating OpenAI API key before TTS generation");
+        if api_key.trim().is_empty() {
+            error!("OpenAI API key is empty");
+            return Err("OpenAI API key is required for TTS generation".to_string());
+        }
+        
+        // Additional validation by making a test request to the OpenAI API
+        match validate_openai_key(api_key.clone()).await {
+            Ok(true) => info!("OpenAI API key validated successfully"),
+            Ok(false) => {
+                error!("Invalid OpenAI API key: Authentication failed");
+                return Err("OpenAI API key validation failed. Please check your API key and ensure it has access to TTS services.".to_string());
+            },
+            Err(e) => {
+                error!("OpenAI API key validation error: {}", e);
+                return Err(format!("Failed to validate OpenAI API key: {}. Please check your internet connection and try again.", e));
+            }
         }
     }
     
-    // Проверяем наличие SoundTouch библиотеки перед тем, как начать TTS процесс
-    info!("Checking SoundTouch installation before starting TTS process");
-    if let Err(e) = soundtouch::ensure_soundtouch_installed() {
-        error!("SoundTouch installation check failed: {}", e);
-        return Err(format!("SoundTouch library is not available: {}. Please ensure that SoundTouch is installed on your system.", e));
-    }
-    info!("SoundTouch is available, proceeding with TTS generation");
-    
-    // Validate input files
+    // Validate input files; with speech-to-speech the translated subtitles are an output
     for (path, desc) in [
         (&video_path, "video"),
         (&audio_path, "audio"),
         (&original_vtt_path, "original subtitles"),
         (&translated_vtt_path, "translated subtitles"),
     ] {
+        if speech_to_speech.is_some() && path == &translated_vtt_path {
+            continue;
+        }
         if !check_file_exists(path).await {
             error!("File not found: {} ({})", path, desc);
             return Err(format!("Required {} file not found: {}", desc, path));
@@ -681,33 +1211,109 @@ pub async fn generate_speech(
     let output_path_obj = std::path::Path::new(&output_path);
     
     // Make sure parent directories exist if output_path is a full file path
-    if let Some(parent) = output_path_obj.parent() {
-        if !parent.exists() {
-            tokio::fs::create_dir_all(parent)
-                .await
-                .map_err(|e| format!("Failed to create output directory: {}", e))?;
-        }
+    if let Some(parent) = output_path_obj.parent()
+        && !parent.exists()
+    {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create output directory: {}", e))?;
     }
     
     info!("TTS output will be saved to: {}", output_path);
     
+    // Per-language speed limits, user overrides take precedence over the built-in table
+    let speed_profile = language_speed::resolve(
+        target_language.as_deref(),
+        &load_speed_overrides(window.app_handle()),
+    );
+    let overlap_policy = load_overlap_policy(window.app_handle());
+    let fit_strategy = fit_strategy.unwrap_or_else(|| load_fit_strategy(window.app_handle()));
+    let stretch = load_time_stretch(window.app_handle());
+    let loudness = load_loudness_target(window.app_handle());
+    let lip_sync = load_lip_sync(window.app_handle());
+    let sentence_merging = load_sentence_merging(window.app_handle());
+    let voice_activity = load_voice_activity(window.app_handle());
+    let lexicon = Lexicon::for_language(&load_pronunciation_lexicon(window.app_handle()), target_language.as_deref());
+    let normalization = normalize::options_for(&load_text_normalization(window.app_handle()), target_language.as_deref());
+    let normalizer = Normalizer::new(target_language.as_deref(), normalization);
+
     // Create progress observer
     let observer = TauriProgressObserver::new(window.clone());
+    let timing = Arc::new(TimingCollector::new());
+    let decisions = Arc::new(DecisionCollector::new());
+    
+    // The voice counts as used for the recents of the voice picker
+    let voice = voice.unwrap_or_else(|| capabilities.default_voice.clone());
+    validate_engine_voice(&engine, &voice)?;
+    if let Err(e) = voices::record_use(window.app_handle(), &engine, &voice).await {
+        warn!("Failed to record voice use: {}", e);
+    }
+    // Voices of another engine are left from before the engine was switched
+    let mut speaker_voices = load_speaker_voices(window.app_handle());
+    speaker_voices.retain(|speaker, speaker_voice| match validate_engine_voice(&engine, speaker_voice) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Speaker {} speaks with the default voice: {}", speaker, e);
+            false
+        }
+    });
     
     // Use our enhanced TTS function with detailed logging
-    match enhanced_tts_with_logging(
-        &video_path,
-        &audio_path,
-        &original_vtt_path,
-        &translated_vtt_path,
-        &output_path,
-        &api_key,
+    let run = TtsRun {
+        video_path: &video_path,
+        audio_path: &audio_path,
+        original_vtt_path: &original_vtt_path,
+        translated_vtt_path: &translated_vtt_path,
+        subtitle_encoding,
+        output_path: &output_path,
+        api_key: &api_key,
+        speed_profile,
+        overlap_policy,
+        fit_strategy,
+        stretch,
+        loudness,
+        lip_sync,
+        sentence_merging,
+        voice_activity,
+        lexicon,
+        normalizer,
+        speech_to_speech,
+        engine,
+        voice,
+        speaker_voices,
+        narration,
+        instrumental,
+        stream_to,
         observer,
-    ).await {
+        timing: timing.clone(),
+        decisions: decisions.clone(),
+        cancel: cancel.cloned().unwrap_or_default(),
+        job_id: job_id.map(str::to_string),
+    };
+    match enhanced_tts_with_logging(run).await {
         Ok(_) => {
             info!("TTS generation completed successfully");
+            let mut timing_report = timing.build_report(job_id.map(str::to_string));
+            if let Some(confidence) = confidence::load(Path::new(&original_vtt_path)).await {
+                timing_report.flag_low_confidence(&confidence);
+            }
+            info!(
+                "Timing report: {} segments, {} sped up, {} heavily stretched (max {:.2}x)",
+                timing_report.summary.segments,
+                timing_report.summary.sped_up,
+                timing_report.summary.heavily_stretched,
+                timing_report.summary.max_speed_factor
+            );
+            if let Err(e) = window.emit("segment-timing-report", &timing_report) {
+                warn!("Failed to emit segment-timing-report: {}", e);
+            }
+            let optimizer_report = decisions.build_report();
+            info!("Optimizer report: {} merges, {} retimed cues", optimizer_report.merged, optimizer_report.retimed);
             Ok(TTSResult {
+                retimed_vtt_path: existing_retimed_subtitles(&output_path),
                 audio_path: output_path,
+                timing_report,
+                optimizer_report: Some(optimizer_report),
             })
         },
         Err(e) => {
@@ -717,115 +1323,1821 @@ pub async fn generate_speech(
     }
 }
 
-/// Helper function to check if a file exists and is valid
-async fn check_file_exists(path: impl AsRef<std::path::Path>) -> bool {
-    tokio::fs::metadata(path).await.is_ok()
+/// Get how often progress events may reach the webview: the coalescing window in ms, 0 when off
+#[tauri::command]
+pub async fn get_event_rate_limit() -> Result<u64, String> {
+    Ok(emitter::window_ms())
 }
 
-/// Check if a file exists and is accessible
+/// Set the coalescing window of progress events in ms, 0 sends every update
 #[tauri::command]
-pub async fn check_file_exists_command(path: String) -> Result<bool, String> {
-    Ok(check_file_exists(path).await)
+pub async fn set_event_rate_limit(app_handle: tauri::AppHandle, window_ms: u64) -> Result<(), String> {
+    emitter::set_window_ms(&app_handle, window_ms).await.map_err(|e| e.to_string())
 }
 
-/// Process video through all steps: download, transcribe, translate, and TTS with synchronization
+/// Get the advanced configuration in effect, with the error of the file on disk if it's invalid
 #[tauri::command]
-pub async fn process_video(
-    url: String,
-    output_path: String,
-    target_language: String,
-    target_language_name: String,
-    source_language_code: String,
-    source_language_name: String,
-    api_key: String,
-    window: tauri::Window,
-) -> Result<ProcessVideoResult, String> {
-    info!("=== Starting Video Processing Pipeline ===");
-    info!("Parameters:");
-    info!("  URL: {}", url);
-    info!("  Output Path: {}", output_path);
-    info!("  Source Language: {} ({})", source_language_name, source_language_code);
-    info!(
-        "  Target Language: {} ({})",
-        target_language_name, target_language
-    );
+pub async fn get_advanced_config() -> Result<AdvancedConfigStatus, String> {
+    advanced_config::status().ok_or_else(|| "Advanced configuration is unavailable".to_string())
+}
 
-    // Step 1: Download video
-    info!("Step 1: Downloading video");
-    let download_result = match download_video(window.clone(), url.clone(), output_path.clone()).await {
-        Ok(json_result) => {
-            let video_path = json_result["video_path"].as_str()
-                .ok_or_else(|| "Missing video_path in download result".to_string())?
-                .to_string();
-            let audio_path = json_result["audio_path"].as_str()
-                .ok_or_else(|| "Missing audio_path in download result".to_string())?
-                .to_string();
-            info!("Download completed successfully");
-            info!("  Video path: {}", video_path);
-            info!("  Audio path: {}", audio_path);
-            (video_path, audio_path)
-        }
-        Err(e) => {
-            error!("Download failed: {}", e);
-            return Err(format!("Download failed: {}", e));
-        }
-    };
+/// Voice of the dubbed speech
+const DUB_VOICE: &str = "ash";
 
-    // Step 2: Transcribe audio
-    info!("Step 2: Transcribing audio");
-    let transcription_result = match transcribe_audio(
-        download_result.1.clone(), // audio_path
-        output_path.clone(),
-        api_key.clone(),
-        None, // language - auto detect
-        window.clone(),
-    )
-    .await {
-        Ok(result) => {
-            info!("Transcription completed successfully");
-            info!("  VTT path: {}", result.vtt_path);
-            result
-        }
-        Err(e) => {
-            error!("Transcription failed: {}", e);
-            return Err(format!("Transcription failed: {}", e));
-        }
-    };
+const TTS_ENGINE_KEY: &str = "tts-engine";
 
-    // Step 3: Translate VTT
-    info!("Step 3: Translating subtitles");
-    let translation_result = match translate_vtt(
-        transcription_result.vtt_path.clone(),
-        output_path.clone(),
-        source_language_code.clone(),  // Use actual source language from parameters
-        target_language_name.clone(), // target language name
-        target_language.clone(),      // target language code
-        api_key.clone(),
-        window.clone(),
-    )
-    .await {
-        Ok(result) => {
-            info!("Translation completed successfully");
-            info!("  Translated VTT path: {}", result.translated_vtt_path);
-            result
-        }
-        Err(e) => {
-            error!("Translation failed: {}", e);
-            return Err(format!("Translation failed: {}", e));
-        }
-    };
+/// Engine that speaks translated text, speech-to-speech is switched separately
+fn load_tts_engine(app_handle: &tauri::AppHandle) -> String {
+    settings::get(app_handle, TTS_ENGINE_KEY).unwrap_or_else(|| "openai-tts".to_string())
+}
 
-    // Небольшая пауза после завершения перевода и проверка файлов
-    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+/// Get the engine that speaks the translated subtitles
+#[tauri::command]
+pub async fn get_tts_engine(app_handle: tauri::AppHandle) -> Result<String, String> {
+    Ok(load_tts_engine(&app_handle))
+}
 
-    // Проверяем наличие всех необходимых файлов перед запуском TTS
-    for path_str in [
-        &download_result.0, // video_path
-        &download_result.1, // audio_path
-        &transcription_result.vtt_path,
-        &translation_result.translated_vtt_path,
-    ] {
-        let path = std::path::Path::new(path_str);
+/// Set the engine that speaks the translated subtitles
+#[tauri::command]
+pub async fn set_tts_engine(app_handle: tauri::AppHandle, engine: String) -> Result<(), String> {
+    let capabilities = provider::registry()
+        .get(&engine)
+        .ok_or_else(|| format!("Unknown speech engine: {}", engine))?;
+    if capabilities.needs_source_audio {
+        return Err(format!("{} is enabled with the speech-to-speech setting", capabilities.name));
+    }
+    settings::set(&app_handle, TTS_ENGINE_KEY, &engine).await.map_err(|e| e.to_string())
+}
+
+/// Piper voices with whether their model is downloaded
+#[tauri::command]
+pub async fn list_piper_voices(app_handle: tauri::AppHandle) -> Result<Vec<piper::PiperVoice>, String> {
+    let dir = piper::models_dir(&app_handle).map_err(|e| e.to_string())?;
+    Ok(piper::list_voices(&dir))
+}
+
+/// Download the model of a Piper voice ahead of the first dub
+#[tauri::command]
+pub async fn download_piper_voice(app_handle: tauri::AppHandle, voice: String) -> Result<(), String> {
+    let dir = piper::models_dir(&app_handle).map_err(|e| e.to_string())?;
+    piper::ensure_model(&dir, &voice).await.map_err(|e| e.to_string())?;
+    // Voices outside the catalog become selectable once downloaded
+    piper::register(&app_handle);
+    Ok(())
+}
+
+/// Clone the voice of the original speaker for Fish Speech from a video or
+/// its audio and the original subtitles
+#[tauri::command]
+pub async fn clone_voice_from_video(
+    app_handle: tauri::AppHandle,
+    media_path: String,
+    vtt_path: String,
+    name: String,
+    speaker: Option<String>,
+) -> Result<ClonedVoice, String> {
+    fish_speech::clone_voice(&app_handle, Path::new(&media_path), Path::new(&vtt_path), &name, speaker.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Check that an engine exists and offers a voice
+fn validate_engine_voice(engine: &str, voice: &str) -> Result<(), String> {
+    let capabilities = provider::registry()
+        .get(engine)
+        .ok_or_else(|| format!("Unknown speech engine: {}", engine))?;
+    if !capabilities.voices.iter().any(|known| known == voice) {
+        return Err(format!("{} has no voice named {}", capabilities.name, voice));
+    }
+    Ok(())
+}
+
+/// Speech engines with their voices, favorites and recently used voices first
+#[tauri::command]
+pub async fn get_engine_capabilities(app_handle: tauri::AppHandle) -> Result<Vec<voices::EngineVoiceChoice>, String> {
+    Ok(voices::with_preferences(provider::engine_capabilities(), &voices::load(&app_handle)))
+}
+
+/// Registered speech engines with their voices, languages and streaming support
+#[tauri::command]
+pub async fn get_available_engines() -> Result<Vec<provider::EngineCapabilities>, String> {
+    Ok(provider::registry().engines())
+}
+
+/// Favorite and recently used voices of every engine
+#[tauri::command]
+pub async fn get_voice_favorites(app_handle: tauri::AppHandle) -> Result<voices::VoicePreferences, String> {
+    Ok(voices::load(&app_handle))
+}
+
+/// Mark or unmark a voice of an engine as favorite
+#[tauri::command]
+pub async fn set_voice_favorite(
+    app_handle: tauri::AppHandle,
+    engine: String,
+    voice: String,
+    favorite: bool,
+) -> Result<voices::EngineVoices, String> {
+    // Unmarking is allowed for voices an engine dropped meanwhile
+    if favorite {
+        validate_engine_voice(&engine, &voice)?;
+    }
+    voices::set_favorite(&app_handle, &engine, &voice, favorite).await.map_err(|e| e.to_string())
+}
+
+/// Remember that the user previewed or picked a voice
+#[tauri::command]
+pub async fn record_voice_use(app_handle: tauri::AppHandle, engine: String, voice: String) -> Result<(), String> {
+    validate_engine_voice(&engine, &voice)?;
+    voices::record_use(&app_handle, &engine, &voice).await.map_err(|e| e.to_string())
+}
+
+/// Settings profiles and which one is active
+#[tauri::command]
+pub async fn list_profiles(app_handle: tauri::AppHandle) -> Result<profiles::Profiles, String> {
+    Ok(profiles::load(&app_handle))
+}
+
+/// Create a profile, or update it when it has an id
+#[tauri::command]
+pub async fn save_profile(
+    app_handle: tauri::AppHandle,
+    profile: profiles::SettingsProfile,
+) -> Result<profiles::SettingsProfile, String> {
+    if let Some(engine) = &profile.tts_engine {
+        engine
+            .as_str()
+            .and_then(|engine| provider::registry().get(engine))
+            .ok_or_else(|| format!("Unknown speech engine: {}", engine))?;
+    }
+    profiles::save(&app_handle, profile).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_profile(app_handle: tauri::AppHandle, id: String) -> Result<(), String> {
+    profiles::delete(&app_handle, &id).await.map_err(|e| e.to_string())
+}
+
+/// Switch to another profile: its API key, default languages, voice and output folder replace the current ones
+#[tauri::command]
+pub async fn set_active_profile(app_handle: tauri::AppHandle, id: String) -> Result<profiles::SettingsProfile, String> {
+    profiles::set_active(&app_handle, &id).await.map_err(|e| e.to_string())
+}
+
+const SPEED_OVERRIDES_KEY: &str = "language-speed-overrides";
+
+/// Load per-language speed overrides from the settings store
+fn load_speed_overrides(app_handle: &tauri::AppHandle) -> HashMap<String, SpeedProfile> {
+    settings::get(app_handle, SPEED_OVERRIDES_KEY).unwrap_or_default()
+}
+
+/// Get the effective speed profile of every language with a built-in or custom profile
+#[tauri::command]
+pub async fn get_language_speed_profiles(app_handle: tauri::AppHandle) -> Result<HashMap<String, SpeedProfile>, String> {
+    let mut profiles = language_speed::builtin_profiles();
+    profiles.extend(load_speed_overrides(&app_handle));
+    Ok(profiles)
+}
+
+/// Replace the user's per-language speed overrides
+#[tauri::command]
+pub async fn set_language_speed_overrides(
+    app_handle: tauri::AppHandle,
+    overrides: HashMap<String, SpeedProfile>,
+) -> Result<(), String> {
+    settings::set(&app_handle, SPEED_OVERRIDES_KEY, &overrides).await.map_err(|e| e.to_string())
+}
+
+const SPEECH_TO_SPEECH_KEY: &str = "speech-to-speech";
+/// Longest video dubbed with speech-to-speech, longer ones use the classic pipeline
+const SPEECH_TO_SPEECH_MAX_DURATION: f64 = 600.0;
+
+/// Load whether the experimental speech-to-speech mode is enabled
+fn load_speech_to_speech(app_handle: &tauri::AppHandle) -> bool {
+    settings::get(app_handle, SPEECH_TO_SPEECH_KEY).unwrap_or(false)
+}
+
+/// Get whether short clips are dubbed with speech-to-speech instead of translation and TTS
+#[tauri::command]
+pub async fn get_speech_to_speech(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    Ok(load_speech_to_speech(&app_handle))
+}
+
+/// Enable or disable the experimental speech-to-speech mode
+#[tauri::command]
+pub async fn set_speech_to_speech(app_handle: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    settings::set(&app_handle, SPEECH_TO_SPEECH_KEY, &enabled).await.map_err(|e| e.to_string())
+}
+
+const LIP_SYNC_KEY: &str = "lip-sync-targeting";
+
+/// Whether speech is fitted to the words heard in the original instead of the cue windows (off by default)
+fn load_lip_sync(app_handle: &tauri::AppHandle) -> bool {
+    settings::get(app_handle, LIP_SYNC_KEY).unwrap_or(false)
+}
+
+/// Get whether speech is fitted to the original speech found in the vocal stem
+#[tauri::command]
+pub async fn get_lip_sync(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    Ok(load_lip_sync(&app_handle))
+}
+
+/// Fit speech to the original speech found in the vocal stem rather than to the subtitle timings
+#[tauri::command]
+pub async fn set_lip_sync(app_handle: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    settings::set(&app_handle, LIP_SYNC_KEY, &enabled).await.map_err(|e| e.to_string())
+}
+
+const SENTENCE_MERGING_KEY: &str = "sentence-merging";
+
+fn load_sentence_merging(app_handle: &tauri::AppHandle) -> SentenceMerging {
+    settings::get(app_handle, SENTENCE_MERGING_KEY).unwrap_or_default()
+}
+
+/// Get whether cues of one sentence are spoken as one
+#[tauri::command]
+pub async fn get_sentence_merging(app_handle: tauri::AppHandle) -> Result<SentenceMerging, String> {
+    Ok(load_sentence_merging(&app_handle))
+}
+
+/// Speak consecutive cues of one sentence as one, the subtitles keep their cues
+#[tauri::command]
+pub async fn set_sentence_merging(app_handle: tauri::AppHandle, merging: SentenceMerging) -> Result<(), String> {
+    if merging.max_pause < 0.0 || merging.max_duration <= 0.0 {
+        return Err("The pause must not be negative and the duration must be positive".to_string());
+    }
+    settings::set(&app_handle, SENTENCE_MERGING_KEY, &merging).await.map_err(|e| e.to_string())
+}
+
+const VOICE_ACTIVITY_KEY: &str = "voice-activity";
+
+fn load_voice_activity(app_handle: &tauri::AppHandle) -> VoiceActivity {
+    settings::get(app_handle, VOICE_ACTIVITY_KEY).unwrap_or_default()
+}
+
+/// Get whether passages without speech are skipped
+#[tauri::command]
+pub async fn get_voice_activity(app_handle: tauri::AppHandle) -> Result<VoiceActivity, String> {
+    Ok(load_voice_activity(&app_handle))
+}
+
+/// Skip passages without speech: they aren't transcribed, and no dub is put over them
+#[tauri::command]
+pub async fn set_voice_activity(app_handle: tauri::AppHandle, voice_activity: VoiceActivity) -> Result<(), String> {
+    if voice_activity.min_gap < 1.0 {
+        return Err("Passages without speech must be at least 1 second long, shorter ones are pauses".to_string());
+    }
+    settings::set(&app_handle, VOICE_ACTIVITY_KEY, &voice_activity).await.map_err(|e| e.to_string())
+}
+
+const STREAMING_MERGE_KEY: &str = "streaming-merge";
+
+/// Whether the TTS mix is piped into ffmpeg instead of written to disk (off by default)
+fn load_streaming_merge(app_handle: &tauri::AppHandle) -> bool {
+    settings::get(app_handle, STREAMING_MERGE_KEY).unwrap_or(false)
+}
+
+/// Get whether the low-disk streaming merge is enabled
+#[tauri::command]
+pub async fn get_streaming_merge(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    Ok(load_streaming_merge(&app_handle))
+}
+
+/// Enable or disable the low-disk streaming merge
+#[tauri::command]
+pub async fn set_streaming_merge(app_handle: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    settings::set(&app_handle, STREAMING_MERGE_KEY, &enabled).await.map_err(|e| e.to_string())
+}
+
+const REVIEW_TRANSLATION_KEY: &str = "review-translation";
+
+/// Whether the pipeline stops after translating so the user can edit the
+/// subtitles before they are spoken (off by default)
+fn load_review_translation(app_handle: &tauri::AppHandle) -> bool {
+    settings::get(app_handle, REVIEW_TRANSLATION_KEY).unwrap_or(false)
+}
+
+/// Get whether translations are reviewed before speech generation
+#[tauri::command]
+pub async fn get_review_translation(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    Ok(load_review_translation(&app_handle))
+}
+
+/// Enable or disable the translation review before speech generation
+#[tauri::command]
+pub async fn set_review_translation(app_handle: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    settings::set(&app_handle, REVIEW_TRANSLATION_KEY, &enabled).await.map_err(|e| e.to_string())
+}
+
+const KEEP_STEMS_KEY: &str = "keep-stems";
+
+/// Whether the separated vocals and instrumental stay in `videonova_stems`
+/// after a finished run (off by default, they are kept for retries either way)
+fn load_keep_stems(app_handle: &tauri::AppHandle) -> bool {
+    settings::get(app_handle, KEEP_STEMS_KEY).unwrap_or(false)
+}
+
+/// Get whether separated stems are kept after processing
+#[tauri::command]
+pub async fn get_keep_stems(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    Ok(load_keep_stems(&app_handle))
+}
+
+/// Keep or remove separated stems after processing
+#[tauri::command]
+pub async fn set_keep_stems(app_handle: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    settings::set(&app_handle, KEEP_STEMS_KEY, &enabled).await.map_err(|e| e.to_string())
+}
+
+const DEMUCS_KEY: &str = "demucs";
+
+/// Load the Demucs settings, applied at startup
+pub(crate) fn load_demucs_config(app_handle: &tauri::AppHandle) -> demucs::DemucsConfig {
+    settings::get(app_handle, DEMUCS_KEY).unwrap_or_default()
+}
+
+/// Probe which devices Demucs can run on (CUDA, Apple MPS, CPU)
+#[tauri::command]
+pub async fn detect_compute_backends() -> Result<Vec<demucs::ComputeBackend>, String> {
+    Ok(demucs::detect_backends().await)
+}
+
+/// Get the voice separation settings in effect
+#[tauri::command]
+pub async fn get_demucs_config() -> Result<demucs::DemucsConfig, String> {
+    Ok(demucs::current())
+}
+
+/// Pick the separation model and the device Demucs runs on
+#[tauri::command]
+pub async fn set_demucs_config(app_handle: tauri::AppHandle, config: demucs::DemucsConfig) -> Result<(), String> {
+    settings::set(&app_handle, DEMUCS_KEY, &config).await.map_err(|e| e.to_string())?;
+    demucs::apply(config);
+    Ok(())
+}
+
+/// Get the size cap the output is split under
+#[tauri::command]
+pub async fn get_output_split(app_handle: tauri::AppHandle) -> Result<split::SplitSettings, String> {
+    Ok(split::load(&app_handle))
+}
+
+/// Set the size cap the output is split under
+#[tauri::command]
+pub async fn set_output_split(app_handle: tauri::AppHandle, split: split::SplitSettings) -> Result<(), String> {
+    split::save(&app_handle, &split).await.map_err(|e| e.to_string())
+}
+
+const OVERLAP_POLICY_KEY: &str = "overlap-policy";
+
+/// Load the policy for overlapping cues from the settings store
+fn load_overlap_policy(app_handle: &tauri::AppHandle) -> OverlapPolicy {
+    settings::get(app_handle, OVERLAP_POLICY_KEY).unwrap_or_default()
+}
+
+/// Get how overlapping cues of different speakers are placed on the timeline
+#[tauri::command]
+pub async fn get_overlap_policy(app_handle: tauri::AppHandle) -> Result<OverlapPolicy, String> {
+    Ok(load_overlap_policy(&app_handle))
+}
+
+/// Set how overlapping cues of different speakers are placed on the timeline
+#[tauri::command]
+pub async fn set_overlap_policy(app_handle: tauri::AppHandle, policy: OverlapPolicy) -> Result<(), String> {
+    settings::set(&app_handle, OVERLAP_POLICY_KEY, &policy).await.map_err(|e| e.to_string())
+}
+
+const FIT_STRATEGY_KEY: &str = "fit-strategy";
+
+/// Load the default strategy for fitting speech to cues from the settings store
+fn load_fit_strategy(app_handle: &tauri::AppHandle) -> FitStrategy {
+    settings::get(app_handle, FIT_STRATEGY_KEY).unwrap_or_default()
+}
+
+fn validate_fit_strategy(strategy: FitStrategy) -> Result<(), String> {
+    match strategy {
+        FitStrategy::Retime { max_drift } if !(0.0..=10.0).contains(&max_drift) => {
+            Err(format!("max_drift must be between 0 and 10 seconds, got {}", max_drift))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Get whether speech is sped up to fit the cues or the cues are retimed to fit the speech
+#[tauri::command]
+pub async fn get_fit_strategy(app_handle: tauri::AppHandle) -> Result<FitStrategy, String> {
+    Ok(load_fit_strategy(&app_handle))
+}
+
+/// Set the default strategy for fitting speech to cues, jobs can override it
+#[tauri::command]
+pub async fn set_fit_strategy(app_handle: tauri::AppHandle, strategy: FitStrategy) -> Result<(), String> {
+    validate_fit_strategy(strategy)?;
+    settings::set(&app_handle, FIT_STRATEGY_KEY, &strategy).await.map_err(|e| e.to_string())
+}
+
+const TRANSCRIPTION_CONDITIONING_KEY: &str = "transcription-conditioning";
+
+/// Load how audio is prepared for Whisper from the settings store
+fn load_transcription_conditioning(app_handle: &tauri::AppHandle) -> TranscriptionConditioning {
+    settings::get(app_handle, TRANSCRIPTION_CONDITIONING_KEY).unwrap_or_default()
+}
+
+/// Get how audio is prepared for transcription
+#[tauri::command]
+pub async fn get_transcription_conditioning(app_handle: tauri::AppHandle) -> Result<TranscriptionConditioning, String> {
+    Ok(load_transcription_conditioning(&app_handle))
+}
+
+/// Set how audio is prepared for transcription
+#[tauri::command]
+pub async fn set_transcription_conditioning(
+    app_handle: tauri::AppHandle,
+    conditioning: TranscriptionConditioning,
+) -> Result<(), String> {
+    if conditioning.highpass_hz > 500 {
+        return Err("The high-pass cutoff must be at most 500 Hz, higher cuts into speech".to_string());
+    }
+    settings::set(&app_handle, TRANSCRIPTION_CONDITIONING_KEY, &conditioning).await.map_err(|e| e.to_string())
+}
+
+const TRANSCRIPT_CLEANUP_KEY: &str = "transcript-cleanup";
+
+/// Load what is cleaned up in Whisper's output from the settings store
+fn load_transcript_cleanup(app_handle: &tauri::AppHandle) -> TranscriptCleanup {
+    settings::get(app_handle, TRANSCRIPT_CLEANUP_KEY).unwrap_or_default()
+}
+
+/// Get what is cleaned up in a transcript before translation
+#[tauri::command]
+pub async fn get_transcript_cleanup(app_handle: tauri::AppHandle) -> Result<TranscriptCleanup, String> {
+    Ok(load_transcript_cleanup(&app_handle))
+}
+
+/// Set what is cleaned up in a transcript before translation
+#[tauri::command]
+pub async fn set_transcript_cleanup(app_handle: tauri::AppHandle, cleanup: TranscriptCleanup) -> Result<(), String> {
+    if cleanup.restore_punctuation && cleanup.punctuation_model.trim().is_empty() {
+        return Err("Punctuation restoration needs a model".to_string());
+    }
+    settings::set(&app_handle, TRANSCRIPT_CLEANUP_KEY, &cleanup).await.map_err(|e| e.to_string())
+}
+
+/// Use the user's own narration for a cue of a video. Either `path` (an audio
+/// file to import) or `recording` (bytes recorded in the app) must be given.
+/// The narration replaces the TTS for that cue on the next dub of the video.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn import_narration(
+    app_handle: tauri::AppHandle,
+    video_id: String,
+    target_language: String,
+    cue_start: f32,
+    cue_end: f32,
+    text: String,
+    path: Option<String>,
+    recording: Option<Vec<u8>>,
+) -> Result<NarrationClip, String> {
+    if video_id.is_empty() || cue_end <= cue_start {
+        return Err("A video and a cue are required to import narration".to_string());
+    }
+    let result = match (path, recording) {
+        (Some(path), _) => {
+            narration::import(&app_handle, &video_id, &target_language, cue_start, cue_end, &text, Path::new(&path)).await
+        }
+        (None, Some(recording)) if !recording.is_empty() => {
+            narration::import_bytes(&app_handle, &video_id, &target_language, cue_start, cue_end, &text, &recording).await
+        }
+        _ => return Err("No narration file or recording given".to_string()),
+    };
+    result.map_err(|e| e.to_string())
+}
+
+/// Narration recorded for a video in a target language
+#[tauri::command]
+pub async fn list_narrations(
+    app_handle: tauri::AppHandle,
+    video_id: String,
+    target_language: String,
+) -> Result<Vec<NarrationClip>, String> {
+    narration::list(&app_handle, &video_id, &target_language).await.map_err(|e| e.to_string())
+}
+
+/// Drop the narration of a cue so it is synthesized again
+#[tauri::command]
+pub async fn remove_narration(
+    app_handle: tauri::AppHandle,
+    video_id: String,
+    target_language: String,
+    cue_start: f32,
+) -> Result<(), String> {
+    narration::remove(&app_handle, &video_id, &target_language, cue_start).await.map_err(|e| e.to_string())
+}
+
+const TIME_STRETCH_KEY: &str = "time-stretch";
+
+/// Load the time-stretching settings from the settings store
+fn load_time_stretch(app_handle: &tauri::AppHandle) -> StretchSettings {
+    settings::get(app_handle, TIME_STRETCH_KEY).unwrap_or_default()
+}
+
+/// Get how TTS fragments are time-stretched to fit their cues
+#[tauri::command]
+pub async fn get_time_stretch(app_handle: tauri::AppHandle) -> Result<StretchSettings, String> {
+    Ok(load_time_stretch(&app_handle))
+}
+
+/// Set how TTS fragments are time-stretched to fit their cues
+#[tauri::command]
+pub async fn set_time_stretch(app_handle: tauri::AppHandle, stretch: StretchSettings) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&stretch.rate_share) {
+        return Err("rate_share must be between 0 and 1".to_string());
+    }
+    if stretch.pitch_semitones.abs() > 12.0 {
+        return Err("pitch_semitones must be within one octave".to_string());
+    }
+    if !(8..=128).contains(&stretch.quality.aa_filter_length) {
+        return Err("aa_filter_length must be between 8 and 128".to_string());
+    }
+    settings::set(&app_handle, TIME_STRETCH_KEY, &stretch).await.map_err(|e| e.to_string())
+}
+
+const LOUDNESS_TARGET_KEY: &str = "loudness-target";
+
+/// Load the loudness target of the dubbed audio from the settings store
+fn load_loudness_target(app_handle: &tauri::AppHandle) -> LoudnessTarget {
+    settings::get(app_handle, LOUDNESS_TARGET_KEY).unwrap_or_default()
+}
+
+/// Get the EBU R128 loudness the dubbed audio is normalized to
+#[tauri::command]
+pub async fn get_loudness_target(app_handle: tauri::AppHandle) -> Result<LoudnessTarget, String> {
+    Ok(load_loudness_target(&app_handle))
+}
+
+/// Set the EBU R128 loudness the dubbed audio is normalized to
+#[tauri::command]
+pub async fn set_loudness_target(app_handle: tauri::AppHandle, loudness: LoudnessTarget) -> Result<(), String> {
+    if !(-70.0..=-5.0).contains(&loudness.integrated_lufs) {
+        return Err("integrated_lufs must be between -70 and -5".to_string());
+    }
+    if !(-9.0..=0.0).contains(&loudness.true_peak_dbtp) {
+        return Err("true_peak_dbtp must be between -9 and 0".to_string());
+    }
+    if !(0.0..=30.0).contains(&loudness.loudness_range_lu) {
+        return Err("loudness_range_lu must be between 0 and 30".to_string());
+    }
+    settings::set(&app_handle, LOUDNESS_TARGET_KEY, &loudness).await.map_err(|e| e.to_string())
+}
+
+/// Load the default styling of merged outputs from the settings store
+fn load_merge_style(app_handle: &tauri::AppHandle) -> MergeStyle {
+    settings::get(app_handle, merge::MERGE_STYLE_KEY).unwrap_or_default()
+}
+
+/// Get the default styling of merged outputs
+#[tauri::command]
+pub async fn get_merge_style(app_handle: tauri::AppHandle) -> Result<MergeStyle, String> {
+    Ok(load_merge_style(&app_handle))
+}
+
+/// Set the default styling of merged outputs, jobs can still override it
+#[tauri::command]
+pub async fn set_merge_style(app_handle: tauri::AppHandle, style: MergeStyle) -> Result<(), String> {
+    style.tracks.validate().map_err(|e| e.to_string())?;
+    style.layout.validate(&style.tracks).map_err(|e| e.to_string())?;
+    if let Some(burn_in) = &style.burn_in {
+        burn_in.validate().map_err(|e| e.to_string())?;
+    }
+    settings::set(&app_handle, merge::MERGE_STYLE_KEY, &style).await.map_err(|e| e.to_string())
+}
+
+const SUBTITLE_EXPORT_KEY: &str = "subtitle-export";
+
+/// Format the translated subtitles are saved in next to the output, None keeps them in the temp dir only
+fn load_subtitle_export(app_handle: &tauri::AppHandle) -> Option<SubtitleFormat> {
+    settings::get(app_handle, SUBTITLE_EXPORT_KEY).unwrap_or_default()
+}
+
+/// Get the format translated subtitles are exported in
+#[tauri::command]
+pub async fn get_subtitle_export(app_handle: tauri::AppHandle) -> Result<Option<SubtitleFormat>, String> {
+    Ok(load_subtitle_export(&app_handle))
+}
+
+/// Export translated subtitles next to the output video as VTT or SRT, or stop exporting them with None
+#[tauri::command]
+pub async fn set_subtitle_export(app_handle: tauri::AppHandle, format: Option<SubtitleFormat>) -> Result<(), String> {
+    settings::set(&app_handle, SUBTITLE_EXPORT_KEY, &format).await.map_err(|e| e.to_string())
+}
+
+const KARAOKE_EXPORT_KEY: &str = "karaoke-export";
+
+/// Format word-highlighted subtitles are saved in next to the output, None saves none
+fn load_karaoke_export(app_handle: &tauri::AppHandle) -> Option<KaraokeFormat> {
+    settings::get(app_handle, KARAOKE_EXPORT_KEY).unwrap_or_default()
+}
+
+/// Get the format word-highlighted subtitles of the dub are exported in
+#[tauri::command]
+pub async fn get_karaoke_export(app_handle: tauri::AppHandle) -> Result<Option<KaraokeFormat>, String> {
+    Ok(load_karaoke_export(&app_handle))
+}
+
+/// Export the translated subtitles with every word highlighted as it is spoken,
+/// as ASS karaoke or word-timed VTT, or stop exporting them with None
+#[tauri::command]
+pub async fn set_karaoke_export(app_handle: tauri::AppHandle, format: Option<KaraokeFormat>) -> Result<(), String> {
+    settings::set(&app_handle, KARAOKE_EXPORT_KEY, &format).await.map_err(|e| e.to_string())
+}
+
+const SUBTITLE_READABILITY_KEY: &str = "subtitle-readability";
+
+fn load_subtitle_readability(app_handle: &tauri::AppHandle) -> ReadabilitySettings {
+    settings::get(app_handle, SUBTITLE_READABILITY_KEY).unwrap_or_default()
+}
+
+/// Get the line length and reading speed limits translated subtitles are optimized for
+#[tauri::command]
+pub async fn get_subtitle_readability(app_handle: tauri::AppHandle) -> Result<ReadabilitySettings, String> {
+    Ok(load_subtitle_readability(&app_handle))
+}
+
+/// Split, merge and wrap translated subtitles to fit the limits, unset limits follow the target language
+#[tauri::command]
+pub async fn set_subtitle_readability(app_handle: tauri::AppHandle, readability: ReadabilitySettings) -> Result<(), String> {
+    if readability.max_lines == 0 || readability.max_chars_per_line == Some(0) {
+        return Err("Subtitles need at least one line of at least one character".to_string());
+    }
+    settings::set(&app_handle, SUBTITLE_READABILITY_KEY, &readability).await.map_err(|e| e.to_string())
+}
+
+/// The readable version of a subtitle file, or the file itself when it can't be optimized
+async fn readable_subtitles(readability: &ReadabilitySettings, vtt_path: &Path, language: &str) -> PathBuf {
+    match subtitle_optimizer::optimize_file(vtt_path, Some(language), readability).await {
+        Ok((readable, _)) => readable,
+        Err(e) => {
+            warn!("Keeping {} as it is, optimizing it failed: {}", vtt_path.display(), e);
+            vtt_path.to_path_buf()
+        }
+    }
+}
+
+const SPEAKER_LABELS_KEY: &str = "speaker-labels";
+
+fn load_speaker_labels(app_handle: &tauri::AppHandle) -> SpeakerLabels {
+    settings::get(app_handle, SPEAKER_LABELS_KEY).unwrap_or_default()
+}
+
+/// Get how speakers are shown in exported subtitles
+#[tauri::command]
+pub async fn get_speaker_labels(app_handle: tauri::AppHandle) -> Result<SpeakerLabels, String> {
+    Ok(load_speaker_labels(&app_handle))
+}
+
+/// Set how speakers are shown in exported subtitles and the names they are shown with
+#[tauri::command]
+pub async fn set_speaker_labels(app_handle: tauri::AppHandle, labels: SpeakerLabels) -> Result<(), String> {
+    if labels.names.values().any(|name| name.trim().is_empty()) {
+        return Err("Speaker names must not be empty".to_string());
+    }
+    settings::set(&app_handle, SPEAKER_LABELS_KEY, &labels).await.map_err(|e| e.to_string())
+}
+
+const SPEAKER_VOICES_KEY: &str = "speaker-voices";
+
+/// Voices of the speakers found by diarization, e.g. "SPEAKER_2" -> "nova"
+fn load_speaker_voices(app_handle: &tauri::AppHandle) -> HashMap<String, String> {
+    settings::get(app_handle, SPEAKER_VOICES_KEY).unwrap_or_default()
+}
+
+/// Get the voice each speaker is dubbed with
+#[tauri::command]
+pub async fn get_speaker_voices(app_handle: tauri::AppHandle) -> Result<HashMap<String, String>, String> {
+    Ok(load_speaker_voices(&app_handle))
+}
+
+/// Set the voice each speaker is dubbed with; the voices belong to the selected engine
+#[tauri::command]
+pub async fn set_speaker_voices(app_handle: tauri::AppHandle, voices: HashMap<String, String>) -> Result<(), String> {
+    let engine = load_tts_engine(&app_handle);
+    for voice in voices.values() {
+        validate_engine_voice(&engine, voice)?;
+    }
+    settings::set(&app_handle, SPEAKER_VOICES_KEY, &voices).await.map_err(|e| e.to_string())
+}
+
+const DIARIZATION_KEY: &str = "diarization";
+
+fn load_diarization(app_handle: &tauri::AppHandle) -> DiarizationSettings {
+    settings::get(app_handle, DIARIZATION_KEY).unwrap_or_default()
+}
+
+/// Get whether and how transcribed cues are split between speakers
+#[tauri::command]
+pub async fn get_diarization(app_handle: tauri::AppHandle) -> Result<DiarizationSettings, String> {
+    Ok(load_diarization(&app_handle))
+}
+
+/// Set whether and how transcribed cues are split between speakers
+#[tauri::command]
+pub async fn set_diarization(app_handle: tauri::AppHandle, diarization: DiarizationSettings) -> Result<(), String> {
+    if !(0.0..=2.0).contains(&diarization.threshold) {
+        return Err("The threshold is a cosine distance between 0 and 2".to_string());
+    }
+    settings::set(&app_handle, DIARIZATION_KEY, &diarization).await.map_err(|e| e.to_string())
+}
+
+/// Hash of everything that shapes a dub of a video into a language
+fn job_settings_hash(
+    app_handle: &tauri::AppHandle,
+    target_language: &str,
+    merge_style: &MergeStyle,
+    fit_strategy: FitStrategy,
+    voice: Option<&str>,
+) -> String {
+    let speed_profile = language_speed::resolve(Some(target_language), &load_speed_overrides(app_handle));
+    let mut settings = json!({
+        "target_language": target_language,
+        "merge_style": merge_style,
+        "speech_to_speech": load_speech_to_speech(app_handle),
+        "time_stretch": load_time_stretch(app_handle),
+        "overlap_policy": load_overlap_policy(app_handle),
+        "fit_strategy": fit_strategy,
+        "speed_profile": speed_profile,
+    });
+    // Only a non-default voice is hashed, so dubs made before voices were selectable still match
+    if let Some(voice) = voice.filter(|voice| *voice != DUB_VOICE) {
+        settings["voice"] = json!(voice);
+    }
+    let speaker_voices = load_speaker_voices(app_handle);
+    if !speaker_voices.is_empty() {
+        settings["speaker_voices"] = json!(speaker_voices);
+    }
+    let glossary = load_translation_glossary(app_handle);
+    if !glossary.is_empty() {
+        settings["glossary"] = json!(glossary);
+    }
+    let loudness = load_loudness_target(app_handle);
+    if loudness != LoudnessTarget::default() {
+        settings["loudness"] = json!(loudness);
+    }
+    if load_lip_sync(app_handle) {
+        settings["lip_sync"] = json!(true);
+    }
+    let sentence_merging = load_sentence_merging(app_handle);
+    if sentence_merging.enabled {
+        settings["sentence_merging"] = json!(sentence_merging);
+    }
+    let voice_activity = load_voice_activity(app_handle);
+    if voice_activity.enabled {
+        settings["voice_activity"] = json!(voice_activity);
+    }
+    library::settings_hash(&settings)
+}
+
+/// Find a finished dub of the video with the same language and settings, so the
+/// user can open it instead of paying for the same job again
+#[tauri::command]
+pub async fn find_duplicate_job(
+    app_handle: tauri::AppHandle,
+    video_id: String,
+    target_language: String,
+    merge_style: Option<MergeStyle>,
+    fit_strategy: Option<FitStrategy>,
+    voice: Option<String>,
+) -> Result<Option<library::CompletedOutput>, String> {
+    if video_id.is_empty() {
+        return Ok(None);
+    }
+    let merge_style = merge_style.unwrap_or_else(|| load_merge_style(&app_handle));
+    let fit_strategy = fit_strategy.unwrap_or_else(|| load_fit_strategy(&app_handle));
+    let hash = job_settings_hash(&app_handle, &target_language, &merge_style, fit_strategy, voice.as_deref());
+    Ok(library::find_completed(&app_handle, &video_id, &target_language, &hash).await)
+}
+
+/// Helper function to check if a file exists and is valid
+async fn check_file_exists(path: impl AsRef<std::path::Path>) -> bool {
+    tokio::fs::metadata(path).await.is_ok()
+}
+
+/// Check if a file exists and is accessible
+#[tauri::command]
+pub async fn check_file_exists_command(path: String) -> Result<bool, String> {
+    Ok(check_file_exists(path).await)
+}
+
+/// Process video through all steps: download, transcribe, translate, and TTS with synchronization
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn process_video(
+    url: String,
+    output_path: String,
+    target_language: String,
+    target_language_name: String,
+    source_language_code: String,
+    source_language_name: String,
+    api_key: String,
+    subtitle_source: Option<SubtitleSource>,
+    merge_style: Option<MergeStyle>,
+    fit_strategy: Option<FitStrategy>,
+    voice: Option<String>,
+    force: Option<bool>,
+    container: Option<OutputContainer>,
+    chapters: Option<Vec<usize>>,
+    start_time: Option<f64>,
+    end_time: Option<f64>,
+    extra_languages: Option<Vec<channels::TemplateLanguage>>,
+    window: tauri::Window,
+) -> Result<ProcessVideoResult, CommandError> {
+    let request = JobRequest {
+        url,
+        output_path,
+        target_language,
+        target_language_name,
+        source_language_code,
+        source_language_name,
+        api_key,
+        subtitle_source,
+        merge_style,
+        fit_strategy,
+        voice,
+        force,
+        container,
+        chapters,
+        start_time,
+        end_time,
+        extra_languages: extra_languages.unwrap_or_default(),
+    };
+    request.validate()?;
+    let job = new_pipeline_job(window.app_handle(), &request)?;
+    Ok(run_job(job, request.api_key, request.force.unwrap_or(false), window).await?)
+}
+
+/// Checkpoint of a new run, with the settings filling in what the request leaves out
+fn new_pipeline_job(app_handle: &tauri::AppHandle, request: &JobRequest) -> Result<PipelineJob, String> {
+    let mut merge_style = request.merge_style.clone().unwrap_or_else(|| load_merge_style(app_handle));
+    if let Some(container) = request.container {
+        merge_style.container = container;
+    }
+    // Tracks of several languages go into one MKV
+    if !request.extra_languages.is_empty() {
+        if request.container.is_some_and(|container| container != OutputContainer::Mkv) {
+            return Err("Several target languages can only be merged into an MKV".to_string());
+        }
+        if merge_style.output_mode == OutputMode::AudioOnly {
+            return Err("Several target languages need a video output, an audio file holds one".to_string());
+        }
+        merge_style.container = OutputContainer::Mkv;
+    }
+    merge_style.tracks.validate().map_err(|e| e.to_string())?;
+    merge_style.layout.validate(&merge_style.tracks).map_err(|e| e.to_string())?;
+    let fit_strategy = request.fit_strategy.unwrap_or_else(|| load_fit_strategy(app_handle));
+    validate_fit_strategy(fit_strategy)?;
+
+    Ok(PipelineJob {
+        job_id: jobs::new_job_id(),
+        url: request.url.clone(),
+        output_path: request.output_path.clone(),
+        target_language: request.target_language.clone(),
+        target_language_name: request.target_language_name.clone(),
+        source_language_code: request.source_language_code.clone(),
+        source_language_name: request.source_language_name.clone(),
+        subtitle_source: request.subtitle_source.clone(),
+        merge_style,
+        fit_strategy,
+        voice: request.voice.clone(),
+        chapters: request.chapters.clone(),
+        start_time: request.start_time,
+        end_time: request.end_time,
+        extra_languages: request.extra_languages.clone(),
+        status: PipelineStatus::Running,
+        completed: Vec::new(),
+        artifacts: Default::default(),
+        optimizer_report: None,
+        error: None,
+        updated_at: 0,
+    })
+}
+
+fn emit_queue_updated(window: &tauri::Window, queue: &JobQueue) {
+    if let Err(e) = emitter::emit(window, "queue-updated", queue.list()) {
+        warn!("Failed to emit queue-updated: {}", e);
+    }
+}
+
+/// Process queued videos one after another, for as long as the app runs
+async fn run_queue(window: tauri::Window) {
+    let app_handle = window.app_handle().clone();
+    let queue = app_handle.state::<JobQueue>();
+    loop {
+        let Some(entry) = queue.start_next() else {
+            queue.wait().await;
+            continue;
+        };
+        emit_queue_updated(&window, &queue);
+
+        let result = match new_pipeline_job(&app_handle, &entry.request) {
+            Ok(job) => {
+                queue.set_job_id(&entry.id, &job.job_id);
+                // The overall progress of the run is also reported per queue entry
+                let listener = {
+                    let window = window.clone();
+                    let listening_handle = app_handle.clone();
+                    let id = entry.id.clone();
+                    let job_id = job.job_id.clone();
+                    app_handle.listen_any(events::PIPELINE_EVENT, move |event| {
+                        let Ok(payload) = serde_json::from_str::<serde_json::Value>(event.payload()) else { return };
+                        // Until the entry has the run slot, another run may be reporting
+                        if payload["job_id"].as_str() != Some(job_id.as_str()) {
+                            return;
+                        }
+                        let Some(total) = payload["total_progress"].as_f64() else { return };
+                        if let Some(progress) = listening_handle.state::<JobQueue>().set_progress(&id, total as f32) {
+                            let _ = emitter::emit(&window, "queue-job-progress", progress);
+                        }
+                    })
+                };
+                let force = entry.request.force.unwrap_or(false);
+                let result = run_job(job, entry.request.api_key.clone(), force, window.clone()).await;
+                app_handle.unlisten(listener);
+                result.map(|result| result.final_path)
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &result {
+            warn!("Queued job {} failed: {}", entry.id, e);
+        }
+        queue.finish(&entry.id, result);
+        emit_queue_updated(&window, &queue);
+    }
+}
+
+/// Add a video to the processing queue; it starts once the jobs before it are done
+#[tauri::command]
+pub async fn enqueue_job(
+    request: JobRequest,
+    queue: tauri::State<'_, JobQueue>,
+    window: tauri::Window,
+) -> Result<queue::QueuedJob, CommandError> {
+    // Reject bad input now rather than when the entry's turn comes
+    request.validate()?;
+    new_pipeline_job(window.app_handle(), &request)?;
+    let entry = queue.enqueue(request).map_err(|e| e.to_string())?;
+    if queue.claim_worker() {
+        tauri::async_runtime::spawn(run_queue(window.clone()));
+    }
+    emit_queue_updated(&window, &queue);
+    Ok(entry)
+}
+
+/// Remove a queued video before it starts, or stop it if it's already running
+#[tauri::command]
+pub async fn cancel_job(id: String, queue: tauri::State<'_, JobQueue>, window: tauri::Window) -> Result<(), String> {
+    if let Some(job_id) = queue.cancel(&id).map_err(|e| e.to_string())? {
+        stop_pipeline(&job_id)?;
+    }
+    emit_queue_updated(&window, &queue);
+    Ok(())
+}
+
+/// Trip the cancellation token of a running pipeline job
+fn stop_pipeline(job_id: &str) -> Result<(), String> {
+    cancellation::cancel(job_id).map_err(|e| e.to_string())?;
+    // A job parked for quota wouldn't notice the token until it's resumed
+    if quota::paused_jobs().iter().any(|paused| paused.job_id == job_id)
+        && let Err(e) = quota::abandon(job_id)
+    {
+        warn!("Failed to release job {} waiting for quota: {}", job_id, e);
+    }
+    Ok(())
+}
+
+/// Stop a running job: downloads, OpenAI requests, speech generation and the
+/// ffmpeg merge are interrupted, and the job can be resumed later
+#[tauri::command]
+pub async fn cancel_processing(job_id: String) -> Result<(), String> {
+    stop_pipeline(&job_id)
+}
+
+/// Entries of the processing queue, in the order they run
+#[tauri::command]
+pub async fn list_jobs(queue: tauri::State<'_, JobQueue>) -> Result<Vec<queue::QueuedJob>, String> {
+    Ok(queue.list())
+}
+
+/// Change the order in which the queued videos run
+#[tauri::command]
+pub async fn reorder_jobs(ids: Vec<String>, queue: tauri::State<'_, JobQueue>, window: tauri::Window) -> Result<(), String> {
+    queue.reorder(&ids).map_err(|e| e.to_string())?;
+    emit_queue_updated(&window, &queue);
+    Ok(())
+}
+
+/// Continue a failed or interrupted run from the first step that didn't finish,
+/// reusing the files of the steps before it
+#[tauri::command]
+pub async fn resume_video_processing(
+    job_id: String,
+    api_key: String,
+    window: tauri::Window,
+) -> Result<ProcessVideoResult, String> {
+    let app_handle = window.app_handle().clone();
+    let mut job = pipeline_job::load(&app_handle, &job_id).await.map_err(|e| e.to_string())?;
+    if job.status == PipelineStatus::Completed {
+        return Err(format!("Job {} has already completed", job_id));
+    }
+    if job_state::get(&job_id).is_some_and(|info| !info.state.is_terminal()) {
+        return Err(format!("Job {} is still running", job_id));
+    }
+    job.status = PipelineStatus::Running;
+    job.error = None;
+    // The duplicate check would only find this very job
+    run_job(job, api_key, true, window).await
+}
+
+/// Runs of process_video that failed or were interrupted and can be resumed
+#[tauri::command]
+pub async fn get_unfinished_jobs(app_handle: tauri::AppHandle) -> Result<Vec<PipelineJob>, String> {
+    let running = events::running_jobs();
+    let mut jobs = pipeline_job::unfinished(&app_handle).await;
+    jobs.retain(|job| !running.contains(&job.job_id));
+    Ok(jobs)
+}
+
+/// Run a pipeline job with event recording, state tracking and notifications
+async fn run_job(
+    mut job: PipelineJob,
+    api_key: String,
+    force: bool,
+    window: tauri::Window,
+) -> Result<ProcessVideoResult, String> {
+    let app_handle = window.app_handle().clone();
+    let job_id = job.job_id.clone();
+    // Progress events, recorders and the job log assume a single run
+    let queue = app_handle.state::<JobQueue>();
+    let _run_slot = queue.take_run_slot(&job_id).await;
+    let started_at = std::time::Instant::now();
+    let url = job.url.clone();
+    let target_language = job.target_language.clone();
+    pipeline_job::checkpoint(&app_handle, &mut job).await;
+
+    // Buffer progress events so a reloaded frontend can catch up
+    let recorder = events::EventRecorder::start(&app_handle, &job_id);
+    job_state::register(&app_handle, &job_id);
+    let _ = window.emit("job-started", json!({
+        "job_id": job_id,
+        "url": url,
+        "target_language": target_language,
+    }));
+
+    let cancel = cancellation::register(&job_id);
+    let result = run_video_pipeline(&mut job, api_key, force, &cancel, window.clone()).await;
+    cancellation::release(&job_id);
+    // Each step words its cancellation differently, the run reports it the same way
+    let cancelled = result.is_err() && cancel.is_cancelled();
+    let result = if cancelled { Err(cancellation::CANCELLED.to_string()) } else { result };
+
+    // The checkpoint keeps what finished, so a failed or cancelled run can be resumed
+    match &result {
+        Ok(_) => job.status = PipelineStatus::Completed,
+        Err(e) => {
+            job.status = PipelineStatus::Failed;
+            job.error = Some(e.clone());
+        }
+    }
+    pipeline_job::checkpoint(&app_handle, &mut job).await;
+
+    if result.is_err() && !cancelled {
+        events::set_taskbar_progress(&window, events::TaskbarProgress::Failed);
+    }
+    let final_state = match &result {
+        Ok(_) => job_state::transition(&app_handle, &job_id, JobState::Completed, None),
+        Err(_) if cancelled => job_state::transition(&app_handle, &job_id, JobState::Cancelled, None),
+        Err(e) => job_state::transition(&app_handle, &job_id, JobState::Failed, Some(e.clone())),
+    };
+    if let Err(e) = final_state {
+        warn!("Failed to record final job state: {}", e);
+    }
+
+    if perf_stats::enabled(&app_handle)
+        && let Some(info) = job_state::get(&job_id)
+    {
+        // The source is in the library once archived; without a library entry
+        // it went with the temp files and the output has the same length
+        let media_secs = match &result {
+            Ok(r) => match get_video_duration(&r.video_path).await {
+                Ok(secs) => Some(secs),
+                Err(_) => get_video_duration(&r.final_path).await.ok(),
+            },
+            Err(_) => None,
+        };
+        if let Err(e) = perf_stats::record_job(&app_handle, &info, media_secs).await {
+            warn!("Failed to record performance statistics: {}", e);
+        }
+    }
+
+    let summary = notify::JobSummary {
+        status: match &result {
+            Ok(_) => notify::JobStatus::Completed,
+            Err(_) if cancelled => notify::JobStatus::Cancelled,
+            Err(_) => notify::JobStatus::Failed,
+        },
+        url,
+        target_language,
+        output_path: result.as_ref().ok().map(|r| r.final_path.clone()),
+        error: result.as_ref().err().cloned(),
+        duration_secs: started_at.elapsed().as_secs(),
+    };
+    let _ = emitter::emit(&window, "job-finished", summary_with_id(&job_id, &summary));
+    notify::notify_job_finished(&app_handle, summary);
+    drop(recorder);
+
+    result
+}
+
+/// Channel templates being processed, a second run of one would dub the same uploads
+static RUNNING_TEMPLATES: once_cell::sync::Lazy<std::sync::Mutex<std::collections::HashSet<String>>> =
+    once_cell::sync::Lazy::new(Default::default);
+
+/// Saved channel templates
+#[tauri::command]
+pub async fn get_channel_templates(app_handle: tauri::AppHandle) -> Result<Vec<channels::ChannelTemplate>, String> {
+    Ok(channels::load(&app_handle))
+}
+
+/// Create or update a channel template, returns it with its id
+#[tauri::command]
+pub async fn save_channel_template(
+    app_handle: tauri::AppHandle,
+    template: channels::ChannelTemplate,
+) -> Result<channels::ChannelTemplate, String> {
+    for language in &template.languages {
+        if let Some(voice) = &language.voice {
+            validate_engine_voice("openai-tts", voice)?;
+        }
+    }
+    channels::save(&app_handle, template).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_channel_template(app_handle: tauri::AppHandle, template_id: String) -> Result<(), String> {
+    channels::delete(&app_handle, &template_id).await.map_err(|e| e.to_string())
+}
+
+/// Fetch the newest uploads of a template's channel and dub the ones not done
+/// yet. Jobs run one after another in the background; the enqueued jobs are
+/// returned right away and report through the usual job events.
+#[tauri::command]
+pub async fn process_latest_from_channel(
+    template_id: String,
+    api_key: String,
+    window: tauri::Window,
+) -> Result<Vec<channels::TemplateJob>, String> {
+    let app_handle = window.app_handle().clone();
+    let template = channels::find(&app_handle, &template_id)
+        .ok_or_else(|| format!("Channel template not found: {}", template_id))?;
+
+    if !RUNNING_TEMPLATES.lock().unwrap().insert(template_id.clone()) {
+        return Err(format!("Channel template \"{}\" is already being processed", template.name));
+    }
+
+    let listed = youtube::list_channel_uploads(&app_handle, &template.channel_url, template.max_videos).await;
+    let jobs = listed
+        .map_err(|e| e.to_string())
+        .and_then(|uploads| {
+            let selected = template.select(&uploads).map_err(|e| e.to_string())?;
+            Ok(template.jobs(&selected))
+        });
+    let jobs = match jobs {
+        Ok(jobs) if !jobs.is_empty() => jobs,
+        other => {
+            RUNNING_TEMPLATES.lock().unwrap().remove(&template_id);
+            if other.is_ok() {
+                info!("No new uploads for channel template {}", template.name);
+            }
+            return other;
+        }
+    };
+    info!("Enqueued {} jobs from channel template {}", jobs.len(), template.name);
+
+    let queued = jobs.clone();
+    tauri::async_runtime::spawn(async move {
+        // Uploads with a failed language stay unprocessed and are retried next time
+        let mut failed: Vec<String> = Vec::new();
+        for (index, job) in jobs.iter().enumerate() {
+            if let Err(e) = channels::prepare_output_dir(&job.output_dir).await {
+                error!("{}", e);
+                failed.push(job.video_id.clone());
+            } else if let Err(e) = process_video(
+                job.url.clone(),
+                job.output_dir.to_string_lossy().to_string(),
+                job.language.code.clone(),
+                job.language.name.clone(),
+                template.source_language_code.clone(),
+                template.source_language_name.clone(),
+                api_key.clone(),
+                None,
+                None,
+                None,
+                job.language.voice.clone(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                window.clone(),
+            )
+            .await
+            {
+                warn!("Channel template job for {} ({}) failed: {}", job.title, job.language.code, e);
+                failed.push(job.video_id.clone());
+            }
+
+            let last_of_video = jobs.get(index + 1).is_none_or(|next| next.video_id != job.video_id);
+            if last_of_video && !failed.contains(&job.video_id)
+                && let Err(e) = channels::mark_processed(&app_handle, &template.id, std::slice::from_ref(&job.video_id)).await
+            {
+                warn!("Failed to mark {} as processed: {}", job.video_id, e);
+            }
+        }
+        RUNNING_TEMPLATES.lock().unwrap().remove(&template.id);
+        info!("Channel template {} finished, {} uploads failed", template.name, failed.len());
+    });
+
+    Ok(queued)
+}
+
+/// Move the job to the next pipeline state. The state only feeds the UI and
+/// the job history, a rejected transition doesn't stop the pipeline.
+fn advance(app_handle: &tauri::AppHandle, job_id: &str, state: JobState) {
+    if let Err(e) = job_state::transition(app_handle, job_id, state, None) {
+        warn!("Failed to record state {:?} of job {}: {}", state, job_id, e);
+    }
+}
+
+/// Where a job ran out of OpenAI quota
+struct QuotaStop<'a> {
+    stage: JobState,
+    error: &'a str,
+    /// Intermediate files the resumed stage needs
+    artifacts: Vec<PathBuf>,
+}
+
+/// Park the job until the user resolves the exhausted OpenAI quota. Swaps in
+/// the replacement API key if the user gave one, fails if they gave up.
+async fn wait_for_quota(
+    app_handle: &tauri::AppHandle,
+    job_id: &str,
+    url: &str,
+    target_language: &str,
+    stop: QuotaStop<'_>,
+    api_key: &mut String,
+) -> Result<(), String> {
+    let QuotaStop { stage, error, artifacts } = stop;
+    warn!("OpenAI quota exhausted in {:?}, pausing job {}: {}", stage, job_id, error);
+    let summary = notify::JobSummary {
+        status: notify::JobStatus::Paused,
+        url: url.to_string(),
+        target_language: target_language.to_string(),
+        output_path: None,
+        error: Some(error.to_string()),
+        duration_secs: 0,
+    };
+    notify::notify_job_finished(app_handle, summary);
+
+    match quota::pause(app_handle, job_id, url, stage, error, artifacts).await.map_err(|e| e.to_string())? {
+        quota::ResumeDecision::Resume { api_key: new_key } => {
+            if let Some(new_key) = new_key {
+                *api_key = new_key;
+            }
+            Ok(())
+        }
+        quota::ResumeDecision::Abandon => Err(format!("Abandoned while waiting for OpenAI quota: {}", error)),
+    }
+}
+
+/// Resume a job paused for OpenAI quota, optionally with another API key
+#[tauri::command]
+pub async fn resume_job(job_id: String, api_key: Option<String>, window: tauri::Window) -> Result<(), String> {
+    // Paused before the app was closed: nothing waits for the decision, the job
+    // is run again from its checkpoint
+    if quota::is_restored(&job_id) {
+        let api_key = api_key
+            .filter(|key| !key.trim().is_empty())
+            .ok_or_else(|| format!("Job {} was paused in an earlier session, resuming it needs the API key", job_id))?;
+        quota::forget_restored(window.app_handle(), &job_id).await;
+        info!("Resuming job {} paused in an earlier session from its checkpoint", job_id);
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = resume_video_processing(job_id.clone(), api_key, window).await {
+                error!("Failed to resume job {}: {}", job_id, e);
+            }
+        });
+        return Ok(());
+    }
+    quota::resume(&job_id, api_key).map_err(|e| e.to_string())
+}
+
+/// Give up on a job paused for OpenAI quota
+#[tauri::command]
+pub async fn abandon_paused_job(app_handle: tauri::AppHandle, job_id: String) -> Result<(), String> {
+    if let Some(paused) = quota::forget_restored(&app_handle, &job_id).await {
+        // The checkpoint stays, the run can still be resumed as a failed one
+        if let Ok(mut job) = pipeline_job::load(&app_handle, &job_id).await {
+            job.status = PipelineStatus::Failed;
+            job.error = Some(paused.error);
+            pipeline_job::checkpoint(&app_handle, &mut job).await;
+        }
+        return Ok(());
+    }
+    quota::abandon(&job_id).map_err(|e| e.to_string())
+}
+
+/// List jobs waiting for OpenAI quota
+#[tauri::command]
+pub async fn get_paused_jobs() -> Result<Vec<quota::PausedJob>, String> {
+    Ok(quota::paused_jobs())
+}
+
+/// Copy the translation of a job waiting for review to a file the user can
+/// edit, returns its path
+#[tauri::command]
+pub async fn export_translation_for_review(app_handle: tauri::AppHandle, job_id: String) -> Result<String, String> {
+    review::export(&app_handle, &job_id)
+        .await
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Continue a job waiting for review with the edited translation
+#[tauri::command]
+pub async fn apply_edited_translation(job_id: String, vtt_path: String) -> Result<(), String> {
+    review::apply(&job_id, Path::new(&vtt_path)).await.map_err(|e| e.to_string())
+}
+
+/// Get how much each dubbed segment of a job was stretched to fit its cue
+#[tauri::command]
+pub async fn get_timing_report(app_handle: tauri::AppHandle, job_id: String) -> Result<TimingReport, String> {
+    timing_report::load(&app_handle, &job_id).await.map_err(|e| e.to_string())
+}
+
+/// Get the bus levels of a job's mix with the instrumental
+#[tauri::command]
+pub async fn get_mix_report(app_handle: tauri::AppHandle, job_id: String) -> Result<serde_json::Value, String> {
+    jobs::mix_report(&app_handle, &job_id).await.map_err(|e| e.to_string())
+}
+
+/// Get the merge and retime decisions behind the dubbed phrasing of a job
+#[tauri::command]
+pub async fn get_optimizer_report(app_handle: tauri::AppHandle, job_id: String) -> Result<OptimizerReport, String> {
+    optimizer_report::load(&app_handle, &job_id).await.map_err(|e| e.to_string())
+}
+
+/// Per-cue confidence of a Whisper transcript, for the confidence heatmap
+#[tauri::command]
+pub async fn get_transcript_confidence(vtt_path: String) -> Result<confidence::TranscriptConfidence, String> {
+    confidence::load(Path::new(&vtt_path))
+        .await
+        .ok_or_else(|| format!("No confidence data for {}", vtt_path))
+}
+
+/// Get the current lifecycle state of a job
+#[tauri::command]
+pub async fn get_job_state(job_id: String) -> Result<job_state::JobStateInfo, String> {
+    job_state::get(&job_id).ok_or_else(|| format!("Unknown job {}", job_id))
+}
+
+/// Explain why a job failed and what to do about it. With an API key the
+/// explanation also gets a summary from an OpenAI model.
+#[tauri::command]
+pub async fn diagnose_failure(job_id: String, api_key: Option<String>) -> Result<diagnose::Diagnosis, String> {
+    let info = job_state::get(&job_id).ok_or_else(|| format!("Unknown job {}", job_id))?;
+    let events = events::replay(&job_id, 0).unwrap_or_default();
+    let context = diagnose::collect(&info, &events, |tool| crate::utils::tools::get_tool_path(tool).is_some())
+        .map_err(|e| e.to_string())?;
+    let mut diagnosis = diagnose::explain(context);
+
+    if let Some(api_key) = api_key.filter(|key| !key.trim().is_empty()) {
+        match diagnose::summarize(&diagnosis, &api_key).await {
+            Ok(summary) => diagnosis.summary = Some(summary),
+            Err(e) => warn!("Failed to summarize failure of job {}: {}", job_id, e),
+        }
+    }
+    Ok(diagnosis)
+}
+
+/// Get aggregated local performance statistics of past jobs
+#[tauri::command]
+pub async fn get_performance_stats(app_handle: tauri::AppHandle) -> Result<perf_stats::PerformanceStats, String> {
+    perf_stats::performance_stats(&app_handle).await.map_err(|e| e.to_string())
+}
+
+/// Get whether local performance statistics are collected
+#[tauri::command]
+pub async fn get_performance_stats_enabled(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    Ok(perf_stats::enabled(&app_handle))
+}
+
+/// Enable or disable collection of local performance statistics
+#[tauri::command]
+pub async fn set_performance_stats_enabled(app_handle: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    perf_stats::set_enabled(&app_handle, enabled).await.map_err(|e| e.to_string())
+}
+
+/// Delete all collected performance statistics
+#[tauri::command]
+pub async fn clear_performance_stats(app_handle: tauri::AppHandle) -> Result<(), String> {
+    perf_stats::clear(&app_handle).await.map_err(|e| e.to_string())
+}
+
+fn summary_with_id(job_id: &str, summary: &notify::JobSummary) -> serde_json::Value {
+    let mut value = serde_json::to_value(summary).unwrap_or_default();
+    value["job_id"] = json!(job_id);
+    value
+}
+
+async fn run_video_pipeline(
+    job: &mut PipelineJob,
+    mut api_key: String,
+    force: bool,
+    cancel: &CancellationToken,
+    window: tauri::Window,
+) -> Result<ProcessVideoResult, String> {
+    let job_id = job.job_id.clone();
+    let url = job.url.clone();
+    let output_path = job.output_path.clone();
+    let job_log = match jobs::job_dir(window.app_handle(), &job_id) {
+        Ok(archive_dir) => logger::JobLog::start(&job_id, &Path::new(&output_path).join("videonova_temp"), &archive_dir)
+            .map_err(|e| warn!("Failed to start the log of job {}: {}", job_id, e))
+            .ok(),
+        Err(e) => {
+            warn!("Failed to start the log of job {}: {}", job_id, e);
+            None
+        }
+    };
+    let target_language = job.target_language.clone();
+    let target_language_name = job.target_language_name.clone();
+    let mut source_language_code = job.source_language_code.clone();
+    let mut source_language_name = job.source_language_name.clone();
+    let subtitle_source = job.subtitle_source.clone();
+    let merge_style = job.merge_style.clone();
+    let fit_strategy = job.fit_strategy;
+    let voice = job.voice.clone();
+    let selected_chapters = job.chapters.clone();
+    let extra_languages = job.extra_languages.clone();
+
+    info!("=== Starting Video Processing Pipeline ===");
+    info!("Parameters:");
+    info!("  URL: {}", url);
+    info!("  Output Path: {}", output_path);
+    info!("  Source Language: {} ({})", source_language_name, source_language_code);
+    info!(
+        "  Target Language: {} ({})",
+        target_language_name, target_language
+    );
+    for language in &extra_languages {
+        info!("  Also dubbing into: {} ({})", language.name, language.code);
+    }
+
+    info!("  Job ID: {}", job_id);
+
+    // Steps of an earlier run of this job whose files can be reused
+    let resumed = job.resumable_steps();
+    if !resumed.is_empty() {
+        info!("Resuming job {} after {:?}", job_id, resumed);
+    }
+
+    // Look for an earlier run of the same video in the library
+    let app_handle = window.app_handle().clone();
+    let video_info = youtube::get_video_info(&url, &window)
+        .await
+        .map_err(|e| format!("Failed to get video info: {}", e))?;
+
+    // Selected chapters and time ranges are processed as a clip of their own,
+    // with a library entry of their own
+    let selection = match (&selected_chapters, job.start_time, job.end_time) {
+        (Some(selected), _, _) => Some(chapters::selection_span(&video_info.chapters, selected).map_err(|e| e.to_string())?),
+        (None, None, None) => None,
+        (None, start, end) => Some(clip_range(start, end, video_info.duration)?),
+    };
+    let library_id = match selection {
+        Some((start, end)) if !video_info.id.is_empty() => format!("{}@{:.0}-{:.0}", video_info.id, start, end),
+        _ => video_info.id.clone(),
+    };
+    let media_duration = selection.map(|(start, end)| end - start).unwrap_or(video_info.duration);
+
+    // The same video, language and settings were dubbed before, don't pay twice
+    let settings_hash = job_settings_hash(&app_handle, &target_language, &merge_style, fit_strategy, voice.as_deref());
+    let extra_settings_hashes: Vec<String> = extra_languages
+        .iter()
+        .map(|language| {
+            let language_voice = language.voice.as_deref().or(voice.as_deref());
+            job_settings_hash(&app_handle, &language.code, &merge_style, fit_strategy, language_voice)
+        })
+        .collect();
+    if !force && !library_id.is_empty()
+        && let Some(existing) = library::find_completed(&app_handle, &library_id, &target_language, &settings_hash).await
+    {
+        return Err(format!(
+            "This video was already dubbed into {} with the same settings: {}. Reprocess with force to run it again.",
+            target_language_name,
+            existing.output_path.display()
+        ));
+    }
+
+    let mut trim_library_media = false;
+    let library_entry = if library_id.is_empty() || resumed.contains(&PipelineStep::Download) {
+        None
+    } else {
+        match library::find_reusable(&app_handle, &library_id).await {
+            Some(entry) => Some(entry),
+            // A clip of a video dubbed before is trimmed out of the whole media
+            None if selection.is_some() => {
+                trim_library_media = true;
+                library::find_reusable(&app_handle, &video_info.id).await
+            }
+            None => None,
+        }
+    };
+    let reused_from_library = library_entry.is_some();
+
+    // Speech-to-speech skips the text translation, it's only worth it for short clips
+    let speech_to_speech = load_speech_to_speech(&app_handle);
+    if speech_to_speech && media_duration > SPEECH_TO_SPEECH_MAX_DURATION {
+        info!(
+            "Video is longer than {:.0}s, using translation and TTS instead of speech-to-speech",
+            SPEECH_TO_SPEECH_MAX_DURATION
+        );
+    }
+    let speech_to_speech = speech_to_speech && media_duration <= SPEECH_TO_SPEECH_MAX_DURATION;
+
+    // Overall progress only counts the steps this job actually runs
+    let mut scheduled_steps = Vec::new();
+    if !reused_from_library {
+        scheduled_steps.extend([PipelineStep::Download, PipelineStep::Transcribe]);
+    }
+    if !speech_to_speech {
+        scheduled_steps.push(PipelineStep::Translate);
+    }
+    scheduled_steps.extend([PipelineStep::Tts, PipelineStep::Merge]);
+    if matches!(remote::load_config(&app_handle), Ok(Some(_))) {
+        scheduled_steps.push(PipelineStep::Upload);
+    }
+    scheduled_steps.retain(|step| !resumed.contains(step));
+    let progress_tracker = PipelineProgressTracker::start(&app_handle, &window, &job_id, &scheduled_steps);
+
+    let (download_result, transcription_result) = if let Some(entry) = library_entry {
+        info!("Reusing media and transcription from library for video {}", entry.video_id);
+        let video_path = entry.video_path.unwrap_or_default().to_string_lossy().to_string();
+        let audio_path = entry.audio_path.unwrap_or_default().to_string_lossy().to_string();
+        let vtt_path = entry.transcription_path.to_string_lossy().to_string();
+        let (video_path, audio_path, vtt_path) = match selection {
+            Some((start, end)) if trim_library_media => {
+                info!("Trimming {:.1}s-{:.1}s out of the library media", start, end);
+                let temp_dir = PathBuf::from(&output_path).join("videonova_temp");
+                tokio::fs::create_dir_all(&temp_dir).await.map_err(|e| e.to_string())?;
+                let trim = |path: String| {
+                    let temp_dir = temp_dir.clone();
+                    async move {
+                        merge::trim(Path::new(&path), start, end, &temp_dir)
+                            .await
+                            .map(|clip| clip.to_string_lossy().to_string())
+                            .map_err(|e| format!("Failed to trim the library media: {}", e))
+                    }
+                };
+                let clipped_vtt = temp_dir.join(format!("transcription_{:.0}-{:.0}.vtt", start, end));
+                clip_transcription(Path::new(&vtt_path), start, end, &clipped_vtt).await?;
+                (trim(video_path).await?, trim(audio_path).await?, clipped_vtt.to_string_lossy().to_string())
+            }
+            _ => (video_path, audio_path, vtt_path),
+        };
+        info!("  Video path: {}", video_path);
+        info!("  Audio path: {}", audio_path);
+        info!("  VTT path: {}", vtt_path);
+        job.artifacts.video_path = Some(PathBuf::from(&video_path));
+        job.artifacts.audio_path = Some(PathBuf::from(&audio_path));
+        job.artifacts.transcription_path = Some(PathBuf::from(&vtt_path));
+        job.complete(PipelineStep::Download);
+        job.complete(PipelineStep::Transcribe);
+        pipeline_job::checkpoint(&app_handle, job).await;
+        ((video_path, audio_path), TranscriptionResult { vtt_path })
+    } else {
+        // Step 1: Download video
+        let download_result = if let (true, Some(video_path), Some(audio_path)) = (
+            resumed.contains(&PipelineStep::Download),
+            job.artifacts.video_path.as_ref(),
+            job.artifacts.audio_path.as_ref(),
+        ) {
+            info!("Step 1: Skipped, reusing the download of the earlier run");
+            (video_path.to_string_lossy().to_string(), audio_path.to_string_lossy().to_string())
+        } else {
+            cancellation::check(cancel)?;
+            advance(&app_handle, &job_id, JobState::Downloading);
+            info!("Step 1: Downloading video");
+            let download_result = match download_media(window.clone(), url.clone(), output_path.clone(), selection, cancel.clone(), Some(&job_id)).await {
+                Ok(json_result) => {
+                    let video_path = json_result["video_path"].as_str()
+                        .ok_or_else(|| "Missing video_path in download result".to_string())?
+                        .to_string();
+                    let audio_path = json_result["audio_path"].as_str()
+                        .ok_or_else(|| "Missing audio_path in download result".to_string())?
+                        .to_string();
+                    info!("Download completed successfully");
+                    info!("  Video path: {}", video_path);
+                    info!("  Audio path: {}", audio_path);
+                    (video_path, audio_path)
+                }
+                Err(e) => {
+                    error!("Download failed: {}", e);
+                    return Err(format!("Download failed: {}", e));
+                }
+            };
+            progress_tracker.complete(&window, PipelineStep::Download);
+            job.artifacts.video_path = Some(PathBuf::from(&download_result.0));
+            job.artifacts.audio_path = Some(PathBuf::from(&download_result.1));
+            job.complete(PipelineStep::Download);
+            pipeline_job::checkpoint(&app_handle, job).await;
+            download_result
+        };
+
+        // Step 2: Transcribe audio, unless the user picked existing subtitles
+        let transcription_result = if let (true, Some(vtt_path)) =
+            (resumed.contains(&PipelineStep::Transcribe), job.artifacts.transcription_path.as_ref())
+        {
+            info!("Step 2: Skipped, reusing the transcription of the earlier run");
+            TranscriptionResult { vtt_path: vtt_path.to_string_lossy().to_string() }
+        } else {
+            cancellation::check(cancel)?;
+            advance(&app_handle, &job_id, JobState::Transcribing);
+            let transcription_result = if let Some(source) = &subtitle_source {
+                info!("Step 2: Using existing {:?} subtitles '{}' instead of transcription", source.kind, source.name);
+                let result = use_subtitle_source(source.clone(), download_result.1.clone(), output_path.clone())
+                    .await
+                    .map_err(|e| {
+                        error!("Failed to use subtitle source: {}", e);
+                        format!("Failed to use subtitle source: {}", e)
+                    })?;
+                // Subtitles of the source are timed to the whole video
+                if let Some((start, end)) = selection {
+                    let vtt_path = PathBuf::from(&result.vtt_path);
+                    clip_transcription(&vtt_path, start, end, &vtt_path).await?;
+                }
+                result
+            } else {
+                info!("Step 2: Transcribing audio");
+                // A language left to detection is checked on a sample before the whole audio is paid for
+                let transcription_language = if source_language::is_auto(&source_language_code) {
+                    match detect_source_language(&window, &job_id, &download_result.1, &output_path, &api_key, cancel).await? {
+                        Some((code, name)) => {
+                            source_language_code = code.clone();
+                            source_language_name = name;
+                            job.source_language_code = source_language_code.clone();
+                            job.source_language_name = source_language_name.clone();
+                            pipeline_job::checkpoint(&app_handle, job).await;
+                            Some(code)
+                        }
+                        None => None,
+                    }
+                } else {
+                    Some(source_language_code.clone())
+                };
+                loop {
+                    let transcription = transcribe_audio_file(
+                        download_result.1.clone(), // audio_path
+                        output_path.clone(),
+                        api_key.clone(),
+                        transcription_language.clone(),
+                        Some(&job_id),
+                        window.clone(),
+                    );
+                    match cancellation::run(cancel, transcription).await.map_err(|e| e.to_string())? {
+                        Ok(result) => {
+                            info!("Transcription completed successfully");
+                            info!("  VTT path: {}", result.vtt_path);
+                            break result;
+                        }
+                        Err(e) if quota::is_quota_error(&e) => {
+                            let artifacts = vec![PathBuf::from(&download_result.0), PathBuf::from(&download_result.1)];
+                            wait_for_quota(&app_handle, &job_id, &url, &target_language, QuotaStop { stage: JobState::Transcribing, error: &e, artifacts }, &mut api_key).await?;
+                        }
+                        Err(e) => {
+                            error!("Transcription failed: {}", e);
+                            return Err(format!("Transcription failed: {}", e));
+                        }
+                    }
+                }
+            };
+            progress_tracker.complete(&window, PipelineStep::Transcribe);
+            job.artifacts.transcription_path = Some(PathBuf::from(&transcription_result.vtt_path));
+            job.complete(PipelineStep::Transcribe);
+            pipeline_job::checkpoint(&app_handle, job).await;
+            transcription_result
+        };
+
+        // Remember the transcription so other target languages can reuse it
+        if !library_id.is_empty()
+            && let Err(e) = library::record_transcription(
+                &app_handle,
+                &library_id,
+                &url,
+                &video_info.title,
+                Some(source_language_code.clone()),
+                Path::new(&transcription_result.vtt_path),
+            ).await
+        {
+            warn!("Failed to store transcription in library: {}", e);
+        }
+
+        (download_result, transcription_result)
+    };
+
+    // Step 3: Translate VTT. With speech-to-speech the synchronizer writes the
+    // translated subtitles from what the model said.
+    let translation_result = if speech_to_speech {
+        info!("Step 3: Skipped, speech-to-speech translates the speech directly");
+        let vtt_path = Path::new(&transcription_result.vtt_path);
+        let base_filename = vtt_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "output".to_string());
+        let translated_vtt_path = PathBuf::from(&output_path)
+            .join("videonova_temp")
+            .join(format!("{}_{}.vtt", sanitize_filename(&base_filename), target_language));
+        TranslationResult {
+            translated_vtt_path: translated_vtt_path.to_string_lossy().to_string(),
+            base_filename,
+        }
+    } else if let (true, Some(translated_vtt_path)) =
+        (resumed.contains(&PipelineStep::Translate), job.artifacts.translation_path.as_ref())
+    {
+        info!("Step 3: Skipped, reusing the translation of the earlier run");
+        TranslationResult {
+            translated_vtt_path: translated_vtt_path.to_string_lossy().to_string(),
+            base_filename: Path::new(&transcription_result.vtt_path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| "output".to_string()),
+        }
+    } else {
+        cancellation::check(cancel)?;
+        advance(&app_handle, &job_id, JobState::Translating);
+        info!("Step 3: Translating subtitles");
+        let translation_result = loop {
+            let request = VttTranslation {
+                vtt_path: transcription_result.vtt_path.clone(),
+                encoding: None,
+                output_path: output_path.clone(),
+                target_language: target_language_name.clone(),
+                target_language_code: target_language.clone(),
+                api_key: api_key.clone(),
+                cancel: Some(cancel),
+                job_id: Some(&job_id),
+            };
+            match translate_vtt_file(request, window.clone()).await {
+                Ok(result) => {
+                    info!("Translation completed successfully");
+                    info!("  Translated VTT path: {}", result.translated_vtt_path);
+                    break result;
+                }
+                Err(e) if quota::is_quota_error(&e) => {
+                    let artifacts = [&download_result.0, &download_result.1, &transcription_result.vtt_path]
+                        .into_iter()
+                        .map(PathBuf::from)
+                        .collect();
+                    wait_for_quota(&app_handle, &job_id, &url, &target_language, QuotaStop { stage: JobState::Translating, error: &e, artifacts }, &mut api_key).await?;
+                }
+                Err(e) => {
+                    error!("Translation failed: {}", e);
+                    return Err(format!("Translation failed: {}", e));
+                }
+            }
+        };
+        progress_tracker.complete(&window, PipelineStep::Translate);
+        job.artifacts.translation_path = Some(PathBuf::from(&translation_result.translated_vtt_path));
+        job.complete(PipelineStep::Translate);
+        pipeline_job::checkpoint(&app_handle, job).await;
+        // Only a fresh translation stops for review, a resumed run goes on with the one it has
+        if load_review_translation(&app_handle) {
+            review::wait(&app_handle, &job_id, Path::new(&translation_result.translated_vtt_path), cancel).await?;
+        }
+        translation_result
+    };
+
+    // Небольшая пауза после завершения перевода и проверка файлов
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Проверяем наличие всех необходимых файлов перед запуском TTS
+    let mut required_files = vec![
+        &download_result.0, // video_path
+        &download_result.1, // audio_path
+        &transcription_result.vtt_path,
+    ];
+    if !speech_to_speech {
+        required_files.push(&translation_result.translated_vtt_path);
+    }
+    for path_str in required_files {
+        let path = std::path::Path::new(path_str);
         if !check_file_exists_and_valid(path).await {
             let error_msg = format!("Required file not found or empty: {}", path_str);
             error!("{}", error_msg);
@@ -834,8 +3146,6 @@ pub async fn process_video(
     }
 
     // Step 4: Generate TTS and synchronize with video
-    info!("Step 4: Generating speech and synchronizing with video");
-    
     // Create a dedicated TTS directory for intermediate audio files
     let tts_dir = PathBuf::from(&output_path).join("videonova_temp").join("tts");
     tokio::fs::create_dir_all(&tts_dir)
@@ -852,50 +3162,406 @@ pub async fn process_video(
     let tts_output = tts_dir.join(format!("{}_tts.wav", original_filename));
     info!("TTS output will be saved to: {}", tts_output.display());
 
-    let tts_result = generate_speech(
-        download_result.0.clone(), // video_path
-        download_result.1.clone(), // audio_path
-        transcription_result.vtt_path.clone(),
-        translation_result.translated_vtt_path.clone(),
-        tts_output.to_string_lossy().to_string(),
-        api_key.clone(),
-        window.clone(),
-    )
-    .await
-    .map_err(|e| {
-        error!("TTS generation and synchronization failed: {}", e);
-        format!("TTS generation and synchronization failed: {}", e)
-    })?;
+    // In low-disk mode the dubbed audio goes straight from the synchronizer into ffmpeg
+    let streaming_merge = load_streaming_merge(&app_handle);
+    if streaming_merge {
+        info!("Streaming merge enabled, the TTS mix won't be written to disk");
+    }
 
-    // We need to determine source language code from transcription
-    let merge_result = merge_video(
-        download_result.0.clone(), // video_path
-        tts_result.audio_path.clone(), // Use the TTS result as the translated audio
-        download_result.1.clone(), // audio_path
-        transcription_result.vtt_path.clone(),
-        translation_result.translated_vtt_path.clone(),
-        output_path.clone(), // Use the user-selected output directory directly
-        source_language_code,
-        target_language.clone(),
-        source_language_name,
-        target_language_name.clone(),
-        window.clone(),
-    )
-    .await
-    .map_err(|e| {
-        error!("Merging failed: {}", e);
-        format!("Merging failed: {}", e)
-    })?;
+    // A TTS mix the earlier run wrote to disk is reused along with its timing report
+    let resumed_tts = match job.artifacts.tts_path.as_ref() {
+        Some(path) if resumed.contains(&PipelineStep::Tts) && !streaming_merge => timing_report::load(&app_handle, &job_id)
+            .await
+            .ok()
+            .map(|timing_report| TTSResult {
+                audio_path: path.to_string_lossy().to_string(),
+                timing_report,
+                optimizer_report: None,
+                retimed_vtt_path: existing_retimed_subtitles(&path.to_string_lossy()),
+            }),
+        _ => None,
+    };
+
+    let (tts_result, stream_rx) = if let Some(tts_result) = resumed_tts {
+        info!("Step 4: Skipped, reusing the speech of the earlier run");
+        (tts_result, None)
+    } else {
+        cancellation::check(cancel)?;
+        advance(&app_handle, &job_id, JobState::GeneratingSpeech);
+        info!("Step 4: Generating speech and synchronizing with video");
+        loop {
+            let (stream_tx, stream_rx) = if streaming_merge {
+                let (tx, rx) = oneshot::channel();
+                (Some(tx), Some(rx))
+            } else {
+                (None, None)
+            };
+
+            let inputs = SpeechInputs {
+                video_path: download_result.0.clone(),
+                audio_path: download_result.1.clone(),
+                original_vtt_path: transcription_result.vtt_path.clone(),
+                translated_vtt_path: translation_result.translated_vtt_path.clone(),
+                output_path: tts_output.to_string_lossy().to_string(),
+                api_key: api_key.clone(),
+                target_language: Some(target_language.clone()),
+                speech_to_speech: speech_to_speech.then(|| target_language_name.clone()),
+                fit_strategy: Some(fit_strategy),
+                voice: voice.clone(),
+                narration: narration::for_synchronizer(&app_handle, &library_id, &target_language).await,
+                stream_to: stream_tx,
+                job_id: Some(&job_id),
+                cancel: Some(cancel),
+                ..Default::default()
+            };
+            match synthesize_speech(inputs, window.clone()).await {
+                Ok(result) => break (result, stream_rx),
+                // Fragments generated before the quota ran out stay in debug_mp3_chunks and are reused
+                Err(e) if quota::is_quota_error(&e) => {
+                    let artifacts = vec![
+                        PathBuf::from(&download_result.0),
+                        PathBuf::from(&download_result.1),
+                        PathBuf::from(&transcription_result.vtt_path),
+                        PathBuf::from(&translation_result.translated_vtt_path),
+                        tts_dir.join("debug_mp3_chunks"),
+                    ];
+                    wait_for_quota(&app_handle, &job_id, &url, &target_language, QuotaStop { stage: JobState::GeneratingSpeech, error: &e, artifacts }, &mut api_key).await?;
+                }
+                Err(e) => {
+                    error!("TTS generation and synchronization failed: {}", e);
+                    return Err(format!("TTS generation and synchronization failed: {}", e));
+                }
+            }
+        }
+    };
+    progress_tracker.complete(&window, PipelineStep::Tts);
+
+    if let Err(e) = timing_report::save(&app_handle, &job_id, &tts_result.timing_report).await {
+        warn!("Failed to store timing report of job {}: {}", job_id, e);
+    }
+    // A reused mix keeps the report already in the checkpoint
+    if let Some(report) = &tts_result.optimizer_report {
+        job.optimizer_report = Some(report.clone());
+    }
+
+    // Nothing to resume from when the mix only lived in memory
+    if !streaming_merge {
+        job.artifacts.tts_path = Some(PathBuf::from(&tts_result.audio_path));
+        job.complete(PipelineStep::Tts);
+        pipeline_job::checkpoint(&app_handle, job).await;
+    }
+
+    // Further target languages share the media, the transcription and the separated
+    // background, each is translated and dubbed on its own. They aren't checkpointed,
+    // a run resumed before the merge dubs them again.
+    let mut language_tracks = Vec::new();
+    let merge_resumed = resumed.contains(&PipelineStep::Merge) && job.artifacts.merged_path.is_some();
+    for language in extra_languages.iter().filter(|_| !merge_resumed) {
+        cancellation::check(cancel)?;
+        info!("Dubbing into {} ({}) as well", language.name, language.code);
+        let translated_vtt_path = if speech_to_speech {
+            PathBuf::from(&output_path)
+                .join("videonova_temp")
+                .join(format!("{}_{}.vtt", sanitize_filename(&translation_result.base_filename), language.code))
+                .to_string_lossy()
+                .to_string()
+        } else {
+            loop {
+                let request = VttTranslation {
+                    vtt_path: transcription_result.vtt_path.clone(),
+                    encoding: None,
+                    output_path: output_path.clone(),
+                    target_language: language.name.clone(),
+                    target_language_code: language.code.clone(),
+                    api_key: api_key.clone(),
+                    cancel: Some(cancel),
+                    job_id: Some(&job_id),
+                };
+                match translate_vtt_file(request, window.clone()).await {
+                    Ok(result) => break result.translated_vtt_path,
+                    Err(e) if quota::is_quota_error(&e) => {
+                        let artifacts = vec![PathBuf::from(&download_result.0), PathBuf::from(&download_result.1)];
+                        // Extra languages are translated while the job is already generating speech
+                        wait_for_quota(&app_handle, &job_id, &url, &language.code, QuotaStop { stage: JobState::GeneratingSpeech, error: &e, artifacts }, &mut api_key).await?;
+                    }
+                    Err(e) => return Err(format!("Translation into {} failed: {}", language.name, e)),
+                }
+            }
+        };
+
+        // Fragments of each language stay apart, so a retry reuses only its own
+        let language_tts_output = tts_dir.join(&language.code).join(format!("{}_tts.wav", original_filename));
+        let instrumental = Some(tts_dir.join("debug_mp3_chunks").join("instrumental.wav")).filter(|path| path.exists());
+        let language_tts = loop {
+            let inputs = SpeechInputs {
+                video_path: download_result.0.clone(),
+                audio_path: download_result.1.clone(),
+                original_vtt_path: transcription_result.vtt_path.clone(),
+                translated_vtt_path: translated_vtt_path.clone(),
+                output_path: language_tts_output.to_string_lossy().to_string(),
+                api_key: api_key.clone(),
+                target_language: Some(language.code.clone()),
+                speech_to_speech: speech_to_speech.then(|| language.name.clone()),
+                fit_strategy: Some(fit_strategy),
+                voice: language.voice.clone().or_else(|| voice.clone()),
+                narration: narration::for_synchronizer(&app_handle, &library_id, &language.code).await,
+                instrumental: instrumental.clone(),
+                job_id: Some(&job_id),
+                cancel: Some(cancel),
+                ..Default::default()
+            };
+            match synthesize_speech(inputs, window.clone()).await {
+                Ok(result) => break result,
+                Err(e) if quota::is_quota_error(&e) => {
+                    let artifacts = vec![
+                        PathBuf::from(&download_result.0),
+                        PathBuf::from(&download_result.1),
+                        PathBuf::from(&translated_vtt_path),
+                        tts_dir.join(&language.code).join("debug_mp3_chunks"),
+                    ];
+                    wait_for_quota(&app_handle, &job_id, &url, &language.code, QuotaStop { stage: JobState::GeneratingSpeech, error: &e, artifacts }, &mut api_key).await?;
+                }
+                Err(e) => return Err(format!("Speech in {} failed: {}", language.name, e)),
+            }
+        };
+        language_tracks.push(merge::LanguageTrack {
+            code: language.code.clone(),
+            name: language.name.clone(),
+            audio_path: PathBuf::from(&language_tts.audio_path),
+            vtt_path: PathBuf::from(language_tts.retimed_vtt_path.unwrap_or(translated_vtt_path)),
+        });
+    }
+
+    // Soft subtitles and the exported file follow the speech when it pushed cues later
+    let mut subtitles_vtt_path = tts_result
+        .retimed_vtt_path
+        .clone()
+        .unwrap_or_else(|| translation_result.translated_vtt_path.clone());
+
+    // The dub is done, only what is shown on screen is made readable
+    let readability = load_subtitle_readability(&app_handle);
+    if readability.enabled {
+        subtitles_vtt_path = readable_subtitles(&readability, Path::new(&subtitles_vtt_path), &target_language)
+            .await
+            .to_string_lossy()
+            .to_string();
+        for track in language_tracks.iter_mut() {
+            track.vtt_path = readable_subtitles(&readability, &track.vtt_path, &track.code).await;
+        }
+    }
+
+    let translated_audio_stream = match stream_rx {
+        Some(rx) => Some(rx.await.map_err(|_| "TTS finished without handing over the audio".to_string())?),
+        None => None,
+    };
+
+    cancellation::check(cancel)?;
+    advance(&app_handle, &job_id, JobState::Merging);
+    let merge_result = match job.artifacts.merged_path.as_ref() {
+        Some(merged_path) if resumed.contains(&PipelineStep::Merge) => {
+            info!("Step 5: Skipped, reusing the merged video of the earlier run");
+            MergeResult { merged_video_path: merged_path.to_string_lossy().to_string(), output_dir: output_path.clone() }
+        }
+        _ => {
+            // Keep chapter navigation in the dubbed output, limited to the clip
+            let output_chapters = match selection {
+                Some((start, end)) => chapters::clip(&video_info.chapters, start, end),
+                None => video_info.chapters.clone(),
+            };
+            let chapters_path = prepare_chapters(
+                &app_handle,
+                &output_chapters,
+                Path::new(&download_result.0),
+                &output_path,
+                &target_language_name,
+                &api_key,
+            )
+            .await;
+
+            let sources = merge::MergeSources {
+                video_path: Path::new(&download_result.0),
+                translated_audio: match translated_audio_stream {
+                    Some(audio) => merge::TranslatedAudio::Stream(audio),
+                    None => merge::TranslatedAudio::File(Path::new(&tts_result.audio_path)),
+                },
+                original_audio_path: Path::new(&download_result.1),
+                original_vtt_path: Path::new(&transcription_result.vtt_path),
+                translated_vtt_path: Path::new(&subtitles_vtt_path),
+                chapters_path: chapters_path.as_deref(),
+            };
+
+            // A listening copy leaves the video out, only the dub and the chapters are kept
+            let merged = if merge_style.output_mode == OutputMode::AudioOnly {
+                export_dubbed_audio(
+                    sources,
+                    &output_path,
+                    &target_language,
+                    &video_info.title,
+                    merge_style.audio_format,
+                    cancel,
+                )
+                .await?
+            } else {
+                let merge = VideoMerge {
+                    sources,
+                    languages: merge::TrackLanguages {
+                        source_code: &source_language_code,
+                        target_code: &target_language,
+                        source_name: &source_language_name,
+                        target_name: &target_language_name,
+                    },
+                    // Use the user-selected output directory directly
+                    output_dir: output_path.clone(),
+                    merge_style,
+                    metadata: merge::OutputMetadata {
+                        title: Some(video_info.title.clone()),
+                        source_url: Some(video_info.url.clone()),
+                        thumbnail_url: Some(video_info.thumbnail.clone()),
+                    },
+                    cancel: Some(cancel),
+                    job_id: Some(&job_id),
+                };
+                merge_video(merge, window.clone()).await?
+            };
+            if language_tracks.is_empty() {
+                merged
+            } else {
+                // One file carries every language, named after all of them
+                let merged_path = PathBuf::from(&merged.merged_video_path);
+                let stem = merged_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                let codes: Vec<&str> = language_tracks.iter().map(|track| track.code.as_str()).collect();
+                let multi_path = merged_path.with_file_name(format!("{}-{}.mkv", stem, codes.join("-")));
+                merge::add_language_tracks(&merged_path, &language_tracks, &multi_path, cancel)
+                    .await
+                    .map_err(|e| format!("Merging failed: {}", e))?;
+                if let Err(e) = tokio::fs::remove_file(&merged_path).await {
+                    warn!("Failed to remove {}: {}", merged_path.display(), e);
+                }
+                MergeResult { merged_video_path: multi_path.to_string_lossy().to_string(), output_dir: merged.output_dir }
+            }
+        }
+    };
+    progress_tracker.complete(&window, PipelineStep::Merge);
+    job.artifacts.merged_path = Some(PathBuf::from(&merge_result.merged_video_path));
+    job.complete(PipelineStep::Merge);
+    pipeline_job::checkpoint(&app_handle, job).await;
 
     info!("=== Video Processing Pipeline Completed Successfully ===");
     info!("Final video saved to: {}", merge_result.merged_video_path);
     info!("Output directory: {}", merge_result.output_dir);
-    info!("TTS audio saved to: {}", tts_result.audio_path);
+    if !streaming_merge {
+        info!("TTS audio saved to: {}", tts_result.audio_path);
+    }
 
     // Emit merge-complete event before returning
-    window.emit("merge-complete", &merge_result)
+    emitter::emit(&window, "merge-complete", &merge_result)
         .map_err(|e| format!("Failed to emit merge-complete event: {}", e))?;
 
+    // Upload the result while the sidecar subtitles still exist in the temp dir
+    cancellation::check(cancel)?;
+    if scheduled_steps.contains(&PipelineStep::Upload) {
+        advance(&app_handle, &job_id, JobState::Uploading);
+    }
+    let mut uploaded_subtitles = vec![transcription_result.vtt_path.as_str(), translation_result.translated_vtt_path.as_str()];
+    uploaded_subtitles.extend(language_tracks.iter().filter_map(|track| track.vtt_path.to_str()));
+    upload_outputs_to_remote(&app_handle, &window, &job_id, &merge_result.merged_video_path, &uploaded_subtitles).await;
+    progress_tracker.complete(&window, PipelineStep::Upload);
+
+    // Cut the output into parts under the size cap, e.g. for FAT32 drives
+    let mut final_path = merge_result.merged_video_path.clone();
+    let mut output_parts = Vec::new();
+    match split::split_if_needed(Path::new(&merge_result.merged_video_path), &split::load(&app_handle)).await {
+        Ok(Some(split_output)) => {
+            final_path = split_output.playlist.to_string_lossy().to_string();
+            output_parts = split_output.parts.iter().map(|part| part.to_string_lossy().to_string()).collect();
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Failed to split the output, keeping it whole: {}", e),
+    }
+
+    // Save the translated subtitles next to the output before the temp dir is removed,
+    // retimed subtitles always are since they no longer match the source timings
+    let mut subtitle_path = None;
+    let export_format = load_subtitle_export(&app_handle)
+        .or_else(|| tts_result.retimed_vtt_path.as_ref().map(|_| SubtitleFormat::Vtt));
+    if let Some(format) = export_format {
+        match subtitles::export(
+            Path::new(&subtitles_vtt_path),
+            Path::new(&merge_result.merged_video_path),
+            format,
+            &load_speaker_labels(&app_handle),
+        ).await {
+            Ok(path) => subtitle_path = Some(path.to_string_lossy().to_string()),
+            Err(e) => warn!("Failed to export translated subtitles: {}", e),
+        }
+    }
+    if let Some(format) = load_karaoke_export(&app_handle)
+        && let Err(e) = karaoke::export(
+            Path::new(&subtitles_vtt_path),
+            &tts_result.timing_report,
+            Path::new(&merge_result.merged_video_path),
+            format,
+        ).await
+    {
+        warn!("Failed to export word-highlighted subtitles: {}", e);
+    }
+
+    // Keep the source media in the library before temp files are removed
+    let mut video_path = PathBuf::from(&download_result.0);
+    let mut original_audio_path = PathBuf::from(&download_result.1);
+    if !reused_from_library && !library_id.is_empty() {
+        match library::archive_media(
+            &app_handle,
+            &library_id,
+            Path::new(&download_result.0),
+            Path::new(&download_result.1),
+        ).await {
+            Ok(entry) => {
+                video_path = entry.video_path.unwrap_or(video_path);
+                original_audio_path = entry.audio_path.unwrap_or(original_audio_path);
+            }
+            Err(e) => warn!("Failed to archive source media in library: {}", e),
+        }
+    }
+
+    if !library_id.is_empty() {
+        if let Err(e) = library::record_output(
+            &app_handle,
+            &library_id,
+            &job_id,
+            &target_language,
+            &settings_hash,
+            Path::new(&final_path),
+        ).await {
+            warn!("Failed to record output in library: {}", e);
+        }
+        for (language, hash) in extra_languages.iter().zip(&extra_settings_hashes) {
+            if let Err(e) = library::record_output(&app_handle, &library_id, &job_id, &language.code, hash, Path::new(&final_path)).await {
+                warn!("Failed to record {} output in library: {}", language.code, e);
+            }
+        }
+    }
+
+    // Preserve what the segment inspector needs before the temp dir is gone
+    let tts_fragments_dir = tts_dir.join("debug_mp3_chunks");
+    let finished = jobs::FinishedJob {
+        job_id: &job_id,
+        url: &url,
+        target_language: &target_language,
+        original_audio_path: &original_audio_path,
+        original_vtt_path: Path::new(&transcription_result.vtt_path),
+        translated_vtt_path: Path::new(&translation_result.translated_vtt_path),
+        tts_fragments_dir: Some(&tts_fragments_dir),
+        final_path: Some(Path::new(&final_path)),
+        voice: voice.as_deref(),
+    };
+    if let Err(e) = jobs::record_job(&app_handle, finished).await {
+        warn!("Failed to store job record {}: {}", job_id, e);
+    }
+
+    // The log leaves the temp directory before it is removed
+    drop(job_log);
+
     // Clean up temporary files
     info!("Starting cleanup of temporary files");
     if let Err(e) = cleanup_temp_files(
@@ -905,62 +3571,461 @@ pub async fn process_video(
         warn!("Failed to cleanup temporary files: {}", e);
         // Don't return error here, as the main process was successful
     }
+    // Failed runs keep the stems regardless, so a retry doesn't separate again
+    if !load_keep_stems(&app_handle) {
+        let stems_dir = Path::new(&output_path).join(demucs::STEMS_DIR);
+        if stems_dir.is_dir()
+            && let Err(e) = tokio::fs::remove_dir_all(&stems_dir).await
+        {
+            warn!("Failed to remove separated stems {}: {}", stems_dir.display(), e);
+        }
+    }
 
+    // The media is reported where it was archived, the temp copies are gone
     Ok(ProcessVideoResult {
-        video_path: download_result.0, // video_path
-        audio_path: download_result.1, // audio_path
+        job_id,
+        video_path: video_path.to_string_lossy().to_string(),
+        audio_path: original_audio_path.to_string_lossy().to_string(),
         transcription_path: transcription_result.vtt_path,
         translation_path: translation_result.translated_vtt_path,
-        tts_path: tts_result.audio_path,
-        final_path: merge_result.merged_video_path.clone(),
+        // Nothing was written in streaming mode
+        tts_path: if streaming_merge { String::new() } else { tts_result.audio_path },
+        final_path,
         merged_path: merge_result.merged_video_path,
+        output_parts,
+        subtitle_path,
     })
 }
 
-/// Merge video with translated audio, original audio, and subtitles
-pub async fn merge_video(
-    video_path: String,
-    translated_audio_path: String,
-    original_audio_path: String,
-    original_vtt_path: String,
-    translated_vtt_path: String,
+/// Detect the source language on a sample of the audio, report it as
+/// `language-detected` and wait for the user to confirm or correct it. None
+/// leaves the detection to the transcription, e.g. when the sample was silent.
+async fn detect_source_language(
+    window: &tauri::Window,
+    job_id: &str,
+    audio_path: &str,
+    output_path: &str,
+    api_key: &str,
+    cancel: &CancellationToken,
+) -> Result<Option<(String, String)>, String> {
+    let detection = transcribe::detect_language(Path::new(audio_path), Path::new(output_path), api_key);
+    let detected = match cancellation::run(cancel, detection).await.map_err(|e| e.to_string())? {
+        Ok(detected) => detected,
+        Err(e) => {
+            warn!("Language detection failed, the transcription detects it on its own: {}", e);
+            return Ok(None);
+        }
+    };
+    let _ = emitter::emit(window, "language-detected", json!({
+        "job_id": job_id,
+        "code": detected.code,
+        "name": detected.name,
+        "confidence": detected.confidence,
+        "timeout_secs": source_language::CONFIRMATION_TIMEOUT.as_secs(),
+    }));
+    let confirmation = source_language::await_confirmation(job_id, &detected.code);
+    let code = cancellation::run(cancel, confirmation).await.map_err(|e| e.to_string())?;
+    if code == detected.code {
+        return Ok(Some((code, detected.name)));
+    }
+    info!("Source language corrected from {} to {}", detected.code, code);
+    let name = transcribe::language_name(&code).unwrap_or_else(|| code.clone());
+    Ok(Some((code, name)))
+}
+
+/// Confirm or correct the source language a job detected, see `language-detected`
+#[tauri::command]
+pub async fn confirm_source_language(job_id: String, language: String) -> Result<(), String> {
+    source_language::confirm(&job_id, &language).map_err(|e| e.to_string())
+}
+
+/// Start and end of a clip; open ends are the start and end of the video
+fn clip_range(start: Option<f64>, end: Option<f64>, duration: f64) -> Result<(f64, f64), String> {
+    let start = start.unwrap_or(0.0);
+    // The duration yt-dlp reports may be unknown (0)
+    let end = match end {
+        Some(end) if duration > 0.0 => end.min(duration),
+        Some(end) => end,
+        None => duration,
+    };
+    if end <= start {
+        return Err(format!("The clip {:.1}s-{:.1}s is outside the video ({:.1}s)", start, end, duration));
+    }
+    Ok((start, end))
+}
+
+/// Subtitles of the whole video cut down to a clip, timed from the clip's start
+async fn clip_transcription(vtt_path: &Path, start: f64, end: f64, output: &Path) -> Result<(), String> {
+    let content = tokio::fs::read_to_string(vtt_path).await.map_err(|e| e.to_string())?;
+    let cues = vtt::parse_vtt_str(&content).map_err(|e| format!("Failed to parse {}: {}", vtt_path.display(), e))?;
+    let clipped = vtt::clip_cues(&cues, start as f32, end as f32);
+    info!("Kept {} of {} cues inside the clip", clipped.len(), cues.len());
+    tokio::fs::write(output, vtt::write_vtt_str(&clipped)).await.map_err(|e| e.to_string())
+}
+
+/// Collect the source chapters, translate their titles unless disabled and write
+/// them as an FFMETADATA file into the temp dir. Returns None when the video has
+/// no chapters or they couldn't be prepared; the merge then keeps the source ones.
+async fn prepare_chapters(
+    app_handle: &tauri::AppHandle,
+    info_chapters: &[chapters::Chapter],
+    video_path: &Path,
+    output_path: &str,
+    target_language_name: &str,
+    api_key: &str,
+) -> Option<PathBuf> {
+    let source_chapters = if info_chapters.is_empty() {
+        match chapters::probe(video_path).await {
+            Ok(probed) => probed,
+            Err(e) => {
+                warn!("Failed to read chapters from {}: {}", video_path.display(), e);
+                Vec::new()
+            }
+        }
+    } else {
+        info_chapters.to_vec()
+    };
+    if source_chapters.is_empty() {
+        return None;
+    }
+
+    let translated = if chapters::translation_enabled(app_handle) {
+        chapters::translate_titles(&source_chapters, target_language_name, api_key).await
+    } else {
+        info!("Chapter translation disabled, keeping {} original titles", source_chapters.len());
+        source_chapters
+    };
+
+    let path = PathBuf::from(output_path).join("videonova_temp").join("chapters.txt");
+    match chapters::write_ffmetadata(&translated, &path).await {
+        Ok(()) => Some(path),
+        Err(e) => {
+            warn!("Failed to write chapters metadata: {}", e);
+            None
+        }
+    }
+}
+
+/// Get whether chapter titles are translated
+#[tauri::command]
+pub async fn get_chapter_translation(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    Ok(chapters::translation_enabled(&app_handle))
+}
+
+/// Enable or disable chapter title translation
+#[tauri::command]
+pub async fn set_chapter_translation(app_handle: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    chapters::set_translation_enabled(&app_handle, enabled).await.map_err(|e| e.to_string())
+}
+
+/// Upload finished outputs to the configured remote destination, if any.
+/// Failures are only logged: the local result is still valid.
+async fn upload_outputs_to_remote(
+    app_handle: &tauri::AppHandle,
+    window: &tauri::Window,
+    job_id: &str,
+    video_path: &str,
+    subtitle_paths: &[&str],
+) {
+    let config = match remote::load_config(app_handle) {
+        Ok(Some(config)) => config,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to load remote destination settings: {}", e);
+            return;
+        }
+    };
+
+    let mut files = vec![video_path];
+    if config.include_subtitles {
+        files.extend_from_slice(subtitle_paths);
+    }
+
+    for file in files {
+        let (tx, mut rx) = mpsc::channel::<remote::RemoteUploadProgress>(32);
+        let progress_window = window.clone();
+        let job_id = job_id.to_string();
+        let monitoring_task = tokio::spawn(async move {
+            while let Some(progress) = rx.recv().await {
+                if let Err(e) = emitter::emit_for_job(&progress_window, "remote-upload-progress", Some(&job_id), progress) {
+                    error!("Failed to emit remote upload progress: {}", e);
+                }
+            }
+        });
+
+        if let Err(e) = remote::upload_file(&config.destination, Path::new(file), Some(tx)).await {
+            error!("Failed to upload {} to remote destination: {}", file, e);
+            let _ = window.emit("remote-upload-error", e.to_string());
+        }
+        let _ = monitoring_task.await;
+    }
+}
+
+/// Get the configured remote output destination
+#[tauri::command]
+pub async fn get_remote_destination(app_handle: tauri::AppHandle) -> Result<Option<remote::RemoteConfig>, String> {
+    remote::load_config(&app_handle).map_err(|e| e.to_string())
+}
+
+/// Set or clear the remote output destination
+#[tauri::command]
+pub async fn set_remote_destination(
+    app_handle: tauri::AppHandle,
+    config: Option<remote::RemoteConfig>,
+) -> Result<(), String> {
+    remote::save_config(&app_handle, config.as_ref()).await.map_err(|e| e.to_string())
+}
+
+const PROCESSING_SERVER_KEY: &str = "processing-server";
+
+fn load_processing_server(app_handle: &tauri::AppHandle) -> Result<ProcessingServer, String> {
+    settings::get::<Option<ProcessingServer>>(app_handle, PROCESSING_SERVER_KEY)
+        .flatten()
+        .ok_or_else(|| "No processing server is configured".to_string())
+}
+
+/// Get the headless machine jobs can be sent to
+#[tauri::command]
+pub async fn get_processing_server(app_handle: tauri::AppHandle) -> Result<Option<ProcessingServer>, String> {
+    Ok(load_processing_server(&app_handle).ok())
+}
+
+/// Set or clear the headless machine jobs can be sent to
+#[tauri::command]
+pub async fn set_processing_server(app_handle: tauri::AppHandle, server: Option<ProcessingServer>) -> Result<(), String> {
+    if let Some(server) = &server {
+        server.validate().map_err(|e| e.to_string())?;
+    }
+    settings::set(&app_handle, PROCESSING_SERVER_KEY, &server).await.map_err(|e| e.to_string())
+}
+
+/// Queue a video on the processing server
+#[tauri::command]
+pub async fn submit_server_job(
+    app_handle: tauri::AppHandle,
+    request: PipelineRequest,
+    api_key: Option<String>,
+) -> Result<RemoteJob, String> {
+    let server = load_processing_server(&app_handle)?;
+    server.submit(&SubmitJob { request, api_key }).await.map_err(|e| e.to_string())
+}
+
+/// Jobs on the processing server
+#[tauri::command]
+pub async fn list_server_jobs(app_handle: tauri::AppHandle) -> Result<Vec<RemoteJob>, String> {
+    let server = load_processing_server(&app_handle)?;
+    server.jobs().await.map_err(|e| e.to_string())
+}
+
+/// Relay the progress of a server job as `server-job-event` until it finished
+#[tauri::command]
+pub async fn watch_server_job(window: tauri::Window, job_id: String) -> Result<RemoteJob, String> {
+    let server = load_processing_server(window.app_handle())?;
+    server
+        .watch(&job_id, |event| {
+            if let Err(e) = emitter::emit(&window, "server-job-event", json!({ "job_id": job_id, "event": event })) {
+                warn!("Failed to emit server-job-event: {}", e);
+            }
+        })
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Download a file a server job produced
+#[tauri::command]
+pub async fn download_server_artifact(
+    app_handle: tauri::AppHandle,
+    job_id: String,
+    name: String,
+    destination: String,
+) -> Result<String, String> {
+    let server = load_processing_server(&app_handle)?;
+    let path = server
+        .download_artifact(&job_id, &name, Path::new(&destination))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Timestamps, texts and audio paths of a single cue of a finished job
+#[tauri::command]
+pub async fn get_cue_context(
+    app_handle: tauri::AppHandle,
+    job_id: String,
+    cue_index: usize,
+) -> Result<jobs::CueContext, String> {
+    jobs::cue_context(&app_handle, &job_id, cue_index)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Get the per-step weights used to compute overall progress
+#[tauri::command]
+pub async fn get_progress_weights(app_handle: tauri::AppHandle) -> Result<progress::StepWeights, String> {
+    Ok(progress::load_weights(&app_handle))
+}
+
+/// Set the per-step weights used to compute overall progress
+#[tauri::command]
+pub async fn set_progress_weights(app_handle: tauri::AppHandle, weights: progress::StepWeights) -> Result<(), String> {
+    progress::save_weights(&app_handle, &weights).await.map_err(|e| e.to_string())
+}
+
+/// Buffered events of a job since the given sequence number, used by the
+/// frontend to rebuild its progress UI after a reload
+#[tauri::command]
+pub async fn replay_events(job_id: String, since: Option<u64>) -> Result<Vec<events::BufferedEvent>, String> {
+    events::replay(&job_id, since.unwrap_or(0))
+        .ok_or_else(|| format!("No buffered events for job {}", job_id))
+}
+
+/// Log of a job as JSON lines, to attach to a bug report
+#[tauri::command]
+pub async fn get_job_log(app_handle: tauri::AppHandle, job_id: String) -> Result<String, String> {
+    let archive_dir = jobs::job_dir(&app_handle, &job_id).map_err(|e| e.to_string())?;
+    logger::read_job_log(&job_id, &archive_dir).map_err(|e| e.to_string())
+}
+
+/// Speak one cue of a finished job again, optionally with edited text or
+/// another voice, and patch it into the dubbed track of the merged video
+#[tauri::command]
+pub async fn regenerate_segment(
+    app_handle: tauri::AppHandle,
+    job_id: String,
+    cue_index: usize,
+    edited_text: Option<String>,
+    voice_override: Option<String>,
+    api_key: String,
+) -> Result<regenerate::RegeneratedSegment, String> {
+    let engine = load_tts_engine(&app_handle);
+    if let Some(voice) = &voice_override {
+        validate_engine_voice(&engine, voice)?;
+    }
+    let edit = regenerate::SegmentEdit { cue_index, edited_text, voice_override };
+    let lexicon = load_pronunciation_lexicon(&app_handle);
+    let normalization = load_text_normalization(&app_handle);
+    let speech = regenerate::SpeechSettings {
+        api_key: &api_key,
+        engine: &engine,
+        stretch: load_time_stretch(&app_handle),
+        lexicon: &lexicon,
+        normalization: &normalization,
+    };
+    regenerate::regenerate_segment(&app_handle, &job_id, edit, speech)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Path, version and origin (managed download or PATH) of yt-dlp, ffmpeg and ffprobe
+#[tauri::command]
+pub async fn get_tools_status() -> Result<Vec<crate::utils::tools::ToolStatus>, String> {
+    Ok(crate::utils::tools::tools_status())
+}
+
+/// Ids of jobs that are still running in the backend
+#[tauri::command]
+pub async fn get_running_jobs() -> Result<Vec<String>, String> {
+    Ok(events::running_jobs())
+}
+
+/// List subtitles the video already has: creator uploads, automatic captions
+/// and files linked in the description
+#[tauri::command]
+pub async fn list_available_subtitles(window: tauri::Window, url: String) -> Result<Vec<SubtitleSource>, String> {
+    let info = youtube::get_video_info(&url, &window)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(info.subtitles)
+}
+
+/// Download a chosen subtitle source and use it in place of the Whisper transcription
+#[tauri::command]
+pub async fn use_subtitle_source(
+    source: SubtitleSource,
+    audio_path: String,
+    output_path: String,
+) -> Result<TranscriptionResult, String> {
+    // Same file name the transcription would get, so later steps name their outputs alike
+    let base_name = Path::new(&audio_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "subtitles".to_string());
+    let vtt_path = subtitles::download_subtitle(&source, Path::new(&output_path), &base_name)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(TranscriptionResult {
+        vtt_path: vtt_path.to_string_lossy().to_string(),
+    })
+}
+
+/// Get the notification channels of the current user
+#[tauri::command]
+pub async fn get_notification_settings(app_handle: tauri::AppHandle) -> Result<notify::NotificationSettings, String> {
+    notify::load_settings(&app_handle).map_err(|e| e.to_string())
+}
+
+/// Save the notification channels of the current user
+#[tauri::command]
+pub async fn set_notification_settings(
+    app_handle: tauri::AppHandle,
+    settings: notify::NotificationSettings,
+) -> Result<(), String> {
+    notify::save_settings(&app_handle, &settings).await.map_err(|e| e.to_string())
+}
+
+/// Send a test notification through a single channel
+#[tauri::command]
+pub async fn test_notification_channel(channel: notify::NotificationChannel) -> Result<(), String> {
+    let summary = notify::JobSummary {
+        status: notify::JobStatus::Completed,
+        url: "https://www.youtube.com/watch?v=test".to_string(),
+        target_language: "test".to_string(),
+        output_path: None,
+        error: None,
+        duration_secs: 0,
+    };
+    channel.send(&summary).await.map_err(|e| e.to_string())
+}
+
+/// Everything [`merge_video`] puts into the output
+struct VideoMerge<'a> {
+    sources: merge::MergeSources<'a>,
+    languages: merge::TrackLanguages<'a>,
+    /// The output is saved here, named after the video and the target language
     output_dir: String,
-    source_language_code: String,
-    target_language_code: String,
-    source_language_name: String,
-    target_language_name: String,
-    window: tauri::Window,
-) -> Result<MergeResult, String> {
+    merge_style: MergeStyle,
+    metadata: merge::OutputMetadata,
+    cancel: Option<&'a CancellationToken>,
+    job_id: Option<&'a str>,
+}
+
+/// Merge video with translated audio, original audio, and subtitles
+async fn merge_video(merge: VideoMerge<'_>, window: tauri::Window) -> Result<MergeResult, String> {
+    let VideoMerge { sources, languages, output_dir, merge_style, metadata, cancel, job_id } = merge;
     info!("Starting video merging process");
     
     let (progress_tx, mut progress_rx) = mpsc::channel::<MergeProgress>(32);
     
     // Clone window for progress updates
     let window_clone = window.clone();
+    let job_id = job_id.map(str::to_string);
     
     // Spawn a task to forward progress updates to the frontend
     tokio::spawn(async move {
         while let Some(progress) = progress_rx.recv().await {
-            let _ = window_clone.emit("merge-progress", json!({
-                "status": progress.status,
-                "progress": progress.progress,
-                // Add additional fields to ensure compatibility with UI
-                "step": "Video Merging",
-                "step_progress": progress.progress,
-                "total_progress": progress.progress
-            }));
+            let _ = emitter::emit_for_job(&window_clone, "merge-progress", job_id.as_deref(), progress);
         }
     });
 
     // Get original video filename without extension
-    let video_filename = Path::new(&video_path)
+    let video_filename = sources.video_path
         .file_stem()
         .and_then(|s| s.to_str())
         .unwrap_or("video");
 
     // Create final output path with language code suffix in user's selected directory
     let final_output_path = PathBuf::from(&output_dir)
-        .join(format!("{}_{}.mp4", video_filename, target_language_code));
+        .join(format!("{}_{}.{}", video_filename, languages.target_code, merge_style.container.extension()));
 
     // Create output directory if it doesn't exist
     tokio::fs::create_dir_all(&output_dir)
@@ -969,25 +4034,14 @@ pub async fn merge_video(
     
     info!("Final output will be: {}", final_output_path.display());
     
-    // Convert paths to Path objects
-    let video_path = Path::new(&video_path);
-    let translated_audio_path = Path::new(&translated_audio_path);
-    let original_audio_path = Path::new(&original_audio_path); 
-    let original_vtt_path = Path::new(&original_vtt_path);
-    let translated_vtt_path = Path::new(&translated_vtt_path);
-    
     // Call the merge_files function with the final output path
     let result = merge::merge_files(
-        video_path,
-        translated_audio_path,
-        original_audio_path,
-        original_vtt_path,
-        translated_vtt_path,
+        sources,
         &final_output_path,
-        &source_language_code,
-        &target_language_code,
-        &source_language_name,
-        &target_language_name,
+        languages,
+        &merge_style,
+        &metadata,
+        cancel,
         Some(progress_tx),
     )
     .await
@@ -1005,44 +4059,29 @@ pub async fn merge_video(
     })
 }
 
-async fn process_steps(
-    steps: Vec<Step>,
-    output_path: PathBuf,
-    window: tauri::Window,
-) -> Result<(), Box<dyn std::error::Error>> {
-    for step in steps {
-        match step {
-            Step::Download { url } => {
-                info!("Step 1: Downloading video");
-                let download_result = match download_video(window.clone(), url.clone(), output_path.to_string_lossy().to_string()).await {
-                    Ok(result) => {
-                        info!("Download completed successfully");
-                        result
-                    }
-                    Err(e) => {
-                        error!("Download failed: {}", e);
-                        return Err(Box::new(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("Download failed: {}", e),
-                        )));
-                    }
-                };
-            }
-            Step::Transcribe => {
-                // TODO: Implement transcribe step
-            }
-            Step::Translate => {
-                // TODO: Implement translate step
-            }
-            Step::GenerateSpeech => {
-                // TODO: Implement speech generation step
-            }
-            Step::Merge => {
-                // TODO: Implement merge step
-            }
-        }
-    }
-    Ok(())
+/// Save only the dubbed audio with the chapters, named like the merged video
+/// would be. Only the video path, the dub and the chapters of `sources` are used.
+async fn export_dubbed_audio(
+    sources: merge::MergeSources<'_>,
+    output_dir: &str,
+    target_language_code: &str,
+    title: &str,
+    format: AudioFormat,
+    cancel: &CancellationToken,
+) -> Result<MergeResult, String> {
+    let video_filename = sources.video_path.file_stem().and_then(|s| s.to_str()).unwrap_or("video");
+    let output = PathBuf::from(output_dir).join(format!("{}_{}.{}", video_filename, target_language_code, format.extension()));
+    tokio::fs::create_dir_all(output_dir)
+        .await
+        .map_err(|e| format!("Failed to create output directory: {}", e))?;
+    let result = merge::export_audio(sources.translated_audio, &output, format, target_language_code, title, sources.chapters_path, cancel)
+        .await
+        .map_err(|e| {
+            error!("Exporting the audio failed: {}", e);
+            format!("Exporting the audio failed: {}", e)
+        })?;
+    info!("Audio-only output saved to: {}", result.display());
+    Ok(MergeResult { merged_video_path: result.to_string_lossy().to_string(), output_dir: output_dir.to_string() })
 }
 
 #[tauri::command]
@@ -1055,13 +4094,6 @@ pub async fn cleanup_temp_files(final_video_path: String, output_dir: String) ->
         return Err(format!("Output directory does not exist or is not a directory: {}", output_dir));
     }
 
-    // Get the filename from the final video path
-    let final_video_name = std::path::Path::new(&final_video_path)
-        .file_name()
-        .ok_or("Failed to get video filename")?
-        .to_str()
-        .ok_or("Invalid video filename")?;
-
     // Get the base filename (without extension and language suffix) from the final video
     let base_filename = std::path::Path::new(&final_video_path)
         .file_stem()
@@ -1138,11 +4170,11 @@ pub async fn check_youtube_availability() -> Result<bool, String> {
                     
                     // Если нет явных признаков блокировки, но статус не успешный, предполагаем проблемы с доступом
                     info!("YouTube returned unsuccessful status but no explicit block indicators");
-                    return Ok(false);
+                    Ok(false)
                 },
                 Err(e) => {
                     warn!("Failed to read YouTube response body: {}", e);
-                    return Ok(false);
+                    Ok(false)
                 }
             }
         },
@@ -1154,12 +4186,12 @@ pub async fn check_youtube_availability() -> Result<bool, String> {
                 // Проблемы с соединением часто указывают на блокировку
                 warn!("Connection problems suggest YouTube might be blocked");
             }
-            return Ok(false);
+            Ok(false)
         },
         Err(_) => {
             warn!("YouTube request timed out");
             // Тайм-аут может указывать на блокировку
-            return Ok(false);
+            Ok(false)
         }
     }
 }
@@ -1173,8 +4205,8 @@ pub async fn check_youtube_availability() -> Result<bool, String> {
 pub async fn check_openai_availability() -> Result<bool, String> {
     info!("Checking OpenAI availability...");
     
-    // Создаем HTTP-клиент с увеличенным таймаутом
-    let client = reqwest::Client::builder()
+    // Создаем HTTP-клиент с увеличенным таймаутом, через прокси из настроек подключения
+    let client = openai_connection::client_builder()
         .timeout(std::time::Duration::from_secs(5))
         .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
         .build()
@@ -1232,13 +4264,13 @@ pub async fn check_openai_availability() -> Result<bool, String> {
         }
     }
 
-    // Проверяем дополнительно основной API эндпоинт OpenAI
-    let api_endpoint = "https://api.openai.com/v1";
+    // Проверяем дополнительно API эндпоинт OpenAI или шлюз из настроек подключения
+    let api_endpoint = openai_connection::endpoint("");
     info!("Checking additional OpenAI API endpoint: {}", api_endpoint);
     
     match tokio::time::timeout(
         std::time::Duration::from_secs(5),
-        client.get(api_endpoint).send()
+        client.get(&api_endpoint).send()
     ).await {
         Ok(Ok(response)) => {
             let status = response.status();
@@ -1274,7 +4306,7 @@ pub async fn check_openai_availability() -> Result<bool, String> {
                     // Если получен другой статус но без явных признаков блокировки,
                     // то считаем услугу доступной (возможно, просто требуется авторизация)
                     info!("OpenAI API returned non-success status but without block indicators");
-                    return Ok(true);
+                    Ok(true)
                 },
                 Err(e) => {
                     warn!("Failed to read OpenAI API response body: {}", e);
@@ -1284,7 +4316,7 @@ pub async fn check_openai_availability() -> Result<bool, String> {
                         return Ok(false);
                     }
                     // В случае других ошибок при чтении, предполагаем, что сервис может быть доступен
-                    return Ok(true);
+                    Ok(true)
                 }
             }
         },
@@ -1299,12 +4331,12 @@ pub async fn check_openai_availability() -> Result<bool, String> {
             }
             
             // Другие типы ошибок могут быть связаны с временными проблемами, не обязательно блокировкой
-            return Ok(false);
+            Ok(false)
         },
         Err(_) => {
             warn!("OpenAI API request timed out");
             // Тайм-аут может указывать на блокировку
-            return Ok(false);
+            Ok(false)
         }
     }
 }
@@ -1408,9 +4440,7 @@ pub async fn check_services_availability(window: tauri::WebviewWindow, is_retry:
                 blocked_services.join(", ")
             )
         } else {
-            format!(
-                "Для корректной работы приложения требуется VPN"
-            )
+            "Для корректной работы приложения требуется VPN".to_string()
         }
     } else {
         if is_retry {
@@ -1445,3 +4475,123 @@ pub async fn open_file(app: tauri::AppHandle, path: String) -> Result<(), String
         .open_path(&path, None::<&str>)
         .map_err(|e| e.to_string())
 }
+
+/// Start connecting a YouTube account, returns the code the user has to enter
+#[tauri::command]
+pub async fn youtube_start_auth(client_id: String) -> Result<publish::DeviceAuthorization, String> {
+    publish::start_device_authorization(&client_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Wait until the user confirms access and store the YouTube token
+#[tauri::command]
+pub async fn youtube_complete_auth(
+    app_handle: tauri::AppHandle,
+    client_id: String,
+    client_secret: String,
+    authorization: publish::DeviceAuthorization,
+) -> Result<(), String> {
+    let token = publish::wait_for_device_token(&client_id, &client_secret, &authorization)
+        .await
+        .map_err(|e| e.to_string())?;
+    publish::save_token(&app_handle, &token).await.map_err(|e| e.to_string())
+}
+
+/// Subtitle track to attach to the published video
+#[derive(Debug, serde::Deserialize)]
+pub struct PublishSubtitle {
+    path: String,
+    language: String,
+    name: String,
+}
+
+/// Upload the dubbed video to YouTube and attach translated subtitles
+#[tauri::command]
+pub async fn publish_to_youtube(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    client_id: String,
+    client_secret: String,
+    video_path: String,
+    metadata: publish::VideoMetadata,
+    subtitles: Option<Vec<PublishSubtitle>>,
+) -> Result<String, String> {
+    let access_token = publish::access_token(&app_handle, &client_id, &client_secret)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let (tx, mut rx) = mpsc::channel::<publish::UploadProgress>(32);
+    let progress_window = window.clone();
+    let monitoring_task = tokio::spawn(async move {
+        while let Some(progress) = rx.recv().await {
+            if let Err(e) = emitter::emit(&progress_window, "upload-progress", progress) {
+                error!("Failed to emit upload progress: {}", e);
+            }
+        }
+    });
+
+    let video_id = publish::upload_video(&access_token, Path::new(&video_path), &metadata, Some(tx))
+        .await
+        .map_err(|e| e.to_string())?;
+    let _ = monitoring_task.await;
+
+    // Ошибка загрузки субтитров не должна отменять уже опубликованное видео
+    attach_subtitles(&access_token, &video_id, subtitles.unwrap_or_default()).await;
+
+    Ok(video_id)
+}
+
+/// Attach translated subtitles to an already published YouTube video.
+/// The API doesn't allow adding audio tracks to existing videos, so only
+/// caption tracks can be attached this way.
+#[tauri::command]
+pub async fn attach_youtube_subtitles(
+    app_handle: tauri::AppHandle,
+    client_id: String,
+    client_secret: String,
+    video_id: String,
+    subtitles: Vec<PublishSubtitle>,
+) -> Result<usize, String> {
+    let access_token = publish::access_token(&app_handle, &client_id, &client_secret)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(attach_subtitles(&access_token, &video_id, subtitles).await)
+}
+
+/// Translate the source video title and description for the published video
+#[tauri::command]
+pub async fn translate_video_metadata(
+    title: String,
+    description: String,
+    target_language: String,
+    api_key: String,
+) -> Result<(String, String), String> {
+    let translated_title = translate::translate_text(&title, &target_language, &api_key)
+        .await
+        .map_err(|e| e.to_string())?;
+    let translated_description = translate::translate_text(&description, &target_language, &api_key)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok((translated_title, translated_description))
+}
+
+/// Upload caption tracks one by one, returns how many were attached
+async fn attach_subtitles(access_token: &str, video_id: &str, subtitles: Vec<PublishSubtitle>) -> usize {
+    let mut attached = 0;
+    for subtitle in subtitles {
+        match publish::upload_captions(
+            access_token,
+            video_id,
+            Path::new(&subtitle.path),
+            &subtitle.language,
+            &subtitle.name,
+        )
+        .await
+        {
+            Ok(_) => attached += 1,
+            Err(e) => warn!("Failed to attach {} subtitles to video {}: {}", subtitle.language, video_id, e),
+        }
+    }
+    attached
+}
@@ -0,0 +1,262 @@
+use log::error;
+use tauri::menu::{MenuBuilder, SubmenuBuilder};
+use tauri::{Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+mod commands;
+pub mod utils;
+
+/// Run the desktop app
+pub fn run() {
+    // Инициализируем логгер с тонкой настройкой
+    utils::logger::init_logger();
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_store::Builder::default().build())
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
+        .setup(|app| {
+            // Create app submenu
+            let app_menu = SubmenuBuilder::new(app, "App")
+                .text("about", "About Videonova")
+                .separator()
+                .text("settings", "Settings")
+                .separator()
+                .quit()
+                .build()?;
+
+            let edit_menu = SubmenuBuilder::new(app, "Edit")
+                .cut()
+                .copy()
+                .paste()
+                .select_all()
+                .build()?;
+            // Create main menu
+            let menu = MenuBuilder::new(app).items(&[&app_menu, &edit_menu]).build()?;
+
+            app.set_menu(menu)?;
+
+            // Initialize store
+            let _store = app.store(".settings.dat")?;
+            app.manage(utils::queue::JobQueue::default());
+            utils::emitter::init(app.handle());
+            utils::advanced_config::watch(app.handle());
+            utils::openai_connection::init(app.handle());
+            utils::http_retry::init(app.handle());
+            utils::tts::tts::demucs::apply(commands::load_demucs_config(app.handle()));
+            utils::piper::register(app.handle());
+            utils::fish_speech::register(app.handle());
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move { utils::quota::restore(&handle).await });
+
+            // Initialize tools in background
+            match app.path().app_data_dir() {
+                Ok(data_dir) => utils::tools::set_tools_dir(data_dir.join("tools")),
+                Err(e) => error!("Failed to resolve app data directory, tools go to the temp dir: {}", e),
+            }
+            tauri::async_runtime::spawn(async {
+                if let Err(e) = utils::tools::init_tools(None).await {
+                    error!("Failed to initialize tools: {}", e);
+                }
+            });
+            
+            // Проверка доступности сервисов при запуске приложения
+            if let Some(main_window) = app.get_webview_window("main") {
+                // Клонируем окно для использования в асинхронном контексте
+                let window_clone = main_window.clone();
+                
+                tauri::async_runtime::spawn(async move {
+                    // Небольшая задержка перед проверкой, чтобы приложение успело загрузиться
+                    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                    
+                    // Проверяем доступность сервисов
+                    match commands::check_services_availability(window_clone, None).await {
+                        Ok(result) => {
+                            if result.vpn_required {
+                                log::warn!("VPN required: YouTube available: {}, OpenAI available: {}", 
+                                          result.youtube_available, 
+                                          result.openai_available);
+                            } else {
+                                log::info!("All services are available");
+                            }
+                        },
+                        Err(e) => {
+                            log::error!("Failed to check services availability: {}", e);
+                        }
+                    }
+                });
+            } else {
+                log::error!("Main window not found");
+            }
+
+            Ok(())
+        })
+        .on_menu_event(|app_handle, event| {
+            let window = app_handle.get_webview_window("main").unwrap();
+            if event.id().0.as_str() == "settings" {
+                // Emit event to show settings
+                window.emit("show-settings", ()).unwrap();
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            commands::get_video_info,
+            commands::download_video,
+            commands::validate_openai_key,
+            commands::transcribe_audio,
+            commands::translate_vtt,
+            commands::generate_speech,
+            commands::process_video,
+            commands::check_file_exists_command,
+            commands::cleanup_temp_files,
+            commands::open_file,
+            commands::check_services_availability,
+            commands::check_youtube_availability,
+            commands::check_openai_availability,
+            commands::youtube_start_auth,
+            commands::youtube_complete_auth,
+            commands::publish_to_youtube,
+            commands::attach_youtube_subtitles,
+            commands::translate_video_metadata,
+            commands::get_remote_destination,
+            commands::set_remote_destination,
+            commands::get_notification_settings,
+            commands::set_notification_settings,
+            commands::test_notification_channel,
+            commands::get_cue_context,
+            commands::get_progress_weights,
+            commands::set_progress_weights,
+            commands::get_language_speed_profiles,
+            commands::set_language_speed_overrides,
+            commands::replay_events,
+            commands::get_running_jobs,
+            commands::get_job_log,
+            commands::get_tools_status,
+            commands::regenerate_segment,
+            commands::list_available_subtitles,
+            commands::use_subtitle_source,
+            commands::get_overlap_policy,
+            commands::set_overlap_policy,
+            commands::get_chapter_translation,
+            commands::set_chapter_translation,
+            commands::get_review_translation,
+            commands::set_review_translation,
+            commands::get_streaming_merge,
+            commands::set_streaming_merge,
+            commands::get_keep_stems,
+            commands::set_keep_stems,
+            commands::detect_compute_backends,
+            commands::get_demucs_config,
+            commands::set_demucs_config,
+            commands::get_job_state,
+            commands::get_performance_stats,
+            commands::get_performance_stats_enabled,
+            commands::set_performance_stats_enabled,
+            commands::clear_performance_stats,
+            commands::get_merge_style,
+            commands::set_merge_style,
+            commands::get_subtitle_export,
+            commands::set_subtitle_export,
+            commands::get_karaoke_export,
+            commands::set_karaoke_export,
+            commands::get_subtitle_readability,
+            commands::set_subtitle_readability,
+            commands::get_speaker_labels,
+            commands::set_speaker_labels,
+            commands::get_speaker_voices,
+            commands::set_speaker_voices,
+            commands::get_diarization,
+            commands::set_diarization,
+            commands::resume_job,
+            commands::confirm_source_language,
+            commands::abandon_paused_job,
+            commands::get_paused_jobs,
+            commands::export_translation_for_review,
+            commands::apply_edited_translation,
+            commands::get_time_stretch,
+            commands::set_time_stretch,
+            commands::get_loudness_target,
+            commands::set_loudness_target,
+            commands::get_lip_sync,
+            commands::set_lip_sync,
+            commands::get_sentence_merging,
+            commands::set_sentence_merging,
+            commands::get_voice_activity,
+            commands::set_voice_activity,
+            commands::import_youtube_cookies,
+            commands::get_timing_report,
+            commands::get_mix_report,
+            commands::get_optimizer_report,
+            commands::translate_subtitle_file,
+            commands::get_speech_to_speech,
+            commands::set_speech_to_speech,
+            commands::find_duplicate_job,
+            commands::get_fit_strategy,
+            commands::set_fit_strategy,
+            commands::import_narration,
+            commands::list_narrations,
+            commands::remove_narration,
+            commands::get_transcription_conditioning,
+            commands::set_transcription_conditioning,
+            commands::get_transcript_cleanup,
+            commands::set_transcript_cleanup,
+            commands::get_available_engines,
+            commands::get_engine_capabilities,
+            commands::get_voice_favorites,
+            commands::set_voice_favorite,
+            commands::record_voice_use,
+            commands::list_profiles,
+            commands::save_profile,
+            commands::delete_profile,
+            commands::set_active_profile,
+            commands::get_event_rate_limit,
+            commands::set_event_rate_limit,
+            commands::get_advanced_config,
+            commands::get_channel_templates,
+            commands::save_channel_template,
+            commands::delete_channel_template,
+            commands::process_latest_from_channel,
+            commands::diagnose_failure,
+            commands::get_translation_scrubbing,
+            commands::set_translation_scrubbing,
+            commands::get_translation_glossary,
+            commands::set_translation_glossary,
+            commands::get_pronunciation_lexicon,
+            commands::set_pronunciation_lexicon,
+            commands::get_text_normalization,
+            commands::set_text_normalization,
+            commands::get_openai_connection,
+            commands::set_openai_connection,
+            commands::get_processing_server,
+            commands::set_processing_server,
+            commands::submit_server_job,
+            commands::list_server_jobs,
+            commands::watch_server_job,
+            commands::download_server_artifact,
+            commands::get_transcript_confidence,
+            commands::get_tts_engine,
+            commands::set_tts_engine,
+            commands::list_piper_voices,
+            commands::download_piper_voice,
+            commands::clone_voice_from_video,
+            commands::get_output_split,
+            commands::set_output_split,
+            commands::resume_video_processing,
+            commands::get_unfinished_jobs,
+            commands::enqueue_job,
+            commands::cancel_job,
+            commands::cancel_processing,
+            commands::list_jobs,
+            commands::reorder_jobs,
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            // Persist settings changes that are still waiting for the debounced save
+            if let tauri::RunEvent::Exit = event
+                && let Err(e) = tauri::async_runtime::block_on(utils::settings::flush(app_handle))
+            {
+                error!("Failed to save settings on exit: {}", e);
+            }
+        });
+}
@@ -0,0 +1,247 @@
+//! Advanced settings from an optional `videonova.toml`.
+//!
+//! Settings that don't deserve a place in the GUI (timeouts, TTS concurrency,
+//! retries of OpenAI requests, extra ffmpeg flags, experimental switches) can be put into `videonova.toml`
+//! in the app config directory. The file is polled and reloaded when it
+//! changes. A file that doesn't parse or validate keeps the last good
+//! configuration in effect and is reported as an `advanced-config-error`
+//! event, so the frontend can tell the user instead of quietly running with
+//! defaults. Deleting the file restores the defaults.
+//!
+//! ```toml
+//! [timeouts]
+//! merge_secs = 1200
+//!
+//! [concurrency]
+//! tts_requests = 8
+//! tts_requests_per_minute = 50
+//!
+//! [retry]
+//! max_retries = 8
+//!
+//! [ffmpeg]
+//! merge_args = ["-threads", "4"]
+//!
+//! [experimental]
+//! some_feature = true
+//! ```
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+use tauri::Manager;
+
+use crate::utils::emitter;
+use crate::utils::http_retry::HttpRetryPolicy;
+
+const FILE_NAME: &str = "videonova.toml";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    /// Downloading the video and audio of a source
+    pub download_secs: u64,
+    /// The ffmpeg run of the merge step
+    pub merge_secs: u64,
+    /// A single OpenAI chat or speech request
+    pub openai_request_secs: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self { download_secs: 3600, merge_secs: 600, openai_request_secs: 120 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Concurrency {
+    /// TTS requests in flight at once, 0 for no limit
+    pub tts_requests: usize,
+    /// TTS requests started per minute, 0 for no limit. Keep it under the
+    /// account's limit, requests over it are rejected with 429 and retried.
+    pub tts_requests_per_minute: u32,
+}
+
+impl Default for Concurrency {
+    fn default() -> Self {
+        Self { tts_requests: 4, tts_requests_per_minute: 0 }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FfmpegFlags {
+    /// Added to the merge command right before the output file
+    pub merge_args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdvancedConfig {
+    pub timeouts: Timeouts,
+    pub concurrency: Concurrency,
+    pub ffmpeg: FfmpegFlags,
+    /// Retries of OpenAI requests that failed for a passing reason
+    pub retry: HttpRetryPolicy,
+    /// Switches of features that aren't finished yet
+    pub experimental: BTreeMap<String, bool>,
+}
+
+impl AdvancedConfig {
+    pub fn validate(&self) -> Result<()> {
+        let timeouts = [
+            ("timeouts.download_secs", self.timeouts.download_secs),
+            ("timeouts.merge_secs", self.timeouts.merge_secs),
+            ("timeouts.openai_request_secs", self.timeouts.openai_request_secs),
+        ];
+        for (name, secs) in timeouts {
+            if !(5..=86_400).contains(&secs) {
+                return Err(anyhow!("{} must be between 5 and 86400 seconds, got {}", name, secs));
+            }
+        }
+        if self.concurrency.tts_requests > 64 {
+            return Err(anyhow!("concurrency.tts_requests must be at most 64"));
+        }
+        if self.concurrency.tts_requests_per_minute > 10_000 {
+            return Err(anyhow!("concurrency.tts_requests_per_minute must be at most 10000"));
+        }
+        self.retry.validate()?;
+        // The merge owns its inputs and output, the flags may only tune the encoding
+        for arg in &self.ffmpeg.merge_args {
+            if arg.trim().is_empty() || matches!(arg.as_str(), "-i" | "-y" | "-n") {
+                return Err(anyhow!("ffmpeg.merge_args may not contain '{}'", arg));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn experimental(&self, feature: &str) -> bool {
+        self.experimental.get(feature).copied().unwrap_or(false)
+    }
+}
+
+/// Parse and validate the content of the file
+pub fn parse(content: &str) -> Result<AdvancedConfig> {
+    let config: AdvancedConfig = toml::from_str(content).map_err(|e| anyhow!("{}", e))?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Payload of `get_advanced_config` and the reload events
+#[derive(Debug, Clone, Serialize)]
+pub struct AdvancedConfigStatus {
+    pub path: Option<PathBuf>,
+    /// The configuration in effect
+    pub config: AdvancedConfig,
+    /// Why the file on disk isn't in effect
+    pub error: Option<String>,
+}
+
+static STATUS: Lazy<RwLock<AdvancedConfigStatus>> =
+    Lazy::new(|| RwLock::new(AdvancedConfigStatus { path: None, config: AdvancedConfig::default(), error: None }));
+
+/// The configuration in effect
+pub fn current() -> AdvancedConfig {
+    STATUS.read().map(|status| status.config.clone()).unwrap_or_default()
+}
+
+pub fn status() -> Option<AdvancedConfigStatus> {
+    STATUS.read().ok().map(|status| status.clone())
+}
+
+fn config_path(app_handle: &tauri::AppHandle) -> Result<PathBuf> {
+    let config_dir = app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| anyhow!("Failed to resolve app config directory: {}", e))?;
+    Ok(config_dir.join(FILE_NAME))
+}
+
+/// Read the file and put it in effect if it's valid
+fn reload(app_handle: &tauri::AppHandle, path: &Path) {
+    let loaded = match std::fs::read_to_string(path) {
+        Ok(content) => parse(&content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(AdvancedConfig::default()),
+        Err(e) => Err(anyhow!("Failed to read {}: {}", path.display(), e)),
+    };
+    let Ok(mut status) = STATUS.write() else { return };
+    status.path = Some(path.to_path_buf());
+    let event = match loaded {
+        Ok(config) => {
+            info!("Advanced configuration loaded from {}", path.display());
+            status.config = config;
+            status.error = None;
+            "advanced-config-reloaded"
+        }
+        Err(e) => {
+            warn!("Keeping the previous advanced configuration, {} is invalid: {}", path.display(), e);
+            status.error = Some(e.to_string());
+            "advanced-config-error"
+        }
+    };
+    let payload = status.clone();
+    drop(status);
+    if let Err(e) = emitter::emit(app_handle, event, &payload) {
+        warn!("Failed to emit {}: {}", event, e);
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Load the file and keep watching it for changes
+pub fn watch(app_handle: &tauri::AppHandle) {
+    let path = match config_path(app_handle) {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Advanced configuration is unavailable: {}", e);
+            return;
+        }
+    };
+    let mut last_modified = modified(&path);
+    if last_modified.is_some() {
+        reload(app_handle, &path);
+    } else if let Ok(mut status) = STATUS.write() {
+        status.path = Some(path.clone());
+    }
+
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let current = modified(&path);
+            if current != last_modified {
+                last_modified = current;
+                reload(&app_handle, &path);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_files_keep_defaults_and_bad_values_are_rejected() {
+        let config = parse("[timeouts]\nmerge_secs = 1200\n\n[experimental]\nfast_merge = true\n").unwrap();
+        assert_eq!(config.timeouts.merge_secs, 1200);
+        assert_eq!(config.timeouts.download_secs, 3600);
+        assert_eq!(config.concurrency.tts_requests, 4);
+        assert!(config.experimental("fast_merge"));
+        assert!(!config.experimental("other"));
+
+        assert!(parse("[timeouts]\nmerge_secs = 1\n").is_err());
+        assert!(parse("[ffmpeg]\nmerge_args = [\"-i\", \"other.mp4\"]\n").is_err());
+        // A misspelled key is an error, not a silent default
+        assert!(parse("[timeouts]\nmerge_sec = 1200\n").is_err());
+    }
+}
@@ -0,0 +1,409 @@
+//! Native reading and writing of ASS/SSA subtitles.
+//!
+//! A script is kept as its sections with the rows of `[V4+ Styles]` and
+//! `[Events]` split into fields by their `Format:` line, so styles, override
+//! tags (positioning, karaoke, colours) and sections we don't know about, like
+//! embedded fonts, are written back untouched. An ASS file supplied as the
+//! subtitle source is turned into cues for the pipeline, and the translated
+//! cues are written back into its styles and positions for the merge.
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+
+use crate::utils::tts::tts::SubtitleCue;
+
+/// Script info of the files we write, the defaults ffmpeg used to produce
+const DEFAULT_SCRIPT_INFO: &[&str] = &[
+    "ScriptType: v4.00+",
+    "PlayResX: 384",
+    "PlayResY: 288",
+    "ScaledBorderAndShadow: yes",
+    "YCbCr Matrix: None",
+];
+const STYLE_FORMAT: &str = "Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding";
+const DEFAULT_STYLE: &str = "Default,Arial,16,&Hffffff,&Hffffff,&H0,&H0,0,0,0,0,100,100,0,0,1,1,0,2,10,10,10,0";
+const EVENT_FORMAT: &str = "Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text";
+
+/// Override tags that place an event on screen. Karaoke and styling tags are
+/// left out when restyling translated text, they belong to the original words.
+const POSITION_TAGS: &[&str] = &["pos", "move", "org", "an", "a", "fad", "fade", "clip", "iclip", "frz", "frx", "fry"];
+
+/// One row of a styles or events section, e.g. a "Dialogue" line
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssRow {
+    /// "Style", "Dialogue", "Comment"...
+    pub kind: String,
+    pub values: Vec<String>,
+}
+
+/// A section whose rows follow its `Format:` line
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AssTable {
+    pub format: Vec<String>,
+    pub rows: Vec<AssRow>,
+}
+
+impl AssTable {
+    fn with_format(format: &str) -> Self {
+        AssTable { format: format.split(',').map(|name| name.trim().to_string()).collect(), rows: Vec::new() }
+    }
+
+    fn index(&self, field: &str) -> Option<usize> {
+        self.format.iter().position(|name| name.eq_ignore_ascii_case(field))
+    }
+
+    /// Value of a field of a row
+    pub fn get<'a>(&self, row: &'a AssRow, field: &str) -> Option<&'a str> {
+        self.index(field).and_then(|index| row.values.get(index)).map(String::as_str)
+    }
+
+    /// Set a field of a row, does nothing if the format has no such field
+    pub fn set(&self, row: &mut AssRow, field: &str, value: impl Into<String>) {
+        if let Some(index) = self.index(field) {
+            if row.values.len() <= index {
+                row.values.resize(index + 1, String::new());
+            }
+            row.values[index] = value.into();
+        }
+    }
+
+    fn parse_row(&self, kind: &str, values: &str) -> AssRow {
+        // The last field is the text and may contain commas itself
+        let count = self.format.len().max(1);
+        let mut values: Vec<String> = values.splitn(count, ',').map(str::to_string).collect();
+        let last = values.len() - 1;
+        for value in &mut values[..last] {
+            *value = value.trim().to_string();
+        }
+        values[last] = values[last].trim_start().to_string();
+        AssRow { kind: kind.to_string(), values }
+    }
+
+    fn write(&self, out: &mut String) {
+        out.push_str(&format!("Format: {}\n", self.format.join(", ")));
+        for row in &self.rows {
+            out.push_str(&format!("{}: {}\n", row.kind, row.values.join(",")));
+        }
+    }
+}
+
+/// An ASS or SSA script
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssScript {
+    /// Lines of `[Script Info]`, comments included
+    pub script_info: Vec<String>,
+    /// `[V4+ Styles]`, or `[V4 Styles]` in SSA files
+    pub styles_header: String,
+    pub styles: AssTable,
+    pub events: AssTable,
+    /// Other sections as they were, e.g. `[Fonts]` or `[Aegisub Project Garbage]`
+    pub extra: Vec<String>,
+}
+
+#[derive(PartialEq)]
+enum Section {
+    Info,
+    Styles,
+    Events,
+    Other,
+}
+
+/// Whether a subtitle file is ASS/SSA, by extension or by its first section
+pub fn is_ass(path: Option<&Path>, content: &str) -> bool {
+    let extension = path
+        .and_then(|path| path.extension())
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    matches!(extension.as_str(), "ass" | "ssa")
+        || content.trim_start_matches('\u{feff}').trim_start().to_ascii_lowercase().starts_with("[script info]")
+}
+
+/// "H:MM:SS.cc" to seconds
+pub fn parse_time(time: &str) -> Option<f64> {
+    let mut parts = time.trim().split(':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// Seconds to "H:MM:SS.cc"
+pub fn format_time(seconds: f64) -> String {
+    let centis = (seconds.max(0.0) * 100.0).round() as u64;
+    format!("{}:{:02}:{:02}.{:02}", centis / 360_000, centis / 6000 % 60, centis / 100 % 60, centis % 100)
+}
+
+pub fn parse(content: &str) -> Result<AssScript> {
+    let mut script = AssScript {
+        script_info: Vec::new(),
+        styles_header: "[V4+ Styles]".to_string(),
+        styles: AssTable::default(),
+        events: AssTable::default(),
+        extra: Vec::new(),
+    };
+    let mut section = Section::Other;
+    for line in content.trim_start_matches('\u{feff}').replace("\r\n", "\n").lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            section = match trimmed.to_ascii_lowercase().as_str() {
+                "[script info]" => Section::Info,
+                "[v4+ styles]" | "[v4 styles]" => {
+                    script.styles_header = trimmed.to_string();
+                    Section::Styles
+                }
+                "[events]" => Section::Events,
+                _ => {
+                    script.extra.push(trimmed.to_string());
+                    Section::Other
+                }
+            };
+            continue;
+        }
+        match section {
+            Section::Info => {
+                if !trimmed.is_empty() {
+                    script.script_info.push(trimmed.to_string());
+                }
+            }
+            Section::Other => script.extra.push(line.to_string()),
+            Section::Styles | Section::Events => {
+                let table = if section == Section::Styles { &mut script.styles } else { &mut script.events };
+                let Some((kind, values)) = line.split_once(':') else { continue };
+                let kind = kind.trim();
+                if kind.starts_with(';') {
+                    continue;
+                }
+                if kind.eq_ignore_ascii_case("Format") {
+                    *table = AssTable::with_format(values);
+                } else if !table.format.is_empty() {
+                    let row = table.parse_row(kind, values);
+                    table.rows.push(row);
+                }
+            }
+        }
+    }
+    for field in ["Start", "End", "Text"] {
+        if script.events.index(field).is_none() {
+            return Err(anyhow!("ASS events have no {} field", field));
+        }
+    }
+    Ok(script)
+}
+
+impl fmt::Display for AssScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::from("[Script Info]\n");
+        for line in &self.script_info {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push_str(&format!("\n{}\n", self.styles_header));
+        self.styles.write(&mut out);
+        out.push_str("\n[Events]\n");
+        self.events.write(&mut out);
+        if !self.extra.is_empty() {
+            out.push('\n');
+            for line in &self.extra {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        f.write_str(&out)
+    }
+}
+
+impl AssScript {
+    /// Script with the default style and no events
+    pub fn new() -> Self {
+        let mut styles = AssTable::with_format(STYLE_FORMAT);
+        styles.rows.push(styles.parse_row("Style", DEFAULT_STYLE));
+        AssScript {
+            script_info: DEFAULT_SCRIPT_INFO.iter().map(|line| line.to_string()).collect(),
+            styles_header: "[V4+ Styles]".to_string(),
+            styles,
+            events: AssTable::with_format(EVENT_FORMAT),
+            extra: Vec::new(),
+        }
+    }
+
+    /// Set a field of every style, e.g. the font of burned-in subtitles
+    pub fn set_style_field(&mut self, field: &str, value: &str) {
+        let Some(index) = self.styles.index(field) else { return };
+        for row in &mut self.styles.rows {
+            if let Some(current) = row.values.get_mut(index) {
+                *current = value.to_string();
+            }
+        }
+    }
+
+    /// Start and end of an event in seconds
+    pub fn times(&self, row: &AssRow) -> Option<(f64, f64)> {
+        Some((parse_time(self.events.get(row, "Start")?)?, parse_time(self.events.get(row, "End")?)?))
+    }
+
+    /// Dialogue events, comments and other rows left out
+    fn dialogues(&self) -> impl Iterator<Item = &AssRow> {
+        self.events.rows.iter().filter(|row| row.kind.eq_ignore_ascii_case("Dialogue"))
+    }
+
+    /// Dialogue events as cues in time order, with the override tags removed
+    /// and the actor as the speaker. Vector drawings have no text and are skipped.
+    pub fn to_cues(&self) -> Vec<SubtitleCue> {
+        let drawing = Regex::new(r"\{[^}]*\\p[1-9]").unwrap();
+        let mut cues: Vec<SubtitleCue> = self
+            .dialogues()
+            .filter_map(|row| {
+                let raw = self.events.get(row, "Text")?;
+                if drawing.is_match(raw) {
+                    return None;
+                }
+                let (start, end) = self.times(row)?;
+                let text = plain_text(raw);
+                if text.is_empty() {
+                    return None;
+                }
+                let speaker = self.events.get(row, "Name").map(str::trim).filter(|name| !name.is_empty());
+                Some(SubtitleCue {
+                    start: start as f32,
+                    end: end as f32,
+                    text,
+                    id: None,
+                    speaker: speaker.map(str::to_string),
+                    notes: Vec::new(),
+                })
+            })
+            .collect();
+        cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+        cues
+    }
+
+    /// Dialogue event that overlaps a time span the most
+    fn best_match(&self, start: f64, end: f64) -> Option<&AssRow> {
+        self.dialogues()
+            .filter_map(|row| {
+                let (row_start, row_end) = self.times(row)?;
+                let overlap = end.min(row_end) - start.max(row_start);
+                (overlap > 0.0).then_some((row, overlap))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(row, _)| row)
+    }
+}
+
+impl Default for AssScript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Event text without override tags, with ASS line breaks as newlines
+pub fn plain_text(text: &str) -> String {
+    let tags = Regex::new(r"\{[^}]*\}").unwrap();
+    tags.replace_all(text, "")
+        .replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", " ")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Cue text as ASS event text: VTT italics, bold and underline become
+/// override tags, other markup is dropped and newlines become `\N`
+fn event_text(text: &str) -> String {
+    let mut text = text.replace('\n', "\\N");
+    for (tag, code) in [("i", "i"), ("b", "b"), ("u", "u")] {
+        text = text.replace(&format!("<{}>", tag), &format!("{{\\{}1}}", code));
+        text = text.replace(&format!("</{}>", tag), &format!("{{\\{}0}}", code));
+    }
+    Regex::new(r"<[^>]*>").unwrap().replace_all(&text, "").to_string()
+}
+
+/// Positioning tags of the leading override block of an event, e.g. `{\an8\pos(10,20)}`
+fn position_tags(text: &str) -> Option<String> {
+    let block = text.strip_prefix('{')?.split_once('}')?.0;
+    let tags: Vec<&str> = block
+        .split('\\')
+        .filter(|tag| {
+            let name: String = tag.chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+            !tag.is_empty() && POSITION_TAGS.contains(&name.as_str())
+        })
+        .collect();
+    (!tags.is_empty()).then(|| format!("{{\\{}}}", tags.join("\\")))
+}
+
+/// Write cues as an ASS script. With a template, such as the original ASS of
+/// the video, its script info and styles are kept and every cue takes the
+/// style, actor, margins, effect and positioning of the template event it
+/// overlaps the most.
+pub fn from_cues(cues: &[SubtitleCue], template: Option<&AssScript>) -> AssScript {
+    let mut script = match template {
+        Some(template) => AssScript { events: AssTable { format: template.events.format.clone(), rows: Vec::new() }, ..template.clone() },
+        None => AssScript::new(),
+    };
+    for cue in cues {
+        let source = template.and_then(|template| template.best_match(cue.start as f64, cue.end as f64));
+        let mut row = match source {
+            Some(source) => source.clone(),
+            None => AssRow { kind: "Dialogue".to_string(), values: vec![String::new(); script.events.format.len()] },
+        };
+        let events = &script.events;
+        if source.is_none() {
+            events.set(&mut row, "Layer", "0");
+            events.set(&mut row, "Style", "Default");
+            for margin in ["MarginL", "MarginR", "MarginV"] {
+                events.set(&mut row, margin, "0");
+            }
+            if let Some(speaker) = &cue.speaker {
+                events.set(&mut row, "Name", speaker.as_str());
+            }
+        }
+        let position = source.and_then(|source| events.get(source, "Text")).and_then(position_tags);
+        events.set(&mut row, "Start", format_time(cue.start as f64));
+        events.set(&mut row, "End", format_time(cue.end as f64));
+        events.set(&mut row, "Text", format!("{}{}", position.unwrap_or_default(), event_text(&cue.text)));
+        script.events.rows.push(row);
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "[Script Info]\n; made by hand\nScriptType: v4.00+\nPlayResX: 1920\nPlayResY: 1080\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize, Alignment\nStyle: Default,Arial,48,2\nStyle: Sign,Georgia,40,8\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nComment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,note\nDialogue: 0,0:00:01.00,0:00:03.00,Default,Anna,0,0,0,,{\\k20}Hello, {\\k30}world\\Nagain\nDialogue: 1,0:00:04.00,0:00:05.50,Sign,,0,0,0,,{\\an8\\pos(960,100)\\c&H00FFFF&}Exit\nDialogue: 0,0:00:06.00,0:00:07.00,Default,,0,0,0,,{\\p1}m 0 0 l 10 10{\\p0}\n\n[Fonts]\nfontname: custom.ttf\n";
+
+    #[test]
+    fn scripts_round_trip_and_give_plain_cues() {
+        let script = parse(SCRIPT).unwrap();
+        assert_eq!(script.to_string(), SCRIPT);
+
+        let cues = script.to_cues();
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].text, "Hello, world\nagain");
+        assert_eq!(cues[0].speaker.as_deref(), Some("Anna"));
+        assert_eq!((cues[1].start, cues[1].end), (4.0, 5.5));
+        assert_eq!(cues[1].text, "Exit");
+    }
+
+    #[test]
+    fn translated_cues_keep_style_and_position() {
+        let template = parse(SCRIPT).unwrap();
+        let mut cues = template.to_cues();
+        cues[0].text = "Привет, мир".to_string();
+        cues[1].text = "Выход".to_string();
+
+        let translated = from_cues(&cues, Some(&template));
+        assert_eq!(translated.styles, template.styles);
+        let rows = &translated.events.rows;
+        assert_eq!(translated.events.get(&rows[0], "Text"), Some("Привет, мир"));
+        assert_eq!(translated.events.get(&rows[0], "Name"), Some("Anna"));
+        assert_eq!(translated.events.get(&rows[1], "Style"), Some("Sign"));
+        assert_eq!(translated.events.get(&rows[1], "Text"), Some("{\\an8\\pos(960,100)}Выход"));
+        assert_eq!(translated.events.get(&rows[1], "End"), Some("0:00:05.50"));
+    }
+}
@@ -0,0 +1,510 @@
+//! Fast duration probing of audio and video files.
+//!
+//! Spawning ffprobe costs tens of milliseconds per file, which adds up when
+//! every TTS fragment is checked. WAV, MP3 and MP4/M4A keep their duration
+//! (or enough to compute it) in the first few kilobytes or in a single box,
+//! so those are read natively. Anything else, or a header that doesn't parse,
+//! falls back to ffprobe. The sample format of a stream always comes from
+//! ffprobe, it's only needed once per mix.
+
+use anyhow::{anyhow, Result};
+use log::debug;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use tokio::process::Command as TokioCommand;
+
+/// How far into an MP3 stream (after ID3v2) to look for the first frame
+const MP3_SCAN_LIMIT: usize = 64 * 1024;
+
+/// Containers this module can read without ffprobe
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Container {
+    Wav,
+    Mp3,
+    Mp4,
+}
+
+fn detect(head: &[u8]) -> Option<Container> {
+    if head.len() >= 12 && &head[0..4] == b"RIFF" && &head[8..12] == b"WAVE" {
+        return Some(Container::Wav);
+    }
+    if head.len() >= 8 && &head[4..8] == b"ftyp" {
+        return Some(Container::Mp4);
+    }
+    if head.starts_with(b"ID3") || (head.len() >= 4 && Mp3Frame::parse(&head[0..4]).is_some()) {
+        return Some(Container::Mp3);
+    }
+    None
+}
+
+fn read_u16_le(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn read_u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn read_u32_be(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn read_u64_be(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]])
+}
+
+/// Duration of a RIFF/WAVE stream from its `fmt ` and `data` chunks
+fn wav_duration<R: Read + Seek>(reader: &mut R, stream_len: u64) -> std::io::Result<Option<f64>> {
+    reader.seek(SeekFrom::Start(12))?;
+    let mut byte_rate: Option<u32> = None;
+    let mut header = [0u8; 8];
+    loop {
+        if reader.read_exact(&mut header).is_err() {
+            return Ok(None);
+        }
+        let size = read_u32_le(&header[4..8]);
+        let body_start = reader.stream_position()?;
+        match &header[0..4] {
+            b"fmt " => {
+                let mut fmt = [0u8; 16];
+                reader.read_exact(&mut fmt)?;
+                // Byte rate as declared; recomputed in case a writer left it at zero
+                let channels = read_u16_le(&fmt[2..4]) as u32;
+                let sample_rate = read_u32_le(&fmt[4..8]);
+                let bits = read_u16_le(&fmt[14..16]) as u32;
+                let declared = read_u32_le(&fmt[8..12]);
+                // A corrupt header can overflow; a zero rate leaves the duration to ffprobe
+                let computed = sample_rate
+                    .checked_mul(channels)
+                    .and_then(|rate| rate.checked_mul(bits.div_ceil(8)))
+                    .unwrap_or(0);
+                byte_rate = Some(if declared > 0 { declared } else { computed });
+            }
+            b"data" => {
+                let Some(byte_rate) = byte_rate.filter(|r| *r > 0) else {
+                    return Ok(None);
+                };
+                // Streaming writers put a placeholder size into the header
+                let available = stream_len.saturating_sub(body_start);
+                let data_len = if size == 0 || size == u32::MAX { available } else { (size as u64).min(available) };
+                return Ok(Some(data_len as f64 / byte_rate as f64));
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even size
+        let next = body_start + size as u64 + (size as u64 & 1);
+        if next >= stream_len {
+            return Ok(None);
+        }
+        reader.seek(SeekFrom::Start(next))?;
+    }
+}
+
+/// Header of an MPEG audio frame
+#[derive(Debug, Clone, Copy)]
+struct Mp3Frame {
+    mpeg1: bool,
+    layer: u8,
+    bitrate: u32, // bits per second
+    sample_rate: u32,
+    padding: bool,
+    mono: bool,
+}
+
+impl Mp3Frame {
+    fn parse(header: &[u8]) -> Option<Mp3Frame> {
+        if header[0] != 0xFF || header[1] & 0xE0 != 0xE0 {
+            return None;
+        }
+        // 0 = MPEG 2.5, 2 = MPEG 2, 3 = MPEG 1
+        let version = (header[1] >> 3) & 0x03;
+        let layer = match (header[1] >> 1) & 0x03 {
+            3 => 1,
+            2 => 2,
+            1 => 3,
+            _ => return None,
+        };
+        if version == 1 {
+            return None;
+        }
+        let mpeg1 = version == 3;
+
+        let bitrate_index = (header[2] >> 4) as usize;
+        if bitrate_index == 0 || bitrate_index == 15 {
+            return None;
+        }
+        const MPEG1_L1: [u32; 15] = [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448];
+        const MPEG1_L2: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384];
+        const MPEG1_L3: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+        const MPEG2_L1: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256];
+        const MPEG2_L23: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+        let kbps = match (mpeg1, layer) {
+            (true, 1) => MPEG1_L1[bitrate_index],
+            (true, 2) => MPEG1_L2[bitrate_index],
+            (true, _) => MPEG1_L3[bitrate_index],
+            (false, 1) => MPEG2_L1[bitrate_index],
+            (false, _) => MPEG2_L23[bitrate_index],
+        };
+
+        let base_rate = match (header[2] >> 2) & 0x03 {
+            0 => 44100,
+            1 => 48000,
+            2 => 32000,
+            _ => return None,
+        };
+        let sample_rate = match version {
+            3 => base_rate,
+            2 => base_rate / 2,
+            _ => base_rate / 4,
+        };
+
+        Some(Mp3Frame {
+            mpeg1,
+            layer,
+            bitrate: kbps * 1000,
+            sample_rate,
+            padding: (header[2] >> 1) & 0x01 == 1,
+            mono: header[3] >> 6 == 3,
+        })
+    }
+
+    fn samples_per_frame(&self) -> u32 {
+        match (self.layer, self.mpeg1) {
+            (1, _) => 384,
+            (3, false) => 576,
+            _ => 1152,
+        }
+    }
+
+    fn frame_len(&self) -> usize {
+        if self.layer == 1 {
+            ((12 * self.bitrate / self.sample_rate + self.padding as u32) * 4) as usize
+        } else {
+            (self.samples_per_frame() / 8 * self.bitrate / self.sample_rate + self.padding as u32) as usize
+        }
+    }
+
+    /// Frame count from a Xing/Info or VBRI header inside the first frame
+    fn vbr_frame_count(&self, frame: &[u8]) -> Option<u32> {
+        let side_info = match (self.mpeg1, self.mono) {
+            (true, false) => 32,
+            (true, true) | (false, false) => 17,
+            (false, true) => 9,
+        };
+        let xing = 4 + side_info;
+        if frame.len() >= xing + 12 && (&frame[xing..xing + 4] == b"Xing" || &frame[xing..xing + 4] == b"Info") {
+            let flags = read_u32_be(&frame[xing + 4..xing + 8]);
+            if flags & 0x01 != 0 {
+                return Some(read_u32_be(&frame[xing + 8..xing + 12]));
+            }
+        }
+        let vbri = 4 + 32;
+        if frame.len() >= vbri + 18 && &frame[vbri..vbri + 4] == b"VBRI" {
+            return Some(read_u32_be(&frame[vbri + 14..vbri + 18]));
+        }
+        None
+    }
+}
+
+/// Duration of an MPEG audio stream from its VBR header or, for CBR, its size
+fn mp3_duration<R: Read + Seek>(reader: &mut R, stream_len: u64) -> std::io::Result<Option<f64>> {
+    reader.seek(SeekFrom::Start(0))?;
+    let mut id3 = [0u8; 10];
+    let mut audio_start = 0u64;
+    if reader.read_exact(&mut id3).is_ok() && id3.starts_with(b"ID3") {
+        // Synchsafe size, 7 bits per byte, plus an optional footer
+        let size = id3[6..10].iter().fold(0u64, |acc, b| (acc << 7) | (*b & 0x7F) as u64);
+        audio_start = 10 + size + if id3[5] & 0x10 != 0 { 10 } else { 0 };
+    }
+
+    reader.seek(SeekFrom::Start(audio_start))?;
+    let mut buffer = Vec::with_capacity(MP3_SCAN_LIMIT);
+    reader.by_ref().take(MP3_SCAN_LIMIT as u64).read_to_end(&mut buffer)?;
+
+    for offset in 0..buffer.len().saturating_sub(4) {
+        let Some(frame) = Mp3Frame::parse(&buffer[offset..offset + 4]) else {
+            continue;
+        };
+        // A lone sync pattern can occur in garbage, require the next frame to line up
+        let next = offset + frame.frame_len();
+        if next + 4 <= buffer.len() && Mp3Frame::parse(&buffer[next..next + 4]).is_none() {
+            continue;
+        }
+
+        let frame_end = (offset + frame.frame_len()).min(buffer.len());
+        if let Some(frames) = frame.vbr_frame_count(&buffer[offset..frame_end]) {
+            return Ok(Some(frames as f64 * frame.samples_per_frame() as f64 / frame.sample_rate as f64));
+        }
+        let audio_len = stream_len.saturating_sub(audio_start + offset as u64);
+        return Ok(Some(audio_len as f64 * 8.0 / frame.bitrate as f64));
+    }
+    Ok(None)
+}
+
+/// Duration of an MP4/M4A file from `moov/mvhd`
+fn mp4_duration<R: Read + Seek>(reader: &mut R, stream_len: u64) -> std::io::Result<Option<f64>> {
+    let mut start = 0u64;
+    let mut end = stream_len;
+    let mut header = [0u8; 8];
+    loop {
+        if start + 8 > end {
+            return Ok(None);
+        }
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(&mut header)?;
+        let mut size = read_u32_be(&header[0..4]) as u64;
+        let mut body = start + 8;
+        if size == 1 {
+            let mut large = [0u8; 8];
+            reader.read_exact(&mut large)?;
+            size = read_u64_be(&large);
+            body += 8;
+        } else if size == 0 {
+            size = end - start;
+        }
+        if size < body - start {
+            return Ok(None);
+        }
+
+        match &header[4..8] {
+            // Descend into the movie box
+            b"moov" => {
+                end = (start + size).min(end);
+                start = body;
+                continue;
+            }
+            b"mvhd" => {
+                let mut version_flags = [0u8; 4];
+                reader.read_exact(&mut version_flags)?;
+                // Version 1 uses 64-bit times and duration
+                let mut fields = [0u8; 28];
+                let (timescale, duration) = if version_flags[0] == 1 {
+                    reader.read_exact(&mut fields)?;
+                    (read_u32_be(&fields[16..20]), read_u64_be(&fields[20..28]))
+                } else {
+                    reader.read_exact(&mut fields[..16])?;
+                    (read_u32_be(&fields[8..12]), read_u32_be(&fields[12..16]) as u64)
+                };
+                if timescale == 0 {
+                    return Ok(None);
+                }
+                return Ok(Some(duration as f64 / timescale as f64));
+            }
+            _ => start += size,
+        }
+    }
+}
+
+fn probe_reader<R: Read + Seek>(reader: &mut R) -> std::io::Result<Option<f64>> {
+    let stream_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let mut head = [0u8; 12];
+    let read = reader.read(&mut head)?;
+    let duration = match detect(&head[..read]) {
+        Some(Container::Wav) => wav_duration(reader, stream_len)?,
+        Some(Container::Mp3) => mp3_duration(reader, stream_len)?,
+        Some(Container::Mp4) => mp4_duration(reader, stream_len)?,
+        None => None,
+    };
+    // A zero duration (e.g. the empty mvhd of fragmented MP4) is left to ffprobe
+    Ok(duration.filter(|d| d.is_finite() && *d > 0.0))
+}
+
+/// Duration of in-memory WAV, MP3 or MP4 data, None for other formats or a broken header
+pub fn duration_from_bytes(bytes: &[u8]) -> Option<f64> {
+    probe_reader(&mut Cursor::new(bytes)).ok().flatten()
+}
+
+/// Duration of a file read natively, None if its format isn't supported
+pub fn native_duration(path: &Path) -> Option<f64> {
+    let mut file = std::fs::File::open(path).ok()?;
+    probe_reader(&mut file).ok().flatten()
+}
+
+async fn ffprobe_duration(path: &Path) -> Result<f64> {
+    let output = TokioCommand::new(crate::utils::tools::ffprobe())
+        .args(["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to execute ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!("ffprobe error: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    let duration = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    duration.parse::<f64>().map_err(|e| anyhow!("Failed to parse duration '{}': {}", duration, e))
+}
+
+/// Duration of a media file in seconds, read natively when possible and with ffprobe otherwise
+pub async fn duration(path: &Path) -> Result<f64> {
+    let owned = path.to_path_buf();
+    if let Ok(Some(duration)) = tokio::task::spawn_blocking(move || native_duration(&owned)).await {
+        return Ok(duration);
+    }
+    debug!("Falling back to ffprobe for the duration of {}", path.display());
+    ffprobe_duration(path).await
+}
+
+/// Sample format of the first audio stream of a file
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Bits per sample of PCM and lossless streams, None for lossy codecs
+    pub bits_per_sample: Option<u32>,
+}
+
+/// Parse `key=value` lines of `ffprobe -show_entries stream=...`
+fn parse_stream_format(output: &str) -> Option<AudioFormat> {
+    let value = |key: &str| {
+        output
+            .lines()
+            .filter_map(|line| line.trim().split_once('='))
+            .find(|(name, _)| *name == key)
+            .and_then(|(_, value)| value.trim().parse::<u32>().ok())
+            .filter(|value| *value > 0)
+    };
+    Some(AudioFormat {
+        sample_rate: value("sample_rate")?,
+        channels: value("channels")? as u16,
+        bits_per_sample: value("bits_per_raw_sample").or_else(|| value("bits_per_sample")),
+    })
+}
+
+/// Sample rate, channels and bit depth of the first audio stream, with ffprobe
+pub async fn audio_format(path: &Path) -> Result<AudioFormat> {
+    let output = TokioCommand::new(crate::utils::tools::ffprobe())
+        .args(["-v", "error", "-select_streams", "a:0"])
+        .args(["-show_entries", "stream=sample_rate,channels,bits_per_raw_sample,bits_per_sample"])
+        .args(["-of", "default=noprint_wrappers=1"])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to execute ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!("ffprobe error: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    parse_stream_format(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| anyhow!("No audio stream in {}", path.display()))
+}
+
+/// Codec name of the first audio stream, e.g. "aac" or "pcm_s16le", with ffprobe
+pub async fn audio_codec(path: &Path) -> Result<String> {
+    let output = TokioCommand::new(crate::utils::tools::ffprobe())
+        .args(["-v", "error", "-select_streams", "a:0"])
+        .args(["-show_entries", "stream=codec_name", "-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to execute ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!("ffprobe error: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    let codec = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if codec.is_empty() {
+        return Err(anyhow!("No audio stream in {}", path.display()));
+    }
+    Ok(codec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav(sample_rate: u32, channels: u16, samples: usize, data_size: Option<u32>) -> Vec<u8> {
+        let data_len = samples * channels as usize * 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+        bytes.extend_from_slice(&(channels * 2).to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        // A chunk the parser has to skip
+        bytes.extend_from_slice(b"LIST");
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_size.unwrap_or(data_len as u32).to_le_bytes());
+        bytes.resize(bytes.len() + data_len, 0);
+        bytes
+    }
+
+    #[test]
+    fn wav_duration_from_data_chunk() {
+        let duration = duration_from_bytes(&wav(24000, 2, 36000, None)).unwrap();
+        assert!((duration - 1.5).abs() < 1e-9);
+
+        // Placeholder size written by streaming encoders
+        let duration = duration_from_bytes(&wav(16000, 1, 8000, Some(u32::MAX))).unwrap();
+        assert!((duration - 0.5).abs() < 1e-9);
+
+        // No declared byte rate and a sample rate that overflows the computed one
+        let mut corrupt = wav(16000, 2, 8000, None);
+        corrupt[24..28].copy_from_slice(&u32::MAX.to_le_bytes());
+        corrupt[28..32].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(duration_from_bytes(&corrupt), None);
+    }
+
+    #[test]
+    fn mp3_cbr_and_xing() {
+        // MPEG 1 layer III, 128 kbps, 44.1 kHz, stereo: 417-byte frames
+        let header = [0xFF, 0xFB, 0x90, 0x00];
+        let mut cbr = Vec::new();
+        for _ in 0..100 {
+            cbr.extend_from_slice(&header);
+            cbr.resize(cbr.len() + 413, 0);
+        }
+        let expected = cbr.len() as f64 * 8.0 / 128_000.0;
+        assert!((duration_from_bytes(&cbr).unwrap() - expected).abs() < 1e-9);
+
+        let mut vbr = cbr.clone();
+        vbr[36..40].copy_from_slice(b"Xing");
+        vbr[40..44].copy_from_slice(&1u32.to_be_bytes());
+        vbr[44..48].copy_from_slice(&1000u32.to_be_bytes());
+        let expected = 1000.0 * 1152.0 / 44100.0;
+        assert!((duration_from_bytes(&vbr).unwrap() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mp4_duration_from_mvhd() {
+        let mut mvhd = Vec::new();
+        mvhd.extend_from_slice(&[0, 0, 0, 0]); // version 0, flags
+        mvhd.extend_from_slice(&[0; 8]); // creation and modification time
+        mvhd.extend_from_slice(&1000u32.to_be_bytes());
+        mvhd.extend_from_slice(&12_345u32.to_be_bytes());
+
+        let box_of = |kind: &[u8], body: &[u8]| {
+            let mut bytes = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+            bytes.extend_from_slice(kind);
+            bytes.extend_from_slice(body);
+            bytes
+        };
+        let mut file = box_of(b"ftyp", b"M4A \0\0\0\0");
+        file.extend(box_of(b"mdat", &[0; 64]));
+        file.extend(box_of(b"moov", &box_of(b"mvhd", &mvhd)));
+
+        assert!((duration_from_bytes(&file).unwrap() - 12.345).abs() < 1e-9);
+    }
+
+    #[test]
+    fn unknown_formats_are_not_guessed() {
+        assert_eq!(duration_from_bytes(b"WEBVTT\n\n00:00.000 --> 00:01.000"), None);
+        assert_eq!(duration_from_bytes(&[]), None);
+    }
+
+    #[test]
+    fn stream_format_from_ffprobe() {
+        let format = parse_stream_format("sample_rate=48000\nchannels=2\nbits_per_sample=0\nbits_per_raw_sample=24\n").unwrap();
+        assert_eq!(format, AudioFormat { sample_rate: 48000, channels: 2, bits_per_sample: Some(24) });
+
+        // AAC reports N/A for the raw bit depth
+        let format = parse_stream_format("sample_rate=44100\nchannels=1\nbits_per_sample=0\nbits_per_raw_sample=N/A\n").unwrap();
+        assert_eq!(format.bits_per_sample, None);
+        assert!(parse_stream_format("").is_none());
+    }
+}
@@ -0,0 +1,82 @@
+//! Cancellation of running pipeline jobs.
+//!
+//! Every pipeline run registers a `CancellationToken` under its job id and
+//! `cancel_processing` trips it. Each step watches the token: yt-dlp and ffmpeg
+//! are killed, OpenAI requests in flight are dropped and the batch loops stop
+//! before sending the next request. A cancelled run keeps its checkpoint, so it
+//! can still be resumed from the steps that finished.
+
+use anyhow::{anyhow, Result};
+use log::info;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Error of a step stopped by cancellation
+pub const CANCELLED: &str = "Processing was cancelled";
+
+static TOKENS: Lazy<Mutex<HashMap<String, CancellationToken>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Token of a starting run
+pub fn register(job_id: &str) -> CancellationToken {
+    let token = CancellationToken::new();
+    if let Ok(mut tokens) = TOKENS.lock() {
+        tokens.insert(job_id.to_string(), token.clone());
+    }
+    token
+}
+
+/// Forget the token of a finished run
+pub fn release(job_id: &str) {
+    if let Ok(mut tokens) = TOKENS.lock() {
+        tokens.remove(job_id);
+    }
+}
+
+/// Stop a running job
+pub fn cancel(job_id: &str) -> Result<()> {
+    let tokens = TOKENS.lock().map_err(|_| anyhow!("Cancellation registry is poisoned"))?;
+    let token = tokens.get(job_id).ok_or_else(|| anyhow!("Job {} is not running", job_id))?;
+    info!("Cancelling job {}", job_id);
+    token.cancel();
+    Ok(())
+}
+
+/// Fail between steps once the job is cancelled
+pub fn check(token: &CancellationToken) -> Result<(), String> {
+    if token.is_cancelled() {
+        Err(CANCELLED.to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Run a step until it finishes or the job is cancelled. The step's future is
+/// dropped on cancellation, which aborts the HTTP request it was waiting for.
+pub async fn run<T>(token: &CancellationToken, step: impl Future<Output = T>) -> Result<T> {
+    tokio::select! {
+        output = step => Ok(output),
+        _ = token.cancelled() => Err(anyhow!(CANCELLED)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cancelled_jobs_stop_their_steps() {
+        let token = register("job-cancel");
+        assert_eq!(run(&token, async { 1 }).await.unwrap(), 1);
+
+        cancel("job-cancel").unwrap();
+        assert!(check(&token).is_err());
+        let pending = run(&token, std::future::pending::<()>()).await;
+        assert_eq!(pending.unwrap_err().to_string(), CANCELLED);
+
+        release("job-cancel");
+        assert!(cancel("job-cancel").is_err());
+    }
+}
@@ -0,0 +1,297 @@
+//! Saved channel templates for recurring dubs.
+//!
+//! A template remembers a channel, which of its uploads to take, the target
+//! languages with their voices and where the results go. Processing a template
+//! lists the newest uploads, skips the ones dubbed from it before and returns
+//! the rest as jobs for the pipeline. An upload counts as dubbed once all its
+//! languages finished, so failed ones are picked up again by the next run.
+
+use anyhow::{anyhow, Result};
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::utils::common::sanitize_filename;
+use crate::utils::settings;
+use crate::utils::youtube::ChannelUpload;
+
+const SETTINGS_KEY: &str = "channel-templates";
+/// Processed video ids kept per template, older ones drop off the channel listing anyway
+const MAX_PROCESSED: usize = 500;
+const DEFAULT_NAMING: &str = "{channel}/{title}";
+
+/// A language to dub the channel's uploads into
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TemplateLanguage {
+    pub code: String,
+    pub name: String,
+    /// Voice of the dub, the default voice when empty
+    #[serde(default)]
+    pub voice: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChannelTemplate {
+    /// Generated on first save when empty
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    /// Channel or playlist URL, e.g. https://www.youtube.com/@name
+    pub channel_url: String,
+    /// Case-insensitive regex that titles must match, every upload when empty
+    #[serde(default)]
+    pub title_pattern: Option<String>,
+    pub source_language_code: String,
+    pub source_language_name: String,
+    pub languages: Vec<TemplateLanguage>,
+    pub output_dir: String,
+    /// Sub-directory of `output_dir` for each upload. Placeholders: {channel},
+    /// {title}, {id}, {date} and {lang}
+    #[serde(default = "default_naming")]
+    pub naming_template: String,
+    /// How many of the newest uploads are looked at
+    #[serde(default = "default_max_videos")]
+    pub max_videos: usize,
+    /// Video ids dubbed from this template into every language
+    #[serde(default)]
+    pub processed: Vec<String>,
+}
+
+fn default_naming() -> String {
+    DEFAULT_NAMING.to_string()
+}
+
+fn default_max_videos() -> usize {
+    5
+}
+
+/// One dub to run for a template
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TemplateJob {
+    pub video_id: String,
+    pub title: String,
+    pub url: String,
+    pub output_dir: PathBuf,
+    pub language: TemplateLanguage,
+}
+
+impl ChannelTemplate {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow!("Template name is required"));
+        }
+        if !self.channel_url.starts_with("http://") && !self.channel_url.starts_with("https://") {
+            return Err(anyhow!("Channel URL must start with http:// or https://"));
+        }
+        if self.languages.is_empty() {
+            return Err(anyhow!("At least one target language is required"));
+        }
+        if self.output_dir.trim().is_empty() {
+            return Err(anyhow!("Output directory is required"));
+        }
+        if !(1..=50).contains(&self.max_videos) {
+            return Err(anyhow!("Max videos must be between 1 and 50"));
+        }
+        if let Some(pattern) = self.title_pattern.as_deref().filter(|p| !p.is_empty()) {
+            RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| anyhow!("Invalid title pattern: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Uploads that match the title pattern and were not processed yet
+    pub fn select<'a>(&self, uploads: &'a [ChannelUpload]) -> Result<Vec<&'a ChannelUpload>> {
+        let pattern = match self.title_pattern.as_deref().filter(|p| !p.is_empty()) {
+            Some(pattern) => Some(
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| anyhow!("Invalid title pattern: {}", e))?,
+            ),
+            None => None,
+        };
+        Ok(uploads
+            .iter()
+            .take(self.max_videos)
+            .filter(|upload| !self.processed.contains(&upload.id))
+            .filter(|upload| pattern.as_ref().is_none_or(|re| re.is_match(&upload.title)))
+            .collect())
+    }
+
+    /// Output directory of an upload dubbed into a language
+    pub fn output_dir_for(&self, upload: &ChannelUpload, language: &str) -> PathBuf {
+        let naming = if self.naming_template.trim().is_empty() {
+            DEFAULT_NAMING
+        } else {
+            &self.naming_template
+        };
+        let channel = upload.channel.clone().unwrap_or_else(|| self.name.clone());
+        let date = upload.upload_date.clone().unwrap_or_default();
+
+        let mut dir = PathBuf::from(&self.output_dir);
+        for part in naming.split('/') {
+            let expanded = part
+                .replace("{channel}", &channel)
+                .replace("{title}", &upload.title)
+                .replace("{id}", &upload.id)
+                .replace("{date}", &date)
+                .replace("{lang}", language);
+            // Every part is a single file name, titles can't climb out of the output dir
+            let name = sanitize_filename(&expanded).replace("..", "_");
+            if !name.is_empty() {
+                dir.push(name);
+            }
+        }
+        dir
+    }
+
+    /// Jobs for the selected uploads, one per upload and language
+    pub fn jobs(&self, uploads: &[&ChannelUpload]) -> Vec<TemplateJob> {
+        uploads
+            .iter()
+            .flat_map(|upload| {
+                self.languages.iter().map(|language| TemplateJob {
+                    video_id: upload.id.clone(),
+                    title: upload.title.clone(),
+                    url: upload.url.clone(),
+                    output_dir: self.output_dir_for(upload, &language.code),
+                    language: language.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+pub fn load(app_handle: &tauri::AppHandle) -> Vec<ChannelTemplate> {
+    settings::get(app_handle, SETTINGS_KEY).unwrap_or_default()
+}
+
+pub fn find(app_handle: &tauri::AppHandle, id: &str) -> Option<ChannelTemplate> {
+    load(app_handle).into_iter().find(|template| template.id == id)
+}
+
+/// Insert or replace a template by id, returns it with its id set
+pub async fn save(app_handle: &tauri::AppHandle, mut template: ChannelTemplate) -> Result<ChannelTemplate> {
+    template.validate()?;
+    if template.id.is_empty() {
+        // Time based like job ids, unique enough for a handful of templates
+        template.id = crate::utils::jobs::new_job_id();
+    }
+    let saved = template.clone();
+    settings::update(app_handle, SETTINGS_KEY, |templates: &mut Vec<ChannelTemplate>| {
+        match templates.iter_mut().find(|existing| existing.id == template.id) {
+            // Keep what was processed, the editor doesn't send it back
+            Some(existing) => {
+                if template.processed.is_empty() {
+                    template.processed = std::mem::take(&mut existing.processed);
+                }
+                *existing = template;
+            }
+            None => templates.push(template),
+        }
+    })
+    .await?;
+    Ok(saved)
+}
+
+pub async fn delete(app_handle: &tauri::AppHandle, id: &str) -> Result<()> {
+    settings::update(app_handle, SETTINGS_KEY, |templates: &mut Vec<ChannelTemplate>| {
+        templates.retain(|template| template.id != id);
+    })
+    .await?;
+    Ok(())
+}
+
+/// Remember uploads as processed so the next run skips them
+pub async fn mark_processed(app_handle: &tauri::AppHandle, id: &str, video_ids: &[String]) -> Result<()> {
+    settings::update(app_handle, SETTINGS_KEY, |templates: &mut Vec<ChannelTemplate>| {
+        if let Some(template) = templates.iter_mut().find(|template| template.id == id) {
+            for video_id in video_ids {
+                if !template.processed.contains(video_id) {
+                    template.processed.push(video_id.clone());
+                }
+            }
+            let excess = template.processed.len().saturating_sub(MAX_PROCESSED);
+            template.processed.drain(..excess);
+        }
+    })
+    .await?;
+    Ok(())
+}
+
+/// Output directories must exist before the pipeline writes into them
+pub async fn prepare_output_dir(dir: &Path) -> Result<()> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| anyhow!("Failed to create output directory {}: {}", dir.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upload(id: &str, title: &str) -> ChannelUpload {
+        ChannelUpload {
+            id: id.to_string(),
+            title: title.to_string(),
+            url: format!("https://www.youtube.com/watch?v={}", id),
+            channel: Some("Some Channel".to_string()),
+            upload_date: Some("20240301".to_string()),
+        }
+    }
+
+    fn template() -> ChannelTemplate {
+        ChannelTemplate {
+            id: "t".to_string(),
+            name: "Weekly".to_string(),
+            channel_url: "https://www.youtube.com/@some".to_string(),
+            title_pattern: Some("episode".to_string()),
+            source_language_code: "en".to_string(),
+            source_language_name: "English".to_string(),
+            languages: vec![
+                TemplateLanguage { code: "ru".to_string(), name: "Russian".to_string(), voice: None },
+                TemplateLanguage { code: "de".to_string(), name: "German".to_string(), voice: Some("nova".to_string()) },
+            ],
+            output_dir: "/out".to_string(),
+            naming_template: "{channel}/{date} {title}".to_string(),
+            max_videos: 3,
+            processed: vec!["b".to_string()],
+        }
+    }
+
+    #[test]
+    fn selects_new_matching_uploads_among_the_newest() {
+        let uploads = vec![
+            upload("a", "Episode 3"),
+            upload("b", "Episode 2"),
+            upload("c", "Behind the scenes"),
+            upload("d", "Episode 1"),
+        ];
+        let selected = template().select(&uploads).unwrap();
+        let ids: Vec<&str> = selected.iter().map(|u| u.id.as_str()).collect();
+        assert_eq!(ids, vec!["a"]);
+    }
+
+    #[test]
+    fn builds_one_job_per_language_inside_the_output_dir() {
+        let upload = upload("a", "Episode 3: ../../etc");
+        let template = template();
+        let jobs = template.jobs(&[&upload]);
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[1].language.voice.as_deref(), Some("nova"));
+        assert!(jobs[0].output_dir.starts_with("/out"));
+        assert_eq!(
+            jobs[0].output_dir,
+            PathBuf::from("/out/some_channel/20240301_episode_3______etc")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let mut template = template();
+        template.title_pattern = Some("(".to_string());
+        assert!(template.validate().is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! Chapter markers of the source video.
+//!
+//! Chapters come from the yt-dlp metadata or, when the video was obtained some
+//! other way, from the container itself via ffprobe. Their titles are
+//! translated together with the subtitles and written into the merged output
+//! through an FFMETADATA file, so chapter navigation survives dubbing.
+//!
+//! A job can also be limited to some adjacent chapters, it then processes
+//! their span like any other time range.
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::process::Command as TokioCommand;
+
+use crate::utils::settings;
+use crate::utils::translate;
+
+const SETTINGS_KEY: &str = "translate-chapters";
+
+/// A single chapter marker
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Chapter {
+    pub start: f64, // seconds
+    pub end: f64,   // seconds
+    pub title: String,
+}
+
+/// Chapters reported by `yt-dlp --dump-json`
+pub fn from_ytdlp_info(info: &serde_json::Value) -> Vec<Chapter> {
+    let Some(chapters) = info["chapters"].as_array() else {
+        return Vec::new();
+    };
+    chapters
+        .iter()
+        .filter_map(|c| {
+            Some(Chapter {
+                start: c["start_time"].as_f64()?,
+                end: c["end_time"].as_f64()?,
+                title: c["title"].as_str().unwrap_or_default().trim().to_string(),
+            })
+        })
+        .filter(|c| c.end > c.start)
+        .collect()
+}
+
+/// Chapters embedded in a media file
+pub async fn probe(media_path: &Path) -> Result<Vec<Chapter>> {
+    let output = TokioCommand::new(crate::utils::tools::ffprobe())
+        .args(["-v", "quiet", "-print_format", "json", "-show_chapters"])
+        .arg(media_path)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!("ffprobe failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let chapters = json["chapters"]
+        .as_array()
+        .map(|chapters| {
+            chapters
+                .iter()
+                .filter_map(|c| {
+                    // ffprobe reports times as strings
+                    let start = c["start_time"].as_str()?.parse().ok()?;
+                    let end = c["end_time"].as_str()?.parse().ok()?;
+                    let title = c["tags"]["title"].as_str().unwrap_or_default().trim().to_string();
+                    Some(Chapter { start, end, title })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(chapters)
+}
+
+/// Whether chapter titles should be translated (enabled by default)
+pub fn translation_enabled(app_handle: &tauri::AppHandle) -> bool {
+    settings::get(app_handle, SETTINGS_KEY).unwrap_or(true)
+}
+
+/// Enable or disable chapter title translation
+pub async fn set_translation_enabled(app_handle: &tauri::AppHandle, enabled: bool) -> Result<()> {
+    settings::set(app_handle, SETTINGS_KEY, &enabled).await
+}
+
+/// Translate all chapter titles in a single request. If the model doesn't
+/// return one line per chapter, the original titles are kept.
+pub async fn translate_titles(chapters: &[Chapter], target_language: &str, api_key: &str) -> Vec<Chapter> {
+    if chapters.iter().all(|c| c.title.is_empty()) {
+        return chapters.to_vec();
+    }
+
+    // Titles are sent as one numbered line each so they can be matched back
+    let text = chapters
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("{}. {}", i + 1, c.title))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let translated = match translate::translate_text(&text, target_language, api_key).await {
+        Ok(translated) => translated,
+        Err(e) => {
+            warn!("Failed to translate chapter titles: {}", e);
+            return chapters.to_vec();
+        }
+    };
+
+    let titles: Vec<String> = translated
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_once(". ") {
+            Some((number, title)) if number.chars().all(|c| c.is_ascii_digit()) => title.trim().to_string(),
+            _ => line.to_string(),
+        })
+        .collect();
+    if titles.len() != chapters.len() {
+        warn!(
+            "Chapter translation returned {} titles for {} chapters, keeping the original titles",
+            titles.len(),
+            chapters.len()
+        );
+        return chapters.to_vec();
+    }
+
+    info!("Translated {} chapter titles to {}", chapters.len(), target_language);
+    chapters
+        .iter()
+        .zip(titles)
+        .map(|(chapter, title)| Chapter { title, ..chapter.clone() })
+        .collect()
+}
+
+/// Escape a value for the FFMETADATA format
+fn escape_metadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Write chapters as an FFMETADATA file for `ffmpeg -map_chapters`
+pub async fn write_ffmetadata(chapters: &[Chapter], path: &Path) -> Result<()> {
+    let mut content = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        content.push_str("[CHAPTER]\nTIMEBASE=1/1000\n");
+        content.push_str(&format!("START={}\n", (chapter.start * 1000.0).round() as u64));
+        content.push_str(&format!("END={}\n", (chapter.end * 1000.0).round() as u64));
+        content.push_str(&format!("title={}\n", escape_metadata(&chapter.title)));
+    }
+    tokio::fs::write(path, content).await?;
+    Ok(())
+}
+
+/// Start and end of the selected chapters, which have to be adjacent so the
+/// clip is continuous
+pub fn selection_span(chapters: &[Chapter], selected: &[usize]) -> Result<(f64, f64)> {
+    let mut selected = selected.to_vec();
+    selected.sort_unstable();
+    selected.dedup();
+    let (Some(&first), Some(&last)) = (selected.first(), selected.last()) else {
+        return Err(anyhow!("No chapters selected"));
+    };
+    if last >= chapters.len() {
+        return Err(anyhow!("The video has {} chapters, chapter {} doesn't exist", chapters.len(), last + 1));
+    }
+    if last - first + 1 != selected.len() {
+        return Err(anyhow!("Only adjacent chapters can be processed together"));
+    }
+    Ok((chapters[first].start, chapters[last].end))
+}
+
+/// The chapters inside a span, moved so the span starts at zero
+pub fn clip(chapters: &[Chapter], start: f64, end: f64) -> Vec<Chapter> {
+    chapters
+        .iter()
+        .filter(|c| c.end > start && c.start < end)
+        .map(|c| Chapter {
+            start: c.start.max(start) - start,
+            end: c.end.min(end) - start,
+            title: c.title.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(start: f64, end: f64, title: &str) -> Chapter {
+        Chapter { start, end, title: title.to_string() }
+    }
+
+    #[test]
+    fn selected_chapters_become_a_clip() {
+        let chapters = vec![chapter(0.0, 60.0, "Intro"), chapter(60.0, 150.0, "Setup"), chapter(150.0, 300.0, "Demo")];
+        assert_eq!(selection_span(&chapters, &[2, 1]).unwrap(), (60.0, 300.0));
+        assert!(selection_span(&chapters, &[0, 2]).is_err());
+        assert!(selection_span(&chapters, &[3]).is_err());
+
+        let clipped = clip(&chapters, 60.0, 300.0);
+        assert_eq!(clipped, vec![chapter(0.0, 90.0, "Setup"), chapter(90.0, 240.0, "Demo")]);
+    }
+}
@@ -0,0 +1,117 @@
+//! Character encoding of subtitle files.
+//!
+//! Subtitles supplied by users are often not UTF-8: Russian SRT files tend to
+//! be CP1251, Japanese ones Shift-JIS. Without transcoding they turn into
+//! mojibake before they reach translation and TTS. Files are decoded by BOM,
+//! then as UTF-16 without BOM, then as UTF-8; legacy encodings are guessed by
+//! chardetng, the detector Firefox uses. A manual override skips detection
+//! entirely.
+
+use anyhow::{anyhow, Result};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use log::{debug, info};
+use std::path::Path;
+
+const BOM: char = '\u{feff}';
+
+/// Guess the encoding of raw subtitle bytes
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    // UTF-16 without BOM, checked first as it is often valid UTF-8 as well:
+    // the timings and spaces put a zero into every other byte, while 8-bit
+    // encodings have next to no zeros in text
+    let zeros_even = bytes.iter().step_by(2).filter(|b| **b == 0).count();
+    let zeros_odd = bytes.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    let min_zeros = bytes.len() / 8;
+    if zeros_odd > min_zeros && zeros_even <= zeros_odd / 8 {
+        return UTF_16LE;
+    }
+    if zeros_even > min_zeros && zeros_odd <= zeros_even / 8 {
+        return UTF_16BE;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+
+    // chardetng does not detect UTF-16, and UTF-8 has been ruled out above
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, false);
+    debug!("Detected charset {}", encoding.name());
+    encoding
+}
+
+/// Decode subtitle bytes to a string without BOM. `override_encoding` is a
+/// WHATWG label such as "windows-1251" or "shift_jis".
+pub fn decode(bytes: &[u8], override_encoding: Option<&str>) -> Result<String> {
+    let encoding = match override_encoding.map(str::trim).filter(|label| !label.is_empty()) {
+        Some(label) => Encoding::for_label(label.as_bytes())
+            .ok_or_else(|| anyhow!("Unknown character encoding: {}", label))?,
+        None => detect(bytes),
+    };
+    if encoding != UTF_8 {
+        info!("Decoding subtitles as {}", encoding.name());
+    }
+
+    // decode() strips a BOM matching the encoding (and prefers a present BOM over it)
+    let (text, _, _) = encoding.decode(bytes);
+    Ok(text.trim_start_matches(BOM).to_string())
+}
+
+/// Read a subtitle file as UTF-8 text, transcoding it if necessary
+pub async fn read_subtitle_file(path: &Path, override_encoding: Option<&str>) -> Result<String> {
+    let bytes = tokio::fs::read(path).await?;
+    decode(&bytes, override_encoding)
+}
+
+/// Blocking variant of [`read_subtitle_file`]
+pub fn read_subtitle_file_sync(path: &Path, override_encoding: Option<&str>) -> Result<String> {
+    let bytes = std::fs::read(path)?;
+    decode(&bytes, override_encoding)
+}
+
+/// UTF-8 bytes for writing a subtitle file. Stray BOMs (e.g. left over from
+/// concatenated sources) are removed; a single leading BOM is written only
+/// when requested, since some players misread the WEBVTT header after it.
+pub fn to_utf8_bytes(content: &str, with_bom: bool) -> Vec<u8> {
+    let cleaned: String = content.chars().filter(|c| *c != BOM).collect();
+    let mut bytes = Vec::with_capacity(cleaned.len() + 3);
+    if with_bom {
+        bytes.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+    }
+    bytes.extend_from_slice(cleaned.as_bytes());
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::WINDOWS_1251;
+
+    const SRT: &str = "1\n00:00:01,000 --> 00:00:03,500\nСъешь же ещё этих мягких французских булок\n";
+
+    #[test]
+    fn detects_legacy_and_bomless_encodings() {
+        let (cp1251, _, _) = WINDOWS_1251.encode(SRT);
+        assert_eq!(detect(&cp1251), WINDOWS_1251);
+        assert_eq!(decode(&cp1251, None).unwrap(), SRT);
+
+        let utf16le: Vec<u8> = SRT.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(detect(&utf16le), UTF_16LE);
+        let utf16be: Vec<u8> = SRT.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(detect(&utf16be), UTF_16BE);
+
+        assert_eq!(detect(SRT.as_bytes()), UTF_8);
+        assert_eq!(decode(&to_utf8_bytes(SRT, true), None).unwrap(), SRT);
+    }
+
+    #[test]
+    fn override_skips_detection() {
+        let (cp1251, _, _) = WINDOWS_1251.encode(SRT);
+        assert_ne!(decode(&cp1251, Some("koi8-r")).unwrap(), SRT);
+        assert!(decode(&cp1251, Some("no-such-charset")).is_err());
+    }
+}
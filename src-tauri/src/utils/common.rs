@@ -1,6 +1,7 @@
 //! Common utility functions used across the application
 
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Sanitize filename to be safe for all operating systems.
 /// Converts the filename to lowercase and replaces special characters with underscores.
@@ -21,14 +22,24 @@ pub fn sanitize_filename(input: &str) -> String {
 
 /// Check if a file exists and has valid content (non-zero size)
 pub async fn check_file_exists_and_valid(path: &Path) -> bool {
-    if let Ok(metadata) = tokio::fs::metadata(path).await {
-        if metadata.is_file() && metadata.len() > 0 {
-            return true;
-        }
+    if let Ok(metadata) = tokio::fs::metadata(path).await
+        && metadata.is_file() && metadata.len() > 0
+    {
+        return true;
     }
     false
 }
 
+/// Seconds since the Unix epoch, 0 if the clock is set before it
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Milliseconds since the Unix epoch, 0 if the clock is set before it
+pub fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,113 @@
+//! How sure Whisper was about each transcribed cue.
+//!
+//! verbose_json reports an average log probability and a no-speech
+//! probability per segment. They are combined into one score per cue and kept
+//! in a sidecar file next to the VTT (`<name>.confidence.json`), because VTT
+//! has no place for them. Translation asks the model to be careful with
+//! low-confidence cues, the timing report flags them for review and
+//! `get_transcript_confidence` returns the scores for a heatmap.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Score below which a cue is flagged as possibly mis-transcribed
+pub const LOW_CONFIDENCE: f32 = 0.5;
+/// Cues of the sidecar and of a VTT are matched by start time within this tolerance
+const MATCH_TOLERANCE: f32 = 0.05;
+
+/// Confidence of one transcribed cue
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CueConfidence {
+    pub start: f32,
+    pub end: f32,
+    pub avg_logprob: f32,
+    pub no_speech_prob: f32,
+    /// 0..1, probability of the tokens discounted by the chance of no speech
+    pub score: f32,
+    pub low: bool,
+}
+
+impl CueConfidence {
+    pub fn new(start: f32, end: f32, avg_logprob: f32, no_speech_prob: f32) -> Self {
+        let score = (avg_logprob.min(0.0).exp() * (1.0 - no_speech_prob.clamp(0.0, 1.0))).clamp(0.0, 1.0);
+        Self { start, end, avg_logprob, no_speech_prob, score, low: score < LOW_CONFIDENCE }
+    }
+}
+
+/// Scores of a transcript, returned by `get_transcript_confidence`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TranscriptConfidence {
+    pub cues: Vec<CueConfidence>,
+    pub mean_score: f32,
+    pub low_count: usize,
+}
+
+impl TranscriptConfidence {
+    pub fn new(cues: Vec<CueConfidence>) -> Self {
+        let mean_score = if cues.is_empty() {
+            0.0
+        } else {
+            cues.iter().map(|cue| cue.score).sum::<f32>() / cues.len() as f32
+        };
+        let low_count = cues.iter().filter(|cue| cue.low).count();
+        Self { cues, mean_score, low_count }
+    }
+
+    /// Confidence of the cue starting at `start`
+    pub fn at(&self, start: f32) -> Option<&CueConfidence> {
+        self.cues.iter().find(|cue| (cue.start - start).abs() <= MATCH_TOLERANCE)
+    }
+
+    /// Whether the cue starting at `start` is flagged as low confidence
+    pub fn is_low(&self, start: f32) -> bool {
+        self.at(start).is_some_and(|cue| cue.low)
+    }
+}
+
+/// Sidecar file of a VTT
+pub fn sidecar_path(vtt_path: &Path) -> PathBuf {
+    vtt_path.with_extension("confidence.json")
+}
+
+pub async fn save(vtt_path: &Path, confidence: &TranscriptConfidence) -> Result<()> {
+    let json = serde_json::to_string_pretty(confidence)
+        .map_err(|e| anyhow!("Failed to serialize transcript confidence: {}", e))?;
+    tokio::fs::write(sidecar_path(vtt_path), json).await?;
+    Ok(())
+}
+
+/// Scores of a VTT, None when it didn't come from Whisper
+pub async fn load(vtt_path: &Path) -> Option<TranscriptConfidence> {
+    let content = tokio::fs::read_to_string(sidecar_path(vtt_path)).await.ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_combine_logprob_and_no_speech() {
+        let sure = CueConfidence::new(0.0, 1.0, -0.1, 0.01);
+        assert!(sure.score > 0.85 && !sure.low);
+
+        let mumbled = CueConfidence::new(1.0, 2.0, -1.2, 0.1);
+        assert!(mumbled.low);
+
+        // Probably not speech at all, however confident the tokens are
+        assert!(CueConfidence::new(2.0, 3.0, -0.05, 0.8).low);
+    }
+
+    #[test]
+    fn cues_are_matched_by_start() {
+        let transcript = TranscriptConfidence::new(vec![
+            CueConfidence::new(0.0, 1.0, -0.1, 0.0),
+            CueConfidence::new(1.5, 2.0, -2.0, 0.0),
+        ]);
+        assert_eq!(transcript.low_count, 1);
+        assert!(transcript.is_low(1.52));
+        assert!(!transcript.is_low(0.0));
+        assert!(transcript.at(5.0).is_none());
+    }
+}
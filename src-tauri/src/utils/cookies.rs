@@ -0,0 +1,257 @@
+//! Where yt-dlp gets YouTube cookies from.
+//!
+//! Reading cookies straight from a browser works differently on every
+//! platform: Chromium browsers on macOS ask for Keychain access, on Linux they
+//! need the Secret Service or KWallet, and on Windows recent Chrome builds use
+//! app-bound encryption yt-dlp can't undo. A running Chromium browser also
+//! keeps its cookie database locked on Windows. Each platform therefore gets
+//! its own ordered list of browsers, limited to those with a profile on disk,
+//! and yt-dlp failures are sorted into [`CookieError`] so the user gets an
+//! actionable message. When no browser works the user is offered to import a
+//! `cookies.txt` export instead, which behaves the same everywhere.
+
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+const COOKIES_FILE: &str = "youtube_cookies.txt";
+
+/// Where yt-dlp reads cookies from
+#[derive(Debug, Clone, PartialEq)]
+pub enum CookieSource {
+    Browser(String),
+    /// A Netscape-format cookies.txt, imported by the user
+    File(PathBuf),
+    /// No cookies, enough for most public videos
+    Anonymous,
+}
+
+impl CookieSource {
+    /// yt-dlp arguments selecting this source
+    pub fn ytdlp_args(&self) -> Vec<String> {
+        match self {
+            CookieSource::Browser(browser) => vec!["--cookies-from-browser".to_string(), browser.clone()],
+            CookieSource::File(path) => vec!["--cookies".to_string(), path.to_string_lossy().to_string()],
+            CookieSource::Anonymous => Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            CookieSource::Browser(browser) => browser.clone(),
+            CookieSource::File(_) => "cookies.txt".to_string(),
+            CookieSource::Anonymous => "no cookies".to_string(),
+        }
+    }
+}
+
+/// Why cookies couldn't be read
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CookieError {
+    #[error("{browser} keeps its cookie database locked while running, close it and try again")]
+    ProfileLocked { browser: String },
+    #[error("Access to the {browser} cookie encryption key was denied ({store})")]
+    KeystoreDenied { browser: String, store: String },
+    #[error("{browser} encrypts cookies in a way yt-dlp can't read on this system")]
+    DecryptionUnsupported { browser: String },
+    #[error("No {browser} profile with cookies was found")]
+    ProfileNotFound { browser: String },
+    #[error("The cookies file is invalid or expired: {reason}")]
+    InvalidCookiesFile { reason: String },
+}
+
+/// Name of the store that protects browser cookie keys on this platform
+fn keystore_name() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "macOS Keychain"
+    } else if cfg!(target_os = "windows") {
+        "Windows DPAPI"
+    } else {
+        "Secret Service / KWallet"
+    }
+}
+
+/// Sort a yt-dlp failure into a cookie error. None means the failure isn't
+/// about cookies (the video is private, the network is down, ...).
+pub fn classify_failure(source: &CookieSource, stderr: &str) -> Option<CookieError> {
+    let lower = stderr.to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|needle| lower.contains(needle));
+    let browser = source.name();
+
+    if let CookieSource::File(_) = source {
+        if has(&["sign in to confirm", "cookies are no longer valid", "invalid netscape format", "does not look like a netscape format"]) {
+            return Some(CookieError::InvalidCookiesFile { reason: stderr.lines().last().unwrap_or_default().trim().to_string() });
+        }
+        return None;
+    }
+    if !matches!(source, CookieSource::Browser(_)) {
+        return None;
+    }
+
+    if has(&["could not copy", "database is locked", "permission denied"]) && has(&["cookie"]) {
+        Some(CookieError::ProfileLocked { browser })
+    } else if has(&["app-bound", "failed to decrypt with dpapi"]) {
+        Some(CookieError::DecryptionUnsupported { browser })
+    } else if has(&["keychain", "keyring", "secretstorage", "kwallet", "cannot decrypt v1", "dpapi"]) {
+        Some(CookieError::KeystoreDenied { browser, store: keystore_name().to_string() })
+    } else if has(&["could not find", "no such file"]) && has(&["cookie", "profile"]) {
+        Some(CookieError::ProfileNotFound { browser })
+    } else {
+        None
+    }
+}
+
+/// Profile directories of a browser on this platform
+fn profile_dirs(app_handle: &tauri::AppHandle, browser: &str) -> Vec<PathBuf> {
+    let path = app_handle.path();
+    let home = path.home_dir().ok();
+    let join = |base: Option<PathBuf>, rel: &str| base.map(|base| base.join(rel));
+
+    let candidates: Vec<Option<PathBuf>> = if cfg!(target_os = "macos") {
+        let support = path.data_dir().ok();
+        match browser {
+            "chrome" => vec![join(support, "Google/Chrome")],
+            "firefox" => vec![join(support, "Firefox/Profiles")],
+            "brave" => vec![join(support, "BraveSoftware/Brave-Browser")],
+            "edge" => vec![join(support, "Microsoft Edge")],
+            "safari" => vec![join(home, "Library/Containers/com.apple.Safari/Data/Library/Cookies")],
+            _ => Vec::new(),
+        }
+    } else if cfg!(target_os = "windows") {
+        let local = path.local_data_dir().ok();
+        let roaming = path.data_dir().ok();
+        match browser {
+            "firefox" => vec![join(roaming, "Mozilla/Firefox/Profiles")],
+            "edge" => vec![join(local, "Microsoft/Edge/User Data")],
+            "chrome" => vec![join(local, "Google/Chrome/User Data")],
+            "brave" => vec![join(local, "BraveSoftware/Brave-Browser/User Data")],
+            _ => Vec::new(),
+        }
+    } else {
+        let config = path.config_dir().ok();
+        match browser {
+            "firefox" => vec![
+                join(home.clone(), ".mozilla/firefox"),
+                join(home.clone(), "snap/firefox/common/.mozilla/firefox"),
+                join(home, ".var/app/org.mozilla.firefox/.mozilla/firefox"),
+            ],
+            "chrome" => vec![join(config, "google-chrome")],
+            "chromium" => vec![join(config.clone(), "chromium"), join(home, "snap/chromium/common/chromium")],
+            "brave" => vec![join(config, "BraveSoftware/Brave-Browser")],
+            "edge" => vec![join(config, "microsoft-edge")],
+            _ => Vec::new(),
+        }
+    };
+    candidates.into_iter().flatten().collect()
+}
+
+/// Browsers worth trying on this platform, most reliable first: Firefox
+/// doesn't encrypt its cookies, so it comes first wherever Chromium browsers
+/// need an OS key store; Safari is tried early on macOS for the same reason.
+fn platform_browsers() -> &'static [&'static str] {
+    if cfg!(target_os = "macos") {
+        &["safari", "firefox", "chrome", "brave", "edge"]
+    } else if cfg!(target_os = "windows") {
+        // Chrome and Edge are last, their app-bound encryption usually defeats yt-dlp
+        &["firefox", "brave", "edge", "chrome"]
+    } else {
+        &["firefox", "chrome", "chromium", "brave", "edge"]
+    }
+}
+
+/// Whether trying a browser may trigger a Keychain prompt
+pub fn needs_keychain_prompt(source: &CookieSource) -> bool {
+    cfg!(target_os = "macos") && matches!(source, CookieSource::Browser(browser) if browser != "safari" && browser != "firefox")
+}
+
+/// Browser sources with a profile on disk, in the platform's preferred order
+pub fn browser_strategies(app_handle: &tauri::AppHandle) -> Vec<CookieSource> {
+    platform_browsers()
+        .iter()
+        .filter(|browser| {
+            let found = profile_dirs(app_handle, browser).iter().any(|dir| dir.exists());
+            if !found {
+                debug!("Skipping {} cookies, no profile found", browser);
+            }
+            found
+        })
+        .map(|browser| CookieSource::Browser(browser.to_string()))
+        .collect()
+}
+
+/// Path where an imported cookies.txt is kept
+pub fn imported_file_path(app_handle: &tauri::AppHandle) -> Result<PathBuf> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow!("Failed to resolve app data directory: {}", e))?;
+    Ok(data_dir.join(COOKIES_FILE))
+}
+
+/// Check that content is a Netscape cookies.txt with YouTube cookies
+fn validate_cookies_file(content: &str) -> std::result::Result<(), CookieError> {
+    let invalid = |reason: &str| CookieError::InvalidCookiesFile { reason: reason.to_string() };
+    let mut youtube_cookies = 0usize;
+    for line in content.lines() {
+        // "#HttpOnly_" prefixes real cookie lines, other comments are skipped
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 {
+            return Err(invalid("not a Netscape cookies.txt (expected 7 tab-separated fields per line)"));
+        }
+        if fields[0].trim_start_matches('.').ends_with("youtube.com") {
+            youtube_cookies += 1;
+        }
+    }
+    if youtube_cookies == 0 {
+        return Err(invalid("the file has no youtube.com cookies"));
+    }
+    Ok(())
+}
+
+/// Validate a cookies.txt export and copy it into the app data directory
+pub async fn import_file(app_handle: &tauri::AppHandle, source: &Path) -> Result<PathBuf> {
+    let content = tokio::fs::read_to_string(source)
+        .await
+        .map_err(|e| anyhow!("Failed to read {}: {}", source.display(), e))?;
+    validate_cookies_file(&content)?;
+
+    let target = imported_file_path(app_handle)?;
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(&target, content).await?;
+    info!("Imported YouTube cookies from {}", source.display());
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_failure() {
+        let chrome = CookieSource::Browser("chrome".to_string());
+        let locked = "ERROR: Could not copy Chrome cookie database. See https://github.com/yt-dlp/yt-dlp/issues/7271";
+        assert!(matches!(classify_failure(&chrome, locked), Some(CookieError::ProfileLocked { .. })));
+        let dpapi = "ERROR: Failed to decrypt with DPAPI. See https://github.com/yt-dlp/yt-dlp/issues/10927";
+        assert!(matches!(classify_failure(&chrome, dpapi), Some(CookieError::DecryptionUnsupported { .. })));
+        let keyring = "WARNING: cannot decrypt v11 cookies: no key found";
+        assert!(matches!(classify_failure(&chrome, keyring), Some(CookieError::KeystoreDenied { .. })));
+        assert!(classify_failure(&chrome, "ERROR: [youtube] abc: Video unavailable").is_none());
+    }
+
+    #[test]
+    fn test_validate_cookies_file() {
+        let valid = "# Netscape HTTP Cookie File\n.youtube.com\tTRUE\t/\tTRUE\t1799999999\tSID\tvalue\n#HttpOnly_.youtube.com\tTRUE\t/\tTRUE\t1799999999\tHSID\tvalue\n";
+        assert!(validate_cookies_file(valid).is_ok());
+        assert!(validate_cookies_file("# Netscape HTTP Cookie File\n.example.com\tTRUE\t/\tTRUE\t0\tid\tv\n").is_err());
+        assert!(validate_cookies_file("SID=value; HSID=value").is_err());
+    }
+}
@@ -0,0 +1,347 @@
+//! Explanations for failed jobs.
+//!
+//! `diagnose` gathers what is known about a failed job — the error with any
+//! tool stderr it carries, the step it failed in and its last progress
+//! messages — and runs it through a small set of local rules. Specific rules
+//! (an exhausted quota, locked browser cookies, a missing tool) come first;
+//! anything they don't recognise falls back to the failure category of
+//! `perf_stats::classify_failure`. Optionally the collected context is also
+//! summarised by an OpenAI model, the local explanation stays the primary one.
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use serde::Serialize;
+use serde_json::json;
+use std::time::Duration;
+
+use crate::utils::events::BufferedEvent;
+use crate::utils::job_state::{JobState, JobStateInfo};
+use crate::utils::openai_connection::{self, OpenAiAuth};
+use crate::utils::perf_stats::{self, FailureCategory};
+
+/// Progress messages included in the context
+const MAX_MESSAGES: usize = 15;
+/// Tools the pipeline can't run without
+const REQUIRED_TOOLS: &[&str] = &["yt-dlp", "ffmpeg", "ffprobe"];
+
+/// Something the user can do about a failure
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NextAction {
+    Retry,
+    ResumeWithNewApiKey,
+    CheckApiKey,
+    InstallTools,
+    CloseBrowser,
+    ImportCookies,
+    FreeDiskSpace,
+    CheckNetwork,
+    TryAnotherVideo,
+    ReportBug,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SuggestedAction {
+    pub action: NextAction,
+    pub label: String,
+}
+
+/// What is known about a failed job
+#[derive(Debug, Serialize, Clone)]
+pub struct FailureContext {
+    pub job_id: String,
+    pub state: JobState,
+    /// Pipeline step the job was in when it failed
+    pub failed_step: Option<JobState>,
+    pub error: String,
+    /// Last progress messages, oldest first
+    pub messages: Vec<String>,
+    pub missing_tools: Vec<String>,
+}
+
+/// Result of `diagnose_failure`
+#[derive(Debug, Serialize, Clone)]
+pub struct Diagnosis {
+    pub category: FailureCategory,
+    pub title: String,
+    pub explanation: String,
+    pub actions: Vec<SuggestedAction>,
+    /// Summary written by the language model, when asked for and available
+    pub summary: Option<String>,
+    pub context: FailureContext,
+}
+
+fn action(action: NextAction, label: &str) -> SuggestedAction {
+    SuggestedAction { action, label: label.to_string() }
+}
+
+/// Human-readable progress messages of the buffered events
+fn messages(events: &[BufferedEvent]) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    for event in events {
+        let text = ["error", "message", "status"]
+            .iter()
+            .find_map(|field| event.payload[*field].as_str())
+            .filter(|text| !text.trim().is_empty());
+        if let Some(text) = text {
+            let line = format!("{}: {}", event.event, text.trim());
+            // Progress repeats the same status many times
+            if messages.last() != Some(&line) {
+                messages.push(line);
+            }
+        }
+    }
+    let excess = messages.len().saturating_sub(MAX_MESSAGES);
+    messages.drain(..excess);
+    messages
+}
+
+/// Collect the context of a failed job
+pub fn collect(
+    info: &JobStateInfo,
+    events: &[BufferedEvent],
+    tool_available: impl Fn(&str) -> bool,
+) -> Result<FailureContext> {
+    if info.state != JobState::Failed {
+        return Err(anyhow!("Job {} has not failed, it is {:?}", info.job_id, info.state));
+    }
+    Ok(FailureContext {
+        job_id: info.job_id.clone(),
+        state: info.state,
+        failed_step: info.previous,
+        error: info.error.clone().unwrap_or_default(),
+        messages: messages(events),
+        missing_tools: REQUIRED_TOOLS
+            .iter()
+            .filter(|tool| !tool_available(tool))
+            .map(|tool| tool.to_string())
+            .collect(),
+    })
+}
+
+/// Explain a failure with the local rules
+pub fn explain(context: FailureContext) -> Diagnosis {
+    let error = context.error.to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|n| error.contains(n));
+    let mut category = perf_stats::classify_failure(&context.error);
+    let step = context
+        .failed_step
+        .map(|step| format!("{:?}", step).to_lowercase())
+        .unwrap_or_else(|| "an unknown step".to_string());
+
+    let (title, explanation, actions) = if !context.missing_tools.is_empty() {
+        category = FailureCategory::MissingTool;
+        (
+            "Required tools are missing",
+            format!(
+                "{} could not be found, the pipeline needs it to download and process media.",
+                context.missing_tools.join(" and ")
+            ),
+            vec![
+                action(NextAction::InstallTools, "Reinstall the missing tools from the settings"),
+                action(NextAction::Retry, "Run the job again"),
+            ],
+        )
+    } else if crate::utils::quota::is_quota_error(&context.error) {
+        category = FailureCategory::RateLimit;
+        (
+            "OpenAI quota exhausted",
+            format!("The OpenAI account ran out of credit during {}. Work done so far is kept.", step),
+            vec![
+                action(NextAction::ResumeWithNewApiKey, "Add credit or resume with another API key"),
+                action(NextAction::CheckApiKey, "Check the billing page of the OpenAI account"),
+            ],
+        )
+    } else if has(&["locked", "could not copy", "database is locked"]) && has(&["cookie"]) {
+        (
+            "Browser cookies are locked",
+            "The browser keeps its cookie database locked while it runs, so yt-dlp could not read it.".to_string(),
+            vec![
+                action(NextAction::CloseBrowser, "Close the browser and retry"),
+                action(NextAction::ImportCookies, "Import a cookies.txt file instead"),
+            ],
+        )
+    } else if has(&["sign in to confirm", "confirm you're not a bot", "cookies"]) {
+        category = FailureCategory::Authentication;
+        (
+            "YouTube asks for a signed-in session",
+            "YouTube refused the download without the cookies of a signed-in browser.".to_string(),
+            vec![
+                action(NextAction::ImportCookies, "Import cookies from a browser signed in to YouTube"),
+                action(NextAction::Retry, "Retry after signing in"),
+            ],
+        )
+    } else if has(&["video unavailable", "private video", "has been removed", "members-only"]) {
+        category = FailureCategory::Media;
+        (
+            "The video can't be downloaded",
+            "The video is private, removed or restricted for this account.".to_string(),
+            vec![action(NextAction::TryAnotherVideo, "Check the URL or try another video")],
+        )
+    } else {
+        let (title, explanation, actions) = match category {
+            FailureCategory::RateLimit => (
+                "OpenAI is rate limiting requests",
+                format!("Too many requests were sent to OpenAI during {}.", step),
+                vec![action(NextAction::Retry, "Wait a minute and run the job again")],
+            ),
+            FailureCategory::Authentication => (
+                "The API key was rejected",
+                "OpenAI rejected the API key, it may be revoked or lack access to the models in use.".to_string(),
+                vec![
+                    action(NextAction::CheckApiKey, "Check the API key in the settings"),
+                    action(NextAction::Retry, "Run the job again"),
+                ],
+            ),
+            FailureCategory::MissingTool => (
+                "A required tool is missing",
+                format!("A program needed during {} could not be started.", step),
+                vec![action(NextAction::InstallTools, "Reinstall the tools from the settings")],
+            ),
+            FailureCategory::Disk => (
+                "Files could not be written",
+                "The disk is full or the output folder is not writable.".to_string(),
+                vec![
+                    action(NextAction::FreeDiskSpace, "Free disk space or choose another output folder"),
+                    action(NextAction::Retry, "Run the job again"),
+                ],
+            ),
+            FailureCategory::Network => (
+                "A network request failed",
+                format!("A connection failed or timed out during {}.", step),
+                vec![
+                    action(NextAction::CheckNetwork, "Check the connection, a VPN may be needed"),
+                    action(NextAction::Retry, "Run the job again"),
+                ],
+            ),
+            FailureCategory::Media => (
+                "The media could not be processed",
+                format!("ffmpeg could not process the media during {}.", step),
+                vec![
+                    action(NextAction::TryAnotherVideo, "Try another video or format"),
+                    action(NextAction::ReportBug, "Report the error with the details below"),
+                ],
+            ),
+            FailureCategory::Other => (
+                "The job failed",
+                format!("The job failed during {} for a reason the app doesn't recognise.", step),
+                vec![
+                    action(NextAction::Retry, "Run the job again"),
+                    action(NextAction::ReportBug, "Report the error with the details below"),
+                ],
+            ),
+        };
+        (title, explanation, actions)
+    };
+
+    debug!("Diagnosed job {} as {:?}: {}", context.job_id, category, title);
+    Diagnosis {
+        category,
+        title: title.to_string(),
+        explanation,
+        actions,
+        summary: None,
+        context,
+    }
+}
+
+/// Ask an OpenAI model for a short plain-language summary of the failure
+pub async fn summarize(diagnosis: &Diagnosis, api_key: &str) -> Result<String> {
+    let context = &diagnosis.context;
+    let prompt = format!(
+        "A video dubbing job failed during {:?}.\nError:\n{}\n\nLast progress messages:\n{}\n\n\
+        Local diagnosis: {}. {}\n\nExplain in two or three sentences what most likely went wrong \
+        and what the user should do next.",
+        context.failed_step,
+        context.error,
+        context.messages.join("\n"),
+        diagnosis.title,
+        diagnosis.explanation
+    );
+    let request = json!({
+        "model": "gpt-4o-mini",
+        "messages": [
+            { "role": "system", "content": "You help users of a desktop video dubbing app fix failed jobs. Be brief and concrete." },
+            { "role": "user", "content": prompt },
+        ],
+        "temperature": 0.2,
+    });
+
+    let response = openai_connection::client()
+        .post(openai_connection::endpoint("/chat/completions"))
+        .openai_auth(api_key)
+        .json(&request)
+        .timeout(Duration::from_secs(60))
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        warn!("Failure summary request failed: HTTP {}, body: {}", status, error_text);
+        return Err(anyhow!("OpenAI API error: HTTP {}", status));
+    }
+    let completion: serde_json::Value = response.json().await?;
+    completion["choices"][0]["message"]["content"]
+        .as_str()
+        .map(|content| content.trim().to_string())
+        .ok_or_else(|| anyhow!("OpenAI API returned no summary"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed(previous: JobState, error: &str) -> JobStateInfo {
+        JobStateInfo {
+            job_id: "job".to_string(),
+            state: JobState::Failed,
+            previous: Some(previous),
+            updated_at: 0,
+            error: Some(error.to_string()),
+            history: Vec::new(),
+        }
+    }
+
+    fn event(seq: u64, status: &str) -> BufferedEvent {
+        BufferedEvent {
+            seq,
+            timestamp: 0,
+            event: "tts-progress".to_string(),
+            payload: json!({ "status": status, "progress": 10.0 }),
+        }
+    }
+
+    #[test]
+    fn specific_rules_win_over_the_category() {
+        let info = failed(JobState::GeneratingSpeech, "HTTP 429: You exceeded your current quota");
+        let context = collect(&info, &[], |_| true).unwrap();
+        let diagnosis = explain(context);
+        assert_eq!(diagnosis.category, FailureCategory::RateLimit);
+        assert_eq!(diagnosis.actions[0].action, NextAction::ResumeWithNewApiKey);
+    }
+
+    #[test]
+    fn missing_tools_are_reported_first() {
+        let info = failed(JobState::Downloading, "No such file or directory (os error 2)");
+        let context = collect(&info, &[], |tool| tool != "yt-dlp").unwrap();
+        let diagnosis = explain(context);
+        assert_eq!(diagnosis.category, FailureCategory::MissingTool);
+        assert_eq!(diagnosis.context.missing_tools, vec!["yt-dlp".to_string()]);
+    }
+
+    #[test]
+    fn repeated_messages_are_collapsed() {
+        let events = vec![event(0, "Генерация TTS"), event(1, "Генерация TTS"), event(2, "Обработка аудио")];
+        let info = failed(JobState::GeneratingSpeech, "connection reset");
+        let context = collect(&info, &events, |_| true).unwrap();
+        assert_eq!(context.messages.len(), 2);
+        assert_eq!(explain(context).category, FailureCategory::Network);
+    }
+
+    #[test]
+    fn only_failed_jobs_are_diagnosed() {
+        let mut info = failed(JobState::Merging, "");
+        info.state = JobState::Completed;
+        assert!(collect(&info, &[], |_| true).is_err());
+    }
+}
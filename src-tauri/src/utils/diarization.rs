@@ -0,0 +1,339 @@
+//! Speaker diarization of transcribed subtitles.
+//!
+//! Whisper doesn't say who is speaking, so without diarization every cue of a
+//! dialog is dubbed by the same voice. After transcription the speech of each
+//! cue is described by a small acoustic embedding (mean and spread of its
+//! MFCCs over the voiced frames, standardized across the file) and the cues
+//! are clustered bottom-up with average linkage on cosine distance until the
+//! closest clusters are further apart than the threshold. Cues of a cluster
+//! get a `<v SPEAKER_n>` tag, numbered by first appearance; the translation
+//! keeps the tags and the synchronizer picks the voice of each speaker.
+//!
+//! This is local clustering, not a neural diarization model: it tells clearly
+//! different voices apart (a host and a guest, a man and a woman) and tends to
+//! merge similar ones. Subtitles that already name their speakers are kept.
+
+use anyhow::{anyhow, Result};
+use log::info;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::utils::tts::tts::{audio, vtt, SubtitleCue};
+
+/// Analysis frame, about 23 ms at 44.1 kHz
+const FRAME: usize = 1024;
+const MEL_BANDS: usize = 26;
+/// Cepstral coefficients without the energy term c0
+const CEPSTRA: usize = 12;
+const MEL_LOW_HZ: f32 = 60.0;
+const MEL_HIGH_HZ: f32 = 7600.0;
+/// Frames this far below the loudest frame of a cue are pauses
+const VOICED_RANGE_DB: f32 = 30.0;
+/// Cues with fewer voiced frames take the speaker of their neighbour
+const MIN_VOICED_FRAMES: usize = 20;
+
+/// How cues are split between speakers
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DiarizationSettings {
+    pub enabled: bool,
+    /// Upper bound of speakers, 0 for no bound
+    pub max_speakers: usize,
+    /// Cosine distance up to which two clusters are one speaker, 0-2
+    pub threshold: f32,
+}
+
+impl Default for DiarizationSettings {
+    fn default() -> Self {
+        Self { enabled: false, max_speakers: 6, threshold: 0.6 }
+    }
+}
+
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// MFCC statistics of a stretch of speech
+struct Embedder {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// Triangular mel filters as (bin, weight) pairs
+    filters: Vec<Vec<(usize, f32)>>,
+    hop: usize,
+}
+
+impl Embedder {
+    fn new(sample_rate: u32) -> Self {
+        let fft = FftPlanner::<f32>::new().plan_fft_forward(FRAME);
+        let window = (0..FRAME)
+            .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (FRAME - 1) as f32).cos())
+            .collect();
+        let high = MEL_HIGH_HZ.min(sample_rate as f32 / 2.0);
+        let (low_mel, high_mel) = (hz_to_mel(MEL_LOW_HZ), hz_to_mel(high));
+        let bins: Vec<f32> = (0..MEL_BANDS + 2)
+            .map(|i| mel_to_hz(low_mel + (high_mel - low_mel) * i as f32 / (MEL_BANDS + 1) as f32))
+            .map(|hz| hz * FRAME as f32 / sample_rate as f32)
+            .collect();
+        let filters = bins
+            .windows(3)
+            .map(|edges| {
+                let (left, center, right) = (edges[0], edges[1], edges[2]);
+                (left.floor() as usize..=right.ceil() as usize)
+                    .filter_map(|bin| {
+                        let position = bin as f32;
+                        let weight = if position <= center {
+                            (position - left) / (center - left).max(f32::EPSILON)
+                        } else {
+                            (right - position) / (right - center).max(f32::EPSILON)
+                        };
+                        (weight > 0.0 && bin <= FRAME / 2).then_some((bin, weight))
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { fft, window, filters, hop: (sample_rate / 100).max(1) as usize }
+    }
+
+    fn embed(&self, samples: &[f32]) -> Option<Vec<f32>> {
+        if samples.len() < FRAME {
+            return None;
+        }
+        let mut frames: Vec<(f32, Vec<f32>)> = Vec::new();
+        let mut buffer = vec![Complex::new(0.0f32, 0.0); FRAME];
+        for start in (0..=samples.len() - FRAME).step_by(self.hop) {
+            for (i, value) in buffer.iter_mut().enumerate() {
+                *value = Complex::new(samples[start + i] * self.window[i], 0.0);
+            }
+            self.fft.process(&mut buffer);
+            let power: Vec<f32> = buffer[..=FRAME / 2].iter().map(|c| c.norm_sqr()).collect();
+            let log_mel = self
+                .filters
+                .iter()
+                .map(|filter| (filter.iter().map(|&(bin, weight)| power[bin] * weight).sum::<f32>() + 1e-10).ln())
+                .collect();
+            frames.push((power.iter().sum(), log_mel));
+        }
+
+        let loudest = frames.iter().map(|(energy, _)| *energy).fold(0.0, f32::max);
+        let floor = loudest * 10f32.powf(-VOICED_RANGE_DB / 10.0);
+        let cepstra: Vec<Vec<f32>> = frames
+            .iter()
+            .filter(|(energy, _)| loudest > 0.0 && *energy >= floor)
+            .map(|(_, log_mel)| {
+                (1..=CEPSTRA)
+                    .map(|k| {
+                        log_mel
+                            .iter()
+                            .enumerate()
+                            .map(|(m, value)| value * (std::f32::consts::PI * k as f32 * (m as f32 + 0.5) / MEL_BANDS as f32).cos())
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect();
+        if cepstra.len() < MIN_VOICED_FRAMES {
+            return None;
+        }
+
+        let count = cepstra.len() as f32;
+        let mean: Vec<f32> = (0..CEPSTRA).map(|k| cepstra.iter().map(|frame| frame[k]).sum::<f32>() / count).collect();
+        let spread = (0..CEPSTRA)
+            .map(|k| (cepstra.iter().map(|frame| (frame[k] - mean[k]).powi(2)).sum::<f32>() / count).sqrt());
+        Some(mean.iter().copied().chain(spread).collect())
+    }
+}
+
+/// Scale every dimension to zero mean and unit variance across the cues, so
+/// no coefficient dominates the distance just by its range
+fn standardize(embeddings: &mut [Vec<f32>]) {
+    let Some(dimensions) = embeddings.first().map(Vec::len) else { return };
+    let count = embeddings.len() as f32;
+    for d in 0..dimensions {
+        let mean = embeddings.iter().map(|e| e[d]).sum::<f32>() / count;
+        let deviation = (embeddings.iter().map(|e| (e[d] - mean).powi(2)).sum::<f32>() / count).sqrt();
+        for embedding in embeddings.iter_mut() {
+            embedding[d] = if deviation > f32::EPSILON { (embedding[d] - mean) / deviation } else { 0.0 };
+        }
+    }
+}
+
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+    if norms > f32::EPSILON { 1.0 - dot / norms } else { 1.0 }
+}
+
+/// Cluster of every embedding, numbered by first appearance
+fn cluster(embeddings: &[Vec<f32>], threshold: f32, max_speakers: usize) -> Vec<usize> {
+    let n = embeddings.len();
+    let mut distance: Vec<Vec<f32>> = embeddings
+        .iter()
+        .map(|a| embeddings.iter().map(|b| cosine_distance(a, b)).collect())
+        .collect();
+    let mut members: Vec<Vec<usize>> = (0..n).map(|i| vec![i]).collect();
+    let mut active = vec![true; n];
+    let mut count = n;
+    while count > 1 {
+        let mut closest = (f32::MAX, 0, 0);
+        for a in (0..n).filter(|&a| active[a]) {
+            for b in (a + 1..n).filter(|&b| active[b]) {
+                if distance[a][b] < closest.0 {
+                    closest = (distance[a][b], a, b);
+                }
+            }
+        }
+        let (nearest, a, b) = closest;
+        if nearest > threshold && (max_speakers == 0 || count <= max_speakers) {
+            break;
+        }
+        // Average linkage: the distance to the merged cluster is weighted by size
+        let (size_a, size_b) = (members[a].len() as f32, members[b].len() as f32);
+        for k in (0..n).filter(|&k| active[k] && k != a && k != b) {
+            let merged = (distance[a][k] * size_a + distance[b][k] * size_b) / (size_a + size_b);
+            distance[a][k] = merged;
+            distance[k][a] = merged;
+        }
+        let moved = std::mem::take(&mut members[b]);
+        members[a].extend(moved);
+        active[b] = false;
+        count -= 1;
+    }
+
+    let mut owner = vec![0; n];
+    for (root, cluster) in members.iter().enumerate() {
+        for &member in cluster {
+            owner[member] = root;
+        }
+    }
+    let mut labels: HashMap<usize, usize> = HashMap::new();
+    owner
+        .into_iter()
+        .map(|root| {
+            let next = labels.len();
+            *labels.entry(root).or_insert(next)
+        })
+        .collect()
+}
+
+/// Speaker of every cue. Cues too short to tell take the speaker of the
+/// previous cue, the first ones that of the next.
+fn label_cues(samples: &[f32], sample_rate: u32, cues: &[SubtitleCue], settings: &DiarizationSettings) -> Vec<usize> {
+    let embedder = Embedder::new(sample_rate);
+    let embeddings: Vec<Option<Vec<f32>>> = cues
+        .iter()
+        .map(|cue| {
+            let start = ((cue.start.max(0.0) * sample_rate as f32) as usize).min(samples.len());
+            let end = ((cue.end * sample_rate as f32) as usize).clamp(start, samples.len());
+            embedder.embed(&samples[start..end])
+        })
+        .collect();
+    let (indices, mut known): (Vec<usize>, Vec<Vec<f32>>) = embeddings
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, embedding)| embedding.map(|embedding| (i, embedding)))
+        .unzip();
+    if known.is_empty() {
+        return vec![0; cues.len()];
+    }
+    standardize(&mut known);
+    let clusters = cluster(&known, settings.threshold, settings.max_speakers);
+
+    let mut labels: Vec<Option<usize>> = vec![None; cues.len()];
+    for (index, label) in indices.into_iter().zip(clusters) {
+        labels[index] = Some(label);
+    }
+    let first = labels.iter().flatten().next().copied().unwrap_or(0);
+    let mut previous = first;
+    labels
+        .into_iter()
+        .map(|label| {
+            previous = label.unwrap_or(previous);
+            previous
+        })
+        .collect()
+}
+
+/// Tag the cues of a VTT file with the speakers heard in the audio. Returns
+/// the number of speakers; the file is only rewritten when there are several.
+pub async fn diarize(audio_path: &Path, vtt_path: &Path, settings: &DiarizationSettings) -> Result<usize> {
+    let mut cues = vtt::parse_vtt(vtt_path)?;
+    if cues.iter().any(|cue| cue.speaker.is_some()) {
+        info!("Subtitles already name their speakers, skipping diarization");
+        return Ok(0);
+    }
+    if cues.is_empty() {
+        return Ok(0);
+    }
+
+    let audio_path = audio_path.to_path_buf();
+    let settings = settings.clone();
+    let (cues_back, labels) = tokio::task::spawn_blocking(move || -> Result<(Vec<SubtitleCue>, Vec<usize>)> {
+        let (samples, sample_rate) = audio::decode_audio_file(&audio_path)?;
+        let labels = label_cues(&samples, sample_rate, &cues, &settings);
+        Ok((cues, labels))
+    })
+    .await
+    .map_err(|e| anyhow!("Diarization task failed: {}", e))??;
+    cues = cues_back;
+
+    let speakers = labels.iter().max().map(|max| max + 1).unwrap_or(0);
+    if speakers < 2 {
+        info!("Diarization found a single speaker, subtitles stay untagged");
+        return Ok(speakers);
+    }
+    for (cue, label) in cues.iter_mut().zip(&labels) {
+        cue.speaker = Some(format!("SPEAKER_{}", label + 1));
+    }
+    tokio::fs::write(vtt_path, vtt::write_vtt_str(&cues)).await?;
+    info!("Diarization found {} speakers in {} cues", speakers, cues.len());
+    Ok(speakers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn close_embeddings_share_a_speaker() {
+        let embeddings = vec![
+            vec![1.0, 0.1, 0.0],
+            vec![0.9, 0.0, 0.1],
+            vec![0.0, 1.0, 0.1],
+            vec![1.0, 0.0, 0.0],
+            vec![0.1, 0.9, 0.0],
+        ];
+        assert_eq!(cluster(&embeddings, 0.3, 0), vec![0, 0, 1, 0, 1]);
+        // The bound merges speakers even when they sound apart
+        assert_eq!(cluster(&embeddings, 0.3, 1), vec![0; 5]);
+    }
+
+    #[test]
+    fn voices_of_different_pitch_are_told_apart() {
+        let sample_rate = 16_000;
+        // A low and a high voice with harmonics, alternating cue by cue
+        let voice = |pitch: f32, seconds: f32| -> Vec<f32> {
+            (0..(seconds * sample_rate as f32) as usize)
+                .map(|i| {
+                    let t = i as f32 / sample_rate as f32;
+                    (1..6).map(|h| (2.0 * std::f32::consts::PI * pitch * h as f32 * t).sin() / h as f32).sum::<f32>() * 0.2
+                })
+                .collect()
+        };
+        let mut samples = Vec::new();
+        let mut cues = Vec::new();
+        for (i, pitch) in [110.0, 330.0, 110.0, 330.0].into_iter().enumerate() {
+            samples.extend(voice(pitch, 1.0));
+            cues.push(SubtitleCue { start: i as f32, end: i as f32 + 1.0, ..SubtitleCue::default() });
+        }
+        let labels = label_cues(&samples, sample_rate, &cues, &DiarizationSettings::default());
+        assert_eq!(labels, vec![0, 1, 0, 1]);
+    }
+}
@@ -0,0 +1,238 @@
+//! Rate-limited event emission to the webview.
+//!
+//! TTS, ffmpeg and uploads report progress many times a second, and on long
+//! jobs every one of those updates crossing the IPC bridge can freeze the UI.
+//! Progress events therefore go out at most once per window (100 ms by
+//! default) for each job and progress stream: an update arriving inside the
+//! window is held back, and a newer one replaces it, so only the latest value
+//! is delivered when the window closes. Any other event (job finished, merge
+//! complete, state changes) is sent right away, after the held-back progress,
+//! so the frontend never sees a stale progress value after a terminal event.
+
+use anyhow::{anyhow, Result};
+use log::warn;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Runtime};
+
+use crate::utils::settings;
+
+const SETTINGS_KEY: &str = "event-rate-limit";
+/// Default coalescing window in milliseconds
+pub const DEFAULT_WINDOW_MS: u64 = 100;
+/// Longer windows make progress bars visibly jump
+pub const MAX_WINDOW_MS: u64 = 2000;
+
+/// Events whose values supersede each other
+const COALESCED_EVENTS: &[&str] = &[
+    "download-progress",
+    "transcription-progress",
+    "translation-progress",
+    "tts-progress",
+    "merge-progress",
+    "pipeline-progress",
+    "remote-upload-progress",
+    "upload-progress",
+];
+
+static WINDOW_MS: AtomicU64 = AtomicU64::new(DEFAULT_WINDOW_MS);
+
+type Delivery = Box<dyn FnOnce() + Send>;
+
+/// Rate window of one progress stream
+#[derive(Default)]
+struct Slot {
+    last_sent: Option<Instant>,
+    pending: Option<Delivery>,
+    flush_scheduled: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Decision {
+    Send,
+    /// Hold the event back; a flush has to be scheduled after the given delay
+    /// unless one already is
+    Hold(Option<Duration>),
+}
+
+impl Slot {
+    fn decide(&mut self, now: Instant, window: Duration) -> Decision {
+        match self.last_sent {
+            Some(last) if now.duration_since(last) < window => {
+                if self.flush_scheduled {
+                    Decision::Hold(None)
+                } else {
+                    self.flush_scheduled = true;
+                    Decision::Hold(Some(window - now.duration_since(last)))
+                }
+            }
+            _ => {
+                // Whatever was still held back is older than this event
+                self.pending = None;
+                self.last_sent = Some(now);
+                Decision::Send
+            }
+        }
+    }
+}
+
+static SLOTS: Lazy<Mutex<HashMap<String, Slot>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Progress streams are told apart by job and, for downloads, by component
+fn slot_key(event: &str, payload: &serde_json::Value) -> String {
+    format!(
+        "{}|{}|{}",
+        event,
+        payload["job_id"].as_str().unwrap_or_default(),
+        payload["component"].as_str().unwrap_or_default()
+    )
+}
+
+fn deliver<R: Runtime, E: Emitter<R>>(target: &E, event: &str, payload: serde_json::Value) -> tauri::Result<()> {
+    target.emit(event, payload)
+}
+
+/// Send the held-back event of a stream
+fn flush(key: &str) {
+    let delivery = {
+        let Ok(mut slots) = SLOTS.lock() else { return };
+        let Some(slot) = slots.get_mut(key) else { return };
+        slot.flush_scheduled = false;
+        let delivery = slot.pending.take();
+        if delivery.is_some() {
+            slot.last_sent = Some(Instant::now());
+        }
+        delivery
+    };
+    if let Some(delivery) = delivery {
+        delivery();
+    }
+}
+
+/// Send every held-back event
+fn flush_all() {
+    let deliveries: Vec<Delivery> = {
+        let Ok(mut slots) = SLOTS.lock() else { return };
+        let now = Instant::now();
+        slots
+            .values_mut()
+            .filter_map(|slot| {
+                let delivery = slot.pending.take()?;
+                slot.last_sent = Some(now);
+                Some(delivery)
+            })
+            .collect()
+    };
+    for delivery in deliveries {
+        delivery();
+    }
+}
+
+/// Emit an event to the webview, coalescing progress events
+pub fn emit<R, E, S>(target: &E, event: &str, payload: S) -> tauri::Result<()>
+where
+    R: Runtime,
+    E: Emitter<R> + Clone + Send + 'static,
+    S: Serialize,
+{
+    let payload = serde_json::to_value(payload)?;
+    let window = Duration::from_millis(WINDOW_MS.load(Ordering::Relaxed));
+    if window.is_zero() || !COALESCED_EVENTS.contains(&event) {
+        flush_all();
+        return deliver(target, event, payload);
+    }
+
+    let key = slot_key(event, &payload);
+    {
+        let Ok(mut slots) = SLOTS.lock() else {
+            return deliver(target, event, payload);
+        };
+        let slot = slots.entry(key.clone()).or_default();
+        if let Decision::Hold(flush_in) = slot.decide(Instant::now(), window) {
+            let target = target.clone();
+            let event = event.to_string();
+            slot.pending = Some(Box::new(move || {
+                if let Err(e) = deliver(&target, &event, payload) {
+                    warn!("Failed to emit {}: {}", event, e);
+                }
+            }));
+            if let Some(delay) = flush_in {
+                tauri::async_runtime::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    flush(&key);
+                });
+            }
+            return Ok(());
+        }
+    }
+    deliver(target, event, payload)
+}
+
+/// Emit a progress event of a pipeline job, tagged with its id so that the
+/// listeners of the job can tell it from the progress of standalone commands
+pub fn emit_for_job<R, E, S>(target: &E, event: &str, job_id: Option<&str>, payload: S) -> tauri::Result<()>
+where
+    R: Runtime,
+    E: Emitter<R> + Clone + Send + 'static,
+    S: Serialize,
+{
+    let mut payload = serde_json::to_value(payload)?;
+    if let (Some(job_id), Some(fields)) = (job_id, payload.as_object_mut()) {
+        fields.insert("job_id".to_string(), job_id.into());
+    }
+    emit(target, event, payload)
+}
+
+/// Load the coalescing window from the settings store
+pub fn init(app_handle: &tauri::AppHandle) {
+    let window_ms: u64 = settings::get(app_handle, SETTINGS_KEY).unwrap_or(DEFAULT_WINDOW_MS);
+    WINDOW_MS.store(window_ms.min(MAX_WINDOW_MS), Ordering::Relaxed);
+}
+
+/// Current coalescing window in milliseconds, 0 when disabled
+pub fn window_ms() -> u64 {
+    WINDOW_MS.load(Ordering::Relaxed)
+}
+
+/// Change the coalescing window, 0 sends every event as it comes
+pub async fn set_window_ms(app_handle: &tauri::AppHandle, window_ms: u64) -> Result<()> {
+    if window_ms > MAX_WINDOW_MS {
+        return Err(anyhow!("The event rate limit must be at most {} ms", MAX_WINDOW_MS));
+    }
+    settings::set(app_handle, SETTINGS_KEY, &window_ms).await?;
+    WINDOW_MS.store(window_ms, Ordering::Relaxed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_coalesces_within_window() {
+        let window = Duration::from_millis(100);
+        let start = Instant::now();
+        let mut slot = Slot::default();
+        assert_eq!(slot.decide(start, window), Decision::Send);
+        // The first held-back event schedules the flush, later ones only replace it
+        assert_eq!(slot.decide(start + Duration::from_millis(30), window), Decision::Hold(Some(Duration::from_millis(70))));
+        assert_eq!(slot.decide(start + Duration::from_millis(60), window), Decision::Hold(None));
+
+        slot.pending = Some(Box::new(|| {}));
+        slot.flush_scheduled = false;
+        assert_eq!(slot.decide(start + Duration::from_millis(150), window), Decision::Send);
+        assert!(slot.pending.is_none());
+    }
+
+    #[test]
+    fn test_slot_key_separates_streams() {
+        let audio = serde_json::json!({ "component": "audio", "progress": 10.0 });
+        let video = serde_json::json!({ "component": "video", "progress": 10.0 });
+        assert_ne!(slot_key("download-progress", &audio), slot_key("download-progress", &video));
+        assert_eq!(slot_key("tts-progress", &serde_json::json!({})), "tts-progress||");
+    }
+}
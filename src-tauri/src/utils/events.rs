@@ -0,0 +1,265 @@
+//! Backend-side buffer of recent job events.
+//!
+//! Progress events are fire-and-forget: if the webview reloads or crashes while
+//! a job keeps running in the backend, everything emitted in between is lost.
+//! While a job runs, its progress events are also kept here (the last
+//! `MAX_EVENTS_PER_JOB` of them) so the frontend can replay them on reload and
+//! rebuild its progress UI.
+//!
+//! The total progress of the running job is also mirrored to the OS: the
+//! taskbar button on Windows and Linux, the dock icon on macOS, so a job can be
+//! followed while the window is minimized.
+//!
+//! Each step reports its own progress event (`download-progress`,
+//! `tts-progress`, ...) with at least `status` and `progress`. The progress
+//! tracker turns every one of them into a [`PipelineEvent`], the single shape
+//! the frontend needs to follow a whole job.
+
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use tauri::window::{ProgressBarState, ProgressBarStatus};
+use tauri::{EventId, Listener};
+
+use crate::utils::common::now_millis;
+use crate::utils::progress::PipelineStep;
+
+const MAX_EVENTS_PER_JOB: usize = 500;
+const MAX_JOBS: usize = 20;
+
+/// Events recorded for every job
+pub const JOB_EVENTS: &[&str] = &[
+    "job-started",
+    "job-state-changed",
+    "download-progress",
+    "transcription-progress",
+    "partial-subtitles-available",
+    "translation-progress",
+    "tts-progress",
+    "merge-progress",
+    "merge-complete",
+    "remote-upload-progress",
+    "pipeline-progress",
+    "job-finished",
+];
+
+/// Name of the event carrying [`PipelineEvent`]
+pub const PIPELINE_EVENT: &str = "pipeline-progress";
+
+/// Progress of a job, the same for every step
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PipelineEvent {
+    pub job_id: String,
+    pub step: PipelineStep,
+    /// Progress of the step, 0-100
+    pub step_progress: f32,
+    /// Progress of the job, the steps weighted by their expected duration, 0-100
+    pub total_progress: f32,
+    /// Status text of the step
+    pub message: Option<String>,
+    /// Estimated seconds until the job is done
+    pub eta: Option<f64>,
+}
+
+/// Payload of step events without a type of their own in the step's module
+#[derive(Debug, Serialize, Clone)]
+pub struct StepProgress {
+    pub status: String,
+    /// 0-100
+    pub progress: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_segment: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_segments: Option<i32>,
+}
+
+/// A single buffered event
+#[derive(Debug, Serialize, Clone)]
+pub struct BufferedEvent {
+    /// Sequence number, increasing within a job
+    pub seq: u64,
+    pub timestamp: u64, // Unix timestamp in milliseconds
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Default)]
+struct JobBuffer {
+    next_seq: u64,
+    running: bool,
+    events: VecDeque<BufferedEvent>,
+}
+
+struct EventLog {
+    jobs: HashMap<String, JobBuffer>,
+    // Job ids in creation order, used to drop the oldest buffers
+    order: VecDeque<String>,
+}
+
+static EVENT_LOG: Lazy<Mutex<EventLog>> = Lazy::new(|| {
+    Mutex::new(EventLog { jobs: HashMap::new(), order: VecDeque::new() })
+});
+
+fn push_event(job_id: &str, event: &str, payload: serde_json::Value) {
+    let Ok(mut log) = EVENT_LOG.lock() else { return };
+    let Some(buffer) = log.jobs.get_mut(job_id) else { return };
+
+    let seq = buffer.next_seq;
+    buffer.next_seq += 1;
+    buffer.events.push_back(BufferedEvent {
+        seq,
+        timestamp: now_millis(),
+        event: event.to_string(),
+        payload,
+    });
+    if buffer.events.len() > MAX_EVENTS_PER_JOB {
+        buffer.events.pop_front();
+    }
+}
+
+/// Records progress events of a running job. Recording stops when dropped,
+/// the buffered events stay available for replay.
+pub struct EventRecorder {
+    app_handle: tauri::AppHandle,
+    job_id: String,
+    listeners: Vec<EventId>,
+}
+
+impl EventRecorder {
+    pub fn start(app_handle: &tauri::AppHandle, job_id: &str) -> Self {
+        if let Ok(mut log) = EVENT_LOG.lock() {
+            log.jobs.insert(job_id.to_string(), JobBuffer { running: true, ..Default::default() });
+            log.order.push_back(job_id.to_string());
+            while log.order.len() > MAX_JOBS {
+                if let Some(oldest) = log.order.pop_front() {
+                    log.jobs.remove(&oldest);
+                }
+            }
+        }
+
+        // Runs share the queue's run slot, so every progress event belongs to this job
+        let listeners = JOB_EVENTS
+            .iter()
+            .map(|event| {
+                let job_id = job_id.to_string();
+                let name = event.to_string();
+                app_handle.listen_any(*event, move |e| {
+                    let payload = serde_json::from_str(e.payload()).unwrap_or(serde_json::Value::Null);
+                    push_event(&job_id, &name, payload);
+                })
+            })
+            .collect();
+
+        debug!("Started recording events for job {}", job_id);
+        Self { app_handle: app_handle.clone(), job_id: job_id.to_string(), listeners }
+    }
+}
+
+impl Drop for EventRecorder {
+    fn drop(&mut self) {
+        for id in self.listeners.drain(..) {
+            self.app_handle.unlisten(id);
+        }
+        if let Ok(mut log) = EVENT_LOG.lock()
+            && let Some(buffer) = log.jobs.get_mut(&self.job_id)
+        {
+            buffer.running = false;
+        }
+    }
+}
+
+/// Buffered events of a job with a sequence number greater or equal to `since`
+pub fn replay(job_id: &str, since: u64) -> Option<Vec<BufferedEvent>> {
+    let log = EVENT_LOG.lock().ok()?;
+    let buffer = log.jobs.get(job_id)?;
+    Some(buffer.events.iter().filter(|e| e.seq >= since).cloned().collect())
+}
+
+/// Ids of jobs that are still running
+pub fn running_jobs() -> Vec<String> {
+    let Ok(log) = EVENT_LOG.lock() else { return Vec::new() };
+    log.order
+        .iter()
+        .filter(|id| log.jobs.get(*id).is_some_and(|b| b.running))
+        .cloned()
+        .collect()
+}
+
+/// What the taskbar/dock shows for the running job
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskbarProgress {
+    /// Total progress 0-100
+    Running(f32),
+    /// The job failed, shown until the next job starts
+    Failed,
+    /// Nothing running, the indicator is removed
+    Idle,
+}
+
+impl TaskbarProgress {
+    fn bar_state(&self) -> ProgressBarState {
+        let (status, progress) = match *self {
+            TaskbarProgress::Running(progress) => (ProgressBarStatus::Normal, Some(progress)),
+            TaskbarProgress::Failed => (ProgressBarStatus::Error, Some(100.0)),
+            TaskbarProgress::Idle => (ProgressBarStatus::None, None),
+        };
+        ProgressBarState {
+            status: Some(status),
+            progress: progress.map(|p| p.clamp(0.0, 100.0).round() as u64),
+        }
+    }
+
+    /// Short text for the dock badge
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    fn badge_label(&self) -> Option<String> {
+        match *self {
+            TaskbarProgress::Running(progress) => Some(format!("{}%", progress.clamp(0.0, 100.0).floor() as u32)),
+            TaskbarProgress::Failed => Some("!".to_string()),
+            TaskbarProgress::Idle => None,
+        }
+    }
+}
+
+/// Show the job progress on the taskbar button or dock icon of the window
+pub fn set_taskbar_progress(window: &tauri::Window, state: TaskbarProgress) {
+    if let Err(e) = window.set_progress_bar(state.bar_state()) {
+        warn!("Failed to update taskbar progress: {}", e);
+    }
+    // The dock progress bar is thin, the badge keeps the percentage readable
+    #[cfg(target_os = "macos")]
+    if let Err(e) = window.set_badge_label(state.badge_label()) {
+        warn!("Failed to update dock badge: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taskbar_progress_is_rounded_and_clamped() {
+        assert_eq!(TaskbarProgress::Running(42.6).bar_state().progress, Some(43));
+        assert_eq!(TaskbarProgress::Running(140.0).bar_state().progress, Some(100));
+        assert_eq!(TaskbarProgress::Idle.bar_state().progress, None);
+        assert_eq!(TaskbarProgress::Running(99.9).badge_label().as_deref(), Some("99%"));
+        assert_eq!(TaskbarProgress::Idle.badge_label(), None);
+    }
+
+    #[test]
+    fn pipeline_events_have_one_shape_for_every_step() {
+        let event = PipelineEvent {
+            job_id: "job".to_string(),
+            step: PipelineStep::Tts,
+            step_progress: 50.0,
+            total_progress: 30.0,
+            message: Some("Генерация TTS".to_string()),
+            eta: None,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["step"], "tts");
+        assert_eq!(json["total_progress"], 30.0);
+        assert!(json["eta"].is_null());
+    }
+}
@@ -0,0 +1,380 @@
+//! Speech synthesis with a local Fish Speech server, including cloned voices.
+//!
+//! Fish Speech runs as its own API server (`tools/api_server.py`, by default
+//! on port 8080); the URL is the "fish-speech-url" setting. Without a
+//! reference the server speaks with its default speaker. A cloned voice is a
+//! short reference recording of the original speaker with its transcript,
+//! cut from the Demucs vocal stem of a video by `clone_voice`. Every request
+//! carries the reference and the server turns it into the speaker prompt,
+//! caching it between requests, so the dub keeps the voice of the original.
+//! Cloned voices live in `fish_speech/voices/<name>/` in the app data dir.
+
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Manager;
+
+use crate::utils::settings;
+use crate::utils::tts::tts::provider::{self, EngineCapabilities, EngineParams, SpeechOutput, SpeechProvider, SpeechRequest};
+use crate::utils::tts::tts::{audio, demucs, vtt, SubtitleCue};
+use crate::utils::tts::tts::{Result as TtsResult, TtsError};
+
+pub const ENGINE_ID: &str = "fish-speech";
+/// The server's own speaker, no reference
+pub const DEFAULT_VOICE: &str = "default";
+const URL_KEY: &str = "fish-speech-url";
+const DEFAULT_URL: &str = "http://127.0.0.1:8080";
+const REFERENCE_AUDIO: &str = "reference.wav";
+const REFERENCE_TEXT: &str = "reference.txt";
+/// Fish Speech needs 10-30 seconds of speech for a stable clone
+const REFERENCE_SECS: f32 = 20.0;
+/// Cues shorter than this are mostly breath and onset, longer ones often mix speakers
+const CUE_SECS: std::ops::RangeInclusive<f32> = 1.5..=12.0;
+const GAP_SECS: f32 = 0.3;
+/// Local synthesis of a long cue on a CPU takes a while
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A cloned voice
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ClonedVoice {
+    pub name: String,
+    /// Length of the reference recording
+    pub reference_secs: f32,
+    /// Cues the reference was cut from
+    pub cues: usize,
+}
+
+pub fn voices_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow!("Failed to resolve app data directory: {}", e))?;
+    Ok(data_dir.join("fish_speech").join("voices"))
+}
+
+fn server_url(app_handle: &tauri::AppHandle) -> String {
+    settings::get::<String>(app_handle, URL_KEY)
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_URL.to_string())
+}
+
+/// The default speaker and the cloned voices by name
+pub fn list_voices(voices_dir: &Path) -> Vec<String> {
+    let mut cloned: Vec<String> = std::fs::read_dir(voices_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().join(REFERENCE_AUDIO).exists())
+                .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    cloned.sort();
+    std::iter::once(DEFAULT_VOICE.to_string()).chain(cloned).collect()
+}
+
+/// Minimal MessagePack writer for the request body; the server takes JSON too,
+/// but only MessagePack carries the reference audio as raw bytes
+mod msgpack {
+    pub fn map(out: &mut Vec<u8>, len: usize) {
+        header(out, len, 0x80, 0xde, 0xdf);
+    }
+
+    pub fn array(out: &mut Vec<u8>, len: usize) {
+        header(out, len, 0x90, 0xdc, 0xdd);
+    }
+
+    fn header(out: &mut Vec<u8>, len: usize, fix: u8, marker16: u8, marker32: u8) {
+        if len < 16 {
+            out.push(fix | len as u8);
+        } else if len <= u16::MAX as usize {
+            out.push(marker16);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        } else {
+            out.push(marker32);
+            out.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+
+    pub fn str(out: &mut Vec<u8>, value: &str) {
+        let len = value.len();
+        if len < 32 {
+            out.push(0xa0 | len as u8);
+        } else {
+            sized(out, len, 0xd9, 0xda, 0xdb);
+        }
+        out.extend_from_slice(value.as_bytes());
+    }
+
+    pub fn bin(out: &mut Vec<u8>, value: &[u8]) {
+        sized(out, value.len(), 0xc4, 0xc5, 0xc6);
+        out.extend_from_slice(value);
+    }
+
+    fn sized(out: &mut Vec<u8>, len: usize, marker8: u8, marker16: u8, marker32: u8) {
+        if len <= u8::MAX as usize {
+            out.extend_from_slice(&[marker8, len as u8]);
+        } else if len <= u16::MAX as usize {
+            out.push(marker16);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        } else {
+            out.push(marker32);
+            out.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+
+    pub fn uint(out: &mut Vec<u8>, value: u32) {
+        if value < 128 {
+            out.push(value as u8);
+        } else {
+            out.push(0xce);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    pub fn bool(out: &mut Vec<u8>, value: bool) {
+        out.push(if value { 0xc3 } else { 0xc2 });
+    }
+}
+
+/// Reference recording and its transcript
+struct Reference {
+    audio: Vec<u8>,
+    text: String,
+}
+
+/// Body of a `/v1/tts` request
+fn tts_request(text: &str, reference: Option<&Reference>) -> Vec<u8> {
+    let mut body = Vec::new();
+    msgpack::map(&mut body, 6);
+    msgpack::str(&mut body, "text");
+    msgpack::str(&mut body, text);
+    msgpack::str(&mut body, "format");
+    msgpack::str(&mut body, "mp3");
+    msgpack::str(&mut body, "chunk_length");
+    msgpack::uint(&mut body, 200);
+    msgpack::str(&mut body, "normalize");
+    msgpack::bool(&mut body, true);
+    // The encoded reference is reused for every cue of the voice
+    msgpack::str(&mut body, "use_memory_cache");
+    msgpack::str(&mut body, "on");
+    msgpack::str(&mut body, "references");
+    match reference {
+        Some(reference) => {
+            msgpack::array(&mut body, 1);
+            msgpack::map(&mut body, 2);
+            msgpack::str(&mut body, "audio");
+            msgpack::bin(&mut body, &reference.audio);
+            msgpack::str(&mut body, "text");
+            msgpack::str(&mut body, &reference.text);
+        }
+        None => msgpack::array(&mut body, 0),
+    }
+    body
+}
+
+/// Speech of a Fish Speech server
+pub struct FishSpeech {
+    url: String,
+    reference: Option<Reference>,
+}
+
+impl FishSpeech {
+    pub fn new(url: &str, voices_dir: &Path, voice: &str) -> Result<Self> {
+        let reference = if voice == DEFAULT_VOICE {
+            None
+        } else {
+            let dir = voices_dir.join(voice);
+            let audio = std::fs::read(dir.join(REFERENCE_AUDIO)).map_err(|_| anyhow!("Unknown Fish Speech voice {}", voice))?;
+            let text = std::fs::read_to_string(dir.join(REFERENCE_TEXT)).unwrap_or_default();
+            Some(Reference { audio, text })
+        };
+        Ok(Self { url: url.trim_end_matches('/').to_string(), reference })
+    }
+
+    async fn synthesize_text(&self, text: &str) -> Result<Vec<u8>> {
+        let response = reqwest::Client::new()
+            .post(format!("{}/v1/tts", self.url))
+            .header("Content-Type", "application/msgpack")
+            .body(tts_request(text, self.reference.as_ref()))
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Fish Speech server at {} is unreachable: {}", self.url, e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let error = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Fish Speech error ({}): {}", status, error.trim()));
+        }
+        let audio = response.bytes().await?.to_vec();
+        if audio.is_empty() {
+            return Err(anyhow!("Fish Speech returned no audio"));
+        }
+        debug!("Fish Speech synthesized {} bytes for {:?}", audio.len(), text);
+        Ok(audio)
+    }
+}
+
+impl SpeechProvider for FishSpeech {
+    fn name(&self) -> &str {
+        ENGINE_ID
+    }
+
+    fn synthesize<'a>(&'a self, request: &'a SpeechRequest<'a>) -> BoxFuture<'a, TtsResult<SpeechOutput>> {
+        Box::pin(async move {
+            let audio = self
+                .synthesize_text(request.text)
+                .await
+                .map_err(|e| TtsError::ConfigError(e.to_string()))?;
+            Ok((audio, request.text.to_string()))
+        })
+    }
+}
+
+/// Register the engine with the voices on disk; called again after cloning
+pub fn register(app_handle: &tauri::AppHandle) {
+    let voices_dir = match voices_dir(app_handle) {
+        Ok(dir) => dir,
+        Err(e) => {
+            warn!("Fish Speech is unavailable: {}", e);
+            return;
+        }
+    };
+    let capabilities = EngineCapabilities {
+        id: ENGINE_ID.to_string(),
+        name: "Fish Speech (local server)".to_string(),
+        voices: list_voices(&voices_dir),
+        default_voice: DEFAULT_VOICE.to_string(),
+        needs_source_audio: false,
+        streaming: false,
+        languages: Vec::new(),
+        requires_api_key: false,
+    };
+    let app_handle = app_handle.clone();
+    provider::registry().register(
+        capabilities,
+        Arc::new(move |params: &EngineParams| {
+            if params.target_language.is_some() {
+                return Err(TtsError::ConfigError("Fish Speech only speaks translated text".to_string()));
+            }
+            let speech = FishSpeech::new(&server_url(&app_handle), &voices_dir, &params.config.voice)
+                .map_err(|e| TtsError::ConfigError(e.to_string()))?;
+            Ok(Arc::new(speech) as Arc<dyn SpeechProvider>)
+        }),
+    );
+}
+
+/// Cues the reference is cut from: the longest clean lines of the speaker,
+/// up to `REFERENCE_SECS` in total, in timeline order
+fn pick_reference_cues(cues: &[SubtitleCue], speaker: Option<&str>) -> Vec<SubtitleCue> {
+    let mut candidates: Vec<&SubtitleCue> = cues
+        .iter()
+        .filter(|cue| CUE_SECS.contains(&(cue.end - cue.start)) && !cue.text.trim().is_empty())
+        .filter(|cue| speaker.is_none() || cue.speaker.as_deref() == speaker)
+        .collect();
+    candidates.sort_by(|a, b| (b.end - b.start).total_cmp(&(a.end - a.start)));
+    let mut picked = Vec::new();
+    let mut total = 0.0;
+    for cue in candidates {
+        if total >= REFERENCE_SECS {
+            break;
+        }
+        total += cue.end - cue.start;
+        picked.push(cue.clone());
+    }
+    picked.sort_by(|a, b| a.start.total_cmp(&b.start));
+    picked
+}
+
+/// Clone the voice of the original speaker from a video or its audio and the
+/// original subtitles. `speaker` limits the reference to cues of one speaker.
+pub async fn clone_voice(
+    app_handle: &tauri::AppHandle,
+    media_path: &Path,
+    vtt_path: &Path,
+    name: &str,
+    speaker: Option<&str>,
+) -> Result<ClonedVoice> {
+    let name = name.trim();
+    if name.is_empty() || name == DEFAULT_VOICE || name.contains(['/', '\\', '.']) {
+        return Err(anyhow!("Invalid voice name: {}", name));
+    }
+    let cues = pick_reference_cues(&vtt::parse_vtt(vtt_path)?, speaker);
+    if cues.is_empty() {
+        return Err(anyhow!("The subtitles have no cues long enough for a voice reference"));
+    }
+
+    info!("Cloning voice '{}' from {} cues of {}", name, cues.len(), media_path.display());
+    let work_dir = tempfile::tempdir()?;
+    let vocals_path = work_dir.path().join("vocals.wav");
+    demucs::extract_vocals(media_path, vocals_path.as_path(), None).await?;
+    let (vocals, sample_rate) = audio::decode_audio_file(&vocals_path)?;
+
+    let gap = vec![0.0f32; (GAP_SECS * sample_rate as f32) as usize];
+    let mut reference = Vec::new();
+    for cue in &cues {
+        let start = ((cue.start.max(0.0) * sample_rate as f32) as usize).min(vocals.len());
+        let end = ((cue.end * sample_rate as f32) as usize).clamp(start, vocals.len());
+        if !reference.is_empty() {
+            reference.extend_from_slice(&gap);
+        }
+        reference.extend(audio::apply_fades(&vocals[start..end], sample_rate, 20));
+    }
+    let text = cues.iter().map(|cue| cue.text.trim()).collect::<Vec<_>>().join(" ");
+
+    let dir = voices_dir(app_handle)?.join(name);
+    tokio::fs::create_dir_all(&dir).await?;
+    let wav = audio::encode_wav_bytes(&reference, sample_rate)?;
+    tokio::fs::write(dir.join(REFERENCE_AUDIO), wav).await?;
+    tokio::fs::write(dir.join(REFERENCE_TEXT), text).await?;
+    register(app_handle);
+
+    let voice = ClonedVoice {
+        name: name.to_string(),
+        reference_secs: audio::duration_in_seconds(reference.len(), sample_rate),
+        cues: cues.len(),
+    };
+    info!("Voice '{}' is ready: {:.1}s of reference speech", voice.name, voice.reference_secs);
+    Ok(voice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: f32, end: f32, speaker: &str) -> SubtitleCue {
+        SubtitleCue { start, end, text: "line".to_string(), speaker: Some(speaker.to_string()), ..SubtitleCue::default() }
+    }
+
+    #[test]
+    fn reference_takes_the_longest_lines_of_the_speaker() {
+        let cues = vec![
+            cue(0.0, 1.0, "A"),
+            cue(2.0, 12.0, "A"),
+            cue(13.0, 23.0, "B"),
+            cue(24.0, 30.0, "A"),
+            cue(31.0, 40.0, "A"),
+            cue(41.0, 45.0, "A"),
+        ];
+        let picked = pick_reference_cues(&cues, Some("A"));
+        let starts: Vec<f32> = picked.iter().map(|cue| cue.start).collect();
+        // 10 + 9 + 6 seconds reach the target, too short and foreign cues are skipped
+        assert_eq!(starts, vec![2.0, 24.0, 31.0]);
+    }
+
+    #[test]
+    fn request_body_is_messagepack() {
+        let reference = Reference { audio: vec![1, 2, 3], text: "hi".to_string() };
+        let body = tts_request("hello", Some(&reference));
+        assert_eq!(body[0], 0x86);
+        assert_eq!(&body[1..6], b"\xa4text");
+        assert_eq!(&body[6..12], b"\xa5hello");
+        // The reference audio goes in as raw bytes
+        assert!(body.windows(5).any(|window| window == [0xc4, 3, 1, 2, 3]));
+        assert!(body.ends_with(b"\xa4text\xa2hi"));
+    }
+}
@@ -0,0 +1,118 @@
+//! Golden-file tests for the subtitle transforms.
+//!
+//! Every case reads a fixture from `fixtures/subtitles/<case>.input.<ext>`,
+//! runs it through a transform and compares the result with
+//! `<case>.golden.vtt`. Both sides are normalized first (line endings,
+//! trailing whitespace, blank-line runs, timestamps rounded to 10 ms) so only
+//! meaningful differences fail. Run with `UPDATE_GOLDEN=1` to rewrite the
+//! golden files after an intended change and review them in the diff.
+
+use regex::Regex;
+use std::path::PathBuf;
+
+use crate::utils::charset;
+use crate::utils::subtitles;
+use crate::utils::translate;
+use crate::utils::tts::tts::{timeline, vtt};
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures").join("subtitles")
+}
+
+fn read_input(case: &str, ext: &str) -> String {
+    let path = fixtures_dir().join(format!("{}.input.{}", case, ext));
+    let bytes = std::fs::read(&path).unwrap_or_else(|e| panic!("Missing fixture {}: {}", path.display(), e));
+    charset::decode(&bytes, None).unwrap()
+}
+
+/// Round a timestamp ("HH:MM:SS.mmm" or SRT-style "HH:MM:SS,mmm") to 10 ms
+fn round_timestamp(caps: &regex::Captures) -> String {
+    let part = |i: usize| caps[i].parse::<u64>().unwrap();
+    let millis = part(1) * 3_600_000 + part(2) * 60_000 + part(3) * 1000 + part(4);
+    let rounded = (millis + 5) / 10 * 10;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        rounded / 3_600_000,
+        rounded / 60_000 % 60,
+        rounded / 1000 % 60,
+        rounded % 1000
+    )
+}
+
+fn normalize(content: &str) -> String {
+    let timestamp = Regex::new(r"(\d{2,}):(\d{2}):(\d{2})[.,](\d{3})").unwrap();
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.replace("\r\n", "\n").lines() {
+        let line = line.trim_end();
+        if line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        if line.contains("-->") {
+            lines.push(timestamp.replace_all(line, |caps: &regex::Captures| round_timestamp(caps)).to_string());
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+fn assert_golden(case: &str, actual: &str) {
+    let golden_path = fixtures_dir().join(format!("{}.golden.vtt", case));
+    let actual = normalize(actual);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&golden_path, format!("{}\n", actual)).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&golden_path)
+        .unwrap_or_else(|e| panic!("Missing golden file {} (run with UPDATE_GOLDEN=1): {}", golden_path.display(), e));
+    assert_eq!(actual, normalize(&expected), "{} differs from {}", case, golden_path.display());
+}
+
+#[test]
+fn srt_is_converted_to_vtt() {
+    let input = read_input("srt_dialogue", "srt");
+    assert_golden("srt_dialogue", &subtitles::to_clean_vtt(&input, "srt"));
+}
+
+#[test]
+fn cp1251_srt_is_transcoded() {
+    let input = read_input("cp1251_dialogue", "srt");
+    assert_golden("cp1251_dialogue", &subtitles::to_clean_vtt(&input, "srt"));
+}
+
+#[test]
+fn automatic_captions_lose_inline_tags() {
+    let input = read_input("auto_captions", "vtt");
+    assert_golden("auto_captions", &subtitles::to_clean_vtt(&input, "vtt"));
+}
+
+#[test]
+fn overlapping_cues_are_merged() {
+    let input = read_input("overlapping", "vtt");
+    let mut cues = vtt::parse_vtt_str(&input).unwrap();
+    timeline::merge_overlapping_cues(&mut cues);
+    for cue in cues.iter_mut() {
+        cue.text = timeline::strip_speaker_tags(&cue.text);
+        cue.speaker = None;
+    }
+    assert_golden("overlapping", &vtt::write_vtt_str(&cues));
+}
+
+#[test]
+fn translation_parser_round_trips() {
+    let input = read_input("translate_roundtrip", "vtt");
+    let file = translate::parse_vtt_content(&input).unwrap();
+    assert_golden("translate_roundtrip", &translate::render_vtt(&file.header, &file.segments));
+}
+
+#[test]
+fn cue_metadata_survives_translation_parser() {
+    let input = read_input("cue_metadata", "vtt");
+    let file = translate::parse_vtt_content(&input).unwrap();
+    assert_golden("cue_metadata", &translate::render_vtt(&file.header, &file.segments));
+}
@@ -0,0 +1,282 @@
+//! The pipeline without the app window.
+//!
+//! `videonova-cli` and the processing server run the steps through these
+//! functions. Settings the app keeps in its store aren't available here, so
+//! every step runs with the defaults apart from what the caller passes in.
+//! Progress of the steps is handed to an event sink as JSON.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::utils::cookies::CookieSource;
+use crate::utils::merge::{self, MergeStyle, TranslatedAudio};
+use crate::utils::transcribe::TranscriptionOptions;
+use crate::utils::translate::{self, TranslationOptions};
+use crate::utils::tts::tts::synchronizer::{process_sync, SyncConfig};
+use crate::utils::tts::tts::TtsConfig;
+use crate::utils::{transcribe, youtube};
+
+/// What a headless run reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HeadlessEvent {
+    /// A step started
+    Step { step: String },
+    /// Progress message of the running step, as the step sends it
+    Progress { step: String, update: Value },
+}
+
+pub type EventSink = Arc<dyn Fn(HeadlessEvent) + Send + Sync>;
+
+/// Channel whose messages go to the sink until the sender is dropped
+fn channel<T: Serialize + Send + 'static>(sink: &EventSink, step: &'static str) -> (mpsc::Sender<T>, tokio::task::JoinHandle<()>) {
+    sink(HeadlessEvent::Step { step: step.to_string() });
+    let (tx, mut rx) = mpsc::channel::<T>(32);
+    let sink = sink.clone();
+    let forwarder = tokio::spawn(async move {
+        while let Some(update) = rx.recv().await {
+            let update = serde_json::to_value(&update).unwrap_or(Value::Null);
+            sink(HeadlessEvent::Progress { step: step.to_string(), update });
+        }
+    });
+    (tx, forwarder)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetLanguage {
+    pub code: String,
+    /// Used in prompts and track titles, the code when not given
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+impl TargetLanguage {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.code)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Voice {
+    pub voice: String,
+    pub model: String,
+    pub speed: f32,
+}
+
+impl Default for Voice {
+    fn default() -> Self {
+        let config = TtsConfig::default();
+        Self { voice: config.voice, model: config.model, speed: config.speed }
+    }
+}
+
+impl Voice {
+    fn tts_config(&self) -> TtsConfig {
+        TtsConfig { model: self.model.clone(), voice: self.voice.clone(), speed: self.speed }
+    }
+}
+
+/// A whole run, from the URL to the dubbed video
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineRequest {
+    pub url: String,
+    pub target: TargetLanguage,
+    #[serde(default)]
+    pub voice: Voice,
+}
+
+/// Files of a finished run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineOutput {
+    pub title: String,
+    pub video_path: PathBuf,
+    pub audio_path: PathBuf,
+    pub vtt_path: PathBuf,
+    pub translated_vtt_path: PathBuf,
+    pub retimed_vtt_path: Option<PathBuf>,
+    pub dubbed_audio_path: PathBuf,
+    pub output_path: PathBuf,
+}
+
+pub async fn download(
+    sink: &EventSink,
+    url: &str,
+    output_dir: &Path,
+    cookies: &CookieSource,
+) -> Result<(youtube::VideoInfo, youtube::DownloadResult)> {
+    let info = youtube::get_video_info_with(url, cookies).await?;
+    let (tx, forwarder) = channel(sink, "download");
+    let result =
+        youtube::download_video_with_info(url, &info, &output_dir.to_path_buf(), None, Some(tx), CancellationToken::new()).await;
+    let _ = forwarder.await;
+    Ok((info, result?))
+}
+
+pub async fn transcribe(
+    sink: &EventSink,
+    api_key: &str,
+    audio: &Path,
+    output_dir: &Path,
+    language: Option<String>,
+) -> Result<PathBuf> {
+    let (tx, forwarder) = channel(sink, "transcribe");
+    let options = TranscriptionOptions { language, ..TranscriptionOptions::default() };
+    let result = transcribe::transcribe_audio(audio, output_dir, api_key, &options, Some(tx)).await;
+    let _ = forwarder.await;
+    result
+}
+
+pub async fn translate(
+    sink: &EventSink,
+    api_key: &str,
+    vtt: &Path,
+    output_dir: &Path,
+    target: &TargetLanguage,
+) -> Result<PathBuf> {
+    let (tx, forwarder) = channel(sink, "translate");
+    let options = TranslationOptions::default();
+    let translation = translate::TranslationTarget {
+        target_language_code: &target.code,
+        target_language_name: target.name(),
+        api_key,
+        options: &options,
+    };
+    let result = translate::translate_vtt(vtt, None, output_dir, &translation, None, Some(tx))
+    .await;
+    let _ = forwarder.await;
+    result
+}
+
+/// Returns the WAV and, when the speech moved the cues, the retimed subtitles
+pub async fn tts(
+    sink: &EventSink,
+    api_key: &str,
+    vtt: &Path,
+    output: &Path,
+    original_audio: Option<&Path>,
+    voice: &Voice,
+) -> Result<(PathBuf, Option<PathBuf>)> {
+    let retimed = output.with_extension("retimed.vtt");
+    let (tx, forwarder) = channel(sink, "tts");
+    let mut config = SyncConfig::new(api_key, vtt, output);
+    config.original_audio_path = original_audio;
+    config.progress_sender = Some(tx);
+    config.tts_config = voice.tts_config();
+    config.retimed_vtt_path = Some(&retimed);
+    let result = process_sync(config).await;
+    let _ = forwarder.await;
+    result.map_err(|e| anyhow!("Speech synthesis failed: {}", e))?;
+    Ok((output.to_path_buf(), retimed.exists().then_some(retimed)))
+}
+
+/// Input files of the merge
+pub struct MergeInputs<'a> {
+    pub video: &'a Path,
+    pub dubbed_audio: &'a Path,
+    pub original_audio: &'a Path,
+    pub original_vtt: &'a Path,
+    pub translated_vtt: &'a Path,
+    /// Title, source and cover art written into the output
+    pub metadata: merge::OutputMetadata,
+}
+
+pub async fn merge(
+    sink: &EventSink,
+    inputs: MergeInputs<'_>,
+    output: &Path,
+    source_language: &str,
+    target: &TargetLanguage,
+) -> Result<PathBuf> {
+    let (tx, forwarder) = channel(sink, "merge");
+    let sources = merge::MergeSources {
+        video_path: inputs.video,
+        translated_audio: TranslatedAudio::File(inputs.dubbed_audio),
+        original_audio_path: inputs.original_audio,
+        original_vtt_path: inputs.original_vtt,
+        translated_vtt_path: inputs.translated_vtt,
+        chapters_path: None,
+    };
+    let languages = merge::TrackLanguages {
+        source_code: source_language,
+        target_code: &target.code,
+        source_name: source_language,
+        target_name: target.name(),
+    };
+    let result =
+        merge::merge_files(sources, output, languages, &MergeStyle::default(), &inputs.metadata, None, Some(tx)).await;
+    let _ = forwarder.await;
+    result.map_err(|e| anyhow!("Merge failed: {}", e))
+}
+
+/// `title_video.mp4` from the download becomes `title`
+fn title_stem(video_path: &Path) -> String {
+    let stem = video_path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    stem.strip_suffix("_video").map(str::to_string).unwrap_or(stem)
+}
+
+/// Download, transcribe, translate, dub and merge a video into `output_dir`
+pub async fn pipeline(
+    sink: &EventSink,
+    api_key: &str,
+    request: &PipelineRequest,
+    output_dir: &Path,
+    cookies: &CookieSource,
+) -> Result<PipelineOutput> {
+    let (info, files) = download(sink, &request.url, output_dir, cookies).await?;
+    let source_language = info.original_language.clone().or(info.language.clone());
+    let vtt_path = transcribe(sink, api_key, &files.audio_path, output_dir, source_language.clone()).await?;
+    let translated_vtt_path = translate(sink, api_key, &vtt_path, output_dir, &request.target).await?;
+
+    let stem = title_stem(&files.video_path);
+    let wav = output_dir.join(format!("{}_{}.wav", stem, request.target.code));
+    let (dubbed_audio_path, retimed_vtt_path) =
+        tts(sink, api_key, &translated_vtt_path, &wav, Some(&files.audio_path), &request.voice).await?;
+
+    let output = output_dir.join(format!("{}_{}.mp4", stem, request.target.code));
+    let inputs = MergeInputs {
+        video: &files.video_path,
+        dubbed_audio: &dubbed_audio_path,
+        original_audio: &files.audio_path,
+        original_vtt: &vtt_path,
+        // Subtitles go where the dubbed speech actually plays
+        translated_vtt: retimed_vtt_path.as_deref().unwrap_or(&translated_vtt_path),
+        metadata: merge::OutputMetadata {
+            title: Some(info.title.clone()),
+            source_url: Some(info.url.clone()),
+            thumbnail_url: Some(info.thumbnail.clone()),
+        },
+    };
+    let source_language = source_language.unwrap_or_else(|| "und".to_string());
+    let output_path = merge(sink, inputs, &output, &source_language, &request.target).await?;
+
+    Ok(PipelineOutput {
+        title: info.title,
+        video_path: files.video_path,
+        audio_path: files.audio_path,
+        vtt_path,
+        translated_vtt_path,
+        retimed_vtt_path,
+        dubbed_audio_path,
+        output_path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_only_need_a_url_and_a_language() {
+        let request: PipelineRequest =
+            serde_json::from_str(r#"{"url": "https://youtu.be/x", "target": {"code": "de"}}"#).unwrap();
+        assert_eq!(request.target.name(), "de");
+        assert_eq!(request.voice.voice, TtsConfig::default().voice);
+        assert_eq!(title_stem(Path::new("/tmp/my_talk_video.mp4")), "my_talk");
+    }
+}
@@ -0,0 +1,192 @@
+//! Retrying OpenAI requests that failed for a passing reason.
+//!
+//! Rate limits (429 without an exhausted quota), server errors and timeouts
+//! usually clear up within seconds, so a single one shouldn't fail the job.
+//! Transcription, translation and speech requests go through [`send`], which
+//! repeats them with exponential backoff and jitter, waiting at least as long
+//! as a `Retry-After` header asks. Every retry is announced as a
+//! `request-retry` event, so the frontend can show it next to the progress
+//! instead of the job silently hanging. An exhausted quota is never retried,
+//! it is left to the quota pause of the pipeline.
+//!
+//! The policy is part of the advanced configuration:
+//!
+//! ```toml
+//! [retry]
+//! max_retries = 8
+//! initial_backoff_ms = 500
+//! ```
+
+use anyhow::{anyhow, Result};
+use log::warn;
+use once_cell::sync::Lazy;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::utils::advanced_config;
+use crate::utils::emitter;
+use crate::utils::quota;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpRetryPolicy {
+    /// Retries after the first attempt, 0 disables retrying
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for every further one
+    pub initial_backoff_ms: u64,
+    /// Longest wait between two attempts
+    pub max_backoff_ms: u64,
+    /// Share of the wait that is randomized, so parallel requests don't retry in step
+    pub jitter: f64,
+    /// Longest `Retry-After` that is honored; a server asking for more gets this
+    pub max_retry_after_ms: u64,
+}
+
+impl Default for HttpRetryPolicy {
+    fn default() -> Self {
+        Self { max_retries: 5, initial_backoff_ms: 1000, max_backoff_ms: 30_000, jitter: 0.2, max_retry_after_ms: 60_000 }
+    }
+}
+
+impl HttpRetryPolicy {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_retries > 20 {
+            return Err(anyhow!("retry.max_retries must be at most 20"));
+        }
+        if self.initial_backoff_ms == 0 || self.initial_backoff_ms > self.max_backoff_ms {
+            return Err(anyhow!("retry.initial_backoff_ms must be positive and at most retry.max_backoff_ms"));
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(anyhow!("retry.jitter must be between 0 and 1"));
+        }
+        Ok(())
+    }
+
+    /// Wait before retry `retry` (1 for the first), with `random` in 0..1
+    /// picking the jitter. A `Retry-After` of the server is a lower bound.
+    pub fn backoff(&self, retry: u32, retry_after: Option<Duration>, random: f64) -> Duration {
+        let exponential = self.initial_backoff_ms.saturating_mul(1u64 << retry.saturating_sub(1).min(30));
+        let capped = exponential.min(self.max_backoff_ms) as f64;
+        let jittered = capped * (1.0 - self.jitter + 2.0 * self.jitter * random.clamp(0.0, 1.0));
+        let wait = Duration::from_millis(jittered.round() as u64);
+        match retry_after {
+            Some(asked) => wait.max(asked.min(Duration::from_millis(self.max_retry_after_ms))),
+            None => wait,
+        }
+    }
+}
+
+/// Payload of the `request-retry` event
+#[derive(Debug, Clone, Serialize)]
+pub struct RetryNotice {
+    /// What was requested, e.g. "Translation"
+    pub request: String,
+    /// The retry about to be made, from 1
+    pub retry: u32,
+    pub max_retries: u32,
+    pub delay_ms: u64,
+    pub reason: String,
+}
+
+static APP_HANDLE: Lazy<RwLock<Option<tauri::AppHandle>>> = Lazy::new(|| RwLock::new(None));
+
+/// Let retries be announced to the frontend
+pub fn init(app_handle: &tauri::AppHandle) {
+    if let Ok(mut handle) = APP_HANDLE.write() {
+        *handle = Some(app_handle.clone());
+    }
+}
+
+fn announce(notice: &RetryNotice) {
+    warn!(
+        "{} failed ({}), retry {}/{} in {} ms",
+        notice.request, notice.reason, notice.retry, notice.max_retries, notice.delay_ms
+    );
+    let Some(app_handle) = APP_HANDLE.read().ok().and_then(|handle| handle.clone()) else { return };
+    if let Err(e) = emitter::emit(&app_handle, "request-retry", notice) {
+        warn!("Failed to emit request-retry: {}", e);
+    }
+}
+
+/// Statuses worth sending the same request again for
+pub fn is_retryable(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 425 | 429 | 500 | 502 | 503 | 504)
+}
+
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+/// Send the request `build` makes, again after every passing failure, under
+/// the policy of the advanced configuration. Returns the first response that
+/// isn't worth retrying, successful or not, for the caller to handle as it
+/// would without retries. A rate limit that outlasts the retries, or an
+/// exhausted quota, is returned as an error with the body of the response.
+pub async fn send<F>(request: &str, build: F) -> Result<reqwest::Response>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let policy = advanced_config::current().retry;
+    let mut retry = 0;
+    loop {
+        let (reason, wait_at_least) = match build().send().await {
+            Ok(response) if !is_retryable(response.status()) => return Ok(response),
+            Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                let status = response.status();
+                let asked = retry_after(&response);
+                let body = response.text().await.unwrap_or_default();
+                // Waiting doesn't bring the quota back
+                if quota::is_quota_error(&body) || retry >= policy.max_retries {
+                    return Err(anyhow!("{} failed (HTTP {}): {}", request, status, body));
+                }
+                (format!("HTTP {}", status), asked)
+            }
+            Ok(response) if retry >= policy.max_retries => return Ok(response),
+            Ok(response) => (format!("HTTP {}", response.status()), retry_after(&response)),
+            Err(e) if (e.is_timeout() || e.is_connect() || e.is_request()) && retry < policy.max_retries => {
+                (e.to_string(), None)
+            }
+            Err(e) => return Err(anyhow!("{} failed: {}", request, e)),
+        };
+        retry += 1;
+        let delay = policy.backoff(retry, wait_at_least, rand::random::<f64>());
+        announce(&RetryNotice {
+            request: request.to_string(),
+            retry,
+            max_retries: policy.max_retries,
+            delay_ms: delay.as_millis() as u64,
+            reason,
+        });
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap_and_honors_retry_after() {
+        let policy = HttpRetryPolicy { jitter: 0.0, ..HttpRetryPolicy::default() };
+        assert_eq!(policy.backoff(1, None, 0.5), Duration::from_millis(1000));
+        assert_eq!(policy.backoff(3, None, 0.5), Duration::from_millis(4000));
+        assert_eq!(policy.backoff(10, None, 0.5), Duration::from_millis(30_000));
+        assert_eq!(policy.backoff(1, Some(Duration::from_secs(7)), 0.5), Duration::from_secs(7));
+        assert_eq!(policy.backoff(1, Some(Duration::from_secs(600)), 0.5), Duration::from_secs(60));
+
+        let jittered = HttpRetryPolicy::default();
+        assert_eq!(jittered.backoff(1, None, 0.0), Duration::from_millis(800));
+        assert_eq!(jittered.backoff(1, None, 1.0), Duration::from_millis(1200));
+
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable(StatusCode::UNAUTHORIZED));
+    }
+}
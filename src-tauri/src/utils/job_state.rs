@@ -0,0 +1,225 @@
+//! Explicit lifecycle of processing jobs.
+//!
+//! A job moves forward through the pipeline stages and ends in exactly one
+//! terminal state. Stages may be skipped (e.g. download and transcription
+//! when the library already has them) but never revisited, and nothing leaves
+//! a terminal state. The one detour is `WaitingForQuota`: a stage that ran out
+//! of OpenAI quota parks there and returns to the same stage once resumed.
+//! `ReviewingTranslation` is an optional stop between translation and speech
+//! generation while the user edits the translated subtitles. Every accepted
+//! transition is broadcast as a `job-state-changed` event, so the UI no longer
+//! has to guess the current step from progress messages.
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::utils::common::now_millis;
+use crate::utils::emitter;
+
+const MAX_JOBS: usize = 50;
+
+/// State of a processing job
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Downloading,
+    Transcribing,
+    Translating,
+    /// Waiting for the user to review the translation before it is spoken
+    ReviewingTranslation,
+    GeneratingSpeech,
+    Merging,
+    Uploading,
+    /// Paused until the user resolves an exhausted OpenAI quota
+    WaitingForQuota,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_terminal(self) -> bool {
+        matches!(self, JobState::Completed | JobState::Failed | JobState::Cancelled)
+    }
+
+    /// Position of an active state in the pipeline
+    fn stage(self) -> Option<u8> {
+        match self {
+            JobState::Queued => Some(0),
+            JobState::Downloading => Some(1),
+            JobState::Transcribing => Some(2),
+            JobState::Translating => Some(3),
+            JobState::ReviewingTranslation => Some(4),
+            JobState::GeneratingSpeech => Some(5),
+            JobState::Merging => Some(6),
+            JobState::Uploading => Some(7),
+            _ => None,
+        }
+    }
+
+    pub fn can_transition_to(self, next: JobState) -> bool {
+        if self.is_terminal() {
+            return false;
+        }
+        match next {
+            JobState::Failed | JobState::Cancelled => true,
+            // Only stages that call the OpenAI API can run out of quota
+            JobState::WaitingForQuota => {
+                matches!(self, JobState::Transcribing | JobState::Translating | JobState::GeneratingSpeech)
+            }
+            // Only a merged job can be complete, uploading is optional
+            JobState::Completed => matches!(self, JobState::Merging | JobState::Uploading),
+            _ => match (self.stage(), next.stage()) {
+                (Some(current), Some(next)) => next > current,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Whether a job in `state`, after `previous`, may go to `to`. A paused job
+/// may only go back to the stage it paused in.
+fn transition_allowed(state: JobState, previous: Option<JobState>, to: JobState) -> bool {
+    let resuming = state == JobState::WaitingForQuota && previous == Some(to);
+    resuming || state.can_transition_to(to)
+}
+
+/// A state the job has been in
+#[derive(Debug, Serialize, Clone)]
+pub struct StateVisit {
+    pub state: JobState,
+    pub entered_at: u64, // Unix timestamp in milliseconds
+}
+
+/// Current state of a job, as returned by `get_job_state`
+#[derive(Debug, Serialize, Clone)]
+pub struct JobStateInfo {
+    pub job_id: String,
+    pub state: JobState,
+    pub previous: Option<JobState>,
+    pub updated_at: u64, // Unix timestamp in milliseconds
+    pub error: Option<String>,
+    /// Every state in the order it was entered, including the current one
+    pub history: Vec<StateVisit>,
+}
+
+/// Payload of the `job-state-changed` event
+#[derive(Debug, Serialize, Clone)]
+pub struct JobStateChanged {
+    pub job_id: String,
+    pub from: Option<JobState>,
+    pub to: JobState,
+    pub error: Option<String>,
+}
+
+struct StateRegistry {
+    jobs: HashMap<String, JobStateInfo>,
+    // Job ids in creation order, used to forget the oldest jobs
+    order: VecDeque<String>,
+}
+
+static JOB_STATES: Lazy<Mutex<StateRegistry>> = Lazy::new(|| {
+    Mutex::new(StateRegistry { jobs: HashMap::new(), order: VecDeque::new() })
+});
+
+fn emit_change(app_handle: &tauri::AppHandle, change: &JobStateChanged) {
+    debug!("Job {}: {:?} -> {:?}", change.job_id, change.from, change.to);
+    if let Err(e) = emitter::emit(app_handle, "job-state-changed", change) {
+        warn!("Failed to emit job-state-changed: {}", e);
+    }
+}
+
+/// Register a new job in the `Queued` state
+pub fn register(app_handle: &tauri::AppHandle, job_id: &str) {
+    {
+        let Ok(mut registry) = JOB_STATES.lock() else { return };
+        let now = now_millis();
+        registry.jobs.insert(
+            job_id.to_string(),
+            JobStateInfo {
+                job_id: job_id.to_string(),
+                state: JobState::Queued,
+                previous: None,
+                updated_at: now,
+                error: None,
+                history: vec![StateVisit { state: JobState::Queued, entered_at: now }],
+            },
+        );
+        registry.order.push_back(job_id.to_string());
+        while registry.order.len() > MAX_JOBS {
+            if let Some(oldest) = registry.order.pop_front() {
+                registry.jobs.remove(&oldest);
+            }
+        }
+    }
+    emit_change(
+        app_handle,
+        &JobStateChanged { job_id: job_id.to_string(), from: None, to: JobState::Queued, error: None },
+    );
+}
+
+/// Move a job to a new state, rejecting transitions the lifecycle doesn't allow
+pub fn transition(app_handle: &tauri::AppHandle, job_id: &str, to: JobState, error: Option<String>) -> Result<()> {
+    let change = {
+        let mut registry = JOB_STATES.lock().map_err(|_| anyhow!("Job state registry is poisoned"))?;
+        let info = registry
+            .jobs
+            .get_mut(job_id)
+            .ok_or_else(|| anyhow!("Unknown job {}", job_id))?;
+        if !transition_allowed(info.state, info.previous, to) {
+            return Err(anyhow!("Job {} cannot go from {:?} to {:?}", job_id, info.state, to));
+        }
+
+        let from = info.state;
+        info.previous = Some(from);
+        info.state = to;
+        info.updated_at = now_millis();
+        info.error = error.clone();
+        info.history.push(StateVisit { state: to, entered_at: info.updated_at });
+        JobStateChanged { job_id: job_id.to_string(), from: Some(from), to, error }
+    };
+    emit_change(app_handle, &change);
+    Ok(())
+}
+
+/// Current state of a job
+pub fn get(job_id: &str) -> Option<JobStateInfo> {
+    JOB_STATES.lock().ok()?.jobs.get(job_id).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages_only_move_forward() {
+        assert!(JobState::Queued.can_transition_to(JobState::Downloading));
+        // Steps served from an earlier run are skipped
+        assert!(JobState::Downloading.can_transition_to(JobState::GeneratingSpeech));
+        assert!(!JobState::Translating.can_transition_to(JobState::Transcribing));
+        assert!(!JobState::Merging.can_transition_to(JobState::Merging));
+
+        assert!(JobState::Merging.can_transition_to(JobState::Completed));
+        assert!(!JobState::GeneratingSpeech.can_transition_to(JobState::Completed));
+        assert!(JobState::Downloading.can_transition_to(JobState::Cancelled));
+        assert!(!JobState::Completed.can_transition_to(JobState::Failed));
+        assert!(!JobState::Failed.can_transition_to(JobState::Queued));
+    }
+
+    #[test]
+    fn paused_job_resumes_its_stage() {
+        assert!(JobState::Translating.can_transition_to(JobState::WaitingForQuota));
+        assert!(!JobState::Downloading.can_transition_to(JobState::WaitingForQuota));
+
+        let paused_in = Some(JobState::GeneratingSpeech);
+        assert!(transition_allowed(JobState::WaitingForQuota, paused_in, JobState::GeneratingSpeech));
+        assert!(!transition_allowed(JobState::WaitingForQuota, paused_in, JobState::Translating));
+        assert!(!transition_allowed(JobState::WaitingForQuota, paused_in, JobState::Merging));
+        assert!(transition_allowed(JobState::WaitingForQuota, paused_in, JobState::Failed));
+    }
+}
@@ -0,0 +1,313 @@
+//! Records of finished processing jobs.
+//!
+//! The pipeline works inside `videonova_temp`, which is removed once the final
+//! video is merged. To let the UI inspect a job afterwards, the subtitles and
+//! TTS fragments are preserved in `<app data>/jobs/<job id>/` together with a
+//! `job.json` describing where everything lives, and the QC report of the mix
+//! with the instrumental as `mix_report.json`.
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use tauri::Manager;
+use tokio::process::Command as TokioCommand;
+
+use crate::utils::common::{now_millis, now_secs};
+use crate::utils::tts::tts::vtt;
+
+const JOB_FILE: &str = "job.json";
+const FRAGMENTS_DIR: &str = "fragments";
+const SNIPPETS_DIR: &str = "snippets";
+/// Cue snippets kept per job, the least recently cut are removed past it
+const MAX_SNIPPETS: usize = 64;
+/// Written by the synchronizer next to the TTS fragments
+const MIX_REPORT_FILE: &str = "mix_report.json";
+
+static JOB_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Persistent description of a processing job
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobRecord {
+    pub id: String,
+    pub url: String,
+    pub target_language: String,
+    pub created_at: u64, // Unix timestamp in seconds
+    pub original_audio_path: PathBuf,
+    pub original_vtt_path: PathBuf,
+    pub translated_vtt_path: PathBuf,
+    pub fragments_dir: Option<PathBuf>,
+    pub final_path: Option<PathBuf>,
+    /// Voice the cues were spoken with, None for the engine's default
+    #[serde(default)]
+    pub voice: Option<String>,
+}
+
+/// Outputs of a finished run that [`record_job`] preserves
+pub struct FinishedJob<'a> {
+    pub job_id: &'a str,
+    pub url: &'a str,
+    pub target_language: &'a str,
+    pub original_audio_path: &'a Path,
+    pub original_vtt_path: &'a Path,
+    pub translated_vtt_path: &'a Path,
+    pub tts_fragments_dir: Option<&'a Path>,
+    pub final_path: Option<&'a Path>,
+    pub voice: Option<&'a str>,
+}
+
+/// Everything the segment inspector needs about a single cue
+#[derive(Debug, Serialize, Clone)]
+pub struct CueContext {
+    pub index: usize,
+    pub start: f32,
+    pub end: f32,
+    pub original_text: String,
+    pub translated_text: String,
+    pub original_audio_snippet: Option<PathBuf>,
+    pub tts_fragment: Option<PathBuf>,
+}
+
+/// Generate a new unique job id
+pub fn new_job_id() -> String {
+    let millis = now_millis();
+    let counter = JOB_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{:04x}", millis, counter & 0xffff)
+}
+
+/// Root directory of job records inside the app data dir
+pub fn jobs_root(app_handle: &tauri::AppHandle) -> Result<PathBuf> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow!("Failed to resolve app data directory: {}", e))?;
+    Ok(data_dir.join("jobs"))
+}
+
+/// Directory of a single job
+pub fn job_dir(app_handle: &tauri::AppHandle, job_id: &str) -> Result<PathBuf> {
+    // Job ids come from the frontend, don't let them escape the jobs root
+    if job_id.is_empty() || job_id.contains(['/', '\\', '.']) {
+        return Err(anyhow!("Invalid job id: {}", job_id));
+    }
+    Ok(jobs_root(app_handle)?.join(job_id))
+}
+
+/// Save the job record
+pub async fn save(app_handle: &tauri::AppHandle, record: &JobRecord) -> Result<()> {
+    let dir = job_dir(app_handle, &record.id)?;
+    tokio::fs::create_dir_all(&dir).await?;
+    let json = serde_json::to_string_pretty(record)
+        .map_err(|e| anyhow!("Failed to serialize job record: {}", e))?;
+    tokio::fs::write(dir.join(JOB_FILE), json).await?;
+    Ok(())
+}
+
+/// Load a job record by id
+pub async fn load(app_handle: &tauri::AppHandle, job_id: &str) -> Result<JobRecord> {
+    let path = job_dir(app_handle, job_id)?.join(JOB_FILE);
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|_| anyhow!("Job {} not found", job_id))?;
+    serde_json::from_str(&content).map_err(|e| anyhow!("Corrupted job record {}: {}", job_id, e))
+}
+
+/// Copy the subtitles and move the TTS fragments of a finished run out of the
+/// temp directory and store the job record
+pub async fn record_job(app_handle: &tauri::AppHandle, job: FinishedJob<'_>) -> Result<JobRecord> {
+    let FinishedJob {
+        job_id,
+        url,
+        target_language,
+        original_audio_path,
+        original_vtt_path,
+        translated_vtt_path,
+        tts_fragments_dir,
+        final_path,
+        voice,
+    } = job;
+    let dir = job_dir(app_handle, job_id)?;
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let original_vtt = dir.join("original.vtt");
+    tokio::fs::copy(original_vtt_path, &original_vtt).await?;
+    let translated_vtt = dir.join("translated.vtt");
+    tokio::fs::copy(translated_vtt_path, &translated_vtt).await?;
+
+    let fragments_dir = match tts_fragments_dir {
+        Some(source) if source.is_dir() => {
+            let target = dir.join(FRAGMENTS_DIR);
+            match move_dir(source, &target).await {
+                Ok(()) => Some(target),
+                Err(e) => {
+                    warn!("Failed to preserve TTS fragments for job {}: {}", job_id, e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    if let Some(report) = fragments_dir.as_ref().map(|fragments| fragments.join(MIX_REPORT_FILE)).filter(|path| path.exists()) {
+        let kept = tokio::fs::rename(&report, dir.join(MIX_REPORT_FILE)).await;
+        if let Err(e) = kept {
+            warn!("Failed to keep the mix report of job {}: {}", job_id, e);
+        }
+    }
+
+    let record = JobRecord {
+        id: job_id.to_string(),
+        url: url.to_string(),
+        target_language: target_language.to_string(),
+        created_at: now_secs(),
+        original_audio_path: original_audio_path.to_path_buf(),
+        original_vtt_path: original_vtt,
+        translated_vtt_path: translated_vtt,
+        fragments_dir,
+        final_path: final_path.map(Path::to_path_buf),
+        voice: voice.map(str::to_string),
+    };
+    save(app_handle, &record).await?;
+    info!("Stored job record {}", job_id);
+    Ok(record)
+}
+
+/// QC report of the mix of a job, with the levels of every bus
+pub async fn mix_report(app_handle: &tauri::AppHandle, job_id: &str) -> Result<serde_json::Value> {
+    let path = job_dir(app_handle, job_id)?.join(MIX_REPORT_FILE);
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|_| anyhow!("No mix report for job {}, it wasn't mixed with the instrumental", job_id))?;
+    serde_json::from_str(&content).map_err(|e| anyhow!("Corrupted mix report of job {}: {}", job_id, e))
+}
+
+async fn move_dir(source: &Path, target: &Path) -> Result<()> {
+    if tokio::fs::rename(source, target).await.is_ok() {
+        return Ok(());
+    }
+
+    // Different filesystems: copy file by file
+    tokio::fs::create_dir_all(target).await?;
+    let mut entries = tokio::fs::read_dir(source).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            tokio::fs::copy(entry.path(), target.join(entry.file_name())).await?;
+        }
+    }
+    let _ = tokio::fs::remove_dir_all(source).await;
+    Ok(())
+}
+
+/// Find the adjusted TTS fragment of a cue ("chunk_007_..._adjusted.wav")
+pub(crate) async fn find_fragment(fragments_dir: &Path, index: usize) -> Option<PathBuf> {
+    let prefix = format!("chunk_{:03}_", index);
+    let mut entries = tokio::fs::read_dir(fragments_dir).await.ok()?;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with(&prefix) && name.ends_with("_adjusted.wav") {
+            return Some(entry.path());
+        }
+    }
+    None
+}
+
+/// Cut the original audio of a cue into a small wav file (cached per cue)
+async fn extract_snippet(dir: &Path, audio_path: &Path, index: usize, start: f32, end: f32) -> Result<PathBuf> {
+    let snippets_dir = dir.join(SNIPPETS_DIR);
+    tokio::fs::create_dir_all(&snippets_dir).await?;
+    let snippet_path = snippets_dir.join(format!("cue_{:03}.wav", index));
+    if snippet_path.exists() {
+        return Ok(snippet_path);
+    }
+
+    let output = TokioCommand::new(crate::utils::tools::ffmpeg())
+        .arg("-y")
+        .args(["-ss", &format!("{:.3}", start)])
+        .args(["-t", &format!("{:.3}", (end - start).max(0.0))])
+        .arg("-i")
+        .arg(audio_path)
+        .arg(&snippet_path)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "ffmpeg failed to extract snippet: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    if let Err(e) = prune_snippets(&snippets_dir, MAX_SNIPPETS).await {
+        warn!("Failed to prune snippets in {}: {}", snippets_dir.display(), e);
+    }
+    Ok(snippet_path)
+}
+
+/// Remove the oldest snippets until at most `keep` are left
+async fn prune_snippets(snippets_dir: &Path, keep: usize) -> Result<()> {
+    let mut snippets = Vec::new();
+    let mut entries = tokio::fs::read_dir(snippets_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if metadata.is_file() {
+            snippets.push((metadata.modified()?, entry.path()));
+        }
+    }
+    if snippets.len() <= keep {
+        return Ok(());
+    }
+    snippets.sort();
+    let excess = snippets.len() - keep;
+    for (_, path) in snippets.into_iter().take(excess) {
+        tokio::fs::remove_file(&path).await?;
+    }
+    Ok(())
+}
+
+/// Collect timestamps, texts and audio paths for a cue of a finished job
+pub async fn cue_context(app_handle: &tauri::AppHandle, job_id: &str, cue_index: usize) -> Result<CueContext> {
+    let record = load(app_handle, job_id).await?;
+    let translated = vtt::parse_vtt(&record.translated_vtt_path).map_err(|e| anyhow!("{}", e))?;
+    let original = vtt::parse_vtt(&record.original_vtt_path).map_err(|e| anyhow!("{}", e))?;
+
+    let cue = translated
+        .get(cue_index)
+        .ok_or_else(|| anyhow!("Cue {} out of range (job has {} cues)", cue_index, translated.len()))?;
+
+    // Translation keeps timestamps, but match by overlap in case cues were dropped
+    let original_text = original
+        .iter()
+        .max_by(|a, b| {
+            let overlap = |c: &&crate::utils::tts::tts::SubtitleCue| c.end.min(cue.end) - c.start.max(cue.start);
+            overlap(a).total_cmp(&overlap(b))
+        })
+        .filter(|c| c.end > cue.start && c.start < cue.end)
+        .map(|c| c.text.clone())
+        .unwrap_or_default();
+
+    let dir = job_dir(app_handle, job_id)?;
+    let original_audio_snippet = if record.original_audio_path.exists() {
+        match extract_snippet(&dir, &record.original_audio_path, cue_index, cue.start, cue.end).await {
+            Ok(path) => Some(path),
+            Err(e) => {
+                warn!("Failed to extract original audio for cue {} of job {}: {}", cue_index, job_id, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let tts_fragment = match &record.fragments_dir {
+        Some(fragments_dir) => find_fragment(fragments_dir, cue_index).await,
+        None => None,
+    };
+
+    Ok(CueContext {
+        index: cue_index,
+        start: cue.start,
+        end: cue.end,
+        original_text,
+        translated_text: cue.text.clone(),
+        original_audio_snippet,
+        tts_fragment,
+    })
+}
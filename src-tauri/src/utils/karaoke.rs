@@ -0,0 +1,245 @@
+//! Word-highlighted subtitles of the dub.
+//!
+//! The translated words have no timestamps of their own, but the synchronizer
+//! reports where the speech of every cue runs and how long it is. The words of
+//! a cue are spread over that span by their length, which follows the dub
+//! closely enough for a sing-along highlight. The result is written as ASS
+//! karaoke (`\k` tags) or as WebVTT with inline timestamps, the form YouTube
+//! uses for word-timed captions. Cues spoken as one fragment share its speech
+//! by their length, cues without a fragment are spread over their own window.
+
+use anyhow::{anyhow, Result};
+use log::info;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::utils::ass;
+use crate::utils::timing_report::{SegmentTiming, TimingReport};
+use crate::utils::tts::tts::{vtt, SubtitleCue};
+
+/// Words already spoken in the ASS output, the rest are white. The colours are &HBBGGRR.
+const SPOKEN_COLOUR: &str = "&H00ffff";
+const UNSPOKEN_COLOUR: &str = "&Hffffff";
+
+/// Format of the word-highlighted export
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KaraokeFormat {
+    /// ASS with a `\k` tag in front of every word
+    Ass,
+    /// WebVTT with a timestamp in front of every word
+    Vtt,
+}
+
+impl KaraokeFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            KaraokeFormat::Ass => "karaoke.ass",
+            KaraokeFormat::Vtt => "karaoke.vtt",
+        }
+    }
+}
+
+/// A word of a cue and when it is spoken
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedWord {
+    pub text: String,
+    pub start: f32,
+    pub end: f32,
+    /// The word starts a new line of the cue
+    pub line_break: bool,
+}
+
+fn char_len(text: &str) -> usize {
+    text.chars().filter(|c| !c.is_whitespace()).count()
+}
+
+/// Where a fragment's speech runs: sped up speech fills the fitted length,
+/// shorter speech is followed by silence
+fn speech_end(segment: &SegmentTiming) -> f32 {
+    segment.start + segment.generated_duration.min(segment.fitted_duration)
+}
+
+/// The span of speech of every cue. A cue belongs to the fragment it overlaps
+/// the most; cues sharing a fragment split its speech by their length.
+pub fn speech_spans(cues: &[SubtitleCue], segments: &[SegmentTiming]) -> Vec<(f32, f32)> {
+    let owner: Vec<Option<usize>> = cues
+        .iter()
+        .map(|cue| {
+            segments
+                .iter()
+                .enumerate()
+                .map(|(index, segment)| (index, cue.end.min(speech_end(segment).max(segment.end)) - cue.start.max(segment.start)))
+                .filter(|(_, overlap)| *overlap > 0.0)
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(index, _)| index)
+        })
+        .collect();
+
+    let mut spans: Vec<(f32, f32)> = cues.iter().map(|cue| (cue.start, cue.end)).collect();
+    let mut first = 0;
+    while first < cues.len() {
+        let mut last = first;
+        while last + 1 < cues.len() && owner[last + 1].is_some() && owner[last + 1] == owner[first] {
+            last += 1;
+        }
+        if let Some(segment) = owner[first].map(|index| &segments[index]) {
+            let group = &cues[first..=last];
+            // Lip sync starts the speech later than the cue, retiming moved both
+            let mut start = segment.start.max(group[0].start);
+            let duration = speech_end(segment) - segment.start;
+            let total: usize = group.iter().map(|cue| char_len(&cue.text)).sum();
+            for (cue, span) in group.iter().zip(&mut spans[first..=last]) {
+                let share = duration * char_len(&cue.text) as f32 / total.max(1) as f32;
+                let cue_start = start.max(cue.start).min(cue.end);
+                *span = (cue_start, (cue_start + share).clamp(cue_start, cue.end));
+                start = cue_start + share;
+            }
+        }
+        first = last + 1;
+    }
+    spans
+}
+
+/// Spread the words of a cue over the span its speech takes, by length
+pub fn time_words(cue: &SubtitleCue, span: (f32, f32)) -> Vec<TimedWord> {
+    let tags = Regex::new(r"<[^>]*>").unwrap();
+    let text = tags.replace_all(&cue.text, "");
+    let mut words = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        for (word_index, word) in line.split_whitespace().enumerate() {
+            words.push(TimedWord { text: word.to_string(), start: 0.0, end: 0.0, line_break: line_index > 0 && word_index == 0 });
+        }
+    }
+    // A word takes its letters and a short pause after it
+    let total: usize = words.iter().map(|word| char_len(&word.text) + 1).sum();
+    let duration = (span.1 - span.0).max(0.0);
+    let mut start = span.0;
+    for word in &mut words {
+        word.start = start;
+        start += duration * (char_len(&word.text) + 1) as f32 / total.max(1) as f32;
+        word.end = start;
+    }
+    words
+}
+
+fn centiseconds(seconds: f32) -> i64 {
+    (seconds * 100.0).round() as i64
+}
+
+/// ASS text of a cue: a `\k` before every word for its duration, led by one for
+/// the silence before the speech
+fn ass_text(cue: &SubtitleCue, words: &[TimedWord]) -> String {
+    let mut text = String::new();
+    let mut position = centiseconds(cue.start);
+    if let Some(first) = words.first() {
+        let lead = centiseconds(first.start) - position;
+        if lead > 0 {
+            text.push_str(&format!("{{\\k{}}}", lead));
+            position += lead;
+        }
+    }
+    for (index, word) in words.iter().enumerate() {
+        if index > 0 {
+            text.push_str(if word.line_break { "\\N" } else { " " });
+        }
+        let end = centiseconds(word.end).max(position);
+        text.push_str(&format!("{{\\k{}}}{}", end - position, word.text.replace(['{', '}'], "")));
+        position = end;
+    }
+    text
+}
+
+/// WebVTT text of a cue: every word after the first is led by the time it is spoken
+fn vtt_text(words: &[TimedWord]) -> String {
+    let mut text = String::new();
+    for (index, word) in words.iter().enumerate() {
+        if index == 0 {
+            text.push_str(&format!("<c>{}</c>", word.text));
+            continue;
+        }
+        text.push_str(&format!("<{}>", vtt::format_time(word.start)));
+        text.push_str(&format!("<c>{}{}</c>", if word.line_break { "\n" } else { " " }, word.text));
+    }
+    text
+}
+
+/// Write cues with their words timed to the speech spans
+pub fn write(cues: &[SubtitleCue], spans: &[(f32, f32)], format: KaraokeFormat) -> String {
+    let timed: Vec<Vec<TimedWord>> = cues.iter().zip(spans).map(|(cue, span)| time_words(cue, *span)).collect();
+    match format {
+        KaraokeFormat::Ass => {
+            let mut script = ass::from_cues(cues, None);
+            script.set_style_field("PrimaryColour", SPOKEN_COLOUR);
+            script.set_style_field("SecondaryColour", UNSPOKEN_COLOUR);
+            let mut rows = std::mem::take(&mut script.events.rows);
+            for ((row, cue), words) in rows.iter_mut().zip(cues).zip(&timed) {
+                script.events.set(row, "Text", ass_text(cue, words));
+            }
+            script.events.rows = rows;
+            script.to_string()
+        }
+        KaraokeFormat::Vtt => {
+            let cues: Vec<SubtitleCue> = cues
+                .iter()
+                .zip(&timed)
+                .map(|(cue, words)| SubtitleCue { text: vtt_text(words), ..cue.clone() })
+                .collect();
+            vtt::write_vtt_str(&cues)
+        }
+    }
+}
+
+/// Export the subtitles of a dub word-highlighted next to `output`, timed by
+/// the timing report of its synchronizer run
+pub async fn export(vtt_path: &Path, report: &TimingReport, output: &Path, format: KaraokeFormat) -> Result<PathBuf> {
+    let cues = vtt::parse_vtt(vtt_path).map_err(|e| anyhow!("Failed to read {}: {}", vtt_path.display(), e))?;
+    let spans = speech_spans(&cues, &report.segments);
+    let path = output.with_extension(format.extension());
+    tokio::fs::write(&path, write(&cues, &spans, format)).await?;
+    info!("Word-highlighted subtitles exported to {}", path.display());
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: f32, end: f32, text: &str) -> SubtitleCue {
+        SubtitleCue { start, end, text: text.to_string(), ..Default::default() }
+    }
+
+    fn segment(start: f32, end: f32, generated: f32, fitted: f32) -> SegmentTiming {
+        SegmentTiming {
+            index: 0,
+            start,
+            end,
+            text: String::new(),
+            cue_duration: end - start,
+            generated_duration: generated,
+            fitted_duration: fitted,
+            delta: fitted - (end - start),
+            speed_factor: generated / fitted,
+            heavily_stretched: false,
+            low_confidence: false,
+        }
+    }
+
+    #[test]
+    fn words_follow_the_speech_of_their_fragment() {
+        let cues = [cue(1.0, 4.0, "ab cd"), cue(5.0, 6.0, "ef")];
+        // The first cue is spoken from 1.5s for 1.2s, the second has no fragment
+        let spans = speech_spans(&cues, &[segment(1.5, 4.0, 1.2, 2.5)]);
+        assert_eq!(spans, vec![(1.5, 2.7), (5.0, 6.0)]);
+
+        let words = time_words(&cues[0], spans[0]);
+        assert_eq!(words.len(), 2);
+        assert!((words[0].end - 2.1).abs() < 1e-4 && (words[1].end - 2.7).abs() < 1e-4);
+
+        let ass = write(&cues, &spans, KaraokeFormat::Ass);
+        assert!(ass.contains(",{\\k50}{\\k60}ab {\\k60}cd\n"), "{}", ass);
+        let vtt = write(&cues, &spans, KaraokeFormat::Vtt);
+        assert!(vtt.contains("<c>ab</c><00:00:02.100><c> cd</c>"), "{}", vtt);
+    }
+}
@@ -0,0 +1,261 @@
+//! Persistent library of processed source videos.
+//!
+//! Every video that went through transcription gets an entry keyed by a hash of
+//! its YouTube id. The entry keeps the original VTT and the downloaded media in
+//! the app data directory, so translating the same video to another language
+//! later can skip download and transcription entirely.
+//!
+//! Finished dubs are recorded on the entry as well, together with a hash of
+//! the settings they were made with, so submitting the same video, language
+//! and settings again can be caught before it spends API credit twice.
+
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+use crate::utils::common::{check_file_exists_and_valid, now_secs};
+use crate::utils::confidence;
+
+const ENTRY_FILE: &str = "entry.json";
+
+/// Library record for a single source video
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LibraryEntry {
+    pub video_id: String,
+    pub url: String,
+    pub title: String,
+    pub source_language: Option<String>,
+    pub transcription_path: PathBuf,
+    pub video_path: Option<PathBuf>,
+    pub audio_path: Option<PathBuf>,
+    pub updated_at: u64, // Unix timestamp in seconds
+    /// Dubs completed from this video
+    #[serde(default)]
+    pub outputs: Vec<CompletedOutput>,
+}
+
+/// A finished dub of a library video
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CompletedOutput {
+    pub job_id: String,
+    pub target_language: String,
+    /// Hash of the settings that shape the output, see [`settings_hash`]
+    pub settings_hash: String,
+    pub output_path: PathBuf,
+    pub completed_at: u64, // Unix timestamp in seconds
+}
+
+impl LibraryEntry {
+    /// Returns true if the transcription and both media files are still on disk
+    pub async fn is_reusable(&self) -> bool {
+        let (Some(video_path), Some(audio_path)) = (&self.video_path, &self.audio_path) else {
+            return false;
+        };
+        check_file_exists_and_valid(&self.transcription_path).await
+            && check_file_exists_and_valid(video_path).await
+            && check_file_exists_and_valid(audio_path).await
+    }
+}
+
+/// Stable directory name for a video id
+pub fn video_key(video_id: &str) -> String {
+    let digest = Sha256::digest(video_id.as_bytes());
+    digest.iter().take(16).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Stable hash of the settings a dub was made with
+pub fn settings_hash(settings: &serde_json::Value) -> String {
+    // Callers build the value the same way every time, so equal settings serialize equally
+    let digest = Sha256::digest(settings.to_string().as_bytes());
+    digest.iter().take(16).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Root directory of the library inside the app data dir
+pub fn library_root(app_handle: &tauri::AppHandle) -> Result<PathBuf> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow!("Failed to resolve app data directory: {}", e))?;
+    Ok(data_dir.join("library"))
+}
+
+pub fn entry_dir(app_handle: &tauri::AppHandle, video_id: &str) -> Result<PathBuf> {
+    Ok(library_root(app_handle)?.join(video_key(video_id)))
+}
+
+async fn write_entry(dir: &Path, entry: &LibraryEntry) -> Result<()> {
+    let json = serde_json::to_string_pretty(entry)
+        .map_err(|e| anyhow!("Failed to serialize library entry: {}", e))?;
+    tokio::fs::write(dir.join(ENTRY_FILE), json).await?;
+    Ok(())
+}
+
+/// Load the library entry for a video id, if any
+pub async fn lookup(app_handle: &tauri::AppHandle, video_id: &str) -> Result<Option<LibraryEntry>> {
+    let path = entry_dir(app_handle, video_id)?.join(ENTRY_FILE);
+    if !path.exists() {
+        debug!("No library entry for video {}", video_id);
+        return Ok(None);
+    }
+
+    let content = tokio::fs::read_to_string(&path).await?;
+    match serde_json::from_str::<LibraryEntry>(&content) {
+        Ok(entry) => Ok(Some(entry)),
+        Err(e) => {
+            warn!("Ignoring corrupted library entry {}: {}", path.display(), e);
+            Ok(None)
+        }
+    }
+}
+
+/// Look up an entry whose transcription and media can be reused as-is
+pub async fn find_reusable(app_handle: &tauri::AppHandle, video_id: &str) -> Option<LibraryEntry> {
+    match lookup(app_handle, video_id).await {
+        Ok(Some(entry)) if entry.is_reusable().await => Some(entry),
+        Ok(Some(_)) => {
+            info!("Library entry for video {} is incomplete, it will be refreshed", video_id);
+            None
+        }
+        Ok(None) => None,
+        Err(e) => {
+            warn!("Failed to read library entry for video {}: {}", video_id, e);
+            None
+        }
+    }
+}
+
+/// Save a copy of the original transcription into the library
+pub async fn record_transcription(
+    app_handle: &tauri::AppHandle,
+    video_id: &str,
+    url: &str,
+    title: &str,
+    source_language: Option<String>,
+    vtt_path: &Path,
+) -> Result<LibraryEntry> {
+    let dir = entry_dir(app_handle, video_id)?;
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let transcription_path = dir.join("original.vtt");
+    if vtt_path != transcription_path {
+        tokio::fs::copy(vtt_path, &transcription_path).await?;
+        // Whisper transcripts come with per-cue confidence, reused transcripts keep it
+        let sidecar = confidence::sidecar_path(vtt_path);
+        if sidecar.exists() {
+            tokio::fs::copy(&sidecar, confidence::sidecar_path(&transcription_path)).await?;
+        }
+    }
+
+    // Keep media references from a previous run if they are still valid
+    let previous = lookup(app_handle, video_id).await.ok().flatten();
+    let entry = LibraryEntry {
+        video_id: video_id.to_string(),
+        url: url.to_string(),
+        title: title.to_string(),
+        source_language,
+        transcription_path,
+        video_path: previous.as_ref().and_then(|p| p.video_path.clone()),
+        audio_path: previous.as_ref().and_then(|p| p.audio_path.clone()),
+        updated_at: now_secs(),
+        outputs: previous.map(|p| p.outputs).unwrap_or_default(),
+    };
+
+    write_entry(&dir, &entry).await?;
+    info!("Stored transcription for video {} in library", video_id);
+    Ok(entry)
+}
+
+/// Move a file into the library directory, falling back to copy when the
+/// source lives on another filesystem. Files already inside the library are
+/// left untouched.
+async fn move_into(dir: &Path, source: &Path) -> Result<PathBuf> {
+    let file_name = source
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid media path: {}", source.display()))?;
+    let target = dir.join(file_name);
+    if source == target {
+        return Ok(target);
+    }
+
+    if tokio::fs::rename(source, &target).await.is_err() {
+        tokio::fs::copy(source, &target).await?;
+        let _ = tokio::fs::remove_file(source).await;
+    }
+    Ok(target)
+}
+
+/// Move the downloaded media into the library so it survives temp cleanup
+pub async fn archive_media(
+    app_handle: &tauri::AppHandle,
+    video_id: &str,
+    video_path: &Path,
+    audio_path: &Path,
+) -> Result<LibraryEntry> {
+    let dir = entry_dir(app_handle, video_id)?;
+    let mut entry = lookup(app_handle, video_id)
+        .await?
+        .ok_or_else(|| anyhow!("No library entry for video {}", video_id))?;
+
+    entry.video_path = Some(move_into(&dir, video_path).await?);
+    entry.audio_path = Some(move_into(&dir, audio_path).await?);
+    entry.updated_at = now_secs();
+
+    write_entry(&dir, &entry).await?;
+    info!("Archived source media for video {} in library", video_id);
+    Ok(entry)
+}
+
+/// Remember a finished dub of a library video. An earlier output with the
+/// same language and settings is replaced.
+pub async fn record_output(
+    app_handle: &tauri::AppHandle,
+    video_id: &str,
+    job_id: &str,
+    target_language: &str,
+    settings_hash: &str,
+    output_path: &Path,
+) -> Result<()> {
+    let dir = entry_dir(app_handle, video_id)?;
+    let mut entry = lookup(app_handle, video_id)
+        .await?
+        .ok_or_else(|| anyhow!("No library entry for video {}", video_id))?;
+
+    entry
+        .outputs
+        .retain(|output| output.target_language != target_language || output.settings_hash != settings_hash);
+    entry.outputs.push(CompletedOutput {
+        job_id: job_id.to_string(),
+        target_language: target_language.to_string(),
+        settings_hash: settings_hash.to_string(),
+        output_path: output_path.to_path_buf(),
+        completed_at: now_secs(),
+    });
+    entry.updated_at = now_secs();
+
+    write_entry(&dir, &entry).await?;
+    info!("Recorded {} dub of video {} in library", target_language, video_id);
+    Ok(())
+}
+
+/// Find a finished dub of the video with the same language and settings whose
+/// output file still exists
+pub async fn find_completed(
+    app_handle: &tauri::AppHandle,
+    video_id: &str,
+    target_language: &str,
+    settings_hash: &str,
+) -> Option<CompletedOutput> {
+    let entry = lookup(app_handle, video_id).await.ok().flatten()?;
+    for output in entry.outputs.into_iter().rev() {
+        if output.target_language == target_language
+            && output.settings_hash == settings_hash
+            && check_file_exists_and_valid(&output.output_path).await
+        {
+            return Some(output);
+        }
+    }
+    None
+}
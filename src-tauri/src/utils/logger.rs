@@ -1,18 +1,145 @@
+//! Console logging and per-job log files.
+//!
+//! Everything goes to stderr through env_logger. While a job runs, the records
+//! that pass the filter are also written as JSON lines into `job.log` in the
+//! job's temp directory, so a user can attach the log of a failed run to a bug
+//! report. When the job ends the file moves next to the job record in the app
+//! data dir, where `get_job_log` finds it after the temp files are gone. Every
+//! run takes the queue's run slot, so every record belongs to the running job.
+
+use anyhow::{anyhow, Result};
 use env_logger::{Builder, Env};
-use log::LevelFilter;
-use std::io::Write;
+use log::{LevelFilter, Log, Metadata, Record};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const JOB_LOG_FILE: &str = "job.log";
+
+/// A line of a job log
+#[derive(Debug, Serialize)]
+struct LogLine<'a> {
+    timestamp: String,
+    level: &'a str,
+    target: &'a str,
+    job_id: &'a str,
+    message: String,
+}
+
+struct Capture {
+    job_id: String,
+    path: PathBuf,
+    file: LineWriter<File>,
+}
+
+static CAPTURE: Lazy<Mutex<Option<Capture>>> = Lazy::new(|| Mutex::new(None));
+
+/// env_logger, plus the log file of the running job
+struct JobLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for JobLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        self.inner.log(record);
+        let Ok(mut capture) = CAPTURE.lock() else { return };
+        if let Some(capture) = capture.as_mut() {
+            let line = LogLine {
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                level: record.level().as_str(),
+                target: record.target(),
+                job_id: &capture.job_id,
+                message: record.args().to_string(),
+            };
+            if let Ok(json) = serde_json::to_string(&line) {
+                let _ = writeln!(capture.file, "{}", json);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        if let Ok(mut capture) = CAPTURE.lock()
+            && let Some(capture) = capture.as_mut()
+        {
+            let _ = capture.file.flush();
+        }
+    }
+}
+
+/// Writes the log of a job while alive. Dropping it stops the capture and
+/// moves the file into `archive_dir`.
+pub struct JobLog {
+    job_id: String,
+    archive_dir: PathBuf,
+}
+
+impl JobLog {
+    pub fn start(job_id: &str, temp_dir: &Path, archive_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(temp_dir)?;
+        let path = temp_dir.join(JOB_LOG_FILE);
+        // A resumed job keeps the log of its earlier attempts
+        let archived = archive_dir.join(JOB_LOG_FILE);
+        if archived.exists() && !path.exists() {
+            std::fs::copy(&archived, &path)?;
+        }
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut capture = CAPTURE.lock().map_err(|_| anyhow!("Job log capture is poisoned"))?;
+        *capture = Some(Capture { job_id: job_id.to_string(), path, file: LineWriter::new(file) });
+        Ok(Self { job_id: job_id.to_string(), archive_dir: archive_dir.to_path_buf() })
+    }
+}
+
+impl Drop for JobLog {
+    fn drop(&mut self) {
+        let capture = match CAPTURE.lock() {
+            Ok(mut capture) if capture.as_ref().is_some_and(|c| c.job_id == self.job_id) => capture.take(),
+            _ => None,
+        };
+        let Some(mut capture) = capture else { return };
+        let _ = capture.file.flush();
+        drop(capture.file);
+        let archived = self.archive_dir.join(JOB_LOG_FILE);
+        let moved = std::fs::create_dir_all(&self.archive_dir)
+            .and_then(|_| std::fs::copy(&capture.path, &archived))
+            .and_then(|_| std::fs::remove_file(&capture.path));
+        if let Err(e) = moved {
+            log::warn!("Failed to archive the log of job {}: {}", self.job_id, e);
+        }
+    }
+}
+
+/// Log of a job: the file being written if it is running, the archived one otherwise
+pub fn read_job_log(job_id: &str, archive_dir: &Path) -> Result<String> {
+    let running = CAPTURE
+        .lock()
+        .ok()
+        .and_then(|capture| capture.as_ref().filter(|c| c.job_id == job_id).map(|c| c.path.clone()));
+    let path = running.unwrap_or_else(|| archive_dir.join(JOB_LOG_FILE));
+    std::fs::read_to_string(&path).map_err(|e| anyhow!("No log for job {}: {}", job_id, e))
+}
 
 pub fn init_logger() {
     // Set RUST_LOG explicitly for HTTP request tracing if not set
     if std::env::var("RUST_LOG").is_err() {
         // Use unsafe block for setting environment variables
         unsafe {
-            std::env::set_var("RUST_LOG", "warn,videonova=info,tts_sync=debug,reqwest=debug,openai=trace");
+            std::env::set_var("RUST_LOG", "warn,videonova=info,videonova_lib=info,tts_sync=debug,reqwest=debug,openai=trace");
         }
     }
     
     // Установка базового фильтра и переопределение через переменные окружения
-    let env = Env::default().filter_or("RUST_LOG", "warn,videonova=info,tts_sync=debug,reqwest=debug,openai=trace");
+    let env = Env::default().filter_or("RUST_LOG", "warn,videonova=info,videonova_lib=info,tts_sync=debug,reqwest=debug,openai=trace");
 
     let mut builder = Builder::from_env(env);
 
@@ -33,7 +160,7 @@ pub fn init_logger() {
         .filter_module("hyper::client", LevelFilter::Debug)
         .filter_module("rustls", LevelFilter::Debug)
         // Для модуля transcribe разрешаем также и DEBUG-сообщения
-        .filter_module("videonova::utils::transcribe", LevelFilter::Debug)
+        .filter_module("videonova_lib::utils::transcribe", LevelFilter::Debug)
         // Форматирование логов
         .format(|buf, record| {
             writeln!(
@@ -44,6 +171,32 @@ pub fn init_logger() {
                 record.args()
             )
         })
-        .target(env_logger::Target::Stderr) // Вывод в stderr для совместимости с консолью Tauri
-        .init();
+        .target(env_logger::Target::Stderr); // Вывод в stderr для совместимости с консолью Tauri
+
+    let inner = builder.build();
+    let max_level = inner.filter();
+    if log::set_boxed_logger(Box::new(JobLogger { inner })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_logs_move_to_the_archive_when_the_job_ends() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp_dir = dir.path().join("videonova_temp");
+        let archive_dir = dir.path().join("jobs").join("job-1");
+
+        let job_log = JobLog::start("job-1", &temp_dir, &archive_dir).unwrap();
+        assert!(temp_dir.join(JOB_LOG_FILE).exists());
+        assert!(read_job_log("job-1", &archive_dir).is_ok());
+        drop(job_log);
+
+        assert!(!temp_dir.join(JOB_LOG_FILE).exists());
+        assert!(read_job_log("job-1", &archive_dir).is_ok());
+        assert!(read_job_log("job-2", &dir.path().join("jobs").join("job-2")).is_err());
+    }
 }
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
 use std::error::Error as StdError;
@@ -6,22 +6,322 @@ use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::process::Command as TokioCommand;
 use tokio::sync::Mutex;
 use tokio::sync::mpsc;
 use tokio::time::{sleep, timeout};
+use tokio_util::sync::CancellationToken;
+
+use crate::utils::advanced_config;
+use crate::utils::ass::{self, AssScript};
+use crate::utils::audio_probe;
+use crate::utils::cancellation;
+use crate::utils::subtitle_layout::{self, SubtitlePosition};
+use crate::utils::subtitles;
+use crate::utils::tts::tts::audio::RenderedAudio;
+use crate::utils::tts::tts::vtt;
 
 /// Structure for holding merge progress information
 #[derive(Clone, Serialize, Deserialize)]
 pub struct MergeProgress {
     pub status: String,
     pub progress: f32,
+    /// Encoding speed as a multiple of real time, while ffmpeg runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f32>,
+}
+
+/// Share of the merge progress before ffmpeg starts encoding
+const ENCODE_START: f32 = 20.0;
+/// The rest is left for cleaning up after ffmpeg
+const ENCODE_END: f32 = 99.0;
+
+/// One report of `ffmpeg -progress`
+#[derive(Debug, Default, PartialEq)]
+struct FfmpegProgress {
+    /// Seconds of output written
+    out_time: f64,
+    speed: Option<f32>,
+    done: bool,
+}
+
+/// Collects the `key=value` lines of `ffmpeg -progress` into reports; each
+/// report ends with a `progress=continue` or `progress=end` line
+#[derive(Default)]
+struct ProgressParser {
+    current: FfmpegProgress,
+}
+
+impl ProgressParser {
+    fn feed(&mut self, line: &str) -> Option<FfmpegProgress> {
+        let (key, value) = line.trim().split_once('=')?;
+        match key {
+            // Despite the name, out_time_ms is in microseconds like out_time_us
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<i64>() {
+                    self.current.out_time = us.max(0) as f64 / 1_000_000.0;
+                }
+            }
+            // "1.52x", or "N/A" before the first frame
+            "speed" => self.current.speed = value.trim_end_matches('x').trim().parse().ok(),
+            "progress" => {
+                self.current.done = value == "end";
+                return Some(std::mem::take(&mut self.current));
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Settings key of the default styling of merged outputs
+pub const MERGE_STYLE_KEY: &str = "merge-style";
+
+/// Styling of the merged output, stored as the "merge-style" setting and
+/// overridable per job
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct MergeStyle {
+    /// Placement of the translated subtitles
+    pub subtitle_position: SubtitlePosition,
+    /// Components included in the output file
+    pub tracks: TrackSelection,
+    /// Mixed audio tracks added after the selected ones
+    pub layout: TrackLayout,
+    /// Burn the translated subtitles into the picture, for players that ignore
+    /// subtitle tracks. The tracks are still muxed as selected.
+    pub burn_in: Option<BurnInStyle>,
+    pub container: OutputContainer,
+    /// Produce the dubbed video or only its audio
+    pub output_mode: OutputMode,
+    /// Format of the audio-only output
+    pub audio_format: AudioFormat,
+}
+
+/// What the pipeline produces
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// The video with the dubbed audio and subtitle tracks
+    #[default]
+    Video,
+    /// Just the dubbed audio with the chapters of the video, to be listened to
+    /// like a podcast. The container, tracks and burn-in are ignored.
+    AudioOnly,
+}
+
+/// Format of the audio-only output
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// AAC in an MPEG-4 audiobook, chapters are shown by most podcast and book players
+    #[default]
+    M4b,
+    /// MP3 with the chapters as ID3 CHAP frames, plays everywhere
+    Mp3,
+}
+
+impl AudioFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::M4b => "m4b",
+            AudioFormat::Mp3 => "mp3",
+        }
+    }
+
+    /// Muxer, encoder and bitrate; speech doesn't need the bitrate of the video tracks
+    fn encoding(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            AudioFormat::M4b => ("ipod", "aac", "128k"),
+            AudioFormat::Mp3 => ("mp3", "libmp3lame", "160k"),
+        }
+    }
+}
+
+/// Container of the merged file
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputContainer {
+    /// H.264, AAC and mov_text subtitles, plays nearly everywhere
+    #[default]
+    Mp4,
+    /// H.264 with the subtitles kept as styled ASS and most audio copied as is
+    Mkv,
+    /// VP9, Opus and WebVTT subtitles, for browsers
+    Webm,
+}
+
+impl OutputContainer {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputContainer::Mp4 => "mp4",
+            OutputContainer::Mkv => "mkv",
+            OutputContainer::Webm => "webm",
+        }
+    }
+
+    /// Subtitle encoder; the inputs are ASS
+    fn subtitle_codec(self) -> &'static str {
+        match self {
+            OutputContainer::Mp4 => "mov_text",
+            OutputContainer::Mkv => "copy",
+            OutputContainer::Webm => "webvtt",
+        }
+    }
+
+    /// Whether an audio stream of this codec can go into the container without re-encoding
+    fn accepts_audio(self, codec: &str) -> bool {
+        match self {
+            OutputContainer::Mp4 => matches!(codec, "aac" | "mp3" | "ac3" | "eac3" | "alac"),
+            // PCM would fit as well, but makes the file huge
+            OutputContainer::Mkv => !codec.starts_with("pcm_"),
+            OutputContainer::Webm => matches!(codec, "opus" | "vorbis"),
+        }
+    }
+
+    /// Encoder and bitrate for audio the container doesn't accept
+    fn audio_encoder(self) -> (&'static str, &'static str) {
+        match self {
+            OutputContainer::Mp4 | OutputContainer::Mkv => ("aac", "192k"),
+            OutputContainer::Webm => ("libopus", "160k"),
+        }
+    }
+}
+
+/// Look of subtitles burned into the picture. Sizes are in script pixels of
+/// the subtitles, 288 of them span the height of the frame.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct BurnInStyle {
+    pub font: String,
+    pub font_size: u32,
+    pub outline: f32,
+    /// Distance from the bottom edge, or the top one with top placement
+    pub margin_v: u32,
+}
+
+impl Default for BurnInStyle {
+    fn default() -> Self {
+        Self { font: "Arial".to_string(), font_size: 20, outline: 2.0, margin_v: 16 }
+    }
+}
+
+impl BurnInStyle {
+    pub fn validate(&self) -> Result<()> {
+        if self.font.trim().is_empty() {
+            return Err(anyhow!("Burned-in subtitles need a font"));
+        }
+        if !(6..=120).contains(&self.font_size) {
+            return Err(anyhow!("Font size of burned-in subtitles must be between 6 and 120"));
+        }
+        if !(0.0..=10.0).contains(&self.outline) {
+            return Err(anyhow!("Outline of burned-in subtitles must be between 0 and 10"));
+        }
+        Ok(())
+    }
+
+    /// Put the font, size, outline and margin on every style of a script
+    fn apply(&self, script: &mut AssScript) {
+        script.set_style_field("Fontname", self.font.trim());
+        script.set_style_field("Fontsize", &self.font_size.to_string());
+        script.set_style_field("Outline", &self.outline.to_string());
+        script.set_style_field("MarginV", &self.margin_v.to_string());
+    }
+}
+
+/// Audio tracks made by mixing the dub with the original. The dub replaces
+/// the original in the first track and the original stays whole in the second;
+/// a voice-over track adds the dub over the original turned down, the way
+/// documentaries are dubbed, so the original voices can still be heard.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TrackLayout {
+    pub voice_over: bool,
+    /// Volume of the original under the dub in the voice-over track, 0 to 1
+    pub voice_over_volume: f32,
+}
+
+impl Default for TrackLayout {
+    fn default() -> Self {
+        Self { voice_over: false, voice_over_volume: 0.2 }
+    }
+}
+
+impl TrackLayout {
+    /// The voice-over track follows the dubbed and the original one
+    pub fn validate(&self, tracks: &TrackSelection) -> Result<()> {
+        if !self.voice_over {
+            return Ok(());
+        }
+        if !tracks.dubbed_audio || !tracks.original_audio {
+            return Err(anyhow!("The voice-over track needs both the dubbed and the original audio track"));
+        }
+        if !(0.0..=1.0).contains(&self.voice_over_volume) {
+            return Err(anyhow!("Volume of the original under the voice-over must be between 0 and 1"));
+        }
+        Ok(())
+    }
+
+    /// Filter mixing the dub of input `dub` over the original of input
+    /// `original`, its output is labelled `[voiceover]`
+    fn voice_over_filter(&self, dub: usize, original: usize) -> String {
+        self.voice_over_mix(&format!("{}:a", dub), &format!("{}:a", original))
+    }
+
+    /// Filter mixing the dub stream `dub` over the original stream `original`,
+    /// both given as ffmpeg stream specifiers
+    fn voice_over_mix(&self, dub: &str, original: &str) -> String {
+        format!(
+            "[{}]volume={:.2}[under];[{}][under]amix=inputs=2:duration=first:normalize=0[voiceover]",
+            original, self.voice_over_volume, dub
+        )
+    }
+}
+
+/// Which audio and subtitle tracks go into the merged file
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TrackSelection {
+    pub original_audio: bool,
+    pub dubbed_audio: bool,
+    pub original_subtitles: bool,
+    pub translated_subtitles: bool,
+    /// Translated text with the original line below it, in one track
+    pub bilingual_subtitles: bool,
+}
+
+impl Default for TrackSelection {
+    fn default() -> Self {
+        Self {
+            original_audio: true,
+            dubbed_audio: true,
+            original_subtitles: true,
+            translated_subtitles: true,
+            bilingual_subtitles: false,
+        }
+    }
+}
+
+impl TrackSelection {
+    /// A video without any audio track is never what the user wants
+    pub fn validate(&self) -> Result<()> {
+        if !self.original_audio && !self.dubbed_audio {
+            return Err(anyhow!("At least one audio track (original or dubbed) must be included"));
+        }
+        Ok(())
+    }
+}
+
+/// Metadata of one output audio or subtitle stream
+struct OutputTrack {
+    language: String,
+    title: String,
+    handler_name: &'static str,
 }
 
 // Add a new structure to control the ffmpeg process
 struct FfmpegMonitor {
-    pid: u32,
     is_stuck: bool,
     last_activity: Instant,
 }
@@ -81,16 +381,15 @@ async fn monitor_ffmpeg_process(pid: u32, monitor: Arc<Mutex<FfmpegMonitor>>) {
                 let lines: Vec<&str> = output_str.lines().collect();
                 if lines.len() >= 2 {
                     let stats = lines[1].trim();
-                    if let Some(cpu_str) = stats.split_whitespace().next() {
-                        if let Ok(cpu) = cpu_str.trim().parse::<f32>() {
-                            if cpu < 0.5 {
-                                warn!(
-                                    "ffmpeg process has very low CPU usage ({}%), possibly stuck",
-                                    cpu
-                                );
-                                is_stuck = true;
-                            }
-                        }
+                    if let Some(cpu_str) = stats.split_whitespace().next()
+                        && let Ok(cpu) = cpu_str.trim().parse::<f32>()
+                        && cpu < 0.5
+                    {
+                        warn!(
+                            "ffmpeg process has very low CPU usage ({}%), possibly stuck",
+                            cpu
+                        );
+                        is_stuck = true;
                     }
                 }
             }
@@ -187,24 +486,128 @@ async fn monitor_ffmpeg_process(pid: u32, monitor: Arc<Mutex<FfmpegMonitor>>) {
     }
 }
 
+/// What the merged file tells about itself, for media libraries like Jellyfin
+#[derive(Debug, Clone, Default)]
+pub struct OutputMetadata {
+    pub title: Option<String>,
+    /// Page of the source video
+    pub source_url: Option<String>,
+    /// Image embedded as cover art, converted to JPEG
+    pub thumbnail_url: Option<String>,
+}
+
+/// Where the translated audio track comes from
+pub enum TranslatedAudio<'a> {
+    /// A WAV file written by the synchronizer
+    File(&'a Path),
+    /// Audio kept in memory and piped into ffmpeg's stdin, so no full-length
+    /// intermediate WAV is written (low-disk mode)
+    Stream(RenderedAudio),
+}
+
+/// Header of a 16-bit PCM WAV stream of `samples` interleaved samples. The
+/// sizes in the header are 32-bit, audio over 4 GiB can't be described.
+fn wav_header(samples: usize, sample_rate: u32, channels: u16) -> std::io::Result<Vec<u8>> {
+    let invalid = |what: &str| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} does not fit a WAV header", what));
+    let data_len = samples
+        .checked_mul(2)
+        .and_then(|len| u32::try_from(len).ok())
+        .ok_or_else(|| invalid("Audio over 4 GiB"))?;
+    let riff_len = data_len.checked_add(36).ok_or_else(|| invalid("Audio over 4 GiB"))?;
+    let block_align = channels.checked_mul(2).ok_or_else(|| invalid("Channel count"))?;
+    let byte_rate = sample_rate.checked_mul(block_align as u32).ok_or_else(|| invalid("Sample rate"))?;
+
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&riff_len.to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    header.extend_from_slice(&channels.to_le_bytes());
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&byte_rate.to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    Ok(header)
+}
+
+/// Write the audio as a WAV stream into ffmpeg's stdin, chunk by chunk
+async fn pipe_audio(mut stdin: tokio::process::ChildStdin, audio: RenderedAudio) -> std::io::Result<()> {
+    const CHUNK_SAMPLES: usize = 64 * 1024;
+
+    stdin.write_all(&wav_header(audio.samples.len(), audio.sample_rate, audio.channels)?).await?;
+    let mut buffer = Vec::with_capacity(CHUNK_SAMPLES * 2);
+    for chunk in audio.samples.chunks(CHUNK_SAMPLES) {
+        buffer.clear();
+        for &sample in chunk {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        stdin.write_all(&buffer).await?;
+    }
+    stdin.flush().await?;
+    // Dropping stdin closes the pipe and lets ffmpeg finish reading
+    Ok(())
+}
+
+/// Files a merged output is made from
+pub struct MergeSources<'a> {
+    pub video_path: &'a Path,
+    pub translated_audio: TranslatedAudio<'a>,
+    pub original_audio_path: &'a Path,
+    pub original_vtt_path: &'a Path,
+    pub translated_vtt_path: &'a Path,
+    /// FFMETADATA chapters written into the output
+    pub chapters_path: Option<&'a Path>,
+}
+
+/// Codes and names the tracks of a merged output are labelled with
+#[derive(Debug, Clone, Copy)]
+pub struct TrackLanguages<'a> {
+    pub source_code: &'a str,
+    pub target_code: &'a str,
+    pub source_name: &'a str,
+    pub target_name: &'a str,
+}
+
 /// Merge video, audio, and subtitles files using ffmpeg
 pub async fn merge_files(
-    video_path: &Path,
-    translated_audio_path: &Path,
-    original_audio_path: &Path,
-    original_vtt_path: &Path,
-    translated_vtt_path: &Path,
+    sources: MergeSources<'_>,
     output_path: &Path,
-    source_language_code: &str,
-    target_language_code: &str,
-    source_language_name: &str,
-    target_language_name: &str,
+    languages: TrackLanguages<'_>,
+    style: &MergeStyle,
+    metadata: &OutputMetadata,
+    cancel: Option<&CancellationToken>,
     progress_tx: Option<mpsc::Sender<MergeProgress>>,
 ) -> Result<PathBuf, Box<dyn StdError + Send + Sync>> {
+    let MergeSources {
+        video_path,
+        translated_audio,
+        original_audio_path,
+        original_vtt_path,
+        translated_vtt_path,
+        chapters_path,
+    } = sources;
+    let TrackLanguages {
+        source_code: source_language_code,
+        target_code: target_language_code,
+        source_name: source_language_name,
+        target_name: target_language_name,
+    } = languages;
     log::info!("=== MERGE_FILES FUNCTION CALLED ===");
     log::info!("Input parameters:");
     log::info!("  Video: {}", video_path.display());
-    log::info!("  Translated Audio: {}", translated_audio_path.display());
+    match &translated_audio {
+        TranslatedAudio::File(path) => log::info!("  Translated Audio: {}", path.display()),
+        TranslatedAudio::Stream(audio) => log::info!(
+            "  Translated Audio: streamed ({} samples, {} Hz, {} ch)",
+            audio.samples.len(),
+            audio.sample_rate,
+            audio.channels
+        ),
+    }
     log::info!("  Original Audio: {}", original_audio_path.display());
     log::info!("  Original VTT: {}", original_vtt_path.display());
     log::info!("  Translated VTT: {}", translated_vtt_path.display());
@@ -231,150 +634,374 @@ pub async fn merge_files(
         tx.send(MergeProgress {
             status: "Starting merge process".to_string(),
             progress: 0.0,
+            speed: None,
         })
         .await?;
     }
 
-    // Convert VTT to ASS format for ffmpeg
+    let tracks = style.tracks;
+    tracks.validate().map_err(|e| e.to_string())?;
+    style.layout.validate(&tracks).map_err(|e| e.to_string())?;
+    if let Some(burn_in) = &style.burn_in {
+        burn_in.validate().map_err(|e| e.to_string())?;
+    }
+
+    // Convert the selected subtitles from VTT to ASS for ffmpeg
     let original_ass = output_dir.join(format!("{}_original.ass", video_stem));
     let translated_ass = output_dir.join(format!("{}_translated.ass", video_stem));
+    let bilingual_vtt = output_dir.join(format!("{}_bilingual.vtt", video_stem));
+    let bilingual_ass = output_dir.join(format!("{}_bilingual.ass", video_stem));
+    let burn_ass = output_dir.join(format!("{}_burn.ass", video_stem));
 
-    // Convert original VTT to ASS
     if let Some(tx) = &progress_tx {
         tx.send(MergeProgress {
             status: "Converting subtitles".to_string(),
             progress: 10.0,
+            speed: None,
         })
         .await?;
     }
 
-    let mut cmd = TokioCommand::new("ffmpeg");
-    cmd.arg("-y")
-        .arg("-i")
-        .arg(original_vtt_path)
-        .arg(&original_ass);
+    // Cue boundaries snap to the frames of the source, so no frame flashes between cues
+    let fps = match video_frame_rate(video_path).await {
+        Ok(fps) => {
+            log::info!("Aligning subtitle timing to {:.3} fps", fps);
+            Some(fps)
+        }
+        Err(e) => {
+            warn!("Failed to read the frame rate, keeping subtitle timing as is: {}", e);
+            None
+        }
+    };
 
-    let output = cmd.output().await?;
-    if !output.status.success() {
-        let error = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to convert original subtitles: {}", error).into());
+    // Source subtitles supplied as ASS keep their script next to the VTT; the
+    // original track is that script and the translations take over its styles
+    let template = match tokio::fs::read_to_string(original_vtt_path.with_extension("ass")).await {
+        Ok(content) => match ass::parse(&content) {
+            Ok(script) => Some(script),
+            Err(e) => {
+                warn!("Ignoring the ASS script of the source subtitles: {}", e);
+                None
+            }
+        },
+        Err(_) => None,
+    };
+
+    if tracks.original_subtitles {
+        let converted = match &template {
+            Some(script) => write_ass(&script.to_string(), &original_ass, fps).await,
+            None => convert_to_ass(original_vtt_path, &original_ass, None, fps).await,
+        };
+        converted.map_err(|e| format!("Failed to convert original subtitles: {}", e))?;
     }
 
-    // Convert translated VTT to ASS
-    let mut cmd = TokioCommand::new("ffmpeg");
-    cmd.arg("-y")
-        .arg("-i")
-        .arg(translated_vtt_path)
-        .arg(&translated_ass);
+    // Keep translated subtitles clear of on-screen graphics if requested
+    let translated_needed = tracks.translated_subtitles || style.burn_in.is_some();
+    let position = if translated_needed || tracks.bilingual_subtitles {
+        Some(subtitle_layout::resolve(style.subtitle_position, video_path).await)
+    } else {
+        None
+    };
 
-    let output = cmd.output().await?;
-    if !output.status.success() {
-        let error = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to convert translated subtitles: {}", error).into());
+    if translated_needed {
+        convert_to_ass(translated_vtt_path, &translated_ass, template.as_ref(), fps)
+            .await
+            .map_err(|e| format!("Failed to convert translated subtitles: {}", e))?;
+        if let Some(position) = position {
+            subtitle_layout::apply_to_ass(&translated_ass, position)
+                .await
+                .map_err(|e| format!("Failed to position translated subtitles: {}", e))?;
+        }
+    }
+
+    if tracks.bilingual_subtitles {
+        let original = tokio::fs::read_to_string(original_vtt_path).await?;
+        let translated = tokio::fs::read_to_string(translated_vtt_path).await?;
+        let combined = subtitles::bilingual_vtt(&original, &translated).map_err(|e| e.to_string())?;
+        tokio::fs::write(&bilingual_vtt, combined).await?;
+        convert_to_ass(&bilingual_vtt, &bilingual_ass, None, fps)
+            .await
+            .map_err(|e| format!("Failed to convert bilingual subtitles: {}", e))?;
+        if let Some(position) = position {
+            subtitle_layout::apply_to_ass(&bilingual_ass, position)
+                .await
+                .map_err(|e| format!("Failed to position bilingual subtitles: {}", e))?;
+        }
+    }
+
+    // The burned-in copy takes the look of the translated track and the configured font
+    if let Some(burn_in) = &style.burn_in {
+        let content = tokio::fs::read_to_string(&translated_ass).await?;
+        let mut script = ass::parse(&content).map_err(|e| format!("Failed to read translated subtitles: {}", e))?;
+        burn_in.apply(&mut script);
+        tokio::fs::write(&burn_ass, script.to_string()).await?;
     }
 
+    // The thumbnail becomes the cover art, WebM has no place for it
+    let container = style.container;
+    let cover = output_dir.join(format!("{}_cover.jpg", video_stem));
+    let has_cover = match metadata.thumbnail_url.as_deref().filter(|url| !url.is_empty()) {
+        Some(url) if container != OutputContainer::Webm => match fetch_cover(url, &cover).await {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Leaving out the cover art: {}", e);
+                false
+            }
+        },
+        _ => false,
+    };
+
     if let Some(tx) = &progress_tx {
         tx.send(MergeProgress {
             status: "Merging video and audio".to_string(),
-            progress: 20.0,
+            progress: ENCODE_START,
+            speed: None,
         })
         .await?;
     }
 
-    // Prepare final merge command
-    let mut cmd = TokioCommand::new("ffmpeg");
+    // Prepare final merge command, inputs are numbered in the order they are added
+    let mut cmd = TokioCommand::new(crate::utils::tools::ffmpeg());
     cmd.arg("-y") // Overwrite output file if it exists
+        .args(["-progress", "pipe:1", "-nostats"])
         .arg("-i")
-        .arg(video_path)
-        .arg("-i")
-        .arg(translated_audio_path)
-        .arg("-i")
-        .arg(original_audio_path)
-        .arg("-i")
-        .arg(&original_ass)
-        .arg("-i")
-        .arg(&translated_ass)
-        .arg("-map")
-        .arg("0:v") // Video stream
-        .arg("-map")
-        .arg("1:a") // First audio track: Translated + Instrumental (final_mixed.wav)
-        .arg("-map")
-        .arg("2:a") // Second audio track: Original
-        .arg("-map")
-        .arg("3") // Original subtitles
-        .arg("-map")
-        .arg("4") // Translated subtitles
-        // Video settings for compatibility
-        .arg("-c:v")
-        .arg("libx264")
-        .arg("-pix_fmt")
-        .arg("yuv420p")
-        .arg("-profile:v")
-        .arg("high")
-        .arg("-level")
-        .arg("4.1")
-        // Audio settings
-        .arg("-c:a")
-        .arg("aac")
-        .arg("-b:a")
-        .arg("192k")
-        // Subtitle settings
-        .arg("-c:s")
-        .arg("mov_text") // Using standard mov_text encoder
-        .arg("-disposition:s:0")
-        .arg("none")
-        .arg("-disposition:s:1")
-        .arg("none")
+        .arg(video_path);
+    let mut input_count = 1;
+    let mut maps = vec!["0:v".to_string()];
+    let mut audio_tracks = Vec::new();
+    // Codec of each audio input, None when unknown
+    let mut audio_codecs: Vec<Option<String>> = Vec::new();
+    let mut subtitle_tracks = Vec::new();
+
+    // The dubbed track comes first so it's the default one
+    let mut streamed_audio = None;
+    let dub_input = input_count;
+    if tracks.dubbed_audio {
+        match translated_audio {
+            TranslatedAudio::File(path) => {
+                cmd.arg("-i").arg(path);
+                audio_codecs.push(audio_probe::audio_codec(path).await.ok());
+            }
+            TranslatedAudio::Stream(audio) => {
+                cmd.args(["-f", "wav", "-i", "pipe:0"]);
+                streamed_audio = Some(audio);
+                audio_codecs.push(Some("pcm_s16le".to_string()));
+            }
+        }
+        maps.push(format!("{}:a", input_count));
+        input_count += 1;
+        audio_tracks.push(OutputTrack {
+            language: convert_to_iso_639_2(target_language_code),
+            title: format!("{} Audio", target_language_name),
+            handler_name: "Audio Track (Translated)",
+        });
+    }
+    if tracks.original_audio {
+        cmd.arg("-i").arg(original_audio_path);
+        audio_codecs.push(audio_probe::audio_codec(original_audio_path).await.ok());
+        maps.push(format!("{}:a", input_count));
+        if style.layout.voice_over {
+            // Mixed audio is always encoded
+            cmd.arg("-filter_complex").arg(style.layout.voice_over_filter(dub_input, input_count));
+            maps.push("[voiceover]".to_string());
+        }
+        input_count += 1;
+        audio_tracks.push(OutputTrack {
+            language: convert_to_iso_639_2(source_language_code),
+            title: format!("{} Audio", source_language_name),
+            handler_name: "Audio Track (Original)",
+        });
+        if style.layout.voice_over {
+            audio_codecs.push(None);
+            audio_tracks.push(OutputTrack {
+                language: convert_to_iso_639_2(target_language_code),
+                title: format!("{} Voice-over", target_language_name),
+                handler_name: "Audio Track (Voice-over)",
+            });
+        }
+    }
+
+    let subtitle_inputs = [
+        (
+            tracks.original_subtitles,
+            &original_ass,
+            source_language_code,
+            format!("{} Subtitles", source_language_name),
+            "Subtitles (Original)",
+        ),
+        (
+            tracks.translated_subtitles,
+            &translated_ass,
+            target_language_code,
+            format!("{} Subtitles", target_language_name),
+            "Subtitles (Translated)",
+        ),
+        (
+            tracks.bilingual_subtitles,
+            &bilingual_ass,
+            target_language_code,
+            format!("{} / {} Subtitles", target_language_name, source_language_name),
+            "Subtitles (Bilingual)",
+        ),
+    ];
+    for (included, path, language, title, handler_name) in subtitle_inputs {
+        if !included {
+            continue;
+        }
+        cmd.arg("-i").arg(path);
+        maps.push(input_count.to_string());
+        input_count += 1;
+        subtitle_tracks.push(OutputTrack { language: convert_to_iso_639_2(language), title, handler_name });
+    }
+
+    // Chapters from an FFMETADATA file replace the ones of the source video
+    if let Some(chapters_path) = chapters_path {
+        cmd.arg("-i")
+            .arg(chapters_path)
+            .arg("-map_chapters")
+            .arg(input_count.to_string());
+        input_count += 1;
+    }
+
+    // MP4 takes the cover as a second video stream, MKV as an attachment
+    if has_cover && container == OutputContainer::Mp4 {
+        cmd.arg("-i").arg(&cover);
+        maps[0] = "0:v:0".to_string();
+        maps.push(format!("{}:v", input_count));
+    }
+
+    for map in &maps {
+        cmd.arg("-map").arg(map);
+    }
+
+    if style.burn_in.is_some() {
+        cmd.arg("-filter:v:0").arg(format!("ass=filename={}", filter_path(&burn_ass)));
+    }
+
+    // Video settings for compatibility
+    if container == OutputContainer::Webm {
+        cmd.args(["-c:v", "libvpx-vp9", "-crf", "32", "-b:v", "0", "-row-mt", "1", "-pix_fmt", "yuv420p"]);
+    } else {
+        cmd.args(["-c:v", "libx264", "-pix_fmt", "yuv420p", "-profile:v", "high", "-level", "4.1"]);
+    }
+    if has_cover && container == OutputContainer::Mp4 {
+        cmd.args(["-c:v:1", "copy", "-disposition:v:1", "attached_pic"]);
+    }
+
+    // Audio the container takes as is is copied, the rest re-encoded
+    for (index, codec) in audio_codecs.iter().enumerate() {
+        match codec.as_deref().filter(|codec| container.accepts_audio(codec)) {
+            Some(codec) => {
+                log::info!("Copying {} audio into track {}", codec, index);
+                cmd.arg(format!("-c:a:{}", index)).arg("copy");
+            }
+            None => {
+                let (encoder, bitrate) = container.audio_encoder();
+                log::info!(
+                    "Re-encoding {} audio of track {} to {}",
+                    codec.as_deref().unwrap_or("unknown"),
+                    index,
+                    encoder
+                );
+                cmd.arg(format!("-c:a:{}", index))
+                    .arg(encoder)
+                    .arg(format!("-b:a:{}", index))
+                    .arg(bitrate);
+                if container == OutputContainer::Mp4 {
+                    cmd.arg(format!("-tag:a:{}", index)).arg("mp4a");
+                }
+            }
+        }
+    }
+
+    cmd.arg("-c:s").arg(container.subtitle_codec());
+    if container == OutputContainer::Mp4 {
         // QuickTime specific compatibility flags
-        .arg("-movflags")
-        .arg("+faststart+rtphint")
-        .arg("-tag:v")
-        .arg("avc1")
-        .arg("-tag:a")
-        .arg("mp4a")
-        // Set metadata for audio tracks
-        // First audio track (translated + instrumental)
-        .arg("-metadata:s:a:0")
-        .arg(format!("language={}", convert_to_iso_639_2(target_language_code)))
-        .arg("-metadata:s:a:0")
-        .arg(format!("title={} Audio", target_language_name))
-        .arg("-metadata:s:a:0")
-        .arg("handler_name=Audio Track (Translated)")
-        .arg("-disposition:a:0")
-        .arg("default")
-        // Second audio track (original)
-        .arg("-metadata:s:a:1")
-        .arg(format!("language={}", convert_to_iso_639_2(source_language_code)))
-        .arg("-metadata:s:a:1")
-        .arg(format!("title={} Audio", source_language_name))
-        .arg("-metadata:s:a:1")
-        .arg("handler_name=Audio Track (Original)")
-        .arg("-disposition:a:1")
-        .arg("none")
-        // Subtitle metadata
-        .arg("-metadata:s:s:0")
-        .arg(format!("language={}", convert_to_iso_639_2(source_language_code)))
-        .arg("-metadata:s:s:0")
-        .arg(format!("title={} Subtitles", source_language_name))
-        .arg("-metadata:s:s:0")
-        .arg("handler_name=Subtitles (Original)")
-        .arg("-metadata:s:s:1")
-        .arg(format!("language={}", convert_to_iso_639_2(target_language_code)))
-        .arg("-metadata:s:s:1")
-        .arg(format!("title={} Subtitles", target_language_name))
-        .arg("-metadata:s:s:1")
-        .arg("handler_name=Subtitles (Translated)")
-        .arg(&output_path);
+        cmd.args(["-movflags", "+faststart+rtphint", "-tag:v:0", "avc1"]);
+    }
+    if has_cover && container == OutputContainer::Mkv {
+        cmd.arg("-attach")
+            .arg(&cover)
+            .args(["-metadata:s:t:0", "mimetype=image/jpeg", "-metadata:s:t:0", "filename=cover.jpg"]);
+    }
+    for tag in global_metadata(
+        metadata,
+        container,
+        source_language_code,
+        target_language_code,
+        source_language_name,
+        target_language_name,
+    ) {
+        cmd.arg("-metadata").arg(tag);
+    }
+
+    // Track metadata; the first audio track is the default, subtitles are off by default
+    for (index, track) in audio_tracks.iter().enumerate() {
+        add_track_metadata(&mut cmd, &format!("a:{}", index), track);
+        cmd.arg(format!("-disposition:a:{}", index))
+            .arg(if index == 0 { "default" } else { "none" });
+    }
+    for (index, track) in subtitle_tracks.iter().enumerate() {
+        add_track_metadata(&mut cmd, &format!("s:{}", index), track);
+        cmd.arg(format!("-disposition:s:{}", index)).arg("none");
+    }
+    let advanced = advanced_config::current();
+    cmd.args(&advanced.ffmpeg.merge_args);
+    cmd.arg(output_path);
 
     log::info!("Executing ffmpeg command: {:?}", cmd);
 
     // Execute ffmpeg with progress monitoring
+    if streamed_audio.is_some() {
+        cmd.stdin(Stdio::piped());
+    }
     let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
 
+    // Feed the translated audio while ffmpeg runs
+    let pipe_task = match (streamed_audio, child.stdin.take()) {
+        (Some(audio), Some(stdin)) => Some(tokio::spawn(pipe_audio(stdin, audio))),
+        (Some(_), None) => return Err("Failed to open ffmpeg stdin".into()),
+        _ => None,
+    };
+
+    // Real progress from the output time ffmpeg reports, against the length of the video
+    let duration = audio_probe::duration(video_path).await.ok().filter(|d| *d > 0.0);
+    let progress_task = match (child.stdout.take(), progress_tx.clone()) {
+        (Some(stdout), Some(tx)) => Some(tokio::spawn(async move {
+            use tokio::io::AsyncBufReadExt;
+            let mut lines = tokio::io::BufReader::new(stdout).lines();
+            let mut parser = ProgressParser::default();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Some(report) = parser.feed(&line) else { continue };
+                let Some(duration) = duration else { continue };
+                let fraction = (report.out_time / duration).clamp(0.0, 1.0) as f32;
+                let progress = MergeProgress {
+                    status: match report.speed {
+                        Some(speed) => format!("Encoding at {:.1}x", speed),
+                        None => "Merging video and audio".to_string(),
+                    },
+                    progress: ENCODE_START + (ENCODE_END - ENCODE_START) * fraction,
+                    speed: report.speed,
+                };
+                if tx.send(progress).await.is_err() || report.done {
+                    break;
+                }
+            }
+        })),
+        _ => None,
+    };
+    // ffmpeg stalls once the pipe is full, its stderr is read while it runs
+    let stderr_task = child.stderr.take().map(|mut stderr| {
+        tokio::spawn(async move {
+            let mut content = Vec::new();
+            let _ = stderr.read_to_end(&mut content).await;
+            content
+        })
+    });
+
     // Monitor progress
     let pid = child.id().ok_or("Failed to get process ID")?;
     let monitor = Arc::new(Mutex::new(FfmpegMonitor {
-        pid,
         is_stuck: false,
         last_activity: Instant::now(),
     }));
@@ -385,37 +1012,80 @@ pub async fn merge_files(
         monitor_ffmpeg_process(pid, monitor_clone).await;
     });
 
-    // Wait for completion with timeout
-    let status = match timeout(Duration::from_secs(600), child.wait()).await {
-        Ok(result) => result?,
-        Err(_) => {
-            error!("ffmpeg process timed out after 10 minutes");
-            return Err("ffmpeg process timed out after 10 minutes".into());
+    // Wait for completion with timeout, or until the job is cancelled
+    let cancelled = async {
+        match cancel {
+            Some(token) => token.cancelled().await,
+            None => std::future::pending().await,
         }
     };
-
-    if !status.success() {
-        let mut stderr_content = Vec::new();
-        if let Some(mut stderr) = child.stderr {
-            if let Err(e) = stderr.read_to_end(&mut stderr_content).await {
-                error!("Failed to read stderr: {}", e);
-                return Err("Failed to read ffmpeg error output".into());
+    let waited = tokio::select! {
+        result = timeout(Duration::from_secs(advanced.timeouts.merge_secs), child.wait()) => Some(result),
+        _ = cancelled => None,
+    };
+    let status = match waited {
+        Some(Ok(result)) => result?,
+        Some(Err(_)) => {
+            let message = format!("ffmpeg process timed out after {} seconds", advanced.timeouts.merge_secs);
+            error!("{}", message);
+            return Err(message.into());
+        }
+        None => {
+            warn!("Merge cancelled, stopping ffmpeg");
+            if let Err(e) = child.kill().await {
+                warn!("Failed to stop ffmpeg: {}", e);
+            }
+            if let Some(pipe_task) = pipe_task {
+                pipe_task.abort();
+            }
+            if let Some(progress_task) = progress_task {
+                progress_task.abort();
             }
+            // Neither the half-written output nor the converted subtitles are of any use
+            let leftovers: [&Path; 7] =
+                [output_path, &original_ass, &translated_ass, &bilingual_vtt, &bilingual_ass, &burn_ass, &cover];
+            for path in leftovers {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+            return Err(cancellation::CANCELLED.into());
         }
+    };
+
+    if let Some(pipe_task) = pipe_task {
+        match pipe_task.await {
+            Ok(Ok(())) => {}
+            // ffmpeg closing the pipe early shows up as a failed status below
+            Ok(Err(e)) => warn!("Failed to stream translated audio into ffmpeg: {}", e),
+            Err(e) => warn!("Audio streaming task failed: {}", e),
+        }
+    }
+
+    // The last reports go out before the completion
+    if let Some(progress_task) = progress_task {
+        let _ = progress_task.await;
+    }
+
+    if !status.success() {
+        let stderr_content = match stderr_task {
+            Some(task) => task.await.unwrap_or_default(),
+            None => Vec::new(),
+        };
         let error_message = String::from_utf8_lossy(&stderr_content);
         error!("ffmpeg error: {}", error_message);
         return Err(format!("ffmpeg failed: {}", error_message).into());
     }
 
     // Clean up temporary subtitle files
-    let _ = tokio::fs::remove_file(&original_ass).await;
-    let _ = tokio::fs::remove_file(&translated_ass).await;
+    for path in [&original_ass, &translated_ass, &bilingual_vtt, &bilingual_ass, &burn_ass, &cover] {
+        let _ = tokio::fs::remove_file(path).await;
+    }
 
     // Send completion progress
     if let Some(tx) = &progress_tx {
         tx.send(MergeProgress {
             status: "Merge complete".to_string(),
             progress: 100.0,
+            speed: None,
         })
         .await?;
     }
@@ -423,6 +1093,467 @@ pub async fn merge_files(
     Ok(output_path.to_path_buf())
 }
 
+/// Cut a start/end span out of a media file into `output_dir`, for clips of
+/// media that was downloaded whole. The streams are re-encoded, so the clip
+/// starts exactly at `start` instead of the keyframe before it and audio and
+/// video stay aligned.
+pub async fn trim(input: &Path, start: f64, end: f64, output_dir: &Path) -> Result<PathBuf> {
+    let stem = input.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = input.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_else(|| "mp4".to_string());
+    let output = output_dir.join(format!("{}_{:.0}-{:.0}.{}", stem, start, end, extension));
+
+    let output_result = TokioCommand::new(crate::utils::tools::ffmpeg())
+        .args(["-y", "-ss", &format!("{:.3}", start), "-i"])
+        .arg(input)
+        .args(["-t", &format!("{:.3}", end - start)])
+        .args(["-map", "0", "-c:v", "libx264", "-preset", "veryfast", "-crf", "18", "-c:a", "aac", "-b:a", "192k"])
+        .arg(&output)
+        .output()
+        .await?;
+    if !output_result.status.success() {
+        return Err(anyhow!("Failed to trim {}: {}", input.display(), String::from_utf8_lossy(&output_result.stderr)));
+    }
+    info!("Trimmed {:.1}s-{:.1}s of {} into {}", start, end, input.display(), output.display());
+    Ok(output)
+}
+
+/// Dubbed audio and subtitles of a further target language of the same video
+#[derive(Debug, Clone)]
+pub struct LanguageTrack {
+    pub code: String,
+    pub name: String,
+    pub audio_path: PathBuf,
+    pub vtt_path: PathBuf,
+}
+
+/// Number of streams of a kind (`a`, `s`) in a media file
+async fn stream_count(path: &Path, kind: &str) -> Result<usize> {
+    let output = TokioCommand::new(crate::utils::tools::ffprobe())
+        .args(["-v", "error", "-select_streams", kind, "-show_entries", "stream=index", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!("ffprobe error: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).lines().filter(|line| !line.trim().is_empty()).count())
+}
+
+/// Add the audio and subtitle tracks of further target languages to a merged
+/// MKV, writing `output`. The streams already in the video are copied, the
+/// added audio is encoded like any other the container doesn't take as is.
+pub async fn add_language_tracks(
+    video: &Path,
+    languages: &[LanguageTrack],
+    output: &Path,
+    cancel: &CancellationToken,
+) -> Result<PathBuf> {
+    let audio_offset = stream_count(video, "a").await?;
+    let subtitle_offset = stream_count(video, "s").await?;
+    let fps = video_frame_rate(video).await.ok();
+
+    let mut cmd = TokioCommand::new(crate::utils::tools::ffmpeg());
+    cmd.arg("-y").arg("-i").arg(video);
+    let mut maps = vec!["0".to_string()];
+    for (index, language) in languages.iter().enumerate() {
+        let ass_path = language.vtt_path.with_extension("ass");
+        convert_to_ass(&language.vtt_path, &ass_path, None, fps)
+            .await
+            .map_err(|e| anyhow!("Failed to convert {} subtitles: {}", language.name, e))?;
+        cmd.arg("-i").arg(&language.audio_path).arg("-i").arg(&ass_path);
+        maps.push(format!("{}:a", 1 + index * 2));
+        maps.push((2 + index * 2).to_string());
+    }
+    for map in &maps {
+        cmd.arg("-map").arg(map);
+    }
+    cmd.args(["-c", "copy"]);
+
+    let (encoder, bitrate) = OutputContainer::Mkv.audio_encoder();
+    for (index, language) in languages.iter().enumerate() {
+        let audio = format!("a:{}", audio_offset + index);
+        let subtitles = format!("s:{}", subtitle_offset + index);
+        cmd.arg(format!("-c:{}", audio)).arg(encoder).arg(format!("-b:{}", audio)).arg(bitrate);
+        let language_code = convert_to_iso_639_2(&language.code);
+        add_track_metadata(
+            &mut cmd,
+            &audio,
+            &OutputTrack {
+                language: language_code.clone(),
+                title: format!("{} Audio", language.name),
+                handler_name: "Audio Track (Translated)",
+            },
+        );
+        add_track_metadata(
+            &mut cmd,
+            &subtitles,
+            &OutputTrack {
+                language: language_code,
+                title: format!("{} Subtitles", language.name),
+                handler_name: "Subtitles (Translated)",
+            },
+        );
+        cmd.arg(format!("-disposition:{}", audio)).arg("none");
+        cmd.arg(format!("-disposition:{}", subtitles)).arg("none");
+    }
+    cmd.arg(output).kill_on_drop(true);
+
+    info!("Adding {} language(s) to {}: {:?}", languages.len(), video.display(), cmd);
+    let output_result = cancellation::run(cancel, cmd.output()).await??;
+    if !output_result.status.success() {
+        return Err(anyhow!("Failed to add language tracks: {}", String::from_utf8_lossy(&output_result.stderr)));
+    }
+    Ok(output.to_path_buf())
+}
+
+/// Encode the dubbed audio alone into `output`, with the chapters of an
+/// FFMETADATA file and the title of the video. Streamed audio is piped into
+/// ffmpeg like for a merge.
+pub async fn export_audio(
+    audio: TranslatedAudio<'_>,
+    output: &Path,
+    format: AudioFormat,
+    target_language_code: &str,
+    title: &str,
+    chapters_path: Option<&Path>,
+    cancel: &CancellationToken,
+) -> Result<PathBuf> {
+    let (muxer, encoder, bitrate) = format.encoding();
+    let mut cmd = TokioCommand::new(crate::utils::tools::ffmpeg());
+    cmd.arg("-y");
+    let streamed_audio = match audio {
+        TranslatedAudio::File(path) => {
+            cmd.arg("-i").arg(path);
+            None
+        }
+        TranslatedAudio::Stream(audio) => {
+            cmd.args(["-f", "wav", "-i", "pipe:0"]).stdin(Stdio::piped());
+            Some(audio)
+        }
+    };
+    if let Some(chapters_path) = chapters_path {
+        cmd.arg("-i").arg(chapters_path).args(["-map_chapters", "1"]);
+    }
+    cmd.args(["-map", "0:a", "-c:a", encoder, "-b:a", bitrate])
+        .arg("-metadata")
+        .arg(format!("title={}", title))
+        .arg("-metadata:s:a:0")
+        .arg(format!("language={}", convert_to_iso_639_2(target_language_code)));
+    if format == AudioFormat::Mp3 {
+        cmd.args(["-id3v2_version", "3"]);
+    }
+    cmd.args(["-f", muxer]).arg(output).stdout(Stdio::null()).stderr(Stdio::piped()).kill_on_drop(true);
+
+    info!("Exporting the dubbed audio to {}: {:?}", output.display(), cmd);
+    let mut child = cmd.spawn()?;
+    let pipe_task = match (streamed_audio, child.stdin.take()) {
+        (Some(audio), Some(stdin)) => Some(tokio::spawn(pipe_audio(stdin, audio))),
+        (Some(_), None) => return Err(anyhow!("Failed to open ffmpeg stdin")),
+        _ => None,
+    };
+    let output_result = match cancellation::run(cancel, child.wait_with_output()).await {
+        Ok(result) => result?,
+        Err(e) => {
+            if let Some(pipe_task) = pipe_task {
+                pipe_task.abort();
+            }
+            let _ = tokio::fs::remove_file(output).await;
+            return Err(e);
+        }
+    };
+    if let Some(pipe_task) = pipe_task
+        && let Ok(Err(e)) = pipe_task.await
+    {
+        warn!("Failed to stream the dubbed audio into ffmpeg: {}", e);
+    }
+    if !output_result.status.success() {
+        return Err(anyhow!("Failed to export the dubbed audio: {}", String::from_utf8_lossy(&output_result.stderr)));
+    }
+    Ok(output.to_path_buf())
+}
+
+/// Handler names and titles of the audio tracks of a media file, in order
+async fn audio_track_labels(path: &Path) -> Result<Vec<(String, String)>> {
+    let output = TokioCommand::new(crate::utils::tools::ffprobe())
+        .args(["-v", "error", "-select_streams", "a", "-show_entries", "stream=index:stream_tags", "-of", "json"])
+        .arg(path)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!("ffprobe error: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    let probe: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let streams = probe["streams"].as_array().cloned().unwrap_or_default();
+    Ok(streams
+        .iter()
+        .map(|stream| {
+            // Matroska upper-cases the tag names
+            let tag = |name: &str| {
+                stream["tags"]
+                    .as_object()
+                    .and_then(|tags| tags.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)))
+                    .and_then(|(_, value)| value.as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            (tag("handler_name"), tag("title"))
+        })
+        .collect())
+}
+
+/// Put `audio` in place of the dubbed track of a merged video, writing
+/// `output`. The dubbed track is the first audio track; the new one takes its
+/// place and metadata, everything else is copied. A voice-over track is mixed
+/// again from the new dub and the original track at `voice_over_volume`.
+pub async fn replace_dubbed_audio(video: &Path, audio: &Path, output: &Path, voice_over_volume: f32) -> Result<PathBuf> {
+    let container = match video.extension().and_then(|extension| extension.to_str()) {
+        Some("webm") => OutputContainer::Webm,
+        Some("mkv") => OutputContainer::Mkv,
+        _ => OutputContainer::Mp4,
+    };
+    let (encoder, bitrate) = container.audio_encoder();
+    let labels = audio_track_labels(video).await?;
+    let voice_over = labels
+        .iter()
+        .position(|(handler, title)| handler == "Audio Track (Voice-over)" || title.ends_with(" Voice-over"))
+        .filter(|&track| track > 0);
+    // merge_files puts the original right before the voice-over
+    let original = labels
+        .iter()
+        .position(|(handler, _)| handler == "Audio Track (Original)")
+        .or_else(|| voice_over.map(|track| track - 1))
+        .filter(|&track| track > 0);
+
+    let mut cmd = TokioCommand::new(crate::utils::tools::ffmpeg());
+    cmd.arg("-y").arg("-i").arg(video).arg("-i").arg(audio);
+    cmd.args(["-map", "0:v", "-map", "1:a:0"]);
+    match (voice_over, original) {
+        (Some(voice_over), Some(original)) => {
+            let layout = TrackLayout { voice_over: true, voice_over_volume };
+            cmd.arg("-filter_complex").arg(layout.voice_over_mix("1:a:0", &format!("0:a:{}", original)));
+            // The tracks after the dub keep their positions
+            for track in 1..labels.len() {
+                if track == voice_over {
+                    cmd.args(["-map", "[voiceover]"]);
+                } else {
+                    cmd.arg("-map").arg(format!("0:a:{}", track));
+                }
+            }
+            cmd.arg(format!("-map_metadata:s:a:{}", voice_over)).arg(format!("0:s:a:{}", voice_over));
+            cmd.arg(format!("-c:a:{}", voice_over)).arg(encoder);
+            cmd.arg(format!("-b:a:{}", voice_over)).arg(bitrate);
+        }
+        (Some(voice_over), None) => {
+            warn!("{} has a voice-over track but no original one to mix it from, dropping it", video.display());
+            cmd.args(["-map", "0:a?", "-map", "-0:a:0"]).arg("-map").arg(format!("-0:a:{}", voice_over));
+        }
+        _ => {
+            cmd.args(["-map", "0:a?", "-map", "-0:a:0"]);
+        }
+    }
+    let output_result = cmd
+        .args(["-map", "0:s?"])
+        .args(["-map_metadata", "0", "-map_metadata:s:a:0", "0:s:a:0", "-c", "copy"])
+        .args(["-c:a:0", encoder, "-b:a:0", bitrate, "-disposition:a:0", "default"])
+        .arg(output)
+        .output()
+        .await?;
+    if !output_result.status.success() {
+        return Err(anyhow!("Failed to replace the dubbed audio: {}", String::from_utf8_lossy(&output_result.stderr)));
+    }
+    info!("Replaced the dubbed audio of {}", video.display());
+    Ok(output.to_path_buf())
+}
+
+/// Convert a subtitle file to ASS, aligning the cues to the frames of a video
+/// with the given frame rate. With a template script the cues are written
+/// natively in its styles and positions, otherwise ffmpeg converts the file.
+async fn convert_to_ass(input: &Path, output: &Path, template: Option<&AssScript>, fps: Option<f64>) -> Result<()> {
+    if let Some(template) = template {
+        let content = tokio::fs::read_to_string(input).await?;
+        let cues = vtt::parse_vtt_str(&content).map_err(|e| anyhow!("Failed to parse subtitles: {}", e))?;
+        return write_ass(&ass::from_cues(&cues, Some(template)).to_string(), output, fps).await;
+    }
+    let output_result = TokioCommand::new(crate::utils::tools::ffmpeg()).arg("-y").arg("-i").arg(input).arg(output).output().await?;
+    if !output_result.status.success() {
+        return Err(anyhow!("{}", String::from_utf8_lossy(&output_result.stderr)));
+    }
+    if let Some(fps) = fps {
+        let content = tokio::fs::read_to_string(output).await?;
+        tokio::fs::write(output, snap_ass_to_frames(&content, fps)).await?;
+    }
+    Ok(())
+}
+
+async fn write_ass(content: &str, output: &Path, fps: Option<f64>) -> Result<()> {
+    let content = match fps {
+        Some(fps) => snap_ass_to_frames(content, fps),
+        None => content.to_string(),
+    };
+    tokio::fs::write(output, content).await?;
+    Ok(())
+}
+
+/// Frame rate of the first video stream, e.g. 29.97 for "30000/1001"
+async fn video_frame_rate(video_path: &Path) -> Result<f64> {
+    let output = TokioCommand::new(crate::utils::tools::ffprobe())
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=avg_frame_rate,r_frame_rate", "-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(video_path)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!("ffprobe error: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    // The average rate is the one players see for variable frame rate sources
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(parse_frame_rate)
+        .next()
+        .ok_or_else(|| anyhow!("No frame rate for {}", video_path.display()))
+}
+
+fn parse_frame_rate(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let fps = match value.split_once('/') {
+        Some((num, den)) => num.parse::<f64>().ok()? / den.parse::<f64>().ok()?,
+        None => value.parse().ok()?,
+    };
+    // ffprobe reports 0/0 for unknown rates; above 100 fps a frame is shorter than an ASS tick
+    Some(fps).filter(|fps| fps.is_finite() && *fps > 1.0 && *fps <= 100.0)
+}
+
+/// ASS time of the start of a frame. ASS counts in centiseconds and a frame is
+/// shown when its time is past the event start, so the time is rounded down:
+/// rounding up could land after the frame and drop it.
+fn ass_frame_time(frame: u64, fps: f64) -> String {
+    let centis = (frame as f64 / fps * 100.0 + 1e-6).floor() as u64;
+    format!("{}:{:02}:{:02}.{:02}", centis / 360_000, centis / 6000 % 60, centis / 100 % 60, centis % 100)
+}
+
+/// Move the start and end of every dialogue line to the nearest frame
+/// boundary. Cues that met within a frame then meet exactly, instead of leaving
+/// a frame without subtitles between them, and no cue is shorter than a frame.
+fn snap_ass_to_frames(content: &str, fps: f64) -> String {
+    let mut in_events = false;
+    let mut fields: Option<(usize, usize, usize)> = None; // start, end, field count
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('[') {
+            in_events = trimmed.eq_ignore_ascii_case("[Events]");
+        } else if in_events {
+            if let Some(format) = trimmed.strip_prefix("Format:") {
+                let names: Vec<&str> = format.split(',').map(str::trim).collect();
+                let start = names.iter().position(|name| *name == "Start");
+                let end = names.iter().position(|name| *name == "End");
+                fields = start.zip(end).map(|(start, end)| (start, end, names.len()));
+            } else if let (Some(dialogue), Some((start, end, count))) = (trimmed.strip_prefix("Dialogue:"), fields) {
+                // The text is the last field and may contain commas itself
+                let mut values: Vec<String> = dialogue.splitn(count, ',').map(str::to_string).collect();
+                if let (Some(start_secs), Some(end_secs)) = (
+                    values.get(start).and_then(|value| ass::parse_time(value)),
+                    values.get(end).and_then(|value| ass::parse_time(value)),
+                ) {
+                    let start_frame = (start_secs * fps).round() as u64;
+                    let end_frame = ((end_secs * fps).round() as u64).max(start_frame + 1);
+                    values[start] = ass_frame_time(start_frame, fps);
+                    values[end] = ass_frame_time(end_frame, fps);
+                    lines.push(format!("Dialogue: {}", values.join(",").trim_start()));
+                    continue;
+                }
+            }
+        }
+        lines.push(line.to_string());
+    }
+    let mut result = lines.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+/// A path as an option value of an ffmpeg filter. It is escaped twice, for
+/// the option parser and then for the filter graph, as the ffmpeg docs describe.
+fn filter_path(path: &Path) -> String {
+    let escape = |value: &str, special: &[char]| {
+        value.chars().fold(String::new(), |mut escaped, c| {
+            if special.contains(&c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        })
+    };
+    let path = path.to_string_lossy().replace('\\', "/");
+    escape(&escape(&path, &['\\', '\'', ':']), &['\\', '\'', '[', ']', ',', ';'])
+}
+
+/// Tags of the merged file as `key=value`. MP4 only keeps the ones it knows,
+/// Matroska takes the source and the languages as tags of their own.
+fn global_metadata(
+    metadata: &OutputMetadata,
+    container: OutputContainer,
+    source_language_code: &str,
+    target_language_code: &str,
+    source_language_name: &str,
+    target_language_name: &str,
+) -> Vec<String> {
+    let mut tags = Vec::new();
+    if let Some(title) = metadata.title.as_deref().filter(|title| !title.is_empty()) {
+        tags.push(format!("title={}", title));
+    }
+    let mut comment = format!("Dubbed from {} to {}", source_language_name, target_language_name);
+    if let Some(url) = metadata.source_url.as_deref().filter(|url| !url.is_empty()) {
+        comment.push_str(&format!(", source: {}", url));
+        if container != OutputContainer::Mp4 {
+            tags.push(format!("source_url={}", url));
+        }
+    }
+    tags.push(format!("comment={}", comment));
+    if container != OutputContainer::Mp4 {
+        tags.push(format!("original_language={}", convert_to_iso_639_2(source_language_code)));
+        tags.push(format!("language={}", convert_to_iso_639_2(target_language_code)));
+    }
+    tags
+}
+
+/// Download the image at `url` and convert it to a JPEG at `output`; YouTube
+/// thumbnails are often WebP, which neither MP4 nor most players take as cover
+async fn fetch_cover(url: &str, output: &Path) -> Result<()> {
+    let image = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+    let downloaded = output.with_extension("thumbnail");
+    tokio::fs::write(&downloaded, &image).await?;
+    let result = TokioCommand::new(crate::utils::tools::ffmpeg())
+        .arg("-y")
+        .arg("-i")
+        .arg(&downloaded)
+        .args(["-frames:v", "1", "-q:v", "2"])
+        .arg(output)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output()
+        .await;
+    let _ = tokio::fs::remove_file(&downloaded).await;
+    let output = result?;
+    if !output.status.success() {
+        return Err(anyhow!("ffmpeg failed to convert the thumbnail: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(())
+}
+
+/// Add language, title and handler name of an output stream
+fn add_track_metadata(cmd: &mut TokioCommand, stream: &str, track: &OutputTrack) {
+    let key = format!("-metadata:s:{}", stream);
+    cmd.arg(&key)
+        .arg(format!("language={}", track.language))
+        .arg(&key)
+        .arg(format!("title={}", track.title))
+        .arg(&key)
+        .arg(format!("handler_name={}", track.handler_name));
+}
+
 /// Convert ISO 639-1 two-letter language code to ISO 639-2 three-letter code
 fn convert_to_iso_639_2(code: &str) -> String {
     match code {
@@ -440,3 +1571,106 @@ fn convert_to_iso_639_2(code: &str) -> String {
         _ => code, // Return original code if no mapping found
     }.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ffmpeg_progress_reports_are_collected() {
+        let mut parser = ProgressParser::default();
+        let lines = ["frame=120", "out_time_us=4000000", "out_time_ms=4000000", "speed=2.5x", "progress=continue"];
+        let reports: Vec<_> = lines.iter().filter_map(|line| parser.feed(line)).collect();
+        assert_eq!(reports, vec![FfmpegProgress { out_time: 4.0, speed: Some(2.5), done: false }]);
+
+        assert_eq!(parser.feed("speed=N/A"), None);
+        let last = parser.feed("progress=end").unwrap();
+        assert!(last.done && last.speed.is_none());
+    }
+
+    #[test]
+    fn wav_header_sizes_are_checked() {
+        let header = wav_header(48000, 24000, 2).unwrap();
+        assert_eq!(header.len(), 44);
+        assert_eq!(u32::from_le_bytes(header[4..8].try_into().unwrap()), 36 + 96000);
+        assert_eq!(u32::from_le_bytes(header[28..32].try_into().unwrap()), 96000);
+        assert_eq!(u32::from_le_bytes(header[40..44].try_into().unwrap()), 96000);
+
+        // 2^31 samples are 4 GiB of 16-bit data, the sizes would wrap
+        assert!(wav_header(1 << 31, 48000, 2).is_err());
+        assert!(wav_header((u32::MAX as usize - 36) / 2 + 1, 48000, 2).is_err());
+    }
+
+    #[test]
+    fn parses_ffprobe_frame_rates() {
+        assert!((parse_frame_rate("30000/1001").unwrap() - 29.97).abs() < 0.001);
+        assert_eq!(parse_frame_rate("25/1"), Some(25.0));
+        assert_eq!(parse_frame_rate("0/0"), None);
+        assert_eq!(parse_frame_rate("N/A"), None);
+    }
+
+    #[test]
+    fn dialogue_snaps_to_frame_boundaries() {
+        let ass = "[Script Info]\nScriptType: v4.00+\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello, world\nDialogue: 0,0:00:02.01,0:00:02.02,Default,,0,0,0,,Next\n";
+        let snapped = snap_ass_to_frames(ass, 25.0);
+        // The second cue started a quarter frame late and lasted less than a frame
+        assert!(snapped.contains("Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello, world\n"));
+        assert!(snapped.contains("Dialogue: 0,0:00:02.00,0:00:02.04,Default,,0,0,0,,Next\n"));
+
+        // 29.97 fps: frame 60 starts at 2.002s and is written as 2.00, not 2.01
+        assert_eq!(ass_frame_time(60, 30000.0 / 1001.0), "0:00:02.00");
+        assert_eq!(ass_frame_time(3600 * 25, 25.0), "1:00:00.00");
+    }
+
+    #[test]
+    fn filter_paths_are_escaped() {
+        assert_eq!(filter_path(Path::new("/tmp/video_burn.ass")), "/tmp/video_burn.ass");
+        assert_eq!(filter_path(Path::new("C:\\Videos\\it's [1].ass")), "C\\\\:/Videos/it\\\\\\'s \\[1\\].ass");
+    }
+
+    #[test]
+    fn voice_over_needs_both_audio_tracks() {
+        let layout = TrackLayout { voice_over: true, ..TrackLayout::default() };
+        assert!(layout.validate(&TrackSelection::default()).is_ok());
+        let dub_only = TrackSelection { original_audio: false, ..TrackSelection::default() };
+        assert!(layout.validate(&dub_only).is_err());
+        assert!(TrackLayout::default().validate(&dub_only).is_ok());
+        assert_eq!(
+            layout.voice_over_filter(1, 2),
+            "[2:a]volume=0.20[under];[1:a][under]amix=inputs=2:duration=first:normalize=0[voiceover]"
+        );
+    }
+
+    #[test]
+    fn outputs_describe_their_source_and_languages() {
+        let metadata = OutputMetadata {
+            title: Some("My talk".to_string()),
+            source_url: Some("https://www.youtube.com/watch?v=abc".to_string()),
+            thumbnail_url: None,
+        };
+        let mkv = global_metadata(&metadata, OutputContainer::Mkv, "en", "ru", "English", "Russian");
+        assert_eq!(
+            mkv,
+            vec![
+                "title=My talk",
+                "source_url=https://www.youtube.com/watch?v=abc",
+                "comment=Dubbed from English to Russian, source: https://www.youtube.com/watch?v=abc",
+                "original_language=eng",
+                "language=rus",
+            ]
+        );
+        let mp4 = global_metadata(&OutputMetadata::default(), OutputContainer::Mp4, "en", "ru", "English", "Russian");
+        assert_eq!(mp4, vec!["comment=Dubbed from English to Russian"]);
+    }
+
+    #[test]
+    fn containers_copy_only_the_audio_they_take() {
+        assert!(OutputContainer::Mp4.accepts_audio("aac"));
+        assert!(!OutputContainer::Mp4.accepts_audio("opus"));
+        assert!(OutputContainer::Mkv.accepts_audio("opus"));
+        assert!(!OutputContainer::Mkv.accepts_audio("pcm_s16le"));
+        assert!(!OutputContainer::Webm.accepts_audio("aac"));
+        let style: MergeStyle = serde_json::from_str(r#"{"container": "webm"}"#).unwrap();
+        assert_eq!(style.container.extension(), "webm");
+    }
+}
@@ -6,3 +6,57 @@ pub mod common;
 pub mod translate;
 pub mod tts;
 pub mod merge;
+pub mod subtitles;
+pub mod chapters;
+pub mod charset;
+pub mod events;
+pub mod jobs;
+pub mod karaoke;
+pub mod library;
+pub mod publish;
+pub mod remote;
+pub mod notify;
+pub mod progress;
+pub mod job_state;
+pub mod perf_stats;
+pub mod audio_probe;
+pub mod subtitle_layout;
+pub mod subtitle_optimizer;
+pub mod settings;
+pub mod quota;
+pub mod http_retry;
+pub mod cookies;
+pub mod timing_report;
+pub mod transcript_cleanup;
+pub mod narration;
+pub mod conditioning;
+pub mod voices;
+pub mod emitter;
+pub mod timestamps;
+pub mod channels;
+pub mod diagnose;
+pub mod scrub;
+pub mod confidence;
+pub mod piper;
+pub mod request_journal;
+pub mod split;
+pub mod pipeline_job;
+pub mod queue;
+pub mod validation;
+pub mod cancellation;
+pub mod profiles;
+pub mod ass;
+pub mod optimizer_report;
+pub mod advanced_config;
+pub mod fish_speech;
+pub mod diarization;
+pub mod rate_limit;
+pub mod openai_connection;
+pub mod headless;
+pub mod server;
+pub mod source_language;
+pub mod regenerate;
+pub mod review;
+
+#[cfg(test)]
+mod golden_tests;
@@ -0,0 +1,199 @@
+//! Narration recorded by the user for selected segments of a dub.
+//!
+//! A hybrid dub mixes human and synthesized speech: the user records or
+//! imports their own reading of some cues, and the synchronizer places those
+//! fragments on the timeline instead of asking the TTS for them. Recordings
+//! are converted to the format the TTS delivers (mono MP3 at 24 kHz) on
+//! import, so they go through the same decoding, fitting and loudness steps
+//! as synthesized fragments. They are kept in the library entry of the video,
+//! per target language, and are matched to cues by start time, which stays
+//! stable when the video is dubbed again.
+
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::process::Command as TokioCommand;
+
+use crate::utils::common::now_secs;
+use crate::utils::library;
+use crate::utils::tts::tts::synchronizer::HumanNarration;
+
+const NARRATION_DIR: &str = "narration";
+const MANIFEST_FILE: &str = "narration.json";
+/// Sample rate of OpenAI TTS MP3, fragments of one dub have to share it
+const SAMPLE_RATE: u32 = 24_000;
+/// Cues starting within this distance are the same cue
+const START_TOLERANCE: f32 = 0.05;
+
+/// A recording that replaces the synthesized speech of one cue
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NarrationClip {
+    pub cue_start: f32,
+    pub cue_end: f32,
+    pub text: String,
+    /// File name inside the narration directory
+    pub file: String,
+    pub imported_at: u64,
+}
+
+fn narration_dir(app_handle: &tauri::AppHandle, video_id: &str, target_language: &str) -> Result<PathBuf> {
+    Ok(library::entry_dir(app_handle, video_id)?
+        .join(NARRATION_DIR)
+        .join(target_language))
+}
+
+/// Replace the clip of the same cue, keeping the list ordered by start time.
+/// Returns the file of the replaced clip.
+fn upsert(clips: &mut Vec<NarrationClip>, clip: NarrationClip) -> Option<String> {
+    let replaced = clips
+        .iter()
+        .position(|existing| (existing.cue_start - clip.cue_start).abs() < START_TOLERANCE)
+        .map(|index| clips.remove(index).file);
+    clips.push(clip);
+    clips.sort_by(|a, b| a.cue_start.total_cmp(&b.cue_start));
+    replaced.filter(|file| clips.iter().all(|clip| &clip.file != file))
+}
+
+async fn read_manifest(dir: &Path) -> Vec<NarrationClip> {
+    match tokio::fs::read_to_string(dir.join(MANIFEST_FILE)).await {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+async fn write_manifest(dir: &Path, clips: &[NarrationClip]) -> Result<()> {
+    let json = serde_json::to_string_pretty(clips)
+        .map_err(|e| anyhow!("Failed to serialize narration list: {}", e))?;
+    tokio::fs::write(dir.join(MANIFEST_FILE), json).await?;
+    Ok(())
+}
+
+/// Recordings of a video for a target language, ordered by cue start
+pub async fn list(app_handle: &tauri::AppHandle, video_id: &str, target_language: &str) -> Result<Vec<NarrationClip>> {
+    Ok(read_manifest(&narration_dir(app_handle, video_id, target_language)?).await)
+}
+
+/// Convert a recording to TTS format and store it for a cue
+pub async fn import(
+    app_handle: &tauri::AppHandle,
+    video_id: &str,
+    target_language: &str,
+    cue_start: f32,
+    cue_end: f32,
+    text: &str,
+    source: &Path,
+) -> Result<NarrationClip> {
+    let dir = narration_dir(app_handle, video_id, target_language)?;
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let file = format!("{:08}.mp3", (cue_start.max(0.0) * 1000.0).round() as u64);
+    let target = dir.join(&file);
+    let output = TokioCommand::new(crate::utils::tools::ffmpeg())
+        .arg("-y")
+        .arg("-i")
+        .arg(source)
+        .args(["-vn", "-ac", "1", "-ar", &SAMPLE_RATE.to_string()])
+        .args(["-c:a", "libmp3lame", "-b:a", "128k"])
+        .arg(&target)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "ffmpeg failed to convert the recording: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let clip = NarrationClip {
+        cue_start,
+        cue_end,
+        text: text.to_string(),
+        file,
+        imported_at: now_secs(),
+    };
+    let mut clips = read_manifest(&dir).await;
+    if let Some(replaced) = upsert(&mut clips, clip.clone()) {
+        let _ = tokio::fs::remove_file(dir.join(replaced)).await;
+    }
+    write_manifest(&dir, &clips).await?;
+    info!("Imported narration for cue at {:.2}s of {} ({})", cue_start, video_id, target_language);
+    Ok(clip)
+}
+
+/// Store a recording made in the app (any format ffmpeg reads) for a cue
+pub async fn import_bytes(
+    app_handle: &tauri::AppHandle,
+    video_id: &str,
+    target_language: &str,
+    cue_start: f32,
+    cue_end: f32,
+    text: &str,
+    recording: &[u8],
+) -> Result<NarrationClip> {
+    let dir = narration_dir(app_handle, video_id, target_language)?;
+    tokio::fs::create_dir_all(&dir).await?;
+    let temp = dir.join("recording.tmp");
+    tokio::fs::write(&temp, recording).await?;
+    let result = import(app_handle, video_id, target_language, cue_start, cue_end, text, &temp).await;
+    let _ = tokio::fs::remove_file(&temp).await;
+    result
+}
+
+/// Drop the recording of a cue, the TTS voices it again
+pub async fn remove(app_handle: &tauri::AppHandle, video_id: &str, target_language: &str, cue_start: f32) -> Result<()> {
+    let dir = narration_dir(app_handle, video_id, target_language)?;
+    let mut clips = read_manifest(&dir).await;
+    let Some(index) = clips
+        .iter()
+        .position(|clip| (clip.cue_start - cue_start).abs() < START_TOLERANCE)
+    else {
+        return Err(anyhow!("No narration for the cue at {:.2}s", cue_start));
+    };
+    let clip = clips.remove(index);
+    let _ = tokio::fs::remove_file(dir.join(&clip.file)).await;
+    write_manifest(&dir, &clips).await
+}
+
+/// Recordings in the form the synchronizer takes them
+pub async fn for_synchronizer(app_handle: &tauri::AppHandle, video_id: &str, target_language: &str) -> Vec<HumanNarration> {
+    let Ok(dir) = narration_dir(app_handle, video_id, target_language) else {
+        return Vec::new();
+    };
+    read_manifest(&dir)
+        .await
+        .into_iter()
+        .map(|clip| HumanNarration { cue_start: clip.cue_start, path: dir.join(clip.file) })
+        .filter(|narration| narration.path.exists())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(cue_start: f32, file: &str) -> NarrationClip {
+        NarrationClip {
+            cue_start,
+            cue_end: cue_start + 2.0,
+            text: String::new(),
+            file: file.to_string(),
+            imported_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_upsert_replaces_same_cue() {
+        let mut clips = vec![clip(5.0, "00005000.mp3")];
+        assert_eq!(upsert(&mut clips, clip(1.0, "00001000.mp3")), None);
+        assert_eq!(clips[0].cue_start, 1.0);
+
+        assert_eq!(upsert(&mut clips, clip(5.02, "00005020.mp3")), Some("00005000.mp3".to_string()));
+        assert_eq!(clips.len(), 2);
+        assert_eq!(clips[1].file, "00005020.mp3");
+
+        // Re-recording to the same file name must not delete the new recording
+        assert_eq!(upsert(&mut clips, clip(1.0, "00001000.mp3")), None);
+        assert_eq!(clips.len(), 2);
+    }
+}
@@ -0,0 +1,344 @@
+//! Completion notifications for long unattended runs.
+//!
+//! Each configured channel receives the same job summary when processing
+//! finishes, fails or pauses because the OpenAI quota ran out. Webhooks get it as a JSON POST body, SMTP channels as a
+//! plain-text email. Channels are stored in the settings store and delivered
+//! independently, so one broken channel doesn't block the others.
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+const SETTINGS_KEY: &str = "notifications";
+/// Limit for a whole SMTP exchange, from connecting to QUIT
+const SMTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Final state of a processing job, or the pause that needs the user's attention
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Completed,
+    Failed,
+    /// Waiting for the OpenAI quota to be resolved, the job resumes afterwards
+    Paused,
+    /// Stopped by the user
+    Cancelled,
+}
+
+/// Payload delivered to every notification channel
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobSummary {
+    pub status: JobStatus,
+    pub url: String,
+    pub target_language: String,
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+    pub duration_secs: u64,
+}
+
+impl JobSummary {
+    fn subject(&self) -> String {
+        match self.status {
+            JobStatus::Completed => format!("Videonova: translation to {} completed", self.target_language),
+            JobStatus::Failed => format!("Videonova: translation to {} failed", self.target_language),
+            JobStatus::Paused => format!("Videonova: translation to {} paused, OpenAI quota exhausted", self.target_language),
+            JobStatus::Cancelled => format!("Videonova: translation to {} cancelled", self.target_language),
+        }
+    }
+
+    fn body(&self) -> String {
+        let mut lines = vec![
+            format!("Video: {}", self.url),
+            format!("Target language: {}", self.target_language),
+            format!("Duration: {} s", self.duration_secs),
+        ];
+        if let Some(path) = &self.output_path {
+            lines.push(format!("Output: {}", path));
+        }
+        if let Some(error) = &self.error {
+            lines.push(format!("Error: {}", error));
+        }
+        lines.join("\r\n")
+    }
+}
+
+/// How SMTP connections are secured
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// TLS from the first byte, usually port 465
+    Tls,
+    /// Plain connection upgraded with STARTTLS, usually port 587
+    StartTls,
+}
+
+/// A single notification destination
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum NotificationChannel {
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    Smtp {
+        host: String,
+        port: u16,
+        security: SmtpSecurity,
+        username: String,
+        password: String,
+        from: String,
+        to: Vec<String>,
+    },
+}
+
+impl NotificationChannel {
+    fn name(&self) -> String {
+        match self {
+            NotificationChannel::Webhook { url, .. } => format!("webhook {}", url),
+            NotificationChannel::Smtp { host, .. } => format!("smtp {}", host),
+        }
+    }
+
+    /// Deliver the summary through this channel
+    pub async fn send(&self, summary: &JobSummary) -> Result<()> {
+        match self {
+            NotificationChannel::Webhook { url, headers } => send_webhook(url, headers, summary).await,
+            NotificationChannel::Smtp { host, port, security, username, password, from, to } => {
+                let message = SmtpMessage { from, to, subject: summary.subject(), body: summary.body() };
+                send_email(host, *port, *security, username, password, &message).await
+            }
+        }
+    }
+}
+
+/// Notification settings of the current user
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotificationSettings {
+    #[serde(default)]
+    pub channels: Vec<NotificationChannel>,
+    #[serde(default = "default_true")]
+    pub on_success: bool,
+    #[serde(default = "default_true")]
+    pub on_failure: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self { channels: Vec::new(), on_success: true, on_failure: true }
+    }
+}
+
+/// Load notification settings from the settings store
+pub fn load_settings(app_handle: &tauri::AppHandle) -> Result<NotificationSettings> {
+    match crate::utils::settings::get_value(app_handle, SETTINGS_KEY) {
+        Some(value) if !value.is_null() => {
+            serde_json::from_value(value).map_err(|e| anyhow!("Invalid notification settings: {}", e))
+        }
+        _ => Ok(NotificationSettings::default()),
+    }
+}
+
+/// Save notification settings to the settings store
+pub async fn save_settings(app_handle: &tauri::AppHandle, settings: &NotificationSettings) -> Result<()> {
+    crate::utils::settings::set(app_handle, SETTINGS_KEY, settings).await
+}
+
+/// Send the summary to every configured channel in the background, logging
+/// failures. A slow mail server or webhook never holds up the job.
+pub fn notify_job_finished(app_handle: &tauri::AppHandle, summary: JobSummary) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        send_to_channels(&app_handle, &summary).await;
+    });
+}
+
+async fn send_to_channels(app_handle: &tauri::AppHandle, summary: &JobSummary) {
+    let settings = match load_settings(app_handle) {
+        Ok(settings) => settings,
+        Err(e) => {
+            warn!("Failed to load notification settings: {}", e);
+            return;
+        }
+    };
+
+    let enabled = match summary.status {
+        JobStatus::Completed => settings.on_success,
+        // A pause blocks the job just like a failure until someone acts on it
+        JobStatus::Failed | JobStatus::Paused => settings.on_failure,
+        // The user stopped it and already knows
+        JobStatus::Cancelled => false,
+    };
+    if !enabled || settings.channels.is_empty() {
+        return;
+    }
+
+    for channel in &settings.channels {
+        match channel.send(summary).await {
+            Ok(()) => info!("Sent job notification via {}", channel.name()),
+            Err(e) => warn!("Failed to send job notification via {}: {}", channel.name(), e),
+        }
+    }
+}
+
+async fn send_webhook(url: &str, headers: &HashMap<String, String>, summary: &JobSummary) -> Result<()> {
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?;
+    let mut request = client.post(url).json(summary);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Webhook returned HTTP {}: {}", status, error_text));
+    }
+    Ok(())
+}
+
+struct SmtpMessage<'a> {
+    from: &'a str,
+    to: &'a [String],
+    subject: String,
+    body: String,
+}
+
+trait SmtpStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> SmtpStream for T {}
+
+/// Line breaks in a header value or SMTP command would let it inject
+/// further headers or commands
+fn check_header_value(name: &str, value: &str) -> Result<()> {
+    if value.contains(['\r', '\n']) {
+        return Err(anyhow!("Email {} must not contain line breaks", name));
+    }
+    Ok(())
+}
+
+/// Minimal SMTP client: EHLO, optional STARTTLS, AUTH PLAIN and a single message
+async fn send_email(
+    host: &str,
+    port: u16,
+    security: SmtpSecurity,
+    username: &str,
+    password: &str,
+    message: &SmtpMessage<'_>,
+) -> Result<()> {
+    if message.to.is_empty() {
+        return Err(anyhow!("No email recipients configured"));
+    }
+    check_header_value("sender", message.from)?;
+    for recipient in message.to {
+        check_header_value("recipient", recipient)?;
+    }
+    check_header_value("subject", &message.subject)?;
+
+    tokio::time::timeout(SMTP_TIMEOUT, smtp_exchange(host, port, security, username, password, message))
+        .await
+        .map_err(|_| anyhow!("Timed out sending email via {}:{}", host, port))?
+}
+
+async fn smtp_exchange(
+    host: &str,
+    port: u16,
+    security: SmtpSecurity,
+    username: &str,
+    password: &str,
+    message: &SmtpMessage<'_>,
+) -> Result<()> {
+    let tcp = TcpStream::connect((host, port)).await?;
+    let connector = tokio_native_tls::TlsConnector::from(native_tls::TlsConnector::new()?);
+
+    let mut stream: BufReader<Box<dyn SmtpStream>> = match security {
+        SmtpSecurity::Tls => BufReader::new(Box::new(connector.connect(host, tcp).await?)),
+        SmtpSecurity::StartTls => {
+            let mut plain = BufReader::new(tcp);
+            expect_reply(&mut plain, 220).await?;
+            command(&mut plain, "EHLO videonova", 250).await?;
+            command(&mut plain, "STARTTLS", 220).await?;
+            let tls = connector.connect(host, plain.into_inner()).await?;
+            BufReader::new(Box::new(tls))
+        }
+    };
+
+    if security == SmtpSecurity::Tls {
+        expect_reply(&mut stream, 220).await?;
+    }
+    command(&mut stream, "EHLO videonova", 250).await?;
+
+    let credentials = base64::engine::general_purpose::STANDARD.encode(format!("\0{}\0{}", username, password));
+    command(&mut stream, &format!("AUTH PLAIN {}", credentials), 235).await?;
+    command(&mut stream, &format!("MAIL FROM:<{}>", message.from), 250).await?;
+    for recipient in message.to {
+        command(&mut stream, &format!("RCPT TO:<{}>", recipient), 250).await?;
+    }
+    command(&mut stream, "DATA", 354).await?;
+
+    // Lines starting with a dot must be escaped (RFC 5321, 4.5.2)
+    let body = message
+        .body
+        .lines()
+        .map(|line| if line.starts_with('.') { format!(".{}", line) } else { line.to_string() })
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    let data = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n.",
+        message.from,
+        message.to.join(", "),
+        message.subject,
+        body
+    );
+    command(&mut stream, &data, 250).await?;
+    let _ = command(&mut stream, "QUIT", 221).await;
+    Ok(())
+}
+
+async fn command<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut BufReader<S>, line: &str, expected: u16) -> Result<()> {
+    stream.get_mut().write_all(format!("{}\r\n", line).as_bytes()).await?;
+    stream.get_mut().flush().await?;
+    expect_reply(stream, expected).await
+}
+
+/// Read a (possibly multi-line) SMTP reply and check its code
+async fn expect_reply<S: AsyncRead + Unpin>(stream: &mut BufReader<S>, expected: u16) -> Result<()> {
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Err(anyhow!("SMTP server closed the connection"));
+        }
+        debug!("SMTP: {}", line.trim_end());
+
+        let code: u16 = line.get(..3).and_then(|c| c.parse().ok())
+            .ok_or_else(|| anyhow!("Malformed SMTP reply: {}", line.trim_end()))?;
+        // "250-..." continues the reply, "250 ..." ends it
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        if code != expected {
+            return Err(anyhow!("SMTP server replied {}, expected {}: {}", code, expected, line.trim_end()));
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_values_with_line_breaks_are_rejected() {
+        assert!(check_header_value("subject", "Videonova: translation to German completed").is_ok());
+        assert!(check_header_value("recipient", "me@example.com\r\nBcc: other@example.com").is_err());
+        assert!(check_header_value("sender", "me@example.com\nRCPT TO:<other@example.com>").is_err());
+    }
+}
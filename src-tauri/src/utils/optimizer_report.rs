@@ -0,0 +1,93 @@
+//! Why the dubbed phrasing differs from the original captions.
+//!
+//! Before and while synthesizing, the synchronizer merges overlapping cues
+//! (overlap policy Merge) and moves or widens cue windows (fit strategy
+//! Retime). Each such decision is reported with the ids of the source cues,
+//! the reason and the timings before and after. The report of a pipeline job
+//! is kept in its `pipeline.json`, next to the rest of the run.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use crate::utils::pipeline_job;
+use crate::utils::tts::tts::progress::ProgressReporter;
+use crate::utils::tts::tts::timeline::{CueAction, CueDecision};
+use crate::utils::tts::tts::ProgressUpdate;
+
+/// Payload of `get_optimizer_report`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OptimizerReport {
+    pub decisions: Vec<CueDecision>,
+    pub merged: usize,
+    pub retimed: usize,
+}
+
+impl OptimizerReport {
+    pub fn new(mut decisions: Vec<CueDecision>) -> Self {
+        decisions.sort_by(|a, b| a.after.start.total_cmp(&b.after.start));
+        let count = |action| decisions.iter().filter(|d| d.action == action).count();
+        let (merged, retimed) = (count(CueAction::Merge), count(CueAction::Retime));
+        Self { decisions, merged, retimed }
+    }
+}
+
+/// Collects the `CueDecision` updates of a synchronizer run
+#[derive(Default)]
+pub struct DecisionCollector {
+    decisions: Mutex<Vec<CueDecision>>,
+}
+
+impl DecisionCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn build_report(&self) -> OptimizerReport {
+        OptimizerReport::new(self.decisions.lock().map(|decisions| decisions.clone()).unwrap_or_default())
+    }
+}
+
+impl ProgressReporter for DecisionCollector {
+    fn report(&self, update: &ProgressUpdate) {
+        if let ProgressUpdate::CueDecision { decision } = update
+            && let Ok(mut decisions) = self.decisions.lock()
+        {
+            decisions.push(decision.clone());
+        }
+    }
+}
+
+/// Load the report of a job from its pipeline state
+pub async fn load(app_handle: &tauri::AppHandle, job_id: &str) -> Result<OptimizerReport> {
+    pipeline_job::load(app_handle, job_id)
+        .await?
+        .optimizer_report
+        .ok_or_else(|| anyhow!("No optimizer report for job {}", job_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tts::tts::timeline::CueWindow;
+
+    fn decision(action: CueAction, start: f32) -> ProgressUpdate {
+        let window = CueWindow { start, end: start + 2.0 };
+        ProgressUpdate::CueDecision {
+            decision: CueDecision { action, source_cues: vec![0], reason: String::new(), before: vec![window], after: window },
+        }
+    }
+
+    #[test]
+    fn collector_counts_decisions_in_timeline_order() {
+        let collector = DecisionCollector::new();
+        collector.report(&decision(CueAction::Retime, 8.0));
+        collector.report(&decision(CueAction::Merge, 1.0));
+        collector.report(&ProgressUpdate::Finished);
+
+        let report = collector.build_report();
+        assert_eq!(report.decisions.len(), 2);
+        assert_eq!(report.decisions[0].action, CueAction::Merge);
+        assert_eq!((report.merged, report.retimed), (1, 1));
+    }
+}
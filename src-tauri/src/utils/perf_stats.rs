@@ -0,0 +1,385 @@
+//! Local performance statistics.
+//!
+//! When the user opts in, every finished job leaves a small record: how long
+//! each pipeline state took, how long the media was, which performance-related
+//! settings were active and, for failed jobs, a coarse failure category. No
+//! URLs, titles, paths or error messages are stored and nothing leaves the
+//! machine. The records are aggregated on demand into per-step trends and a
+//! comparison of setting combinations, so users can tell whether a change to
+//! their setup actually made processing faster.
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tauri::Manager;
+
+use crate::utils::job_state::{JobState, JobStateInfo};
+use crate::utils::settings;
+
+const SETTINGS_KEY: &str = "performance-stats";
+const STATS_FILE: &str = "performance_stats.json";
+const MAX_RECORDS: usize = 500;
+/// Runs on each side of the trend comparison
+const TREND_WINDOW: usize = 5;
+
+/// Settings that influence processing speed, recorded with every job. A
+/// `key.field` entry records one field of a setting stored as an object.
+const TRACKED_SETTINGS: &[&str] = &["streaming-merge", "overlap-policy", "demucs.device", "demucs.model"];
+
+/// Pipeline states that are timed
+const TIMED_STATES: &[JobState] = &[
+    JobState::Downloading,
+    JobState::Transcribing,
+    JobState::Translating,
+    JobState::GeneratingSpeech,
+    JobState::Merging,
+    JobState::Uploading,
+];
+
+// Serializes read-modify-write cycles of the stats file between concurrent jobs
+static STATS_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+
+/// Coarse reason a job failed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCategory {
+    Network,
+    RateLimit,
+    Authentication,
+    MissingTool,
+    Disk,
+    Media,
+    Other,
+}
+
+/// Sort an error message into a failure category
+pub fn classify_failure(error: &str) -> FailureCategory {
+    let error = error.to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|n| error.contains(n));
+    if has(&["429", "rate limit", "too many requests", "quota"]) {
+        FailureCategory::RateLimit
+    } else if has(&["401", "403", "unauthorized", "api key", "forbidden"]) {
+        FailureCategory::Authentication
+    } else if has(&["not installed", "not found in path", "no such file or directory (os error 2)"]) {
+        FailureCategory::MissingTool
+    } else if has(&["no space", "disk full", "permission denied", "read-only file system"]) {
+        FailureCategory::Disk
+    } else if has(&["timed out", "timeout", "connection", "dns", "network", "http"]) {
+        FailureCategory::Network
+    } else if has(&["ffmpeg", "ffprobe", "codec", "invalid data", "duration"]) {
+        FailureCategory::Media
+    } else {
+        FailureCategory::Other
+    }
+}
+
+/// Time spent in one pipeline state
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StepTiming {
+    pub step: JobState,
+    pub secs: f64,
+}
+
+/// Anonymized record of a finished job
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobRecord {
+    pub finished_at: u64, // Unix timestamp in seconds
+    pub outcome: JobState,
+    /// State the job was in when it failed
+    pub failed_step: Option<JobState>,
+    pub failure: Option<FailureCategory>,
+    /// Duration of the processed media, when known
+    pub media_secs: Option<f64>,
+    pub steps: Vec<StepTiming>,
+    pub settings: BTreeMap<String, serde_json::Value>,
+}
+
+impl JobRecord {
+    fn total_secs(&self) -> f64 {
+        self.steps.iter().map(|s| s.secs).sum()
+    }
+}
+
+/// Aggregated timings of one pipeline state
+#[derive(Debug, Serialize, Clone)]
+pub struct StepStats {
+    pub step: JobState,
+    pub runs: usize,
+    pub mean_secs: f64,
+    pub median_secs: f64,
+    pub p90_secs: f64,
+    /// Mean processing time per minute of media, over runs with a known media duration
+    pub secs_per_media_minute: Option<f64>,
+    /// Change of the mean over the last runs compared with the runs before, in
+    /// percent; negative means faster
+    pub trend_percent: Option<f64>,
+}
+
+/// How often jobs failed for a given reason
+#[derive(Debug, Serialize, Clone)]
+pub struct FailureStats {
+    pub category: FailureCategory,
+    pub count: usize,
+    /// State in which this kind of failure happened most often
+    pub most_common_step: Option<JobState>,
+}
+
+/// Completed jobs that ran with the same tracked settings
+#[derive(Debug, Serialize, Clone)]
+pub struct ConfigurationStats {
+    pub settings: BTreeMap<String, serde_json::Value>,
+    pub jobs: usize,
+    pub mean_total_secs: f64,
+    pub secs_per_media_minute: Option<f64>,
+}
+
+/// Summary returned by `get_performance_stats`
+#[derive(Debug, Serialize, Clone)]
+pub struct PerformanceStats {
+    pub enabled: bool,
+    pub jobs: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    pub steps: Vec<StepStats>,
+    pub failures: Vec<FailureStats>,
+    pub configurations: Vec<ConfigurationStats>,
+}
+
+/// Whether statistics are collected (off by default)
+pub fn enabled(app_handle: &tauri::AppHandle) -> bool {
+    settings::get(app_handle, SETTINGS_KEY).unwrap_or(false)
+}
+
+/// Enable or disable statistics collection
+pub async fn set_enabled(app_handle: &tauri::AppHandle, enabled: bool) -> Result<()> {
+    settings::set(app_handle, SETTINGS_KEY, &enabled).await
+}
+
+fn stats_path(app_handle: &tauri::AppHandle) -> Result<PathBuf> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow!("Failed to resolve app data directory: {}", e))?;
+    Ok(data_dir.join(STATS_FILE))
+}
+
+async fn load_records(app_handle: &tauri::AppHandle) -> Result<Vec<JobRecord>> {
+    let path = stats_path(app_handle)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = tokio::fs::read_to_string(&path).await?;
+    match serde_json::from_str(&content) {
+        Ok(records) => Ok(records),
+        Err(e) => {
+            warn!("Ignoring corrupted performance statistics {}: {}", path.display(), e);
+            Ok(Vec::new())
+        }
+    }
+}
+
+fn settings_snapshot(app_handle: &tauri::AppHandle) -> BTreeMap<String, serde_json::Value> {
+    TRACKED_SETTINGS
+        .iter()
+        .filter_map(|tracked| {
+            let value = match tracked.split_once('.') {
+                Some((key, field)) => settings::get_value(app_handle, key)?.get(field)?.clone(),
+                None => settings::get_value(app_handle, tracked)?,
+            };
+            Some((tracked.to_string(), value))
+        })
+        .collect()
+}
+
+/// Build the anonymized record of a job that reached a terminal state
+fn build_record(info: &JobStateInfo, media_secs: Option<f64>, settings: BTreeMap<String, serde_json::Value>) -> JobRecord {
+    let steps = info
+        .history
+        .windows(2)
+        .filter(|pair| TIMED_STATES.contains(&pair[0].state))
+        .map(|pair| StepTiming {
+            step: pair[0].state,
+            secs: pair[1].entered_at.saturating_sub(pair[0].entered_at) as f64 / 1000.0,
+        })
+        .collect();
+
+    let failed = info.state == JobState::Failed;
+    JobRecord {
+        finished_at: info.updated_at / 1000,
+        outcome: info.state,
+        failed_step: if failed { info.previous } else { None },
+        failure: if failed { Some(classify_failure(info.error.as_deref().unwrap_or_default())) } else { None },
+        media_secs: media_secs.filter(|secs| *secs > 0.0),
+        steps,
+        settings,
+    }
+}
+
+/// Record a finished job, if statistics are enabled
+pub async fn record_job(app_handle: &tauri::AppHandle, info: &JobStateInfo, media_secs: Option<f64>) -> Result<()> {
+    if !enabled(app_handle) || !info.state.is_terminal() {
+        return Ok(());
+    }
+    let record = build_record(info, media_secs, settings_snapshot(app_handle));
+
+    let _guard = STATS_LOCK.lock().await;
+    let mut records = load_records(app_handle).await?;
+    records.push(record);
+    if records.len() > MAX_RECORDS {
+        let excess = records.len() - MAX_RECORDS;
+        records.drain(..excess);
+    }
+
+    let path = stats_path(app_handle)?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(&path, serde_json::to_string(&records)?).await?;
+    debug!("Recorded performance statistics for job {}", info.job_id);
+    Ok(())
+}
+
+/// Delete all collected statistics
+pub async fn clear(app_handle: &tauri::AppHandle) -> Result<()> {
+    let _guard = STATS_LOCK.lock().await;
+    let path = stats_path(app_handle)?;
+    if path.exists() {
+        tokio::fs::remove_file(&path).await?;
+    }
+    Ok(())
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Value at a quantile of sorted values (nearest rank)
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((sorted.len() as f64 * q).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+/// Mean processing time per media minute over records with a known media duration
+fn per_media_minute<'a>(pairs: impl Iterator<Item = (f64, Option<f64>)> + 'a) -> Option<f64> {
+    let (secs, media): (f64, f64) = pairs
+        .filter_map(|(secs, media)| Some((secs, media?)))
+        .fold((0.0, 0.0), |(s, m), (secs, media)| (s + secs, m + media));
+    if media <= 0.0 {
+        return None;
+    }
+    Some(secs / (media / 60.0))
+}
+
+fn step_stats(records: &[JobRecord], step: JobState) -> Option<StepStats> {
+    // Records are in chronological order, so are the runs
+    let runs: Vec<(f64, Option<f64>)> = records
+        .iter()
+        .filter(|r| r.outcome == JobState::Completed)
+        .filter_map(|r| r.steps.iter().find(|s| s.step == step).map(|s| (s.secs, r.media_secs)))
+        .collect();
+    if runs.is_empty() {
+        return None;
+    }
+
+    let secs: Vec<f64> = runs.iter().map(|(secs, _)| *secs).collect();
+    let mut sorted = secs.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    let trend_percent = if secs.len() >= TREND_WINDOW * 2 {
+        let recent = mean(&secs[secs.len() - TREND_WINDOW..]);
+        let before = mean(&secs[secs.len() - TREND_WINDOW * 2..secs.len() - TREND_WINDOW]);
+        (before > 0.0).then(|| (recent - before) / before * 100.0)
+    } else {
+        None
+    };
+
+    Some(StepStats {
+        step,
+        runs: runs.len(),
+        mean_secs: mean(&secs),
+        median_secs: quantile(&sorted, 0.5),
+        p90_secs: quantile(&sorted, 0.9),
+        secs_per_media_minute: per_media_minute(runs.iter().copied()),
+        trend_percent,
+    })
+}
+
+fn failure_stats(records: &[JobRecord]) -> Vec<FailureStats> {
+    let mut by_category: BTreeMap<FailureCategory, Vec<Option<JobState>>> = BTreeMap::new();
+    for record in records {
+        if let Some(category) = record.failure {
+            by_category.entry(category).or_default().push(record.failed_step);
+        }
+    }
+
+    let mut failures: Vec<FailureStats> = by_category
+        .into_iter()
+        .map(|(category, steps)| {
+            let most_common_step = TIMED_STATES
+                .iter()
+                .map(|state| (*state, steps.iter().filter(|s| **s == Some(*state)).count()))
+                .filter(|(_, count)| *count > 0)
+                .max_by_key(|(_, count)| *count)
+                .map(|(state, _)| state);
+            FailureStats { category, count: steps.len(), most_common_step }
+        })
+        .collect();
+    failures.sort_by_key(|failure| std::cmp::Reverse(failure.count));
+    failures
+}
+
+fn configuration_stats(records: &[JobRecord]) -> Vec<ConfigurationStats> {
+    let mut groups: Vec<(BTreeMap<String, serde_json::Value>, Vec<&JobRecord>)> = Vec::new();
+    for record in records.iter().filter(|r| r.outcome == JobState::Completed) {
+        match groups.iter_mut().find(|(settings, _)| *settings == record.settings) {
+            Some((_, group)) => group.push(record),
+            None => groups.push((record.settings.clone(), vec![record])),
+        }
+    }
+
+    let mut configurations: Vec<ConfigurationStats> = groups
+        .into_iter()
+        .map(|(settings, group)| {
+            let totals: Vec<f64> = group.iter().map(|r| r.total_secs()).collect();
+            ConfigurationStats {
+                settings,
+                jobs: group.len(),
+                mean_total_secs: mean(&totals),
+                secs_per_media_minute: per_media_minute(group.iter().map(|r| (r.total_secs(), r.media_secs))),
+            }
+        })
+        .collect();
+    configurations.sort_by_key(|configuration| std::cmp::Reverse(configuration.jobs));
+    configurations
+}
+
+/// Aggregate records into the summary shown to the user
+fn summarize(records: &[JobRecord], enabled: bool) -> PerformanceStats {
+    let count = |state: JobState| records.iter().filter(|r| r.outcome == state).count();
+    PerformanceStats {
+        enabled,
+        jobs: records.len(),
+        completed: count(JobState::Completed),
+        failed: count(JobState::Failed),
+        cancelled: count(JobState::Cancelled),
+        steps: TIMED_STATES.iter().filter_map(|step| step_stats(records, *step)).collect(),
+        failures: failure_stats(records),
+        configurations: configuration_stats(records),
+    }
+}
+
+/// Aggregated statistics over all recorded jobs
+pub async fn performance_stats(app_handle: &tauri::AppHandle) -> Result<PerformanceStats> {
+    let records = load_records(app_handle).await?;
+    Ok(summarize(&records, enabled(app_handle)))
+}
@@ -0,0 +1,241 @@
+//! Checkpoints of the processing pipeline.
+//!
+//! Every `process_video` run keeps a `pipeline.json` in its job directory with
+//! the parameters of the run, the steps that finished and the files they
+//! produced. When a run fails or the app is closed mid-job, the intermediate
+//! files stay in `videonova_temp` and `resume_video_processing` starts the job
+//! again from the first step that didn't finish, instead of downloading,
+//! transcribing and translating everything anew.
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::utils::channels::TemplateLanguage;
+use crate::utils::common::now_secs;
+use crate::utils::jobs;
+use crate::utils::merge::MergeStyle;
+use crate::utils::optimizer_report::OptimizerReport;
+use crate::utils::progress::PipelineStep;
+use crate::utils::subtitles::SubtitleSource;
+use crate::utils::tts::tts::timeline::FitStrategy;
+
+const PIPELINE_FILE: &str = "pipeline.json";
+
+/// Where a pipeline run stands
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineStatus {
+    Running,
+    Failed,
+    Completed,
+}
+
+/// Files produced by the finished steps
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PipelineArtifacts {
+    pub video_path: Option<PathBuf>,
+    pub audio_path: Option<PathBuf>,
+    pub transcription_path: Option<PathBuf>,
+    pub translation_path: Option<PathBuf>,
+    pub tts_path: Option<PathBuf>,
+    pub merged_path: Option<PathBuf>,
+}
+
+/// Persistent state of one `process_video` run
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PipelineJob {
+    pub job_id: String,
+    pub url: String,
+    pub output_path: String,
+    pub target_language: String,
+    pub target_language_name: String,
+    pub source_language_code: String,
+    pub source_language_name: String,
+    pub subtitle_source: Option<SubtitleSource>,
+    pub merge_style: MergeStyle,
+    pub fit_strategy: FitStrategy,
+    pub voice: Option<String>,
+    /// Adjacent chapters the run is limited to
+    #[serde(default)]
+    pub chapters: Option<Vec<usize>>,
+    /// Start and end of the clip the run is limited to, seconds
+    #[serde(default)]
+    pub start_time: Option<f64>,
+    #[serde(default)]
+    pub end_time: Option<f64>,
+    /// Languages dubbed in the same run besides the target language
+    #[serde(default)]
+    pub extra_languages: Vec<TemplateLanguage>,
+    pub status: PipelineStatus,
+    /// Finished steps in the order they finished
+    pub completed: Vec<PipelineStep>,
+    pub artifacts: PipelineArtifacts,
+    /// Merge and retime decisions of the speech step
+    #[serde(default)]
+    pub optimizer_report: Option<OptimizerReport>,
+    pub error: Option<String>,
+    pub updated_at: u64, // Unix timestamp in seconds
+}
+
+fn exists(path: &Option<PathBuf>) -> bool {
+    path.as_deref().is_some_and(Path::exists)
+}
+
+impl PipelineJob {
+    /// Whether a step finished and its files are still there to build on
+    pub fn is_done(&self, step: PipelineStep) -> bool {
+        if !self.completed.contains(&step) {
+            return false;
+        }
+        let artifacts = &self.artifacts;
+        match step {
+            PipelineStep::Download => exists(&artifacts.video_path) && exists(&artifacts.audio_path),
+            PipelineStep::Transcribe => exists(&artifacts.transcription_path),
+            PipelineStep::Translate => exists(&artifacts.translation_path),
+            PipelineStep::Tts => exists(&artifacts.tts_path),
+            PipelineStep::Merge => exists(&artifacts.merged_path),
+            PipelineStep::Upload => true,
+        }
+    }
+
+    /// Steps that can be skipped on resume. A step only counts if every step
+    /// before it can be skipped too, since it was built from their output.
+    pub fn resumable_steps(&self) -> Vec<PipelineStep> {
+        let order = [
+            PipelineStep::Download,
+            PipelineStep::Transcribe,
+            PipelineStep::Translate,
+            PipelineStep::Tts,
+            PipelineStep::Merge,
+        ];
+        let mut steps = Vec::new();
+        for step in order {
+            if self.is_done(step) {
+                steps.push(step);
+            } else if step != PipelineStep::Translate || self.completed.contains(&step) {
+                // Translation is skipped altogether with speech-to-speech
+                break;
+            }
+        }
+        steps
+    }
+
+    /// Record a finished step
+    pub fn complete(&mut self, step: PipelineStep) {
+        if !self.completed.contains(&step) {
+            self.completed.push(step);
+        }
+    }
+}
+
+fn pipeline_path(app_handle: &tauri::AppHandle, job_id: &str) -> Result<PathBuf> {
+    Ok(jobs::job_dir(app_handle, job_id)?.join(PIPELINE_FILE))
+}
+
+/// Write the checkpoint of a run
+pub async fn save(app_handle: &tauri::AppHandle, job: &mut PipelineJob) -> Result<()> {
+    job.updated_at = now_secs();
+    let path = pipeline_path(app_handle, &job.job_id)?;
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let json = serde_json::to_string_pretty(job).map_err(|e| anyhow!("Failed to serialize pipeline state: {}", e))?;
+    tokio::fs::write(&path, json).await?;
+    debug!("Saved pipeline state of job {}: {:?}", job.job_id, job.completed);
+    Ok(())
+}
+
+/// Write the checkpoint, a failure only costs the ability to resume
+pub async fn checkpoint(app_handle: &tauri::AppHandle, job: &mut PipelineJob) {
+    if let Err(e) = save(app_handle, job).await {
+        warn!("Failed to save pipeline state of job {}: {}", job.job_id, e);
+    }
+}
+
+/// Load the checkpoint of a run
+pub async fn load(app_handle: &tauri::AppHandle, job_id: &str) -> Result<PipelineJob> {
+    let path = pipeline_path(app_handle, job_id)?;
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|_| anyhow!("No saved pipeline state for job {}", job_id))?;
+    serde_json::from_str(&content).map_err(|e| anyhow!("Corrupted pipeline state of job {}: {}", job_id, e))
+}
+
+/// Runs that didn't complete, newest first
+pub async fn unfinished(app_handle: &tauri::AppHandle) -> Vec<PipelineJob> {
+    let Ok(root) = jobs::jobs_root(app_handle) else { return Vec::new() };
+    let Ok(mut entries) = tokio::fs::read_dir(&root).await else { return Vec::new() };
+    let mut found = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let Ok(content) = tokio::fs::read_to_string(entry.path().join(PIPELINE_FILE)).await else { continue };
+        match serde_json::from_str::<PipelineJob>(&content) {
+            Ok(job) if job.status != PipelineStatus::Completed => found.push(job),
+            Ok(_) => {}
+            Err(e) => warn!("Skipping corrupted pipeline state in {}: {}", entry.path().display(), e),
+        }
+    }
+    found.sort_by_key(|job| std::cmp::Reverse(job.updated_at));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(completed: Vec<PipelineStep>, artifacts: PipelineArtifacts) -> PipelineJob {
+        PipelineJob {
+            job_id: "job".to_string(),
+            url: String::new(),
+            output_path: String::new(),
+            target_language: "ru".to_string(),
+            target_language_name: "Russian".to_string(),
+            source_language_code: "en".to_string(),
+            source_language_name: "English".to_string(),
+            subtitle_source: None,
+            merge_style: MergeStyle::default(),
+            fit_strategy: FitStrategy::default(),
+            voice: None,
+            chapters: None,
+            start_time: None,
+            end_time: None,
+            extra_languages: Vec::new(),
+            status: PipelineStatus::Failed,
+            completed,
+            artifacts,
+            optimizer_report: None,
+            error: None,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn resumes_after_the_last_step_with_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = |name: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, b"data").unwrap();
+            Some(path)
+        };
+        let artifacts = PipelineArtifacts {
+            video_path: file("video.mp4"),
+            audio_path: file("audio.m4a"),
+            transcription_path: file("video.vtt"),
+            translation_path: Some(dir.path().join("missing.vtt")),
+            ..Default::default()
+        };
+        let steps = vec![PipelineStep::Download, PipelineStep::Transcribe, PipelineStep::Translate];
+        let state = job(steps.clone(), artifacts.clone());
+        assert_eq!(state.resumable_steps(), vec![PipelineStep::Download, PipelineStep::Transcribe]);
+
+        // Speech-to-speech runs never translate, TTS still counts
+        let artifacts = PipelineArtifacts { tts_path: file("tts.wav"), ..artifacts };
+        let state = job(vec![PipelineStep::Download, PipelineStep::Transcribe, PipelineStep::Tts], artifacts);
+        assert_eq!(
+            state.resumable_steps(),
+            vec![PipelineStep::Download, PipelineStep::Transcribe, PipelineStep::Tts]
+        );
+    }
+}
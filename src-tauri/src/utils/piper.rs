@@ -0,0 +1,301 @@
+//! Offline speech synthesis with Piper.
+//!
+//! Piper runs as an external program (`piper` from the tools directory or the
+//! PATH) with one ONNX model per voice. Models are downloaded on first use
+//! from the rhasspy/piper-voices repository into `piper/` in the app data dir.
+//! The engine registers itself in the speech engine registry at startup, so a
+//! job with engine `piper` needs no OpenAI key for speech.
+
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tauri::Manager;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command as TokioCommand;
+
+use crate::utils::tools::get_tool_path;
+use crate::utils::tts::tts::provider::{self, EngineCapabilities, EngineParams, SpeechOutput, SpeechProvider, SpeechRequest};
+use crate::utils::tts::tts::{Result as TtsResult, TtsError};
+
+pub const ENGINE_ID: &str = "piper";
+const VOICES_URL: &str = "https://huggingface.co/rhasspy/piper-voices/resolve/main";
+const DEFAULT_VOICE: &str = "en_US-lessac-medium";
+
+/// Voices offered before anything is downloaded, one or two per language
+const CATALOG: &[&str] = &[
+    "en_US-lessac-medium",
+    "en_US-ryan-high",
+    "en_GB-alan-medium",
+    "de_DE-thorsten-medium",
+    "es_ES-davefx-medium",
+    "fr_FR-siwis-medium",
+    "it_IT-riccardo-x_low",
+    "pl_PL-gosia-medium",
+    "pt_BR-faber-medium",
+    "ru_RU-irina-medium",
+    "ru_RU-dmitri-medium",
+    "uk_UA-ukrainian_tts-medium",
+    "tr_TR-dfki-medium",
+    "zh_CN-huayan-medium",
+];
+
+// One download at a time, segments synthesized in parallel wait for the same model
+static DOWNLOAD_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A Piper voice and whether its model is on disk
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PiperVoice {
+    pub key: String,
+    /// Locale, e.g. "en_US"
+    pub language: String,
+    pub installed: bool,
+}
+
+/// Directory of the downloaded models
+pub fn models_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow!("Failed to resolve app data directory: {}", e))?;
+    Ok(data_dir.join("piper"))
+}
+
+/// Locale, speaker and quality of a voice key like `en_US-lessac-medium`
+fn parse_key(key: &str) -> Option<(&str, &str, &str)> {
+    let (locale, rest) = key.split_once('-')?;
+    let (name, quality) = rest.rsplit_once('-')?;
+    if locale.len() < 2 || name.is_empty() || quality.is_empty() || key.contains(['/', '\\', '.']) {
+        return None;
+    }
+    Some((locale, name, quality))
+}
+
+/// URL of a voice model in the piper-voices repository
+fn model_url(key: &str) -> Option<String> {
+    let (locale, name, quality) = parse_key(key)?;
+    let family = locale.split('_').next()?;
+    Some(format!("{}/{}/{}/{}/{}/{}.onnx", VOICES_URL, family, locale, name, quality, key))
+}
+
+fn model_path(models_dir: &Path, key: &str) -> PathBuf {
+    models_dir.join(format!("{}.onnx", key))
+}
+
+/// Catalog voices and downloaded models, in catalog order then by name
+pub fn list_voices(models_dir: &Path) -> Vec<PiperVoice> {
+    let mut keys: Vec<String> = CATALOG.iter().map(|key| key.to_string()).collect();
+    let mut installed: Vec<String> = std::fs::read_dir(models_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".onnx").map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    installed.sort();
+    for key in installed {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    keys.into_iter()
+        .filter_map(|key| {
+            let (locale, _, _) = parse_key(&key)?;
+            Some(PiperVoice {
+                language: locale.to_string(),
+                installed: model_path(models_dir, &key).exists(),
+                key,
+            })
+        })
+        .collect()
+}
+
+async fn download(url: &str, target: &Path) -> Result<()> {
+    let partial = target.with_extension("part");
+    let mut response = reqwest::get(url).await?.error_for_status()?;
+    let mut file = tokio::fs::File::create(&partial).await?;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    drop(file);
+    tokio::fs::rename(&partial, target).await?;
+    Ok(())
+}
+
+/// Download the model of a voice unless it is already on disk
+pub async fn ensure_model(models_dir: &Path, key: &str) -> Result<PathBuf> {
+    let path = model_path(models_dir, key);
+    let config = path.with_extension("onnx.json");
+    if path.exists() && config.exists() {
+        return Ok(path);
+    }
+
+    let _guard = DOWNLOAD_LOCK.lock().await;
+    // Another segment may have finished the download while we waited
+    if path.exists() && config.exists() {
+        return Ok(path);
+    }
+    let url = model_url(key).ok_or_else(|| anyhow!("Invalid Piper voice: {}", key))?;
+    tokio::fs::create_dir_all(models_dir).await?;
+    info!("Downloading Piper voice {} from {}", key, url);
+    download(&format!("{}.json", url), &config).await?;
+    download(&url, &path).await?;
+    info!("Piper voice {} is ready", key);
+    Ok(path)
+}
+
+fn piper_binary() -> PathBuf {
+    get_tool_path("piper").unwrap_or_else(|| PathBuf::from("piper"))
+}
+
+/// Speech of the Piper engine
+pub struct Piper {
+    models_dir: PathBuf,
+    voice: String,
+    /// Piper's length scale, the inverse of the speech speed
+    length_scale: f32,
+}
+
+impl Piper {
+    pub fn new(models_dir: PathBuf, voice: &str, speed: f32) -> Self {
+        Self { models_dir, voice: voice.to_string(), length_scale: 1.0 / speed.clamp(0.5, 2.0) }
+    }
+
+    async fn synthesize_text(&self, text: &str) -> Result<Vec<u8>> {
+        let model = ensure_model(&self.models_dir, &self.voice).await?;
+        let wav = std::env::temp_dir().join(format!(
+            "videonova_piper_{}_{}.wav",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut child = TokioCommand::new(piper_binary())
+            .arg("--model")
+            .arg(&model)
+            .arg("--length_scale")
+            .arg(format!("{:.3}", self.length_scale))
+            .arg("--output_file")
+            .arg(&wav)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start piper, is it installed? {}", e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // One line per utterance, line breaks inside a cue would split it
+            stdin.write_all(text.replace('\n', " ").as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            let _ = tokio::fs::remove_file(&wav).await;
+            return Err(anyhow!("piper failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+
+        // The synchronizer works with MP3 fragments like the cloud engines return
+        let encoded = TokioCommand::new(crate::utils::tools::ffmpeg())
+            .arg("-i")
+            .arg(&wav)
+            .args(["-codec:a", "libmp3lame", "-q:a", "2", "-f", "mp3", "pipe:1"])
+            .stderr(Stdio::piped())
+            .output()
+            .await;
+        let _ = tokio::fs::remove_file(&wav).await;
+        let encoded = encoded?;
+        if !encoded.status.success() || encoded.stdout.is_empty() {
+            return Err(anyhow!("ffmpeg failed to encode Piper output: {}", String::from_utf8_lossy(&encoded.stderr).trim()));
+        }
+        debug!("Piper synthesized {} bytes for {:?}", encoded.stdout.len(), text);
+        Ok(encoded.stdout)
+    }
+}
+
+impl SpeechProvider for Piper {
+    fn name(&self) -> &str {
+        ENGINE_ID
+    }
+
+    fn synthesize<'a>(&'a self, request: &'a SpeechRequest<'a>) -> BoxFuture<'a, TtsResult<SpeechOutput>> {
+        Box::pin(async move {
+            let audio = self
+                .synthesize_text(request.text)
+                .await
+                .map_err(|e| TtsError::ConfigError(e.to_string()))?;
+            Ok((audio, request.text.to_string()))
+        })
+    }
+}
+
+/// Register the Piper engine with the voices known at startup
+pub fn register(app_handle: &tauri::AppHandle) {
+    let models_dir = match models_dir(app_handle) {
+        Ok(dir) => dir,
+        Err(e) => {
+            warn!("Piper is unavailable: {}", e);
+            return;
+        }
+    };
+    let voices = list_voices(&models_dir);
+    let mut languages: Vec<String> = Vec::new();
+    for voice in &voices {
+        if !languages.contains(&voice.language) {
+            languages.push(voice.language.clone());
+        }
+    }
+
+    let capabilities = EngineCapabilities {
+        id: ENGINE_ID.to_string(),
+        name: "Piper (offline)".to_string(),
+        voices: voices.into_iter().map(|voice| voice.key).collect(),
+        default_voice: DEFAULT_VOICE.to_string(),
+        needs_source_audio: false,
+        streaming: false,
+        languages,
+        requires_api_key: false,
+    };
+    provider::registry().register(
+        capabilities,
+        Arc::new(move |params: &EngineParams| {
+            if params.target_language.is_some() {
+                return Err(TtsError::ConfigError("Piper only speaks translated text".to_string()));
+            }
+            Ok(Arc::new(Piper::new(models_dir.clone(), &params.config.voice, params.config.speed)) as Arc<dyn SpeechProvider>)
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn voice_keys_map_to_repository_urls() {
+        assert_eq!(
+            model_url("en_US-lessac-medium").unwrap(),
+            format!("{}/en/en_US/lessac/medium/en_US-lessac-medium.onnx", VOICES_URL)
+        );
+        // Speaker names may contain dashes, the quality is always last
+        assert_eq!(parse_key("uk_UA-ukrainian_tts-medium"), Some(("uk_UA", "ukrainian_tts", "medium")));
+        assert!(model_url("../../etc-passwd-x").is_none());
+        assert!(model_url("nodash").is_none());
+    }
+
+    #[test]
+    fn installed_models_are_listed_after_the_catalog() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ru_RU-irina-medium.onnx"), b"model").unwrap();
+        std::fs::write(dir.path().join("nl_NL-mls-medium.onnx"), b"model").unwrap();
+        let voices = list_voices(dir.path());
+        assert_eq!(voices.len(), CATALOG.len() + 1);
+        assert!(voices.iter().find(|voice| voice.key == "ru_RU-irina-medium").unwrap().installed);
+        assert!(!voices[0].installed);
+        assert_eq!(voices.last().unwrap().language, "nl_NL");
+    }
+}
@@ -0,0 +1,198 @@
+//! Named settings profiles, e.g. one per client or account.
+//!
+//! A profile has its own OpenAI key, speech engine, voice preferences and
+//! remote output destination. These settings stay under their usual keys,
+//! where the frontend and the commands read them. `set_active_profile` first saves the
+//! current values into the profile being left, then writes the values of the
+//! new one over them. A value the profile doesn't set is removed, so the
+//! built-in default applies.
+
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::settings;
+
+const SETTINGS_KEY: &str = "settings-profiles";
+
+// Settings a profile switches
+const OPENAI_API_KEY: &str = "openai-api-key";
+const TTS_ENGINE: &str = "tts-engine";
+const VOICE_PREFERENCES: &str = "voice-preferences";
+const REMOTE_DESTINATION: &str = "remote-destination";
+
+/// A named set of settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SettingsProfile {
+    /// Assigned when the profile is first saved
+    pub id: String,
+    pub name: String,
+    pub openai_api_key: Option<serde_json::Value>,
+    pub tts_engine: Option<serde_json::Value>,
+    /// Favorite and recent voices per engine, as kept by `voices`
+    pub voice_preferences: Option<serde_json::Value>,
+    /// Where finished outputs are uploaded, as kept by `remote`
+    pub remote_destination: Option<serde_json::Value>,
+}
+
+impl SettingsProfile {
+    /// Values of the profile with the settings keys they stand for
+    fn fields(&mut self) -> [(&'static str, &mut Option<serde_json::Value>); 4] {
+        [
+            (OPENAI_API_KEY, &mut self.openai_api_key),
+            (TTS_ENGINE, &mut self.tts_engine),
+            (VOICE_PREFERENCES, &mut self.voice_preferences),
+            (REMOTE_DESTINATION, &mut self.remote_destination),
+        ]
+    }
+
+    /// Take over the values of another profile, keeping id and name
+    fn take_values(&mut self, mut other: SettingsProfile) {
+        for ((_, value), (_, other_value)) in self.fields().into_iter().zip(other.fields()) {
+            *value = other_value.take();
+        }
+    }
+}
+
+/// All profiles, stored as the "settings-profiles" setting
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Profiles {
+    pub active: Option<String>,
+    pub profiles: Vec<SettingsProfile>,
+}
+
+impl Profiles {
+    fn find(&self, id: &str) -> Option<&SettingsProfile> {
+        self.profiles.iter().find(|profile| profile.id == id)
+    }
+
+    /// Make `id` the active profile. `current` holds the settings as they are
+    /// now and goes into the profile being left. Returns the profile to apply.
+    fn switch(&mut self, id: &str, current: SettingsProfile) -> Result<SettingsProfile> {
+        let next = self.find(id).cloned().ok_or_else(|| anyhow!("Unknown profile {}", id))?;
+        let active = self.active.clone();
+        if let Some(previous) = self.profiles.iter_mut().find(|profile| Some(&profile.id) == active.as_ref()) {
+            previous.take_values(current);
+        }
+        self.active = Some(id.to_string());
+        Ok(next)
+    }
+}
+
+pub fn load(app_handle: &tauri::AppHandle) -> Profiles {
+    settings::get(app_handle, SETTINGS_KEY).unwrap_or_default()
+}
+
+/// The settings a profile switches, as they are now
+fn capture(app_handle: &tauri::AppHandle) -> SettingsProfile {
+    let mut current = SettingsProfile::default();
+    for (key, value) in current.fields() {
+        *value = settings::get_value(app_handle, key).filter(|value| !value.is_null());
+    }
+    current
+}
+
+/// Write the values of a profile into the settings
+async fn apply(app_handle: &tauri::AppHandle, mut profile: SettingsProfile) -> Result<()> {
+    for (key, value) in profile.fields() {
+        match value {
+            Some(value) => settings::set(app_handle, key, value).await?,
+            None => settings::remove(app_handle, key).await?,
+        }
+    }
+    Ok(())
+}
+
+/// Create or update a profile, returns it with its id
+pub async fn save(app_handle: &tauri::AppHandle, mut profile: SettingsProfile) -> Result<SettingsProfile> {
+    if profile.name.trim().is_empty() {
+        return Err(anyhow!("The profile needs a name"));
+    }
+    if profile.id.is_empty() {
+        profile.id = uuid::Uuid::new_v4().to_string();
+    }
+    let saved = profile.clone();
+    let profiles = settings::update(app_handle, SETTINGS_KEY, |profiles: &mut Profiles| {
+        match profiles.profiles.iter_mut().find(|existing| existing.id == saved.id) {
+            Some(existing) => *existing = saved.clone(),
+            None => profiles.profiles.push(saved.clone()),
+        }
+    })
+    .await?;
+    // Edits of the active profile take effect right away
+    if profiles.active.as_deref() == Some(profile.id.as_str()) {
+        apply(app_handle, profile.clone()).await?;
+    }
+    Ok(profile)
+}
+
+/// Delete a profile. The settings stay as they are if it was the active one.
+pub async fn delete(app_handle: &tauri::AppHandle, id: &str) -> Result<()> {
+    let mut found = false;
+    settings::update(app_handle, SETTINGS_KEY, |profiles: &mut Profiles| {
+        found = profiles.find(id).is_some();
+        profiles.profiles.retain(|profile| profile.id != id);
+        if profiles.active.as_deref() == Some(id) {
+            profiles.active = None;
+        }
+    })
+    .await?;
+    if !found {
+        return Err(anyhow!("Unknown profile {}", id));
+    }
+    Ok(())
+}
+
+/// Switch to another profile, returns it
+pub async fn set_active(app_handle: &tauri::AppHandle, id: &str) -> Result<SettingsProfile> {
+    // Capture and switch under the settings lock, so a concurrent save or
+    // delete of a profile can't be overwritten
+    let mut switched = Err(anyhow!("Unknown profile {}", id));
+    settings::update(app_handle, SETTINGS_KEY, |profiles: &mut Profiles| {
+        switched = profiles.switch(id, capture(app_handle));
+    })
+    .await?;
+    let next = switched?;
+    apply(app_handle, next.clone()).await?;
+    info!("Switched to settings profile '{}'", next.name);
+    Ok(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(id: &str, api_key: &str) -> SettingsProfile {
+        SettingsProfile {
+            id: id.to_string(),
+            name: id.to_string(),
+            openai_api_key: Some(api_key.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn switching_keeps_the_settings_of_the_profile_left() {
+        let mut profiles = Profiles { active: None, profiles: vec![profile("work", "sk-work"), profile("home", "sk-home")] };
+        assert!(profiles.switch("missing", SettingsProfile::default()).is_err());
+
+        let next = profiles.switch("work", SettingsProfile::default()).unwrap();
+        assert_eq!(next.openai_api_key, Some("sk-work".into()));
+
+        // The key was changed while "work" was active
+        let current = SettingsProfile {
+            openai_api_key: Some("sk-work-2".into()),
+            tts_engine: Some("fish-speech".into()),
+            ..Default::default()
+        };
+        let next = profiles.switch("home", current).unwrap();
+        assert_eq!(next.openai_api_key, Some("sk-home".into()));
+        assert_eq!(profiles.active.as_deref(), Some("home"));
+        let work = profiles.find("work").unwrap();
+        assert_eq!(work.name, "work");
+        assert_eq!(work.openai_api_key, Some("sk-work-2".into()));
+        assert_eq!(work.tts_engine, Some("fish-speech".into()));
+    }
+}
@@ -0,0 +1,364 @@
+//! Overall progress of the processing pipeline.
+//!
+//! Every step reports its own 0-100% progress. The total is computed from the
+//! steps that are actually scheduled for the job, each weighted by its
+//! estimated duration, so skipping download/transcription (library reuse) or
+//! adding a remote upload still gives a meaningful overall percentage.
+//!
+//! The remaining time comes from the throughput of the running step, in the
+//! units the step reports (segments for TTS, bytes for uploads, its
+//! percentage otherwise), and for the steps still to come from the time the
+//! job has needed per unit of weight so far.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{EventId, Listener};
+
+use crate::utils::emitter;
+use crate::utils::events::{self, PipelineEvent, TaskbarProgress, PIPELINE_EVENT};
+use crate::utils::settings;
+
+const SETTINGS_KEY: &str = "progress-weights";
+/// Throughput is measured over this much of the latest progress
+const ETA_WINDOW: Duration = Duration::from_secs(30);
+/// Shorter measurements give estimates that jump around
+const ETA_MIN_SPAN: Duration = Duration::from_secs(2);
+/// Share of the job that has to be done before later steps are estimated
+const ETA_MIN_DONE: f32 = 0.02;
+
+/// Pipeline steps that report progress
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineStep {
+    Download,
+    Transcribe,
+    Translate,
+    Tts,
+    Merge,
+    Upload,
+}
+
+impl PipelineStep {
+    /// Event through which the step reports its own progress
+    fn event_name(&self) -> &'static str {
+        match self {
+            PipelineStep::Download => "download-progress",
+            PipelineStep::Transcribe => "transcription-progress",
+            PipelineStep::Translate => "translation-progress",
+            PipelineStep::Tts => "tts-progress",
+            PipelineStep::Merge => "merge-progress",
+            PipelineStep::Upload => "remote-upload-progress",
+        }
+    }
+}
+
+/// Estimated processing cost of each step in seconds per second of video.
+/// Only the ratios matter, the absolute values are never shown.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StepWeights {
+    pub download: f32,
+    pub transcribe: f32,
+    pub translate: f32,
+    pub tts: f32,
+    pub merge: f32,
+    pub upload: f32,
+}
+
+impl Default for StepWeights {
+    fn default() -> Self {
+        Self {
+            download: 0.3,
+            transcribe: 0.2,
+            translate: 0.1,
+            tts: 1.0,
+            merge: 0.3,
+            upload: 0.3,
+        }
+    }
+}
+
+impl StepWeights {
+    fn weight(&self, step: PipelineStep) -> f32 {
+        match step {
+            PipelineStep::Download => self.download,
+            PipelineStep::Transcribe => self.transcribe,
+            PipelineStep::Translate => self.translate,
+            PipelineStep::Tts => self.tts,
+            PipelineStep::Merge => self.merge,
+            PipelineStep::Upload => self.upload,
+        }
+        .max(0.0)
+    }
+}
+
+/// Load user weights from the settings store, falling back to defaults
+pub fn load_weights(app_handle: &tauri::AppHandle) -> StepWeights {
+    settings::get(app_handle, SETTINGS_KEY).unwrap_or_default()
+}
+
+/// Save user weights to the settings store
+pub async fn save_weights(app_handle: &tauri::AppHandle, weights: &StepWeights) -> Result<()> {
+    settings::set(app_handle, SETTINGS_KEY, weights).await
+}
+
+/// Weighted plan of the steps scheduled for a job
+#[derive(Debug)]
+pub struct ProgressPlan {
+    steps: Vec<(PipelineStep, f32)>,
+    fractions: HashMap<PipelineStep, f32>,
+    // Download reports audio and video separately
+    download_components: HashMap<String, f32>,
+}
+
+impl ProgressPlan {
+    pub fn new(steps: &[PipelineStep], weights: &StepWeights) -> Self {
+        let total: f32 = steps.iter().map(|s| weights.weight(*s)).sum();
+        let steps = steps
+            .iter()
+            .map(|s| {
+                // Without usable weights every step counts the same
+                let weight = if total > 0.0 { weights.weight(*s) / total } else { 1.0 / steps.len() as f32 };
+                (*s, weight)
+            })
+            .collect();
+        Self { steps, fractions: HashMap::new(), download_components: HashMap::new() }
+    }
+
+    /// Update the progress (0-100) of a step and return the new total (0-100)
+    pub fn update(&mut self, step: PipelineStep, progress: f32) -> f32 {
+        let fraction = (progress / 100.0).clamp(0.0, 1.0);
+        // Steps never go backwards, retries inside a step shouldn't move the total back
+        let entry = self.fractions.entry(step).or_insert(0.0);
+        *entry = entry.max(fraction);
+        self.total()
+    }
+
+    /// Update one download component ("audio" or "video"). Only the streams
+    /// that reported are averaged, an audio-only download has no video.
+    pub fn update_download(&mut self, component: &str, progress: f32) -> f32 {
+        let entry = self.download_components.entry(component.to_string()).or_insert(0.0);
+        *entry = entry.max(progress.clamp(0.0, 100.0));
+        let average = self.download_components.values().sum::<f32>() / self.download_components.len() as f32;
+        self.update(PipelineStep::Download, average)
+    }
+
+    /// Mark a step as done
+    pub fn complete(&mut self, step: PipelineStep) -> f32 {
+        self.update(step, 100.0)
+    }
+
+    pub fn total(&self) -> f32 {
+        self.steps
+            .iter()
+            .map(|(step, weight)| weight * self.fractions.get(step).copied().unwrap_or(0.0))
+            .sum::<f32>()
+            * 100.0
+    }
+
+    pub fn contains(&self, step: PipelineStep) -> bool {
+        self.steps.iter().any(|(s, _)| *s == step)
+    }
+
+    /// Weight of the work left in the steps other than `step`, 0-1
+    fn remaining_weight_except(&self, step: PipelineStep) -> f32 {
+        self.steps
+            .iter()
+            .filter(|(s, _)| *s != step)
+            .map(|(s, weight)| weight * (1.0 - self.fractions.get(s).copied().unwrap_or(0.0)))
+            .sum()
+    }
+}
+
+/// Remaining time of a step from its recent throughput
+#[derive(Debug, Default)]
+pub struct EtaEstimator {
+    /// Time and units done
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl EtaEstimator {
+    /// Record `done` of `total` units and return the seconds left, once there
+    /// is enough history to tell
+    pub fn observe(&mut self, at: Instant, done: f64, total: f64) -> Option<f64> {
+        // Progress going back means the step started over (a retry or another language)
+        if self.samples.back().is_some_and(|(_, last)| done < *last) {
+            self.samples.clear();
+        }
+        self.samples.push_back((at, done));
+        while self.samples.len() > 2 && self.samples.front().is_some_and(|(time, _)| at.duration_since(*time) > ETA_WINDOW) {
+            self.samples.pop_front();
+        }
+
+        let (first_at, first_done) = *self.samples.front()?;
+        let span = at.duration_since(first_at);
+        if span < ETA_MIN_SPAN || done <= first_done {
+            return None;
+        }
+        let rate = (done - first_done) / span.as_secs_f64();
+        Some((total - done).max(0.0) / rate)
+    }
+}
+
+/// Units done and in total of a step event: segments, bytes or percent
+fn work_units(payload: &serde_json::Value, step_progress: f32) -> (f64, f64) {
+    let pair = |done: &str, total: &str| Some((payload[done].as_f64()?, payload[total].as_f64().filter(|t| *t > 0.0)?));
+    pair("current_segment", "total_segments")
+        .or_else(|| pair("bytes_sent", "total_bytes"))
+        .unwrap_or((step_progress as f64, 100.0))
+}
+
+/// Plan, step estimators and start of a tracked job
+struct TrackerState {
+    plan: ProgressPlan,
+    estimators: HashMap<PipelineStep, EtaEstimator>,
+    started: Instant,
+}
+
+impl TrackerState {
+    /// Seconds until the job is done: what is left of `step` at its own
+    /// throughput, and the other steps at the pace of the job so far
+    fn eta(&self, step: PipelineStep, step_eta: Option<f64>) -> Option<f64> {
+        let done = self.plan.total() / 100.0;
+        if done < ETA_MIN_DONE {
+            return step_eta;
+        }
+        let secs_per_weight = self.started.elapsed().as_secs_f64() / done as f64;
+        let others = self.plan.remaining_weight_except(step) as f64 * secs_per_weight;
+        match step_eta {
+            Some(step_eta) => Some(step_eta + others),
+            None => Some((1.0 - done as f64).max(0.0) * secs_per_weight),
+        }
+    }
+}
+
+/// Listens to the step events and emits a [`PipelineEvent`] with the total for
+/// each of them. Listeners are removed when the tracker is dropped.
+pub struct PipelineProgressTracker {
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    job_id: String,
+    state: Arc<Mutex<TrackerState>>,
+    listeners: Vec<EventId>,
+}
+
+impl PipelineProgressTracker {
+    pub fn start(app_handle: &tauri::AppHandle, window: &tauri::Window, job_id: &str, steps: &[PipelineStep]) -> Self {
+        let state = Arc::new(Mutex::new(TrackerState {
+            plan: ProgressPlan::new(steps, &load_weights(app_handle)),
+            estimators: HashMap::new(),
+            started: Instant::now(),
+        }));
+        let mut listeners = Vec::new();
+
+        for step in steps.iter().copied() {
+            let state = state.clone();
+            let window = window.clone();
+            let job_id = job_id.to_string();
+            // Standalone commands emit the same events, without the job id
+            let id = app_handle.listen_any(step.event_name(), move |event| {
+                let Ok(payload) = serde_json::from_str::<serde_json::Value>(event.payload()) else {
+                    return;
+                };
+                if payload["job_id"].as_str() != Some(job_id.as_str()) {
+                    return;
+                }
+                let Some(step_progress) = payload["progress"].as_f64().map(|p| p as f32) else {
+                    return;
+                };
+
+                let (total_progress, eta) = {
+                    let Ok(mut state) = state.lock() else { return };
+                    let total_progress = match (step, payload["component"].as_str()) {
+                        (PipelineStep::Download, Some(component)) => state.plan.update_download(component, step_progress),
+                        _ => state.plan.update(step, step_progress),
+                    };
+                    // Audio and video download side by side, the step's share tells more
+                    let units = match step {
+                        PipelineStep::Download => (state.plan.fractions.get(&step).copied().unwrap_or(0.0) as f64 * 100.0, 100.0),
+                        _ => work_units(&payload, step_progress),
+                    };
+                    let step_eta = state.estimators.entry(step).or_default().observe(Instant::now(), units.0, units.1);
+                    (total_progress, state.eta(step, step_eta))
+                };
+                events::set_taskbar_progress(&window, TaskbarProgress::Running(total_progress));
+                let _ = emitter::emit(&window, PIPELINE_EVENT, PipelineEvent {
+                    job_id: job_id.clone(),
+                    step,
+                    step_progress,
+                    total_progress,
+                    message: payload["status"].as_str().map(str::to_string),
+                    eta,
+                });
+            });
+            listeners.push(id);
+        }
+
+        events::set_taskbar_progress(window, TaskbarProgress::Running(0.0));
+        Self { app_handle: app_handle.clone(), window: window.clone(), job_id: job_id.to_string(), state, listeners }
+    }
+
+    /// Mark a step as finished, e.g. when it was served from cache without reporting progress
+    pub fn complete(&self, window: &tauri::Window, step: PipelineStep) {
+        let (total_progress, eta) = match self.state.lock() {
+            Ok(mut state) if state.plan.contains(step) => {
+                let total_progress = state.plan.complete(step);
+                (total_progress, state.eta(step, Some(0.0)))
+            }
+            _ => return,
+        };
+        events::set_taskbar_progress(window, TaskbarProgress::Running(total_progress));
+        let _ = emitter::emit(window, PIPELINE_EVENT, PipelineEvent {
+            job_id: self.job_id.clone(),
+            step,
+            step_progress: 100.0,
+            total_progress,
+            message: None,
+            eta,
+        });
+    }
+}
+
+impl Drop for PipelineProgressTracker {
+    fn drop(&mut self) {
+        for id in self.listeners.drain(..) {
+            self.app_handle.unlisten(id);
+        }
+        events::set_taskbar_progress(&self.window, TaskbarProgress::Idle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_follows_recent_throughput() {
+        let start = Instant::now();
+        let mut estimator = EtaEstimator::default();
+        assert_eq!(estimator.observe(start, 0.0, 100.0), None);
+        // 10 segments in 5 seconds, 90 left at 2 per second
+        let eta = estimator.observe(start + Duration::from_secs(5), 10.0, 100.0).unwrap();
+        assert!((eta - 45.0).abs() < 1e-6);
+        // The step starting over drops the old history
+        assert_eq!(estimator.observe(start + Duration::from_secs(6), 1.0, 100.0), None);
+
+        let payload = serde_json::json!({"progress": 40.0, "current_segment": 12, "total_segments": 30});
+        assert_eq!(work_units(&payload, 40.0), (12.0, 30.0));
+        assert_eq!(work_units(&serde_json::json!({"progress": 40.0}), 40.0), (40.0, 100.0));
+    }
+
+    #[test]
+    fn download_averages_reported_streams() {
+        let weights = StepWeights::default();
+        let mut plan = ProgressPlan::new(&[PipelineStep::Download], &weights);
+        // A stream served from an earlier download never reports
+        assert!((plan.update_download("audio", 100.0) - 100.0).abs() < 1e-4);
+
+        let mut plan = ProgressPlan::new(&[PipelineStep::Download], &weights);
+        plan.update_download("audio", 80.0);
+        assert!((plan.update_download("video", 40.0) - 60.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,421 @@
+//! Publishing of dubbed results to YouTube via the YouTube Data API.
+//!
+//! Authorization uses the OAuth 2.0 device flow, so the user confirms access in
+//! their browser while the app polls for the token. Videos are sent with the
+//! resumable upload protocol; translated subtitles can be attached to an
+//! existing video as a caption track. The API does not allow adding extra
+//! audio tracks to an existing video, so dubbed audio is published as a new
+//! upload of the merged file.
+
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::mpsc;
+
+use crate::utils::common::now_secs;
+use crate::utils::settings;
+
+const DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const UPLOAD_URL: &str = "https://www.googleapis.com/upload/youtube/v3/videos";
+const CAPTIONS_URL: &str = "https://www.googleapis.com/upload/youtube/v3/captions";
+const SCOPES: &str = "https://www.googleapis.com/auth/youtube.upload https://www.googleapis.com/auth/youtube.force-ssl";
+const TOKEN_STORE_KEY: &str = "youtube-oauth";
+
+// Resumable upload chunks must be a multiple of 256 KiB
+const UPLOAD_CHUNK_SIZE: usize = 32 * 256 * 1024;
+/// Chunks in a row YouTube may answer without storing anything of them
+const MAX_STALLED_CHUNKS: u32 = 3;
+
+/// Upload progress reported to the frontend
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UploadProgress {
+    pub status: String,
+    pub progress: f32,
+    pub bytes_sent: u64,
+    pub total_bytes: u64,
+}
+
+/// Device code issued by Google for the user to confirm
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_url: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+/// OAuth token persisted in the settings store
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct YoutubeToken {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: u64, // Unix timestamp in seconds
+}
+
+/// Metadata of the video to publish
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VideoMetadata {
+    pub title: String,
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// "private", "unlisted" or "public"
+    pub privacy_status: String,
+    /// ISO 639-1 code of the dubbed audio
+    pub language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+}
+
+/// Request a device code the user has to enter at the verification url
+pub async fn start_device_authorization(client_id: &str) -> Result<DeviceAuthorization> {
+    info!("Requesting YouTube device authorization code");
+    let client = reqwest::Client::new();
+    let response = client
+        .post(DEVICE_CODE_URL)
+        .form(&[("client_id", client_id), ("scope", SCOPES)])
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Device authorization failed (HTTP {}): {}", status, error_text));
+    }
+
+    Ok(response.json::<DeviceAuthorization>().await?)
+}
+
+/// Poll the token endpoint until the user confirms access or the code expires
+pub async fn wait_for_device_token(
+    client_id: &str,
+    client_secret: &str,
+    authorization: &DeviceAuthorization,
+) -> Result<YoutubeToken> {
+    let client = reqwest::Client::new();
+    let mut interval = authorization.interval.max(1);
+    let deadline = now_secs() + authorization.expires_in;
+
+    while now_secs() < deadline {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        let response = client
+            .post(TOKEN_URL)
+            .form(&[
+                ("client_id", client_id),
+                ("client_secret", client_secret),
+                ("device_code", authorization.device_code.as_str()),
+                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ])
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            let token: TokenResponse = response.json().await?;
+            info!("YouTube authorization granted");
+            return Ok(YoutubeToken {
+                access_token: token.access_token,
+                refresh_token: token.refresh_token,
+                expires_at: now_secs() + token.expires_in,
+            });
+        }
+
+        let error: TokenErrorResponse = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Unexpected token endpoint response: {}", e))?;
+        match error.error.as_str() {
+            "authorization_pending" => debug!("Waiting for the user to confirm YouTube access"),
+            "slow_down" => interval += 5,
+            "access_denied" => return Err(anyhow!("YouTube access was denied by the user")),
+            "expired_token" => break,
+            other => return Err(anyhow!("YouTube authorization failed: {}", other)),
+        }
+    }
+
+    Err(anyhow!("YouTube authorization code expired, please start again"))
+}
+
+/// Exchange the refresh token for a new access token
+pub async fn refresh_token(client_id: &str, client_secret: &str, token: &YoutubeToken) -> Result<YoutubeToken> {
+    let refresh_token = token
+        .refresh_token
+        .as_deref()
+        .ok_or_else(|| anyhow!("No refresh token stored, please authorize again"))?;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(TOKEN_URL)
+        .form(&[
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("refresh_token", refresh_token),
+            ("grant_type", "refresh_token"),
+        ])
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to refresh YouTube token (HTTP {}): {}", status, error_text));
+    }
+
+    let refreshed: TokenResponse = response.json().await?;
+    Ok(YoutubeToken {
+        access_token: refreshed.access_token,
+        // Google omits the refresh token on refresh, keep the old one
+        refresh_token: refreshed.refresh_token.or_else(|| token.refresh_token.clone()),
+        expires_at: now_secs() + refreshed.expires_in,
+    })
+}
+
+/// Save the token in the settings store
+pub async fn save_token(app_handle: &tauri::AppHandle, token: &YoutubeToken) -> Result<()> {
+    settings::set(app_handle, TOKEN_STORE_KEY, token).await
+}
+
+/// Load the token from the settings store
+pub fn load_token(app_handle: &tauri::AppHandle) -> Result<Option<YoutubeToken>> {
+    Ok(settings::get(app_handle, TOKEN_STORE_KEY))
+}
+
+/// Return a valid access token, refreshing it if it is about to expire
+pub async fn access_token(app_handle: &tauri::AppHandle, client_id: &str, client_secret: &str) -> Result<String> {
+    let token = load_token(app_handle)?
+        .ok_or_else(|| anyhow!("YouTube account is not connected"))?;
+
+    if token.expires_at > now_secs() + 60 {
+        return Ok(token.access_token);
+    }
+
+    info!("YouTube access token expired, refreshing");
+    let refreshed = refresh_token(client_id, client_secret, &token).await?;
+    save_token(app_handle, &refreshed).await?;
+    Ok(refreshed.access_token)
+}
+
+async fn send_progress(sender: &Option<mpsc::Sender<UploadProgress>>, status: &str, sent: u64, total: u64) {
+    if let Some(tx) = sender {
+        let progress = if total > 0 { sent as f32 / total as f32 * 100.0 } else { 0.0 };
+        let _ = tx
+            .send(UploadProgress {
+                status: status.to_string(),
+                progress,
+                bytes_sent: sent,
+                total_bytes: total,
+            })
+            .await;
+    }
+}
+
+/// Upload a video file and return the id of the created YouTube video
+pub async fn upload_video(
+    access_token: &str,
+    video_path: &Path,
+    metadata: &VideoMetadata,
+    progress_sender: Option<mpsc::Sender<UploadProgress>>,
+) -> Result<String> {
+    let total_bytes = tokio::fs::metadata(video_path).await?.len();
+    info!("Uploading {} ({} bytes) to YouTube", video_path.display(), total_bytes);
+    send_progress(&progress_sender, "Starting upload", 0, total_bytes).await;
+
+    let mut snippet = json!({
+        "title": metadata.title,
+        "description": metadata.description,
+        "tags": metadata.tags,
+    });
+    if let Some(lang) = &metadata.language {
+        snippet["defaultAudioLanguage"] = json!(lang);
+        snippet["defaultLanguage"] = json!(lang);
+    }
+    let body = json!({
+        "snippet": snippet,
+        "status": { "privacyStatus": metadata.privacy_status },
+    });
+
+    // Open a resumable upload session
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}?uploadType=resumable&part=snippet,status", UPLOAD_URL))
+        .bearer_auth(access_token)
+        .header("X-Upload-Content-Type", "video/*")
+        .header("X-Upload-Content-Length", total_bytes.to_string())
+        .json(&body)
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to start YouTube upload (HTTP {}): {}", status, error_text));
+    }
+
+    let session_url = response
+        .headers()
+        .get("location")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| anyhow!("YouTube did not return an upload session url"))?
+        .to_string();
+
+    // Send the file in chunks
+    let mut file = tokio::fs::File::open(video_path).await?;
+    let mut buffer = vec![0u8; UPLOAD_CHUNK_SIZE];
+    let mut sent: u64 = 0;
+    let mut stalled = 0;
+
+    loop {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            let read = file.read(&mut buffer[filled..]).await?;
+            if read == 0 {
+                break;
+            }
+            filled += read;
+        }
+        if filled == 0 && sent > 0 {
+            return Err(anyhow!("Upload finished without a response from YouTube"));
+        }
+
+        let end = sent + filled as u64;
+        let response = client
+            .put(&session_url)
+            .bearer_auth(access_token)
+            .header("Content-Length", filled.to_string())
+            .header("Content-Range", format!("bytes {}-{}/{}", sent, end.saturating_sub(1), total_bytes))
+            .body(buffer[..filled].to_vec())
+            .send()
+            .await?;
+
+        let status = response.status();
+
+        // 308 means "Resume Incomplete": the Range header tells how much of the
+        // file is stored, which may be only part of the chunk
+        if status.as_u16() == 308 {
+            let range = response.headers().get("range").and_then(|v| v.to_str().ok());
+            let stored = stored_bytes(range).min(end);
+            if stored <= sent {
+                stalled += 1;
+                if stalled >= MAX_STALLED_CHUNKS {
+                    return Err(anyhow!("YouTube stopped accepting the upload at byte {}", stored));
+                }
+            } else {
+                stalled = 0;
+            }
+            if stored < end {
+                debug!("YouTube stored {} of {} bytes sent, resuming from there", stored, end);
+                file.seek(std::io::SeekFrom::Start(stored)).await?;
+            }
+            sent = stored;
+            send_progress(&progress_sender, "Uploading video", sent, total_bytes).await;
+            continue;
+        }
+
+        if status.is_success() {
+            let video: serde_json::Value = response.json().await?;
+            let video_id = video["id"]
+                .as_str()
+                .ok_or_else(|| anyhow!("YouTube response did not contain a video id"))?
+                .to_string();
+            send_progress(&progress_sender, "Upload complete", total_bytes, total_bytes).await;
+            info!("Video uploaded to YouTube with id {}", video_id);
+            return Ok(video_id);
+        }
+
+        let error_text = response.text().await.unwrap_or_default();
+        warn!("YouTube upload chunk failed: HTTP {}", status);
+        return Err(anyhow!("YouTube upload failed (HTTP {}): {}", status, error_text));
+    }
+}
+
+/// Bytes of the upload YouTube has stored, from the `Range: bytes=0-<last>`
+/// header of a 308 reply. Without the header nothing was stored yet.
+fn stored_bytes(range: Option<&str>) -> u64 {
+    range
+        .and_then(|range| range.trim().strip_prefix("bytes=0-"))
+        .and_then(|last| last.parse::<u64>().ok())
+        .map_or(0, |last| last + 1)
+}
+
+/// Attach a subtitle file to an existing video as a caption track
+pub async fn upload_captions(
+    access_token: &str,
+    video_id: &str,
+    subtitle_path: &Path,
+    language: &str,
+    name: &str,
+) -> Result<String> {
+    info!("Uploading {} captions for YouTube video {}", language, video_id);
+    let content = tokio::fs::read(subtitle_path).await?;
+    let file_name = subtitle_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "captions.vtt".to_string());
+
+    let snippet = json!({
+        "snippet": {
+            "videoId": video_id,
+            "language": language,
+            "name": name,
+            "isDraft": false,
+        }
+    });
+
+    let form = reqwest::multipart::Form::new()
+        .part(
+            "snippet",
+            reqwest::multipart::Part::text(snippet.to_string()).mime_str("application/json")?,
+        )
+        .part(
+            "file",
+            reqwest::multipart::Part::bytes(content)
+                .file_name(file_name)
+                .mime_str("application/octet-stream")?,
+        );
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}?uploadType=multipart&part=snippet", CAPTIONS_URL))
+        .bearer_auth(access_token)
+        .multipart(form)
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to upload captions (HTTP {}): {}", status, error_text));
+    }
+
+    let caption: serde_json::Value = response.json().await?;
+    Ok(caption["id"].as_str().unwrap_or_default().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_offset_comes_from_the_range_header() {
+        assert_eq!(stored_bytes(Some("bytes=0-8388607")), 8_388_608);
+        assert_eq!(stored_bytes(Some("bytes=0-0")), 1);
+        assert_eq!(stored_bytes(None), 0);
+        assert_eq!(stored_bytes(Some("garbage")), 0);
+    }
+}
@@ -0,0 +1,363 @@
+//! Queue of videos waiting to be processed.
+//!
+//! `enqueue_job` adds a video to the queue and returns right away; a single
+//! worker takes the queued entries one after another and runs them through the
+//! pipeline. Only one pipeline runs at a time, because the step progress
+//! events don't carry a job id. Runs started outside the worker (`process_video`,
+//! channel templates, resumed jobs) take the same run slot, so they wait for
+//! the run in progress instead of mixing their events and logs with it. The
+//! queue lives in Tauri state and is not kept across restarts: an interrupted
+//! run is resumed through its pipeline checkpoint instead.
+
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard, Notify};
+
+use crate::utils::channels::TemplateLanguage;
+use crate::utils::common::now_secs;
+use crate::utils::merge::{MergeStyle, OutputContainer};
+use crate::utils::source_language;
+use crate::utils::subtitles::{SubtitleKind, SubtitleSource};
+use crate::utils::tts::tts::timeline::FitStrategy;
+use crate::utils::validation::{CommandError, Validate, Validator};
+
+static QUEUE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// What to process, the same parameters `process_video` takes
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobRequest {
+    pub url: String,
+    pub output_path: String,
+    pub target_language: String,
+    pub target_language_name: String,
+    pub source_language_code: String,
+    pub source_language_name: String,
+    /// Kept in memory only, never sent back to the frontend
+    #[serde(skip_serializing, default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub subtitle_source: Option<SubtitleSource>,
+    #[serde(default)]
+    pub merge_style: Option<MergeStyle>,
+    #[serde(default)]
+    pub fit_strategy: Option<FitStrategy>,
+    #[serde(default)]
+    pub voice: Option<String>,
+    #[serde(default)]
+    pub force: Option<bool>,
+    /// Overrides the container of the merge style
+    #[serde(default)]
+    pub container: Option<OutputContainer>,
+    /// Indexes of adjacent chapters to process instead of the whole video
+    #[serde(default)]
+    pub chapters: Option<Vec<usize>>,
+    /// Start of the clip to process, seconds
+    #[serde(default)]
+    pub start_time: Option<f64>,
+    /// End of the clip to process, seconds
+    #[serde(default)]
+    pub end_time: Option<f64>,
+    /// More languages to dub into in the same run, as tracks of one MKV
+    #[serde(default)]
+    pub extra_languages: Vec<TemplateLanguage>,
+}
+
+impl Validate for JobRequest {
+    fn validate(&self) -> Result<(), CommandError> {
+        let mut validator = Validator::new();
+        validator
+            .url("url", &self.url)
+            .output_dir("output_path", &self.output_path)
+            .language("target_language", &self.target_language)
+            .not_empty("target_language_name", &self.target_language_name);
+        // The source language may be left to detection
+        if !source_language::is_auto(&self.source_language_code) {
+            validator.language("source_language_code", &self.source_language_code);
+        }
+        validator.check(
+            "source_language_code",
+            self.source_language_code != self.target_language,
+            "Must differ from the target language",
+        );
+        validator.check(
+            "chapters",
+            self.chapters.as_ref().is_none_or(|chapters| !chapters.is_empty()),
+            "Select at least one chapter",
+        );
+        let range_set = self.start_time.is_some() || self.end_time.is_some();
+        validator.check("start_time", !(range_set && self.chapters.is_some()), "Either select chapters or a time range");
+        validator.check("start_time", self.start_time.is_none_or(|start| start >= 0.0), "Must not be negative");
+        validator.check(
+            "end_time",
+            match (self.start_time, self.end_time) {
+                (Some(start), Some(end)) => end > start,
+                (None, Some(end)) => end > 0.0,
+                _ => true,
+            },
+            "Must be after the start time",
+        );
+        let mut targets = vec![self.target_language.as_str()];
+        for language in &self.extra_languages {
+            validator
+                .language("extra_languages", &language.code)
+                .not_empty("extra_languages", &language.name);
+            targets.push(&language.code);
+        }
+        let distinct = targets.iter().enumerate().all(|(i, code)| !targets[..i].contains(code));
+        validator.check("extra_languages", distinct, "Each target language may be given only once");
+        validator.check(
+            "extra_languages",
+            !self.extra_languages.iter().any(|language| language.code == self.source_language_code),
+            "Must differ from the source language",
+        );
+        if let Some(source) = &self.subtitle_source {
+            if source.kind == SubtitleKind::LocalFile {
+                validator.existing_file("subtitle_source", &source.url);
+            } else {
+                validator.url("subtitle_source", &source.url);
+            }
+        }
+        validator.finish()
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// An entry of the queue
+#[derive(Debug, Serialize, Clone)]
+pub struct QueuedJob {
+    pub id: String,
+    pub request: JobRequest,
+    pub status: QueueStatus,
+    /// Pipeline job id, once the entry started
+    pub job_id: Option<String>,
+    /// Overall progress 0-100 of a running entry
+    pub progress: f32,
+    pub final_path: Option<String>,
+    pub error: Option<String>,
+    pub enqueued_at: u64, // Unix timestamp in seconds
+}
+
+/// Payload of the `queue-job-progress` event
+#[derive(Debug, Serialize, Clone)]
+pub struct QueueJobProgress {
+    pub id: String,
+    pub job_id: Option<String>,
+    pub progress: f32,
+}
+
+/// Queue kept in Tauri state
+#[derive(Default)]
+pub struct JobQueue {
+    jobs: Mutex<Vec<QueuedJob>>,
+    wake: Notify,
+    worker_started: Mutex<bool>,
+    /// Held by the pipeline run in progress
+    run_slot: AsyncMutex<()>,
+}
+
+impl JobQueue {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Vec<QueuedJob>>> {
+        self.jobs.lock().map_err(|_| anyhow!("Job queue is poisoned"))
+    }
+
+    /// Add a request at the end of the queue
+    pub fn enqueue(&self, request: JobRequest) -> Result<QueuedJob> {
+        let job = QueuedJob {
+            id: format!("q{}-{}", now_secs(), QUEUE_COUNTER.fetch_add(1, Ordering::Relaxed)),
+            request,
+            status: QueueStatus::Queued,
+            job_id: None,
+            progress: 0.0,
+            final_path: None,
+            error: None,
+            enqueued_at: now_secs(),
+        };
+        self.lock()?.push(job.clone());
+        self.wake.notify_one();
+        Ok(job)
+    }
+
+    /// All entries in queue order
+    pub fn list(&self) -> Vec<QueuedJob> {
+        self.lock().map(|jobs| jobs.clone()).unwrap_or_default()
+    }
+
+    /// Take an entry out of the line. Returns the pipeline job id of a running
+    /// entry, which the caller has to stop.
+    pub fn cancel(&self, id: &str) -> Result<Option<String>> {
+        let mut jobs = self.lock()?;
+        let job = jobs.iter_mut().find(|job| job.id == id).ok_or_else(|| anyhow!("Unknown queue entry {}", id))?;
+        match job.status {
+            QueueStatus::Queued => {
+                job.status = QueueStatus::Cancelled;
+                Ok(None)
+            }
+            QueueStatus::Running => {
+                let job_id = job.job_id.clone().ok_or_else(|| anyhow!("Queue entry {} is still starting", id))?;
+                job.status = QueueStatus::Cancelled;
+                Ok(Some(job_id))
+            }
+            _ => Err(anyhow!("Queue entry {} has already finished", id)),
+        }
+    }
+
+    /// Put the queued entries in the given order. Entries not listed keep their
+    /// relative order after the listed ones; started entries don't move.
+    pub fn reorder(&self, ids: &[String]) -> Result<()> {
+        let mut jobs = self.lock()?;
+        if let Some(unknown) = ids.iter().find(|id| !jobs.iter().any(|job| &job.id == *id)) {
+            return Err(anyhow!("Unknown queue entry {}", unknown));
+        }
+        let (mut queued, started): (Vec<QueuedJob>, Vec<QueuedJob>) =
+            jobs.drain(..).partition(|job| job.status == QueueStatus::Queued);
+        // Stable sort: listed entries by their position, the rest after them
+        queued.sort_by_key(|job| ids.iter().position(|id| *id == job.id).unwrap_or(usize::MAX));
+        jobs.extend(started);
+        jobs.extend(queued);
+        Ok(())
+    }
+
+    /// Mark the next queued entry as running and return it
+    pub fn start_next(&self) -> Option<QueuedJob> {
+        let mut jobs = self.lock().ok()?;
+        let job = jobs.iter_mut().find(|job| job.status == QueueStatus::Queued)?;
+        job.status = QueueStatus::Running;
+        Some(job.clone())
+    }
+
+    /// Wait until something is enqueued
+    pub async fn wait(&self) {
+        self.wake.notified().await;
+    }
+
+    pub fn set_job_id(&self, id: &str, job_id: &str) {
+        if let Ok(mut jobs) = self.lock()
+            && let Some(job) = jobs.iter_mut().find(|job| job.id == id)
+        {
+            job.job_id = Some(job_id.to_string());
+        }
+    }
+
+    /// Update the progress of a running entry and return it for the event
+    pub fn set_progress(&self, id: &str, progress: f32) -> Option<QueueJobProgress> {
+        let mut jobs = self.lock().ok()?;
+        let job = jobs.iter_mut().find(|job| job.id == id)?;
+        job.progress = progress;
+        Some(QueueJobProgress { id: job.id.clone(), job_id: job.job_id.clone(), progress })
+    }
+
+    /// Record the outcome of a run
+    pub fn finish(&self, id: &str, result: Result<String, String>) {
+        let Ok(mut jobs) = self.lock() else { return };
+        let Some(job) = jobs.iter_mut().find(|job| job.id == id) else { return };
+        match result {
+            Ok(final_path) => {
+                job.status = QueueStatus::Completed;
+                job.progress = 100.0;
+                job.final_path = Some(final_path);
+            }
+            // A cancelled entry stays cancelled, whatever its run reports on the way out
+            Err(e) => {
+                if job.status != QueueStatus::Cancelled {
+                    job.status = QueueStatus::Failed;
+                }
+                job.error = Some(e);
+            }
+        }
+        // The key isn't needed anymore
+        job.request.api_key.clear();
+    }
+
+    /// Wait until no other pipeline runs; the run holds the guard until it ends
+    pub async fn take_run_slot(&self, job_id: &str) -> AsyncMutexGuard<'_, ()> {
+        if let Ok(guard) = self.run_slot.try_lock() {
+            return guard;
+        }
+        info!("Job {} waits for the running job to finish", job_id);
+        self.run_slot.lock().await
+    }
+
+    /// Whether the worker still has to be started; true only for the first caller
+    pub fn claim_worker(&self) -> bool {
+        let Ok(mut started) = self.worker_started.lock() else { return false };
+        !std::mem::replace(&mut *started, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(url: &str) -> JobRequest {
+        serde_json::from_value(serde_json::json!({
+            "url": url,
+            "output_path": "/tmp",
+            "target_language": "ru",
+            "target_language_name": "Russian",
+            "source_language_code": "en",
+            "source_language_name": "English",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn entries_run_in_the_reordered_order() {
+        let queue = JobQueue::default();
+        let a = queue.enqueue(request("a")).unwrap();
+        let b = queue.enqueue(request("b")).unwrap();
+        let c = queue.enqueue(request("c")).unwrap();
+
+        assert_eq!(queue.start_next().unwrap().id, a.id);
+        queue.reorder(std::slice::from_ref(&c.id)).unwrap();
+        assert!(queue.reorder(&["missing".to_string()]).is_err());
+        let order: Vec<String> = queue.list().into_iter().map(|job| job.id).collect();
+        assert_eq!(order, vec![a.id.clone(), c.id.clone(), b.id.clone()]);
+
+        assert_eq!(queue.cancel(&c.id).unwrap(), None);
+        assert!(queue.cancel(&a.id).is_err());
+        queue.set_job_id(&a.id, "job-a");
+        assert_eq!(queue.cancel(&a.id).unwrap().as_deref(), Some("job-a"));
+        queue.finish(&a.id, Err("Processing was cancelled".to_string()));
+        assert_eq!(queue.list()[0].status, QueueStatus::Cancelled);
+        assert_eq!(queue.start_next().unwrap().id, b.id);
+        assert!(queue.start_next().is_none());
+    }
+
+    #[test]
+    fn requests_are_validated_per_field() {
+        let mut job = request("not a link");
+        job.target_language = "xx".to_string();
+        let Err(CommandError::Validation { errors }) = job.validate() else { panic!("expected validation errors") };
+        let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+        assert_eq!(fields, vec!["url", "target_language"]);
+
+        let mut job = request("https://youtu.be/abc");
+        assert!(job.validate().is_ok());
+
+        let german = TemplateLanguage { code: "de".to_string(), name: "German".to_string(), voice: None };
+        job.extra_languages = vec![german.clone()];
+        assert!(job.validate().is_ok());
+        job.extra_languages.push(german);
+        let Err(CommandError::Validation { errors }) = job.validate() else { panic!("expected validation errors") };
+        assert_eq!(errors[0].field, "extra_languages");
+    }
+
+    #[test]
+    fn api_key_is_not_serialized() {
+        let mut job = request("a");
+        job.api_key = "sk-secret".to_string();
+        assert!(!serde_json::to_string(&job).unwrap().contains("sk-secret"));
+    }
+}
@@ -0,0 +1,199 @@
+//! Pausing jobs that ran out of OpenAI quota.
+//!
+//! When the account hits its hard quota mid-job, failing would throw away the
+//! download, the transcription and every TTS fragment generated so far. The
+//! job is parked in `WaitingForQuota` instead: the artifacts stay in the temp
+//! directory, their paths are written to `quota_pause.json` in the job
+//! directory, and the pipeline waits until the user resumes it (optionally
+//! with another API key) or abandons it. Pause records left by an app that
+//! was closed meanwhile are loaded at startup; those jobs are resumed from
+//! their pipeline checkpoint.
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+use crate::utils::common::now_secs;
+use crate::utils::job_state::{self, JobState};
+use crate::utils::jobs;
+
+const PAUSE_FILE: &str = "quota_pause.json";
+
+/// A job waiting for the quota to be resolved
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PausedJob {
+    pub job_id: String,
+    pub url: String,
+    /// Stage that ran out of quota and is retried on resume
+    pub stage: JobState,
+    pub error: String,
+    pub paused_at: u64, // Unix timestamp in seconds
+    /// Intermediate files kept for the resumed run
+    pub artifacts: Vec<PathBuf>,
+}
+
+/// What the user decided about a paused job
+#[derive(Debug)]
+pub enum ResumeDecision {
+    /// Retry the stage, with a replacement API key if given
+    Resume { api_key: Option<String> },
+    Abandon,
+}
+
+/// A paused job and where its pipeline waits for the decision
+type Waiting = (PausedJob, oneshot::Sender<ResumeDecision>);
+
+static PAUSED: Lazy<Mutex<HashMap<String, Waiting>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Jobs that were waiting for quota when the app was closed; no pipeline waits for them
+static RESTORED: Lazy<Mutex<HashMap<String, PausedJob>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether an API error means the account is out of quota or credit, as
+/// opposed to a short-lived rate limit that is worth retrying right away
+pub fn is_quota_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    ["insufficient_quota", "exceeded your current quota", "billing_hard_limit_reached", "billing hard limit"]
+        .iter()
+        .any(|needle| error.contains(needle))
+}
+
+async fn save_pause_record(app_handle: &tauri::AppHandle, paused: &PausedJob) -> Result<()> {
+    let dir = jobs::job_dir(app_handle, &paused.job_id)?;
+    tokio::fs::create_dir_all(&dir).await?;
+    let json = serde_json::to_string_pretty(paused).map_err(|e| anyhow!("Failed to serialize pause record: {}", e))?;
+    tokio::fs::write(dir.join(PAUSE_FILE), json).await?;
+    Ok(())
+}
+
+async fn remove_pause_record(app_handle: &tauri::AppHandle, job_id: &str) {
+    if let Ok(dir) = jobs::job_dir(app_handle, job_id) {
+        let _ = tokio::fs::remove_file(dir.join(PAUSE_FILE)).await;
+    }
+}
+
+/// Move the job to `WaitingForQuota` and wait for the user's decision. On
+/// resume the job goes back to the stage it paused in before this returns.
+pub async fn pause(
+    app_handle: &tauri::AppHandle,
+    job_id: &str,
+    url: &str,
+    stage: JobState,
+    error: &str,
+    artifacts: Vec<PathBuf>,
+) -> Result<ResumeDecision> {
+    job_state::transition(app_handle, job_id, JobState::WaitingForQuota, Some(error.to_string()))?;
+
+    let paused = PausedJob {
+        job_id: job_id.to_string(),
+        url: url.to_string(),
+        stage,
+        error: error.to_string(),
+        paused_at: now_secs(),
+        artifacts: artifacts.into_iter().filter(|path| path.exists()).collect(),
+    };
+    if let Err(e) = save_pause_record(app_handle, &paused).await {
+        warn!("Failed to persist pause record of job {}: {}", job_id, e);
+    }
+
+    let (tx, rx) = oneshot::channel();
+    PAUSED
+        .lock()
+        .map_err(|_| anyhow!("Paused job registry is poisoned"))?
+        .insert(job_id.to_string(), (paused, tx));
+    info!("Job {} paused in {:?}: OpenAI quota exhausted", job_id, stage);
+
+    // A dropped sender can only mean the registry entry vanished, treat it as abandoned
+    let decision = rx.await.unwrap_or(ResumeDecision::Abandon);
+    remove_pause_record(app_handle, job_id).await;
+    if let ResumeDecision::Resume { .. } = decision {
+        info!("Resuming job {} in {:?}", job_id, stage);
+        job_state::transition(app_handle, job_id, stage, None)?;
+    }
+    Ok(decision)
+}
+
+fn decide(job_id: &str, decision: ResumeDecision) -> Result<()> {
+    let (_, tx) = PAUSED
+        .lock()
+        .map_err(|_| anyhow!("Paused job registry is poisoned"))?
+        .remove(job_id)
+        .ok_or_else(|| anyhow!("Job {} is not waiting for quota", job_id))?;
+    tx.send(decision).map_err(|_| anyhow!("Job {} is no longer running", job_id))
+}
+
+/// Retry the paused stage, optionally with another API key
+pub fn resume(job_id: &str, api_key: Option<String>) -> Result<()> {
+    decide(job_id, ResumeDecision::Resume { api_key: api_key.filter(|key| !key.trim().is_empty()) })
+}
+
+/// Give up on a paused job, it fails with the quota error
+pub fn abandon(job_id: &str) -> Result<()> {
+    decide(job_id, ResumeDecision::Abandon)
+}
+
+/// Jobs currently waiting for quota, including those restored at startup
+pub fn paused_jobs() -> Vec<PausedJob> {
+    let mut jobs = match PAUSED.lock() {
+        Ok(paused) => paused.values().map(|(job, _)| job.clone()).collect(),
+        Err(_) => Vec::new(),
+    };
+    if let Ok(restored) = RESTORED.lock() {
+        jobs.extend(restored.values().cloned());
+    }
+    jobs
+}
+
+/// Load the pause records of jobs that were waiting for quota when the app was closed
+pub async fn restore(app_handle: &tauri::AppHandle) {
+    let Ok(root) = jobs::jobs_root(app_handle) else { return };
+    let Ok(mut entries) = tokio::fs::read_dir(&root).await else { return };
+    let mut found = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let Ok(content) = tokio::fs::read_to_string(entry.path().join(PAUSE_FILE)).await else { continue };
+        match serde_json::from_str::<PausedJob>(&content) {
+            Ok(paused) => found.push(paused),
+            Err(e) => warn!("Skipping corrupted pause record in {}: {}", entry.path().display(), e),
+        }
+    }
+    if found.is_empty() {
+        return;
+    }
+    info!("{} job(s) were waiting for OpenAI quota when the app was closed", found.len());
+    if let Ok(mut restored) = RESTORED.lock() {
+        restored.extend(found.into_iter().map(|paused| (paused.job_id.clone(), paused)));
+    }
+}
+
+/// Whether the job was restored from its pause record, rather than waiting in a running pipeline
+pub fn is_restored(job_id: &str) -> bool {
+    RESTORED.lock().is_ok_and(|restored| restored.contains_key(job_id))
+}
+
+/// Drop a restored job and its pause record once it is resumed or abandoned
+pub async fn forget_restored(app_handle: &tauri::AppHandle, job_id: &str) -> Option<PausedJob> {
+    let paused = RESTORED.lock().ok()?.remove(job_id)?;
+    remove_pause_record(app_handle, job_id).await;
+    Some(paused)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_quota_error() {
+        let quota = r#"OpenAI API error: {"error": {"message": "You exceeded your current quota, please check your plan and billing details.", "type": "insufficient_quota", "code": "insufficient_quota"}}"#;
+        assert!(is_quota_error(quota));
+        assert!(is_quota_error("Ошибка API (код 429 Too Many Requests): billing_hard_limit_reached"));
+
+        let rate_limit = r#"API request failed (HTTP 429 Too Many Requests): {"error": {"message": "Rate limit reached for requests", "type": "requests", "code": "rate_limit_exceeded"}}"#;
+        assert!(!is_quota_error(rate_limit));
+        assert!(!is_quota_error("Failed to connect to OpenAI API: timed out"));
+    }
+}
@@ -0,0 +1,80 @@
+//! Token bucket for speech requests.
+//!
+//! The bucket holds up to `burst` tokens and refills at a steady rate. Every
+//! request takes a token and waits for the next one when the bucket is empty,
+//! so a burst goes out at once while the long-run rate stays under the
+//! account's requests-per-minute limit. Waiters are served in arrival order.
+
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl BucketState {
+    /// Take a token at `now`, or tell how long until one is available
+    fn take(&mut self, now: Instant, burst: f64, per_second: f64) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(burst);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / per_second))
+        }
+    }
+}
+
+pub struct TokenBucket {
+    burst: f64,
+    per_second: f64,
+    state: Mutex<BucketState>,
+}
+
+impl TokenBucket {
+    /// `requests` per minute with bursts of up to `burst` requests; starts full
+    pub fn per_minute(requests: u32, burst: usize) -> Self {
+        let burst = burst.max(1) as f64;
+        Self {
+            burst,
+            per_second: requests.max(1) as f64 / 60.0,
+            state: Mutex::new(BucketState { tokens: burst, updated: Instant::now() }),
+        }
+    }
+
+    /// Wait for a token
+    pub async fn acquire(&self) {
+        // The lock is held while waiting, so later callers queue up behind
+        let mut state = self.state.lock().await;
+        while let Err(wait) = state.take(Instant::now(), self.burst, self.per_second) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_goes_out_then_the_rate_applies() {
+        let start = Instant::now();
+        let mut state = BucketState { tokens: 2.0, updated: start };
+        // 60 requests per minute, one token a second
+        assert!(state.take(start, 2.0, 1.0).is_ok());
+        assert!(state.take(start, 2.0, 1.0).is_ok());
+        let wait = state.take(start, 2.0, 1.0).unwrap_err();
+        assert!((wait.as_secs_f64() - 1.0).abs() < 1e-6);
+
+        assert!(state.take(start + Duration::from_millis(1500), 2.0, 1.0).is_ok());
+        // A long pause doesn't save up more than the burst
+        let later = start + Duration::from_secs(60);
+        assert!(state.take(later, 2.0, 1.0).is_ok());
+        assert!(state.take(later, 2.0, 1.0).is_ok());
+        assert!(state.take(later, 2.0, 1.0).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+//! Re-synthesis of a single cue of a finished job.
+//!
+//! A badly pronounced sentence shouldn't cost a whole new run. The job record
+//! keeps the fitted TTS fragment of every cue and the rendered dub
+//! (`final_output_copy.wav`), so one cue can be spoken again, fitted into the
+//! footprint of its old fragment and swapped into the dub in place. Nothing
+//! else on the timeline moves.
+//!
+//! The dub is a mix: the voice bus is ridden against the background and the
+//! whole is normalized, so the old fragment can't just be cut out. Its gain in
+//! the dub is estimated per block by projecting the dub onto it (the
+//! background doesn't correlate with the speech), the old fragment is taken
+//! out with that gain and the new one put in with the same. Finally the
+//! dubbed track of the merged video is replaced.
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::utils::jobs;
+use crate::utils::merge;
+use crate::utils::settings;
+use crate::utils::tts::normalize::{self, NormalizationOptions, Normalizer};
+use crate::utils::tts::tts::audio;
+use crate::utils::tts::tts::lexicon::{Lexicon, LexiconEntry};
+use crate::utils::tts::tts::provider::{self, EngineParams, SpeechProvider, SpeechRequest};
+use crate::utils::tts::tts::synchronizer::chunk_name;
+use crate::utils::tts::tts::timestretch::StretchSettings;
+use crate::utils::tts::tts::{vtt, AudioProcessingConfig, TtsConfig};
+
+/// Rendered dub the synchronizer leaves among the fragments
+const DUB_FILE: &str = "final_output_copy.wav";
+/// Voice bus before mixing, where fragments sit unchanged
+const VOICE_FILE: &str = "merged_raw.wav";
+/// Length of the blocks the gain of the old fragment is estimated in, follows
+/// the 400 ms windows of the voice riding
+const GAIN_BLOCK_SECS: f32 = 0.4;
+/// How far from the cue start the old fragment is looked for (retiming and
+/// lip sync move fragments)
+const SEARCH_SECS: f32 = 2.0;
+/// Samples compared when looking for the old fragment
+const PROBE_LEN: usize = 2048;
+
+/// What a regeneration changed
+#[derive(Debug, Serialize, Clone)]
+pub struct RegeneratedSegment {
+    pub cue_index: usize,
+    pub text: String,
+    pub voice: String,
+    /// The new fitted fragment
+    pub fragment: PathBuf,
+    /// The merged video with the patched dub, None when the job has no video
+    pub final_path: Option<PathBuf>,
+}
+
+/// Interleaved samples, sample rate and channel count of a WAV file
+fn read_wav(path: &Path) -> Result<(Vec<f32>, u32, u16)> {
+    let mut reader = hound::WavReader::open(path).map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<std::result::Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|s| s as f32 / scale))
+                .collect::<std::result::Result<Vec<_>, _>>()?
+        }
+    };
+    Ok((samples, spec.sample_rate, spec.channels))
+}
+
+/// Start in seconds of `fragment` (mono, at `rate`) in the interleaved voice
+/// bus, looked for around `expected`
+fn locate(voice: &[f32], channels: usize, rate: u32, fragment: &[f32], expected: f32) -> Option<f32> {
+    let onset = fragment.iter().position(|s| s.abs() > 0.01)?;
+    let probe = &fragment[onset..(onset + PROBE_LEN).min(fragment.len())];
+    let probe_energy: f32 = probe.iter().map(|s| s * s).sum();
+    let frames = voice.len() / channels.max(1);
+    // Channels are summed, a panned fragment is still the same shape
+    let mono = |frame: usize| voice[frame * channels..(frame + 1) * channels].iter().sum::<f32>();
+
+    let search = (SEARCH_SECS * rate as f32) as isize;
+    let expected = (expected * rate as f32) as isize;
+    let mut best = (f32::MIN, 0usize);
+    for start in (expected - search).max(0)..=expected + search {
+        let from = start as usize + onset;
+        if from + probe.len() > frames {
+            break;
+        }
+        let (mut correlation, mut energy) = (0.0f32, 0.0f32);
+        for (i, &p) in probe.iter().enumerate() {
+            let v = mono(from + i);
+            correlation += p * v;
+            energy += v * v;
+        }
+        let score = correlation / (energy * probe_energy).sqrt().max(1e-9);
+        if score > best.0 {
+            best = (score, start as usize);
+        }
+    }
+    (best.0 > 0.9).then(|| best.1 as f32 / rate as f32)
+}
+
+/// Swap `old` for `new` (mono, same length) in the interleaved `dub` from frame
+/// `start`, with the gain of `old` in each channel estimated per block of
+/// `block` frames. Returns false when the old fragment isn't audible in the dub.
+fn swap_fragment(dub: &mut [f32], channels: usize, start: usize, old: &[f32], new: &[f32], block: usize) -> bool {
+    let frames = (dub.len() / channels).saturating_sub(start).min(old.len()).min(new.len());
+    let blocks: Vec<(usize, usize)> = (0..frames).step_by(block.max(1)).map(|from| (from, (from + block).min(frames))).collect();
+    let mut patched = false;
+    for channel in 0..channels {
+        let sample = |frame: usize| (start + frame) * channels + channel;
+        let mut gains: Vec<Option<f32>> = blocks
+            .iter()
+            .map(|&(from, to)| {
+                let energy: f32 = old[from..to].iter().map(|s| s * s).sum();
+                let projection: f32 = (from..to).map(|frame| dub[sample(frame)] * old[frame]).sum();
+                // Blocks the old fragment is silent in say nothing about its gain
+                (energy > 1e-4).then(|| (projection / energy).max(0.0))
+            })
+            .collect();
+        // Silent blocks take the gain of the nearest block with speech, the new
+        // fragment may speak where the old one didn't
+        let mut last = None;
+        for gain in gains.iter_mut() {
+            *gain = gain.or(last);
+            last = *gain;
+        }
+        let mut next = None;
+        for gain in gains.iter_mut().rev() {
+            *gain = gain.or(next);
+            next = *gain;
+        }
+
+        for (&(from, to), gain) in blocks.iter().zip(&gains) {
+            let Some(gain) = gain else { continue };
+            for frame in from..to {
+                let index = sample(frame);
+                dub[index] = (dub[index] + gain * (new[frame] - old[frame])).clamp(-1.0, 1.0);
+            }
+            patched = true;
+        }
+    }
+    patched
+}
+
+/// A cue to speak again and what changes about it
+pub struct SegmentEdit {
+    pub cue_index: usize,
+    /// Spoken instead of the translation if given
+    pub edited_text: Option<String>,
+    /// Spoken with instead of the job's voice if given
+    pub voice_override: Option<String>,
+}
+
+/// Engine and text processing the cue is spoken with
+pub struct SpeechSettings<'a> {
+    pub api_key: &'a str,
+    pub engine: &'a str,
+    pub stretch: StretchSettings,
+    pub lexicon: &'a [LexiconEntry],
+    pub normalization: &'a HashMap<String, NormalizationOptions>,
+}
+
+/// Speak a cue of a finished job again as `edit` asks and patch the result
+/// into the dub and the merged video
+pub async fn regenerate_segment(
+    app_handle: &tauri::AppHandle,
+    job_id: &str,
+    edit: SegmentEdit,
+    speech: SpeechSettings<'_>,
+) -> Result<RegeneratedSegment> {
+    let SegmentEdit { cue_index, edited_text, voice_override } = edit;
+    let SpeechSettings { api_key, engine, stretch, lexicon, normalization } = speech;
+    let record = jobs::load(app_handle, job_id).await?;
+    let fragments_dir = record
+        .fragments_dir
+        .clone()
+        .ok_or_else(|| anyhow!("Job {} kept no TTS fragments to regenerate", job_id))?;
+    let mut cues = vtt::parse_vtt(&record.translated_vtt_path).map_err(|e| anyhow!("{}", e))?;
+    let cue = cues
+        .get(cue_index)
+        .cloned()
+        .ok_or_else(|| anyhow!("Cue {} out of range (job has {} cues)", cue_index, cues.len()))?;
+    let old_path = jobs::find_fragment(&fragments_dir, cue_index)
+        .await
+        .ok_or_else(|| anyhow!("Job {} has no fragment for cue {}", job_id, cue_index))?;
+
+    let text = edited_text.map(|text| text.trim().to_string()).filter(|text| !text.is_empty()).unwrap_or(cue.text.clone());
+    let capabilities = provider::registry().get(engine).ok_or_else(|| anyhow!("Unknown speech engine: {}", engine))?;
+    let voice = voice_override.or(record.voice.clone()).unwrap_or(capabilities.default_voice.clone());
+    info!("Regenerating cue {} of job {} with {} / {}: {}", cue_index, job_id, engine, voice, text);
+
+    let params = EngineParams { api_key, config: TtsConfig { voice: voice.clone(), ..TtsConfig::default() }, target_language: None };
+    let speech_provider = provider::registry().create(engine, &params).map_err(|e| anyhow!("{}", e))?;
+    let language = Some(record.target_language.as_str());
+    let lexicon = Lexicon::for_language(lexicon, language);
+    let normalizer = Normalizer::new(language, normalize::options_for(normalization, language));
+    let speech_provider = if lexicon.is_empty() && !normalizer.is_active() {
+        speech_provider
+    } else {
+        Arc::new(provider::Pronounced::new(speech_provider, Arc::new(lexicon), Arc::new(normalizer))) as Arc<dyn SpeechProvider>
+    };
+    let request = SpeechRequest { text: &text, source_audio: None, speaker: cue.speaker.as_deref(), on_chunk: None };
+    let (mp3, _) = speech_provider.synthesize(&request).await.map_err(|e| anyhow!("{}", e))?;
+
+    // The new speech is fitted into the samples of the old fragment, so no other cue moves
+    let (old, old_rate, _) = read_wav(&old_path)?;
+    let (pcm, rate) = audio::decode_mp3(&mp3).map_err(|e| anyhow!("{}", e))?;
+    let pcm = audio::resample(&pcm, 1, rate, old_rate).map_err(|e| anyhow!("{}", e))?;
+    let config = AudioProcessingConfig { stretch, ..AudioProcessingConfig::default() };
+    let actual = audio::duration_in_seconds(pcm.len(), old_rate);
+    let target = audio::duration_in_seconds(old.len(), old_rate);
+    let (mut new, used) =
+        audio::adjust_duration(&pcm, actual, target, 0.0, old_rate, &config).map_err(|e| anyhow!("{}", e))?;
+    if used > target + 0.01 {
+        warn!("Cue {} needs {:.2}s but has {:.2}s, its end is cut", cue_index, used, target);
+    }
+    new.resize(old.len(), 0.0);
+
+    // Where the old fragment sits: exact in the voice bus, the cue start otherwise
+    let start_secs = match read_wav(&fragments_dir.join(VOICE_FILE)) {
+        Ok((voice_bus, voice_rate, channels)) if voice_rate == old_rate => {
+            locate(&voice_bus, channels as usize, voice_rate, &old, cue.start).unwrap_or(cue.start)
+        }
+        _ => cue.start,
+    };
+
+    let dub_path = fragments_dir.join(DUB_FILE);
+    let (mut dub, dub_rate, channels) = read_wav(&dub_path)?;
+    let old_in_dub = audio::resample(&old, 1, old_rate, dub_rate).map_err(|e| anyhow!("{}", e))?;
+    let new_in_dub = audio::resample(&new, 1, old_rate, dub_rate).map_err(|e| anyhow!("{}", e))?;
+    let block = (GAIN_BLOCK_SECS * dub_rate as f32) as usize;
+    let start = (start_secs * dub_rate as f32).round() as usize;
+    if !swap_fragment(&mut dub, channels as usize, start, &old_in_dub, &new_in_dub, block) {
+        return Err(anyhow!("The old speech of cue {} can't be found in the dub", cue_index));
+    }
+
+    // Fragment files of the cue are replaced, the inspector finds the new one by index
+    let mut entries = tokio::fs::read_dir(&fragments_dir).await?;
+    let prefix = format!("chunk_{:03}_", cue_index);
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            tokio::fs::remove_file(entry.path()).await?;
+        }
+    }
+    let name = chunk_name(cue_index, &text);
+    tokio::fs::write(fragments_dir.join(format!("{}.mp3", name)), &mp3).await?;
+    let fragment = fragments_dir.join(format!("{}_adjusted.wav", name));
+    audio::encode_wav(&new, old_rate, &fragment.to_string_lossy()).map_err(|e| anyhow!("{}", e))?;
+    audio::encode_wav_channels(&dub, dub_rate, channels, &dub_path.to_string_lossy()).map_err(|e| anyhow!("{}", e))?;
+
+    if text != cue.text {
+        cues[cue_index].text = text.clone();
+        tokio::fs::write(&record.translated_vtt_path, vtt::write_vtt_str(&cues)).await?;
+    }
+
+    let final_path = match record.final_path.as_deref().filter(|path| path.exists()) {
+        Some(video) => {
+            let extension = video.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_else(|| "mp4".to_string());
+            let patched = video.with_extension(format!("regenerated.{}", extension));
+            let style: merge::MergeStyle = settings::get(app_handle, merge::MERGE_STYLE_KEY).unwrap_or_default();
+            merge::replace_dubbed_audio(video, &dub_path, &patched, style.layout.voice_over_volume).await?;
+            tokio::fs::rename(&patched, video).await?;
+            Some(video.to_path_buf())
+        }
+        None => None,
+    };
+    info!("Cue {} of job {} regenerated", cue_index, job_id);
+
+    Ok(RegeneratedSegment { cue_index, text, voice, fragment, final_path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fragments_are_swapped_with_the_gain_they_have_in_the_dub() {
+        let rate = 8_000;
+        // Rising tones, a steady one would match itself a period later
+        let tone = |frequency: f32, len: usize| -> Vec<f32> {
+            (0..len)
+                .map(|i| {
+                    let t = i as f32 / rate as f32;
+                    (2.0 * std::f32::consts::PI * (frequency + 100.0 * t) * t).sin() * 0.5
+                })
+                .collect()
+        };
+        let old = tone(300.0, 4_000);
+        let new = tone(450.0, 4_000);
+        let background = tone(55.0, 16_000).iter().map(|s| s * 0.2).collect::<Vec<f32>>();
+
+        // Stereo dub: the old fragment at 1 s, quieter on the right
+        let mut dub: Vec<f32> = background.iter().flat_map(|&s| [s, s]).collect();
+        for (i, &s) in old.iter().enumerate() {
+            dub[(rate as usize + i) * 2] += 0.8 * s;
+            dub[(rate as usize + i) * 2 + 1] += 0.4 * s;
+        }
+        let voice: Vec<f32> = (0..16_000).map(|i| if (8_000..12_000).contains(&i) { old[i - 8_000] } else { 0.0 }).collect();
+        assert_eq!(locate(&voice, 1, rate, &old, 1.2), Some(1.0));
+
+        assert!(swap_fragment(&mut dub, 2, rate as usize, &old, &new, 3_200));
+        for (i, &s) in new.iter().enumerate() {
+            let frame = rate as usize + i;
+            assert!((dub[frame * 2] - (background[frame] + 0.8 * s)).abs() < 0.02);
+            assert!((dub[frame * 2 + 1] - (background[frame] + 0.4 * s)).abs() < 0.02);
+        }
+        assert!(!swap_fragment(&mut dub, 2, 0, &vec![0.0; 100], &new[..100], 50));
+    }
+}
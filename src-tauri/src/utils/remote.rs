@@ -0,0 +1,484 @@
+//! Upload of finished outputs to a remote destination.
+//!
+//! Two kinds of destinations are supported: S3-compatible object storage
+//! (AWS, MinIO, R2, ...) using multipart uploads signed with AWS Signature V4,
+//! and WebDAV servers using plain PUT requests. The destination is kept in the
+//! settings store so headless setups can configure it once.
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tokio_util::io::ReaderStream;
+
+use crate::utils::settings;
+
+const SETTINGS_KEY: &str = "remote-destination";
+const MAX_ATTEMPTS: u32 = 3;
+
+// S3 requires every part except the last one to be at least 5 MiB
+const S3_PART_SIZE: usize = 8 * 1024 * 1024;
+/// Read size when a file is streamed or hashed
+const STREAM_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Where finished outputs should be uploaded
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum RemoteDestination {
+    S3 {
+        endpoint: String,
+        bucket: String,
+        region: String,
+        access_key: String,
+        secret_key: String,
+        #[serde(default)]
+        prefix: String,
+    },
+    WebDav {
+        url: String,
+        username: Option<String>,
+        password: Option<String>,
+    },
+}
+
+/// Remote output settings
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteConfig {
+    pub destination: RemoteDestination,
+    /// Also upload the subtitle files next to the video
+    #[serde(default)]
+    pub include_subtitles: bool,
+}
+
+/// Upload progress of a single file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteUploadProgress {
+    pub status: String,
+    pub file_name: String,
+    pub progress: f32,
+}
+
+/// Load the remote destination from the settings store
+pub fn load_config(app_handle: &tauri::AppHandle) -> Result<Option<RemoteConfig>> {
+    match settings::get_value(app_handle, SETTINGS_KEY) {
+        Some(value) if !value.is_null() => Ok(Some(
+            serde_json::from_value(value).map_err(|e| anyhow!("Invalid remote destination settings: {}", e))?,
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// Save (or clear, when `None`) the remote destination in the settings store
+pub async fn save_config(app_handle: &tauri::AppHandle, config: Option<&RemoteConfig>) -> Result<()> {
+    match config {
+        Some(config) => settings::set(app_handle, SETTINGS_KEY, config).await,
+        None => settings::remove(app_handle, SETTINGS_KEY).await,
+    }
+}
+
+/// Upload a file to the destination and return its remote url
+pub async fn upload_file(
+    destination: &RemoteDestination,
+    file_path: &Path,
+    progress_sender: Option<mpsc::Sender<RemoteUploadProgress>>,
+) -> Result<String> {
+    let file_name = file_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow!("Invalid file path: {}", file_path.display()))?;
+    info!("Uploading {} to remote destination", file_path.display());
+
+    let reporter = ProgressReporter { sender: progress_sender, file_name: file_name.clone() };
+    reporter.send("Starting upload", 0.0).await;
+
+    let url = match destination {
+        RemoteDestination::S3 { .. } => s3_upload(destination, file_path, &file_name, &reporter).await?,
+        RemoteDestination::WebDav { url, username, password } => {
+            webdav_upload(url, username.as_deref(), password.as_deref(), file_path, &file_name, &reporter).await?
+        }
+    };
+
+    reporter.send("Upload complete", 100.0).await;
+    info!("Uploaded {} to {}", file_name, url);
+    Ok(url)
+}
+
+#[derive(Clone)]
+struct ProgressReporter {
+    sender: Option<mpsc::Sender<RemoteUploadProgress>>,
+    file_name: String,
+}
+
+impl ProgressReporter {
+    async fn send(&self, status: &str, progress: f32) {
+        if let Some(tx) = &self.sender {
+            let _ = tx
+                .send(RemoteUploadProgress {
+                    status: status.to_string(),
+                    file_name: self.file_name.clone(),
+                    progress,
+                })
+                .await;
+        }
+    }
+}
+
+/// Run a request up to `MAX_ATTEMPTS` times with exponential backoff
+async fn with_retry<T, F, Fut>(what: &str, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < MAX_ATTEMPTS => {
+                let delay = Duration::from_secs(2u64.pow(attempt));
+                warn!("{} failed (attempt {}/{}): {}, retrying in {:?}", what, attempt, MAX_ATTEMPTS, e, delay);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(anyhow!("{} failed after {} attempts: {}", what, MAX_ATTEMPTS, e)),
+        }
+    }
+}
+
+/// Body streaming the file from disk, reporting the share sent in whole percents
+async fn file_body(file_path: &Path, total_bytes: u64, reporter: &ProgressReporter) -> Result<reqwest::Body> {
+    let file = tokio::fs::File::open(file_path).await?;
+    let reporter = reporter.clone();
+    let mut sent: u64 = 0;
+    let mut reported = 0u64;
+    let stream = ReaderStream::with_capacity(file, STREAM_CHUNK_SIZE).then(move |chunk| {
+        if let Ok(bytes) = &chunk {
+            sent += bytes.len() as u64;
+        }
+        let percent = (sent * 100).checked_div(total_bytes).unwrap_or(100);
+        let report = (percent > reported).then(|| {
+            reported = percent;
+            (reporter.clone(), percent)
+        });
+        async move {
+            if let Some((reporter, percent)) = report {
+                reporter.send("Uploading", percent as f32).await;
+            }
+            chunk
+        }
+    });
+    Ok(reqwest::Body::wrap_stream(stream))
+}
+
+/// SHA-256 of a file, read in chunks
+async fn file_sha256(file_path: &Path) -> Result<String> {
+    let mut file = tokio::fs::File::open(file_path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; STREAM_CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+async fn check_response(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let error_text = response.text().await.unwrap_or_default();
+    Err(anyhow!("HTTP {}: {}", status, error_text))
+}
+
+// ---------------------------------------------------------------------------
+// WebDAV
+
+async fn webdav_upload(
+    base_url: &str,
+    username: Option<&str>,
+    password: Option<&str>,
+    file_path: &Path,
+    file_name: &str,
+    reporter: &ProgressReporter,
+) -> Result<String> {
+    let target = format!("{}/{}", base_url.trim_end_matches('/'), encode_path_segment(file_name));
+    let total_bytes = tokio::fs::metadata(file_path).await?.len();
+    let client = reqwest::Client::new();
+
+    // Every attempt streams the file from the start
+    with_retry("WebDAV upload", || {
+        let mut request = client.put(&target).header(reqwest::header::CONTENT_LENGTH, total_bytes);
+        if let Some(user) = username {
+            request = request.basic_auth(user, password);
+        }
+        async move {
+            let body = file_body(file_path, total_bytes, reporter).await?;
+            check_response(request.body(body).send().await?).await?;
+            Ok(())
+        }
+    })
+    .await?;
+
+    Ok(target)
+}
+
+// ---------------------------------------------------------------------------
+// S3
+
+async fn s3_upload(
+    destination: &RemoteDestination,
+    file_path: &Path,
+    file_name: &str,
+    reporter: &ProgressReporter,
+) -> Result<String> {
+    let RemoteDestination::S3 { endpoint, bucket, region, access_key, secret_key, prefix } = destination else {
+        return Err(anyhow!("Not an S3 destination"));
+    };
+    let signer = S3Signer { region, access_key, secret_key };
+
+    let key = if prefix.is_empty() {
+        file_name.to_string()
+    } else {
+        format!("{}/{}", prefix.trim_matches('/'), file_name)
+    };
+    let object_url = format!(
+        "{}/{}/{}",
+        endpoint.trim_end_matches('/'),
+        bucket,
+        key.split('/').map(encode_path_segment).collect::<Vec<_>>().join("/")
+    );
+
+    let total_bytes = tokio::fs::metadata(file_path).await?.len();
+    let client = reqwest::Client::new();
+
+    // Small files don't need a multipart upload
+    if total_bytes <= S3_PART_SIZE as u64 {
+        let sha256 = file_sha256(file_path).await?;
+        with_retry("S3 upload", || async {
+            let body = file_body(file_path, total_bytes, reporter).await?;
+            let payload = SignedBody { body, sha256: sha256.clone(), length: total_bytes };
+            signer.send(&client, "PUT", &object_url, "", payload).await
+        })
+        .await?;
+        return Ok(object_url);
+    }
+
+    let response = with_retry("S3 multipart start", || {
+        signer.send(&client, "POST", &object_url, "uploads=", SignedBody::bytes(Bytes::new()))
+    })
+    .await?;
+    let body = response.text().await?;
+    let upload_id = extract_xml_value(&body, "UploadId")
+        .ok_or_else(|| anyhow!("S3 did not return an upload id"))?;
+    debug!("Started S3 multipart upload {}", upload_id);
+
+    match s3_upload_parts(&signer, &client, &object_url, &upload_id, file_path, total_bytes, reporter).await {
+        Ok(parts) => {
+            let manifest = parts
+                .iter()
+                .enumerate()
+                .map(|(i, etag)| format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", i + 1, etag))
+                .collect::<String>();
+            let xml = format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", manifest);
+            let query = format!("uploadId={}", encode_query_value(&upload_id));
+            let xml = Bytes::from(xml);
+            with_retry("S3 multipart complete", || {
+                signer.send(&client, "POST", &object_url, &query, SignedBody::bytes(xml.clone()))
+            })
+            .await?;
+            Ok(object_url)
+        }
+        Err(e) => {
+            // Don't leave orphaned parts behind, they are billed until removed
+            let query = format!("uploadId={}", encode_query_value(&upload_id));
+            if let Err(abort_error) = signer.send(&client, "DELETE", &object_url, &query, SignedBody::bytes(Bytes::new())).await {
+                warn!("Failed to abort S3 multipart upload {}: {}", upload_id, abort_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+async fn s3_upload_parts(
+    signer: &S3Signer<'_>,
+    client: &reqwest::Client,
+    object_url: &str,
+    upload_id: &str,
+    file_path: &Path,
+    total_bytes: u64,
+    reporter: &ProgressReporter,
+) -> Result<Vec<String>> {
+    let mut file = tokio::fs::File::open(file_path).await?;
+    let mut etags = Vec::new();
+    let mut sent: u64 = 0;
+    let mut part_number = 1;
+
+    loop {
+        let mut buffer = vec![0u8; S3_PART_SIZE];
+        let mut filled = 0;
+        while filled < buffer.len() {
+            let read = file.read(&mut buffer[filled..]).await?;
+            if read == 0 {
+                break;
+            }
+            filled += read;
+        }
+        if filled == 0 {
+            break;
+        }
+        buffer.truncate(filled);
+        // Retries share the part instead of copying it
+        let part = Bytes::from(buffer);
+
+        let query = format!("partNumber={}&uploadId={}", part_number, encode_query_value(upload_id));
+        let response = with_retry(&format!("S3 part {}", part_number), || {
+            signer.send(client, "PUT", object_url, &query, SignedBody::bytes(part.clone()))
+        })
+        .await?;
+        let etag = response
+            .headers()
+            .get("etag")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| anyhow!("S3 did not return an ETag for part {}", part_number))?
+            .to_string();
+        etags.push(etag);
+
+        sent += filled as u64;
+        part_number += 1;
+        reporter
+            .send("Uploading", sent as f32 / total_bytes as f32 * 100.0)
+            .await;
+    }
+
+    Ok(etags)
+}
+
+/// Request body with the SHA-256 the signature covers
+struct SignedBody {
+    body: reqwest::Body,
+    sha256: String,
+    length: u64,
+}
+
+impl SignedBody {
+    fn bytes(bytes: Bytes) -> Self {
+        Self { sha256: hex::encode(Sha256::digest(&bytes)), length: bytes.len() as u64, body: bytes.into() }
+    }
+}
+
+struct S3Signer<'a> {
+    region: &'a str,
+    access_key: &'a str,
+    secret_key: &'a str,
+}
+
+impl S3Signer<'_> {
+    /// Send a request signed with AWS Signature Version 4.
+    /// `query` must already be in canonical form (sorted, url-encoded).
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        method: &str,
+        url: &str,
+        query: &str,
+        payload: SignedBody,
+    ) -> Result<reqwest::Response> {
+        let parsed = url::Url::parse(url)?;
+        let host = match parsed.port() {
+            Some(port) => format!("{}:{}", parsed.host_str().unwrap_or_default(), port),
+            None => parsed.host_str().unwrap_or_default().to_string(),
+        };
+        let (amz_date, date) = amz_timestamp();
+        let SignedBody { body, sha256: payload_hash, length } = payload;
+
+        let canonical_headers = format!(
+            "host:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n",
+            host, payload_hash, amz_date
+        );
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method,
+            parsed.path(),
+            query,
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut key = hmac_sha256(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes());
+        for part in [self.region, "s3", "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+
+        let full_url = if query.is_empty() { url.to_string() } else { format!("{}?{}", url, query) };
+        let method = reqwest::Method::from_bytes(method.as_bytes())?;
+        let response = client
+            .request(method, full_url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("authorization", authorization)
+            .header(reqwest::header::CONTENT_LENGTH, length)
+            .body(body)
+            .send()
+            .await?;
+        check_response(response).await
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Current time as (`YYYYMMDDTHHMMSSZ`, `YYYYMMDD`)
+fn amz_timestamp() -> (String, String) {
+    let now = chrono::Utc::now();
+    (now.format("%Y%m%dT%H%M%SZ").to_string(), now.format("%Y%m%d").to_string())
+}
+
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn encode_query_value(value: &str) -> String {
+    encode_path_segment(value)
+}
+
+fn extract_xml_value(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&close)? + start;
+    Some(xml[start..end].to_string())
+}
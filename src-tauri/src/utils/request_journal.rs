@@ -0,0 +1,140 @@
+//! Recovery-safe journal of in-flight OpenAI requests.
+//!
+//! Long translations are submitted as background requests: OpenAI keeps
+//! working on them and the result can be fetched later by response id. The id
+//! is written to `openai_requests.json` in the job's temp directory before the
+//! app starts waiting for it, so when the app crashes or is closed mid-job, the
+//! next run of the same job finds the id, polls for the result that was
+//! already paid for and only submits requests that never got an id.
+//!
+//! Requests are matched across runs by a fingerprint of their body, which
+//! stays the same as long as the input and the options don't change.
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::utils::common::now_secs;
+
+pub const JOURNAL_FILE: &str = "openai_requests.json";
+
+/// A request OpenAI accepted but whose result hasn't been collected yet
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct InflightRequest {
+    /// What the request is for, e.g. "translation"
+    pub kind: String,
+    pub fingerprint: String,
+    pub response_id: String,
+    pub submitted_at: u64, // Unix timestamp in seconds
+}
+
+/// Journal file of one job
+pub struct RequestJournal {
+    path: PathBuf,
+    requests: Mutex<Vec<InflightRequest>>,
+}
+
+/// Stable fingerprint of a request body
+pub fn fingerprint(body: &serde_json::Value) -> String {
+    // serde_json keeps object keys sorted, so equal bodies serialize equally
+    hex::encode(Sha256::digest(body.to_string().as_bytes()))
+}
+
+impl RequestJournal {
+    /// Open the journal in a job directory; a missing or broken file is an empty journal
+    pub async fn open(dir: &Path) -> Self {
+        let path = dir.join(JOURNAL_FILE);
+        let requests: Vec<InflightRequest> = match tokio::fs::read_to_string(&path).await {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable request journal {}: {}", path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        if !requests.is_empty() {
+            debug!("Found {} in-flight OpenAI requests in {}", requests.len(), path.display());
+        }
+        Self { path, requests: Mutex::new(requests) }
+    }
+
+    /// Response id of an earlier submission of the same request
+    pub fn pending(&self, fingerprint: &str) -> Option<String> {
+        let requests = self.requests.lock().ok()?;
+        requests
+            .iter()
+            .find(|request| request.fingerprint == fingerprint)
+            .map(|request| request.response_id.clone())
+    }
+
+    /// Remember a submitted request before waiting for it
+    pub async fn record(&self, kind: &str, fingerprint: &str, response_id: &str) -> Result<()> {
+        let snapshot = {
+            let mut requests = self.requests.lock().map_err(|_| anyhow!("Request journal is poisoned"))?;
+            requests.retain(|request| request.fingerprint != fingerprint);
+            requests.push(InflightRequest {
+                kind: kind.to_string(),
+                fingerprint: fingerprint.to_string(),
+                response_id: response_id.to_string(),
+                submitted_at: now_secs(),
+            });
+            requests.clone()
+        };
+        self.save(&snapshot).await
+    }
+
+    /// Forget a request once its result is collected or it can't be recovered
+    pub async fn forget(&self, fingerprint: &str) -> Result<()> {
+        let snapshot = {
+            let mut requests = self.requests.lock().map_err(|_| anyhow!("Request journal is poisoned"))?;
+            let before = requests.len();
+            requests.retain(|request| request.fingerprint != fingerprint);
+            if requests.len() == before {
+                return Ok(());
+            }
+            requests.clone()
+        };
+        self.save(&snapshot).await
+    }
+
+    async fn save(&self, requests: &[InflightRequest]) -> Result<()> {
+        if requests.is_empty() {
+            let _ = tokio::fs::remove_file(&self.path).await;
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // Write and rename, a crash mid-write must not lose the ids already recorded
+        let json = serde_json::to_string_pretty(requests).map_err(|e| anyhow!("Failed to serialize request journal: {}", e))?;
+        let partial = self.path.with_extension("json.part");
+        tokio::fs::write(&partial, json).await?;
+        tokio::fs::rename(&partial, &self.path).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn recorded_requests_survive_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let body = json!({ "model": "gpt-4o-mini", "input": "1. Hello" });
+        let key = fingerprint(&body);
+        assert_eq!(key, fingerprint(&json!({ "input": "1. Hello", "model": "gpt-4o-mini" })));
+
+        let journal = RequestJournal::open(dir.path()).await;
+        journal.record("translation", &key, "resp_1").await.unwrap();
+
+        let reopened = RequestJournal::open(dir.path()).await;
+        assert_eq!(reopened.pending(&key).as_deref(), Some("resp_1"));
+        reopened.forget(&key).await.unwrap();
+        assert!(reopened.pending(&key).is_none());
+        assert!(!dir.path().join(JOURNAL_FILE).exists());
+    }
+}
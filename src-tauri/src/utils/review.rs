@@ -0,0 +1,161 @@
+//! Reviewing the translation before it is spoken.
+//!
+//! With review enabled the pipeline stops after the translation step and
+//! parks the job in `ReviewingTranslation`. The user exports the translated
+//! subtitles, fixes them in any editor and applies the edited file; once its
+//! timing checks out against the translation it replaces it and the job goes
+//! on to speech generation with the edited text.
+
+use anyhow::{anyhow, Result};
+use log::info;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+
+use crate::utils::cancellation;
+use crate::utils::job_state::{self, JobState};
+use crate::utils::jobs;
+use crate::utils::tts::tts::{vtt, SubtitleCue};
+
+const REVIEW_FILE: &str = "translation_review.vtt";
+
+/// How far an edited cue may move from the translation's timing. The cues
+/// follow the original speech, speaking them much earlier or later would put
+/// the dub out of step with the video.
+const MAX_TIMING_SHIFT: f32 = 0.5;
+
+/// Translated VTT the pipeline goes on with and the sender that resumes it
+type PendingReview = (PathBuf, oneshot::Sender<()>);
+
+/// Jobs waiting for their translation to be reviewed
+static WAITING: Lazy<Mutex<HashMap<String, PendingReview>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn translation_path(job_id: &str) -> Result<PathBuf> {
+    WAITING
+        .lock()
+        .map_err(|_| anyhow!("Review registry is poisoned"))?
+        .get(job_id)
+        .map(|(path, _)| path.clone())
+        .ok_or_else(|| anyhow!("Job {} is not waiting for a translation review", job_id))
+}
+
+/// Move the job to `ReviewingTranslation` and wait until an edited
+/// translation is applied or the job is cancelled
+pub async fn wait(
+    app_handle: &tauri::AppHandle,
+    job_id: &str,
+    translated_vtt: &Path,
+    cancel: &CancellationToken,
+) -> Result<(), String> {
+    job_state::transition(app_handle, job_id, JobState::ReviewingTranslation, None).map_err(|e| e.to_string())?;
+
+    let (tx, rx) = oneshot::channel();
+    WAITING
+        .lock()
+        .map_err(|_| "Review registry is poisoned".to_string())?
+        .insert(job_id.to_string(), (translated_vtt.to_path_buf(), tx));
+    info!("Job {} waits for its translation to be reviewed", job_id);
+
+    let applied = cancellation::run(cancel, rx).await;
+    if let Ok(mut waiting) = WAITING.lock() {
+        waiting.remove(job_id);
+    }
+    match applied {
+        Ok(Ok(())) => {
+            info!("Translation of job {} reviewed, continuing", job_id);
+            Ok(())
+        }
+        Ok(Err(_)) => Err(format!("Review of job {} was dropped", job_id)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Copy the translation of a waiting job into its job directory for editing
+pub async fn export(app_handle: &tauri::AppHandle, job_id: &str) -> Result<PathBuf> {
+    let translation = translation_path(job_id)?;
+    let dir = jobs::job_dir(app_handle, job_id)?;
+    tokio::fs::create_dir_all(&dir).await?;
+    let review = dir.join(REVIEW_FILE);
+    tokio::fs::copy(&translation, &review).await?;
+    Ok(review)
+}
+
+/// Check that the edited cues still line up with the translation: one cue
+/// for each cue of the translation, none emptied, in order without overlaps
+/// and close to where they were
+pub fn validate_edit(translation: &[SubtitleCue], edited: &[SubtitleCue]) -> Result<()> {
+    if edited.len() != translation.len() {
+        return Err(anyhow!(
+            "The edited file has {} cues but the translation has {}, cues can be changed but not added, removed or emptied",
+            edited.len(),
+            translation.len()
+        ));
+    }
+    let mut previous_end = 0.0;
+    for (index, (original, cue)) in translation.iter().zip(edited).enumerate() {
+        let number = index + 1;
+        if cue.text.trim().is_empty() {
+            return Err(anyhow!("Cue {} has no text", number));
+        }
+        if cue.end <= cue.start {
+            return Err(anyhow!("Cue {} ends before it starts", number));
+        }
+        if cue.start < previous_end {
+            return Err(anyhow!("Cue {} starts before cue {} ends", number, index));
+        }
+        let shift = (cue.start - original.start).abs().max((cue.end - original.end).abs());
+        if shift > MAX_TIMING_SHIFT {
+            return Err(anyhow!(
+                "Cue {} moved by {:.2}s, timings may move by at most {:.1}s",
+                number,
+                shift,
+                MAX_TIMING_SHIFT
+            ));
+        }
+        previous_end = cue.end;
+    }
+    Ok(())
+}
+
+/// Replace the translation of a waiting job with the edited VTT and let the
+/// pipeline continue. A file that fails validation leaves the job waiting.
+pub async fn apply(job_id: &str, edited_vtt: &Path) -> Result<()> {
+    let translation = translation_path(job_id)?;
+    let original = vtt::parse_vtt(&translation).map_err(|e| anyhow!("{}", e))?;
+    let edited = vtt::parse_vtt(edited_vtt).map_err(|e| anyhow!("Failed to read {}: {}", edited_vtt.display(), e))?;
+    validate_edit(&original, &edited)?;
+
+    tokio::fs::write(&translation, vtt::write_vtt_str(&edited)).await?;
+    let (_, tx) = WAITING
+        .lock()
+        .map_err(|_| anyhow!("Review registry is poisoned"))?
+        .remove(job_id)
+        .ok_or_else(|| anyhow!("Job {} is not waiting for a translation review", job_id))?;
+    tx.send(()).map_err(|_| anyhow!("Job {} is no longer running", job_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: f32, end: f32, text: &str) -> SubtitleCue {
+        SubtitleCue { start, end, text: text.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn edits_must_keep_the_timing_of_the_translation() {
+        let translation = vec![cue(0.0, 2.0, "Hallo"), cue(2.5, 5.0, "Welt")];
+
+        let reworded = vec![cue(0.0, 2.0, "Guten Tag"), cue(2.6, 5.2, "liebe Welt")];
+        assert!(validate_edit(&translation, &reworded).is_ok());
+
+        assert!(validate_edit(&translation, &reworded[..1]).is_err());
+        assert!(validate_edit(&translation, &[cue(0.0, 2.0, "Hallo"), cue(1.8, 5.0, "Welt")]).is_err());
+        assert!(validate_edit(&translation, &[cue(0.0, 2.0, "Hallo"), cue(3.5, 5.0, "Welt")]).is_err());
+        assert!(validate_edit(&translation, &[cue(0.0, 2.0, " "), cue(2.5, 5.0, "Welt")]).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! Masking of personal data and profanity before cloud translation.
+//!
+//! Emails, phone numbers and names found in the transcript are swapped for
+//! numbered placeholders like `[[1]]` before the text leaves the machine, and
+//! put back into the translation afterwards. Names are found with a light
+//! heuristic (runs of capitalized words, words after a title such as "Dr.")
+//! plus a list of names the user always wants masked. Profanity is replaced by
+//! its first letter and asterisks and is not restored.
+
+use log::warn;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Words masked as profanity in addition to the user's list
+const BUILTIN_PROFANITY: &[&str] = &["fuck", "fucking", "shit", "bullshit", "bitch", "asshole", "bastard", "dick"];
+
+/// Capitalized words that start a run without being a name
+const NOT_NAMES: &[&str] = &[
+    "a", "an", "and", "but", "he", "her", "his", "i", "if", "in", "it", "my", "no", "oh", "ok", "okay", "or", "our",
+    "she", "so", "that", "the", "then", "there", "they", "this", "we", "well", "what", "when", "yes", "you",
+];
+
+/// Titles that precede a name
+const TITLES: &[&str] = &["mr", "mrs", "ms", "dr", "prof"];
+
+static EMAIL: Lazy<Regex> = Lazy::new(|| Regex::new(r"[\w.+-]+@[\w-]+(?:\.[\w-]+)+").unwrap());
+static PHONE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\+?\d[\d\s().-]{6,}\d").unwrap());
+static CAPITALIZED_RUN: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b\p{Lu}\p{Ll}+(?:\s+\p{Lu}\p{Ll}+)+\b").unwrap());
+static TITLED_NAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b(?:Mr|Mrs|Ms|Dr|Prof)\.?\s+(\p{Lu}\p{Ll}+)").unwrap());
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[\[(\d+)\]\]").unwrap());
+
+/// What to mask before translation, stored under the "translation-scrubbing" setting
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ScrubOptions {
+    pub enabled: bool,
+    pub emails: bool,
+    pub phones: bool,
+    /// Names found by the heuristic
+    pub names: bool,
+    /// Names and other terms that are always masked
+    pub always_mask: Vec<String>,
+    pub profanity: bool,
+    /// Extra words masked as profanity
+    pub profanity_words: Vec<String>,
+}
+
+impl Default for ScrubOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            emails: true,
+            phones: true,
+            names: true,
+            always_mask: Vec::new(),
+            profanity: false,
+            profanity_words: Vec::new(),
+        }
+    }
+}
+
+/// Placeholders handed out while masking, shared by all cues of a file so the
+/// same value always gets the same placeholder
+#[derive(Debug, Default)]
+pub struct Scrubber {
+    values: Vec<String>,
+    by_value: HashMap<String, usize>,
+}
+
+impl Scrubber {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether anything was replaced by a placeholder
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn placeholder(&mut self, value: &str) -> String {
+        let next = self.values.len() + 1;
+        let number = *self.by_value.entry(value.to_string()).or_insert(next);
+        if number == next {
+            self.values.push(value.to_string());
+        }
+        format!("[[{}]]", number)
+    }
+
+    fn replace_all(&mut self, text: &str, regex: &Regex, group: usize) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut last = 0;
+        for captures in regex.captures_iter(text) {
+            let Some(found) = captures.get(group) else { continue };
+            result.push_str(&text[last..found.start()]);
+            result.push_str(&self.placeholder(found.as_str()));
+            last = found.end();
+        }
+        result.push_str(&text[last..]);
+        result
+    }
+
+    /// Mask a cue text
+    pub fn mask(&mut self, text: &str, options: &ScrubOptions) -> String {
+        if !options.enabled {
+            return text.to_string();
+        }
+        let mut text = text.to_string();
+        if options.emails {
+            text = self.replace_all(&text, &EMAIL, 0);
+        }
+        if options.phones {
+            // Only number runs long enough to be a phone number, not years or prices
+            text = PHONE
+                .replace_all(&text, |captures: &regex::Captures| {
+                    let found = &captures[0];
+                    if found.chars().filter(|c| c.is_ascii_digit()).count() >= 8 {
+                        self.placeholder(found)
+                    } else {
+                        found.to_string()
+                    }
+                })
+                .into_owned();
+        }
+        for term in options.always_mask.iter().map(|term| term.trim()).filter(|term| !term.is_empty()) {
+            let regex = Regex::new(&format!(r"(?i)\b{}\b", regex::escape(term))).unwrap();
+            text = self.replace_all(&text, &regex, 0);
+        }
+        if options.names {
+            text = self.replace_all(&text, &TITLED_NAME, 1);
+            text = CAPITALIZED_RUN
+                .replace_all(&text, |captures: &regex::Captures| {
+                    let found = &captures[0];
+                    let mut words = found.split_whitespace().map(str::to_lowercase);
+                    let first = words.next().unwrap_or_default();
+                    // "Ask Dr. Novak" leaves "Ask Dr" behind once the titled name is masked
+                    let has_title = words.any(|word| TITLES.contains(&word.as_str()));
+                    if NOT_NAMES.contains(&first.as_str()) || has_title {
+                        found.to_string()
+                    } else {
+                        self.placeholder(found)
+                    }
+                })
+                .into_owned();
+        }
+        if options.profanity {
+            text = mask_profanity(&text, &options.profanity_words);
+        }
+        text
+    }
+
+    /// Put the masked values back into a translated text
+    pub fn restore(&self, text: &str) -> String {
+        PLACEHOLDER
+            .replace_all(text, |captures: &regex::Captures| {
+                let number: usize = captures[1].parse().unwrap_or(0);
+                match number.checked_sub(1).and_then(|index| self.values.get(index)) {
+                    Some(value) => value.clone(),
+                    None => {
+                        warn!("Translation contains unknown placeholder {}", &captures[0]);
+                        captures[0].to_string()
+                    }
+                }
+            })
+            .into_owned()
+    }
+
+    /// Placeholders of `masked` that are missing from `translated`
+    pub fn missing(&self, masked: &str, translated: &str) -> Vec<String> {
+        PLACEHOLDER
+            .find_iter(masked)
+            .map(|found| found.as_str().to_string())
+            .filter(|placeholder| !translated.contains(placeholder.as_str()))
+            .collect()
+    }
+}
+
+/// Replace profanity with its first letter followed by asterisks
+pub fn mask_profanity(text: &str, extra_words: &[String]) -> String {
+    let words: Vec<String> = BUILTIN_PROFANITY
+        .iter()
+        .map(|word| word.to_string())
+        .chain(extra_words.iter().map(|word| word.trim().to_lowercase()))
+        .filter(|word| !word.is_empty())
+        .map(|word| regex::escape(&word))
+        .collect();
+    let regex = Regex::new(&format!(r"(?i)\b(?:{})\b", words.join("|"))).unwrap();
+    regex
+        .replace_all(text, |captures: &regex::Captures| {
+            let mut chars = captures[0].chars();
+            let first = chars.next().map(String::from).unwrap_or_default();
+            first + &"*".repeat(chars.count())
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> ScrubOptions {
+        ScrubOptions { enabled: true, profanity: true, ..Default::default() }
+    }
+
+    #[test]
+    fn masks_and_restores_personal_data() {
+        let mut scrubber = Scrubber::new();
+        let options = ScrubOptions { always_mask: vec!["Acme".to_string()], ..enabled() };
+        let text = "The Acme report goes to John Smith, mail john@acme.com or call +1 (555) 123-4567 by 2024.";
+        let masked = scrubber.mask(text, &options);
+        assert_eq!(masked, "The [[3]] report goes to [[4]], mail [[1]] or call [[2]] by 2024.");
+
+        // The same name later in the file reuses its placeholder
+        assert_eq!(scrubber.mask("Thanks, John Smith!", &options), "Thanks, [[4]]!");
+
+        let translated = "Отчет [[3]] отправляется [[4]], почта [[1]] или звоните [[2]] до 2024.";
+        assert_eq!(
+            scrubber.restore(translated),
+            "Отчет Acme отправляется John Smith, почта john@acme.com или звоните +1 (555) 123-4567 до 2024."
+        );
+        assert_eq!(scrubber.missing(&masked, "Отчет [[3]]"), vec!["[[4]]", "[[1]]", "[[2]]"]);
+    }
+
+    #[test]
+    fn leaves_ordinary_capitalized_words() {
+        let mut scrubber = Scrubber::new();
+        let masked = scrubber.mask("The Project starts. Ask Dr. Novak about it", &enabled());
+        assert_eq!(masked, "The Project starts. Ask Dr. [[1]] about it");
+    }
+
+    #[test]
+    fn masks_profanity_without_placeholders() {
+        let mut scrubber = Scrubber::new();
+        assert_eq!(scrubber.mask("what the fuck, Shit happens", &enabled()), "what the f***, S*** happens");
+        assert!(scrubber.is_empty());
+    }
+
+    #[test]
+    fn disabled_scrubbing_keeps_the_text() {
+        let mut scrubber = Scrubber::new();
+        let text = "Mail john@acme.com";
+        assert_eq!(scrubber.mask(text, &ScrubOptions::default()), text);
+    }
+}
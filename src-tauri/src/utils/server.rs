@@ -0,0 +1,534 @@
+//! Processing server for remote job submission.
+//!
+//! With the `server` feature, `videonova-cli serve` runs the pipeline on a
+//! headless machine and exposes it over a small HTTP API:
+//!
+//! - `POST /jobs` queues a [`SubmitJob`] and returns the [`RemoteJob`]
+//! - `GET /jobs` and `GET /jobs/{id}` return the jobs
+//! - `GET /jobs/{id}/events` streams [`ServerEvent`]s as server-sent events,
+//!   starting with the current state of the job and ending once it finished
+//! - `GET /jobs/{id}/artifacts/{name}` downloads a produced file
+//!
+//! Jobs run one at a time, in submission order. When a token is set, every
+//! request needs it as a bearer token; a server listening beyond localhost
+//! must have one. The client half below is what the app uses to submit jobs
+//! and follow them, and doesn't need the feature.
+
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+use crate::utils::headless::{HeadlessEvent, PipelineOutput, PipelineRequest};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteJobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl RemoteJobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, RemoteJobStatus::Completed | RemoteJobStatus::Failed)
+    }
+}
+
+/// A job on the processing server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteJob {
+    pub id: String,
+    pub request: PipelineRequest,
+    pub status: RemoteJobStatus,
+    /// Step running now
+    pub step: Option<String>,
+    /// Progress of the step, percent
+    pub progress: Option<f32>,
+    pub error: Option<String>,
+    /// Names of the files that can be downloaded
+    pub artifacts: Vec<String>,
+    /// Paths on the server, never sent
+    #[serde(skip)]
+    pub output: Option<PipelineOutput>,
+}
+
+impl RemoteJob {
+    /// Server path of a produced file
+    pub fn artifact_path(&self, name: &str) -> Option<&Path> {
+        let output = self.output.as_ref()?;
+        match name {
+            "video" => Some(&output.output_path),
+            "dubbed_audio" => Some(&output.dubbed_audio_path),
+            "vtt" => Some(&output.vtt_path),
+            "translated_vtt" => Some(&output.translated_vtt_path),
+            "retimed_vtt" => output.retimed_vtt_path.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Mark the job done with the files it produced
+    pub fn complete(&mut self, output: PipelineOutput) {
+        self.status = RemoteJobStatus::Completed;
+        self.step = None;
+        self.progress = None;
+        self.output = Some(output);
+        self.artifacts = ["video", "dubbed_audio", "vtt", "translated_vtt", "retimed_vtt"]
+            .into_iter()
+            .filter(|name| self.artifact_path(name).is_some())
+            .map(str::to_string)
+            .collect();
+    }
+}
+
+/// Body of `POST /jobs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitJob {
+    #[serde(flatten)]
+    pub request: PipelineRequest,
+    /// Used instead of the server's own key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+}
+
+/// What the events stream of a job sends; the tag is also the SSE event name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ServerEvent {
+    Step { step: String },
+    Progress { step: String, update: Value },
+    /// State of the job, sent first and whenever the status changes. Boxed,
+    /// it is much larger than the other events.
+    Job { job: Box<RemoteJob> },
+}
+
+impl ServerEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerEvent::Step { .. } => "step",
+            ServerEvent::Progress { .. } => "progress",
+            ServerEvent::Job { .. } => "job",
+        }
+    }
+}
+
+impl From<HeadlessEvent> for ServerEvent {
+    fn from(event: HeadlessEvent) -> Self {
+        match event {
+            HeadlessEvent::Step { step } => ServerEvent::Step { step },
+            HeadlessEvent::Progress { step, update } => ServerEvent::Progress { step, update },
+        }
+    }
+}
+
+/// Where the app sends its jobs, kept in the settings store
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProcessingServer {
+    /// e.g. `http://render-box.local:8787`
+    pub url: String,
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl ProcessingServer {
+    pub fn validate(&self) -> Result<()> {
+        let parsed = url::Url::parse(&self.url).map_err(|e| anyhow!("Invalid server URL {}: {}", self.url, e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(anyhow!("The server URL must be http or https, got {}", parsed.scheme()));
+        }
+        Ok(())
+    }
+
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}{}", self.url.trim_end_matches('/'), path)
+    }
+
+    fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.authorize(reqwest::Client::new().get(self.endpoint(path)))
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    pub async fn submit(&self, job: &SubmitJob) -> Result<RemoteJob> {
+        let request = self.authorize(reqwest::Client::new().post(self.endpoint("/jobs")).json(job));
+        Ok(checked(request.send().await?).await?.json().await?)
+    }
+
+    pub async fn jobs(&self) -> Result<Vec<RemoteJob>> {
+        Ok(checked(self.get("/jobs").send().await?).await?.json().await?)
+    }
+
+    pub async fn job(&self, id: &str) -> Result<RemoteJob> {
+        Ok(checked(self.get(&format!("/jobs/{}", id)).send().await?).await?.json().await?)
+    }
+
+    /// Follow a job until it finished, returns its final state
+    pub async fn watch(&self, id: &str, mut on_event: impl FnMut(&ServerEvent)) -> Result<RemoteJob> {
+        let response = checked(self.get(&format!("/jobs/{}/events", id)).send().await?).await?;
+        let mut stream = response.bytes_stream();
+        // Bytes, not text: a chunk may end inside a UTF-8 sequence
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut connected = false;
+        while let Some(chunk) = stream.next().await {
+            buffer.extend_from_slice(&chunk?);
+            while let Some(end) = buffer.windows(2).position(|pair| pair == b"\n\n") {
+                let block: Vec<u8> = buffer.drain(..end + 2).collect();
+                let Some(event) = parse_sse_block(&String::from_utf8_lossy(&block)) else { continue };
+                on_event(&event);
+                if let ServerEvent::Job { job } = event {
+                    if job.status.is_finished() {
+                        return Ok(*job);
+                    }
+                    connected = true;
+                }
+            }
+        }
+        // The stream ended early, ask for the state instead
+        if connected {
+            self.job(id).await
+        } else {
+            Err(anyhow!("The server closed the event stream of job {}", id))
+        }
+    }
+
+    pub async fn download_artifact(&self, id: &str, name: &str, destination: &Path) -> Result<PathBuf> {
+        let response = checked(self.get(&format!("/jobs/{}/artifacts/{}", id, name)).send().await?).await?;
+        let mut file = tokio::fs::File::create(destination).await?;
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            file.write_all(&chunk?).await?;
+        }
+        file.flush().await?;
+        Ok(destination.to_path_buf())
+    }
+}
+
+async fn checked(response: reqwest::Response) -> Result<reqwest::Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    Err(anyhow!("Processing server returned {}: {}", status, body.trim()))
+}
+
+/// The data lines of one SSE message
+fn parse_sse_block(block: &str) -> Option<ServerEvent> {
+    let data = block
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if data.is_empty() {
+        return None;
+    }
+    serde_json::from_str(&data).ok()
+}
+
+#[cfg(feature = "server")]
+pub mod http {
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::{Path as UrlPath, Request, State};
+    use axum::http::{header, StatusCode};
+    use axum::middleware::{self, Next};
+    use axum::response::sse::{Event, KeepAlive, Sse};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use hmac::{Hmac, Mac};
+    use log::{error, info};
+    use sha2::Sha256;
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::broadcast;
+
+    use crate::utils::cookies::CookieSource;
+    use crate::utils::headless::{self, EventSink};
+    use crate::utils::jobs;
+
+    pub struct ServerConfig {
+        pub bind: SocketAddr,
+        pub token: Option<String>,
+        /// Every job works in a directory of its own in here
+        pub data_dir: PathBuf,
+        /// Key of jobs submitted without one
+        pub api_key: Option<String>,
+        pub cookies: CookieSource,
+    }
+
+    struct ServerState {
+        config: ServerConfig,
+        jobs: Mutex<Vec<RemoteJob>>,
+        /// Keys of the queued jobs that brought their own
+        api_keys: Mutex<std::collections::HashMap<String, String>>,
+        events: broadcast::Sender<(String, ServerEvent)>,
+        queue: std::sync::mpsc::Sender<String>,
+    }
+
+    type Shared = Arc<ServerState>;
+
+    impl ServerState {
+        fn job(&self, id: &str) -> Option<RemoteJob> {
+            self.jobs.lock().ok()?.iter().find(|job| job.id == id).cloned()
+        }
+
+        /// Change a job and tell its watchers
+        fn update(&self, id: &str, change: impl FnOnce(&mut RemoteJob)) {
+            let Ok(mut jobs) = self.jobs.lock() else { return };
+            let Some(job) = jobs.iter_mut().find(|job| job.id == id) else { return };
+            change(job);
+            let _ = self.events.send((id.to_string(), ServerEvent::Job { job: Box::new(job.clone()) }));
+        }
+
+        fn sink(self: &Arc<Self>, id: &str) -> EventSink {
+            let state = self.clone();
+            let id = id.to_string();
+            Arc::new(move |event: HeadlessEvent| {
+                if let Ok(mut jobs) = state.jobs.lock()
+                    && let Some(job) = jobs.iter_mut().find(|job| job.id == id)
+                {
+                    match &event {
+                        HeadlessEvent::Step { step } => {
+                            job.step = Some(step.clone());
+                            job.progress = Some(0.0);
+                        }
+                        HeadlessEvent::Progress { update, .. } => {
+                            if let Some(progress) = update.get("progress").and_then(Value::as_f64) {
+                                job.progress = Some(progress as f32);
+                            }
+                        }
+                    }
+                }
+                let _ = state.events.send((id.clone(), event.into()));
+            })
+        }
+    }
+
+    /// Run the jobs one after another. The synchronizer isn't `Send`, so the
+    /// worker has a thread and a runtime of its own.
+    fn start_worker(state: Shared, queue: std::sync::mpsc::Receiver<String>) {
+        std::thread::spawn(move || {
+            let runtime = match tokio::runtime::Runtime::new() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    error!("Failed to start the job worker: {}", e);
+                    return;
+                }
+            };
+            while let Ok(id) = queue.recv() {
+                let Some(job) = state.job(&id) else { continue };
+                let api_key = state
+                    .api_keys
+                    .lock()
+                    .ok()
+                    .and_then(|mut keys| keys.remove(&id))
+                    .or_else(|| state.config.api_key.clone());
+                state.update(&id, |job| job.status = RemoteJobStatus::Running);
+                info!("Running remote job {} for {}", id, job.request.url);
+
+                let output_dir = state.config.data_dir.join(&id);
+                let result = runtime.block_on(async {
+                    let api_key = api_key.ok_or_else(|| anyhow!("No OpenAI API key, submit one with the job"))?;
+                    tokio::fs::create_dir_all(&output_dir).await?;
+                    headless::pipeline(&state.sink(&id), &api_key, &job.request, &output_dir, &state.config.cookies).await
+                });
+                match result {
+                    Ok(output) => state.update(&id, |job| job.complete(output)),
+                    Err(e) => {
+                        error!("Remote job {} failed: {}", id, e);
+                        state.update(&id, |job| {
+                            job.status = RemoteJobStatus::Failed;
+                            job.error = Some(e.to_string());
+                        });
+                    }
+                }
+            }
+        });
+    }
+
+    /// Compare the bearer token in constant time. Both tokens are hashed to
+    /// digests of the same length, so neither the length nor the first wrong
+    /// byte of the given token shows in the response time.
+    pub(super) fn token_matches(expected: &str, given: &str) -> bool {
+        let digest = |token: &str| {
+            let mut mac = Hmac::<Sha256>::new_from_slice(b"videonova-server-token").expect("HMAC accepts keys of any size");
+            mac.update(token.as_bytes());
+            mac
+        };
+        digest(given).verify_slice(&digest(expected).finalize().into_bytes()).is_ok()
+    }
+
+    async fn authorize(State(state): State<Shared>, request: Request, next: Next) -> Response {
+        if let Some(token) = &state.config.token {
+            let given = request
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            if !given.is_some_and(|given| token_matches(token, given)) {
+                return (StatusCode::UNAUTHORIZED, "Missing or wrong token").into_response();
+            }
+        }
+        next.run(request).await
+    }
+
+    async fn submit(State(state): State<Shared>, Json(submit): Json<SubmitJob>) -> Response {
+        if url::Url::parse(&submit.request.url).is_err() {
+            return (StatusCode::BAD_REQUEST, "Invalid video URL").into_response();
+        }
+        let job = RemoteJob {
+            id: jobs::new_job_id(),
+            request: submit.request,
+            status: RemoteJobStatus::Queued,
+            step: None,
+            progress: None,
+            error: None,
+            artifacts: Vec::new(),
+            output: None,
+        };
+        if let (Some(key), Ok(mut keys)) = (submit.api_key, state.api_keys.lock()) {
+            keys.insert(job.id.clone(), key);
+        }
+        if let Ok(mut jobs) = state.jobs.lock() {
+            jobs.push(job.clone());
+        }
+        if state.queue.send(job.id.clone()).is_err() {
+            return (StatusCode::SERVICE_UNAVAILABLE, "The job worker stopped").into_response();
+        }
+        (StatusCode::ACCEPTED, Json(job)).into_response()
+    }
+
+    async fn list(State(state): State<Shared>) -> Json<Vec<RemoteJob>> {
+        Json(state.jobs.lock().map(|jobs| jobs.clone()).unwrap_or_default())
+    }
+
+    async fn status(State(state): State<Shared>, UrlPath(id): UrlPath<String>) -> Response {
+        match state.job(&id) {
+            Some(job) => Json(job).into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    async fn events(State(state): State<Shared>, UrlPath(id): UrlPath<String>) -> Response {
+        // Subscribe before reading the state, so nothing falls in between
+        let receiver = state.events.subscribe();
+        let Some(job) = state.job(&id) else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        let first = ServerEvent::Job { job: Box::new(job.clone()) };
+        let finished = job.status.is_finished();
+
+        let live = futures::stream::unfold((receiver, finished), move |(mut receiver, finished)| {
+            let id = id.clone();
+            async move {
+                if finished {
+                    return None;
+                }
+                loop {
+                    match receiver.recv().await {
+                        Ok((job_id, event)) if job_id == id => {
+                            let finished = matches!(&event, ServerEvent::Job { job } if job.status.is_finished());
+                            return Some((event, (receiver, finished)));
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+        let stream = futures::stream::once(async move { first }).chain(live).map(|event| {
+            let sse = Event::default().event(event.name()).json_data(&event).unwrap_or_default();
+            Ok::<_, Infallible>(sse)
+        });
+        Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+    }
+
+    async fn artifact(State(state): State<Shared>, UrlPath((id, name)): UrlPath<(String, String)>) -> Response {
+        let Some(path) = state.job(&id).and_then(|job| job.artifact_path(&name).map(Path::to_path_buf)) else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        let file = match tokio::fs::File::open(&path).await {
+            Ok(file) => file,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        };
+        let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or(name);
+        (
+            [
+                (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name.replace('"', ""))),
+            ],
+            Body::from_stream(tokio_util::io::ReaderStream::new(file)),
+        )
+            .into_response()
+    }
+
+    /// Serve until the listener fails
+    pub async fn serve(config: ServerConfig) -> Result<()> {
+        if config.token.is_none() && !config.bind.ip().is_loopback() {
+            return Err(anyhow!("A server listening on {} needs a token", config.bind));
+        }
+        tokio::fs::create_dir_all(&config.data_dir).await?;
+        let bind = config.bind;
+        let (queue, jobs_rx) = std::sync::mpsc::channel();
+        let state = Arc::new(ServerState {
+            config,
+            jobs: Mutex::new(Vec::new()),
+            api_keys: Mutex::new(Default::default()),
+            events: broadcast::channel(256).0,
+            queue,
+        });
+        start_worker(state.clone(), jobs_rx);
+
+        let app = Router::new()
+            .route("/jobs", get(list).post(submit))
+            .route("/jobs/:id", get(status))
+            .route("/jobs/:id/events", get(events))
+            .route("/jobs/:id/artifacts/:name", get(artifact))
+            .layer(middleware::from_fn_with_state(state.clone(), authorize))
+            .with_state(state);
+
+        let listener = tokio::net::TcpListener::bind(bind).await?;
+        info!("Processing server listening on http://{}", bind);
+        axum::serve(listener, app).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sse_messages_parse_into_events() {
+        let block = "event: progress\ndata: {\"event\":\"progress\",\"step\":\"tts\",\"update\":{\"type\":\"started\"}}\n\n";
+        match parse_sse_block(block) {
+            Some(ServerEvent::Progress { step, update }) => {
+                assert_eq!(step, "tts");
+                assert_eq!(update["type"], "started");
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        // Keep-alive comments carry no data
+        assert!(parse_sse_block(":\n\n").is_none());
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn bearer_token_must_match_exactly() {
+        assert!(http::token_matches("s3cret-token", "s3cret-token"));
+        assert!(!http::token_matches("s3cret-token", "s3cret-tokem"));
+        assert!(!http::token_matches("s3cret-token", "s3cret"));
+        assert!(!http::token_matches("s3cret-token", ""));
+    }
+}
@@ -0,0 +1,148 @@
+//! Coordinated access to the settings store.
+//!
+//! Commands, running jobs and the frontend all touch `.settings.dat`. A single
+//! key read or write is atomic in the store itself, but read-modify-write
+//! sequences and the save that follows them are not, so a setting edited
+//! while a job updates another one could be lost or persisted half-way. All
+//! backend writes go through this module: they take the write side of an
+//! async RwLock, saving takes the read side, and saves are debounced so a
+//! burst of edits hits the disk once. Every change is announced to the
+//! frontend as a `settings-changed` event.
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tauri::Emitter;
+use tauri_plugin_store::StoreExt;
+use tokio::sync::RwLock;
+
+const STORE_PATH: &str = ".settings.dat";
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+static STORE_LOCK: Lazy<RwLock<()>> = Lazy::new(|| RwLock::new(()));
+// Bumped on every change; a scheduled save only runs if no newer change followed it
+static SAVE_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Payload of the `settings-changed` event, `value` is null for removed keys
+#[derive(Debug, Serialize, Clone)]
+pub struct SettingsChanged {
+    pub key: String,
+    pub value: serde_json::Value,
+}
+
+fn store(app_handle: &tauri::AppHandle) -> Result<std::sync::Arc<tauri_plugin_store::Store<tauri::Wry>>> {
+    app_handle
+        .store(STORE_PATH)
+        .map_err(|e| anyhow!("Failed to open settings store: {}", e))
+}
+
+/// Raw value of a setting
+pub fn get_value(app_handle: &tauri::AppHandle, key: &str) -> Option<serde_json::Value> {
+    match store(app_handle) {
+        Ok(store) => store.get(key),
+        Err(e) => {
+            warn!("{}", e);
+            None
+        }
+    }
+}
+
+/// A setting deserialized into `T`, None if missing or of another shape
+pub fn get<T: DeserializeOwned>(app_handle: &tauri::AppHandle, key: &str) -> Option<T> {
+    let value = get_value(app_handle, key)?;
+    match serde_json::from_value(value) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!("Ignoring invalid value of setting '{}': {}", key, e);
+            None
+        }
+    }
+}
+
+fn announce(app_handle: &tauri::AppHandle, key: &str, value: serde_json::Value) {
+    let change = SettingsChanged { key: key.to_string(), value };
+    if let Err(e) = app_handle.emit("settings-changed", &change) {
+        warn!("Failed to emit settings-changed: {}", e);
+    }
+}
+
+async fn save_now(app_handle: &tauri::AppHandle) -> Result<()> {
+    let _guard = STORE_LOCK.read().await;
+    store(app_handle)?
+        .save()
+        .map_err(|e| anyhow!("Failed to save settings: {}", e))
+}
+
+fn schedule_save(app_handle: &tauri::AppHandle) {
+    let generation = SAVE_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(SAVE_DEBOUNCE).await;
+        if SAVE_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        match save_now(&app_handle).await {
+            Ok(()) => debug!("Settings saved"),
+            Err(e) => warn!("{}", e),
+        }
+    });
+}
+
+/// Change a setting
+pub async fn set<T: Serialize>(app_handle: &tauri::AppHandle, key: &str, value: &T) -> Result<()> {
+    let value = serde_json::to_value(value).map_err(|e| anyhow!("Failed to serialize setting '{}': {}", key, e))?;
+    {
+        let _guard = STORE_LOCK.write().await;
+        store(app_handle)?.set(key, value.clone());
+    }
+    announce(app_handle, key, value);
+    schedule_save(app_handle);
+    Ok(())
+}
+
+/// Remove a setting
+pub async fn remove(app_handle: &tauri::AppHandle, key: &str) -> Result<()> {
+    {
+        let _guard = STORE_LOCK.write().await;
+        store(app_handle)?.delete(key);
+    }
+    announce(app_handle, key, serde_json::Value::Null);
+    schedule_save(app_handle);
+    Ok(())
+}
+
+/// Read, modify and write a setting without other backend writes in between.
+/// A missing or unreadable value starts from `T::default()`.
+pub async fn update<T, F>(app_handle: &tauri::AppHandle, key: &str, modify: F) -> Result<T>
+where
+    T: Serialize + DeserializeOwned + Default,
+    F: FnOnce(&mut T),
+{
+    let (updated, value) = {
+        let _guard = STORE_LOCK.write().await;
+        let store = store(app_handle)?;
+        let mut current: T = store
+            .get(key)
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default();
+        modify(&mut current);
+        let value = serde_json::to_value(&current)
+            .map_err(|e| anyhow!("Failed to serialize setting '{}': {}", key, e))?;
+        store.set(key, value.clone());
+        (current, value)
+    };
+    announce(app_handle, key, value);
+    schedule_save(app_handle);
+    Ok(updated)
+}
+
+/// Write pending changes to disk right away, e.g. before the app exits
+pub async fn flush(app_handle: &tauri::AppHandle) -> Result<()> {
+    // Supersede any scheduled save
+    SAVE_GENERATION.fetch_add(1, Ordering::SeqCst);
+    save_now(app_handle).await
+}
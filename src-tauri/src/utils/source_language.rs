@@ -0,0 +1,80 @@
+//! Confirming the source language detected before transcription.
+//!
+//! A job that leaves the source language to detection sends a short sample of
+//! the audio to Whisper first and reports what it heard as a
+//! `language-detected` event. The job then waits a moment for the user to
+//! confirm or correct the language with `confirm_source_language`, before the
+//! whole transcription is paid for. Without an answer it goes on with the
+//! detected language, so unattended runs aren't held up.
+
+use anyhow::{anyhow, Result};
+use log::info;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+use crate::utils::validation::is_language_code;
+
+/// How long a job waits for the detected language to be confirmed
+pub const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(20);
+
+static PENDING: Lazy<Mutex<HashMap<String, oneshot::Sender<String>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether the source language is left to detection
+pub fn is_auto(code: &str) -> bool {
+    code.trim().is_empty() || code == "auto"
+}
+
+/// Wait for the user to confirm or correct the detected language, the detected
+/// one once the time is up
+pub async fn await_confirmation(job_id: &str, detected: &str) -> String {
+    let (tx, rx) = oneshot::channel();
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.insert(job_id.to_string(), tx);
+    }
+    let answer = tokio::time::timeout(CONFIRMATION_TIMEOUT, rx).await;
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.remove(job_id);
+    }
+    match answer {
+        Ok(Ok(code)) => code,
+        _ => {
+            info!("No answer about the source language of job {}, going on with {}", job_id, detected);
+            detected.to_string()
+        }
+    }
+}
+
+/// Answer a job waiting for its source language
+pub fn confirm(job_id: &str, code: &str) -> Result<()> {
+    if !is_language_code(code) {
+        return Err(anyhow!("Unknown language code: {}", code));
+    }
+    let tx = PENDING
+        .lock()
+        .map_err(|_| anyhow!("Language confirmation registry is poisoned"))?
+        .remove(job_id)
+        .ok_or_else(|| anyhow!("Job {} is not waiting for its source language", job_id))?;
+    tx.send(code.to_string()).map_err(|_| anyhow!("Job {} is no longer running", job_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn a_confirmed_language_replaces_the_detected_one() {
+        let waiting = tokio::spawn(async { await_confirmation("job-1", "en").await });
+        // Give the job a moment to register
+        while PENDING.lock().unwrap().get("job-1").is_none() {
+            tokio::task::yield_now().await;
+        }
+        assert!(confirm("job-1", "xx").is_err());
+        confirm("job-1", "de").unwrap();
+        assert_eq!(waiting.await.unwrap(), "de");
+        assert!(confirm("job-1", "de").is_err());
+        assert!(is_auto("auto") && is_auto("") && !is_auto("en"));
+    }
+}
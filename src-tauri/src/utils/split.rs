@@ -0,0 +1,221 @@
+//! Splitting the merged output into parts under a size cap.
+//!
+//! FAT32 drives and some upload targets refuse files of 4 GB and more. When a
+//! cap is set and the merged video exceeds it, the video is cut with ffmpeg's
+//! segment muxer (stream copy, so cuts land on the next keyframe) into
+//! `<name>_part01.mp4`, `<name>_part02.mp4`, ... and an `.m3u` playlist with the
+//! parts in order is written next to them. Cuts prefer chapter starts, so a
+//! chapter isn't split across files when the cap allows it.
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::process::Command as TokioCommand;
+
+use crate::utils::audio_probe;
+use crate::utils::chapters::{self, Chapter};
+use crate::utils::settings;
+
+const SETTINGS_KEY: &str = "output-split";
+
+/// Largest file a FAT32 drive can hold
+pub const FAT32_LIMIT: u64 = 4 * 1024 * 1024 * 1024 - 1;
+
+// Parts are planned below the cap, a cut can only happen on the next keyframe
+const SIZE_MARGIN: f64 = 0.9;
+
+/// Splitting settings, stored as the "output-split" setting
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct SplitSettings {
+    pub enabled: bool,
+    /// Largest size of a part in bytes
+    pub max_size_bytes: u64,
+}
+
+impl Default for SplitSettings {
+    fn default() -> Self {
+        Self { enabled: false, max_size_bytes: FAT32_LIMIT }
+    }
+}
+
+impl SplitSettings {
+    pub fn validate(&self) -> Result<()> {
+        // Anything smaller would cut a typical video into hundreds of parts
+        if self.max_size_bytes < 16 * 1024 * 1024 {
+            return Err(anyhow!("The maximum part size must be at least 16 MB"));
+        }
+        Ok(())
+    }
+}
+
+/// Parts of a split output
+#[derive(Debug, Serialize, Clone)]
+pub struct SplitOutput {
+    pub parts: Vec<PathBuf>,
+    pub playlist: PathBuf,
+}
+
+pub fn load(app_handle: &tauri::AppHandle) -> SplitSettings {
+    settings::get(app_handle, SETTINGS_KEY).unwrap_or_default()
+}
+
+pub async fn save(app_handle: &tauri::AppHandle, split: &SplitSettings) -> Result<()> {
+    split.validate()?;
+    settings::set(app_handle, SETTINGS_KEY, split).await
+}
+
+/// Times in seconds where the output is cut. Parts stay under `max_part_secs`
+/// where possible; a cut moves back to the latest chapter start inside the part
+/// unless that would leave the part shorter than half the limit.
+pub fn split_points(duration: f64, max_part_secs: f64, chapters: &[Chapter]) -> Vec<f64> {
+    if max_part_secs <= 0.0 || duration <= max_part_secs {
+        return Vec::new();
+    }
+    let mut points = Vec::new();
+    let mut part_start = 0.0;
+    while duration - part_start > max_part_secs {
+        let limit = part_start + max_part_secs;
+        let chapter_cut = chapters
+            .iter()
+            .map(|chapter| chapter.start)
+            .filter(|&start| start > part_start + max_part_secs / 2.0 && start <= limit)
+            .fold(None, |latest: Option<f64>, start| Some(latest.map_or(start, |latest| latest.max(start))));
+        let cut = chapter_cut.unwrap_or(limit);
+        points.push(cut);
+        part_start = cut;
+    }
+    points
+}
+
+/// Path of a part, numbered from 1
+fn part_path(output: &Path, number: usize) -> PathBuf {
+    let stem = output.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| "output".to_string());
+    let extension = output.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_else(|| "mp4".to_string());
+    output.with_file_name(format!("{}_part{:02}.{}", stem, number, extension))
+}
+
+/// Extended M3U playlist of the parts, with paths relative to the playlist
+pub fn playlist(parts: &[(PathBuf, f64)], title: &str) -> String {
+    let mut content = String::from("#EXTM3U\n");
+    for (index, (path, duration)) in parts.iter().enumerate() {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        content.push_str(&format!(
+            "#EXTINF:{},{} ({}/{})\n{}\n",
+            duration.round() as u64,
+            title,
+            index + 1,
+            parts.len(),
+            name
+        ));
+    }
+    content
+}
+
+/// Split the output if it exceeds the cap. Returns None when it fits, the
+/// original file is replaced by its parts otherwise.
+pub async fn split_if_needed(output: &Path, split: &SplitSettings) -> Result<Option<SplitOutput>> {
+    if !split.enabled {
+        return Ok(None);
+    }
+    split.validate()?;
+    let size = tokio::fs::metadata(output).await?.len();
+    if size <= split.max_size_bytes {
+        return Ok(None);
+    }
+
+    let duration = audio_probe::duration(output).await?;
+    let max_part_secs = duration * split.max_size_bytes as f64 / size as f64 * SIZE_MARGIN;
+    let chapters = chapters::probe(output).await.unwrap_or_else(|e| {
+        warn!("Failed to read chapters of {}: {}", output.display(), e);
+        Vec::new()
+    });
+    let points = split_points(duration, max_part_secs, &chapters);
+    if points.is_empty() {
+        return Ok(None);
+    }
+    info!(
+        "Splitting {} ({} bytes) into {} parts under {} bytes",
+        output.display(),
+        size,
+        points.len() + 1,
+        split.max_size_bytes
+    );
+
+    let times: Vec<String> = points.iter().map(|p| format!("{:.3}", p)).collect();
+    let pattern = {
+        let template = part_path(output, 0).to_string_lossy().to_string();
+        template.replace("_part00.", "_part%02d.")
+    };
+    let result = TokioCommand::new(crate::utils::tools::ffmpeg())
+        .arg("-y")
+        .arg("-i")
+        .arg(output)
+        .args(["-map", "0", "-c", "copy", "-f", "segment"])
+        .arg("-segment_times")
+        .arg(times.join(","))
+        .args(["-segment_start_number", "1", "-reset_timestamps", "1"])
+        .args(["-segment_format_options", "movflags=+faststart"])
+        .arg(&pattern)
+        .output()
+        .await?;
+    if !result.status.success() {
+        return Err(anyhow!("ffmpeg failed to split the output: {}", String::from_utf8_lossy(&result.stderr)));
+    }
+
+    let mut parts = Vec::new();
+    for number in 1.. {
+        let path = part_path(output, number);
+        if !path.exists() {
+            break;
+        }
+        let size = tokio::fs::metadata(&path).await?.len();
+        if size > split.max_size_bytes {
+            warn!("Part {} is {} bytes, over the cap: no keyframe near the cut", path.display(), size);
+        }
+        let duration = audio_probe::duration(&path).await.unwrap_or(0.0);
+        parts.push((path, duration));
+    }
+    if parts.is_empty() {
+        return Err(anyhow!("ffmpeg produced no parts"));
+    }
+
+    let title = output.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let playlist_path = output.with_extension("m3u");
+    tokio::fs::write(&playlist_path, playlist(&parts, &title)).await?;
+    tokio::fs::remove_file(output).await?;
+    info!("Output split into {} parts, playlist {}", parts.len(), playlist_path.display());
+
+    Ok(Some(SplitOutput { parts: parts.into_iter().map(|(path, _)| path).collect(), playlist: playlist_path }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(start: f64, end: f64) -> Chapter {
+        Chapter { start, end, title: String::new() }
+    }
+
+    #[test]
+    fn cuts_prefer_chapter_starts() {
+        assert!(split_points(100.0, 120.0, &[]).is_empty());
+        assert_eq!(split_points(250.0, 100.0, &[]), vec![100.0, 200.0]);
+
+        // The chapter at 80s fits the first part, the one at 30s would leave it too short
+        let chapters = [chapter(0.0, 30.0), chapter(30.0, 80.0), chapter(80.0, 250.0)];
+        assert_eq!(split_points(250.0, 100.0, &chapters), vec![80.0, 180.0]);
+    }
+
+    #[test]
+    fn parts_are_numbered_and_listed() {
+        let output = Path::new("/videos/talk_ru.mp4");
+        assert_eq!(part_path(output, 1), PathBuf::from("/videos/talk_ru_part01.mp4"));
+        let parts = vec![(part_path(output, 1), 80.4), (part_path(output, 2), 99.6)];
+        assert_eq!(
+            playlist(&parts, "talk_ru"),
+            "#EXTM3U\n#EXTINF:80,talk_ru (1/2)\ntalk_ru_part01.mp4\n#EXTINF:100,talk_ru (2/2)\ntalk_ru_part02.mp4\n"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Vertical placement of translated subtitles.
+//!
+//! Subtitles go to the bottom of the frame by default, which is where news
+//! tickers, lower-third name plates and burned-in captions live. Videos with
+//! heavy on-screen graphics can have them moved to the top, either always or
+//! only when sampled frames show a busy lower third. The position is applied
+//! to the ASS files the merge converts the subtitles through.
+
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::Path;
+use tokio::process::Command as TokioCommand;
+
+use crate::utils::audio_probe;
+
+/// Frames sampled for lower-third detection, evenly spread over the video
+const SAMPLE_COUNT: usize = 8;
+/// Size the sampled frames are scaled to, detail beyond this doesn't matter
+const FRAME_WIDTH: usize = 160;
+const FRAME_HEIGHT: usize = 90;
+/// Height of the top and bottom strips compared, a quarter of the frame
+const STRIP_ROWS: usize = FRAME_HEIGHT / 4;
+/// Luma step between neighbouring pixels that counts as an edge
+const EDGE_THRESHOLD: i16 = 40;
+/// Share of edge pixels above which a strip counts as busy; text and graphics
+/// have sharp edges, camera footage mostly doesn't
+const BUSY_DENSITY: f32 = 0.12;
+/// Share of samples that must have a busy lower third to move subtitles up
+const BUSY_SAMPLES: f32 = 0.35;
+
+/// Where translated subtitles are placed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtitlePosition {
+    #[default]
+    Bottom,
+    Top,
+    /// Top if the lower third of the video is busy with graphics, bottom otherwise
+    Auto,
+}
+
+/// Grab one grayscale frame, scaled down to FRAME_WIDTH x FRAME_HEIGHT
+async fn sample_frame(video_path: &Path, at_secs: f64) -> Result<Vec<u8>> {
+    let output = TokioCommand::new(crate::utils::tools::ffmpeg())
+        .args(["-v", "error", "-ss", &format!("{:.3}", at_secs), "-i"])
+        .arg(video_path)
+        .args([
+            "-frames:v",
+            "1",
+            "-vf",
+            &format!("scale={}:{},format=gray", FRAME_WIDTH, FRAME_HEIGHT),
+            "-f",
+            "rawvideo",
+            "-",
+        ])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!("ffmpeg failed to sample a frame: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    if output.stdout.len() < FRAME_WIDTH * FRAME_HEIGHT {
+        return Err(anyhow!("ffmpeg returned an incomplete frame at {:.1}s", at_secs));
+    }
+    Ok(output.stdout)
+}
+
+/// Share of pixels in the given rows that sit on a horizontal or vertical edge
+fn edge_density(frame: &[u8], rows: Range<usize>) -> f32 {
+    let pixel = |x: usize, y: usize| frame[y * FRAME_WIDTH + x] as i16;
+    let mut edges = 0usize;
+    let mut total = 0usize;
+    for y in rows.start..rows.end.min(FRAME_HEIGHT - 1) {
+        for x in 0..FRAME_WIDTH - 1 {
+            total += 1;
+            let here = pixel(x, y);
+            if (pixel(x + 1, y) - here).abs() > EDGE_THRESHOLD || (pixel(x, y + 1) - here).abs() > EDGE_THRESHOLD {
+                edges += 1;
+            }
+        }
+    }
+    if total == 0 {
+        return 0.0;
+    }
+    edges as f32 / total as f32
+}
+
+/// Whether graphics in the lower third would collide with bottom subtitles.
+/// The bottom strip has to be busy in absolute terms and clearly busier than
+/// the top one, so uniformly detailed footage doesn't trigger it.
+pub async fn lower_third_busy(video_path: &Path) -> Result<bool> {
+    let duration = audio_probe::duration(video_path).await?;
+    if duration <= 0.0 {
+        return Err(anyhow!("Video has no duration"));
+    }
+
+    let mut sampled = 0usize;
+    let mut busy = 0usize;
+    for i in 0..SAMPLE_COUNT {
+        let at = duration * (i as f64 + 0.5) / SAMPLE_COUNT as f64;
+        let frame = match sample_frame(video_path, at).await {
+            Ok(frame) => frame,
+            Err(e) => {
+                debug!("Skipping frame sample at {:.1}s: {}", at, e);
+                continue;
+            }
+        };
+        sampled += 1;
+        let top = edge_density(&frame, 0..STRIP_ROWS);
+        let bottom = edge_density(&frame, FRAME_HEIGHT - STRIP_ROWS..FRAME_HEIGHT);
+        debug!("Frame at {:.1}s: top edges {:.3}, bottom edges {:.3}", at, top, bottom);
+        if bottom > BUSY_DENSITY && bottom > top * 1.5 {
+            busy += 1;
+        }
+    }
+    if sampled == 0 {
+        return Err(anyhow!("Could not sample any frames"));
+    }
+    Ok(busy as f32 / sampled as f32 >= BUSY_SAMPLES)
+}
+
+/// Turn `Auto` into a concrete position by looking at the video. Detection
+/// failures keep the subtitles at the bottom.
+pub async fn resolve(position: SubtitlePosition, video_path: &Path) -> SubtitlePosition {
+    if position != SubtitlePosition::Auto {
+        return position;
+    }
+    match lower_third_busy(video_path).await {
+        Ok(true) => {
+            info!("Busy lower third detected, placing subtitles at the top");
+            SubtitlePosition::Top
+        }
+        Ok(false) => SubtitlePosition::Bottom,
+        Err(e) => {
+            warn!("Lower-third detection failed, keeping subtitles at the bottom: {}", e);
+            SubtitlePosition::Bottom
+        }
+    }
+}
+
+/// Set the alignment of every style in ASS content: bottom-centre (2) or
+/// top-centre (8). Override tags inside individual events are left alone.
+fn set_ass_alignment(content: &str, alignment: u8) -> String {
+    let mut in_styles = false;
+    let mut alignment_index: Option<usize> = None;
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('[') {
+            in_styles = trimmed.eq_ignore_ascii_case("[V4+ Styles]") || trimmed.eq_ignore_ascii_case("[V4 Styles]");
+        } else if in_styles {
+            if let Some(format) = trimmed.strip_prefix("Format:") {
+                alignment_index = format.split(',').position(|field| field.trim() == "Alignment");
+            } else if let (Some(style), Some(index)) = (trimmed.strip_prefix("Style:"), alignment_index) {
+                let mut fields: Vec<String> = style.split(',').map(str::to_string).collect();
+                if let Some(field) = fields.get_mut(index) {
+                    *field = alignment.to_string();
+                    lines.push(format!("Style:{}", fields.join(",")));
+                    continue;
+                }
+            }
+        }
+        lines.push(line.to_string());
+    }
+    let mut result = lines.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+/// Rewrite an ASS file so its subtitles appear at the given position
+pub async fn apply_to_ass(ass_path: &Path, position: SubtitlePosition) -> Result<()> {
+    let alignment = match position {
+        SubtitlePosition::Top => 8,
+        // Bottom is what ffmpeg writes already
+        SubtitlePosition::Bottom | SubtitlePosition::Auto => return Ok(()),
+    };
+    let content = tokio::fs::read_to_string(ass_path).await?;
+    tokio::fs::write(ass_path, set_ass_alignment(&content, alignment)).await?;
+    debug!("Subtitles in {} moved to alignment {}", ass_path.display(), alignment);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_ass_alignment() {
+        let ass = "[Script Info]\nScriptType: v4.00+\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize, Alignment, MarginV\nStyle: Default,Arial,16,2,10\n\n[Events]\nFormat: Layer, Start, End, Style, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,Hello, world\n";
+        let expected = ass.replace("Style: Default,Arial,16,2,10", "Style: Default,Arial,16,8,10");
+        assert_eq!(set_ass_alignment(ass, 8), expected);
+    }
+}
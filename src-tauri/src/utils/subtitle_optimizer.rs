@@ -0,0 +1,369 @@
+//! Line length and reading speed of translated subtitles.
+//!
+//! Translations run longer than the captions they were made from while the
+//! cues keep the timing of the original speech, so translated subtitles often
+//! end up as three-line blocks that flash by. Broadcast style guides ask for at
+//! most two lines of 42 characters read at no more than 17 characters per
+//! second. The optimizer splits cues that don't fit into two lines at sentence
+//! and clause boundaries, merges fragments too short to read, breaks lines at
+//! natural points and lets cues that read too fast run into the following gap.
+//! Chinese and Japanese count characters without spaces and get shorter lines
+//! and slower reading speeds. The dub is not affected, only the subtitle file
+//! that is muxed and exported.
+
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::utils::tts::tts::{vtt, SubtitleCue};
+
+/// Shortest time a cue stays on screen, five sixths of a second (20 frames at 24 fps)
+const MIN_DURATION: f32 = 0.833;
+/// Longest time a cue stays on screen, merged cues don't grow beyond it
+const MAX_DURATION: f32 = 7.0;
+/// Gap kept before the next cue when a cue is extended, two frames at 24 fps
+const MIN_GAP: f32 = 0.083;
+/// Cues further apart than this are never merged
+const MERGE_GAP: f32 = 0.5;
+
+/// Subtitle readability settings, limits not given follow the language
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReadabilitySettings {
+    pub enabled: bool,
+    pub max_chars_per_line: Option<usize>,
+    pub max_lines: usize,
+    /// Characters per second, spaces included
+    pub max_cps: Option<f32>,
+}
+
+impl Default for ReadabilitySettings {
+    fn default() -> Self {
+        Self { enabled: false, max_chars_per_line: None, max_lines: 2, max_cps: None }
+    }
+}
+
+/// Limits a subtitle file is optimized for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadabilityLimits {
+    pub max_chars_per_line: usize,
+    pub max_lines: usize,
+    pub max_cps: f32,
+    /// Lines break between any two characters rather than at spaces
+    pub unspaced: bool,
+}
+
+impl ReadabilityLimits {
+    /// Limits of a language with the user's overrides applied
+    pub fn for_language(language: Option<&str>, settings: &ReadabilitySettings) -> Self {
+        let base = language.map(|code| code.to_lowercase()).and_then(|code| code.split(['-', '_']).next().map(str::to_string));
+        let (max_chars_per_line, max_cps, unspaced) = match base.as_deref() {
+            Some("zh") => (16, 9.0, true),
+            Some("ja") => (13, 4.0, true),
+            Some("ko") => (16, 12.0, false),
+            _ => (42, 17.0, false),
+        };
+        Self {
+            max_chars_per_line: settings.max_chars_per_line.unwrap_or(max_chars_per_line).max(1),
+            max_lines: settings.max_lines.max(1),
+            max_cps: settings.max_cps.unwrap_or(max_cps).max(1.0),
+            unspaced,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.max_chars_per_line * self.max_lines
+    }
+}
+
+/// What the optimizer changed
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct ReadabilityReport {
+    pub cues_before: usize,
+    pub cues_after: usize,
+    pub split: usize,
+    pub merged: usize,
+    pub extended: usize,
+    /// Cues still read faster than the limit, there was no room to extend them
+    pub too_fast: usize,
+}
+
+fn char_len(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Words a line shouldn't end with, they belong to what follows
+fn is_weak_word(word: &str) -> bool {
+    const WEAK: &[&str] = &[
+        "a", "an", "the", "of", "to", "in", "on", "at", "for", "and", "or", "with", "by", "from", "my", "your", "our",
+        "в", "во", "на", "с", "со", "и", "а", "но", "к", "по", "о", "об", "у", "за", "из", "от", "до", "не",
+        "der", "die", "das", "den", "dem", "des", "ein", "eine", "einen", "und", "oder", "zu", "mit", "von", "im", "am", "für",
+        "le", "la", "les", "un", "une", "de", "du", "des", "et", "el", "los", "las", "y", "que", "il", "lo", "di", "e",
+    ];
+    let word = word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    WEAK.contains(&word.as_str())
+}
+
+/// Words a new sentence part starts with, a good place to split before
+fn is_conjunction(word: &str) -> bool {
+    const CONJUNCTIONS: &[&str] = &[
+        "and", "but", "or", "because", "so", "which", "that", "when", "while", "although",
+        "и", "а", "но", "или", "потому", "поэтому", "что", "который", "которая", "которые", "когда", "хотя", "если",
+        "und", "aber", "oder", "weil", "denn", "dass", "wenn", "als", "obwohl",
+        "et", "mais", "ou", "parce", "quand", "y", "pero", "porque", "cuando", "e", "ma", "perché", "quando",
+    ];
+    CONJUNCTIONS.contains(&word.to_lowercase().as_str())
+}
+
+/// Byte offsets where the text may be split or broken, with the text before
+/// and after trimmed around them
+fn break_points(text: &str, unspaced: bool) -> Vec<usize> {
+    if unspaced {
+        text.char_indices().map(|(i, _)| i).filter(|&i| i > 0).collect()
+    } else {
+        text.match_indices(' ').map(|(i, _)| i).collect()
+    }
+}
+
+/// How good a place to split or break the text is, lower is better
+fn break_penalty(before: &str, after: &str) -> f32 {
+    let last = before.trim_end().chars().last();
+    let last_word = before.split_whitespace().last().unwrap_or("");
+    let next_word = after.split_whitespace().next().unwrap_or("");
+    match last {
+        Some('.' | '!' | '?' | '…' | '。' | '！' | '？') => 0.0,
+        Some(',' | ';' | ':' | '—' | '–' | '，' | '、' | '；' | '：') => 0.15,
+        _ if is_conjunction(next_word) => 0.3,
+        _ if is_weak_word(last_word) => 1.0,
+        _ => 0.6,
+    }
+}
+
+fn split_at(text: &str, at: usize) -> (String, String) {
+    (text[..at].trim().to_string(), text[at..].trim().to_string())
+}
+
+/// Whether the text can be broken into lines within the limits
+fn fits(text: &str, limits: &ReadabilityLimits) -> bool {
+    let wrapped = wrap_lines(text, limits);
+    wrapped.lines().count() <= limits.max_lines && wrapped.lines().all(|line| char_len(line) <= limits.max_chars_per_line)
+}
+
+/// Split text too long for one cue into parts that each fit the lines,
+/// preferring sentence and clause boundaries
+fn split_text(text: &str, limits: &ReadabilityLimits) -> Vec<String> {
+    let capacity = limits.capacity();
+    let mut parts = Vec::new();
+    let mut rest = text.trim().to_string();
+    while !fits(&rest, limits) {
+        // A later break keeps more text together, a better boundary is worth
+        // giving up some of it. Text that makes two cues is split evenly so the
+        // second one isn't left as a fragment.
+        let halves = char_len(&rest) <= capacity * 2;
+        let best = break_points(&rest, limits.unspaced)
+            .into_iter()
+            .filter(|&at| !rest[..at].trim().is_empty() && fits(&rest[..at], limits))
+            .map(|at| {
+                let (before, after) = (&rest[..at], &rest[at..]);
+                let length = char_len(before.trim()) as f32;
+                let unused = if halves {
+                    (length - char_len(&rest) as f32 / 2.0).abs() / capacity as f32
+                } else {
+                    (capacity as f32 - length) / capacity as f32
+                };
+                (at, break_penalty(before, after) + unused)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(at, _)| at);
+        // A single word longer than the cue is cut where it overflows
+        let at = best.unwrap_or_else(|| rest.char_indices().nth(limits.max_chars_per_line).map(|(i, _)| i).unwrap_or(rest.len()));
+        let (before, after) = split_at(&rest, at);
+        parts.push(before);
+        rest = after;
+    }
+    if !rest.is_empty() {
+        parts.push(rest);
+    }
+    parts
+}
+
+/// Break the text of a cue into at most `max_lines` lines, as balanced as the
+/// line length and good break points allow. Text that doesn't fit is left to
+/// the last line.
+fn wrap_lines(text: &str, limits: &ReadabilityLimits) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(if limits.unspaced { "" } else { " " });
+    let text = if limits.unspaced { text } else { text.trim().to_string() };
+    let mut lines = Vec::new();
+    let mut rest = text;
+    while lines.len() + 1 < limits.max_lines && char_len(&rest) > limits.max_chars_per_line {
+        let lines_left = limits.max_lines - lines.len();
+        // Balanced lines: aim for an even share of what is left
+        let target = char_len(&rest).div_ceil(lines_left).min(limits.max_chars_per_line) as f32;
+        let best = break_points(&rest, limits.unspaced)
+            .into_iter()
+            .filter(|&at| {
+                let (before, after) = (rest[..at].trim(), rest[at..].trim());
+                !before.is_empty()
+                    && char_len(before) <= limits.max_chars_per_line
+                    && char_len(after) <= limits.max_chars_per_line * (lines_left - 1)
+            })
+            .map(|at| {
+                let imbalance = (char_len(rest[..at].trim()) as f32 - target).abs() / limits.max_chars_per_line as f32;
+                (at, break_penalty(&rest[..at], &rest[at..]) + imbalance * 2.0)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(at, _)| at);
+        let Some(at) = best else { break };
+        let (line, after) = split_at(&rest, at);
+        lines.push(line);
+        rest = after;
+    }
+    lines.push(rest);
+    lines.join("\n")
+}
+
+fn visible_chars(text: &str) -> usize {
+    text.chars().filter(|c| *c != '\n').count()
+}
+
+/// Make cues fit the line and reading speed limits
+pub fn optimize(cues: &[SubtitleCue], limits: &ReadabilityLimits) -> (Vec<SubtitleCue>, ReadabilityReport) {
+    let mut report = ReadabilityReport { cues_before: cues.len(), ..Default::default() };
+    let flat = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    // 1. Cues too long for the lines are split, time shared by characters
+    let mut split = Vec::with_capacity(cues.len());
+    for cue in cues {
+        let text = flat(&cue.text);
+        let parts = split_text(&text, limits);
+        if parts.len() <= 1 {
+            split.push(SubtitleCue { text, ..cue.clone() });
+            continue;
+        }
+        report.split += 1;
+        let total: usize = parts.iter().map(|part| char_len(part)).sum();
+        let duration = cue.end - cue.start;
+        let mut start = cue.start;
+        for (k, part) in parts.iter().enumerate() {
+            let end = if k + 1 == parts.len() {
+                cue.end
+            } else {
+                start + duration * char_len(part) as f32 / total.max(1) as f32
+            };
+            split.push(SubtitleCue {
+                start,
+                end,
+                text: part.clone(),
+                id: cue.id.as_ref().map(|id| if k == 0 { id.clone() } else { format!("{}.{}", id, k + 1) }),
+                speaker: cue.speaker.clone(),
+                notes: if k == 0 { cue.notes.clone() } else { Vec::new() },
+            });
+            start = end;
+        }
+    }
+
+    // 2. Fragments too short to read join the next cue of the same speaker,
+    // cues that read too fast only when the sentence goes on in the next one
+    let mut merged: Vec<SubtitleCue> = Vec::with_capacity(split.len());
+    for cue in split {
+        if let Some(last) = merged.last_mut() {
+            let too_fast = visible_chars(&last.text) as f32 / (last.end - last.start).max(0.01) > limits.max_cps;
+            let short = last.end - last.start < MIN_DURATION || (too_fast && break_penalty(&last.text, &cue.text) > 0.0);
+            let joined = if limits.unspaced { format!("{}{}", last.text, cue.text) } else { format!("{} {}", last.text, cue.text) };
+            if short
+                && last.speaker == cue.speaker
+                && cue.start - last.end <= MERGE_GAP
+                && cue.end - last.start <= MAX_DURATION
+                && fits(&joined, limits)
+                && cue.notes.is_empty()
+            {
+                last.text = joined;
+                last.end = cue.end;
+                report.merged += 1;
+                continue;
+            }
+        }
+        merged.push(cue);
+    }
+
+    // 3. Lines are broken, and cues that read too fast run into the gap after them
+    let next_starts: Vec<Option<f32>> = (0..merged.len()).map(|i| merged.get(i + 1).map(|next| next.start)).collect();
+    for (cue, next_start) in merged.iter_mut().zip(next_starts) {
+        cue.text = wrap_lines(&cue.text, limits);
+        let needed = (visible_chars(&cue.text) as f32 / limits.max_cps).clamp(MIN_DURATION, MAX_DURATION);
+        if cue.end - cue.start < needed {
+            let room = next_start.map(|next| next - MIN_GAP).unwrap_or(f32::INFINITY);
+            let end = (cue.start + needed).min(room);
+            if end > cue.end {
+                cue.end = end;
+                report.extended += 1;
+            }
+        }
+        if visible_chars(&cue.text) as f32 / (cue.end - cue.start).max(0.01) > limits.max_cps {
+            report.too_fast += 1;
+        }
+    }
+
+    report.cues_after = merged.len();
+    (merged, report)
+}
+
+/// Optimize a subtitle file into `<name>.readable.vtt` next to it
+pub async fn optimize_file(path: &Path, language: Option<&str>, settings: &ReadabilitySettings) -> Result<(PathBuf, ReadabilityReport)> {
+    let cues = vtt::parse_vtt(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let limits = ReadabilityLimits::for_language(language, settings);
+    let (cues, report) = optimize(&cues, &limits);
+    let output = path.with_extension("readable.vtt");
+    tokio::fs::write(&output, vtt::write_vtt_str(&cues)).await?;
+    info!(
+        "Subtitles optimized for {} chars x {} lines at {:.0} cps: {} -> {} cues, {} split, {} merged, {} extended, {} still too fast",
+        limits.max_chars_per_line,
+        limits.max_lines,
+        limits.max_cps,
+        report.cues_before,
+        report.cues_after,
+        report.split,
+        report.merged,
+        report.extended,
+        report.too_fast
+    );
+    Ok((output, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: f32, end: f32, text: &str) -> SubtitleCue {
+        SubtitleCue { start, end, text: text.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn long_cues_are_split_and_wrapped_into_two_lines() {
+        let limits = ReadabilityLimits::for_language(Some("en"), &ReadabilitySettings::default());
+        let cues = [
+            cue(
+                0.0,
+                8.0,
+                "We spent the whole summer rebuilding the engine of the old boat. When it finally started, \
+                 the entire harbour came out to watch and cheer for us.",
+            ),
+            cue(8.5, 8.9, "Really?"),
+            cue(9.0, 10.0, "Yes, really."),
+        ];
+        let (optimized, report) = optimize(&cues, &limits);
+
+        assert_eq!(report.split, 1);
+        assert_eq!(optimized[0].text, "We spent the whole summer rebuilding\nthe engine of the old boat.");
+        assert!(optimized[1].text.starts_with("When it finally started,"));
+        for cue in &optimized {
+            let lines: Vec<&str> = cue.text.lines().collect();
+            assert!(lines.len() <= 2 && lines.iter().all(|line| line.chars().count() <= 42), "{:?}", cue.text);
+        }
+        // The fragment too short to read joins the next cue
+        assert_eq!(optimized.last().unwrap().text, "Really? Yes, really.");
+        assert_eq!(report.merged, 1);
+        assert!(optimized.windows(2).all(|pair| pair[0].end <= pair[1].start));
+    }
+}
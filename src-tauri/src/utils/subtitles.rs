@@ -0,0 +1,336 @@
+//! Discovery of existing subtitles for a video.
+//!
+//! Before spending a Whisper call, look for subtitles the video already has:
+//! tracks uploaded by the creator, YouTube automatic captions reported by
+//! yt-dlp, SRT/VTT/ASS files linked in the description, and local files the user
+//! picks. A chosen source is downloaded or read and converted to VTT so it can
+//! replace the transcription step.
+
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::utils::ass;
+use crate::utils::charset;
+use crate::utils::common::sanitize_filename;
+use crate::utils::tts::tts::vtt::{self, SubtitleFormat};
+use crate::utils::tts::tts::SubtitleCue;
+
+/// Where a subtitle source comes from
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtitleKind {
+    /// Uploaded by the creator or the community
+    Uploaded,
+    /// Generated by YouTube speech recognition
+    Automatic,
+    /// File linked in the video description
+    DescriptionLink,
+    /// SRT, VTT or ASS file on disk, `url` is its path
+    LocalFile,
+}
+
+/// A subtitle file that can be used instead of transcription
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubtitleSource {
+    pub kind: SubtitleKind,
+    pub language: Option<String>,
+    pub name: String,
+    /// "vtt", "srt" or "ass"
+    pub format: String,
+    pub url: String,
+    /// Character encoding chosen by the user (e.g. "windows-1251"), detected when empty
+    #[serde(default)]
+    pub encoding: Option<String>,
+}
+
+/// Pick the best format yt-dlp offers for a language: VTT first, then SRT
+fn pick_track(tracks: &[serde_json::Value]) -> Option<(&str, &str, Option<&str>)> {
+    ["vtt", "srt"].iter().find_map(|wanted| {
+        tracks.iter().find_map(|track| {
+            let ext = track["ext"].as_str()?;
+            if ext != *wanted {
+                return None;
+            }
+            Some((ext, track["url"].as_str()?, track["name"].as_str()))
+        })
+    })
+}
+
+fn collect_tracks(info: &serde_json::Value, field: &str, kind: SubtitleKind) -> Vec<SubtitleSource> {
+    let Some(map) = info[field].as_object() else {
+        return Vec::new();
+    };
+
+    let mut sources: Vec<SubtitleSource> = map
+        .iter()
+        // "live_chat" is a replay of the chat, not subtitles
+        .filter(|(language, _)| language.as_str() != "live_chat")
+        .filter_map(|(language, tracks)| {
+            let (format, url, name) = pick_track(tracks.as_array()?)?;
+            Some(SubtitleSource {
+                kind,
+                language: Some(language.clone()),
+                name: name.unwrap_or(language).to_string(),
+                format: format.to_string(),
+                url: url.to_string(),
+                encoding: None,
+            })
+        })
+        .collect();
+    sources.sort_by(|a, b| a.language.cmp(&b.language));
+    sources
+}
+
+/// Subtitle tracks reported by `yt-dlp --dump-json`
+pub fn from_ytdlp_info(info: &serde_json::Value) -> Vec<SubtitleSource> {
+    let mut sources = collect_tracks(info, "subtitles", SubtitleKind::Uploaded);
+
+    // Automatic captions are listed for every translation language YouTube
+    // offers; only the track in the original language is useful
+    let original_lang = info["language"].as_str();
+    sources.extend(
+        collect_tracks(info, "automatic_captions", SubtitleKind::Automatic)
+            .into_iter()
+            .filter(|s| match (original_lang, s.language.as_deref()) {
+                (Some(orig), Some(lang)) => lang == orig || lang == format!("{}-orig", orig),
+                _ => true,
+            }),
+    );
+    sources
+}
+
+/// SRT/VTT/ASS links found in a video description
+pub fn from_description(description: &str) -> Vec<SubtitleSource> {
+    let re = Regex::new(r#"https?://[^\s"'<>]+?\.(srt|vtt|ass|ssa)(\?[^\s"'<>]*)?(\s|$)"#).unwrap();
+    let mut sources: Vec<SubtitleSource> = Vec::new();
+    for caps in re.captures_iter(description) {
+        let whole = caps.get(0).map(|m| m.as_str().trim()).unwrap_or_default();
+        if sources.iter().any(|s| s.url == whole) {
+            continue;
+        }
+        let name = whole
+            .split('?')
+            .next()
+            .and_then(|path| path.rsplit('/').next())
+            .unwrap_or(whole)
+            .to_string();
+        sources.push(SubtitleSource {
+            kind: SubtitleKind::DescriptionLink,
+            language: None,
+            name,
+            format: caps[1].to_lowercase(),
+            url: whole.to_string(),
+            encoding: None,
+        });
+    }
+    sources
+}
+
+/// Convert SRT content to WebVTT
+pub fn srt_to_vtt(srt: &str) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for line in srt.replace("\r\n", "\n").lines() {
+        if line.contains("-->") {
+            // SRT uses a comma as the decimal separator
+            vtt.push_str(&line.replace(',', "."));
+        } else {
+            vtt.push_str(line);
+        }
+        vtt.push('\n');
+    }
+    vtt
+}
+
+/// Turn downloaded subtitle text (VTT or SRT) into clean VTT
+pub fn to_clean_vtt(text: &str, format: &str) -> String {
+    let content = if format == "srt" || !text.trim_start().starts_with("WEBVTT") {
+        srt_to_vtt(text)
+    } else {
+        text.to_string()
+    };
+    strip_inline_tags(&content)
+}
+
+/// Remove inline timing and styling tags (`<00:00:01.500>`, `<c>`) from cue
+/// text; automatic captions use them for word-by-word highlighting
+fn strip_inline_tags(vtt: &str) -> String {
+    let re = Regex::new(r"<[^>]*>").unwrap();
+    vtt.lines()
+        .map(|line| if line.contains("-->") { line.to_string() } else { re.replace_all(line, "").to_string() })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Download a subtitle source, or read it from disk, into the temp dir as a VTT file
+pub async fn download_subtitle(source: &SubtitleSource, output_dir: &Path, base_name: &str) -> Result<PathBuf> {
+    info!("Downloading {:?} subtitles '{}' from {}", source.kind, source.name, source.url);
+
+    let temp_dir = output_dir.join("videonova_temp");
+    tokio::fs::create_dir_all(&temp_dir).await?;
+
+    // Transcode to UTF-8 without BOM, a BOM breaks the WEBVTT header check
+    let (text, format) = if source.kind == SubtitleKind::LocalFile {
+        let path = Path::new(&source.url);
+        let text = charset::read_subtitle_file(path, source.encoding.as_deref()).await?;
+        let format = if ass::is_ass(Some(path), &text) {
+            "ass"
+        } else {
+            SubtitleFormat::detect(Some(path), &text).extension()
+        };
+        (text, format.to_string())
+    } else {
+        let response = reqwest::get(&source.url).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!("Failed to download subtitles (HTTP {})", status));
+        }
+        let bytes = response.bytes().await?;
+        (charset::decode(&bytes, source.encoding.as_deref())?, source.format.clone())
+    };
+
+    let output_path = temp_dir.join(format!("{}.vtt", sanitize_filename(base_name)));
+    // The ASS script stays next to the VTT, the merge takes the styles from it
+    let script_path = output_path.with_extension("ass");
+    let content = if format == "ass" || format == "ssa" || ass::is_ass(None, &text) {
+        let script = ass::parse(&text)?;
+        tokio::fs::write(&script_path, script.to_string()).await?;
+        vtt::write_vtt_str(&script.to_cues())
+    } else {
+        let _ = tokio::fs::remove_file(&script_path).await;
+        to_clean_vtt(&text, &format)
+    };
+    if !content.contains("-->") {
+        return Err(anyhow!("Downloaded file does not contain any subtitles"));
+    }
+
+    tokio::fs::write(&output_path, charset::to_utf8_bytes(&content, false)).await?;
+    debug!("Subtitles saved to {}", output_path.display());
+    Ok(output_path)
+}
+
+/// How speakers of multi-speaker subtitles are shown in exported files
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeakerStyle {
+    /// WebVTT voice spans `<v Anna>`; SRT has none and gets prefixes instead
+    #[default]
+    VoiceSpan,
+    /// "Anna: " in front of the cue text
+    Prefix,
+    /// Drop the speakers
+    Hidden,
+}
+
+/// Speaker attribution of exported subtitles
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SpeakerLabels {
+    pub style: SpeakerStyle,
+    /// Display names of the detected speakers, e.g. "SPEAKER 1" -> "Анна"
+    pub names: HashMap<String, String>,
+}
+
+impl SpeakerLabels {
+    /// Whether VTT output would come out as it is
+    fn is_identity(&self) -> bool {
+        self.style == SpeakerStyle::VoiceSpan && self.names.is_empty()
+    }
+
+    /// Rename the speakers of the cues and put them in the chosen form
+    pub fn apply(&self, cues: &mut [SubtitleCue], format: SubtitleFormat) {
+        let style = match (self.style, format) {
+            (SpeakerStyle::VoiceSpan, SubtitleFormat::Srt) => SpeakerStyle::Prefix,
+            (style, _) => style,
+        };
+        for cue in cues.iter_mut() {
+            let Some(speaker) = cue.speaker.take() else { continue };
+            let name = self.names.get(&speaker).cloned().unwrap_or(speaker);
+            match style {
+                SpeakerStyle::VoiceSpan => cue.speaker = Some(name),
+                SpeakerStyle::Prefix => cue.text = format!("{}: {}", name, cue.text),
+                SpeakerStyle::Hidden => {}
+            }
+        }
+    }
+}
+
+/// Convert VTT content to SRT
+pub fn vtt_to_srt(vtt_content: &str) -> Result<String> {
+    let cues = vtt::parse_vtt_str(vtt_content).map_err(|e| anyhow!("Failed to parse subtitles: {}", e))?;
+    Ok(vtt::write_srt_str(&cues))
+}
+
+/// Write VTT subtitles to `path` in the given format with the speakers shown as
+/// `labels` say, returns the path with the extension of the format
+pub async fn export(vtt_path: &Path, path: &Path, format: SubtitleFormat, labels: &SpeakerLabels) -> Result<PathBuf> {
+    let path = path.with_extension(format.extension());
+    let content = tokio::fs::read_to_string(vtt_path).await?;
+    let content = if format == SubtitleFormat::Vtt && labels.is_identity() {
+        content
+    } else {
+        let mut cues = vtt::parse_vtt_str(&content).map_err(|e| anyhow!("Failed to parse subtitles: {}", e))?;
+        labels.apply(&mut cues, format);
+        vtt::write_str(&cues, format)
+    };
+    tokio::fs::write(&path, charset::to_utf8_bytes(&content, false)).await?;
+    debug!("Subtitles exported to {}", path.display());
+    Ok(path)
+}
+
+/// Combine translated and original subtitles into one bilingual track: every
+/// translated cue keeps its timing and gets the text of the original cues it
+/// overlaps on a second line
+pub fn bilingual_vtt(original_vtt: &str, translated_vtt: &str) -> Result<String> {
+    let original = vtt::parse_vtt_str(original_vtt).map_err(|e| anyhow!("Failed to parse original subtitles: {}", e))?;
+    let mut translated =
+        vtt::parse_vtt_str(translated_vtt).map_err(|e| anyhow!("Failed to parse translated subtitles: {}", e))?;
+    for cue in translated.iter_mut() {
+        let overlapping: Vec<&str> = original
+            .iter()
+            .filter(|source| source.start < cue.end && source.end > cue.start)
+            .map(|source| source.text.as_str())
+            .collect();
+        if !overlapping.is_empty() {
+            cue.text = format!("{}\n{}", cue.text, overlapping.join(" "));
+        }
+    }
+    Ok(vtt::write_vtt_str(&translated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bilingual_vtt_pairs_overlapping_cues() {
+        let original = "WEBVTT\n\n00:00:00.000 --> 00:00:02.000\nHello\n\n00:00:02.000 --> 00:00:04.000\nworld\n\n00:00:05.000 --> 00:00:06.000\nBye\n";
+        let translated = "WEBVTT\n\n00:00:00.000 --> 00:00:04.000\nПривет, мир\n\n00:00:05.000 --> 00:00:06.000\nПока\n";
+        let bilingual = bilingual_vtt(original, translated).unwrap();
+        assert!(bilingual.contains("Привет, мир\nHello world\n"));
+        assert!(bilingual.contains("Пока\nBye\n"));
+    }
+
+    #[test]
+    fn speakers_are_renamed_and_prefixed_in_srt() {
+        let translated = "WEBVTT\n\n00:00:00.000 --> 00:00:02.000\n<v SPEAKER_1>Привет\n\n00:00:02.000 --> 00:00:04.000\n<v SPEAKER_2>Пока\n";
+        let labels = SpeakerLabels {
+            names: HashMap::from([("SPEAKER_1".to_string(), "Анна".to_string())]),
+            ..Default::default()
+        };
+
+        let mut cues = vtt::parse_vtt_str(translated).unwrap();
+        labels.apply(&mut cues, SubtitleFormat::Vtt);
+        assert_eq!(cues[0].speaker.as_deref(), Some("Анна"));
+        assert_eq!(cues[1].speaker.as_deref(), Some("SPEAKER_2"));
+
+        let mut cues = vtt::parse_vtt_str(translated).unwrap();
+        labels.apply(&mut cues, SubtitleFormat::Srt);
+        let srt = vtt::write_srt_str(&cues);
+        assert!(srt.contains("Анна: Привет\n"));
+        assert!(srt.contains("SPEAKER_2: Пока\n"));
+    }
+}
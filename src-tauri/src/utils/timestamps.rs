@@ -0,0 +1,241 @@
+//! Post-correction of Whisper timestamps.
+//!
+//! Word timestamps from Whisper sometimes jitter backwards or overlap their
+//! neighbours, and segment boundaries inherit the noise. The timing math
+//! downstream (overlap policies, retiming, stretch factors) assumes cues that
+//! only move forward, so the verbose_json response is cleaned up before it
+//! becomes a VTT: word times are made monotonic with isotonic regression,
+//! segments are rebuilt from their words, and cue edges close to a pause in
+//! the audio are snapped onto it. Pauses come from ffmpeg's silencedetect.
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::path::Path;
+use tokio::process::Command as TokioCommand;
+
+use crate::utils::confidence::{CueConfidence, TranscriptConfidence};
+use crate::utils::tts::tts::{vtt, SubtitleCue};
+
+/// Shortest cue left after the correction, seconds
+const MIN_CUE_DURATION: f32 = 0.2;
+/// Cue edges this close to a pause move onto it, seconds
+const SNAP_TOLERANCE: f32 = 0.25;
+
+/// The parts of a verbose_json transcription that matter for timing
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerboseTranscription {
+    #[serde(default)]
+    pub segments: Vec<WhisperSegment>,
+    #[serde(default)]
+    pub words: Vec<WhisperWord>,
+    /// Language Whisper heard, by its English name ("english")
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WhisperSegment {
+    pub start: f32,
+    pub end: f32,
+    pub text: String,
+    #[serde(default)]
+    pub avg_logprob: f32,
+    #[serde(default)]
+    pub no_speech_prob: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WhisperWord {
+    pub start: f32,
+    pub end: f32,
+}
+
+/// Closest non-decreasing sequence in the least-squares sense (pool adjacent violators)
+fn isotonic(values: &[f32]) -> Vec<f32> {
+    // Blocks of (mean, length)
+    let mut blocks: Vec<(f32, usize)> = Vec::with_capacity(values.len());
+    for &value in values {
+        blocks.push((value, 1));
+        while blocks.len() > 1 && blocks[blocks.len() - 2].0 > blocks[blocks.len() - 1].0 {
+            let (mean, len) = blocks.pop().unwrap();
+            let last = blocks.last_mut().unwrap();
+            last.0 = (last.0 * last.1 as f32 + mean * len as f32) / (last.1 + len) as f32;
+            last.1 += len;
+        }
+    }
+    blocks.into_iter().flat_map(|(mean, len)| std::iter::repeat_n(mean, len)).collect()
+}
+
+/// Make word times monotonic and free of overlaps
+fn smooth_words(words: &mut [WhisperWord]) {
+    let starts = isotonic(&words.iter().map(|word| word.start).collect::<Vec<_>>());
+    let ends = isotonic(&words.iter().zip(&starts).map(|(word, start)| word.end.max(*start)).collect::<Vec<_>>());
+    for i in 0..words.len() {
+        let next_start = starts.get(i + 1).copied().unwrap_or(f32::INFINITY);
+        words[i].start = starts[i];
+        words[i].end = ends[i].min(next_start).max(starts[i]);
+    }
+}
+
+/// Rebuild segment times from their words and keep segments in order. Every
+/// cue comes with the segment it was made from.
+fn smooth_segments<'a>(segments: &'a [WhisperSegment], words: &[WhisperWord]) -> Vec<(SubtitleCue, &'a WhisperSegment)> {
+    // Words are matched to segments by their original times, before smoothing
+    let mut smoothed_words = words.to_vec();
+    smooth_words(&mut smoothed_words);
+
+    let mut cues: Vec<(SubtitleCue, &WhisperSegment)> = Vec::with_capacity(segments.len());
+    let mut next_word = 0;
+    for (index, segment) in segments.iter().enumerate() {
+        let first = next_word;
+        let is_last = index + 1 == segments.len();
+        while next_word < words.len() && (is_last || words[next_word].start < segment.end) {
+            next_word += 1;
+        }
+        let own_words = &smoothed_words[first..next_word];
+        let (mut start, mut end) = match (own_words.first(), own_words.last()) {
+            (Some(first), Some(last)) => (first.start, last.end),
+            _ => (segment.start, segment.end),
+        };
+        let prev_end = cues.last().map_or(0.0, |(cue, _)| cue.end);
+        start = start.max(prev_end);
+        end = end.max(start + MIN_CUE_DURATION);
+
+        let text = segment.text.trim().to_string();
+        if !text.is_empty() {
+            cues.push((SubtitleCue { start, end, text, ..SubtitleCue::default() }, segment));
+        }
+    }
+    cues
+}
+
+/// Edges of the pauses in an audio file
+#[derive(Debug, Clone, Default)]
+pub struct Pauses {
+    /// Where speech resumes after a pause
+    pub speech_starts: Vec<f32>,
+    /// Where a pause begins
+    pub speech_ends: Vec<f32>,
+}
+
+/// Move cue starts onto nearby speech onsets and cue ends onto nearby pause
+/// starts, without reordering cues
+fn snap_to_pauses(cues: &mut [SubtitleCue], pauses: &Pauses) {
+    let nearest = |boundaries: &[f32], time: f32, low: f32, high: f32| {
+        boundaries
+            .iter()
+            .copied()
+            .filter(|b| (b - time).abs() <= SNAP_TOLERANCE && *b >= low && *b <= high)
+            .min_by(|a, b| (a - time).abs().total_cmp(&(b - time).abs()))
+    };
+    for i in 0..cues.len() {
+        let prev_end = if i > 0 { cues[i - 1].end } else { 0.0 };
+        let next_start = cues.get(i + 1).map_or(f32::INFINITY, |cue| cue.start);
+        let cue = &mut cues[i];
+        if let Some(start) = nearest(&pauses.speech_starts, cue.start, prev_end, cue.end - MIN_CUE_DURATION) {
+            cue.start = start;
+        }
+        if let Some(end) = nearest(&pauses.speech_ends, cue.end, cue.start + MIN_CUE_DURATION, next_start) {
+            cue.end = end;
+        }
+    }
+}
+
+/// Pauses reported by ffmpeg's silencedetect
+fn parse_silencedetect(stderr: &str) -> Pauses {
+    let times = |marker: &str| -> Vec<f32> {
+        stderr
+            .lines()
+            .filter_map(|line| line.split_once(marker)?.1.split_whitespace().next()?.parse().ok())
+            .collect()
+    };
+    Pauses { speech_starts: times("silence_end: "), speech_ends: times("silence_start: ") }
+}
+
+/// Find pauses in an audio file
+pub async fn detect_pauses(audio_path: &Path) -> Result<Pauses> {
+    let output = TokioCommand::new(crate::utils::tools::ffmpeg())
+        .arg("-i")
+        .arg(audio_path)
+        .args(["-af", "silencedetect=noise=-35dB:d=0.3", "-f", "null", "-"])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!("ffmpeg silencedetect failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(parse_silencedetect(&String::from_utf8_lossy(&output.stderr)))
+}
+
+/// Turn a verbose_json transcription into a VTT with corrected timings and
+/// the confidence of its cues
+pub fn to_vtt(transcription: &VerboseTranscription, pauses: &Pauses) -> (String, TranscriptConfidence) {
+    let (mut cues, sources): (Vec<SubtitleCue>, Vec<&WhisperSegment>) =
+        smooth_segments(&transcription.segments, &transcription.words).into_iter().unzip();
+    snap_to_pauses(&mut cues, pauses);
+    let confidence = cues
+        .iter()
+        .zip(sources)
+        .map(|(cue, segment)| CueConfidence::new(cue.start, cue.end, segment.avg_logprob, segment.no_speech_prob))
+        .collect();
+    (vtt::write_vtt_str(&cues), TranscriptConfidence::new(confidence))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(start: f32, end: f32) -> WhisperWord {
+        WhisperWord { start, end }
+    }
+
+    fn segment(start: f32, end: f32, text: &str) -> WhisperSegment {
+        WhisperSegment { start, end, text: text.to_string(), avg_logprob: -0.2, no_speech_prob: 0.0 }
+    }
+
+    #[test]
+    fn test_isotonic_pools_violators() {
+        assert_eq!(isotonic(&[1.0, 3.0, 2.0, 4.0]), vec![1.0, 2.5, 2.5, 4.0]);
+        assert_eq!(isotonic(&[0.0, 1.0, 2.0]), vec![0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_segments_become_monotonic() {
+        let segments = vec![segment(0.0, 2.0, " Hello there"), segment(1.8, 3.0, " General Kenobi")];
+        // The third word jumps back before the second one
+        let words = vec![word(0.0, 0.6), word(1.2, 2.1), word(1.0, 1.5), word(2.2, 3.0)];
+        let cues: Vec<SubtitleCue> = smooth_segments(&segments, &words).into_iter().map(|(cue, _)| cue).collect();
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].text, "Hello there");
+        assert!(cues[0].end <= cues[1].start);
+        assert!(cues[1].start < cues[1].end);
+    }
+
+    #[test]
+    fn test_confidence_follows_the_cues() {
+        let mut unsure = segment(1.0, 2.0, " mumble");
+        unsure.avg_logprob = -1.5;
+        let transcription = VerboseTranscription {
+            segments: vec![segment(0.0, 1.0, " Hi"), segment(1.0, 1.0, " "), unsure],
+            words: Vec::new(),
+            language: None,
+        };
+        let (_, confidence) = to_vtt(&transcription, &Pauses::default());
+        // The empty segment has no cue and no score
+        assert_eq!(confidence.cues.len(), 2);
+        assert!(!confidence.cues[0].low && confidence.cues[1].low);
+        assert_eq!(confidence.cues[1].start, 1.0);
+    }
+
+    #[test]
+    fn test_snap_to_pauses() {
+        let mut cues = vec![
+            SubtitleCue { start: 0.1, end: 1.9, text: "a".to_string(), ..SubtitleCue::default() },
+            SubtitleCue { start: 2.0, end: 3.0, text: "b".to_string(), ..SubtitleCue::default() },
+        ];
+        snap_to_pauses(&mut cues, &parse_silencedetect("[silencedetect @ 0x1] silence_end: 0.05 | silence_duration: 0.05\n[silencedetect @ 0x1] silence_start: 1.95\n[silencedetect @ 0x1] silence_end: 2.6"));
+        assert_eq!(cues[0].start, 0.05);
+        assert_eq!(cues[0].end, 1.95);
+        // 2.6 is too far from both edges of the second cue
+        assert_eq!((cues[1].start, cues[1].end), (2.0, 3.0));
+    }
+}
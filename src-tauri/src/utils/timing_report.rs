@@ -0,0 +1,187 @@
+//! How far every dubbed segment had to be bent to fit its cue.
+//!
+//! The synchronizer reports the length of each synthesized fragment before
+//! and after fitting it to the cue. The report puts the numbers next to the
+//! original cue length, so the user can find the segments that were sped up
+//! the most and may read better with a shorter translation. Reports of
+//! pipeline jobs are kept as `timing_report.json` in the job directory.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use crate::utils::confidence::TranscriptConfidence;
+use crate::utils::jobs;
+use crate::utils::tts::tts::progress::ProgressReporter;
+use crate::utils::tts::tts::ProgressUpdate;
+
+const REPORT_FILE: &str = "timing_report.json";
+/// Speed-up above which a segment is flagged for manual editing
+const HEAVY_SPEEDUP: f32 = 1.3;
+
+/// Timing of a single dubbed segment
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SegmentTiming {
+    pub index: usize,
+    pub start: f32,
+    pub end: f32,
+    pub text: String,
+    /// Length of the original cue
+    pub cue_duration: f32,
+    /// Length of the speech as the TTS generated it
+    pub generated_duration: f32,
+    /// Length after fitting, including time borrowed from the gap after the cue
+    pub fitted_duration: f32,
+    /// fitted - cue, positive when the segment runs into the following gap
+    pub delta: f32,
+    /// generated / fitted: above 1 the speech was sped up, below 1 padded with silence
+    pub speed_factor: f32,
+    pub heavily_stretched: bool,
+    /// The transcript of the cue may be wrong, see [`crate::utils::confidence`]
+    #[serde(default)]
+    pub low_confidence: bool,
+}
+
+/// Totals over all segments
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TimingSummary {
+    pub segments: usize,
+    pub sped_up: usize,
+    pub heavily_stretched: usize,
+    pub max_speed_factor: f32,
+    pub mean_abs_delta: f32,
+    #[serde(default)]
+    pub low_confidence: usize,
+}
+
+/// Payload of the `segment-timing-report` event and of `get_timing_report`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimingReport {
+    pub job_id: Option<String>,
+    pub segments: Vec<SegmentTiming>,
+    pub summary: TimingSummary,
+}
+
+impl TimingReport {
+    pub fn new(job_id: Option<String>, mut segments: Vec<SegmentTiming>) -> Self {
+        segments.sort_by_key(|segment| segment.index);
+        let count = segments.len();
+        let summary = TimingSummary {
+            segments: count,
+            sped_up: segments.iter().filter(|s| s.speed_factor > 1.01).count(),
+            heavily_stretched: segments.iter().filter(|s| s.heavily_stretched).count(),
+            max_speed_factor: segments.iter().map(|s| s.speed_factor).fold(0.0, f32::max),
+            mean_abs_delta: if count == 0 {
+                0.0
+            } else {
+                segments.iter().map(|s| s.delta.abs()).sum::<f32>() / count as f32
+            },
+            low_confidence: segments.iter().filter(|s| s.low_confidence).count(),
+        };
+        Self { job_id, segments, summary }
+    }
+
+    /// Flag the segments whose transcript Whisper was unsure about
+    pub fn flag_low_confidence(&mut self, confidence: &TranscriptConfidence) {
+        for segment in &mut self.segments {
+            segment.low_confidence = confidence.is_low(segment.start);
+        }
+        self.summary.low_confidence = self.segments.iter().filter(|s| s.low_confidence).count();
+    }
+}
+
+/// Collects `SegmentTiming` updates of a synchronizer run
+#[derive(Default)]
+pub struct TimingCollector {
+    segments: Mutex<Vec<SegmentTiming>>,
+}
+
+impl TimingCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the report from everything collected so far
+    pub fn build_report(&self, job_id: Option<String>) -> TimingReport {
+        let segments = self.segments.lock().map(|segments| segments.clone()).unwrap_or_default();
+        TimingReport::new(job_id, segments)
+    }
+}
+
+impl ProgressReporter for TimingCollector {
+    fn report(&self, update: &ProgressUpdate) {
+        let ProgressUpdate::SegmentTiming { index, start, end, text, generated_duration, fitted_duration } = update else {
+            return;
+        };
+        let cue_duration = end - start;
+        let speed_factor = if *fitted_duration > 0.0 { generated_duration / fitted_duration } else { 1.0 };
+        let segment = SegmentTiming {
+            index: *index,
+            start: *start,
+            end: *end,
+            text: text.clone(),
+            cue_duration,
+            generated_duration: *generated_duration,
+            fitted_duration: *fitted_duration,
+            delta: fitted_duration - cue_duration,
+            speed_factor,
+            heavily_stretched: speed_factor > HEAVY_SPEEDUP,
+            low_confidence: false,
+        };
+        if let Ok(mut segments) = self.segments.lock() {
+            segments.push(segment);
+        }
+    }
+}
+
+/// Store the report of a job
+pub async fn save(app_handle: &tauri::AppHandle, job_id: &str, report: &TimingReport) -> Result<()> {
+    let dir = jobs::job_dir(app_handle, job_id)?;
+    tokio::fs::create_dir_all(&dir).await?;
+    let json = serde_json::to_string_pretty(report).map_err(|e| anyhow!("Failed to serialize timing report: {}", e))?;
+    tokio::fs::write(dir.join(REPORT_FILE), json).await?;
+    Ok(())
+}
+
+/// Load the report of a job
+pub async fn load(app_handle: &tauri::AppHandle, job_id: &str) -> Result<TimingReport> {
+    let path = jobs::job_dir(app_handle, job_id)?.join(REPORT_FILE);
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|_| anyhow!("No timing report for job {}", job_id))?;
+    serde_json::from_str(&content).map_err(|e| anyhow!("Corrupted timing report of job {}: {}", job_id, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(index: usize, generated: f32, fitted: f32) -> ProgressUpdate {
+        ProgressUpdate::SegmentTiming {
+            index,
+            start: index as f32 * 3.0,
+            end: index as f32 * 3.0 + 2.0,
+            text: format!("cue {}", index),
+            generated_duration: generated,
+            fitted_duration: fitted,
+        }
+    }
+
+    #[test]
+    fn test_collector_builds_report() {
+        let collector = TimingCollector::new();
+        collector.report(&timing(1, 3.0, 2.0));
+        collector.report(&timing(0, 1.5, 2.0));
+        collector.report(&ProgressUpdate::Finished);
+
+        let report = collector.build_report(Some("job".to_string()));
+        assert_eq!(report.segments.len(), 2);
+        assert_eq!(report.segments[0].index, 0);
+        assert!(!report.segments[0].heavily_stretched);
+        assert!((report.segments[1].speed_factor - 1.5).abs() < 1e-6);
+        assert!(report.segments[1].heavily_stretched);
+        assert_eq!(report.summary.sped_up, 1);
+        assert_eq!(report.summary.heavily_stretched, 1);
+        assert!((report.summary.max_speed_factor - 1.5).abs() < 1e-6);
+    }
+}
@@ -1,8 +1,18 @@
+//! External programs the pipeline runs: yt-dlp, ffmpeg and ffprobe.
+//!
+//! ffmpeg and ffprobe come from a static build that is downloaded per
+//! platform into the tools directory (in the app data dir once the app sets
+//! it), checked against the checksum its publisher lists, and preferred over
+//! whatever is on the PATH, so every machine runs the same known build. When
+//! the download fails, a PATH installation is used instead.
+
 use anyhow::{Context, Result, anyhow};
-use log::{debug, info};
+use log::{debug, info, warn};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use semver::Version;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
@@ -11,11 +21,22 @@ use tokio::sync::mpsc;
 use walkdir;
 use zip;
 
+/// Where a tool in use comes from
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolSource {
+    /// Downloaded into the tools directory
+    Managed,
+    /// Found on the PATH
+    Path,
+}
+
 // Structure to represent an external tool
 #[derive(Debug, Clone)]
 pub struct ExternalTool {
     pub name: String,
     pub path: PathBuf,
+    pub source: ToolSource,
     #[allow(dead_code)]
     pub description: String,
     #[allow(dead_code)]
@@ -27,84 +48,118 @@ pub struct ExternalTool {
 // Global storage for tools
 static TOOLS: Lazy<Mutex<Vec<ExternalTool>>> = Lazy::new(|| Mutex::new(Vec::new()));
 
+/// Directory of downloaded tools, the temp dir until the app sets its data dir
+static TOOLS_DIR: Lazy<Mutex<PathBuf>> = Lazy::new(|| Mutex::new(std::env::temp_dir().join("videonova").join("tools")));
+
+/// Tools every run needs
+const REQUIRED_TOOLS: &[&str] = &["yt-dlp", "ffmpeg", "ffprobe"];
+
 // Tool download URLs
 const YTDLP_DOWNLOAD_URL: &str = "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp";
-const FFMPEG_DOWNLOAD_URLS: &[(&str, &str)] = &[
-    (
-        "windows",
-        "https://github.com/BtbN/FFmpeg-Builds/releases/download/latest/ffmpeg-master-latest-win64-gpl.zip",
-    ),
-    ("macos", "https://evermeet.cx/ffmpeg/getrelease/zip"),
-    (
-        "linux",
-        "https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-amd64-static.tar.xz",
-    ),
-];
+
+/// How the download of an archive is checked
+#[derive(Debug, Clone, Copy)]
+enum Checksum {
+    /// `sha256sum` output listing the archive under `file_name`
+    Sha256List { url: &'static str, file_name: &'static str },
+    /// File holding the MD5 of the archive
+    Md5File(&'static str),
+    /// The publisher only signs its builds; the build is checked by running it,
+    /// and an ffmpeg on the PATH is used instead where there is one
+    Unpublished,
+}
+
+/// Archive of a static ffmpeg build
+#[derive(Debug, Clone, Copy)]
+struct FfmpegArchive {
+    url: &'static str,
+    /// Extension the archive is extracted by
+    extension: &'static str,
+    checksum: Checksum,
+}
+
+/// Archives with ffmpeg and ffprobe for this platform
+fn ffmpeg_archives() -> Result<&'static [FfmpegArchive]> {
+    const WINDOWS: &[FfmpegArchive] = &[FfmpegArchive {
+        url: "https://github.com/BtbN/FFmpeg-Builds/releases/download/latest/ffmpeg-master-latest-win64-gpl.zip",
+        extension: "zip",
+        checksum: Checksum::Sha256List {
+            url: "https://github.com/BtbN/FFmpeg-Builds/releases/download/latest/checksums.sha256",
+            file_name: "ffmpeg-master-latest-win64-gpl.zip",
+        },
+    }];
+    // evermeet ships ffmpeg and ffprobe as separate archives
+    const MACOS: &[FfmpegArchive] = &[
+        FfmpegArchive {
+            url: "https://evermeet.cx/ffmpeg/getrelease/ffmpeg/zip",
+            extension: "zip",
+            checksum: Checksum::Unpublished,
+        },
+        FfmpegArchive {
+            url: "https://evermeet.cx/ffmpeg/getrelease/ffprobe/zip",
+            extension: "zip",
+            checksum: Checksum::Unpublished,
+        },
+    ];
+    const LINUX_AMD64: &[FfmpegArchive] = &[FfmpegArchive {
+        url: "https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-amd64-static.tar.xz",
+        extension: "tar.xz",
+        checksum: Checksum::Md5File("https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-amd64-static.tar.xz.md5"),
+    }];
+    const LINUX_ARM64: &[FfmpegArchive] = &[FfmpegArchive {
+        url: "https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-arm64-static.tar.xz",
+        extension: "tar.xz",
+        checksum: Checksum::Md5File("https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-arm64-static.tar.xz.md5"),
+    }];
+
+    if cfg!(target_os = "windows") {
+        Ok(WINDOWS)
+    } else if cfg!(target_os = "macos") {
+        Ok(MACOS)
+    } else if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        Ok(LINUX_AMD64)
+    } else if cfg!(all(target_os = "linux", target_arch = "aarch64")) {
+        Ok(LINUX_ARM64)
+    } else {
+        Err(anyhow!("No ffmpeg build is available for this platform, install ffmpeg and ffprobe on the PATH"))
+    }
+}
+
+/// Keep downloaded tools in `dir` (the app data dir) instead of the temp dir
+pub fn set_tools_dir(dir: PathBuf) {
+    if let Ok(mut tools_dir) = TOOLS_DIR.lock() {
+        *tools_dir = dir;
+    }
+}
+
+fn tools_dir() -> PathBuf {
+    TOOLS_DIR.lock().map(|dir| dir.clone()).unwrap_or_else(|_| std::env::temp_dir().join("videonova").join("tools"))
+}
+
+fn executable_name(name: &str) -> String {
+    if cfg!(target_os = "windows") { format!("{}.exe", name) } else { name.to_string() }
+}
+
+/// Directory of the managed ffmpeg and ffprobe
+fn managed_ffmpeg_dir() -> PathBuf {
+    tools_dir().join("ffmpeg")
+}
 
 /// Initialize external tools (ffmpeg, yt-dlp)
 pub async fn init_tools(progress_sender: Option<mpsc::Sender<(String, f32)>>) -> Result<()> {
     // Check if tools are already in PATH
-    let ffmpeg_path_result = check_command_in_path("ffmpeg");
     let ytdlp_path_result = check_command_in_path("yt-dlp");
 
     // Initialize tools vector
     let mut initialized_tools = Vec::new();
 
-    // Handle ffmpeg
-    let _ffmpeg_path = match ffmpeg_path_result {
-        Ok(path) => {
-            info!("Found ffmpeg at {}", path.display());
-            if let Ok(version) = check_ffmpeg_version(&path) {
-                initialized_tools.push(ExternalTool {
-                    name: "ffmpeg".to_string(),
-                    path: path.clone(),
-                    description: "".to_string(),
-                    version: Some(version.clone()),
-                    min_version: Version::new(4, 0, 0),
-                });
-                info!("FFmpeg version: {}", version);
-                path
-            } else {
-                // Version check failed, download
-                info!("FFmpeg version check failed, will download");
-                if let Some(sender) = &progress_sender {
-                    sender
-                        .send(("Downloading FFmpeg...".to_string(), 20.0))
-                        .await?;
-                }
-                let downloaded_path = download_ffmpeg().await?;
-                let version = check_ffmpeg_version(&downloaded_path)?;
-                initialized_tools.push(ExternalTool {
-                    name: "ffmpeg".to_string(),
-                    path: downloaded_path.clone(),
-                    description: "".to_string(),
-                    version: Some(version.clone()),
-                    min_version: Version::new(4, 0, 0),
-                });
-                info!("Downloaded FFmpeg version: {}", version);
-                downloaded_path
-            }
-        }
-        Err(_) => {
-            info!("FFmpeg not found in PATH, will attempt to download");
-            if let Some(sender) = &progress_sender {
-                sender
-                    .send(("Downloading FFmpeg...".to_string(), 20.0))
-                    .await?;
-            }
-            let downloaded_path = download_ffmpeg().await?;
-            let version = check_ffmpeg_version(&downloaded_path)?;
-            initialized_tools.push(ExternalTool {
-                name: "ffmpeg".to_string(),
-                path: downloaded_path.clone(),
-                description: "".to_string(),
-                version: Some(version.clone()),
-                min_version: Version::new(4, 0, 0),
-            });
-            info!("Downloaded FFmpeg version: {}", version);
-            downloaded_path
-        }
-    };
+    // Handle ffmpeg and ffprobe, the managed build first
+    if let Some(sender) = &progress_sender {
+        sender
+            .send(("Preparing FFmpeg...".to_string(), 20.0))
+            .await?;
+    }
+    initialized_tools.extend(resolve_ffmpeg().await?);
 
     // Handle yt-dlp
     let _ytdlp_path = match ytdlp_path_result {
@@ -114,6 +169,7 @@ pub async fn init_tools(progress_sender: Option<mpsc::Sender<(String, f32)>>) ->
                 initialized_tools.push(ExternalTool {
                     name: "yt-dlp".to_string(),
                     path: path.clone(),
+                    source: ToolSource::Path,
                     description: "".to_string(),
                     version: Some(version.clone()),
                     min_version: Version::new(23, 11, 0),
@@ -133,6 +189,7 @@ pub async fn init_tools(progress_sender: Option<mpsc::Sender<(String, f32)>>) ->
                 initialized_tools.push(ExternalTool {
                     name: "yt-dlp".to_string(),
                     path: downloaded_path.clone(),
+                    source: ToolSource::Managed,
                     description: "".to_string(),
                     version: Some(version.clone()),
                     min_version: Version::new(23, 11, 0),
@@ -153,6 +210,7 @@ pub async fn init_tools(progress_sender: Option<mpsc::Sender<(String, f32)>>) ->
             initialized_tools.push(ExternalTool {
                 name: "yt-dlp".to_string(),
                 path: downloaded_path.clone(),
+                source: ToolSource::Managed,
                 description: "".to_string(),
                 version: Some(version.clone()),
                 min_version: Version::new(23, 11, 0),
@@ -195,18 +253,18 @@ fn check_command_in_path(command: &str) -> Result<PathBuf> {
     }
 }
 
-/// Check ffmpeg version
-fn check_ffmpeg_version(path: &Path) -> Result<Version> {
+/// Check the version of ffmpeg or ffprobe (`name`)
+fn check_ffmpeg_version(path: &Path, name: &str) -> Result<Version> {
     let output = Command::new(path)
         .args(["-version"])
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .output()
-        .context("Failed to execute ffmpeg")?;
+        .with_context(|| format!("Failed to execute {}", name))?;
 
     if output.status.success() {
         let version_str = String::from_utf8_lossy(&output.stdout);
-        let re = Regex::new(r"ffmpeg version (\d+\.\d+(?:\.\d+)?)")?;
+        let re = Regex::new(&format!(r"{} version (\d+\.\d+(?:\.\d+)?)", name))?;
         if let Some(caps) = re.captures(&version_str) {
             let version = caps.get(1).map_or("", |m| m.as_str());
             let parts: Vec<&str> = version.split('.').collect();
@@ -217,11 +275,11 @@ fn check_ffmpeg_version(path: &Path) -> Result<Version> {
             };
             Ok(Version::parse(&version_str)?)
         } else {
-            debug!("Could not parse ffmpeg version, using default");
+            debug!("Could not parse {} version, using default", name);
             Ok(Version::new(4, 0, 0))
         }
     } else {
-        Err(anyhow!("Failed to get ffmpeg version"))
+        Err(anyhow!("Failed to get {} version", name))
     }
 }
 
@@ -272,10 +330,7 @@ fn check_ytdlp_version(path: &Path) -> Result<Version> {
 
 /// Download yt-dlp
 async fn download_ytdlp() -> Result<PathBuf> {
-    // Use a default path for now
-    let app_dir = std::env::temp_dir().join("videonova");
-
-    let tools_dir = app_dir.join("tools");
+    let tools_dir = tools_dir();
     std::fs::create_dir_all(&tools_dir)?;
 
     let target_path = tools_dir.join(if cfg!(target_os = "windows") {
@@ -298,109 +353,163 @@ async fn download_ytdlp() -> Result<PathBuf> {
     Ok(target_path)
 }
 
-/// Extract downloaded FFmpeg archive
-async fn extract_ffmpeg(archive_path: &Path, target_dir: &Path) -> Result<PathBuf> {
-    let extension = archive_path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .ok_or_else(|| anyhow!("Invalid archive extension"))?;
+/// ffmpeg and ffprobe of the managed build, downloaded if they aren't there
+/// yet; the PATH installation if the download fails. Where the build can't be
+/// verified against a published checksum, a PATH installation is preferred to
+/// downloading it
+async fn resolve_ffmpeg() -> Result<Vec<ExternalTool>> {
+    let downloaded = ["ffmpeg", "ffprobe"].iter().all(|name| managed_ffmpeg_dir().join(executable_name(name)).exists());
+    if !downloaded && !ffmpeg_download_verified() {
+        match path_ffmpeg() {
+            Ok(tools) => return Ok(tools),
+            Err(e) => warn!("{}, downloading an FFmpeg build without a published checksum", e),
+        }
+    }
 
-    match extension {
-        "zip" => {
-            let file = std::fs::File::open(archive_path)?;
-            let mut archive = zip::ZipArchive::new(file)?;
+    let managed = match managed_ffmpeg().await {
+        Ok(tools) => return Ok(tools),
+        Err(e) => e,
+    };
+    warn!("Managed FFmpeg is unavailable ({}), looking on the PATH", managed);
+    path_ffmpeg().map_err(|e| anyhow!("{} and could not be downloaded: {}", e, managed))
+}
 
-            // Extract all files
-            archive.extract(target_dir)?;
+/// Whether every archive of this platform's build has a published checksum
+fn ffmpeg_download_verified() -> bool {
+    ffmpeg_archives()
+        .map(|archives| archives.iter().all(|archive| !matches!(archive.checksum, Checksum::Unpublished)))
+        .unwrap_or(true)
+}
 
-            // Find ffmpeg executable in extracted files
-            let ffmpeg_name = if cfg!(target_os = "windows") {
-                "ffmpeg.exe"
-            } else {
-                "ffmpeg"
-            };
+/// ffmpeg and ffprobe installed on the PATH
+fn path_ffmpeg() -> Result<Vec<ExternalTool>> {
+    let mut tools = Vec::new();
+    for name in ["ffmpeg", "ffprobe"] {
+        let path = check_command_in_path(name).map_err(|_| anyhow!("{} is not installed", name))?;
+        let version = check_ffmpeg_version(&path, name)?;
+        info!("Using {} {} from the PATH at {}", name, version, path.display());
+        tools.push(ExternalTool {
+            name: name.to_string(),
+            path,
+            source: ToolSource::Path,
+            description: "".to_string(),
+            version: Some(version),
+            min_version: Version::new(4, 0, 0),
+        });
+    }
+    Ok(tools)
+}
 
-            // Recursively find ffmpeg executable
-            let mut ffmpeg_path = None;
-            for entry in walkdir::WalkDir::new(target_dir) {
-                let entry = entry?;
-                if entry.file_name().to_string_lossy() == ffmpeg_name {
-                    ffmpeg_path = Some(entry.path().to_path_buf());
-                    break;
-                }
-            }
+async fn managed_ffmpeg() -> Result<Vec<ExternalTool>> {
+    let dir = managed_ffmpeg_dir();
+    let paths = ["ffmpeg", "ffprobe"].map(|name| dir.join(executable_name(name)));
+    if !paths.iter().all(|path| path.exists()) {
+        download_ffmpeg(&dir).await?;
+    }
 
-            let ffmpeg_path =
-                ffmpeg_path.ok_or_else(|| anyhow!("FFmpeg executable not found in archive"))?;
+    let mut tools = Vec::new();
+    for (name, path) in ["ffmpeg", "ffprobe"].into_iter().zip(paths) {
+        let version = check_ffmpeg_version(&path, name)?;
+        info!("Using managed {} {} at {}", name, version, path.display());
+        tools.push(ExternalTool {
+            name: name.to_string(),
+            path,
+            source: ToolSource::Managed,
+            description: "".to_string(),
+            version: Some(version),
+            min_version: Version::new(4, 0, 0),
+        });
+    }
+    Ok(tools)
+}
 
-            // Make executable on Unix-like systems
-            #[cfg(not(target_os = "windows"))]
-            std::fs::set_permissions(&ffmpeg_path, std::fs::Permissions::from_mode(0o755))?;
+/// Checksum listed for `file_name` in `sha256sum` output
+fn listed_checksum(list: &str, file_name: &str) -> Option<String> {
+    list.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == file_name).then(|| hash.to_lowercase())
+    })
+}
 
-            Ok(ffmpeg_path)
+async fn verify_checksum(content: &[u8], checksum: Checksum) -> Result<()> {
+    let (expected, actual) = match checksum {
+        Checksum::Sha256List { url, file_name } => {
+            let list = reqwest::get(url).await?.error_for_status()?.text().await?;
+            let expected = listed_checksum(&list, file_name)
+                .ok_or_else(|| anyhow!("{} is not listed in {}", file_name, url))?;
+            (expected, hex::encode(Sha256::digest(content)))
+        }
+        Checksum::Md5File(url) => {
+            let text = reqwest::get(url).await?.error_for_status()?.text().await?;
+            let expected = text.split_whitespace().next().unwrap_or_default().to_lowercase();
+            (expected, format!("{:x}", md5::compute(content)))
+        }
+        Checksum::Unpublished => {
+            warn!("No checksum is published for this FFmpeg build, it is only checked by running it");
+            return Ok(());
         }
-        "xz" => {
-            use std::process::Command;
+    };
+    if expected != actual {
+        return Err(anyhow!("Checksum mismatch: expected {}, got {}", expected, actual));
+    }
+    Ok(())
+}
 
+/// Extract an archive into `target_dir`
+fn extract_archive(archive_path: &Path, extension: &str, target_dir: &Path) -> Result<()> {
+    match extension {
+        "zip" => {
+            let file = std::fs::File::open(archive_path)?;
+            let mut archive = zip::ZipArchive::new(file)?;
+            archive.extract(target_dir)?;
+            Ok(())
+        }
+        "tar.xz" => {
             // For Linux, we use tar command line tool as it's more reliable
             let status = Command::new("tar")
-                .args(&["xf", archive_path.to_str().unwrap()])
+                .arg("xf")
+                .arg(archive_path)
                 .current_dir(target_dir)
                 .status()?;
-
             if !status.success() {
                 return Err(anyhow!("Failed to extract tar.xz archive"));
             }
-
-            let ffmpeg_path = target_dir.join("ffmpeg");
-            if !ffmpeg_path.exists() {
-                return Err(anyhow!("FFmpeg executable not found after extraction"));
-            }
-
-            // Make executable
-            std::fs::set_permissions(&ffmpeg_path, std::fs::Permissions::from_mode(0o755))?;
-
-            Ok(ffmpeg_path)
+            Ok(())
         }
         _ => Err(anyhow!("Unsupported archive format: {}", extension)),
     }
 }
 
-/// Download FFmpeg
-async fn download_ffmpeg() -> Result<PathBuf> {
-    // Use a default path for now
-    let app_dir = std::env::temp_dir().join("videonova");
-
-    let tools_dir = app_dir.join("tools");
-    std::fs::create_dir_all(&tools_dir)?;
-
-    // Get download URL for current platform
-    let (_, url) = FFMPEG_DOWNLOAD_URLS
-        .iter()
-        .find(|(platform, _)| match *platform {
-            "windows" => cfg!(target_os = "windows"),
-            "macos" => cfg!(target_os = "macos"),
-            "linux" => cfg!(target_os = "linux"),
-            _ => false,
-        })
-        .ok_or_else(|| anyhow!("Unsupported platform"))?;
-
-    // Download the archive
-    let response = reqwest::get(*url).await?;
-    let content = response.bytes().await?;
-
-    // Create a temporary file for the archive
-    let temp_dir = tempfile::tempdir()?;
-    let archive_path = temp_dir.path().join("ffmpeg_archive");
-    std::fs::write(&archive_path, content)?;
-
-    // Extract the archive
-    let ffmpeg_path = extract_ffmpeg(&archive_path, &tools_dir).await?;
-
-    // Clean up temporary directory
-    drop(temp_dir);
+/// Download the static build for this platform and put ffmpeg and ffprobe into `dir`
+async fn download_ffmpeg(dir: &Path) -> Result<()> {
+    let staging = tempfile::tempdir()?;
+    for (index, archive) in ffmpeg_archives()?.iter().enumerate() {
+        info!("Downloading FFmpeg from {}", archive.url);
+        let content = reqwest::get(archive.url).await?.error_for_status()?.bytes().await?;
+        verify_checksum(&content, archive.checksum)
+            .await
+            .with_context(|| format!("Failed to verify {}", archive.url))?;
+
+        let archive_path = staging.path().join(format!("ffmpeg-{}.{}", index, archive.extension));
+        std::fs::write(&archive_path, &content)?;
+        extract_archive(&archive_path, archive.extension, staging.path())?;
+    }
 
-    Ok(ffmpeg_path)
+    std::fs::create_dir_all(dir)?;
+    for name in ["ffmpeg", "ffprobe"] {
+        let file_name = executable_name(name);
+        let found = walkdir::WalkDir::new(staging.path())
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .find(|entry| entry.file_type().is_file() && entry.file_name().to_string_lossy() == file_name.as_str())
+            .ok_or_else(|| anyhow!("{} not found in the downloaded archive", file_name))?;
+        let target = dir.join(&file_name);
+        std::fs::copy(found.path(), &target)?;
+        #[cfg(not(target_os = "windows"))]
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o755))?;
+    }
+    info!("FFmpeg is installed in {}", dir.display());
+    Ok(())
 }
 
 /// Get tool path by name
@@ -412,3 +521,52 @@ pub fn get_tool_path(name: &str) -> Option<PathBuf> {
         .find(|tool| tool.name == name)
         .map(|tool| tool.path.clone())
 }
+
+/// Path of ffmpeg to run, the bare name until the tools are initialized
+pub fn ffmpeg() -> PathBuf {
+    get_tool_path("ffmpeg").unwrap_or_else(|| PathBuf::from("ffmpeg"))
+}
+
+/// Path of ffprobe to run, the bare name until the tools are initialized
+pub fn ffprobe() -> PathBuf {
+    get_tool_path("ffprobe").unwrap_or_else(|| PathBuf::from("ffprobe"))
+}
+
+/// A required tool and where it comes from
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolStatus {
+    pub name: String,
+    pub path: Option<PathBuf>,
+    pub version: Option<String>,
+    /// None when the tool is missing
+    pub source: Option<ToolSource>,
+}
+
+/// Status of every tool the pipeline needs
+pub fn tools_status() -> Vec<ToolStatus> {
+    let tools = TOOLS.lock().map(|tools| tools.clone()).unwrap_or_default();
+    REQUIRED_TOOLS
+        .iter()
+        .map(|name| match tools.iter().find(|tool| tool.name == *name) {
+            Some(tool) => ToolStatus {
+                name: tool.name.clone(),
+                path: Some(tool.path.clone()),
+                version: tool.version.as_ref().map(Version::to_string),
+                source: Some(tool.source),
+            },
+            None => ToolStatus { name: name.to_string(), path: None, version: None, source: None },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_are_found_in_sha256sum_lists() {
+        let list = "0a1b2c  ffmpeg-master-latest-win64-gpl-shared.zip\nDEADBEEF *ffmpeg-master-latest-win64-gpl.zip\n";
+        assert_eq!(listed_checksum(list, "ffmpeg-master-latest-win64-gpl.zip").as_deref(), Some("deadbeef"));
+        assert_eq!(listed_checksum(list, "ffmpeg-linux64-gpl.tar.xz"), None);
+    }
+}
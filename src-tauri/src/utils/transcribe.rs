@@ -1,43 +1,68 @@
 use anyhow::{anyhow, Result};
-use log::{info, error};
+use log::{info, warn, error};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use tokio::fs::{self, File};
 use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc;
+use tokio::process::Command as TokioCommand;
+use crate::utils::audio_probe;
 use crate::utils::common::{sanitize_filename, check_file_exists_and_valid};
+use crate::utils::conditioning::{self, TranscriptionConditioning};
+use crate::utils::http_retry;
+use crate::utils::confidence::{self, CueConfidence, TranscriptConfidence};
+use crate::utils::openai_connection::{self, OpenAiAuth};
+use crate::utils::timestamps;
+use crate::utils::transcript_cleanup::{self, TranscriptCleanup};
+use crate::utils::tts::tts::timeline::CueWindow;
+use crate::utils::tts::tts::vad::{self, VoiceActivity};
+use crate::utils::tts::tts::SubtitleCue;
+
+/// Media longer than this is transcribed in chunks, seconds
+const CHUNKED_AFTER_SECS: f64 = 20.0 * 60.0;
+/// Length of one chunk, seconds
+const CHUNK_SECS: f64 = 10.0 * 60.0;
+/// Whisper rejects larger uploads, bigger files are transcribed in chunks
+const WHISPER_MAX_BYTES: u64 = 25 * 1024 * 1024;
+/// A chunk ends in the longest pause this far before its full length, seconds
+const SPLIT_SEARCH_SECS: f64 = 60.0;
+/// Audio added on both sides of a chunk, so a word at a cut is heard whole by
+/// one of the chunks, seconds
+const CHUNK_OVERLAP_SECS: f64 = 2.0;
+/// Length of the sample the source language is detected from, seconds
+const DETECTION_SAMPLE_SECS: f64 = 30.0;
+const VTT_HEADER: &str = "WEBVTT\n\n";
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TranscriptionProgress {
     pub status: String,
     pub progress: f32,
+    /// Set when a chunk of long media finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial: Option<PartialTranscript>,
 }
 
 // Добавляем атрибут #[allow(dead_code)] к неиспользуемым вариантам enum
 #[allow(dead_code)]
+#[derive(Default)]
 pub enum ResponseFormat {
     Json,
     Text,
     Srt,
     VerboseJson,
+    #[default]
     Vtt,
 }
 
-impl Default for ResponseFormat {
-    fn default() -> Self {
-        ResponseFormat::Vtt
-    }
-}
-
-impl ToString for ResponseFormat {
-    fn to_string(&self) -> String {
-        match self {
-            ResponseFormat::Json => "json".to_string(),
-            ResponseFormat::Text => "text".to_string(),
-            ResponseFormat::Srt => "srt".to_string(),
-            ResponseFormat::VerboseJson => "verbose_json".to_string(),
-            ResponseFormat::Vtt => "vtt".to_string(),
-        }
+impl std::fmt::Display for ResponseFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ResponseFormat::Json => "json",
+            ResponseFormat::Text => "text",
+            ResponseFormat::Srt => "srt",
+            ResponseFormat::VerboseJson => "verbose_json",
+            ResponseFormat::Vtt => "vtt",
+        })
     }
 }
 
@@ -99,13 +124,24 @@ impl MultipartFormBuilder {
     }
 }
 
+/// How the audio is prepared for Whisper and its result cleaned up
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptionOptions {
+    /// Language of the speech, None lets Whisper detect it
+    pub language: Option<String>,
+    pub conditioning: TranscriptionConditioning,
+    pub cleanup: TranscriptCleanup,
+    pub voice_activity: VoiceActivity,
+}
+
 pub async fn transcribe_audio(
     audio_path: &Path,
     output_dir: &Path,
     api_key: &str,
-    language: Option<String>,
+    options: &TranscriptionOptions,
     progress_sender: Option<mpsc::Sender<TranscriptionProgress>>,
 ) -> Result<PathBuf> {
+    let TranscriptionOptions { language, conditioning, cleanup, voice_activity } = options;
     info!("Starting transcription process");
     
     // Validate API key
@@ -114,17 +150,7 @@ pub async fn transcribe_audio(
         return Err(anyhow!("OpenAI API key is required for transcription"));
     }
     
-    // Always use VTT format
-    let format = ResponseFormat::Vtt;
-    
-    // Расширение выходного файла зависит от формата ответа
-    let file_extension = match format {
-        ResponseFormat::Json => "json",
-        ResponseFormat::Text => "txt",
-        ResponseFormat::Srt => "srt",
-        ResponseFormat::VerboseJson => "json",
-        ResponseFormat::Vtt => "vtt",
-    };
+    let file_extension = "vtt";
     
     // Create output directory if it doesn't exist
     if let Err(e) = fs::create_dir_all(output_dir).await {
@@ -181,13 +207,172 @@ pub async fn transcribe_audio(
             .send(TranscriptionProgress {
                 status: "Preparing transcription".to_string(),
                 progress: 0.0,
+                partial: None,
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
+    }
+
+    // Whisper получает подготовленную копию, оригинал остается для сведения
+    let upload_path = if conditioning.enabled {
+        if let Some(sender) = &progress_sender {
+            sender
+                .send(TranscriptionProgress {
+                    status: "Conditioning audio for transcription".to_string(),
+                    progress: 2.0,
+                    partial: None,
+                })
+                .await
+                .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
+        }
+        match conditioning::condition(audio_path, &temp_dir, conditioning).await {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("Audio conditioning failed, transcribing the original audio: {}", e);
+                audio_path.to_path_buf()
+            }
+        }
+    } else {
+        audio_path.to_path_buf()
+    };
+
+    let client = openai_connection::client();
+
+    // Long passages without speech (music, intros, silence) aren't uploaded,
+    // Whisper only makes up text for them
+    let non_speech = if voice_activity.enabled {
+        match vad::speech_in_file(&upload_path).await {
+            Ok(spans) => vad::non_speech(&spans, voice_activity.min_gap),
+            Err(e) => {
+                warn!("Voice activity detection failed, transcribing all of the audio: {}", e);
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    // Multi-hour media, media too big for a single upload and media with
+    // passages to skip is transcribed in chunks, its VTT grows as they finish
+    let duration = audio_probe::duration(&upload_path).await.ok();
+    let too_big = fs::metadata(&upload_path).await.is_ok_and(|meta| meta.len() > WHISPER_MAX_BYTES);
+    let chunked = |duration: &f64| *duration > CHUNKED_AFTER_SECS || too_big || !non_speech.is_empty();
+    if let Some(duration) = duration.filter(chunked) {
+        let audio = ChunkedAudio { path: &upload_path, duration, non_speech: &non_speech };
+        let confidence = transcribe_in_chunks(&client, audio, &output_path, api_key, options, progress_sender.as_ref()).await?;
+        return finish_transcription(&output_path, &confidence, api_key, cleanup, progress_sender.as_ref()).await;
+    }
+
+    // Send progress update - preparing the request
+    if let Some(sender) = &progress_sender {
+        sender
+            .send(TranscriptionProgress {
+                status: "Preparing request to OpenAI".to_string(),
+                progress: 5.0,
+                partial: None,
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
+    }
+
+    let mut transcription = request_transcription(&client, &upload_path, api_key, language.as_deref()).await?;
+
+    // Send progress update
+    if let Some(sender) = &progress_sender {
+        sender
+            .send(TranscriptionProgress {
+                status: "Processing transcription result".to_string(),
+                progress: 90.0,
+                partial: None,
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
+    }
+
+    // Pauses in the audio anchor the corrected cue edges
+    let pauses = match timestamps::detect_pauses(&upload_path).await {
+        Ok(pauses) => pauses,
+        Err(e) => {
+            warn!("Pause detection failed, cue edges stay where Whisper put them: {}", e);
+            timestamps::Pauses::default()
+        }
+    };
+    transcript_cleanup::clean(&mut transcription, &pauses, cleanup);
+    drop_non_speech(&mut transcription, &non_speech);
+    let (content, confidence) = timestamps::to_vtt(&transcription, &pauses);
+
+    // Send progress update
+    if let Some(sender) = &progress_sender {
+        sender
+            .send(TranscriptionProgress {
+                status: "Saving transcription file".to_string(),
+                progress: 95.0,
+                partial: None,
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
+    }
+
+    // Write content to file
+    let mut output_file = File::create(&output_path).await?;
+    output_file.write_all(content.as_bytes()).await?;
+    finish_transcription(&output_path, &confidence, api_key, cleanup, progress_sender.as_ref()).await
+}
+
+/// Restore punctuation if asked to, save the confidence sidecar and report completion
+async fn finish_transcription(
+    output_path: &Path,
+    confidence: &TranscriptConfidence,
+    api_key: &str,
+    cleanup: &TranscriptCleanup,
+    progress_sender: Option<&mpsc::Sender<TranscriptionProgress>>,
+) -> Result<PathBuf> {
+    if cleanup.restore_punctuation {
+        if let Some(sender) = progress_sender {
+            sender
+                .send(TranscriptionProgress {
+                    status: "Restoring punctuation".to_string(),
+                    progress: 97.0,
+                    partial: None,
+                })
+                .await
+                .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
+        }
+        // Unpunctuated subtitles are still usable, the job goes on without it
+        if let Err(e) = transcript_cleanup::restore_punctuation(output_path, api_key, &cleanup.punctuation_model).await {
+            warn!("Punctuation restoration failed, the transcript stays as Whisper wrote it: {}", e);
+        }
+    }
+    if let Err(e) = confidence::save(output_path, confidence).await {
+        warn!("Failed to save transcript confidence: {}", e);
+    }
+    info!("{} of {} cues have low confidence", confidence.low_count, confidence.cues.len());
+
+    // Send completion progress
+    if let Some(sender) = progress_sender {
+        sender
+            .send(TranscriptionProgress {
+                status: "Transcription complete".to_string(),
+                progress: 100.0,
+                partial: None,
             })
             .await
             .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
     }
 
+    info!("Transcription completed successfully");
+    Ok(output_path.to_path_buf())
+}
+
+/// Send one audio file to Whisper and return its verbose transcription
+async fn request_transcription(
+    client: &reqwest::Client,
+    upload_path: &Path,
+    api_key: &str,
+    language: Option<&str>,
+) -> Result<timestamps::VerboseTranscription> {
     // Читаем файл целиком в память
-    let file_content = match tokio::fs::read(audio_path).await {
+    let file_content = match tokio::fs::read(upload_path).await {
         Ok(content) => content,
         Err(e) => {
             error!("Failed to read audio file: {}", e);
@@ -197,14 +382,16 @@ pub async fn transcribe_audio(
 
     // Создаем multipart form-data с помощью builder'а
     let mut form = MultipartFormBuilder::new();
-    let filename = audio_path.file_name().unwrap().to_string_lossy();
-    
-    // Добавляем все поля
+    let filename = upload_path.file_name().unwrap().to_string_lossy();
+
+    // Word timestamps come with verbose_json; the VTT is built from them after correction
     form.add_text("model", "whisper-1")
-        .add_text("response_format", &format.to_string());
+        .add_text("response_format", &ResponseFormat::VerboseJson.to_string())
+        .add_text("timestamp_granularities[]", "word")
+        .add_text("timestamp_granularities[]", "segment");
 
     // Добавляем язык если есть
-    if let Some(lang) = &language {
+    if let Some(lang) = language {
         form.add_text("language", lang);
     }
 
@@ -213,90 +400,436 @@ pub async fn transcribe_audio(
 
     // Получаем финальное тело запроса
     let body = form.finish();
-    
-    // Send progress update - preparing the request
-    if let Some(sender) = &progress_sender {
-        sender
-            .send(TranscriptionProgress {
-                status: "Preparing request to OpenAI".to_string(),
-                progress: 5.0,
-            })
-            .await
-            .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
-    }
-    
-    // Create the client and request
-    let client = reqwest::Client::new();
-    
+
     // Отправляем запрос
     info!("Sending request to OpenAI Whisper API");
-    
-    let response_result = client
-        .post("https://api.openai.com/v1/audio/transcriptions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", form.content_type())
-        .body(body)
-        .send()
-        .await;
-    
-    match response_result {
-        Ok(response) => {
-            let status = response.status();
-            info!("OpenAI API response status: {}", status);
-            
-            // Send progress update
-            if let Some(sender) = &progress_sender {
-                sender
-                    .send(TranscriptionProgress {
-                        status: format!("Processing transcription result (HTTP {})", status.as_u16()),
-                        progress: 90.0,
-                    })
-                    .await
-                    .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
-            }
-            
-            // Check if request was successful
-            if !status.is_success() {
-                let error_text = response.text().await?;
-                error!("OpenAI API error: HTTP {}", status);
-                return Err(anyhow!("API request failed (HTTP {}): {}", status, error_text));
-            }
-            
-            // Get response text
-            let content = response.text().await?;
-            
-            // Send progress update
-            if let Some(sender) = &progress_sender {
-                sender
-                    .send(TranscriptionProgress {
-                        status: "Saving transcription file".to_string(),
-                        progress: 95.0,
-                    })
-                    .await
-                    .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
+
+    let content_type = form.content_type();
+    let response = http_retry::send("Transcription", || {
+        client
+            .post(openai_connection::endpoint("/audio/transcriptions"))
+            .openai_auth(api_key)
+            .header("Content-Type", content_type.clone())
+            .body(body.clone())
+    })
+    .await
+    .map_err(|err| {
+        error!("Failed to connect to OpenAI API: {}", err);
+        anyhow!("Failed to connect to OpenAI API: {}", err)
+    })?;
+
+    let status = response.status();
+    info!("OpenAI API response status: {}", status);
+
+    // Check if request was successful
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        error!("OpenAI API error: HTTP {}", status);
+        return Err(anyhow!("API request failed (HTTP {}): {}", status, error_text));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| anyhow!("Failed to parse transcription response: {}", e))
+}
+
+/// Source language heard in a sample of the audio
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DetectedLanguage {
+    /// ISO 639-1 code
+    pub code: String,
+    /// English name
+    pub name: String,
+    /// 0..1, how sure Whisper was of what it heard in the sample
+    pub confidence: f32,
+}
+
+/// Languages Whisper reports by name, with their ISO 639-1 codes
+const WHISPER_LANGUAGES: &[(&str, &str)] = &[
+    ("english", "en"), ("chinese", "zh"), ("german", "de"), ("spanish", "es"), ("russian", "ru"),
+    ("korean", "ko"), ("french", "fr"), ("japanese", "ja"), ("portuguese", "pt"), ("turkish", "tr"),
+    ("polish", "pl"), ("catalan", "ca"), ("dutch", "nl"), ("arabic", "ar"), ("swedish", "sv"),
+    ("italian", "it"), ("indonesian", "id"), ("hindi", "hi"), ("finnish", "fi"), ("vietnamese", "vi"),
+    ("hebrew", "he"), ("ukrainian", "uk"), ("greek", "el"), ("malay", "ms"), ("czech", "cs"),
+    ("romanian", "ro"), ("danish", "da"), ("hungarian", "hu"), ("tamil", "ta"), ("norwegian", "no"),
+    ("thai", "th"), ("urdu", "ur"), ("croatian", "hr"), ("bulgarian", "bg"), ("lithuanian", "lt"),
+    ("latin", "la"), ("welsh", "cy"), ("slovak", "sk"), ("telugu", "te"), ("persian", "fa"),
+    ("latvian", "lv"), ("bengali", "bn"), ("serbian", "sr"), ("azerbaijani", "az"), ("slovenian", "sl"),
+    ("kannada", "kn"), ("estonian", "et"), ("macedonian", "mk"), ("basque", "eu"), ("icelandic", "is"),
+    ("armenian", "hy"), ("nepali", "ne"), ("mongolian", "mn"), ("bosnian", "bs"), ("kazakh", "kk"),
+    ("albanian", "sq"), ("swahili", "sw"), ("galician", "gl"), ("marathi", "mr"), ("punjabi", "pa"),
+    ("georgian", "ka"), ("belarusian", "be"), ("tagalog", "tl"), ("afrikaans", "af"), ("uzbek", "uz"),
+];
+
+/// Name and ISO 639-1 code of a language Whisper reported; newer models may send the code
+fn whisper_language(language: &str) -> Option<(&'static str, &'static str)> {
+    let language = language.trim().to_lowercase();
+    WHISPER_LANGUAGES.iter().find(|(name, code)| *name == language || *code == language).copied()
+}
+
+/// Detect the spoken language from a short sample, before paying for the whole
+/// transcription. The sample is taken a bit into the audio, past intros that
+/// are often music or a jingle.
+pub async fn detect_language(audio_path: &Path, output_dir: &Path, api_key: &str) -> Result<DetectedLanguage> {
+    let duration = audio_probe::duration(audio_path).await.unwrap_or(0.0);
+    let offset = (duration * 0.1).min(60.0).min((duration - DETECTION_SAMPLE_SECS).max(0.0));
+    let temp_dir = output_dir.join("videonova_temp");
+    fs::create_dir_all(&temp_dir).await?;
+    let sample_path = temp_dir.join("language_sample.mp3");
+    extract_chunk(audio_path, &sample_path, offset, DETECTION_SAMPLE_SECS).await?;
+
+    let transcription = request_transcription(&openai_connection::client(), &sample_path, api_key, None).await;
+    let _ = fs::remove_file(&sample_path).await;
+    let transcription = transcription?;
+
+    let reported = transcription.language.clone().unwrap_or_default();
+    let (name, code) =
+        whisper_language(&reported).ok_or_else(|| anyhow!("Whisper reported an unknown language: {}", reported))?;
+    // Duration-weighted confidence of what was heard; silence makes it low
+    let (weighted, total) = transcription.segments.iter().fold((0.0f32, 0.0f32), |(weighted, total), segment| {
+        let length = (segment.end - segment.start).max(0.0);
+        let score = CueConfidence::new(segment.start, segment.end, segment.avg_logprob, segment.no_speech_prob).score;
+        (weighted + score * length, total + length)
+    });
+    let confidence = if total > 0.0 { weighted / total } else { 0.0 };
+    info!("Detected {} ({}) with confidence {:.2}", name, code, confidence);
+    Ok(DetectedLanguage { code: code.to_string(), name: capitalize(name), confidence })
+}
+
+/// English name of an ISO 639-1 code Whisper knows
+pub fn language_name(code: &str) -> Option<String> {
+    WHISPER_LANGUAGES.iter().find(|(_, known)| *known == code).map(|(name, _)| capitalize(name))
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Subtitles of the chunks transcribed so far, sent with the progress of a
+/// chunked transcription and emitted as `partial-subtitles-available`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartialTranscript {
+    /// VTT with the cues of the finished chunks
+    pub path: String,
+    pub chunks_done: usize,
+    pub chunks_total: usize,
+    /// Cues before this time won't change anymore, seconds
+    pub until: f64,
+}
+
+/// Resume point of a chunked transcription, kept next to the partial VTT
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct ChunkState {
+    chunk_secs: f64,
+    /// Where the chunks start, followed by the end of the media, seconds
+    #[serde(default)]
+    split_points: Vec<f64>,
+    chunks_done: usize,
+    /// Length of the partial VTT after the last finished chunk
+    vtt_len: u64,
+    confidence: Vec<CueConfidence>,
+}
+
+/// Pick up a partial VTT left by an interrupted run, or start a new one. The
+/// file is cut back to the last finished chunk, which drops an append that
+/// was cut short.
+async fn prepare_partial(partial_path: &Path, state_path: &Path) -> Result<ChunkState> {
+    let previous = match fs::read_to_string(state_path).await {
+        Ok(json) => serde_json::from_str::<ChunkState>(&json).ok(),
+        Err(_) => None,
+    };
+    let resumable = previous.filter(|state| {
+        state.chunk_secs == CHUNK_SECS
+            && !state.split_points.is_empty()
+            && std::fs::metadata(partial_path).is_ok_and(|meta| meta.len() >= state.vtt_len)
+    });
+    if let Some(state) = resumable {
+        let file = fs::OpenOptions::new().write(true).open(partial_path).await?;
+        file.set_len(state.vtt_len).await?;
+        info!("Resuming transcription after chunk {}", state.chunks_done);
+        return Ok(state);
+    }
+    fs::write(partial_path, VTT_HEADER).await?;
+    Ok(ChunkState { chunk_secs: CHUNK_SECS, vtt_len: VTT_HEADER.len() as u64, ..Default::default() })
+}
+
+/// Write the resume point, through a temp file so it's never half written
+async fn save_chunk_state(state_path: &Path, state: &ChunkState) -> Result<()> {
+    let json = serde_json::to_string(state).map_err(|e| anyhow!("Failed to serialize chunk state: {}", e))?;
+    let temp_path = state_path.with_extension("json.tmp");
+    fs::write(&temp_path, json).await?;
+    fs::rename(&temp_path, state_path).await?;
+    Ok(())
+}
+
+/// Cut one chunk out of the audio as a small mono MP3
+async fn extract_chunk(audio_path: &Path, chunk_path: &Path, offset: f64, length: f64) -> Result<()> {
+    let output = TokioCommand::new(crate::utils::tools::ffmpeg())
+        .args(["-y", "-v", "error", "-ss", &format!("{:.3}", offset), "-t", &format!("{:.3}", length), "-i"])
+        .arg(audio_path)
+        .args(["-vn", "-ac", "1", "-ar", "16000", "-b:a", "64k"])
+        .arg(chunk_path)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!("ffmpeg failed to cut a chunk: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(())
+}
+
+/// Start and end of every pause, seconds
+fn silences(pauses: &timestamps::Pauses) -> Vec<(f64, f64)> {
+    pauses
+        .speech_ends
+        .iter()
+        .filter_map(|&start| {
+            let end = pauses.speech_starts.iter().copied().find(|end| *end > start)?;
+            Some((start as f64, end as f64))
+        })
+        .collect()
+}
+
+/// Where to cut media of `duration` into chunks of at most CHUNK_SECS: in the
+/// middle of the longest silence shortly before a chunk would get too long, or
+/// right there if nobody pauses. Starts with 0 and ends with `duration`.
+fn split_points(duration: f64, silences: &[(f64, f64)]) -> Vec<f64> {
+    let mut points = vec![0.0];
+    let mut last = 0.0;
+    while duration - last > CHUNK_SECS {
+        let limit = last + CHUNK_SECS;
+        let window = (limit - SPLIT_SEARCH_SECS).max(last);
+        let longest = silences
+            .iter()
+            .map(|&(start, end)| (start.max(window), end.min(limit)))
+            .filter(|(start, end)| end > start)
+            .max_by(|a, b| (a.1 - a.0).total_cmp(&(b.1 - b.0)));
+        let cut = longest.map_or(limit, |(start, end)| (start + end) / 2.0);
+        points.push(cut);
+        last = cut;
+    }
+    points.push(duration);
+    points
+}
+
+/// Keep what a chunk heard between `from` and `to`: its overlap with the
+/// neighbours is transcribed by them too. A segment or word belongs to the
+/// chunk its middle falls into, so one cut through by a split is kept once.
+fn keep_between(transcription: &mut timestamps::VerboseTranscription, pauses: &mut timestamps::Pauses, from: f32, to: f32) {
+    let inside = |start: f32, end: f32| (from..to).contains(&((start + end) / 2.0));
+    transcription.segments.retain(|segment| inside(segment.start, segment.end));
+    transcription.words.retain(|word| inside(word.start, word.end));
+    for times in [&mut pauses.speech_starts, &mut pauses.speech_ends] {
+        times.retain(|time| (from..to).contains(time));
+    }
+}
+
+/// Drop segments and words heard in a passage without speech: the overlap of
+/// a chunk reaches into them, and Whisper may have made up text there
+fn drop_non_speech(transcription: &mut timestamps::VerboseTranscription, non_speech: &[CueWindow]) {
+    let silent = |start: f32, end: f32| {
+        let middle = (start + end) / 2.0;
+        non_speech.iter().any(|region| middle >= region.start && middle < region.end)
+    };
+    transcription.segments.retain(|segment| !silent(segment.start, segment.end));
+    transcription.words.retain(|word| !silent(word.start, word.end));
+}
+
+/// Move the times of a chunk's transcription and pauses to the whole media
+fn shift(transcription: &mut timestamps::VerboseTranscription, pauses: &mut timestamps::Pauses, offset: f32) {
+    for segment in &mut transcription.segments {
+        segment.start += offset;
+        segment.end += offset;
+    }
+    for word in &mut transcription.words {
+        word.start += offset;
+        word.end += offset;
+    }
+    for time in pauses.speech_starts.iter_mut().chain(pauses.speech_ends.iter_mut()) {
+        *time += offset;
+    }
+}
+
+/// Audio transcribed in chunks, with the passages without speech it skips
+struct ChunkedAudio<'a> {
+    path: &'a Path,
+    /// Length of the audio, seconds
+    duration: f64,
+    non_speech: &'a [CueWindow],
+}
+
+/// Transcribe long media chunk by chunk. The cues of every finished chunk are
+/// appended to `<name>.partial.vtt` right away, so the early part can be
+/// reviewed while the rest is still transcribing, and an interrupted run
+/// continues after the last finished chunk. The partial file becomes the
+/// transcription once every chunk is done.
+async fn transcribe_in_chunks(
+    client: &reqwest::Client,
+    audio: ChunkedAudio<'_>,
+    output_path: &Path,
+    api_key: &str,
+    options: &TranscriptionOptions,
+    progress_sender: Option<&mpsc::Sender<TranscriptionProgress>>,
+) -> Result<TranscriptConfidence> {
+    let ChunkedAudio { path: audio_path, duration, non_speech } = audio;
+    let language = options.language.as_deref();
+    let cleanup = &options.cleanup;
+    let partial_path = output_path.with_extension("partial.vtt");
+    let state_path = output_path.with_extension("partial.json");
+    let mut state = prepare_partial(&partial_path, &state_path).await?;
+    if state.split_points.is_empty() {
+        let pauses = timestamps::detect_pauses(audio_path).await.unwrap_or_else(|e| {
+            warn!("Pause detection failed, chunks are cut at fixed lengths: {}", e);
+            timestamps::Pauses::default()
+        });
+        // Passages without speech are the best places to cut
+        let mut silences = silences(&pauses);
+        silences.extend(non_speech.iter().map(|region| (region.start as f64, (region.end as f64).min(duration))));
+        state.split_points = split_points(duration, &silences);
+        save_chunk_state(&state_path, &state).await?;
+    }
+    let chunks_total = state.split_points.len() - 1;
+    info!("Transcribing {:.0}s of audio in {} chunks", duration, chunks_total);
+
+    for index in state.chunks_done..chunks_total {
+        if let Some(sender) = progress_sender {
+            sender
+                .send(TranscriptionProgress {
+                    status: format!("Transcribing part {} of {}", index + 1, chunks_total),
+                    progress: 5.0 + 90.0 * index as f32 / chunks_total as f32,
+                    partial: None,
+                })
+                .await
+                .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
+        }
+
+        let (from, to) = (state.split_points[index], state.split_points[index + 1]);
+        // Only the speech of a chunk is uploaded, a chunk without any is skipped
+        let chunk = SubtitleCue { start: from as f32, end: to as f32, ..SubtitleCue::default() };
+        let (mut transcription, mut pauses) = match vad::outside_non_speech(&chunk, non_speech) {
+            Some(speech) => {
+                let offset = (speech.start as f64 - CHUNK_OVERLAP_SECS).max(0.0);
+                let length = (speech.end as f64 + CHUNK_OVERLAP_SECS).min(duration) - offset;
+                let chunk_path = output_path.with_extension(format!("chunk{}.mp3", index));
+                extract_chunk(audio_path, &chunk_path, offset, length).await?;
+                let transcription = request_transcription(client, &chunk_path, api_key, language).await;
+                let pauses = timestamps::detect_pauses(&chunk_path).await;
+                let _ = fs::remove_file(&chunk_path).await;
+                let mut transcription = transcription?;
+                let mut pauses = pauses.unwrap_or_else(|e| {
+                    warn!("Pause detection failed for chunk {}, cue edges stay where Whisper put them: {}", index, e);
+                    timestamps::Pauses::default()
+                });
+                shift(&mut transcription, &mut pauses, offset as f32);
+                (transcription, pauses)
             }
-            
-            // Write content to file
-            let mut output_file = File::create(&output_path).await?;
-            output_file.write_all(content.as_bytes()).await?;
-            
-            // Send completion progress
-            if let Some(sender) = &progress_sender {
-                sender
-                    .send(TranscriptionProgress {
-                        status: "Transcription complete".to_string(),
-                        progress: 100.0,
-                    })
-                    .await
-                    .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
+            None => {
+                info!("Chunk {} has no speech, skipping it", index);
+                let nothing = timestamps::VerboseTranscription { segments: Vec::new(), words: Vec::new(), language: None };
+                (nothing, timestamps::Pauses::default())
             }
-            
-            info!("Transcription completed successfully");
-            Ok(output_path)
-        },
-        Err(err) => {
-            error!("Failed to connect to OpenAI API: {}", err);
-            Err(anyhow!("Failed to connect to OpenAI API: {}", err))
+        };
+        // The first and the last chunk keep everything before and after them
+        let keep_from = if index == 0 { f32::NEG_INFINITY } else { from as f32 };
+        let keep_to = if index + 1 == chunks_total { f32::INFINITY } else { to as f32 };
+        keep_between(&mut transcription, &mut pauses, keep_from, keep_to);
+        transcript_cleanup::clean(&mut transcription, &pauses, cleanup);
+        drop_non_speech(&mut transcription, non_speech);
+        let (content, confidence) = timestamps::to_vtt(&transcription, &pauses);
+
+        // Only the cues are appended, the header is already there
+        let cues = content.strip_prefix(VTT_HEADER).unwrap_or(&content);
+        let mut file = fs::OpenOptions::new().append(true).open(&partial_path).await?;
+        file.write_all(cues.as_bytes()).await?;
+        file.sync_data().await?;
+        state.vtt_len += cues.len() as u64;
+        state.chunks_done = index + 1;
+        state.confidence.extend(confidence.cues);
+        save_chunk_state(&state_path, &state).await?;
+
+        if let Some(sender) = progress_sender {
+            sender
+                .send(TranscriptionProgress {
+                    status: format!("Transcribed part {} of {}", index + 1, chunks_total),
+                    progress: 5.0 + 90.0 * (index + 1) as f32 / chunks_total as f32,
+                    partial: Some(PartialTranscript {
+                        path: partial_path.to_string_lossy().to_string(),
+                        chunks_done: state.chunks_done,
+                        chunks_total,
+                        until: to,
+                    }),
+                })
+                .await
+                .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
         }
     }
-} 
\ No newline at end of file
+
+    fs::rename(&partial_path, output_path).await?;
+    let _ = fs::remove_file(&state_path).await;
+    Ok(TranscriptConfidence::new(state.confidence))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn partial_vtt_resumes_after_the_last_finished_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let partial_path = dir.path().join("talk.partial.vtt");
+        let state_path = dir.path().join("talk.partial.json");
+
+        let mut state = prepare_partial(&partial_path, &state_path).await.unwrap();
+        assert_eq!(state.chunks_done, 0);
+        let cue = "00:00:01.000 --> 00:00:02.000\nHello\n\n";
+        let mut file = fs::OpenOptions::new().append(true).open(&partial_path).await.unwrap();
+        file.write_all(cue.as_bytes()).await.unwrap();
+        state.vtt_len += cue.len() as u64;
+        state.split_points = vec![0.0, CHUNK_SECS, 2.0 * CHUNK_SECS];
+        state.chunks_done = 1;
+        save_chunk_state(&state_path, &state).await.unwrap();
+
+        // The next chunk was being appended when the app closed
+        file.write_all(b"00:10:00.000 --> 00:1").await.unwrap();
+        drop(file);
+
+        let resumed = prepare_partial(&partial_path, &state_path).await.unwrap();
+        assert_eq!(resumed.chunks_done, 1);
+        let content = std::fs::read_to_string(&partial_path).unwrap();
+        assert_eq!(content, format!("{}{}", VTT_HEADER, cue));
+    }
+
+    #[test]
+    fn chunks_are_cut_in_pauses_and_keep_their_own_words() {
+        // A long pause 30s before the first chunk would get too long, none for the second
+        let pauses = timestamps::Pauses { speech_ends: vec![100.0, 569.0], speech_starts: vec![101.0, 571.0] };
+        assert_eq!(split_points(1500.0, &silences(&pauses)), vec![0.0, 570.0, 1170.0, 1500.0]);
+        assert_eq!(split_points(300.0, &silences(&pauses)), vec![0.0, 300.0]);
+
+        // The chunk from 570s heard the last word of the previous one in its overlap
+        let word = |start, end| timestamps::WhisperWord { start, end };
+        let mut transcription = timestamps::VerboseTranscription {
+            segments: Vec::new(),
+            words: vec![word(568.0, 569.0), word(569.8, 570.6), word(571.0, 571.5)],
+            language: None,
+        };
+        let mut pauses = timestamps::Pauses { speech_ends: vec![569.0], speech_starts: vec![571.0] };
+        keep_between(&mut transcription, &mut pauses, 570.0, 1170.0);
+        let starts: Vec<f32> = transcription.words.iter().map(|word| word.start).collect();
+        assert_eq!(starts, vec![569.8, 571.0]);
+        assert_eq!(pauses.speech_ends, Vec::<f32>::new());
+    }
+
+    #[test]
+    fn whisper_language_names_map_to_codes() {
+        assert_eq!(whisper_language("english"), Some(("english", "en")));
+        assert_eq!(whisper_language("Russian"), Some(("russian", "ru")));
+        assert_eq!(whisper_language("de"), Some(("german", "de")));
+        assert_eq!(whisper_language("klingon"), None);
+        assert_eq!(capitalize("japanese"), "Japanese");
+    }
+}
@@ -0,0 +1,273 @@
+//! Cleanup of Whisper's output before it is translated.
+//!
+//! Whisper fills silence and music with text it saw often during training:
+//! "Thanks for watching!", "Subtitles by the Amara.org community" and the
+//! like. It also gets stuck in loops, repeating one segment over and over
+//! after a hard passage. Both end up translated and dubbed unless they are
+//! dropped here, from the segments and words of the verbose transcription
+//! before cues are made of them. A segment is a hallucination when Whisper
+//! itself doubts there was speech, or when it is one of the known phrases and
+//! lies mostly in a pause.
+//!
+//! Optionally a chat model restores punctuation and capitalization of the
+//! finished VTT, which Whisper often leaves out on fast or informal speech and
+//! which the translation and sentence splitting rely on. The model may only
+//! change punctuation and case: a line whose words came back different keeps
+//! its original text.
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::utils::advanced_config;
+use crate::utils::http_retry;
+use crate::utils::openai_connection::{self, OpenAiAuth};
+use crate::utils::timestamps::{Pauses, VerboseTranscription};
+use crate::utils::tts::tts::vtt;
+
+/// Phrases Whisper is known to make up, lowercased without punctuation
+const HALLUCINATIONS: &[&str] = &[
+    "thanks for watching",
+    "thank you for watching",
+    "thank you so much for watching",
+    "please subscribe",
+    "like and subscribe",
+    "dont forget to like and subscribe",
+    "see you in the next video",
+    "subtitles by the amaraorg community",
+    "subtitles by",
+    "transcribed by",
+    "спасибо за просмотр",
+    "продолжение следует",
+    "субтитры сделал dimatorzok",
+    "редактор субтитров",
+    "подписывайтесь на канал",
+    "untertitel im auftrag des zdf",
+    "soustitrage stfr",
+];
+/// Whisper's own test for a segment without speech
+const NO_SPEECH_PROB: f32 = 0.6;
+const LOW_LOGPROB: f32 = -1.0;
+/// A known phrase is dropped when this share of it lies in pauses
+const SILENT_SHARE: f32 = 0.5;
+/// A segment repeated this many times in a row is a loop
+const LOOP_REPEATS: usize = 3;
+/// Cues sent to the model at once for punctuation
+const PUNCTUATION_BATCH: usize = 40;
+
+/// What the cleanup after transcription does
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TranscriptCleanup {
+    /// Drop made-up segments in silence
+    pub drop_hallucinations: bool,
+    /// Keep one segment of a run of identical ones
+    pub collapse_loops: bool,
+    /// Restore punctuation and capitalization with a chat model
+    pub restore_punctuation: bool,
+    pub punctuation_model: String,
+}
+
+impl Default for TranscriptCleanup {
+    fn default() -> Self {
+        Self {
+            drop_hallucinations: true,
+            collapse_loops: true,
+            restore_punctuation: false,
+            punctuation_model: "gpt-4o-mini".to_string(),
+        }
+    }
+}
+
+/// Lowercase letters, digits and single spaces of a text
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Share of `start..end` that lies in pauses
+fn silent_share(pauses: &Pauses, start: f32, end: f32) -> f32 {
+    if end <= start {
+        return 1.0;
+    }
+    let silent: f32 = pauses
+        .speech_ends
+        .iter()
+        .filter_map(|&pause_start| {
+            let pause_end = pauses.speech_starts.iter().copied().find(|time| *time > pause_start)?;
+            Some((pause_end.min(end) - pause_start.max(start)).max(0.0))
+        })
+        .sum();
+    silent / (end - start)
+}
+
+fn is_hallucination(text: &str, no_speech_prob: f32, avg_logprob: f32, silent_share: f32) -> bool {
+    if no_speech_prob > NO_SPEECH_PROB && avg_logprob < LOW_LOGPROB {
+        return true;
+    }
+    let text = normalize(text);
+    let known = HALLUCINATIONS.iter().any(|phrase| text == *phrase || text.starts_with(&format!("{} ", phrase)));
+    known && (silent_share >= SILENT_SHARE || no_speech_prob > NO_SPEECH_PROB)
+}
+
+/// Drop hallucinated and looping segments with their words. Returns how many
+/// segments were dropped.
+pub fn clean(transcription: &mut VerboseTranscription, pauses: &Pauses, cleanup: &TranscriptCleanup) -> usize {
+    let segments = &transcription.segments;
+    let mut keep = vec![true; segments.len()];
+    if cleanup.drop_hallucinations {
+        for (index, segment) in segments.iter().enumerate() {
+            let share = silent_share(pauses, segment.start, segment.end);
+            if is_hallucination(&segment.text, segment.no_speech_prob, segment.avg_logprob, share) {
+                info!("Dropping hallucinated segment at {:.1}s: {}", segment.start, segment.text.trim());
+                keep[index] = false;
+            }
+        }
+    }
+    if cleanup.collapse_loops {
+        let mut first = 0;
+        while first < segments.len() {
+            let text = normalize(&segments[first].text);
+            let mut last = first;
+            while last + 1 < segments.len() && !text.is_empty() && normalize(&segments[last + 1].text) == text {
+                last += 1;
+            }
+            if last + 1 - first >= LOOP_REPEATS {
+                info!("Collapsing {} repeats of a segment at {:.1}s", last + 1 - first, segments[first].start);
+                keep[first + 1..=last].iter_mut().for_each(|keep| *keep = false);
+            }
+            first = last + 1;
+        }
+    }
+
+    let dropped: Vec<(f32, f32)> = segments
+        .iter()
+        .zip(&keep)
+        .filter(|(_, keep)| !**keep)
+        .map(|(segment, _)| (segment.start, segment.end))
+        .collect();
+    transcription
+        .words
+        .retain(|word| !dropped.iter().any(|(start, end)| word.start >= *start && word.start < *end));
+    let mut keep = keep.into_iter();
+    transcription.segments.retain(|_| keep.next().unwrap_or(true));
+    dropped.len()
+}
+
+/// Text of a corrected line, if its words are the ones of the original
+fn same_words(original: &str, corrected: &str) -> bool {
+    normalize(original) == normalize(corrected)
+}
+
+/// Ask the model to punctuate numbered lines, returning them by number
+async fn punctuate_batch(lines: &[&str], api_key: &str, model: &str) -> Result<Vec<Option<String>>> {
+    let numbered: String = lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| format!("{}. {}\n", index + 1, line.replace('\n', " ")))
+        .collect();
+    let payload = json!({
+        "model": model,
+        "temperature": 0.0,
+        "messages": [
+            { "role": "system", "content": "You restore punctuation and capitalization of speech transcripts. \
+                Keep every word as it is, in the same order and language. Do not add, remove, translate or fix words. \
+                Answer with the same numbered lines and nothing else." },
+            { "role": "user", "content": numbered }
+        ]
+    });
+    let client = openai_connection::client();
+    let response = http_retry::send("Punctuation", || {
+        client
+            .post(openai_connection::endpoint("/chat/completions"))
+            .openai_auth(api_key)
+            .json(&payload)
+            .timeout(Duration::from_secs(advanced_config::current().timeouts.openai_request_secs))
+    })
+    .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(anyhow!("OpenAI API error (HTTP {}): {}", status, error_text));
+    }
+    let body: serde_json::Value = response.json().await?;
+    let content = body["choices"][0]["message"]["content"].as_str().unwrap_or_default();
+
+    let mut corrected = vec![None; lines.len()];
+    for line in content.lines() {
+        let Some((number, text)) = line.trim().split_once(". ") else { continue };
+        let Some(slot) = number.parse::<usize>().ok().and_then(|number| corrected.get_mut(number.wrapping_sub(1))) else {
+            continue;
+        };
+        *slot = Some(text.trim().to_string());
+    }
+    Ok(corrected)
+}
+
+/// Restore punctuation of the cues in a VTT in place
+pub async fn restore_punctuation(vtt_path: &Path, api_key: &str, model: &str) -> Result<()> {
+    let mut cues = vtt::parse_vtt(vtt_path).map_err(|e| anyhow!("Failed to read {}: {}", vtt_path.display(), e))?;
+    let mut changed = 0;
+    for (batch_index, batch) in cues.chunks_mut(PUNCTUATION_BATCH).enumerate() {
+        let lines: Vec<&str> = batch.iter().map(|cue| cue.text.as_str()).collect();
+        let corrected = punctuate_batch(&lines, api_key, model).await?;
+        for (cue, text) in batch.iter_mut().zip(corrected) {
+            match text {
+                Some(text) if text == cue.text => {}
+                Some(text) if same_words(&cue.text, &text) => {
+                    cue.text = text;
+                    changed += 1;
+                }
+                Some(text) => warn!("Punctuation changed the words of a cue in batch {}, keeping it: {}", batch_index, text),
+                None => {}
+            }
+        }
+    }
+    tokio::fs::write(vtt_path, vtt::write_vtt_str(&cues)).await?;
+    info!("Restored punctuation of {} of {} cues", changed, cues.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::timestamps::{WhisperSegment, WhisperWord};
+
+    fn segment(start: f32, end: f32, text: &str) -> WhisperSegment {
+        WhisperSegment { start, end, text: text.to_string(), avg_logprob: -0.3, no_speech_prob: 0.1 }
+    }
+
+    #[test]
+    fn hallucinations_in_silence_and_loops_are_dropped() {
+        let mut transcription = VerboseTranscription {
+            segments: vec![
+                segment(0.0, 2.0, " Thanks for watching!"),
+                segment(10.0, 12.0, " Thanks for watching!"),
+                segment(12.0, 13.0, " I said no."),
+                segment(13.0, 14.0, " I said no."),
+                segment(14.0, 15.0, " I said no."),
+                segment(15.0, 16.0, " Fine."),
+            ],
+            words: vec![WhisperWord { start: 0.5, end: 1.0 }, WhisperWord { start: 10.5, end: 11.0 }, WhisperWord { start: 13.2, end: 13.5 }],
+            language: None,
+        };
+        // The first one is said over a pause, the second one is spoken
+        let pauses = Pauses { speech_ends: vec![0.0], speech_starts: vec![3.0] };
+        let dropped = clean(&mut transcription, &pauses, &TranscriptCleanup::default());
+        assert_eq!(dropped, 3);
+        let starts: Vec<f32> = transcription.segments.iter().map(|segment| segment.start).collect();
+        assert_eq!(starts, vec![10.0, 12.0, 15.0]);
+        assert_eq!(transcription.words.len(), 1);
+
+        assert!(same_words("so what do we do now", "So, what do we do now?"));
+        assert!(!same_words("so what do we do now", "So, what should we do now?"));
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::{anyhow, Result};
-use log::{debug, info, error};
+use log::{debug, info, error, warn};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use tokio::fs;
@@ -7,7 +7,20 @@ use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc;
 use reqwest;
 use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use crate::utils::advanced_config;
+use crate::utils::ass;
+use crate::utils::cancellation;
+use crate::utils::charset;
+use crate::utils::confidence;
+use crate::utils::http_retry;
+use crate::utils::openai_connection::{self, OpenAiAuth};
+use crate::utils::request_journal::{self, RequestJournal};
 use crate::utils::common::{sanitize_filename, check_file_exists_and_valid};
+use crate::utils::scrub::{ScrubOptions, Scrubber};
+use crate::utils::subtitles;
+use crate::utils::tts::tts::vtt;
+use crate::utils::validation::{CommandError, Validate, Validator};
 
 // Progress structure for translation
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -16,19 +29,105 @@ pub struct TranslationProgress {
     pub progress: f32,
 }
 
+/// Model trade-off for a translation
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationQuality {
+    /// gpt-4o-mini, cheap and fast
+    #[default]
+    Standard,
+    /// gpt-4o, better with idioms and terminology
+    High,
+}
+
+impl TranslationQuality {
+    fn model(self) -> &'static str {
+        match self {
+            TranslationQuality::Standard => "gpt-4o-mini",
+            TranslationQuality::High => "gpt-4o",
+        }
+    }
+}
+
+/// A term with a fixed translation; an empty translation keeps the term as is
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GlossaryEntry {
+    pub term: String,
+    #[serde(default)]
+    pub translation: String,
+}
+
+/// Options of a subtitle translation
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TranslationOptions {
+    pub quality: TranslationQuality,
+    pub glossary: Vec<GlossaryEntry>,
+    /// Personal data and profanity masked before the text is sent
+    pub scrub: ScrubOptions,
+}
+
+impl TranslationOptions {
+    /// Glossary instructions appended to the system prompt
+    fn glossary_prompt(&self) -> String {
+        let entries: Vec<String> = self
+            .glossary
+            .iter()
+            .filter(|entry| !entry.term.trim().is_empty())
+            .map(|entry| {
+                if entry.translation.trim().is_empty() {
+                    format!("- \"{}\": keep untranslated", entry.term.trim())
+                } else {
+                    format!("- \"{}\": translate as \"{}\"", entry.term.trim(), entry.translation.trim())
+                }
+            })
+            .collect();
+        if entries.is_empty() {
+            return String::new();
+        }
+        format!(" Always use this glossary:\n{}", entries.join("\n"))
+    }
+
+    /// Placeholder instructions appended to the system prompt
+    fn scrub_prompt(&self) -> &'static str {
+        if self.scrub.enabled {
+            " Placeholders like [[1]] stand for names and contact details: copy them unchanged into the translation."
+        } else {
+            ""
+        }
+    }
+}
+
 // Structure for VTT segments
 #[derive(Debug, Clone)]
-struct VttSegment {
-    index: usize,
-    timestamp: String,
-    text: String,
+pub(crate) struct VttSegment {
+    pub(crate) index: usize,
+    pub(crate) timestamp: String,
+    // Text without the speaker tag, which is all the translator sees
+    pub(crate) text: String,
+    pub(crate) identifier: Option<String>,
+    pub(crate) speaker: Option<String>,
+    // NOTE blocks right before the cue, as written
+    pub(crate) notes: Vec<String>,
+    // Whisper was unsure about the transcript of this cue
+    pub(crate) low_confidence: bool,
+}
+
+impl VttSegment {
+    // Cue text with the speaker tag put back
+    fn tagged_text(&self) -> String {
+        match &self.speaker {
+            Some(speaker) => format!("<v {}>{}", speaker, self.text),
+            None => self.text.clone(),
+        }
+    }
 }
 
 // Structure for VTT file
 #[derive(Debug)]
-struct VttFile {
-    header: String,
-    segments: Vec<VttSegment>,
+pub(crate) struct VttFile {
+    pub(crate) header: String,
+    pub(crate) segments: Vec<VttSegment>,
 }
 
 // Chat message structure for OpenAI API
@@ -59,11 +158,34 @@ struct Choice {
 }
 
 // Parse VTT file into segments
-async fn parse_vtt_file(vtt_path: &Path) -> Result<VttFile> {
+async fn parse_vtt_file(vtt_path: &Path, encoding: Option<&str>) -> Result<VttFile> {
     debug!("Parsing VTT file: {}", vtt_path.display());
     
-    // Read file content
-    let content = fs::read_to_string(vtt_path).await?;
+    // Read file content, transcoding non-UTF-8 files
+    let content = charset::read_subtitle_file(vtt_path, encoding).await?;
+    parse_vtt_content(&content)
+}
+
+// Flag the segments Whisper was unsure about, using the confidence sidecar of the VTT
+async fn mark_low_confidence(vtt_path: &Path, segments: &mut [VttSegment]) {
+    let Some(confidence) = confidence::load(vtt_path).await else { return };
+    for segment in segments.iter_mut() {
+        let start = segment.timestamp.split("-->").next().and_then(|start| vtt::parse_time(start.trim()).ok());
+        segment.low_confidence = start.is_some_and(|start| confidence.is_low(start));
+    }
+    let flagged = segments.iter().filter(|segment| segment.low_confidence).count();
+    if flagged > 0 {
+        info!("{} cues have a low-confidence transcript", flagged);
+    }
+}
+
+// A cue identifier (or SRT counter) is a text line right before a timing line
+fn is_cue_identifier(lines: &[&str], i: usize) -> bool {
+    !lines[i].trim().is_empty() && lines.get(i + 1).is_some_and(|next| next.contains("-->"))
+}
+
+// Parse VTT content into segments
+pub(crate) fn parse_vtt_content(content: &str) -> Result<VttFile> {
     let lines: Vec<&str> = content.lines().collect();
     
     if lines.is_empty() {
@@ -73,37 +195,59 @@ async fn parse_vtt_file(vtt_path: &Path) -> Result<VttFile> {
     // Extract header (usually "WEBVTT" and metadata)
     let mut header_lines = Vec::new();
     let mut i = 0;
-    while i < lines.len() && !lines[i].contains("-->") {
+    while i < lines.len() && !lines[i].contains("-->") && !is_cue_identifier(&lines, i) {
         header_lines.push(lines[i]);
         i += 1;
     }
     
-    let header = header_lines.join("\n");
+    let header = header_lines.join("\n").trim_end().to_string();
     debug!("VTT header: {}", header);
     
     // Parse segments
     let mut segments = Vec::new();
     let mut current_timestamp = String::new();
     let mut current_text = Vec::new();
+    let mut current_identifier = None;
+    let mut current_notes = Vec::new();
+    // Identifier and notes seen after the current cue belong to the next one
+    let mut pending_identifier = None;
+    let mut pending_notes = Vec::new();
     let mut index = 0;
     
+    let mut push_segment = |index: usize, timestamp: String, text: &[String], identifier, notes| {
+        let (speaker, text) = vtt::split_speaker(&text.join("\n"));
+        segments.push(VttSegment { index, timestamp, text, identifier, speaker, notes, low_confidence: false });
+    };
+    
     while i < lines.len() {
         let line = lines[i].trim();
         
+        // A NOTE block runs until the next blank line
+        if line.starts_with("NOTE") && (i == 0 || lines[i - 1].trim().is_empty()) {
+            let mut note = vec![line];
+            while i + 1 < lines.len() && !lines[i + 1].trim().is_empty() {
+                i += 1;
+                note.push(lines[i].trim_end());
+            }
+            pending_notes.push(note.join("\n"));
+            i += 1;
+            continue;
+        }
+        
         // If line contains timestamp
         if line.contains("-->") {
             // If we already have a timestamp and text, add segment
             if !current_timestamp.is_empty() && !current_text.is_empty() {
-                segments.push(VttSegment {
-                    index,
-                    timestamp: current_timestamp,
-                    text: current_text.join("\n"),
-                });
+                push_segment(index, current_timestamp, &current_text, current_identifier.take(), std::mem::take(&mut current_notes));
                 index += 1;
                 current_text.clear();
             }
             
             current_timestamp = line.to_string();
+            current_identifier = pending_identifier.take();
+            current_notes = std::mem::take(&mut pending_notes);
+        } else if is_cue_identifier(&lines, i) {
+            pending_identifier = Some(line.to_string()).filter(|id| !id.is_empty());
         } else if !line.is_empty() && !current_timestamp.is_empty() {
             // Add text line to current segment
             current_text.push(line.to_string());
@@ -114,11 +258,7 @@ async fn parse_vtt_file(vtt_path: &Path) -> Result<VttFile> {
     
     // Add the last segment if any
     if !current_timestamp.is_empty() && !current_text.is_empty() {
-        segments.push(VttSegment {
-            index,
-            timestamp: current_timestamp,
-            text: current_text.join("\n"),
-        });
+        push_segment(index, current_timestamp, &current_text, current_identifier, current_notes);
     }
     
     debug!("Parsed {} segments from VTT file", segments.len());
@@ -126,39 +266,26 @@ async fn parse_vtt_file(vtt_path: &Path) -> Result<VttFile> {
     Ok(VttFile { header, segments })
 }
 
-// Translate a batch of VTT segments
-async fn translate_segments(
-    segments: &[VttSegment],
-    target_language: &str,
-    api_key: &str,
-) -> Result<Vec<VttSegment>> {
-    debug!("Translating batch of {} segments to {}", segments.len(), target_language);
-    
-    if segments.is_empty() {
-        return Ok(Vec::new());
+// Render a VTT header and segments back into VTT content
+pub(crate) fn render_vtt(header: &str, segments: &[VttSegment]) -> String {
+    let mut content = format!("{}\n\n", header);
+    for segment in segments {
+        for note in &segment.notes {
+            content.push_str(&format!("{}\n\n", note));
+        }
+        if let Some(identifier) = &segment.identifier {
+            content.push_str(&format!("{}\n", identifier));
+        }
+        content.push_str(&format!("{}\n{}\n\n", segment.timestamp, segment.tagged_text()));
     }
-    
-    // Extract text from segments
-    let segments_text = segments
-        .iter()
-        .map(|s| format!("{}. {}", s.index + 1, s.text))
-        .collect::<Vec<String>>()
-        .join("\n\n");
-    
-    // Create system message with translation instructions
-    let system_message = format!(
-        "You are a professional translator. \
-        Translate the following subtitles from their original language into {}. \
-        Maintain the same format and numbering. \
-        Keep the translations natural, accurate, and appropriate for the video context. \
-        ONLY include the translated text and numbering in your response.",
-        target_language
-    );
-    
-    // Create request to OpenAI API
-    let client = reqwest::Client::new();
+    content
+}
+
+// Send a translation request and wait for the answer
+async fn chat_completion(model: &str, system_message: String, user_message: String, api_key: &str) -> Result<String> {
+    let client = openai_connection::client();
     let request = TranslationRequest {
-        model: "gpt-4o-mini".to_string(),
+        model: model.to_string(),
         messages: vec![
             Message {
                 role: "system".to_string(),
@@ -166,7 +293,7 @@ async fn translate_segments(
             },
             Message {
                 role: "user".to_string(),
-                content: segments_text,
+                content: user_message,
             },
         ],
         temperature: 0.3,
@@ -174,14 +301,15 @@ async fn translate_segments(
     
     // Send request to OpenAI API
     debug!("Sending translation request to OpenAI API");
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request)
-        .timeout(Duration::from_secs(120))
-        .send()
-        .await?;
+    let response = http_retry::send("Translation", || {
+        client
+            .post(openai_connection::endpoint("/chat/completions"))
+            .openai_auth(api_key)
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .timeout(Duration::from_secs(advanced_config::current().timeouts.openai_request_secs))
+    })
+    .await?;
     
     let status = response.status();
     debug!("OpenAI API response status: {}", status);
@@ -192,9 +320,210 @@ async fn translate_segments(
         return Err(anyhow!("OpenAI API error: {}", error_text));
     }
     
-    // Parse response
     let completion: ChatCompletion = response.json().await?;
-    let translated_text = completion.choices[0].message.content.trim();
+    let choice = completion
+        .choices
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("OpenAI API returned no choices"))?;
+    Ok(choice.message.content)
+}
+
+// Text of a finished Responses API object
+fn response_output_text(response: &serde_json::Value) -> Option<String> {
+    let text: String = response["output"]
+        .as_array()?
+        .iter()
+        .filter_map(|item| item["content"].as_array())
+        .flatten()
+        .filter(|part| part["type"] == "output_text")
+        .filter_map(|part| part["text"].as_str())
+        .collect();
+    Some(text).filter(|text| !text.is_empty())
+}
+
+// Poll a background response until OpenAI finishes it. Ok(None) means the
+// response is gone or didn't complete and the request has to be sent again.
+async fn poll_response(client: &reqwest::Client, response_id: &str, api_key: &str) -> Result<Option<String>> {
+    loop {
+        let response = http_retry::send("Translation status", || {
+            client
+                .get(openai_connection::endpoint(&format!("/responses/{}", response_id)))
+                .openai_auth(api_key)
+                .timeout(Duration::from_secs(30))
+        })
+        .await?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            return Err(anyhow!("OpenAI API error: {}", error_text));
+        }
+        let body: serde_json::Value = response.json().await?;
+        match body["status"].as_str() {
+            Some("queued") | Some("in_progress") => tokio::time::sleep(Duration::from_secs(2)).await,
+            Some("completed") => return Ok(response_output_text(&body)),
+            other => {
+                warn!("Background response {} ended as {:?}", response_id, other);
+                return Ok(None);
+            }
+        }
+    }
+}
+
+// Run a translation request in the background on OpenAI's side, keeping its
+// response id in the journal so a crashed run can collect it instead of paying twice
+async fn background_completion(
+    model: &str,
+    system_message: &str,
+    user_message: &str,
+    api_key: &str,
+    journal: &RequestJournal,
+) -> Result<String> {
+    let body = serde_json::json!({
+        "model": model,
+        "instructions": system_message,
+        "input": user_message,
+        "temperature": 0.3,
+        "background": true,
+        "store": true,
+    });
+    let fingerprint = request_journal::fingerprint(&body);
+    let client = openai_connection::client();
+
+    if let Some(response_id) = journal.pending(&fingerprint) {
+        info!("Collecting translation {} submitted before the restart", response_id);
+        let recovered = poll_response(&client, &response_id, api_key).await?;
+        journal.forget(&fingerprint).await?;
+        if let Some(text) = recovered {
+            return Ok(text);
+        }
+        warn!("Translation {} can't be recovered, sending it again", response_id);
+    }
+
+    debug!("Sending background translation request to OpenAI API");
+    let response = http_retry::send("Translation", || {
+        client
+            .post(openai_connection::endpoint("/responses"))
+            .openai_auth(api_key)
+            .json(&body)
+            .timeout(Duration::from_secs(advanced_config::current().timeouts.openai_request_secs))
+    })
+    .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        error!("OpenAI API error: HTTP {}, body: {}", status, error_text);
+        return Err(anyhow!("OpenAI API error: {}", error_text));
+    }
+    let created: serde_json::Value = response.json().await?;
+    let response_id = created["id"]
+        .as_str()
+        .ok_or_else(|| anyhow!("OpenAI API returned no response id"))?
+        .to_string();
+    if let Err(e) = journal.record("translation", &fingerprint, &response_id).await {
+        warn!("Failed to record in-flight translation {}: {}", response_id, e);
+    }
+
+    let text = poll_response(&client, &response_id, api_key).await?;
+    journal.forget(&fingerprint).await?;
+    text.ok_or_else(|| anyhow!("OpenAI did not complete translation {}", response_id))
+}
+
+// Cues around a batch, shown to the model so that the batches read as one
+// text: names, forms of address and references stay consistent across them
+#[derive(Debug, Default)]
+struct BatchContext<'a> {
+    // Preceding cues with their translations
+    before: Vec<(&'a str, &'a str)>,
+    // Following cues, translated by the next batch
+    after: Vec<&'a str>,
+}
+
+// User message of a batch: the numbered cues to translate between context
+// lines, which start with '>' and are not part of the answer
+fn batch_message(segments: &[VttSegment], context: &BatchContext) -> String {
+    let one_line = |text: &str| text.replace('\n', " ");
+    let mut parts = Vec::new();
+    if !context.before.is_empty() {
+        let lines: Vec<String> = context
+            .before
+            .iter()
+            .map(|(source, translation)| format!("> {} => {}", one_line(source), one_line(translation)))
+            .collect();
+        parts.push(lines.join("\n"));
+    }
+    parts.push(
+        segments
+            .iter()
+            .map(|s| format!("{}. {}", s.index + 1, s.text))
+            .collect::<Vec<String>>()
+            .join("\n\n"),
+    );
+    if !context.after.is_empty() {
+        let lines: Vec<String> = context.after.iter().map(|source| format!("> {}", one_line(source))).collect();
+        parts.push(lines.join("\n"));
+    }
+    parts.join("\n\n")
+}
+
+// Translate a batch of VTT segments
+async fn translate_segments(
+    segments: &[VttSegment],
+    context: &BatchContext<'_>,
+    target_language: &str,
+    api_key: &str,
+    options: &TranslationOptions,
+    journal: Option<&RequestJournal>,
+) -> Result<Vec<VttSegment>> {
+    debug!("Translating batch of {} segments to {}", segments.len(), target_language);
+    
+    if segments.is_empty() {
+        return Ok(Vec::new());
+    }
+    
+    // Extract text from segments
+    let segments_text = batch_message(segments, context);
+    
+    // Cues the transcription may have misheard
+    let uncertain: Vec<String> = segments
+        .iter()
+        .filter(|s| s.low_confidence)
+        .map(|s| (s.index + 1).to_string())
+        .collect();
+    let uncertain_prompt = if uncertain.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " The transcript may be wrong in subtitles {}: translate the most plausible meaning in context.",
+            uncertain.join(", ")
+        )
+    };
+
+    // Create system message with translation instructions
+    let system_message = format!(
+        "You are a professional translator. \
+        Translate the following subtitles from their original language into {}. \
+        Maintain the same format and numbering. \
+        Keep the translations natural, accurate, and appropriate for the video context. \
+        Lines starting with '>' are the subtitles around these ones, with the earlier ones already translated: \
+        use them to stay consistent, but don't translate or repeat them. \
+        ONLY include the translated text and numbering in your response.{}{}{}",
+        target_language,
+        options.glossary_prompt(),
+        options.scrub_prompt(),
+        uncertain_prompt
+    );
+    
+    let translated_text = match journal {
+        Some(journal) => {
+            background_completion(options.quality.model(), &system_message, &segments_text, api_key, journal).await?
+        }
+        None => chat_completion(options.quality.model(), system_message, segments_text, api_key).await?,
+    };
+    let translated_text = translated_text.trim();
     debug!("Received translation from OpenAI API");
     
     // Split translated text into segments
@@ -229,9 +558,14 @@ async fn translate_segments(
             let line = translated_lines[i].trim();
             
             // If line is empty or starts with next index, break
-            if line.is_empty() || (line.contains('.') && line.chars().next().unwrap().is_digit(10)) {
+            if line.is_empty() || (line.contains('.') && line.chars().next().unwrap().is_ascii_digit()) {
                 break;
             }
+            // Context echoed back by the model
+            if line.starts_with('>') {
+                i += 1;
+                continue;
+            }
             
             segment_text.push(line.to_string());
             i += 1;
@@ -239,9 +573,8 @@ async fn translate_segments(
         
         // Create translated segment
         translated_segments.push(VttSegment {
-            index: segment.index,
-            timestamp: segment.timestamp.clone(),
             text: segment_text.join("\n"),
+            ..segment.clone()
         });
     }
     
@@ -249,15 +582,166 @@ async fn translate_segments(
     Ok(translated_segments)
 }
 
-// Translate VTT file
+/// Translate free text (e.g. a video title or description) keeping its line breaks
+pub async fn translate_text(text: &str, target_language: &str, api_key: &str) -> Result<String> {
+    if text.trim().is_empty() {
+        return Ok(String::new());
+    }
+
+    let system_message = format!(
+        "You are a professional translator. \
+        Translate the following video metadata into {}. \
+        Preserve line breaks, links and hashtags. \
+        ONLY include the translated text in your response.",
+        target_language
+    );
+
+    let client = openai_connection::client();
+    let request = TranslationRequest {
+        model: "gpt-4o-mini".to_string(),
+        messages: vec![
+            Message {
+                role: "system".to_string(),
+                content: system_message,
+            },
+            Message {
+                role: "user".to_string(),
+                content: text.to_string(),
+            },
+        ],
+        temperature: 0.3,
+    };
+
+    let response = http_retry::send("Translation", || {
+        client
+            .post(openai_connection::endpoint("/chat/completions"))
+            .openai_auth(api_key)
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .timeout(Duration::from_secs(advanced_config::current().timeouts.openai_request_secs))
+    })
+    .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        error!("OpenAI API error: HTTP {}, body: {}", status, error_text);
+        return Err(anyhow!("OpenAI API error: {}", error_text));
+    }
+
+    let completion: ChatCompletion = response.json().await?;
+    let choice = completion
+        .choices
+        .first()
+        .ok_or_else(|| anyhow!("OpenAI API returned no choices"))?;
+    Ok(choice.message.content.trim().to_string())
+}
+
+// Translate segments in batches, reporting progress per batch
+async fn translate_in_batches(
+    segments: &[VttSegment],
+    target_language_name: &str,
+    api_key: &str,
+    options: &TranslationOptions,
+    journal: Option<&RequestJournal>,
+    cancel: Option<&CancellationToken>,
+    progress_sender: Option<&mpsc::Sender<TranslationProgress>>,
+) -> Result<Vec<VttSegment>> {
+    // Process in batches of 10 segments
+    const BATCH_SIZE: usize = 10;
+    // Cues shown around a batch as context
+    const CONTEXT_BEFORE: usize = 4;
+    const CONTEXT_AFTER: usize = 2;
+    let total_segments = segments.len();
+    let batch_count = total_segments.div_ceil(BATCH_SIZE);
+    
+    info!("Starting translation in {} batches", batch_count);
+    
+    let mut translated_segments = Vec::new();
+    // Translations as sent back, with placeholders, for the context of later batches
+    let mut masked_translations: Vec<String> = Vec::new();
+
+    // Mask the whole file up front, so a value keeps its placeholder across batches
+    let mut scrubber = Scrubber::new();
+    let masked: Vec<VttSegment> = segments
+        .iter()
+        .map(|segment| VttSegment { text: scrubber.mask(&segment.text, &options.scrub), ..segment.clone() })
+        .collect();
+    if !scrubber.is_empty() {
+        info!("Masked personal data in the transcript before translation");
+    }
+    
+    for (batch_index, chunk) in masked.chunks(BATCH_SIZE).enumerate() {
+        if cancel.is_some_and(CancellationToken::is_cancelled) {
+            return Err(anyhow!(cancellation::CANCELLED));
+        }
+        if let Some(sender) = progress_sender {
+            let progress = (batch_index as f32 / batch_count as f32) * 100.0;
+            sender
+                .send(TranslationProgress {
+                    status: format!("Translating segments ({}/{})", batch_index + 1, batch_count),
+                    progress,
+                })
+                .await
+                .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
+        }
+        
+        debug!("Translating batch {}/{}", batch_index + 1, batch_count);
+        let offset = batch_index * BATCH_SIZE;
+        let context_start = offset.saturating_sub(CONTEXT_BEFORE);
+        let context = BatchContext {
+            before: masked[context_start..offset]
+                .iter()
+                .zip(&masked_translations[context_start..offset])
+                .map(|(source, translation)| (source.text.as_str(), translation.as_str()))
+                .collect(),
+            after: masked[offset + chunk.len()..]
+                .iter()
+                .take(CONTEXT_AFTER)
+                .map(|segment| segment.text.as_str())
+                .collect(),
+        };
+        // A background request dropped here stays in the journal, a resumed run collects it
+        let request = translate_segments(chunk, &context, target_language_name, api_key, options, journal);
+        let batch_translated = match cancel {
+            Some(token) => cancellation::run(token, request).await??,
+            None => request.await?,
+        };
+        for (source, mut translated) in chunk.iter().zip(batch_translated) {
+            masked_translations.push(translated.text.clone());
+            let missing = scrubber.missing(&source.text, &translated.text);
+            if !missing.is_empty() {
+                warn!("Translation of cue {} lost placeholders {}", source.index + 1, missing.join(", "));
+            }
+            translated.text = scrubber.restore(&translated.text);
+            translated_segments.push(translated);
+        }
+        
+        // Small delay to avoid API rate limits
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    
+    Ok(translated_segments)
+}
+
+/// Language a translation goes to, and the account and options it runs with
+pub struct TranslationTarget<'a> {
+    pub target_language_code: &'a str,
+    pub target_language_name: &'a str,
+    pub api_key: &'a str,
+    pub options: &'a TranslationOptions,
+}
+
+// Translate VTT file; `encoding` overrides the detected charset of the source
 pub async fn translate_vtt(
     vtt_path: &Path,
+    encoding: Option<&str>,
     output_dir: &Path,
-    target_language_code: &str,
-    target_language_name: &str,
-    api_key: &str,
+    target: &TranslationTarget<'_>,
+    cancel: Option<&CancellationToken>,
     progress_sender: Option<mpsc::Sender<TranslationProgress>>,
 ) -> Result<PathBuf> {
+    let TranslationTarget { target_language_code, target_language_name, api_key, options } = *target;
     info!("Starting VTT translation to {}", target_language_name);
     
     // Create output directory if it doesn't exist
@@ -294,41 +778,18 @@ pub async fn translate_vtt(
             .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
     }
     
-    let vtt_file = parse_vtt_file(vtt_path).await?;
+    let mut vtt_file = parse_vtt_file(vtt_path, encoding).await?;
     debug!("Successfully parsed VTT file with {} segments", vtt_file.segments.len());
+    mark_low_confidence(vtt_path, &mut vtt_file.segments).await;
     
     if vtt_file.segments.is_empty() {
         return Err(anyhow!("No segments found in VTT file"));
     }
     
-    // Process in batches of 10 segments
-    const BATCH_SIZE: usize = 10;
-    let total_segments = vtt_file.segments.len();
-    let batch_count = (total_segments + BATCH_SIZE - 1) / BATCH_SIZE;
-    
-    info!("Starting translation in {} batches", batch_count);
-    
-    let mut translated_segments = Vec::new();
-    
-    for (batch_index, chunk) in vtt_file.segments.chunks(BATCH_SIZE).enumerate() {
-        if let Some(sender) = &progress_sender {
-            let progress = (batch_index as f32 / batch_count as f32) * 100.0;
-            sender
-                .send(TranslationProgress {
-                    status: format!("Translating segments ({}/{})", batch_index + 1, batch_count),
-                    progress,
-                })
-                .await
-                .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
-        }
-        
-        debug!("Translating batch {}/{}", batch_index + 1, batch_count);
-        let batch_translated = translate_segments(chunk, target_language_name, api_key).await?;
-        translated_segments.extend(batch_translated);
-        
-        // Small delay to avoid API rate limits
-        tokio::time::sleep(Duration::from_millis(500)).await;
-    }
+    // Batches OpenAI already accepted in an interrupted run are collected, not sent again
+    let journal = RequestJournal::open(&temp_dir).await;
+    let translated_segments =
+        translate_in_batches(&vtt_file.segments, target_language_name, api_key, options, Some(&journal), cancel, progress_sender.as_ref()).await?;
     
     // Write translated VTT to file
     if let Some(sender) = &progress_sender {
@@ -341,19 +802,10 @@ pub async fn translate_vtt(
             .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
     }
     
+    // Header and segments, written as UTF-8 without BOM
+    let content = render_vtt(&vtt_file.header, &translated_segments);
     let mut output_file = fs::File::create(&output_path).await?;
-    
-    // Write header
-    output_file.write_all(vtt_file.header.as_bytes()).await?;
-    output_file.write_all(b"\n\n").await?;
-    
-    // Write translated segments
-    for segment in &translated_segments {
-        output_file.write_all(segment.timestamp.as_bytes()).await?;
-        output_file.write_all(b"\n").await?;
-        output_file.write_all(segment.text.as_bytes()).await?;
-        output_file.write_all(b"\n\n").await?;
-    }
+    output_file.write_all(&charset::to_utf8_bytes(&content, false)).await?;
     
     info!("Translation complete. Saved to: {}", output_path.display());
     
@@ -369,4 +821,194 @@ pub async fn translate_vtt(
     }
     
     Ok(output_path)
-} 
\ No newline at end of file
+} 
+// Bring a VTT timestamp (`01:02.500` or `00:01:02.500`) into SRT form
+fn srt_timestamp(timestamp: &str) -> String {
+    let timestamp = timestamp.trim().replace('.', ",");
+    if timestamp.matches(':').count() == 1 {
+        format!("00:{}", timestamp)
+    } else {
+        timestamp
+    }
+}
+
+// Render segments as SRT, dropping VTT cue settings the format has no place for
+fn render_srt(segments: &[VttSegment]) -> String {
+    let mut content = String::new();
+    for (number, segment) in segments.iter().enumerate() {
+        let mut times = segment.timestamp.split("-->");
+        let start = times.next().unwrap_or_default();
+        let end = times.next().unwrap_or_default().split_whitespace().next().unwrap_or_default();
+        content.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            number + 1,
+            srt_timestamp(start),
+            srt_timestamp(end),
+            segment.tagged_text()
+        ));
+    }
+    content
+}
+
+/// Input of the `translate_subtitle_file` command
+#[derive(Debug, Deserialize, Clone)]
+pub struct SubtitleFileRequest {
+    pub input_path: String,
+    /// Next to the source when empty
+    #[serde(default)]
+    pub output_path: Option<String>,
+    /// Language name the model translates into, e.g. "German"
+    pub target_language: String,
+    pub target_language_code: String,
+    pub api_key: String,
+    #[serde(default)]
+    pub options: Option<TranslationOptions>,
+    /// Character encoding chosen by the user (e.g. "windows-1251"), detected when empty
+    #[serde(default)]
+    pub encoding: Option<String>,
+}
+
+impl Validate for SubtitleFileRequest {
+    fn validate(&self) -> Result<(), CommandError> {
+        let mut validator = Validator::new();
+        validator
+            .existing_file("input_path", &self.input_path)
+            .not_empty("target_language", &self.target_language)
+            .language("target_language_code", &self.target_language_code)
+            .not_empty("api_key", &self.api_key);
+        if let Some(output_path) = self.output_path.as_deref().filter(|path| !path.trim().is_empty()) {
+            validator.check("output_path", output_path != self.input_path, "Must differ from the source file");
+        }
+        validator.finish()
+    }
+}
+
+/// Translate a standalone VTT, SRT or ASS file, without a video or a pipeline job.
+/// The result keeps the source format and is written to `output_path`, or
+/// next to the source as `<name>_<language code>.<ext>` when none is given.
+/// `encoding` overrides the detected charset of the source.
+pub async fn translate_subtitle_file(
+    input_path: &Path,
+    encoding: Option<&str>,
+    output_path: Option<&Path>,
+    target: &TranslationTarget<'_>,
+    progress_sender: Option<mpsc::Sender<TranslationProgress>>,
+) -> Result<PathBuf> {
+    let TranslationTarget { target_language_code, target_language_name, api_key, options } = *target;
+    info!("Translating subtitle file {} to {}", input_path.display(), target_language_name);
+
+    let content = charset::read_subtitle_file(input_path, encoding).await?;
+    // ASS scripts are translated as cues and written back into their styles
+    let script = if ass::is_ass(Some(input_path), &content) { Some(ass::parse(&content)?) } else { None };
+    let is_srt = script.is_none() && vtt::SubtitleFormat::detect(Some(input_path), &content) == vtt::SubtitleFormat::Srt;
+    let vtt_content = match &script {
+        Some(script) => vtt::write_vtt_str(&script.to_cues()),
+        None if is_srt => subtitles::srt_to_vtt(&content),
+        None => content,
+    };
+
+    let mut vtt_file = parse_vtt_content(&vtt_content)?;
+    if vtt_file.segments.is_empty() {
+        return Err(anyhow!("No subtitles found in {}", input_path.display()));
+    }
+    mark_low_confidence(input_path, &mut vtt_file.segments).await;
+
+    let output_path = match output_path {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let file_stem = input_path
+                .file_stem()
+                .ok_or_else(|| anyhow!("Failed to get file stem"))?
+                .to_string_lossy();
+            let parent = input_path.parent().unwrap_or_else(|| Path::new("."));
+            parent.join(format!(
+                "{}_{}.{}",
+                sanitize_filename(&file_stem),
+                target_language_code,
+                match (&script, is_srt) {
+                    (Some(_), _) => "ass",
+                    (None, true) => "srt",
+                    (None, false) => "vtt",
+                }
+            ))
+        }
+    };
+    if output_path == input_path {
+        return Err(anyhow!("Output file must differ from the source file"));
+    }
+
+    let translated_segments =
+        translate_in_batches(&vtt_file.segments, target_language_name, api_key, options, None, None, progress_sender.as_ref()).await?;
+
+    let rendered = match &script {
+        Some(script) => {
+            let translated = render_vtt(&vtt_file.header, &translated_segments);
+            let cues = vtt::parse_vtt_str(&translated).map_err(|e| anyhow!("Failed to parse translation: {}", e))?;
+            ass::from_cues(&cues, Some(script)).to_string()
+        }
+        None if is_srt => render_srt(&translated_segments),
+        None => render_vtt(&vtt_file.header, &translated_segments),
+    };
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(&output_path, charset::to_utf8_bytes(&rendered, false)).await?;
+    info!("Subtitle translation saved to {}", output_path.display());
+
+    if let Some(sender) = &progress_sender {
+        sender
+            .send(TranslationProgress {
+                status: "Translation complete".to_string(),
+                progress: 100.0,
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to send progress: {}", e))?;
+    }
+
+    Ok(output_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_srt_from_vtt_segments() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,000\nFirst\n\n2\n00:00:03,000 --> 00:00:04,000\nSecond\n";
+        let parsed = parse_vtt_content(&subtitles::srt_to_vtt(srt)).unwrap();
+        assert_eq!(parsed.header, "WEBVTT");
+        assert_eq!(parsed.segments.len(), 2);
+        assert_eq!(parsed.segments[0].text, "First");
+        assert_eq!(render_srt(&parsed.segments), srt.to_string() + "\n");
+
+        let vtt = parse_vtt_content("WEBVTT\n\n01:02.500 --> 01:04.000 align:start\nHello\n\n00:01:05.000 --> 00:01:06.250\nTwo\nlines\n").unwrap();
+        assert_eq!(
+            render_srt(&vtt.segments),
+            "1\n00:01:02,500 --> 00:01:04,000\nHello\n\n2\n00:01:05,000 --> 00:01:06,250\nTwo\nlines\n\n"
+        );
+    }
+
+    #[test]
+    fn test_batch_message_surrounds_cues_with_context() {
+        let segments = parse_vtt_content("WEBVTT\n\n00:01.000 --> 00:02.000\nSee you\ntomorrow\n").unwrap().segments;
+        let context = BatchContext { before: vec![("Hi, Anna", "Привет, Анна")], after: vec!["Bye"] };
+        assert_eq!(
+            batch_message(&segments, &context),
+            "> Hi, Anna => Привет, Анна\n\n1. See you\ntomorrow\n\n> Bye"
+        );
+        assert_eq!(batch_message(&segments, &BatchContext::default()), "1. See you\ntomorrow");
+    }
+
+    #[test]
+    fn test_response_output_text() {
+        let response = serde_json::json!({
+            "status": "completed",
+            "output": [
+                { "type": "reasoning", "summary": [] },
+                { "type": "message", "content": [{ "type": "output_text", "text": "1. Hallo" }] }
+            ]
+        });
+        assert_eq!(response_output_text(&response).as_deref(), Some("1. Hallo"));
+        assert_eq!(response_output_text(&serde_json::json!({ "output": [] })), None);
+    }
+}
@@ -0,0 +1,841 @@
+//! Нормализация текста реплик перед синтезом.
+//!
+//! Движки читают "1920x1080", "$5", "15.03.2024" и "Dr." как придется: по
+//! цифрам, по буквам или на другом языке. Перед синтезом числа, даты, проценты,
+//! валюты, единицы измерения и частые сокращения раскрываются в слова языка
+//! озвучки. Правила есть для английского, русского и немецкого; для остальных
+//! языков применяются только сокращения пользователя.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Настройки нормализации для одного языка
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizationOptions {
+    pub enabled: bool,
+    /// Числа, даты, проценты, валюты и единицы измерения
+    pub numbers: bool,
+    /// Встроенная таблица сокращений языка
+    pub abbreviations: bool,
+    /// Сокращения пользователя ("ул." -> "улица"), важнее встроенных
+    pub custom_abbreviations: HashMap<String, String>,
+}
+
+impl Default for NormalizationOptions {
+    fn default() -> Self {
+        Self { enabled: true, numbers: true, abbreviations: true, custom_abbreviations: HashMap::new() }
+    }
+}
+
+/// Настройки для языка: сначала "pt-BR", затем "pt", иначе по умолчанию
+pub fn options_for(settings: &HashMap<String, NormalizationOptions>, language: Option<&str>) -> NormalizationOptions {
+    let Some(language) = language else {
+        return NormalizationOptions::default();
+    };
+    let code = language.to_lowercase();
+    let base = code.split(['-', '_']).next().unwrap_or(&code).to_string();
+    settings.get(&code).or_else(|| settings.get(&base)).cloned().unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    En,
+    Ru,
+    De,
+    Other,
+}
+
+/// Существительное при числе: формы для единственного и множественного числа
+/// (в русском: один, два, пять)
+struct Noun {
+    en: [&'static str; 2],
+    ru: [&'static str; 3],
+    ru_feminine: bool,
+    de: [&'static str; 2],
+    de_feminine: bool,
+}
+
+const fn noun(en: [&'static str; 2], ru: [&'static str; 3], ru_feminine: bool, de: [&'static str; 2], de_feminine: bool) -> Noun {
+    Noun { en, ru, ru_feminine, de, de_feminine }
+}
+
+const PERCENT: Noun = noun(["percent", "percent"], ["процент", "процента", "процентов"], false, ["Prozent", "Prozent"], false);
+
+/// Единицы измерения после числа: варианты записи и название
+const UNITS: &[(&[&str], Noun)] = &[
+    (&["km", "км"], noun(["kilometer", "kilometers"], ["километр", "километра", "километров"], false, ["Kilometer", "Kilometer"], false)),
+    (&["km/h", "км/ч"], noun(["kilometer per hour", "kilometers per hour"], ["километр в час", "километра в час", "километров в час"], false, ["Kilometer pro Stunde", "Kilometer pro Stunde"], false)),
+    (&["kg", "кг"], noun(["kilogram", "kilograms"], ["килограмм", "килограмма", "килограммов"], false, ["Kilogramm", "Kilogramm"], false)),
+    (&["cm", "см"], noun(["centimeter", "centimeters"], ["сантиметр", "сантиметра", "сантиметров"], false, ["Zentimeter", "Zentimeter"], false)),
+    (&["mm", "мм"], noun(["millimeter", "millimeters"], ["миллиметр", "миллиметра", "миллиметров"], false, ["Millimeter", "Millimeter"], false)),
+    (&["ms", "мс"], noun(["millisecond", "milliseconds"], ["миллисекунда", "миллисекунды", "миллисекунд"], true, ["Millisekunde", "Millisekunden"], true)),
+    (&["KB", "kB", "Кб", "КБ"], noun(["kilobyte", "kilobytes"], ["килобайт", "килобайта", "килобайт"], false, ["Kilobyte", "Kilobyte"], false)),
+    (&["MB", "Мб", "МБ"], noun(["megabyte", "megabytes"], ["мегабайт", "мегабайта", "мегабайт"], false, ["Megabyte", "Megabyte"], false)),
+    (&["GB", "Гб", "ГБ"], noun(["gigabyte", "gigabytes"], ["гигабайт", "гигабайта", "гигабайт"], false, ["Gigabyte", "Gigabyte"], false)),
+    (&["TB", "Тб", "ТБ"], noun(["terabyte", "terabytes"], ["терабайт", "терабайта", "терабайт"], false, ["Terabyte", "Terabyte"], false)),
+    (&["Hz", "Гц"], noun(["hertz", "hertz"], ["герц", "герца", "герц"], false, ["Hertz", "Hertz"], false)),
+    (&["kHz", "кГц"], noun(["kilohertz", "kilohertz"], ["килогерц", "килогерца", "килогерц"], false, ["Kilohertz", "Kilohertz"], false)),
+    (&["MHz", "МГц"], noun(["megahertz", "megahertz"], ["мегагерц", "мегагерца", "мегагерц"], false, ["Megahertz", "Megahertz"], false)),
+    (&["GHz", "ГГц"], noun(["gigahertz", "gigahertz"], ["гигагерц", "гигагерца", "гигагерц"], false, ["Gigahertz", "Gigahertz"], false)),
+    (&["fps"], noun(["frame per second", "frames per second"], ["кадр в секунду", "кадра в секунду", "кадров в секунду"], false, ["Bild pro Sekunde", "Bilder pro Sekunde"], false)),
+    (&["°C", "℃"], noun(["degree Celsius", "degrees Celsius"], ["градус Цельсия", "градуса Цельсия", "градусов Цельсия"], false, ["Grad Celsius", "Grad Celsius"], false)),
+];
+
+/// Валюты: символ, название и название разменной монеты
+const CURRENCIES: &[(char, Noun, Noun)] = &[
+    (
+        '$',
+        noun(["dollar", "dollars"], ["доллар", "доллара", "долларов"], false, ["Dollar", "Dollar"], false),
+        noun(["cent", "cents"], ["цент", "цента", "центов"], false, ["Cent", "Cent"], false),
+    ),
+    (
+        '€',
+        noun(["euro", "euros"], ["евро", "евро", "евро"], false, ["Euro", "Euro"], false),
+        noun(["cent", "cents"], ["цент", "цента", "центов"], false, ["Cent", "Cent"], false),
+    ),
+    (
+        '£',
+        noun(["pound", "pounds"], ["фунт", "фунта", "фунтов"], false, ["Pfund", "Pfund"], false),
+        noun(["penny", "pence"], ["пенни", "пенни", "пенни"], false, ["Penny", "Pence"], false),
+    ),
+    (
+        '₽',
+        noun(["ruble", "rubles"], ["рубль", "рубля", "рублей"], false, ["Rubel", "Rubel"], false),
+        noun(["kopeck", "kopecks"], ["копейка", "копейки", "копеек"], true, ["Kopeke", "Kopeken"], true),
+    ),
+];
+
+const EN_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("Dr.", "Doctor"),
+    ("Mr.", "Mister"),
+    ("Mrs.", "Missus"),
+    ("Ms.", "Miz"),
+    ("Prof.", "Professor"),
+    ("Jr.", "Junior"),
+    ("Sr.", "Senior"),
+    ("e.g.", "for example"),
+    ("i.e.", "that is"),
+    ("etc.", "et cetera"),
+    ("vs.", "versus"),
+    ("approx.", "approximately"),
+];
+
+const RU_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("т.е.", "то есть"),
+    ("т.д.", "так далее"),
+    ("т.п.", "тому подобное"),
+    ("т.к.", "так как"),
+    ("др.", "другие"),
+    ("напр.", "например"),
+    ("см.", "смотри"),
+    ("проф.", "профессор"),
+    ("им.", "имени"),
+];
+
+const DE_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("z.B.", "zum Beispiel"),
+    ("d.h.", "das heißt"),
+    ("u.a.", "unter anderem"),
+    ("usw.", "und so weiter"),
+    ("bzw.", "beziehungsweise"),
+    ("ggf.", "gegebenenfalls"),
+    ("inkl.", "inklusive"),
+    ("evtl.", "eventuell"),
+    ("ca.", "circa"),
+    ("Dr.", "Doktor"),
+    ("Prof.", "Professor"),
+];
+
+const EN_ONES: [&str; 20] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven", "twelve",
+    "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+const EN_TENS: [&str; 10] = ["", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+const EN_MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November",
+    "December",
+];
+
+const RU_ONES: [&str; 20] = [
+    "ноль", "один", "два", "три", "четыре", "пять", "шесть", "семь", "восемь", "девять", "десять", "одиннадцать",
+    "двенадцать", "тринадцать", "четырнадцать", "пятнадцать", "шестнадцать", "семнадцать", "восемнадцать",
+    "девятнадцать",
+];
+const RU_TENS: [&str; 10] =
+    ["", "", "двадцать", "тридцать", "сорок", "пятьдесят", "шестьдесят", "семьдесят", "восемьдесят", "девяносто"];
+const RU_HUNDREDS: [&str; 10] =
+    ["", "сто", "двести", "триста", "четыреста", "пятьсот", "шестьсот", "семьсот", "восемьсот", "девятьсот"];
+/// Основы порядковых числительных: "пят" -> "пятое", "пятого", "пятом"
+const RU_ORDINAL_ONES: [&str; 20] = [
+    "", "перв", "втор", "трет", "четверт", "пят", "шест", "седьм", "восьм", "девят", "десят", "одиннадцат",
+    "двенадцат", "тринадцат", "четырнадцат", "пятнадцат", "шестнадцат", "семнадцат", "восемнадцат", "девятнадцат",
+];
+const RU_ORDINAL_TENS: [&str; 10] =
+    ["", "", "двадцат", "тридцат", "сороков", "пятидесят", "шестидесят", "семидесят", "восьмидесят", "девяност"];
+const RU_ORDINAL_HUNDREDS: [&str; 10] =
+    ["", "сот", "двухсот", "трехсот", "четырехсот", "пятисот", "шестисот", "семисот", "восьмисот", "девятисот"];
+const RU_ORDINAL_THOUSANDS: [&str; 10] =
+    ["", "тысячн", "двухтысячн", "трехтысячн", "четырехтысячн", "пятитысячн", "шеститысячн", "семитысячн", "восьмитысячн", "девятитысячн"];
+const RU_MONTHS: [&str; 12] = [
+    "января", "февраля", "марта", "апреля", "мая", "июня", "июля", "августа", "сентября", "октября", "ноября", "декабря",
+];
+
+const DE_ONES: [&str; 20] = [
+    "null", "eins", "zwei", "drei", "vier", "fünf", "sechs", "sieben", "acht", "neun", "zehn", "elf", "zwölf",
+    "dreizehn", "vierzehn", "fünfzehn", "sechzehn", "siebzehn", "achtzehn", "neunzehn",
+];
+const DE_TENS: [&str; 10] = ["", "", "zwanzig", "dreißig", "vierzig", "fünfzig", "sechzig", "siebzig", "achtzig", "neunzig"];
+const DE_MONTHS: [&str; 12] = [
+    "Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September", "Oktober", "November", "Dezember",
+];
+
+/// Число из текста: целая часть и цифры после запятой
+#[derive(Debug, Clone, PartialEq)]
+struct Amount {
+    int: u64,
+    frac: Option<String>,
+}
+
+/// Падеж порядкового числительного в русских датах
+#[derive(Clone, Copy)]
+enum RuCase {
+    /// "пятнадцатое марта"
+    NeuterNominative,
+    /// "две тысячи двадцать четвертого года"
+    Genitive,
+    /// "в две тысячи двадцать четвертом году"
+    Prepositional,
+}
+
+/// Индекс формы существительного при русском числе: один, два, пять
+fn ru_plural(n: u64) -> usize {
+    match (n % 10, n % 100) {
+        (_, 11..=14) => 2,
+        (1, _) => 0,
+        (2..=4, _) => 1,
+        _ => 2,
+    }
+}
+
+fn en_below_thousand(n: u64) -> String {
+    let mut words = Vec::new();
+    if n >= 100 {
+        words.push(format!("{} hundred", EN_ONES[(n / 100) as usize]));
+    }
+    match n % 100 {
+        0 if n > 0 => {}
+        rest @ 0..=19 => words.push(EN_ONES[rest as usize].to_string()),
+        rest if rest % 10 == 0 => words.push(EN_TENS[(rest / 10) as usize].to_string()),
+        rest => words.push(format!("{}-{}", EN_TENS[(rest / 10) as usize], EN_ONES[(rest % 10) as usize])),
+    }
+    words.join(" ")
+}
+
+fn en_cardinal(n: u64) -> String {
+    if n < 1000 {
+        return en_below_thousand(n);
+    }
+    let mut words = Vec::new();
+    let mut rest = n;
+    for (scale, name) in [(1_000_000_000, "billion"), (1_000_000, "million"), (1_000, "thousand")] {
+        if rest >= scale {
+            words.push(format!("{} {}", en_below_thousand(rest / scale), name));
+            rest %= scale;
+        }
+    }
+    if rest > 0 {
+        words.push(en_below_thousand(rest));
+    }
+    words.join(" ")
+}
+
+fn en_ordinal(n: u64) -> String {
+    let cardinal = en_cardinal(n);
+    let split = cardinal.rfind([' ', '-']).map(|i| i + 1).unwrap_or(0);
+    let (head, last) = cardinal.split_at(split);
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        word if word.ends_with('y') => format!("{}ieth", &word[..word.len() - 1]),
+        word => format!("{}th", word),
+    };
+    format!("{}{}", head, last)
+}
+
+/// Годы читаются парами: "nineteen eighty-four", "twenty twenty-four"
+fn en_year(n: u64) -> String {
+    match n {
+        1100..=1999 | 2010..=2099 => match n % 100 {
+            0 => format!("{} hundred", en_cardinal(n / 100)),
+            rest @ 1..=9 => format!("{} oh {}", en_cardinal(n / 100), EN_ONES[rest as usize]),
+            rest => format!("{} {}", en_cardinal(n / 100), en_cardinal(rest)),
+        },
+        _ => en_cardinal(n),
+    }
+}
+
+fn ru_below_thousand(n: u64, feminine: bool) -> Vec<&'static str> {
+    let mut words = Vec::new();
+    if n >= 100 {
+        words.push(RU_HUNDREDS[(n / 100) as usize]);
+    }
+    let rest = n % 100;
+    if rest >= 20 {
+        words.push(RU_TENS[(rest / 10) as usize]);
+    }
+    let ones = if rest >= 20 { rest % 10 } else { rest };
+    match ones {
+        0 => {}
+        1 if feminine => words.push("одна"),
+        2 if feminine => words.push("две"),
+        ones => words.push(RU_ONES[ones as usize]),
+    }
+    words
+}
+
+fn ru_cardinal(n: u64, feminine: bool) -> String {
+    if n == 0 {
+        return RU_ONES[0].to_string();
+    }
+    let mut words = Vec::new();
+    let mut rest = n;
+    let scales: [(u64, [&str; 3], bool); 3] = [
+        (1_000_000_000, ["миллиард", "миллиарда", "миллиардов"], false),
+        (1_000_000, ["миллион", "миллиона", "миллионов"], false),
+        (1_000, ["тысяча", "тысячи", "тысяч"], true),
+    ];
+    for (scale, forms, scale_feminine) in scales {
+        if rest >= scale {
+            let count = rest / scale;
+            words.extend(ru_below_thousand(count, scale_feminine));
+            words.push(forms[ru_plural(count)]);
+            rest %= scale;
+        }
+    }
+    words.extend(ru_below_thousand(rest, feminine));
+    words.join(" ")
+}
+
+fn ru_ordinal(n: u64, case: RuCase) -> String {
+    // Порядковым становится только последнее слово, остальные остаются количественными
+    let (stem, last) = if n.is_multiple_of(1000) && n < 10_000 {
+        (RU_ORDINAL_THOUSANDS[(n / 1000) as usize], n)
+    } else if n.is_multiple_of(100) {
+        (RU_ORDINAL_HUNDREDS[((n % 1000) / 100) as usize], n % 1000)
+    } else if n % 100 < 20 {
+        (RU_ORDINAL_ONES[(n % 100) as usize], n % 100)
+    } else if n.is_multiple_of(10) {
+        (RU_ORDINAL_TENS[((n % 100) / 10) as usize], n % 100)
+    } else {
+        (RU_ORDINAL_ONES[(n % 10) as usize], n % 10)
+    };
+    let ending = match (case, stem == "трет") {
+        (RuCase::NeuterNominative, false) => "ое",
+        (RuCase::NeuterNominative, true) => "ье",
+        (RuCase::Genitive, false) => "ого",
+        (RuCase::Genitive, true) => "ьего",
+        (RuCase::Prepositional, false) => "ом",
+        (RuCase::Prepositional, true) => "ьем",
+    };
+    match n - last {
+        0 => format!("{}{}", stem, ending),
+        head => format!("{} {}{}", ru_cardinal(head, false), stem, ending),
+    }
+}
+
+/// Числа до 100 в немецком; `counted` — перед существительным ("ein", а не "eins")
+fn de_below_hundred(n: u64, counted: bool) -> String {
+    match n {
+        1 if counted => "ein".to_string(),
+        0..=19 => DE_ONES[n as usize].to_string(),
+        _ if n.is_multiple_of(10) => DE_TENS[(n / 10) as usize].to_string(),
+        _ => format!("{}und{}", if n % 10 == 1 { "ein" } else { DE_ONES[(n % 10) as usize] }, DE_TENS[(n / 10) as usize]),
+    }
+}
+
+fn de_below_thousand(n: u64, counted: bool) -> String {
+    let mut word = String::new();
+    if n >= 100 {
+        // "hundert", "zweihundert"
+        if n / 100 > 1 {
+            word.push_str(DE_ONES[(n / 100) as usize]);
+        }
+        word.push_str("hundert");
+    }
+    if !n.is_multiple_of(100) || n == 0 {
+        word.push_str(&de_below_hundred(n % 100, counted));
+    }
+    word
+}
+
+fn de_cardinal(n: u64, counted: bool) -> String {
+    if n < 1000 {
+        return de_below_thousand(n, counted);
+    }
+    let mut words = Vec::new();
+    let mut rest = n;
+    for (scale, one, many) in [(1_000_000_000, "eine Milliarde", "Milliarden"), (1_000_000, "eine Million", "Millionen")] {
+        if rest >= scale {
+            let count = rest / scale;
+            words.push(if count == 1 { one.to_string() } else { format!("{} {}", de_below_thousand(count, false), many) });
+            rest %= scale;
+        }
+    }
+    // Тысячи пишутся слитно с остатком: "zweitausendvierundzwanzig"
+    let mut word = String::new();
+    if rest >= 1000 {
+        word.push_str(&de_below_thousand(rest / 1000, true));
+        word.push_str("tausend");
+        rest %= 1000;
+    }
+    if rest > 0 {
+        word.push_str(&de_below_thousand(rest, counted));
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words.join(" ")
+}
+
+/// Порядковое в дательном падеже, как в "am fünfzehnten März"
+fn de_ordinal(n: u64) -> String {
+    let stem = match n {
+        1 => "erst".to_string(),
+        3 => "dritt".to_string(),
+        7 => "siebt".to_string(),
+        8 => "acht".to_string(),
+        2..=19 => format!("{}t", DE_ONES[n as usize]),
+        _ => format!("{}st", de_cardinal(n, false)),
+    };
+    format!("{}en", stem)
+}
+
+fn de_year(n: u64) -> String {
+    match n {
+        1100..=1999 => {
+            let rest = n % 100;
+            let rest = if rest == 0 { String::new() } else { de_below_hundred(rest, false) };
+            format!("{}hundert{}", de_below_hundred(n / 100, false), rest)
+        }
+        _ => de_cardinal(n, false),
+    }
+}
+
+fn all_digits(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Год, месяц и день из "2024-03-15" или (не для английского) "15.03.2024"
+fn parse_date(core: &str, language: Language) -> Option<(u64, usize, u64)> {
+    let (year, month, day) = match core.split('-').collect::<Vec<_>>()[..] {
+        [year, month, day] if year.len() == 4 && month.len() == 2 && day.len() == 2 => (year, month, day),
+        _ if language == Language::En => return None,
+        _ => match core.split('.').collect::<Vec<_>>()[..] {
+            [day, month, year] if year.len() == 4 && (1..=2).contains(&month.len()) && (1..=2).contains(&day.len()) => {
+                (year, month, day)
+            }
+            _ => return None,
+        },
+    };
+    if ![year, month, day].iter().all(|part| all_digits(part)) {
+        return None;
+    }
+    let (year, month, day) = (year.parse().ok()?, month.parse::<usize>().ok()?, day.parse().ok()?);
+    ((1..=12).contains(&month) && (1..=31).contains(&day)).then_some((year, month, day))
+}
+
+/// Отделяет кавычки и скобки в начале и знаки препинания в конце слова
+fn split_punctuation(word: &str) -> (&str, &str, &str) {
+    let core_start = word.find(|c: char| !"([{\"'«„“".contains(c)).unwrap_or(word.len());
+    let (prefix, rest) = word.split_at(core_start);
+    let core_end = rest.trim_end_matches(|c: char| ")]}\"'»“”.,;:!?".contains(c)).len();
+    let (core, suffix) = rest.split_at(core_end);
+    (prefix, core, suffix)
+}
+
+/// Раскрывает числа и сокращения в тексте реплики для одного языка
+pub struct Normalizer {
+    language: Language,
+    options: NormalizationOptions,
+}
+
+impl Normalizer {
+    pub fn new(language: Option<&str>, options: NormalizationOptions) -> Self {
+        let base = language.map(|code| code.to_lowercase()).and_then(|code| code.split(['-', '_']).next().map(str::to_string));
+        let language = match base.as_deref() {
+            Some("en") => Language::En,
+            Some("ru") => Language::Ru,
+            Some("de") => Language::De,
+            _ => Language::Other,
+        };
+        Self { language, options }
+    }
+
+    /// Есть ли что применять к тексту
+    pub fn is_active(&self) -> bool {
+        self.options.enabled && (self.language != Language::Other || !self.options.custom_abbreviations.is_empty())
+    }
+
+    fn abbreviation(&self, candidate: &str) -> Option<String> {
+        if let Some(expansion) = self.options.custom_abbreviations.get(candidate) {
+            return Some(expansion.clone());
+        }
+        if !self.options.abbreviations {
+            return None;
+        }
+        let table = match self.language {
+            Language::En => EN_ABBREVIATIONS,
+            Language::Ru => RU_ABBREVIATIONS,
+            Language::De => DE_ABBREVIATIONS,
+            Language::Other => return None,
+        };
+        let find = |candidate: &str| table.iter().find(|(short, _)| *short == candidate).map(|(_, long)| long.to_string());
+        find(candidate).or_else(|| {
+            // "Т.е." в начале предложения
+            let mut chars = candidate.chars();
+            let first = chars.next()?;
+            let lowered: String = first.to_lowercase().chain(chars).collect();
+            let expansion = find(&lowered)?;
+            let mut chars = expansion.chars();
+            Some(chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default())
+        })
+    }
+
+    fn parse_amount(&self, s: &str) -> Option<Amount> {
+        if !s.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        let (decimal, grouping): (&[char], Option<char>) = match self.language {
+            Language::En => (&['.'], Some(',')),
+            Language::De => (&[','], Some('.')),
+            _ => (&[',', '.'], None),
+        };
+        let (int, frac) = match s.find(decimal) {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        let int: String = match grouping.filter(|separator| int.contains(*separator)) {
+            Some(separator) => {
+                let groups: Vec<&str> = int.split(separator).collect();
+                let valid = groups[0].len() <= 3 && groups[1..].iter().all(|group| group.len() == 3);
+                if !valid {
+                    return None;
+                }
+                groups.concat()
+            }
+            None => int.to_string(),
+        };
+        if !all_digits(&int) || int.len() > 12 || frac.is_some_and(|frac| !all_digits(frac)) {
+            return None;
+        }
+        Some(Amount { int: int.parse().ok()?, frac: frac.map(str::to_string) })
+    }
+
+    fn cardinal(&self, n: u64) -> String {
+        match self.language {
+            Language::Ru => ru_cardinal(n, false),
+            Language::De => de_cardinal(n, false),
+            _ => en_cardinal(n),
+        }
+    }
+
+    fn digits(&self, digits: &str) -> String {
+        let ones = match self.language {
+            Language::Ru => &RU_ONES,
+            Language::De => &DE_ONES,
+            _ => &EN_ONES,
+        };
+        digits.chars().filter_map(|c| c.to_digit(10)).map(|d| ones[d as usize]).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Число само по себе или перед существительным рода `feminine`
+    fn amount(&self, amount: &Amount, counted: Option<bool>) -> String {
+        let Some(frac) = &amount.frac else {
+            return match (self.language, counted) {
+                (Language::Ru, Some(feminine)) => ru_cardinal(amount.int, feminine),
+                (Language::De, Some(true)) if amount.int % 100 == 1 => format!("{}e", de_cardinal(amount.int, true)),
+                (Language::De, Some(false)) => de_cardinal(amount.int, true),
+                _ => self.cardinal(amount.int),
+            };
+        };
+        match self.language {
+            // "две целых пять десятых"
+            Language::Ru if frac.len() <= 3 => {
+                let denominators = [["десятая", "десятых"], ["сотая", "сотых"], ["тысячная", "тысячных"]];
+                let fraction: u64 = frac.parse().unwrap_or(0);
+                let whole = if ru_plural(amount.int) == 0 { "целая" } else { "целых" };
+                let part = denominators[frac.len() - 1][usize::from(ru_plural(fraction) != 0)];
+                format!("{} {} {} {}", ru_cardinal(amount.int, true), whole, ru_cardinal(fraction, true), part)
+            }
+            Language::Ru => format!("{} запятая {}", ru_cardinal(amount.int, true), self.digits(frac)),
+            Language::De => format!("{} Komma {}", de_cardinal(amount.int, false), self.digits(frac)),
+            _ => format!("{} point {}", en_cardinal(amount.int), self.digits(frac)),
+        }
+    }
+
+    fn noun(&self, amount: &Amount, noun: &Noun) -> String {
+        let single = amount.int == 1 && amount.frac.is_none();
+        let form = match self.language {
+            Language::Ru if amount.frac.is_some() => noun.ru[1],
+            Language::Ru => noun.ru[ru_plural(amount.int)],
+            Language::De => noun.de[usize::from(!single)],
+            _ => noun.en[usize::from(!single)],
+        };
+        let feminine = match self.language {
+            Language::Ru => noun.ru_feminine,
+            Language::De => noun.de_feminine,
+            _ => false,
+        };
+        format!("{} {}", self.amount(amount, Some(feminine)), form)
+    }
+
+    fn money(&self, amount: &Amount, currency: &Noun, subunit: &Noun) -> String {
+        let cents = amount.frac.as_deref().filter(|frac| frac.len() == 2).and_then(|frac| frac.parse::<u64>().ok());
+        let Some(cents) = cents else {
+            return self.noun(amount, currency);
+        };
+        let whole = self.noun(&Amount { int: amount.int, frac: None }, currency);
+        if cents == 0 {
+            return whole;
+        }
+        let and = match self.language {
+            Language::En => " and ",
+            _ => " ",
+        };
+        format!("{}{}{}", whole, and, self.noun(&Amount { int: cents, frac: None }, subunit))
+    }
+
+    fn date(&self, (year, month, day): (u64, usize, u64)) -> String {
+        match self.language {
+            Language::Ru => format!(
+                "{} {} {} года",
+                ru_ordinal(day, RuCase::NeuterNominative),
+                RU_MONTHS[month - 1],
+                ru_ordinal(year, RuCase::Genitive)
+            ),
+            Language::De => format!("{} {} {}", de_ordinal(day), DE_MONTHS[month - 1], de_year(year)),
+            _ => format!("{} {}, {}", EN_MONTHS[month - 1], en_ordinal(day), en_year(year)),
+        }
+    }
+
+    fn unit(symbol: &str) -> Option<&'static Noun> {
+        UNITS.iter().find(|(symbols, _)| symbols.contains(&symbol)).map(|(_, noun)| noun)
+    }
+
+    fn currency(symbol: char) -> Option<(&'static Noun, &'static Noun)> {
+        CURRENCIES.iter().find(|(sign, _, _)| *sign == symbol).map(|(_, currency, subunit)| (currency, subunit))
+    }
+
+    /// Слово с числом в словах; `next` — следующее слово, если правило его
+    /// поглотило (единица измерения или знак валюты отдельно от числа),
+    /// возвращается true
+    fn number_word(&self, core: &str, next: Option<&str>) -> Option<(String, bool)> {
+        if let Some(date) = parse_date(core, self.language) {
+            return Some((self.date(date), false));
+        }
+        // Разрешение экрана: "1920x1080"
+        if let Some((width, height)) = core.split_once(['x', 'х', '×'])
+            && all_digits(width)
+            && all_digits(height)
+        {
+            let by = match self.language {
+                Language::Ru => "на",
+                Language::De => "mal",
+                _ => "by",
+            };
+            let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+            return Some((format!("{} {} {}", self.cardinal(width), by, self.cardinal(height)), false));
+        }
+        if let Some(number) = core.strip_suffix('%') {
+            return Some((self.noun(&self.parse_amount(number)?, &PERCENT), false));
+        }
+        if let Some(number) = core.strip_prefix('№').filter(|_| self.language == Language::Ru) {
+            let number = self.parse_amount(number)?;
+            return Some((format!("номер {}", self.amount(&number, None)), false));
+        }
+        let mut chars = core.chars();
+        if let Some((currency, subunit)) = chars.next().and_then(Self::currency) {
+            return Some((self.money(&self.parse_amount(chars.as_str())?, currency, subunit), false));
+        }
+        let mut chars = core.chars();
+        if let Some((currency, subunit)) = chars.next_back().and_then(Self::currency) {
+            return Some((self.money(&self.parse_amount(chars.as_str())?, currency, subunit), false));
+        }
+        if self.language == Language::En {
+            let lower = core.to_lowercase();
+            for suffix in ["st", "nd", "rd", "th"] {
+                let Some(number) = lower.strip_suffix(suffix) else { continue };
+                let n: u64 = number.parse().ok().filter(|_| all_digits(number))?;
+                let expected = match (n % 10, n % 100) {
+                    (_, 11..=13) => "th",
+                    (1, _) => "st",
+                    (2, _) => "nd",
+                    (3, _) => "rd",
+                    _ => "th",
+                };
+                return (suffix == expected).then(|| (en_ordinal(n), false));
+            }
+        }
+
+        // Число с единицей вплотную ("16GB") или следующим словом ("16 GB")
+        let number_end = core.find(|c: char| !(c.is_ascii_digit() || ",.".contains(c))).unwrap_or(core.len());
+        let (number, unit) = core.split_at(number_end);
+        let amount = self.parse_amount(number)?;
+        if !unit.is_empty() {
+            return Some((self.noun(&amount, Self::unit(unit)?), false));
+        }
+        if let Some(next) = next {
+            if let Some(noun) = Self::unit(next) {
+                return Some((self.noun(&amount, noun), true));
+            }
+            let mut chars = next.chars();
+            if let (Some(sign), None) = (chars.next(), chars.next())
+                && let Some((currency, subunit)) = Self::currency(sign)
+            {
+                return Some((self.money(&amount, currency, subunit), true));
+            }
+            // "в 2024 году", "до 2024 года"
+            if self.language == Language::Ru && amount.frac.is_none() && (1000..=2999).contains(&amount.int) {
+                let case = match next {
+                    "года" => Some(RuCase::Genitive),
+                    "году" => Some(RuCase::Prepositional),
+                    _ => None,
+                };
+                if let Some(case) = case {
+                    return Some((ru_ordinal(amount.int, case), false));
+                }
+            }
+        }
+        // Четырехзначные числа без разделителей читаются как годы
+        let plain = amount.frac.is_none() && all_digits(number) && number.len() == 4;
+        let words = match self.language {
+            Language::En if plain => en_year(amount.int),
+            Language::De if plain => de_year(amount.int),
+            _ => self.amount(&amount, None),
+        };
+        Some((words, false))
+    }
+
+    /// Текст с раскрытыми числами и сокращениями
+    pub fn apply(&self, text: &str) -> String {
+        if !self.is_active() {
+            return text.to_string();
+        }
+        let mut words: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+        // "1 000 000": группы разрядов через пробел собираются в одно число
+        if self.language == Language::Ru && self.options.numbers {
+            let mut merged: Vec<String> = Vec::with_capacity(words.len());
+            for word in words {
+                match merged.last_mut() {
+                    Some(last) if word.len() == 3 && all_digits(&word) && all_digits(last.trim_start_matches(['$', '€', '£'])) => {
+                        last.push_str(&word);
+                    }
+                    _ => merged.push(word),
+                }
+            }
+            words = merged;
+        }
+
+        let mut output = Vec::with_capacity(words.len());
+        let mut i = 0;
+        while i < words.len() {
+            let word = &words[i];
+            let (prefix, core, suffix) = split_punctuation(word);
+            let next = words.get(i + 1).map(|next| split_punctuation(next));
+            // Сокращение вместе с точкой; "z. B." в два слова тоже
+            let dotted = word[prefix.len()..].trim_end_matches(|c: char| ")]}\"'»“”,;:!?".contains(c));
+            let tail = &word[prefix.len() + dotted.len()..];
+            if let Some((_, next_core, _)) = next.filter(|_| dotted.ends_with('.')) {
+                let next_word = &words[i + 1];
+                let next_dotted = next_word.trim_end_matches(|c: char| ")]}\"'»“”,;:!?".contains(c));
+                let joined = format!("{}{}", dotted, next_dotted);
+                if !next_core.is_empty()
+                    && next_dotted.ends_with('.')
+                    && let Some(expansion) = self.abbreviation(&joined)
+                {
+                    output.push(format!("{}{}{}", prefix, expansion, &next_word[next_dotted.len()..]));
+                    i += 2;
+                    continue;
+                }
+            }
+            if let Some(expansion) = self.abbreviation(dotted) {
+                output.push(format!("{}{}{}", prefix, expansion, tail));
+                i += 1;
+                continue;
+            }
+            // "No. 5", "Nr. 5"
+            let number_follows = next.is_some_and(|(_, next_core, _)| next_core.starts_with(|c: char| c.is_ascii_digit()));
+            let before_number = match (self.language, dotted) {
+                (Language::En, "No.") => Some("number"),
+                (Language::De, "Nr.") => Some("Nummer"),
+                (Language::Ru, "№") => Some("номер"),
+                _ => None,
+            };
+            if let (Some(expansion), true, true) = (before_number, number_follows, self.options.abbreviations) {
+                output.push(format!("{}{}{}", prefix, expansion, tail));
+                i += 1;
+                continue;
+            }
+
+            let next_core = next.filter(|(next_prefix, _, _)| next_prefix.is_empty()).map(|(_, core, _)| core);
+            let numbered = if self.options.numbers && self.language != Language::Other {
+                self.number_word(core, next_core)
+            } else {
+                None
+            };
+            match numbered {
+                Some((words_of_number, consumed_next)) => {
+                    if consumed_next {
+                        let (_, _, next_suffix) = next.unwrap_or_default();
+                        output.push(format!("{}{}{}", prefix, words_of_number, next_suffix));
+                        i += 2;
+                    } else {
+                        output.push(format!("{}{}{}", prefix, words_of_number, suffix));
+                        i += 1;
+                    }
+                }
+                None => {
+                    output.push(word.clone());
+                    i += 1;
+                }
+            }
+        }
+        output.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(language: &str, text: &str) -> String {
+        Normalizer::new(Some(language), NormalizationOptions::default()).apply(text)
+    }
+
+    #[test]
+    fn test_numbers_units_and_abbreviations_are_spoken_in_the_language() {
+        assert_eq!(
+            normalize("en", "Dr. Smith records 1920x1080 at 60fps, 25% faster, for $4.99 (since 2024-03-15)."),
+            "Doctor Smith records one thousand nine hundred twenty by one thousand eighty at sixty frames per second, \
+             twenty-five percent faster, for four dollars and ninety-nine cents (since March fifteenth, twenty twenty-four)."
+        );
+        assert_eq!(normalize("en", "The 21st track of 1984 is No. 3"), "The twenty-first track of nineteen eighty-four is number three");
+        assert_eq!(
+            normalize("ru", "Это 21 км, т.е. 2,5 часа; 15.03.2024 было 1 000 000 ₽, в 2024 году"),
+            "Это двадцать один километр, то есть две целых пять десятых часа; пятнадцатое марта две тысячи двадцать \
+             четвертого года было один миллион рублей, в две тысячи двадцать четвертом году"
+        );
+        assert_eq!(
+            normalize("de", "Am 03.10.1990 kostete es z. B. 1 € bei 5 ms"),
+            "Am dritten Oktober neunzehnhundertneunzig kostete es zum Beispiel ein Euro bei fünf Millisekunden"
+        );
+        // Для языков без правил текст не меняется
+        assert_eq!(normalize("fi", "Dr. 1920x1080"), "Dr. 1920x1080");
+    }
+}
@@ -9,201 +9,283 @@
 //! 4. Корректировка длительности фрагментов с помощью rubato, чтобы итоговая длительность каждого фрагмента стала равной целевому интервалу (без обрезки).
 //! 5. Склейка фрагментов с применением fade‑in/fade‑out для устранения щелчков.
 //! 6. Нормализация громкости: если указан путь к исходному аудио (mp3/m4a), итоговое аудио приводится к такому же уровню.
+//!    При микшировании с инструменталом оригинала целевой уровень применяется к суммарному миксу.
 //! 7. Кодирование итогового аудио в WAV.
 //! 8. Асинхронная передача обновлений прогресса выполнения.
 //!
 //! **Замечание:** Для полноценного использования потребуется доработка обработки ошибок и параметризация DSP‑алгоритмов.
 
-use std::path::Path;
-use std::process::Command;
-use log::error;
-use tokio::sync::mpsc::Sender;
-use rubato::{SincFixedIn, FftFixedIn};
-use anyhow::Context;
-
-/// Модуль для работы с библиотекой SoundTouch через FFI
-pub mod soundtouch {
+/// Изменение темпа и тона речи без нативных библиотек.
+///
+/// Темп меняется алгоритмом WSOLA: вход режется на перекрывающиеся
+/// последовательности, и каждая следующая вклеивается в точке наибольшего
+/// сходства с хвостом предыдущей, поэтому тон сохраняется. Тон и rate меняются
+/// передискретизацией с оконным sinc-фильтром.
+pub mod timestretch {
     use super::TtsError;
     use super::Result;
-    use log::{info, warn, error};
-    use std::process::Command;
-    use std::path::Path;
-    use anyhow::Context;
+    use serde::{Deserialize, Serialize};
 
-    /// Структура для FFI-обертки SoundTouch
-    #[repr(C)]
-    pub struct SoundTouch {
-        _private: [u8; 0],
+    /// Параметры качества алгоритма WSOLA
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct StretchQuality {
+        /// Антиалиасинговый фильтр при изменении rate и тона
+        pub use_aa_filter: bool,
+        /// Длина антиалиасингового фильтра (8..128, кратна 4)
+        pub aa_filter_length: u32,
+        /// Быстрый, но менее точный поиск точки склейки
+        pub use_quick_seek: bool,
+        /// Длина обрабатываемой последовательности, мс (0 — автоматически)
+        pub sequence_ms: u32,
+        /// Окно поиска наилучшей точки склейки, мс (0 — автоматически)
+        pub seek_window_ms: u32,
+        /// Перекрытие соседних последовательностей, мс
+        pub overlap_ms: u32,
     }
 
-    /// FFI-обёртки для библиотеки SoundTouch.
-    unsafe extern "C" {
-        pub fn soundtouch_createInstance() -> *mut SoundTouch;
-        pub fn soundtouch_destroyInstance(instance: *mut SoundTouch);
-        pub fn soundtouch_setSampleRate(instance: *mut SoundTouch, srate: u32);
-        pub fn soundtouch_setChannels(instance: *mut SoundTouch, numChannels: u32);
-        pub fn soundtouch_setTempo(instance: *mut SoundTouch, newTempo: f32);
-        pub fn soundtouch_setPitch(instance: *mut SoundTouch, newPitch: f32);
-        pub fn soundtouch_putSamples(instance: *mut SoundTouch, samples: *const f32, numSamples: u32);
-        pub fn soundtouch_receiveSamples(instance: *mut SoundTouch, outBuffer: *mut f32, maxSamples: u32) -> u32;
+    impl StretchQuality {
+        /// Настройки для речи: короткие последовательности меньше «дублируют»
+        /// слоги при ускорении, чем автоматические значения, рассчитанные на музыку
+        pub fn speech() -> Self {
+            Self {
+                use_aa_filter: true,
+                aa_filter_length: 64,
+                use_quick_seek: false,
+                sequence_ms: 40,
+                seek_window_ms: 15,
+                overlap_ms: 8,
+            }
+        }
+
+        /// Длины последовательности, окна поиска и перекрытия в сэмплах для темпа `tempo`
+        fn lengths(&self, sample_rate: u32, tempo: f64) -> (usize, usize, usize) {
+            let samples = |ms: f64| (sample_rate as f64 * ms / 1000.0).round() as usize;
+            // Автоматические значения как в SoundTouch: при замедлении длиннее
+            let sequence_ms = match self.sequence_ms {
+                0 => (150.0 - 50.0 * tempo).clamp(50.0, 125.0),
+                ms => ms as f64,
+            };
+            let seek_window_ms = match self.seek_window_ms {
+                0 => (28.0 - 6.0 * tempo).clamp(15.0, 25.0),
+                ms => ms as f64,
+            };
+            let sequence = samples(sequence_ms).max(2);
+            let overlap = samples(self.overlap_ms as f64).min(sequence / 2);
+            (sequence, samples(seek_window_ms), overlap)
+        }
     }
 
-    /// Проверяет, установлена ли библиотека SoundTouch
-    pub fn is_soundtouch_installed() -> bool {
-        #[cfg(target_os = "macos")]
-        {
-            // На macOS проверяем наличие библиотеки через Homebrew
-            let output = Command::new("brew")
-                .args(&["list", "sound-touch"])
-                .output();
-            
-            match output {
-                Ok(out) => out.status.success(),
-                Err(_) => false,
-            }
+    impl Default for StretchQuality {
+        fn default() -> Self {
+            Self::speech()
         }
-        
-        #[cfg(target_os = "linux")]
-        {
-            // На Linux проверяем наличие библиотеки через pkg-config или в стандартных путях
-            let pkg_config = Command::new("pkg-config")
-                .args(&["--exists", "soundtouch"])
-                .status();
-                
-            match pkg_config {
-                Ok(status) => status.success(),
-                Err(_) => {
-                    // Проверим наличие файла библиотеки в стандартных путях
-                    Path::new("/usr/lib/libSoundTouch.so").exists() || 
-                    Path::new("/usr/local/lib/libSoundTouch.so").exists()
-                },
-            }
+    }
+
+    /// Как подгоняется длительность фрагмента.
+    ///
+    /// Три параметра: tempo меняет длительность без изменения тона, rate —
+    /// длительность вместе с тоном (как ускоренное воспроизведение), pitch —
+    /// только тон. Небольшая доля rate делает быструю речь естественнее, чем
+    /// чистый tempo, ценой слегка повышенного голоса.
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct StretchSettings {
+        /// Доля изменения длительности через rate: 0.0 — только tempo (тон
+        /// сохраняется), 1.0 — только rate
+        pub rate_share: f32,
+        /// Постоянный сдвиг тона в полутонах поверх изменения от rate
+        pub pitch_semitones: f32,
+        pub quality: StretchQuality,
+    }
+
+    impl Default for StretchSettings {
+        fn default() -> Self {
+            Self { rate_share: 0.0, pitch_semitones: 0.0, quality: StretchQuality::default() }
         }
-        
-        #[cfg(target_os = "windows")]
-        {
-            // На Windows проверяем наличие DLL
-            Path::new("C:\\Program Files\\SoundTouch\\bin\\SoundTouch.dll").exists() ||
-            Path::new("C:\\Program Files (x86)\\SoundTouch\\bin\\SoundTouch.dll").exists()
+    }
+
+    impl StretchSettings {
+        /// Раскладывает коэффициент ускорения на (tempo, rate, pitch)
+        pub fn controls(&self, speed_factor: f32) -> (f32, f32, f32) {
+            let rate = speed_factor.powf(self.rate_share.clamp(0.0, 1.0));
+            let tempo = speed_factor / rate;
+            let pitch = 2f32.powf(self.pitch_semitones / 12.0);
+            (tempo, rate, pitch)
         }
-        
-        #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
-        {
-            false // На других ОС просто возвращаем false
+    }
+
+    /// Ускоряет моно-фрагмент в `speed_factor` раз, распределяя ускорение
+    /// между tempo и rate по настройкам
+    pub fn process(input: &[f32], sample_rate: u32, speed_factor: f32, settings: &StretchSettings) -> Result<Vec<f32>> {
+        if !speed_factor.is_finite() || speed_factor <= 0.0 {
+            return Err(TtsError::TimeStretchingError(format!("Недопустимый коэффициент ускорения: {}", speed_factor)));
         }
+        if sample_rate == 0 {
+            return Err(TtsError::TimeStretchingError("Частота дискретизации не задана".to_string()));
+        }
+        let (tempo, rate, pitch) = settings.controls(speed_factor);
+        // Сначала WSOLA растягивает так, чтобы после передискретизации с
+        // изменением тона в rate * pitch раз получилась нужная длительность
+        let shift = rate as f64 * pitch as f64;
+        let stretch = tempo as f64 / pitch as f64;
+        let stretched = wsola(input, sample_rate, stretch, &settings.quality);
+        Ok(resample(&stretched, shift, &settings.quality))
     }
 
-    /// Устанавливает библиотеку SoundTouch
-    pub fn install_soundtouch() -> Result<()> {
-        info!("Установка библиотеки SoundTouch...");
-        
-        #[cfg(target_os = "macos")]
-        {
-            let status = Command::new("brew")
-                .args(&["install", "sound-touch"])
-                .status()
-                .map_err(|e| TtsError::Other(anyhow::anyhow!("Ошибка установки SoundTouch через Homebrew: {}", e)))?;
-                
-            if !status.success() {
-                return Err(TtsError::Other(anyhow::anyhow!("Не удалось установить SoundTouch через Homebrew")));
-            }
+    /// Меняет длительность в `1 / tempo` раз без изменения тона
+    fn wsola(input: &[f32], sample_rate: u32, tempo: f64, quality: &StretchQuality) -> Vec<f32> {
+        if (tempo - 1.0).abs() < 1e-4 {
+            return input.to_vec();
         }
-        
-        #[cfg(target_os = "linux")]
-        {
-            // Пробуем установить через apt для Debian/Ubuntu
-            let apt_status = Command::new("apt-get")
-                .args(&["install", "-y", "libsoundtouch-dev"])
-                .status();
-                
-            if let Ok(status) = apt_status {
-                if status.success() {
-                    return Ok(());
-                }
+        let target_len = (input.len() as f64 / tempo).round() as usize;
+        let (sequence, seek, overlap) = quality.lengths(sample_rate, tempo);
+        if input.len() < sequence + seek {
+            // Слишком короткий фрагмент для склеек: несколько миллисекунд
+            // можно растянуть и со сдвигом тона
+            return resample(input, tempo, quality);
+        }
+
+        // За шаг выводится sequence - overlap сэмплов, вход сдвигается в tempo раз больше.
+        // Вход дополнен тишиной, чтобы последние сэмплы тоже растянулись
+        let step = sequence - overlap;
+        let skip = step as f64 * tempo;
+        let mut padded = input.to_vec();
+        padded.resize(input.len() + sequence + seek + skip.ceil() as usize, 0.0);
+        let mut output = Vec::with_capacity(target_len + sequence);
+        let mut tail: Vec<f32> = Vec::new();
+        let mut position = 0.0f64;
+        while output.len() < target_len && position as usize + seek + sequence <= padded.len() {
+            let base = position as usize;
+            let start = if tail.is_empty() { base } else { base + best_offset(&padded, base, seek, &tail, quality.use_quick_seek) };
+            let segment = &padded[start..start + sequence];
+            // Линейный кроссфейд хвоста предыдущей последовательности с началом новой
+            for (i, &sample) in segment[..overlap].iter().enumerate() {
+                let fade = i as f32 / overlap as f32;
+                let previous = tail.get(i).copied().unwrap_or(0.0);
+                output.push(if tail.is_empty() { sample } else { previous * (1.0 - fade) + sample * fade });
             }
-            
-            // Пробуем через pacman для Arch Linux
-            let pacman_status = Command::new("pacman")
-                .args(&["-S", "--noconfirm", "soundtouch"])
-                .status();
-                
-            if let Ok(status) = pacman_status {
-                if status.success() {
-                    return Ok(());
-                }
+            output.extend_from_slice(&segment[overlap..sequence - overlap]);
+            tail = segment[sequence - overlap..].to_vec();
+            position += skip;
+        }
+        output.extend_from_slice(&tail);
+        output.resize(target_len, 0.0);
+        output
+    }
+
+    /// Смещение в окне поиска, с которого вход лучше всего продолжает `tail`
+    fn best_offset(input: &[f32], base: usize, seek: usize, tail: &[f32], quick: bool) -> usize {
+        let similarity = |offset: usize| {
+            let candidate = &input[base + offset..base + offset + tail.len()];
+            let (mut correlation, mut energy) = (0.0f64, 0.0f64);
+            for (&a, &b) in tail.iter().zip(candidate) {
+                correlation += a as f64 * b as f64;
+                energy += b as f64 * b as f64;
             }
-            
-            // Если ни один менеджер пакетов не сработал, возвращаем ошибку
-            return Err(TtsError::Other(anyhow::anyhow!("Не удалось установить SoundTouch. Пожалуйста, установите вручную libsoundtouch-dev или аналогичный пакет для вашего дистрибутива")));
+            correlation / (energy + 1e-9).sqrt()
+        };
+        let best_of = |offsets: &mut dyn Iterator<Item = usize>| {
+            offsets.fold((0, f64::MIN), |best, offset| {
+                let score = similarity(offset);
+                if score > best.1 { (offset, score) } else { best }
+            })
+        };
+
+        if !quick {
+            return best_of(&mut (0..seek)).0;
         }
-        
-        #[cfg(target_os = "windows")]
-        {
-            error!("Автоматическая установка SoundTouch на Windows не поддерживается");
-            return Err(TtsError::Other(anyhow::anyhow!("Пожалуйста, скачайте и установите SoundTouch вручную с официального сайта")));
+        // Быстрый поиск: грубый проход с шагом 4 и уточнение вокруг лучшей точки
+        let (coarse, _) = best_of(&mut (0..seek).step_by(4));
+        best_of(&mut (coarse.saturating_sub(3)..(coarse + 4).min(seek))).0
+    }
+
+    /// Передискретизация, ускоряющая воспроизведение в `factor` раз: длительность
+    /// делится на `factor`, тон умножается на него
+    fn resample(input: &[f32], factor: f64, quality: &StretchQuality) -> Vec<f32> {
+        if (factor - 1.0).abs() < 1e-4 || input.is_empty() {
+            return input.to_vec();
         }
-        
-        #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
-        {
-            return Err(TtsError::Other(anyhow::anyhow!("Автоматическая установка SoundTouch не поддерживается для данной ОС")));
+        let output_len = (input.len() as f64 / factor).round() as usize;
+        let sample_at = |index: isize| {
+            if index < 0 { 0.0 } else { input.get(index as usize).copied().unwrap_or(0.0) }
+        };
+
+        if !quality.use_aa_filter {
+            return (0..output_len)
+                .map(|i| {
+                    let position = i as f64 * factor;
+                    let index = position.floor() as isize;
+                    let frac = (position - index as f64) as f32;
+                    sample_at(index) * (1.0 - frac) + sample_at(index + 1) * frac
+                })
+                .collect();
         }
-        
-        info!("SoundTouch успешно установлен");
-        Ok(())
+
+        // Оконный sinc; при ускорении частота среза снижается, чтобы не было наложения спектров
+        let taps = (quality.aa_filter_length.clamp(8, 128) / 2) as isize;
+        let cutoff = (1.0 / factor).min(1.0);
+        (0..output_len)
+            .map(|i| {
+                let position = i as f64 * factor;
+                let center = position.floor() as isize;
+                let mut sum = 0.0f64;
+                for index in center - taps + 1..=center + taps {
+                    let x = position - index as f64;
+                    let window = 0.5 + 0.5 * (std::f64::consts::PI * x / taps as f64).cos();
+                    if x.abs() < taps as f64 {
+                        sum += sample_at(index) as f64 * cutoff * sinc(cutoff * x) * window;
+                    }
+                }
+                sum as f32
+            })
+            .collect()
     }
 
-    /// Проверяет, установлен ли SoundTouch, и устанавливает его при необходимости
-    pub fn ensure_soundtouch_installed() -> Result<()> {
-        if !is_soundtouch_installed() {
-            info!("SoundTouch не установлен, начинаем установку...");
-            install_soundtouch()?;
+    fn sinc(x: f64) -> f64 {
+        if x.abs() < 1e-9 {
+            1.0
         } else {
-            info!("SoundTouch уже установлен");
+            let px = std::f64::consts::PI * x;
+            px.sin() / px
         }
-        Ok(())
     }
 
-    /// Обёртка для обработки аудио через SoundTouch с сохранением pitch.
-    pub fn process_with_soundtouch(input: &[f32], sample_rate: u32, tempo: f32) -> Result<Vec<f32>> {
-        // Проверка установки SoundTouch теперь не нужна здесь, так как она выполняется
-        // в начале всего процесса TTS в synchronizer::process_sync
+    #[cfg(test)]
+    mod tests {
+        use super::*;
 
-        unsafe {
-            let instance = soundtouch_createInstance();
-            if instance.is_null() {
-                return Err(TtsError::Other(anyhow::anyhow!("Не удалось создать экземпляр SoundTouch")));
-            }
-            
-            // Используем RAII-паттерн для гарантированного уничтожения экземпляра
-            struct SoundTouchInstance(*mut SoundTouch);
-            impl Drop for SoundTouchInstance {
-                fn drop(&mut self) {
-                    unsafe { soundtouch_destroyInstance(self.0); }
-                }
-            }
-            let _instance_guard = SoundTouchInstance(instance);
-            
-            soundtouch_setSampleRate(instance, sample_rate);
-            soundtouch_setChannels(instance, 1);
-            // Устанавливаем темп (tempo factor) — изменение длительности без изменения pitch.
-            soundtouch_setTempo(instance, tempo);
-            // Гарантируем, что тон остаётся неизменным.
-            soundtouch_setPitch(instance, 1.0);
-            // Передаём сэмплы.
-            soundtouch_putSamples(instance, input.as_ptr(), input.len() as u32);
-
-            // Считываем обработанные сэмплы.
-            let mut output = Vec::new();
-            let mut buffer = vec![0f32; 1024];
-            loop {
-                let received = soundtouch_receiveSamples(instance, buffer.as_mut_ptr(), buffer.len() as u32);
-                if received == 0 {
-                    break;
-                }
-                output.extend_from_slice(&buffer[..received as usize]);
-            }
-            
-            Ok(output)
+        #[test]
+        fn test_stretch_controls_split_speed_factor() {
+            let preserve = StretchSettings::default();
+            assert_eq!(preserve.controls(1.5), (1.5, 1.0, 1.0));
+
+            let natural = StretchSettings { rate_share: 0.5, pitch_semitones: 12.0, ..StretchSettings::default() };
+            let (tempo, rate, pitch) = natural.controls(1.44);
+            assert!((tempo - 1.2).abs() < 1e-5);
+            assert!((rate - 1.2).abs() < 1e-5);
+            assert!((pitch - 2.0).abs() < 1e-5);
+        }
+
+        #[test]
+        fn test_tempo_changes_duration_but_not_pitch() {
+            let sample_rate = 16_000;
+            let tone: Vec<f32> = (0..sample_rate * 2)
+                .map(|i| (2.0 * std::f32::consts::PI * 220.0 * i as f32 / sample_rate as f32).sin())
+                .collect();
+            let crossings = |audio: &[f32]| audio.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count() as f32;
+            let seconds = |audio: &[f32]| audio.len() as f32 / sample_rate as f32;
+
+            let faster = process(&tone, sample_rate, 1.5, &StretchSettings::default()).unwrap();
+            assert!((seconds(&faster) - 2.0 / 1.5).abs() < 0.01);
+            assert!((crossings(&faster) / seconds(&faster) - 220.0).abs() < 5.0);
+
+            let octave_up = StretchSettings { pitch_semitones: 12.0, ..StretchSettings::default() };
+            let shifted = process(&tone, sample_rate, 1.0, &octave_up).unwrap();
+            assert!((seconds(&shifted) - 2.0).abs() < 0.01);
+            assert!((crossings(&shifted) / seconds(&shifted) - 440.0).abs() < 10.0);
+
+            assert!(process(&tone, sample_rate, 0.0, &StretchSettings::default()).is_err());
         }
     }
 }
@@ -246,24 +328,40 @@ pub enum TtsError {
 pub type Result<T> = std::result::Result<T, TtsError>;
 
 /// Структура для представления одного субтитра из VTT.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct SubtitleCue {
     pub start: f32,   // время начала в секундах
     pub end: f32,     // время окончания в секундах
-    pub text: String, // текст реплики
+    pub text: String, // текст реплики без тега говорящего
+    /// Идентификатор реплики (строка перед таймингом)
+    pub id: Option<String>,
+    /// Говорящий из тега `<v Имя>` в начале реплики
+    pub speaker: Option<String>,
+    /// Блоки NOTE перед репликой в исходном виде
+    pub notes: Vec<String>,
 }
 
 /// Тип обновления прогресса выполнения.
-#[derive(Debug)]
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ProgressUpdate {
     Started,
+    #[serde(rename = "parsing_vtt")]
     ParsingVTT,
+    #[serde(rename = "parsed_vtt")]
     ParsedVTT { total: usize },
+    #[serde(rename = "tts_generation")]
     TTSGeneration { current: usize, total: usize },
     ProcessingFragment { index: usize, total: usize, step: String },
+    /// Длительность фрагмента до и после подгонки под реплику
+    SegmentTiming { index: usize, start: f32, end: f32, text: String, generated_duration: f32, fitted_duration: f32 },
+    /// Решение оптимизатора реплик: объединение или новое окно
+    CueDecision { decision: timeline::CueDecision },
     MergingFragments,
     Normalizing { using_original: bool },
     Encoding,
+    /// Получена очередная часть аудио реплики; `bytes` — сколько пришло всего
+    StreamingChunk { index: usize, bytes: usize },
     Finished,
 }
 
@@ -295,13 +393,44 @@ pub struct AudioProcessingConfig {
     pub window_size: usize,
     /// Размер перекрытия для FFT при time-stretching
     pub hop_size: usize,
-    /// Целевой уровень нормализации при отсутствии референсного аудио
-    pub target_peak_level: f32,
+    /// Цель нормализации громкости при отсутствии референсного аудио
+    pub loudness: loudness::LoudnessTarget,
     /// Баланс между голосом и инструментальной дорожкой (0.0 - 1.0)
-    /// 0.5 означает равный баланс
+    /// 0.5 означает равный баланс. Задает уровень фона в паузах речи, дакинг приглушает
+    /// фон относительно этого уровня
     pub voice_to_instrumental_ratio: f32,
     /// Коэффициент усиления инструментальной дорожки (1.0 = без изменений)
     pub instrumental_boost: f32,
+    /// Микшировать TTS с инструментальной дорожкой оригинала (требует original_audio_path)
+    pub mix_with_background: bool,
+    /// На сколько дБ голос должен быть громче инструментальной дорожки при микшировании
+    pub voice_over_background_db: f32,
+    /// На сколько дБ приглушать инструментальную дорожку, пока звучит речь (0 - без дакинга)
+    pub duck_depth_db: f32,
+    /// Уровень голосовой шины, выше которого она считается речью, дБ
+    pub duck_threshold_db: f32,
+    /// Время, за которое фон опускается к началу речи, мс
+    pub duck_attack_ms: f32,
+    /// Время, за которое фон возвращается после окончания речи, мс
+    pub duck_release_ms: f32,
+    /// Максимальный коэффициент ускорения фрагмента при подгонке длительности.
+    /// Речь, которой не хватает и такого ускорения, сдвигает следующие реплики
+    pub max_tempo: f32,
+    /// Минимальный коэффициент (замедление) для речи короче окна реплики, 1.0 - не замедлять
+    pub min_tempo: f32,
+    /// Распределение ускорения между tempo, rate и pitch и качество WSOLA
+    pub stretch: timestretch::StretchSettings,
+    /// Как разрешать пересечения реплик разных говорящих на таймлайне
+    pub overlap_policy: timeline::OverlapPolicy,
+    /// Ускорять речь под окна реплик или раздвигать сами окна
+    pub fit_strategy: timeline::FitStrategy,
+    /// Подгонять речь к фактической речи оригинала (VAD по дорожке голоса), а не к
+    /// окнам субтитров, которые часто висят на экране дольше слов
+    pub lip_sync: bool,
+    /// Озвучивать реплики одного предложения одним фрагментом
+    pub sentence_merging: timeline::SentenceMerging,
+    /// Не озвучивать поверх музыкальных проигрышей без речи в оригинале
+    pub voice_activity: vad::VoiceActivity,
 }
 
 impl Default for AudioProcessingConfig {
@@ -309,295 +438,2598 @@ impl Default for AudioProcessingConfig {
         Self {
             window_size: 512,
             hop_size: 256,
-            target_peak_level: 0.8,
+            loudness: loudness::LoudnessTarget::default(),
             voice_to_instrumental_ratio: 0.4, // Баланс: 40% голос, 60% музыка
             instrumental_boost: 1.5, // Усиление инструментальной дорожки в 1.5 раза
+            mix_with_background: true,
+            voice_over_background_db: 6.0,
+            duck_depth_db: 10.0,
+            duck_threshold_db: -45.0,
+            duck_attack_ms: 60.0,
+            duck_release_ms: 450.0,
+            max_tempo: 2.0,
+            min_tempo: language_speed::DEFAULT_MIN_TEMPO,
+            stretch: timestretch::StretchSettings::default(),
+            overlap_policy: timeline::OverlapPolicy::default(),
+            fit_strategy: timeline::FitStrategy::default(),
+            lip_sync: false,
+            sentence_merging: timeline::SentenceMerging::default(),
+            voice_activity: vad::VoiceActivity::default(),
         }
     }
 }
 
-/// Модуль для парсинга VTT-файлов.
-pub mod vtt {
-    use super::{SubtitleCue, Result, TtsError};
-    use std::fs;
+/// Измерение громкости по EBU R128 / ITU-R BS.1770.
+///
+/// Интегральная громкость считается по K-взвешенному сигналу блоками по 400 мс
+/// с перекрытием 75% и двумя порогами (абсолютным -70 LUFS и относительным
+/// -10 LU), диапазон громкости (LRA) - по кратковременной громкости блоками по 3 с
+/// между 10-м и 95-м процентилями. Истинный пик ищется после 4-кратной
+/// передискретизации. Все каналы входят с весом 1 (моно и стерео).
+pub mod loudness {
+    use super::audio;
+    use log::{info, warn};
+    use std::f64::consts::PI;
 
-    /// Парсит VTT-файл и возвращает вектор структур SubtitleCue.
-    pub fn parse_vtt<P: AsRef<std::path::Path>>(file_path: P) -> Result<Vec<SubtitleCue>> {
-        let data = fs::read_to_string(file_path)
-            .map_err(|e| TtsError::IoError(e))?;
-        let mut cues = Vec::new();
+    const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+    /// Предел усиления при сжатии диапазона громкости, дБ
+    const MAX_RANGE_GAIN_DB: f64 = 12.0;
 
-        // Разбиваем файл на блоки по пустой строке
-        for block in data.split("\n\n").filter(|b| b.contains("-->")) {
-            let mut lines = block.lines();
-            // Пропускаем возможный индекс или метку
-            let timing_line = lines.find(|l| l.contains("-->"))
-                .ok_or_else(|| TtsError::VttParsingError("Не найден тайминг в блоке".to_string()))?;
-            let times: Vec<&str> = timing_line.split_whitespace().collect();
-            if times.len() >= 3 && times[1] == "-->" {
-                let start = parse_time(times[0])?;
-                let end = parse_time(times[2])?;
-                // Оставшиеся строки считаем текстом реплики
-                let text = lines.collect::<Vec<_>>().join(" ").trim().to_string();
-                // Пропускаем пустые субтитры
-                if !text.is_empty() {
-                    cues.push(SubtitleCue { start, end, text });
-                }
-            }
-        }
-        Ok(cues)
+    /// Цель нормализации громкости
+    #[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+    #[serde(default)]
+    pub struct LoudnessTarget {
+        /// Интегральная громкость, LUFS
+        pub integrated_lufs: f32,
+        /// Потолок истинного пика, dBTP
+        pub true_peak_dbtp: f32,
+        /// Наибольший диапазон громкости, LU (0 - не сжимать)
+        pub loudness_range_lu: f32,
     }
 
-    /// Преобразует строку времени формата "HH:MM:SS.mmm" в секунды.
-    fn parse_time(t: &str) -> Result<f32> {
-        let parts: Vec<&str> = t.split(|c| c == ':' || c == '.').collect();
-        if parts.len() < 3 {
-            return Err(TtsError::VttParsingError(format!("Неверный формат времени: {}", t)));
+    impl Default for LoudnessTarget {
+        /// Рекомендация для онлайн-видео
+        fn default() -> Self {
+            Self { integrated_lufs: -16.0, true_peak_dbtp: -1.5, loudness_range_lu: 11.0 }
         }
-        
-        let hours: f32 = parts[0].parse()
-            .map_err(|_| TtsError::VttParsingError(format!("Не удалось распознать часы: {}", parts[0])))?;
-        let minutes: f32 = parts[1].parse()
-            .map_err(|_| TtsError::VttParsingError(format!("Не удалось распознать минуты: {}", parts[1])))?;
-        let seconds: f32 = parts[2].parse()
-            .map_err(|_| TtsError::VttParsingError(format!("Не удалось распознать секунды: {}", parts[2])))?;
-        let millis: f32 = if parts.len() > 3 { 
-            parts[3].parse()
-                .map_err(|_| TtsError::VttParsingError(format!("Не удалось распознать миллисекунды: {}", parts[3])))?
-        } else { 
-            0.0 
-        };
-        
-        Ok(hours * 3600.0 + minutes * 60.0 + seconds + millis / 1000.0)
     }
-}
 
-/// Модуль для обращения к OpenAI TTS API.
-pub mod tts {
-    use super::{Result, TtsError, TtsConfig};
-    use reqwest::Client;
-    use serde_json::json;
-    use log::{debug, info, warn, error};
-    use tokio::time::{sleep, Duration};
+    #[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+    pub struct LoudnessMeasurement {
+        /// Интегральная громкость, LUFS (-70 для тишины)
+        pub integrated_lufs: f32,
+        pub true_peak_dbtp: f32,
+        pub loudness_range_lu: f32,
+    }
 
-    /// Генерирует аудиофрагмент через TTS API для заданного текста.
-    /// Возвращает Vec<u8> с данными аудио (например, MP3) и текст для отладки.
-    pub async fn generate_tts(api_key: &str, text: &str, config: &TtsConfig) -> Result<(Vec<u8>, String)> {
-        const MAX_RETRIES: u32 = 5;
-        const INITIAL_BACKOFF_MS: u64 = 1000;
+    /// Результат двухпроходной нормализации
+    #[derive(Debug, Clone, Copy, serde::Serialize)]
+    pub struct NormalizationReport {
+        pub before: LoudnessMeasurement,
+        pub after: LoudnessMeasurement,
+        /// Итоговое усиление, дБ
+        pub gain_db: f32,
+        /// Сжат ли диапазон громкости
+        pub range_compressed: bool,
+    }
 
-        let payload = json!({
-            "model": config.model,
-            "voice": config.voice,
-            "input": text,
-            "response_format": "mp3",
-            "speed": config.speed
-        });
+    struct Biquad {
+        b: [f64; 3],
+        a: [f64; 3],
+        x: [f64; 2],
+        y: [f64; 2],
+    }
 
-        let client = Client::new();
-        let mut attempt = 0;
-        let mut last_error = None;
+    impl Biquad {
+        fn process(&mut self, x0: f64) -> f64 {
+            let y0 = self.b[0] * x0 + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+                - self.a[1] * self.y[0]
+                - self.a[2] * self.y[1];
+            self.x = [x0, self.x[0]];
+            self.y = [y0, self.y[0]];
+            y0
+        }
+    }
 
-        while attempt < MAX_RETRIES {
-            if attempt > 0 {
-                let backoff = INITIAL_BACKOFF_MS * (2_u64.pow(attempt - 1));
-                info!("Повторная попытка #{} через {} мс...", attempt + 1, backoff);
-                sleep(Duration::from_millis(backoff)).await;
-            }
-
-            let resp = match client
-                .post("https://api.openai.com/v1/audio/speech")
-                .bearer_auth(api_key)
-                .json(&payload)
-                .send()
-                .await {
-                    Ok(r) => r,
-                    Err(e) => {
-                        warn!("Ошибка сети при попытке #{}: {}", attempt + 1, e);
-                        last_error = Some(TtsError::HttpError(e));
-                        attempt += 1;
-                        continue;
-                    }
-                };
+    /// Фильтры K-взвешивания (полка +4 дБ и фильтр верхних частот RLB) для любой частоты
+    fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+        let rate = sample_rate.max(1) as f64;
 
-            if resp.status().is_success() {
-                let audio_bytes = resp.bytes().await
-                    .map_err(|e| TtsError::HttpError(e))?;
-                    
-                info!("Получено {} байт аудио от OpenAI для текста: {}", audio_bytes.len(), text);
-                
-                if audio_bytes.is_empty() {
-                    warn!("Получен пустой ответ от OpenAI TTS API для текста: {}", text);
-                    last_error = Some(TtsError::OpenAiApiError("Получен пустой ответ от API".to_string()));
-                    attempt += 1;
-                    continue;
-                }
-                
-                // Проверяем, что первые байты похожи на MP3
-                if audio_bytes.len() > 2 {
-                    let is_id3 = audio_bytes.len() > 3 && &audio_bytes[0..3] == b"ID3";
-                    let is_mpeg = audio_bytes.len() > 2 && (audio_bytes[0] == 0xFF && (audio_bytes[1] & 0xE0) == 0xE0);
-                    
-                    if !is_id3 && !is_mpeg {
-                        warn!("Получены данные, не похожие на MP3 (нет ID3/MPEG заголовка) для текста: {}", text);
-                    }
-                }
-                
-                return Ok((audio_bytes.to_vec(), text.to_string()));
-            } else {
-                let status = resp.status();
-                let error_text = resp.text().await.unwrap_or_else(|_| "Неизвестная ошибка".to_string());
-                
-                // Для 503 ошибок всегда делаем повторную попытку
-                if status == 503 {
-                    warn!("Сервер перегружен (503), попытка #{}: {}", attempt + 1, error_text);
-                    last_error = Some(TtsError::OpenAiApiError(format!(
-                        "Ошибка API (код {}): {}", status, error_text
-                    )));
-                    attempt += 1;
-                    continue;
-                }
-                
-                // Для других ошибок возвращаем сразу
-                return Err(TtsError::OpenAiApiError(format!(
-                    "Ошибка API (код {}): {}", status, error_text
-                )));
-            }
-        }
+        let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad {
+            b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        };
 
-        // Если все попытки исчерпаны, возвращаем последнюю ошибку
-        Err(last_error.unwrap_or_else(|| TtsError::OpenAiApiError(
-            "Превышено максимальное количество попыток".to_string()
-        )))
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        };
+        [shelf, high_pass]
     }
-}
 
-/// Модуль для работы с Demucs через командную строку
-pub mod demucs {
-    use super::{TtsError, Result};
-    use log::{info, warn, error};
-    use std::process::Command;
-    use std::path::Path;
-    use tokio::sync::mpsc::Sender;
-    use serde_json::json;
+    /// Мощность K-взвешенного сигнала, сумма по каналам, по значению на кадр
+    fn weighted_power(samples: &[f32], sample_rate: u32, channels: u16) -> Vec<f64> {
+        let channels = channels.max(1) as usize;
+        let mut filters: Vec<[Biquad; 2]> = (0..channels).map(|_| k_weighting(sample_rate)).collect();
+        samples
+            .chunks_exact(channels)
+            .map(|frame| {
+                frame
+                    .iter()
+                    .zip(filters.iter_mut())
+                    .map(|(&sample, [shelf, high_pass])| {
+                        let y = high_pass.process(shelf.process(sample as f64));
+                        y * y
+                    })
+                    .sum::<f64>()
+            })
+            .collect()
+    }
 
-    #[derive(Debug)]
-    pub enum DemucsSeparationProgress {
-        Started,
-        LoadingModel,
-        Processing { progress: f32 },
-        Converting,
-        Finished,
-        Error(String),
+    fn power_to_lufs(power: f64) -> f64 {
+        if power <= 0.0 { f64::NEG_INFINITY } else { -0.691 + 10.0 * power.log10() }
     }
 
-    /// Удаляет вокал из аудиофайла с помощью Demucs
-    pub async fn remove_vocals<P: AsRef<Path>>(
-        input_path: P,
-        output_path: P,
-        progress_sender: Option<Sender<DemucsSeparationProgress>>,
-        debug_dir: Option<P>,
-    ) -> Result<()> {
-        // Проверяем установку Demucs
-        ensure_demucs_installed().await?;
+    fn lufs_to_power(lufs: f64) -> f64 {
+        10f64.powf((lufs + 0.691) / 10.0)
+    }
 
-        info!("Удаление вокала с помощью Demucs из файла: {}", input_path.as_ref().display());
-        
-        // Отправляем статус начала работы
-        send_progress(&progress_sender, DemucsSeparationProgress::Started).await;
+    /// Громкость блоков по `block` кадров с шагом `step`
+    fn block_loudness(power: &[f64], block: usize, step: usize) -> Vec<f64> {
+        if block == 0 || step == 0 || power.len() < block {
+            return Vec::new();
+        }
+        let mut prefix = Vec::with_capacity(power.len() + 1);
+        prefix.push(0.0);
+        for p in power {
+            prefix.push(prefix[prefix.len() - 1] + p);
+        }
+        (0..=(power.len() - block) / step)
+            .map(|i| power_to_lufs((prefix[i * step + block] - prefix[i * step]) / block as f64))
+            .collect()
+    }
 
-        // Создаем временную директорию для результатов Demucs
-        let temp_dir = tempfile::tempdir()
-            .map_err(|e| TtsError::IoError(e))?;
+    fn mean_loudness<'a>(blocks: impl Iterator<Item = &'a f64>) -> f64 {
+        let (sum, count) = blocks.fold((0.0, 0usize), |(sum, count), &l| (sum + lufs_to_power(l), count + 1));
+        if count == 0 { f64::NEG_INFINITY } else { power_to_lufs(sum / count as f64) }
+    }
 
-        // Отправляем статус загрузки модели
-        send_progress(&progress_sender, DemucsSeparationProgress::LoadingModel).await;
+    fn integrated(momentary: &[f64]) -> f64 {
+        let ungated = mean_loudness(momentary.iter().filter(|&&l| l > ABSOLUTE_GATE_LUFS));
+        let relative_gate = ungated - 10.0;
+        mean_loudness(momentary.iter().filter(|&&l| l > ABSOLUTE_GATE_LUFS && l > relative_gate))
+    }
 
-        // Создаем канал для передачи прогресса из потока чтения вывода
-        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel(32);
-        let progress_sender_clone = progress_sender.clone();
+    fn loudness_range(short_term: &[f64]) -> f64 {
+        let relative_gate = mean_loudness(short_term.iter().filter(|&&l| l > ABSOLUTE_GATE_LUFS)) - 20.0;
+        let mut gated: Vec<f64> = short_term
+            .iter()
+            .copied()
+            .filter(|&l| l > ABSOLUTE_GATE_LUFS && l > relative_gate)
+            .collect();
+        if gated.len() < 2 {
+            return 0.0;
+        }
+        gated.sort_by(|a, b| a.total_cmp(b));
+        let percentile = |p: f64| gated[((gated.len() - 1) as f64 * p).round() as usize];
+        percentile(0.95) - percentile(0.10)
+    }
 
-        // Запускаем Demucs с выводом прогресса
-        let mut child = tokio::process::Command::new("demucs")
-            .args(&[
-                "--two-stems=vocals",  // Разделяем только на вокал и остальное
-                "-n", "htdemucs",      // Используем лучшую модель
-                "--mp3",               // Выходной формат MP3 для экономии места
-                "-o", temp_dir.path().to_str().unwrap(),
-                input_path.as_ref().to_str().unwrap(),
-            ])
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка запуска Demucs: {}", e)))?;
+    /// Истинный пик, dBTP: максимум после 4-кратной передискретизации фильтром windowed-sinc
+    pub fn true_peak_dbtp(samples: &[f32], channels: u16) -> f32 {
+        const FACTOR: usize = 4;
+        const TAPS: usize = 12;
+        let length = (FACTOR * TAPS) as f64;
+        // Фаза 0 совпадает с исходными сэмплами, остальные - промежуточные точки
+        let phases: Vec<Vec<f64>> = (0..FACTOR)
+            .map(|phase| {
+                (0..TAPS)
+                    .map(|tap| {
+                        let m = (tap * FACTOR + phase) as f64;
+                        let x = (m - length / 2.0) / FACTOR as f64;
+                        let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+                        sinc * (0.5 - 0.5 * (2.0 * PI * m / length).cos())
+                    })
+                    .collect()
+            })
+            .collect();
 
-        // Читаем вывод в реальном времени для отслеживания прогресса
-        let stderr = child.stderr.take().unwrap();
-        
-        // Запускаем задачу для чтения вывода
-        tokio::spawn(async move {
-            use tokio::io::{BufReader, AsyncBufReadExt};
-            let reader = BufReader::new(stderr);
-            let mut lines = reader.lines();
-            
-            while let Ok(Some(line)) = lines.next_line().await {
-                // Анализируем вывод Demucs для определения прогресса
-                if let Some(progress) = parse_demucs_progress(&line) {
-                    let _ = progress_tx.send(progress).await;
+        let channels = channels.max(1) as usize;
+        let frames = samples.len() / channels;
+        let mut peak = 0.0f64;
+        for channel in 0..channels {
+            let sample = |frame: usize| samples[frame * channels + channel] as f64;
+            for frame in 0..frames + TAPS {
+                for taps in &phases {
+                    let value: f64 = taps
+                        .iter()
+                        .enumerate()
+                        .filter(|(tap, _)| *tap <= frame && frame - tap < frames)
+                        .map(|(tap, h)| h * sample(frame - tap))
+                        .sum();
+                    peak = peak.max(value.abs());
                 }
-                // Логируем все строки для отладки
-                info!("Demucs output: {}", line);
             }
-        });
+        }
+        audio::amplitude_to_db(peak as f32)
+    }
 
-        // Обрабатываем прогресс
-        tokio::spawn(async move {
-            while let Some(progress) = progress_rx.recv().await {
-                send_progress(&progress_sender_clone, 
-                    DemucsSeparationProgress::Processing { progress }).await;
-            }
-        });
+    /// Интегральная громкость, истинный пик и диапазон громкости
+    pub fn measure(samples: &[f32], sample_rate: u32, channels: u16) -> LoudnessMeasurement {
+        let power = weighted_power(samples, sample_rate, channels);
+        let rate = sample_rate.max(10) as usize;
+        let momentary = block_loudness(&power, rate * 4 / 10, rate / 10);
+        let short_term = block_loudness(&power, rate * 3, rate / 10);
+        LoudnessMeasurement {
+            integrated_lufs: integrated(&momentary).max(ABSOLUTE_GATE_LUFS) as f32,
+            true_peak_dbtp: true_peak_dbtp(samples, channels),
+            loudness_range_lu: loudness_range(&short_term) as f32,
+        }
+    }
 
-        // Ждем завершения процесса
-        let status = child.wait()
-            .await
-            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка выполнения Demucs: {}", e)))?;
+    /// Сжимает диапазон громкости: отклонение кратковременной громкости от
+    /// интегральной уменьшается в `target / measured` раз. Блоки тише
+    /// относительного порога LRA (паузы) сохраняют предыдущее усиление, поэтому
+    /// фон в паузах не вытягивается. Усиление интерполируется между центрами блоков.
+    fn compress_range(samples: &mut [f32], sample_rate: u32, channels: u16, measured: &LoudnessMeasurement, target_lu: f32) {
+        let rate = sample_rate.max(10) as usize;
+        let (block, step) = (rate * 3, rate / 10);
+        let short_term = block_loudness(&weighted_power(samples, sample_rate, channels), block, step);
+        if short_term.is_empty() {
+            return;
+        }
+        let integrated = measured.integrated_lufs as f64;
+        let ratio = (target_lu / measured.loudness_range_lu) as f64;
+        let mut current = 0.0;
+        let gains: Vec<f64> = short_term
+            .iter()
+            .map(|&loudness| {
+                if loudness > integrated - 20.0 {
+                    let deviation = loudness - integrated;
+                    current = (deviation * ratio - deviation).clamp(-MAX_RANGE_GAIN_DB, MAX_RANGE_GAIN_DB);
+                }
+                current
+            })
+            .collect();
 
-        if !status.success() {
-            let error_msg = format!("Demucs завершился с ошибкой: {}", status);
-            error!("{}", error_msg);
-            send_progress(&progress_sender, DemucsSeparationProgress::Error(error_msg.clone())).await;
-            return Err(TtsError::AudioProcessingError(error_msg));
+        let channels = channels.max(1) as usize;
+        let last = gains.len() - 1;
+        for (frame, samples) in samples.chunks_exact_mut(channels).enumerate() {
+            let position = ((frame as f64 - block as f64 / 2.0) / step as f64).max(0.0);
+            let left = (position.floor() as usize).min(last);
+            let right = (left + 1).min(last);
+            let t = (position - left as f64).clamp(0.0, 1.0);
+            let gain = 10f64.powf((gains[left] * (1.0 - t) + gains[right] * t) / 20.0) as f32;
+            for sample in samples {
+                *sample *= gain;
+            }
+        }
+    }
+
+    /// Двухпроходная нормализация: измерение, сжатие диапазона и усиление к цели,
+    /// повторное измерение результата. Усиление ограничивается так, чтобы истинный
+    /// пик не превысил потолок, даже если из-за этого цель не достигнута.
+    pub fn normalize(samples: &mut [f32], sample_rate: u32, channels: u16, target: &LoudnessTarget) -> NormalizationReport {
+        let before = measure(samples, sample_rate, channels);
+        if before.integrated_lufs as f64 <= ABSOLUTE_GATE_LUFS {
+            warn!("Аудио тише порога измерения громкости, нормализация пропущена");
+            return NormalizationReport { before, after: before, gain_db: 0.0, range_compressed: false };
+        }
+
+        let range_compressed = target.loudness_range_lu > 0.0 && before.loudness_range_lu > target.loudness_range_lu;
+        let current = if range_compressed {
+            compress_range(samples, sample_rate, channels, &before, target.loudness_range_lu);
+            measure(samples, sample_rate, channels)
+        } else {
+            before
+        };
+
+        let mut gain_db = target.integrated_lufs - current.integrated_lufs;
+        if current.true_peak_dbtp + gain_db > target.true_peak_dbtp {
+            gain_db = target.true_peak_dbtp - current.true_peak_dbtp;
+            warn!(
+                "Истинный пик ограничивает усиление: {:.1} LUFS вместо {:.1} LUFS",
+                current.integrated_lufs + gain_db,
+                target.integrated_lufs
+            );
+        }
+        let gain = 10f32.powf(gain_db / 20.0);
+        for sample in samples.iter_mut() {
+            *sample *= gain;
+        }
+
+        let after = measure(samples, sample_rate, channels);
+        info!(
+            "Нормализация громкости: {:.1} LUFS / {:.1} dBTP / LRA {:.1} LU -> {:.1} LUFS / {:.1} dBTP / LRA {:.1} LU",
+            before.integrated_lufs, before.true_peak_dbtp, before.loudness_range_lu,
+            after.integrated_lufs, after.true_peak_dbtp, after.loudness_range_lu
+        );
+        NormalizationReport { before, after, gain_db, range_compressed }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn sine(frequency: f32, amplitude: f32, seconds: f32, sample_rate: u32) -> Vec<f32> {
+            (0..(seconds * sample_rate as f32) as usize)
+                .map(|i| amplitude * (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin())
+                .collect()
+        }
+
+        #[test]
+        fn test_reference_tone_and_normalization() {
+            // Синус 1 кГц с амплитудой 1.0 в одном канале - это -3.01 LUFS
+            let tone = sine(1000.0, 1.0, 5.0, 48_000);
+            let measured = measure(&tone, 48_000, 1);
+            assert!((measured.integrated_lufs + 3.01).abs() < 0.1, "{:?}", measured);
+            assert!(measured.loudness_range_lu < 0.1);
+
+            let mut quiet = sine(1000.0, 0.05, 5.0, 48_000);
+            let report = normalize(&mut quiet, 48_000, 1, &LoudnessTarget::default());
+            assert!((report.after.integrated_lufs + 16.0).abs() < 0.1, "{:?}", report);
+            assert!(report.after.true_peak_dbtp <= -1.5 + 0.01);
+        }
+    }
+}
+
+/// Сборка таймлайна из аудиофрагментов.
+///
+/// Субтитры с одновременными говорящими содержат пересекающиеся по времени реплики.
+/// Политика пересечений определяет, что с ними делать: сдвинуть фрагмент за конец
+/// предыдущего, развести говорящих по стереопанораме или объединить реплики в одну
+/// еще до генерации TTS.
+pub mod timeline {
+    use super::SubtitleCue;
+    use log::info;
+    use once_cell::sync::Lazy;
+    use regex::Regex;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    /// Пауза между сдвинутыми репликами разных говорящих, секунды
+    const OFFSET_GAP: f32 = 0.05;
+
+    static VOICE_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<v(?:\.[^\s>]*)?\s+([^>]+)>").unwrap());
+    static VOICE_TAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"</?v[^>]*>").unwrap());
+
+    /// Политика разрешения пересекающихся реплик
+    #[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum OverlapPolicy {
+        /// Сдвинуть пересекающийся фрагмент сразу за конец предыдущего (моно)
+        #[default]
+        Offset,
+        /// Оставить реплики на своих местах и развести говорящих влево/вправо (стерео)
+        Pan,
+        /// Объединить пересекающиеся реплики в одну до генерации TTS (моно)
+        Merge,
+    }
+
+    /// Объединение реплик одного предложения перед озвучкой.
+    ///
+    /// Пословная разметка Whisper режет предложения на короткие реплики, и TTS,
+    /// озвучивая их по отдельности, читает рублено, с интонацией конца фразы на
+    /// каждом обрывке. Реплики, идущие подряд без знака конца предложения и без
+    /// долгой паузы, озвучиваются одним фрагментом. Отображаемые субтитры сохраняют
+    /// исходные реплики.
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct SentenceMerging {
+        pub enabled: bool,
+        /// Самая длинная пауза внутри предложения, секунды
+        pub max_pause: f32,
+        /// Самая длинная объединенная реплика, секунды
+        pub max_duration: f32,
+    }
+
+    impl Default for SentenceMerging {
+        fn default() -> Self {
+            Self { enabled: false, max_pause: 0.6, max_duration: 12.0 }
+        }
+    }
+
+    /// Как подгонять озвучку к окнам реплик
+    #[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+    #[serde(tag = "mode", rename_all = "snake_case")]
+    pub enum FitStrategy {
+        /// Ускорять речь, чтобы она уложилась в исходное окно реплики
+        #[default]
+        Stretch,
+        /// Раздвигать окна реплик под естественную длительность речи. Реплика может
+        /// начаться не более чем на `max_drift` секунд позже оригинала; накопленный
+        /// сдвиг гасится паузами между сценами. Речь звучит естественнее, синхронизация
+        /// с картинкой слабее.
+        Retime { max_drift: f32 },
+    }
+
+    /// Новое окно реплики для стратегии Retime.
+    ///
+    /// Реплика начинается не раньше исходного начала и конца предыдущей, звучит
+    /// столько, сколько нужно речи (но не меньше исходного окна), и заканчивается не
+    /// позже, чем позволяет бюджет сдвига следующей реплики. Если речь не помещается
+    /// и в такое окно, остаток подгоняется обычным ускорением.
+    pub fn retime_window(
+        cue: &SubtitleCue,
+        natural_duration: f32,
+        previous_end: f32,
+        next_start: Option<f32>,
+        max_drift: f32,
+    ) -> (f32, f32) {
+        let max_drift = max_drift.max(0.0);
+        let start = cue.start.max(previous_end).min(cue.start + max_drift);
+        let latest_end = next_start.unwrap_or(cue.end) + max_drift;
+        let end = (start + natural_duration).max(cue.end).min(latest_end).max(start);
+        (start, end)
+    }
+
+    /// Окно реплики в секундах
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub struct CueWindow {
+        pub start: f32,
+        pub end: f32,
+    }
+
+    impl CueWindow {
+        pub fn of(cue: &SubtitleCue) -> Self {
+            Self { start: cue.start, end: cue.end }
+        }
+    }
+
+    /// Что оптимизатор сделал с репликами
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum CueAction {
+        Merge,
+        Retime,
+    }
+
+    /// Одно решение оптимизатора реплик, для отчета пользователю
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct CueDecision {
+        pub action: CueAction,
+        /// Номера исходных реплик в файле субтитров, с нуля
+        pub source_cues: Vec<usize>,
+        pub reason: String,
+        /// Окна исходных реплик
+        pub before: Vec<CueWindow>,
+        /// Окно итоговой реплики
+        pub after: CueWindow,
+    }
+
+    impl CueDecision {
+        /// Решение Retime для реплики, окно которой сдвинулось или растянулось
+        pub fn retime(
+            source_cues: Vec<usize>,
+            cue: &SubtitleCue,
+            window: (f32, f32),
+            natural_duration: f32,
+            previous_end: f32,
+        ) -> Option<Self> {
+            let mut reasons = Vec::new();
+            if window.0 > cue.start + 0.01 {
+                reasons.push(format!("the previous line runs until {:.2}s", previous_end));
+            }
+            if window.1 > cue.end + 0.01 {
+                reasons.push(format!("the speech needs {:.2}s, the cue gives {:.2}s", natural_duration, cue.end - cue.start));
+            }
+            if reasons.is_empty() {
+                return None;
+            }
+            Some(Self {
+                action: CueAction::Retime,
+                source_cues,
+                reason: reasons.join("; "),
+                before: vec![CueWindow::of(cue)],
+                after: CueWindow { start: window.0, end: window.1 },
+            })
+        }
+    }
+
+    /// Фрагмент, готовый к размещению на таймлайне
+    pub struct TimelineFragment<'a> {
+        pub samples: &'a [f32],
+        pub start_time: f32,
+        pub speaker: Option<&'a str>,
+    }
+
+    /// Извлекает имя говорящего из тега WebVTT `<v Имя>`
+    pub fn speaker_of(text: &str) -> Option<String> {
+        VOICE_TAG.captures(text).map(|caps| caps[1].trim().to_string())
+    }
+
+    /// Убирает теги говорящих, чтобы TTS не зачитывал их
+    pub fn strip_speaker_tags(text: &str) -> String {
+        VOICE_TAGS.replace_all(text, "").trim().to_string()
+    }
+
+    fn overlaps(a: &SubtitleCue, b: &SubtitleCue) -> bool {
+        a.start < b.end && b.start < a.end
+    }
+
+    /// Объединяет пересекающиеся реплики (политика Merge). Возвращает номера исходных
+    /// реплик для каждой итоговой и решения по объединенным.
+    pub fn merge_overlapping_cues(cues: &mut Vec<SubtitleCue>) -> (Vec<Vec<usize>>, Vec<CueDecision>) {
+        let mut indexed: Vec<(usize, SubtitleCue)> = cues.drain(..).enumerate().collect();
+        indexed.sort_by(|a, b| a.1.start.total_cmp(&b.1.start));
+        let mut merged: Vec<SubtitleCue> = Vec::with_capacity(indexed.len());
+        let mut sources: Vec<Vec<usize>> = Vec::with_capacity(indexed.len());
+        let mut before: Vec<Vec<CueWindow>> = Vec::with_capacity(indexed.len());
+        for (index, cue) in indexed {
+            match merged.last_mut() {
+                Some(last) if overlaps(last, &cue) => {
+                    if let (Some(ids), Some(windows)) = (sources.last_mut(), before.last_mut()) {
+                        ids.push(index);
+                        windows.push(CueWindow::of(&cue));
+                    }
+                    last.end = last.end.max(cue.end);
+                    // Реплика другого говорящего сохраняет свой тег внутри текста
+                    last.text = match &cue.speaker {
+                        Some(speaker) if last.speaker.as_ref() != Some(speaker) => {
+                            format!("{} <v {}>{}", last.text, speaker, cue.text)
+                        }
+                        _ => format!("{} {}", last.text, cue.text),
+                    };
+                    last.notes.extend(cue.notes);
+                }
+                _ => {
+                    sources.push(vec![index]);
+                    before.push(vec![CueWindow::of(&cue)]);
+                    merged.push(cue);
+                }
+            }
+        }
+        let decisions = merged
+            .iter()
+            .zip(&sources)
+            .zip(before)
+            .filter(|((_, ids), _)| ids.len() > 1)
+            .map(|((cue, ids), before)| CueDecision {
+                action: CueAction::Merge,
+                source_cues: ids.clone(),
+                reason: format!("{} overlapping lines are spoken as one", ids.len()),
+                before,
+                after: CueWindow::of(cue),
+            })
+            .collect();
+        *cues = merged;
+        (sources, decisions)
+    }
+
+    /// Заканчивается ли текст реплики концом предложения (кавычки и скобки после
+    /// знака не мешают)
+    fn ends_sentence(text: &str) -> bool {
+        let text = strip_speaker_tags(text);
+        let text = text.trim_end_matches(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '»' | '“' | '”' | ')' | ']'));
+        matches!(text.chars().last(), Some('.' | '!' | '?' | '…' | '。' | '！' | '？'))
+    }
+
+    /// Объединяет идущие подряд реплики одного предложения одного говорящего.
+    /// Реплики из `fixed` (например, с озвучкой пользователя) не объединяются.
+    /// Возвращает номера исходных реплик для каждой итоговой (всегда подряд) и
+    /// решения по объединенным.
+    pub fn merge_sentences(
+        cues: &mut Vec<SubtitleCue>,
+        settings: &SentenceMerging,
+        fixed: &[bool],
+    ) -> (Vec<Vec<usize>>, Vec<CueDecision>) {
+        let speaker = |cue: &SubtitleCue| cue.speaker.clone().or_else(|| speaker_of(&cue.text));
+        let mut merged: Vec<SubtitleCue> = Vec::with_capacity(cues.len());
+        let mut sources: Vec<Vec<usize>> = Vec::with_capacity(cues.len());
+        let mut before: Vec<Vec<CueWindow>> = Vec::with_capacity(cues.len());
+        for (index, cue) in cues.drain(..).enumerate() {
+            let is_fixed = |i: usize| fixed.get(i).copied().unwrap_or(false);
+            let joins = match (merged.last(), sources.last()) {
+                (Some(last), Some(ids)) => {
+                    !ends_sentence(&last.text)
+                        && !is_fixed(index)
+                        && !ids.iter().any(|&i| is_fixed(i))
+                        && speaker(last) == speaker(&cue)
+                        && cue.start - last.end <= settings.max_pause
+                        && cue.start >= last.end - 0.01
+                        && cue.end - last.start <= settings.max_duration
+                }
+                _ => false,
+            };
+            if joins {
+                if let (Some(last), Some(ids), Some(windows)) = (merged.last_mut(), sources.last_mut(), before.last_mut()) {
+                    ids.push(index);
+                    windows.push(CueWindow::of(&cue));
+                    last.end = cue.end;
+                    last.text = format!("{} {}", last.text, strip_speaker_tags(&cue.text));
+                    last.notes.extend(cue.notes);
+                }
+            } else {
+                sources.push(vec![index]);
+                before.push(vec![CueWindow::of(&cue)]);
+                merged.push(cue);
+            }
+        }
+        let decisions = merged
+            .iter()
+            .zip(&sources)
+            .zip(before)
+            .filter(|((_, ids), _)| ids.len() > 1)
+            .map(|((cue, ids), before)| CueDecision {
+                action: CueAction::Merge,
+                source_cues: ids.clone(),
+                reason: format!("{} lines of one sentence are spoken as one", ids.len()),
+                before,
+                after: CueWindow::of(cue),
+            })
+            .collect();
+        *cues = merged;
+        (sources, decisions)
+    }
+
+    /// Делит окно объединенной реплики между исходными репликами пропорционально
+    /// их исходному положению
+    pub fn spread_window(cues: &[SubtitleCue], window: CueWindow) -> Vec<CueWindow> {
+        let (Some(first), Some(last)) = (cues.first(), cues.last()) else {
+            return Vec::new();
+        };
+        let span = last.end - first.start;
+        if cues.len() == 1 || span <= 0.0 {
+            return vec![window; cues.len()];
+        }
+        let scale = (window.end - window.start) / span;
+        cues.iter()
+            .map(|cue| CueWindow {
+                start: window.start + (cue.start - first.start) * scale,
+                end: window.start + (cue.end - first.start) * scale,
+            })
+            .collect()
+    }
+
+    /// Стереопозиции говорящих: первый левее центра, второй правее и так далее
+    fn pan_positions(fragments: &[TimelineFragment], sample_rate: u32) -> Vec<f32> {
+        const POSITIONS: [f32; 4] = [-0.5, 0.5, -0.25, 0.25];
+        let mut speakers: HashMap<&str, usize> = HashMap::new();
+        // Единственного говорящего не смещаем, размечен он или нет
+        let labeled = fragments
+            .iter()
+            .filter_map(|f| f.speaker)
+            .collect::<std::collections::HashSet<_>>()
+            .len()
+            > 1;
+        let mut positions = Vec::with_capacity(fragments.len());
+        let mut previous_end = f32::MIN;
+        let mut previous_slot = 1;
+
+        for fragment in fragments {
+            let slot = match fragment.speaker.filter(|_| labeled) {
+                Some(name) => {
+                    let next = speakers.len();
+                    Some(*speakers.entry(name).or_insert(next))
+                }
+                None => None,
+            };
+            let overlapping = fragment.start_time < previous_end;
+            let position = match slot {
+                Some(slot) => POSITIONS[slot % POSITIONS.len()],
+                // Без меток говорящих разводим в стороны только пересекающиеся реплики
+                None if overlapping => {
+                    previous_slot = 1 - previous_slot;
+                    POSITIONS[previous_slot]
+                }
+                None => 0.0,
+            };
+            positions.push(position);
+            let duration = fragment.samples.len() as f32 / sample_rate as f32;
+            previous_end = previous_end.max(fragment.start_time + duration);
+        }
+        positions
+    }
+
+    /// Собирает таймлайн. Возвращает сэмплы (для стерео - чередующиеся L/R) и число каналов.
+    pub fn assemble(fragments: &[TimelineFragment], sample_rate: u32, policy: OverlapPolicy) -> (Vec<f32>, u16) {
+        match policy {
+            OverlapPolicy::Pan => (assemble_panned(fragments, sample_rate), 2),
+            OverlapPolicy::Offset | OverlapPolicy::Merge => (assemble_sequential(fragments, sample_rate), 1),
+        }
+    }
+
+    /// Начала фрагментов в сэмплах при последовательной сборке
+    fn sequential_starts(fragments: &[TimelineFragment], sample_rate: u32) -> Vec<usize> {
+        let mut end = 0;
+        let mut previous_speaker = None;
+        fragments
+            .iter()
+            .map(|fragment| {
+                let mut start = (fragment.start_time * sample_rate as f32).round() as usize;
+                if start < end {
+                    // Пересечение: фрагмент начинается сразу после предыдущего. Паузу
+                    // оставляем только между разными говорящими, иначе сдвиг копится
+                    let gap = if fragment.speaker != previous_speaker { OFFSET_GAP } else { 0.0 };
+                    start = end + (gap * sample_rate as f32) as usize;
+                }
+                end = start + fragment.samples.len();
+                previous_speaker = fragment.speaker;
+                start
+            })
+            .collect()
+    }
+
+    fn assemble_sequential(fragments: &[TimelineFragment], sample_rate: u32) -> Vec<f32> {
+        let mut output = Vec::new();
+        let mut shifted = 0;
+        for (fragment, start) in fragments.iter().zip(sequential_starts(fragments, sample_rate)) {
+            if start != (fragment.start_time * sample_rate as f32).round() as usize {
+                shifted += 1;
+            }
+            output.resize(start, 0.0);
+            output.extend_from_slice(fragment.samples);
+        }
+        if shifted > 0 {
+            info!("Сдвинуто {} пересекающихся фрагментов", shifted);
+        }
+        output
+    }
+
+    /// Где фрагменты фактически звучат после сборки таймлайна с политикой `policy`
+    pub fn placements(fragments: &[TimelineFragment], sample_rate: u32, policy: OverlapPolicy) -> Vec<CueWindow> {
+        let rate = sample_rate.max(1) as f32;
+        let starts = match policy {
+            OverlapPolicy::Pan => fragments.iter().map(|f| (f.start_time * rate).round() as usize).collect(),
+            OverlapPolicy::Offset | OverlapPolicy::Merge => sequential_starts(fragments, sample_rate),
+        };
+        fragments
+            .iter()
+            .zip(starts)
+            .map(|(fragment, start)| CueWindow {
+                start: start as f32 / rate,
+                end: (start + fragment.samples.len()) as f32 / rate,
+            })
+            .collect()
+    }
+
+    /// Переносит реплики субтитров туда, где фактически звучит их озвучка.
+    ///
+    /// Реплика начинается вместе со своим фрагментом и остается на экране не меньше
+    /// исходной длительности и не меньше самой речи, но не заходит на следующую
+    /// реплику, если и в оригинале они не пересекались. Реплика без фрагмента
+    /// (TTS не удался) сдвигается вместе с предыдущей. Возвращает реплики и число
+    /// перенесенных.
+    pub fn retime_subtitles_to_audio(cues: &[SubtitleCue], placed: &[Option<CueWindow>]) -> (Vec<SubtitleCue>, usize) {
+        let mut shift = 0.0f32;
+        let mut moved = 0;
+        let mut retimed: Vec<SubtitleCue> = cues
+            .iter()
+            .enumerate()
+            .map(|(i, cue)| {
+                let speech = placed.get(i).copied().flatten();
+                if let Some(window) = speech {
+                    shift = window.start - cue.start;
+                }
+                let mut retimed = cue.clone();
+                retimed.start = cue.start + shift;
+                retimed.end = (cue.end + shift).max(speech.map_or(0.0, |window| window.end));
+                if (retimed.start - cue.start).abs() > 0.01 || (retimed.end - cue.end).abs() > 0.01 {
+                    moved += 1;
+                }
+                retimed
+            })
+            .collect();
+        for i in 1..retimed.len() {
+            let next_start = retimed[i].start;
+            let previous = &mut retimed[i - 1];
+            if cues[i - 1].end <= cues[i].start && previous.end > next_start {
+                previous.end = next_start.max(previous.start);
+            }
+        }
+        (retimed, moved)
+    }
+
+    fn assemble_panned(fragments: &[TimelineFragment], sample_rate: u32) -> Vec<f32> {
+        let positions = pan_positions(fragments, sample_rate);
+        let total_frames = fragments
+            .iter()
+            .map(|f| (f.start_time * sample_rate as f32).round() as usize + f.samples.len())
+            .max()
+            .unwrap_or(0);
+
+        let mut output = vec![0.0f32; total_frames * 2];
+        for (fragment, position) in fragments.iter().zip(positions) {
+            // Панорамирование с постоянной мощностью
+            let angle = (position + 1.0) * std::f32::consts::FRAC_PI_4;
+            let (left_gain, right_gain) = (angle.cos(), angle.sin());
+            let start = (fragment.start_time * sample_rate as f32).round() as usize;
+            for (i, &sample) in fragment.samples.iter().enumerate() {
+                let frame = start + i;
+                output[frame * 2] += sample * left_gain;
+                output[frame * 2 + 1] += sample * right_gain;
+            }
+        }
+        for sample in output.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+        output
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn cue(start: f32, end: f32) -> SubtitleCue {
+            SubtitleCue { start, end, ..SubtitleCue::default() }
+        }
+
+        #[test]
+        fn test_retime_window_spends_drift_budget() {
+            // Длинная речь занимает паузу и часть бюджета следующей реплики
+            assert_eq!(retime_window(&cue(0.0, 2.0), 3.5, 0.0, Some(2.5), 1.0), (0.0, 3.5));
+            // Следующая реплика сдвигается за концом предыдущей
+            assert_eq!(retime_window(&cue(2.5, 4.0), 1.0, 3.5, Some(8.0), 1.0), (3.5, 4.5));
+            // Бюджет исчерпан: окно ограничено, остаток ускорится
+            assert_eq!(retime_window(&cue(0.0, 2.0), 6.0, 0.0, Some(2.5), 1.0), (0.0, 3.5));
+            // Короткая речь не сжимает исходное окно
+            assert_eq!(retime_window(&cue(10.0, 12.0), 1.0, 4.5, None, 1.0), (10.0, 12.0));
+        }
+
+        #[test]
+        fn test_subtitles_follow_shifted_speech() {
+            let samples = vec![0.0f32; 3000];
+            let fragments = [
+                TimelineFragment { samples: &samples, start_time: 0.0, speaker: None },
+                TimelineFragment { samples: &samples[..1000], start_time: 2.0, speaker: None },
+            ];
+            // Первый фрагмент звучит 3 с и выталкивает второй за свой конец
+            let placed = placements(&fragments, 1000, OverlapPolicy::Offset);
+            assert_eq!(placed[1], CueWindow { start: 3.0, end: 4.0 });
+            // Реплику другого говорящего отделяет пауза
+            let dialogue = [
+                TimelineFragment { samples: &samples, start_time: 0.0, speaker: Some("Anna") },
+                TimelineFragment { samples: &samples[..1000], start_time: 2.0, speaker: Some("Ben") },
+            ];
+            assert_eq!(placements(&dialogue, 1000, OverlapPolicy::Offset)[1], CueWindow { start: 3.05, end: 4.05 });
+
+            let cues = [cue(0.0, 2.0), cue(2.0, 5.0), cue(6.0, 7.0)];
+            let (retimed, moved) = retime_subtitles_to_audio(&cues, &[Some(placed[0]), Some(placed[1]), None]);
+            assert_eq!(moved, 3);
+            assert_eq!((retimed[0].start, retimed[0].end), (0.0, 3.0));
+            assert!((retimed[1].start - 3.0).abs() < 1e-4 && (retimed[1].end - 6.0).abs() < 1e-4);
+            // Без озвучки реплика сдвигается вместе с предыдущей
+            assert!((retimed[2].start - 7.0).abs() < 1e-4);
+        }
+
+        #[test]
+        fn test_merge_reports_source_cues() {
+            let mut cues = vec![cue(5.0, 6.0), cue(0.0, 2.0), cue(1.5, 3.0)];
+            let (sources, decisions) = merge_overlapping_cues(&mut cues);
+            assert_eq!(sources, vec![vec![1, 2], vec![0]]);
+            assert_eq!(decisions.len(), 1);
+            assert_eq!(decisions[0].action, CueAction::Merge);
+            assert_eq!(decisions[0].source_cues, vec![1, 2]);
+            assert_eq!(decisions[0].after, CueWindow { start: 0.0, end: 3.0 });
+
+            let decision = CueDecision::retime(vec![0], &cue(5.0, 6.0), (5.5, 7.0), 1.5, 5.5).unwrap();
+            assert_eq!(decision.before, vec![CueWindow { start: 5.0, end: 6.0 }]);
+            assert!(decision.reason.contains("previous line"));
+            assert!(CueDecision::retime(vec![0], &cue(5.0, 6.0), (5.0, 6.0), 0.5, 0.0).is_none());
+        }
+
+        #[test]
+        fn test_sentences_are_spoken_whole() {
+            let line = |start: f32, end: f32, text: &str| SubtitleCue { text: text.to_string(), ..cue(start, end) };
+            let mut cues = vec![
+                line(0.0, 1.0, "So when we"),
+                line(1.1, 2.0, "got there,"),
+                line(2.2, 3.0, "it was closed."),
+                line(3.1, 4.0, "Then we"),
+                line(5.5, 6.0, "went home."),
+                line(6.0, 7.0, "<v Anna>Really"),
+                line(7.0, 8.0, "<v Ben>yes"),
+            ];
+            let settings = SentenceMerging { enabled: true, ..SentenceMerging::default() };
+            let (sources, decisions) = merge_sentences(&mut cues, &settings, &[false; 7]);
+            assert_eq!(sources, vec![vec![0, 1, 2], vec![3], vec![4], vec![5], vec![6]]);
+            assert_eq!(cues[0].text, "So when we got there, it was closed.");
+            assert_eq!((cues[0].start, cues[0].end), (0.0, 3.0));
+            assert_eq!(decisions.len(), 1);
+
+            // Окно озвучки делится между исходными репликами
+            let display = [cue(0.0, 1.0), cue(1.0, 2.0)];
+            let spread = spread_window(&display, CueWindow { start: 1.0, end: 5.0 });
+            assert_eq!(spread, vec![CueWindow { start: 1.0, end: 3.0 }, CueWindow { start: 3.0, end: 5.0 }]);
+        }
+    }
+}
+
+/// Языковые ограничения скорости речи.
+///
+/// Для некоторых языков (немецкий, финский) речь после ускорения быстро становится
+/// неразборчивой, поэтому для них задаются более медленная базовая скорость TTS и
+/// более строгий предел ускорения при подгонке длительности.
+pub mod language_speed {
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    /// Насколько можно замедлить речь, которая короче окна реплики
+    pub const DEFAULT_MIN_TEMPO: f32 = 0.85;
+
+    fn default_min_tempo() -> f32 {
+        DEFAULT_MIN_TEMPO
+    }
+
+    /// Скоростной профиль языка
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub struct SpeedProfile {
+        /// Скорость, передаваемая в TTS API (0.5 - 2.0)
+        pub tts_speed: f32,
+        /// Максимальный коэффициент ускорения при подгонке длительности
+        pub max_tempo: f32,
+        /// Минимальный коэффициент (замедление) при подгонке длительности
+        #[serde(default = "default_min_tempo")]
+        pub min_tempo: f32,
+    }
+
+    impl Default for SpeedProfile {
+        fn default() -> Self {
+            Self { tts_speed: 1.0, max_tempo: 2.0, min_tempo: DEFAULT_MIN_TEMPO }
+        }
+    }
+
+    /// Встроенная таблица профилей (ISO 639-1)
+    pub fn builtin_profiles() -> HashMap<String, SpeedProfile> {
+        [
+            ("de", 0.95, 1.5),
+            ("fi", 0.9, 1.4),
+            ("hu", 0.95, 1.5),
+            ("nl", 0.95, 1.6),
+            ("pl", 0.95, 1.6),
+            ("ru", 1.0, 1.7),
+            ("uk", 1.0, 1.7),
+            ("ja", 1.0, 1.6),
+            ("ko", 1.0, 1.6),
+            ("zh", 1.0, 1.6),
+            ("fr", 1.0, 1.8),
+            ("it", 1.0, 1.8),
+            ("es", 1.0, 1.8),
+            ("pt", 1.0, 1.8),
+            ("en", 1.0, 2.0),
+        ]
+        .into_iter()
+        .map(|(code, tts_speed, max_tempo)| {
+            (code.to_string(), SpeedProfile { tts_speed, max_tempo, min_tempo: DEFAULT_MIN_TEMPO })
+        })
+        .collect()
+    }
+
+    /// Возвращает профиль для языка: сначала пользовательские настройки, затем встроенная таблица
+    pub fn resolve(language: Option<&str>, overrides: &HashMap<String, SpeedProfile>) -> SpeedProfile {
+        let Some(language) = language else {
+            return SpeedProfile::default();
+        };
+        // "pt-BR" и "pt" используют один профиль, если для региона нет отдельного
+        let code = language.to_lowercase();
+        let base = code.split(['-', '_']).next().unwrap_or(&code).to_string();
+
+        let profile = overrides
+            .get(&code)
+            .or_else(|| overrides.get(&base))
+            .copied()
+            .or_else(|| builtin_profiles().get(&base).copied())
+            .unwrap_or_default();
+
+        SpeedProfile {
+            tts_speed: profile.tts_speed.clamp(0.5, 2.0),
+            max_tempo: profile.max_tempo.max(1.0),
+            min_tempo: profile.min_tempo.clamp(0.5, 1.0),
+        }
+    }
+}
+
+/// Словарь произношения.
+///
+/// Имена и термины движки синтеза часто читают неправильно. Пользователь
+/// задает для термина фонетическую запись (или IPA, если движок ее понимает),
+/// и перед синтезом термин в тексте реплики заменяется ею. Записи без языка
+/// действуют для любого языка озвучки.
+pub mod lexicon {
+    use regex::Regex;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    /// Запись словаря
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct LexiconEntry {
+        pub term: String,
+        /// Что произносит движок вместо термина
+        pub pronunciation: String,
+        /// Код языка (ISO 639-1), для которого действует запись; None — для всех
+        #[serde(default)]
+        pub language: Option<String>,
+    }
+
+    /// Записи словаря для одного языка, собранные в одно регулярное выражение
+    #[derive(Debug, Clone, Default)]
+    pub struct Lexicon {
+        pattern: Option<Regex>,
+        /// Произношение по термину в нижнем регистре
+        pronunciations: HashMap<String, String>,
+    }
+
+    impl Lexicon {
+        /// Словарь для языка озвучки; запись для "pt-BR" имеет приоритет над
+        /// записью для "pt", а та — над записью без языка
+        pub fn for_language(entries: &[LexiconEntry], language: Option<&str>) -> Self {
+            let code = language.map(str::to_lowercase);
+            let base = code.as_deref().map(|code| code.split(['-', '_']).next().unwrap_or(code).to_string());
+            let priority = |entry: &LexiconEntry| match entry.language.as_deref().map(str::to_lowercase) {
+                None => Some(0),
+                Some(language) if Some(&language) == base.as_ref() => Some(1),
+                Some(language) if Some(&language) == code.as_ref() => Some(2),
+                Some(_) => None,
+            };
+
+            let mut chosen: HashMap<String, (u8, String)> = HashMap::new();
+            for entry in entries {
+                let term = entry.term.trim();
+                let Some(priority) = priority(entry) else { continue };
+                if term.is_empty() || entry.pronunciation.trim().is_empty() {
+                    continue;
+                }
+                let key = term.to_lowercase();
+                if chosen.get(&key).is_none_or(|(existing, _)| priority >= *existing) {
+                    chosen.insert(key, (priority, entry.pronunciation.trim().to_string()));
+                }
+            }
+            if chosen.is_empty() {
+                return Self::default();
+            }
+
+            // Длинные термины раньше коротких, чтобы "New York Times" не стал "New York" + "Times".
+            // Граница слова ставится только там, где термин начинается или кончается буквой
+            let mut terms: Vec<&String> = chosen.keys().collect();
+            terms.sort_by(|a, b| b.chars().count().cmp(&a.chars().count()).then(a.cmp(b)));
+            let alternatives: Vec<String> = terms
+                .iter()
+                .map(|term| {
+                    let word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+                    format!(
+                        "{}{}{}",
+                        if word(term.chars().next()) { r"\b" } else { "" },
+                        regex::escape(term),
+                        if word(term.chars().last()) { r"\b" } else { "" },
+                    )
+                })
+                .collect();
+            let pattern = Regex::new(&format!("(?i)(?:{})", alternatives.join("|"))).ok();
+            let pronunciations = chosen.into_iter().map(|(term, (_, pronunciation))| (term, pronunciation)).collect();
+            Self { pattern, pronunciations }
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.pattern.is_none()
+        }
+
+        /// Текст реплики с произношением вместо терминов словаря
+        pub fn apply(&self, text: &str) -> String {
+            let Some(pattern) = &self.pattern else {
+                return text.to_string();
+            };
+            pattern
+                .replace_all(text, |captures: &regex::Captures| {
+                    let matched = &captures[0];
+                    self.pronunciations.get(&matched.to_lowercase()).cloned().unwrap_or_else(|| matched.to_string())
+                })
+                .into_owned()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn entry(term: &str, pronunciation: &str, language: Option<&str>) -> LexiconEntry {
+            LexiconEntry {
+                term: term.to_string(),
+                pronunciation: pronunciation.to_string(),
+                language: language.map(str::to_string),
+            }
+        }
+
+        #[test]
+        fn test_terms_are_replaced_as_whole_words_of_the_language() {
+            let entries = [
+                entry("Nginx", "engine-x", None),
+                entry("C++", "си плюс плюс", Some("ru")),
+                entry("Nginx", "энджин-икс", Some("ru")),
+                entry("Kubernetes", "Kuber-nee-tees", Some("en")),
+            ];
+
+            let russian = Lexicon::for_language(&entries, Some("ru-RU"));
+            assert_eq!(russian.apply("Пишем на C++ модуль для nginx."), "Пишем на си плюс плюс модуль для энджин-икс.");
+            assert_eq!(russian.apply("Kubernetes, nginxconf"), "Kubernetes, nginxconf");
+
+            let german = Lexicon::for_language(&entries, Some("de"));
+            assert_eq!(german.apply("NGINX und C++"), "engine-x und C++");
+            assert!(Lexicon::for_language(&[], Some("de")).is_empty());
+        }
+    }
+}
+
+/// Модуль для парсинга VTT- и SRT-файлов.
+pub mod vtt {
+    use super::{SubtitleCue, Result, TtsError};
+    use serde::{Deserialize, Serialize};
+    use std::path::Path;
+
+    /// Формат файла субтитров
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum SubtitleFormat {
+        Vtt,
+        Srt,
+    }
+
+    impl SubtitleFormat {
+        /// Определяет формат по расширению файла, а если оно неизвестно - по содержимому
+        pub fn detect(path: Option<&Path>, content: &str) -> Self {
+            let extension = path
+                .and_then(|path| path.extension())
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            match extension.as_str() {
+                "srt" => SubtitleFormat::Srt,
+                "vtt" => SubtitleFormat::Vtt,
+                _ if content.trim_start_matches('\u{feff}').trim_start().starts_with("WEBVTT") => SubtitleFormat::Vtt,
+                _ => SubtitleFormat::Srt,
+            }
+        }
+
+        pub fn extension(self) -> &'static str {
+            match self {
+                SubtitleFormat::Vtt => "vtt",
+                SubtitleFormat::Srt => "srt",
+            }
+        }
+    }
+
+    /// Парсит файл субтитров (VTT или SRT) и возвращает вектор структур SubtitleCue.
+    pub fn parse_vtt<P: AsRef<std::path::Path>>(file_path: P) -> Result<Vec<SubtitleCue>> {
+        parse_vtt_with_encoding(file_path, None)
+    }
+
+    /// То же, что [`parse_vtt`], но с кодировкой, выбранной пользователем (например, "windows-1251").
+    pub fn parse_vtt_with_encoding<P: AsRef<std::path::Path>>(file_path: P, encoding: Option<&str>) -> Result<Vec<SubtitleCue>> {
+        // Файлы пользователей бывают не в UTF-8, перекодируем с автоопределением кодировки
+        let data = crate::utils::charset::read_subtitle_file_sync(file_path.as_ref(), encoding)?;
+        match SubtitleFormat::detect(Some(file_path.as_ref()), &data) {
+            SubtitleFormat::Vtt => parse_vtt_str(&data),
+            SubtitleFormat::Srt => parse_srt_str(&data),
+        }
+    }
+
+    /// Парсит содержимое SRT из строки. Номера реплик не сохраняются,
+    /// при записи они все равно проставляются заново.
+    pub fn parse_srt_str(data: &str) -> Result<Vec<SubtitleCue>> {
+        let mut cues = parse_vtt_str(data.trim_start_matches('\u{feff}'))?;
+        for cue in cues.iter_mut() {
+            if cue.id.as_deref().is_some_and(|id| id.chars().all(|c| c.is_ascii_digit())) {
+                cue.id = None;
+            }
+        }
+        Ok(cues)
+    }
+
+    /// Парсит содержимое VTT из строки.
+    pub fn parse_vtt_str(data: &str) -> Result<Vec<SubtitleCue>> {
+        let data = data.replace("\r\n", "\n");
+        let mut cues = Vec::new();
+        // Блоки NOTE достаются следующей за ними реплике
+        let mut notes = Vec::new();
+
+        // Разбиваем файл на блоки по пустой строке
+        for block in data.split("\n\n") {
+            let block = block.trim_matches('\n');
+            if block.starts_with("NOTE") && !block.contains("-->") {
+                notes.push(block.to_string());
+                continue;
+            }
+            if !block.contains("-->") {
+                continue;
+            }
+            let mut lines = block.lines();
+            // Строка перед таймингом - идентификатор реплики
+            let mut id = None;
+            let timing_line = loop {
+                let line = lines.next()
+                    .ok_or_else(|| TtsError::VttParsingError("Не найден тайминг в блоке".to_string()))?;
+                if line.contains("-->") {
+                    break line;
+                }
+                id = Some(line.trim().to_string()).filter(|id| !id.is_empty() && !id.starts_with("WEBVTT"));
+            };
+            let times: Vec<&str> = timing_line.split_whitespace().collect();
+            if times.len() >= 3 && times[1] == "-->" {
+                let start = parse_time(times[0])?;
+                let end = parse_time(times[2])?;
+                // Оставшиеся строки считаем текстом реплики
+                let text = lines.collect::<Vec<_>>().join(" ").trim().to_string();
+                let (speaker, text) = split_speaker(&text);
+                // Пропускаем пустые субтитры
+                if !text.is_empty() {
+                    cues.push(SubtitleCue { start, end, text, id, speaker, notes: std::mem::take(&mut notes) });
+                }
+            }
+        }
+        Ok(cues)
+    }
+
+    /// Отделяет тег говорящего `<v Имя>` в начале реплики от текста
+    pub fn split_speaker(text: &str) -> (Option<String>, String) {
+        let Some(rest) = text.strip_prefix("<v") else {
+            return (None, text.to_string());
+        };
+        // `<v.class Имя>`: классы пропускаем, имя идет после пробела
+        let Some(close) = rest.find('>') else {
+            return (None, text.to_string());
+        };
+        let speaker = rest[..close].split_once(char::is_whitespace).map(|(_, name)| name.trim().to_string());
+        let body = rest[close + 1..].trim();
+        let body = body.strip_suffix("</v>").unwrap_or(body).trim();
+        match speaker {
+            Some(speaker) if !speaker.is_empty() => (Some(speaker), body.to_string()),
+            _ => (None, text.to_string()),
+        }
+    }
+
+    /// Преобразует строку времени формата "HH:MM:SS.mmm" (или "HH:MM:SS,mmm" из SRT) в секунды.
+    pub fn parse_time(t: &str) -> Result<f32> {
+        let parts: Vec<&str> = t.split([':', '.', ',']).collect();
+        if parts.len() < 3 {
+            return Err(TtsError::VttParsingError(format!("Неверный формат времени: {}", t)));
+        }
+        
+        let hours: f32 = parts[0].parse()
+            .map_err(|_| TtsError::VttParsingError(format!("Не удалось распознать часы: {}", parts[0])))?;
+        let minutes: f32 = parts[1].parse()
+            .map_err(|_| TtsError::VttParsingError(format!("Не удалось распознать минуты: {}", parts[1])))?;
+        let seconds: f32 = parts[2].parse()
+            .map_err(|_| TtsError::VttParsingError(format!("Не удалось распознать секунды: {}", parts[2])))?;
+        let millis: f32 = if parts.len() > 3 { 
+            parts[3].parse()
+                .map_err(|_| TtsError::VttParsingError(format!("Не удалось распознать миллисекунды: {}", parts[3])))?
+        } else { 
+            0.0 
+        };
+        
+        Ok(hours * 3600.0 + minutes * 60.0 + seconds + millis / 1000.0)
+    }
+
+    /// Форматирует время в секундах как "HH:MM:SS.mmm".
+    pub fn format_time(seconds: f32) -> String {
+        let total_millis = (seconds.max(0.0) * 1000.0).round() as u64;
+        format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            total_millis / 3_600_000,
+            total_millis / 60_000 % 60,
+            total_millis / 1000 % 60,
+            total_millis % 1000
+        )
+    }
+
+    /// Сериализует реплики в VTT вместе с заметками, идентификаторами и говорящими.
+    pub fn write_vtt_str(cues: &[SubtitleCue]) -> String {
+        let mut output = String::from("WEBVTT\n\n");
+        for cue in cues {
+            for note in &cue.notes {
+                output.push_str(&format!("{}\n\n", note));
+            }
+            if let Some(id) = &cue.id {
+                output.push_str(&format!("{}\n", id));
+            }
+            let text = match &cue.speaker {
+                Some(speaker) => format!("<v {}>{}", speaker, cue.text),
+                None => cue.text.clone(),
+            };
+            output.push_str(&format!("{} --> {}\n{}\n\n", format_time(cue.start), format_time(cue.end), text));
+        }
+        output
+    }
+
+    /// Сериализует реплики в SRT. Заметки, идентификаторы и говорящие в SRT
+    /// не записываются: у формата для них нет синтаксиса.
+    pub fn write_srt_str(cues: &[SubtitleCue]) -> String {
+        let mut output = String::new();
+        for (index, cue) in cues.iter().enumerate() {
+            output.push_str(&format!(
+                "{}\n{} --> {}\n{}\n\n",
+                index + 1,
+                format_time(cue.start).replace('.', ","),
+                format_time(cue.end).replace('.', ","),
+                cue.text
+            ));
+        }
+        output
+    }
+
+    /// Сериализует реплики в выбранном формате
+    pub fn write_str(cues: &[SubtitleCue], format: SubtitleFormat) -> String {
+        match format {
+            SubtitleFormat::Vtt => write_vtt_str(cues),
+            SubtitleFormat::Srt => write_srt_str(cues),
+        }
+    }
+
+    /// Реплики отрезка `start..end` исходного видео со временем от начала
+    /// отрезка — для обработки фрагмента. Реплики на границах обрезаются.
+    pub fn clip_cues(cues: &[SubtitleCue], start: f32, end: f32) -> Vec<SubtitleCue> {
+        cues.iter()
+            .filter(|cue| cue.end > start && cue.start < end)
+            .map(|cue| SubtitleCue {
+                start: cue.start.max(start) - start,
+                end: cue.end.min(end) - start,
+                ..cue.clone()
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_cue_metadata_round_trip() {
+            let input = "WEBVTT\n\nNOTE recorded in studio\n\nintro\n00:00:01.000 --> 00:00:02.000\n<v.loud Anna Maria>Hello</v>\n\n00:00:03.000 --> 00:00:04.000\n<i>Music</i>\n\n";
+            let cues = parse_vtt_str(input).unwrap();
+            assert_eq!(cues.len(), 2);
+            assert_eq!(cues[0].id.as_deref(), Some("intro"));
+            assert_eq!(cues[0].speaker.as_deref(), Some("Anna Maria"));
+            assert_eq!(cues[0].text, "Hello");
+            assert_eq!(cues[0].notes, vec!["NOTE recorded in studio".to_string()]);
+            assert_eq!(cues[1].speaker, None);
+            assert_eq!(cues[1].text, "<i>Music</i>");
+
+            let written = write_vtt_str(&cues);
+            assert!(written.contains("NOTE recorded in studio\n\nintro\n00:00:01.000 --> 00:00:02.000\n<v Anna Maria>Hello"));
+            let reparsed = parse_vtt_str(&written).unwrap();
+            assert_eq!(reparsed[0].speaker, cues[0].speaker);
+            assert_eq!(reparsed[0].notes, cues[0].notes);
+        }
+
+        #[test]
+        fn test_srt_round_trip() {
+            let input = "\u{feff}1\r\n00:00:01,500 --> 00:00:02,250\r\nHello\r\nworld\r\n\r\n2\r\n00:01:03,000 --> 00:01:04,000\r\nBye\r\n";
+            assert_eq!(SubtitleFormat::detect(None, input), SubtitleFormat::Srt);
+            assert_eq!(SubtitleFormat::detect(Some(Path::new("a.vtt")), input), SubtitleFormat::Vtt);
+            let cues = parse_srt_str(input).unwrap();
+            assert_eq!(cues.len(), 2);
+            assert_eq!(cues[0].id, None);
+            assert!((cues[0].start - 1.5).abs() < 1e-6);
+            assert_eq!(cues[0].text, "Hello world");
+            assert!((cues[1].start - 63.0).abs() < 1e-6);
+
+            let written = write_srt_str(&cues);
+            assert_eq!(written, "1\n00:00:01,500 --> 00:00:02,250\nHello world\n\n2\n00:01:03,000 --> 00:01:04,000\nBye\n\n");
+        }
+
+        #[test]
+        fn test_clip_cues_to_a_range() {
+            let cues = parse_vtt_str("WEBVTT\n\n00:00:05.000 --> 00:00:09.000\nBefore\n\n00:00:09.000 --> 00:00:12.000\nAcross\n\n00:00:20.000 --> 00:00:22.000\nAfter\n\n").unwrap();
+            let clipped = clip_cues(&cues, 10.0, 15.0);
+            assert_eq!(clipped.len(), 1);
+            assert_eq!(clipped[0].text, "Across");
+            assert!(clipped[0].start.abs() < 1e-6 && (clipped[0].end - 2.0).abs() < 1e-6);
+        }
+    }
+}
+
+/// Модуль для обращения к OpenAI TTS API.
+#[allow(clippy::module_inception)]
+pub mod tts {
+    use super::{Result, TtsError, TtsConfig};
+    use crate::utils::advanced_config;
+    use crate::utils::http_retry;
+    use crate::utils::openai_connection::{self, OpenAiAuth};
+    use futures::StreamExt;
+    use serde_json::json;
+    use log::{info, warn};
+    use tokio::time::sleep;
+
+    /// Генерирует аудиофрагмент через TTS API для заданного текста.
+    /// Возвращает Vec<u8> с данными аудио (например, MP3) и текст для отладки.
+    /// Ответ читается по частям по мере передачи; `on_chunk` получает число
+    /// байт, пришедших к этому моменту.
+    pub async fn generate_tts(
+        api_key: &str,
+        text: &str,
+        config: &TtsConfig,
+        on_chunk: Option<&(dyn Fn(usize) + Send + Sync)>,
+    ) -> Result<(Vec<u8>, String)> {
+        let payload = json!({
+            "model": config.model,
+            "voice": config.voice,
+            "input": text,
+            "response_format": "mp3",
+            "speed": config.speed
+        });
+
+        let client = openai_connection::client();
+        let policy = advanced_config::current().retry;
+
+        // Ошибки сети, 429 и 5xx повторяет http_retry; здесь повторяем только
+        // пустой ответ, который приходит со статусом 200
+        for attempt in 0..=policy.max_retries {
+            if attempt > 0 {
+                let backoff = policy.backoff(attempt, None, rand::random::<f64>());
+                info!("Повторная попытка #{} через {} мс...", attempt + 1, backoff.as_millis());
+                sleep(backoff).await;
+            }
+
+            let resp = http_retry::send("Speech", || {
+                client
+                    .post(openai_connection::endpoint("/audio/speech"))
+                    .openai_auth(api_key)
+                    .json(&payload)
+            })
+            .await
+            .map_err(|e| TtsError::OpenAiApiError(e.to_string()))?;
+
+            let status = resp.status();
+            if !status.is_success() {
+                let error_text = resp.text().await.unwrap_or_else(|_| "Неизвестная ошибка".to_string());
+                return Err(TtsError::OpenAiApiError(format!(
+                    "Ошибка API (код {}): {}", status, error_text
+                )));
+            }
+
+            let mut audio_bytes = Vec::new();
+            let mut chunks = resp.bytes_stream();
+            while let Some(chunk) = chunks.next().await {
+                audio_bytes.extend_from_slice(&chunk.map_err(TtsError::HttpError)?);
+                if let Some(on_chunk) = on_chunk {
+                    on_chunk(audio_bytes.len());
+                }
+            }
+
+            info!("Получено {} байт аудио от OpenAI для текста: {}", audio_bytes.len(), text);
+
+            if audio_bytes.is_empty() {
+                warn!("Получен пустой ответ от OpenAI TTS API для текста: {}", text);
+                continue;
+            }
+
+            // Проверяем, что первые байты похожи на MP3
+            if audio_bytes.len() > 2 {
+                let is_id3 = audio_bytes.len() > 3 && &audio_bytes[0..3] == b"ID3";
+                let is_mpeg = audio_bytes.len() > 2 && (audio_bytes[0] == 0xFF && (audio_bytes[1] & 0xE0) == 0xE0);
+
+                if !is_id3 && !is_mpeg {
+                    warn!("Получены данные, не похожие на MP3 (нет ID3/MPEG заголовка) для текста: {}", text);
+                }
+            }
+
+            return Ok((audio_bytes, text.to_string()));
+        }
+
+        Err(TtsError::OpenAiApiError("Получен пустой ответ от API".to_string()))
+    }
+}
+
+/// Источники речи для реплик.
+///
+/// Синхронизатору неважно, откуда берется аудио реплики: из классического TTS по
+/// переведенному тексту или из модели speech-to-speech, которая сразу переводит
+/// исходную речь. Провайдеры можно выстраивать в цепочку [`provider::Fallback`],
+/// чтобы при ошибке одного реплику озвучивал следующий.
+pub mod provider {
+    use super::{tts, Result, TtsConfig, TtsError};
+    use base64::Engine;
+    use futures::future::BoxFuture;
+    use log::{info, warn};
+    use crate::utils::openai_connection::{self, OpenAiAuth};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use once_cell::sync::Lazy;
+    use std::sync::{Arc, RwLock};
+    use std::time::Duration;
+
+    /// Что нужно озвучить
+    pub struct SpeechRequest<'a> {
+        /// Текст реплики из VTT
+        pub text: &'a str,
+        /// Исходная речь реплики в WAV, если провайдер ее запросил
+        pub source_audio: Option<&'a [u8]>,
+        /// Говорящий реплики, если субтитры его называют
+        pub speaker: Option<&'a str>,
+        /// Вызывается по мере получения аудио с числом пришедших байт, если
+        /// движок отдает аудио по частям
+        pub on_chunk: Option<&'a (dyn Fn(usize) + Send + Sync)>,
+    }
+
+    /// Аудио реплики (MP3) и текст, который в нем произносится
+    pub type SpeechOutput = (Vec<u8>, String);
+
+    /// Что умеет движок синтеза: по этому списку интерфейс строит выбор голоса
+    #[derive(Debug, Clone, serde::Serialize)]
+    pub struct EngineCapabilities {
+        pub id: String,
+        pub name: String,
+        pub voices: Vec<String>,
+        pub default_voice: String,
+        pub needs_source_audio: bool,
+        /// Отдает ли движок аудио по частям, пока реплика еще синтезируется
+        pub streaming: bool,
+        /// Коды языков, которые движок произносит; пустой список — любые
+        pub languages: Vec<String>,
+        /// Нужен ли движку ключ OpenAI
+        pub requires_api_key: bool,
+    }
+
+    const OPENAI_TTS_VOICES: [&str; 10] = ["alloy", "ash", "ballad", "coral", "echo", "fable", "nova", "onyx", "sage", "shimmer"];
+    const OPENAI_AUDIO_VOICES: [&str; 8] = ["alloy", "ash", "ballad", "coral", "echo", "sage", "shimmer", "verse"];
+
+    /// Из чего фабрика движка создает провайдера
+    pub struct EngineParams<'a> {
+        pub api_key: &'a str,
+        pub config: TtsConfig,
+        /// Язык, на который движок сам переводит реплику; None — текст уже переведен
+        pub target_language: Option<&'a str>,
+    }
+
+    pub type EngineFactory = Arc<dyn Fn(&EngineParams) -> Result<Arc<dyn SpeechProvider>> + Send + Sync>;
+
+    struct RegisteredEngine {
+        capabilities: EngineCapabilities,
+        factory: EngineFactory,
+    }
+
+    /// Реестр движков синтеза. Встроенные движки OpenAI регистрируются сразу,
+    /// остальные добавляются при запуске через `register`
+    pub struct TtsEngineRegistry {
+        engines: RwLock<Vec<RegisteredEngine>>,
+    }
+
+    impl TtsEngineRegistry {
+        /// Пустой реестр, без встроенных движков
+        pub fn new() -> Self {
+            Self { engines: RwLock::new(Vec::new()) }
+        }
+
+        /// Реестр со встроенными движками OpenAI
+        pub fn with_builtin() -> Self {
+            let registry = Self::new();
+            let voices = |list: &[&str]| list.iter().map(|voice| voice.to_string()).collect();
+            registry.register(
+                EngineCapabilities {
+                    id: "openai-tts".to_string(),
+                    name: "OpenAI TTS".to_string(),
+                    voices: voices(&OPENAI_TTS_VOICES),
+                    default_voice: "ash".to_string(),
+                    needs_source_audio: false,
+                    streaming: true,
+                    languages: Vec::new(),
+                    requires_api_key: true,
+                },
+                Arc::new(|params: &EngineParams| {
+                    let provider = match params.target_language {
+                        Some(language) => OpenAiTts::translating(params.api_key, params.config.clone(), language),
+                        None => OpenAiTts::new(params.api_key, params.config.clone()),
+                    };
+                    Ok(Arc::new(provider) as Arc<dyn SpeechProvider>)
+                }),
+            );
+            registry.register(
+                EngineCapabilities {
+                    id: "openai-speech-to-speech".to_string(),
+                    name: "OpenAI speech-to-speech (experimental)".to_string(),
+                    voices: voices(&OPENAI_AUDIO_VOICES),
+                    default_voice: "ash".to_string(),
+                    needs_source_audio: true,
+                    streaming: false,
+                    languages: Vec::new(),
+                    requires_api_key: true,
+                },
+                Arc::new(|params: &EngineParams| {
+                    let language = params.target_language.ok_or_else(|| {
+                        TtsError::ConfigError("Для speech-to-speech нужен язык перевода".to_string())
+                    })?;
+                    Ok(Arc::new(OpenAiSpeechToSpeech::new(params.api_key, &params.config.voice, language)) as Arc<dyn SpeechProvider>)
+                }),
+            );
+            registry
+        }
+
+        /// Добавляет движок; движок с тем же id заменяется
+        pub fn register(&self, capabilities: EngineCapabilities, factory: EngineFactory) {
+            let Ok(mut engines) = self.engines.write() else { return };
+            info!("Регистрация движка синтеза {} ({})", capabilities.name, capabilities.id);
+            let engine = RegisteredEngine { capabilities, factory };
+            match engines.iter_mut().find(|existing| existing.capabilities.id == engine.capabilities.id) {
+                Some(existing) => *existing = engine,
+                None => engines.push(engine),
+            }
+        }
+
+        /// Возможности движка по id
+        pub fn get(&self, id: &str) -> Option<EngineCapabilities> {
+            let engines = self.engines.read().ok()?;
+            engines.iter().find(|engine| engine.capabilities.id == id).map(|engine| engine.capabilities.clone())
+        }
+
+        /// Все движки в порядке регистрации
+        pub fn engines(&self) -> Vec<EngineCapabilities> {
+            self.engines
+                .read()
+                .map(|engines| engines.iter().map(|engine| engine.capabilities.clone()).collect())
+                .unwrap_or_default()
+        }
+
+        /// Создает провайдера движка
+        pub fn create(&self, id: &str, params: &EngineParams) -> Result<Arc<dyn SpeechProvider>> {
+            // Фабрика вызывается без блокировки: она может сама обращаться к реестру
+            let factory = {
+                let engines = self
+                    .engines
+                    .read()
+                    .map_err(|_| TtsError::ConfigError("Реестр движков синтеза поврежден".to_string()))?;
+                engines
+                    .iter()
+                    .find(|engine| engine.capabilities.id == id)
+                    .map(|engine| engine.factory.clone())
+                    .ok_or_else(|| TtsError::ConfigError(format!("Неизвестный движок синтеза: {}", id)))?
+            };
+            factory(params)
+        }
+    }
+
+    impl Default for TtsEngineRegistry {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    static REGISTRY: Lazy<TtsEngineRegistry> = Lazy::new(TtsEngineRegistry::with_builtin);
+
+    /// Общий реестр приложения
+    pub fn registry() -> &'static TtsEngineRegistry {
+        &REGISTRY
+    }
+
+    /// Доступные движки синтеза
+    pub fn engine_capabilities() -> Vec<EngineCapabilities> {
+        registry().engines()
+    }
+
+    pub trait SpeechProvider: Send + Sync {
+        fn name(&self) -> &str;
+
+        /// Нужна ли провайдеру исходная речь реплики
+        fn needs_source_audio(&self) -> bool {
+            false
+        }
+
+        fn synthesize<'a>(&'a self, request: &'a SpeechRequest<'a>) -> BoxFuture<'a, Result<SpeechOutput>>;
+    }
+
+    /// OpenAI TTS по тексту реплики; если задан язык, текст сначала переводится
+    pub struct OpenAiTts {
+        api_key: String,
+        config: TtsConfig,
+        translate_to: Option<String>,
+    }
+
+    impl OpenAiTts {
+        pub fn new(api_key: &str, config: TtsConfig) -> Self {
+            Self { api_key: api_key.to_string(), config, translate_to: None }
+        }
+
+        /// Классический конвейер для непереведенных реплик: перевод и TTS
+        pub fn translating(api_key: &str, config: TtsConfig, target_language: &str) -> Self {
+            Self { translate_to: Some(target_language.to_string()), ..Self::new(api_key, config) }
+        }
+    }
+
+    impl SpeechProvider for OpenAiTts {
+        fn name(&self) -> &str {
+            if self.translate_to.is_some() { "openai-translate-tts" } else { "openai-tts" }
+        }
+
+        fn synthesize<'a>(&'a self, request: &'a SpeechRequest<'a>) -> BoxFuture<'a, Result<SpeechOutput>> {
+            Box::pin(async move {
+                let text = match &self.translate_to {
+                    Some(language) => crate::utils::translate::translate_text(request.text, language, &self.api_key).await?,
+                    None => request.text.to_string(),
+                };
+                tts::generate_tts(&self.api_key, &text, &self.config, request.on_chunk).await
+            })
+        }
+    }
+
+    /// Экспериментальный перевод речи в речь аудиомоделью OpenAI: исходная
+    /// речь реплики сразу превращается в переведенную, без текстового перевода
+    pub struct OpenAiSpeechToSpeech {
+        api_key: String,
+        model: String,
+        voice: String,
+        target_language: String,
+    }
+
+    impl OpenAiSpeechToSpeech {
+        pub const DEFAULT_MODEL: &'static str = "gpt-4o-audio-preview";
+
+        pub fn new(api_key: &str, voice: &str, target_language: &str) -> Self {
+            Self {
+                api_key: api_key.to_string(),
+                model: Self::DEFAULT_MODEL.to_string(),
+                voice: voice.to_string(),
+                target_language: target_language.to_string(),
+            }
+        }
+    }
+
+    impl SpeechProvider for OpenAiSpeechToSpeech {
+        fn name(&self) -> &str {
+            "openai-speech-to-speech"
+        }
+
+        fn needs_source_audio(&self) -> bool {
+            true
+        }
+
+        fn synthesize<'a>(&'a self, request: &'a SpeechRequest<'a>) -> BoxFuture<'a, Result<SpeechOutput>> {
+            Box::pin(async move {
+                let source_audio = request.source_audio.ok_or_else(|| {
+                    TtsError::ConfigError("Для speech-to-speech нужна исходная речь реплики".to_string())
+                })?;
+                let instructions = format!(
+                    "You are a dubbing interpreter. Listen to the speech and say its translation into {}. \
+                    Keep the meaning, tone and approximate length. Respond only with the translated speech.",
+                    self.target_language
+                );
+                let payload = json!({
+                    "model": self.model,
+                    "modalities": ["text", "audio"],
+                    "audio": { "voice": self.voice, "format": "mp3" },
+                    "messages": [
+                        { "role": "system", "content": instructions },
+                        { "role": "user", "content": [{
+                            "type": "input_audio",
+                            "input_audio": {
+                                "data": base64::engine::general_purpose::STANDARD.encode(source_audio),
+                                "format": "wav"
+                            }
+                        }]}
+                    ]
+                });
+
+                let client = openai_connection::client();
+                let resp = crate::utils::http_retry::send("Speech-to-speech", || {
+                    client
+                        .post(openai_connection::endpoint("/chat/completions"))
+                        .openai_auth(&self.api_key)
+                        .json(&payload)
+                        .timeout(Duration::from_secs(crate::utils::advanced_config::current().timeouts.openai_request_secs))
+                })
+                .await
+                .map_err(|e| TtsError::OpenAiApiError(e.to_string()))?;
+                let status = resp.status();
+                if !status.is_success() {
+                    let error_text = resp.text().await.unwrap_or_else(|_| "Неизвестная ошибка".to_string());
+                    return Err(TtsError::OpenAiApiError(format!("Ошибка API (код {}): {}", status, error_text)));
+                }
+
+                let body: serde_json::Value = resp.json().await?;
+                let audio = &body["choices"][0]["message"]["audio"];
+                let data = audio["data"]
+                    .as_str()
+                    .ok_or_else(|| TtsError::OpenAiApiError("Ответ не содержит аудио".to_string()))?;
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(data)
+                    .map_err(|e| TtsError::OpenAiApiError(format!("Некорректное аудио в ответе: {}", e)))?;
+                if bytes.is_empty() {
+                    return Err(TtsError::OpenAiApiError("Получен пустой ответ от API".to_string()));
+                }
+                let transcript = audio["transcript"].as_str().unwrap_or(request.text).trim().to_string();
+                info!("Speech-to-speech: {} байт аудио, текст: {}", bytes.len(), transcript);
+                Ok((bytes, transcript))
+            })
+        }
+    }
+
+    /// Пробует провайдеров по очереди, пока один не справится.
+    /// Ошибки исчерпанной квоты не маскируются: у запасного провайдера та же учетная запись.
+    pub struct Fallback {
+        providers: Vec<Arc<dyn SpeechProvider>>,
+    }
+
+    impl Fallback {
+        pub fn new(providers: Vec<Arc<dyn SpeechProvider>>) -> Self {
+            Self { providers }
+        }
+    }
+
+    impl SpeechProvider for Fallback {
+        fn name(&self) -> &str {
+            self.providers.first().map(|provider| provider.name()).unwrap_or("fallback")
+        }
+
+        fn needs_source_audio(&self) -> bool {
+            self.providers.iter().any(|provider| provider.needs_source_audio())
+        }
+
+        fn synthesize<'a>(&'a self, request: &'a SpeechRequest<'a>) -> BoxFuture<'a, Result<SpeechOutput>> {
+            Box::pin(async move {
+                let mut last_error = None;
+                for provider in &self.providers {
+                    match provider.synthesize(request).await {
+                        Ok(output) => return Ok(output),
+                        Err(e) if crate::utils::quota::is_quota_error(&e.to_string()) => return Err(e),
+                        Err(e) => {
+                            warn!("Провайдер {} не озвучил реплику, пробуем следующий: {}", provider.name(), e);
+                            last_error = Some(e);
+                        }
+                    }
+                }
+                Err(last_error.unwrap_or_else(|| TtsError::ConfigError("Нет провайдеров речи".to_string())))
+            })
+        }
+    }
+
+    /// Озвучивает каждого говорящего своим голосом: провайдер говорящего из
+    /// списка, остальных — провайдер по умолчанию
+    pub struct SpeakerVoices {
+        default: Arc<dyn SpeechProvider>,
+        speakers: HashMap<String, Arc<dyn SpeechProvider>>,
+    }
+
+    impl SpeakerVoices {
+        pub fn new(default: Arc<dyn SpeechProvider>, speakers: HashMap<String, Arc<dyn SpeechProvider>>) -> Self {
+            Self { default, speakers }
+        }
+
+        fn provider_for(&self, speaker: Option<&str>) -> &Arc<dyn SpeechProvider> {
+            speaker.and_then(|speaker| self.speakers.get(speaker)).unwrap_or(&self.default)
+        }
+    }
+
+    impl SpeechProvider for SpeakerVoices {
+        fn name(&self) -> &str {
+            self.default.name()
+        }
+
+        fn needs_source_audio(&self) -> bool {
+            self.default.needs_source_audio() || self.speakers.values().any(|provider| provider.needs_source_audio())
+        }
+
+        fn synthesize<'a>(&'a self, request: &'a SpeechRequest<'a>) -> BoxFuture<'a, Result<SpeechOutput>> {
+            self.provider_for(request.speaker).synthesize(request)
+        }
+    }
+
+    /// Подставляет произношение из словаря в текст реплики перед синтезом и
+    /// раскрывает числа и сокращения. Произнесенным текстом остается текст
+    /// реплики, чтобы в субтитры не попадала фонетическая запись.
+    pub struct Pronounced {
+        inner: Arc<dyn SpeechProvider>,
+        lexicon: Arc<super::lexicon::Lexicon>,
+        normalizer: Arc<crate::utils::tts::normalize::Normalizer>,
+    }
+
+    impl Pronounced {
+        pub fn new(
+            inner: Arc<dyn SpeechProvider>,
+            lexicon: Arc<super::lexicon::Lexicon>,
+            normalizer: Arc<crate::utils::tts::normalize::Normalizer>,
+        ) -> Self {
+            Self { inner, lexicon, normalizer }
+        }
+    }
+
+    impl SpeechProvider for Pronounced {
+        fn name(&self) -> &str {
+            self.inner.name()
+        }
+
+        fn needs_source_audio(&self) -> bool {
+            self.inner.needs_source_audio()
+        }
+
+        fn synthesize<'a>(&'a self, request: &'a SpeechRequest<'a>) -> BoxFuture<'a, Result<SpeechOutput>> {
+            Box::pin(async move {
+                // Словарь раньше нормализации: термины вроде "C++" или "4K" задает пользователь
+                let text = self.normalizer.apply(&self.lexicon.apply(request.text));
+                if text == request.text {
+                    return self.inner.synthesize(request).await;
+                }
+                let pronounced = SpeechRequest { text: &text, ..*request };
+                let (bytes, spoken) = self.inner.synthesize(&pronounced).await?;
+                let spoken = if spoken == text { request.text.to_string() } else { spoken };
+                Ok((bytes, spoken))
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        struct Fixed(std::result::Result<&'static str, &'static str>);
+
+        impl SpeechProvider for Fixed {
+            fn name(&self) -> &str {
+                "fixed"
+            }
+
+            fn synthesize<'a>(&'a self, _request: &'a SpeechRequest<'a>) -> BoxFuture<'a, Result<SpeechOutput>> {
+                Box::pin(async move {
+                    match self.0 {
+                        Ok(text) => Ok((vec![1, 2, 3], text.to_string())),
+                        Err(error) => Err(TtsError::OpenAiApiError(error.to_string())),
+                    }
+                })
+            }
+        }
+
+        fn chain(results: &[std::result::Result<&'static str, &'static str>]) -> Fallback {
+            Fallback::new(results.iter().map(|result| Arc::new(Fixed(*result)) as Arc<dyn SpeechProvider>).collect())
+        }
+
+        #[test]
+        fn test_registry_registers_and_replaces_engines() {
+            let registry = TtsEngineRegistry::with_builtin();
+            assert_eq!(registry.engines().len(), 2);
+
+            let mut capabilities = registry.get("openai-tts").unwrap();
+            capabilities.id = "fixed".to_string();
+            capabilities.streaming = true;
+            registry.register(capabilities.clone(), Arc::new(|_: &EngineParams| Ok(Arc::new(Fixed(Ok("a"))) as Arc<dyn SpeechProvider>)));
+            registry.register(capabilities, Arc::new(|_: &EngineParams| Ok(Arc::new(Fixed(Ok("b"))) as Arc<dyn SpeechProvider>)));
+            assert_eq!(registry.engines().len(), 3);
+            assert!(registry.get("fixed").unwrap().streaming);
+
+            let params = EngineParams {
+                api_key: "key",
+                config: TtsConfig { model: "tts-1".to_string(), voice: "ash".to_string(), speed: 1.0 },
+                target_language: None,
+            };
+            assert_eq!(registry.create("fixed", &params).unwrap().name(), "fixed");
+            assert!(registry.create("missing", &params).is_err());
+            // Speech-to-speech can't work without a target language
+            assert!(registry.create("openai-speech-to-speech", &params).is_err());
+        }
+
+        #[tokio::test]
+        async fn test_fallback_uses_next_provider() {
+            let request = SpeechRequest { text: "hello", source_audio: None, speaker: None, on_chunk: None };
+            let (_, text) = chain(&[Err("model not found"), Ok("привет")]).synthesize(&request).await.unwrap();
+            assert_eq!(text, "привет");
+
+            // Another provider of the same account won't have quota either
+            let result = chain(&[Err("insufficient_quota"), Ok("привет")]).synthesize(&request).await;
+            assert!(result.is_err());
+        }
+
+        #[tokio::test]
+        async fn test_speaker_voices_route_by_speaker() {
+            let voices = SpeakerVoices::new(
+                Arc::new(Fixed(Ok("narrator"))),
+                HashMap::from([("SPEAKER_2".to_string(), Arc::new(Fixed(Ok("guest"))) as Arc<dyn SpeechProvider>)]),
+            );
+            let spoken = |speaker| SpeechRequest { text: "hello", source_audio: None, speaker, on_chunk: None };
+            assert_eq!(voices.synthesize(&spoken(Some("SPEAKER_2"))).await.unwrap().1, "guest");
+            assert_eq!(voices.synthesize(&spoken(Some("SPEAKER_1"))).await.unwrap().1, "narrator");
+            assert_eq!(voices.synthesize(&spoken(None)).await.unwrap().1, "narrator");
+        }
+    }
+}
+
+/// Поиск речи в дорожке голоса (WebRTC VAD).
+///
+/// Субтитры часто висят на экране дольше, чем звучат слова. Чтобы озвучка
+/// совпадала с артикуляцией, окна реплик сужаются до фактической речи, найденной
+/// в выделенной Demucs дорожке голоса оригинала.
+///
+/// Длинные участки без речи (музыкальные проигрыши, заставки, тишина) не
+/// отправляются на распознавание и не озвучиваются: Whisper сочиняет на них
+/// текст, а озвучка поверх музыки звучит как ошибка.
+pub mod vad {
+    use super::timeline::CueWindow;
+    use super::{audio, demucs, Result, SubtitleCue, TtsError};
+    use log::info;
+    use serde::{Deserialize, Serialize};
+    use std::io::Read;
+    use std::path::Path;
+    use std::process::Stdio;
+    use webrtc_vad::{SampleRate, Vad, VadMode};
+
+    const VAD_RATE: u32 = 16_000;
+    const FRAME_MS: u32 = 30;
+    /// Паузы короче этого не разрывают речь, секунды
+    const MAX_GAP: f32 = 0.2;
+    /// Поля вокруг найденной речи, секунды
+    const PADDING: f32 = 0.1;
+    /// Более короткой речи не доверяем: VAD мог поймать только вдох
+    const MIN_SPEECH: f32 = 0.3;
+    const FRAME_LEN: usize = (VAD_RATE * FRAME_MS / 1000) as usize;
+
+    /// Пропуск участков без речи
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+    #[serde(default)]
+    pub struct VoiceActivity {
+        /// Не распознавать и не озвучивать длинные участки без речи
+        pub enabled: bool,
+        /// Участки без речи короче этого остаются обычными паузами, секунды
+        pub min_gap: f32,
+    }
+
+    impl Default for VoiceActivity {
+        fn default() -> Self {
+            Self { enabled: false, min_gap: 4.0 }
+        }
+    }
+
+    /// Добавляет кадр с номером `index` к промежуткам речи
+    fn add_frame(vad: &mut Vad, spans: &mut Vec<CueWindow>, index: usize, frame: &[i16]) -> Result<()> {
+        let voiced = vad
+            .is_voice_segment(frame)
+            .map_err(|_| TtsError::AudioProcessingError("Ошибка VAD: некорректный кадр".to_string()))?;
+        if voiced {
+            let frame_secs = FRAME_MS as f32 / 1000.0;
+            let start = index as f32 * frame_secs;
+            match spans.last_mut() {
+                Some(last) if start - last.end < MAX_GAP => last.end = start + frame_secs,
+                _ => spans.push(CueWindow { start, end: start + frame_secs }),
+            }
+        }
+        Ok(())
+    }
+
+    /// Промежутки речи в моно-сигнале, секунды
+    pub fn speech_spans(samples: &[f32], sample_rate: u32) -> Result<Vec<CueWindow>> {
+        let pcm = audio::resample(samples, 1, sample_rate, VAD_RATE)?;
+        let mut vad = Vad::new_with_rate_and_mode(SampleRate::Rate16kHz, VadMode::Aggressive);
+        let mut spans: Vec<CueWindow> = Vec::new();
+        for (i, frame) in pcm.chunks_exact(FRAME_LEN).enumerate() {
+            let frame: Vec<i16> = frame.iter().map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).collect();
+            add_frame(&mut vad, &mut spans, i, &frame)?;
+        }
+        Ok(spans)
+    }
+
+    /// Промежутки речи во всем аудиофайле, без выделения голоса. Файл читается
+    /// через ffmpeg по кадрам, так что многочасовая запись не занимает память.
+    pub async fn speech_in_file(audio_path: &Path) -> Result<Vec<CueWindow>> {
+        let name = audio_path.display().to_string();
+        let audio_path = audio_path.to_path_buf();
+        let spans = tokio::task::spawn_blocking(move || -> Result<Vec<CueWindow>> {
+            let mut child = std::process::Command::new(crate::utils::tools::ffmpeg())
+                .args(["-v", "error", "-i"])
+                .arg(&audio_path)
+                .args(["-vn", "-ac", "1", "-ar", &VAD_RATE.to_string(), "-f", "s16le", "-"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()?;
+            let mut stdout = child
+                .stdout
+                .take()
+                .ok_or_else(|| TtsError::AudioProcessingError("ffmpeg не отдал поток аудио".to_string()))?;
+            let mut vad = Vad::new_with_rate_and_mode(SampleRate::Rate16kHz, VadMode::Aggressive);
+            let mut spans = Vec::new();
+            let mut bytes = vec![0u8; FRAME_LEN * 2];
+            let mut index = 0;
+            loop {
+                match stdout.read_exact(&mut bytes) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                    Err(e) => return Err(e.into()),
+                }
+                let frame: Vec<i16> = bytes.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect();
+                add_frame(&mut vad, &mut spans, index, &frame)?;
+                index += 1;
+            }
+            if !child.wait()?.success() {
+                return Err(TtsError::AudioProcessingError(format!("ffmpeg не смог прочитать {}", audio_path.display())));
+            }
+            Ok(spans)
+        })
+        .await
+        .map_err(|e| TtsError::AudioProcessingError(format!("Поиск речи прерван: {}", e)))??;
+        info!("VAD: найдено {} промежутков речи в {}", spans.len(), name);
+        Ok(spans)
+    }
+
+    /// Промежутки речи в дорожке голоса, выделенной из `audio_path`
+    pub async fn speech_in_vocals(audio_path: &Path, cache: Option<&demucs::StemCache>) -> Result<Vec<CueWindow>> {
+        let temp_dir = tempfile::tempdir().map_err(TtsError::IoError)?;
+        let vocals_path = temp_dir.path().join("vocals.wav");
+        demucs::extract_vocals(audio_path, vocals_path.as_path(), cache).await?;
+        let (samples, sample_rate) = audio::decode_audio_file(&vocals_path)?;
+        let spans = speech_spans(&samples, sample_rate)?;
+        info!("VAD: найдено {} промежутков речи в дорожке голоса", spans.len());
+        Ok(spans)
+    }
+
+    /// Участки без речи не короче `min_gap`: перед первой речью, между речью и
+    /// после последней. Если речь не найдена вовсе, VAD ничего не понял, и
+    /// участков нет.
+    pub fn non_speech(spans: &[CueWindow], min_gap: f32) -> Vec<CueWindow> {
+        let (Some(first), Some(last)) = (spans.first(), spans.last()) else { return Vec::new() };
+        let mut gaps = vec![CueWindow { start: 0.0, end: first.start }];
+        gaps.extend(spans.windows(2).map(|pair| CueWindow { start: pair[0].end, end: pair[1].start }));
+        gaps.push(CueWindow { start: last.end, end: f32::INFINITY });
+        gaps.retain(|gap| gap.end - gap.start >= min_gap);
+        gaps
+    }
+
+    /// Окно реплики без участков без речи по ее краям, None если реплика
+    /// целиком лежит в таком участке. Участок посреди реплики ее не делит.
+    pub fn outside_non_speech(cue: &SubtitleCue, regions: &[CueWindow]) -> Option<CueWindow> {
+        let (mut start, mut end) = (cue.start, cue.end);
+        for region in regions {
+            if region.start <= start && region.end > start {
+                start = region.end;
+            }
+        }
+        for region in regions.iter().rev() {
+            if region.start < end && region.end >= end {
+                end = region.start;
+            }
+        }
+        (end - start >= MIN_SPEECH).then_some(CueWindow { start, end })
+    }
+
+    /// Окно фактической речи внутри реплики с полями, None если речь не найдена
+    pub fn speech_window(cue: &SubtitleCue, spans: &[CueWindow]) -> Option<CueWindow> {
+        let mut inside = spans.iter().filter(|span| span.start < cue.end && span.end > cue.start);
+        let first = inside.next()?;
+        let last = inside.next_back().unwrap_or(first);
+        let start = (first.start - PADDING).max(cue.start);
+        let end = (last.end + PADDING).min(cue.end);
+        (end - start >= MIN_SPEECH).then_some(CueWindow { start, end })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_speech_window_trims_cue_to_spoken_words() {
+            let cue = SubtitleCue { start: 1.0, end: 6.0, ..SubtitleCue::default() };
+            let spans = [
+                CueWindow { start: 0.2, end: 0.8 },
+                CueWindow { start: 1.5, end: 2.5 },
+                CueWindow { start: 3.0, end: 3.6 },
+                CueWindow { start: 7.0, end: 8.0 },
+            ];
+            let window = speech_window(&cue, &spans).unwrap();
+            assert!((window.start - 1.4).abs() < 1e-5 && (window.end - 3.7).abs() < 1e-5);
+
+            // Речь, начавшаяся до реплики, не выводит окно за ее пределы
+            let early = SubtitleCue { start: 0.5, end: 2.0, ..SubtitleCue::default() };
+            assert_eq!(speech_window(&early, &spans).unwrap().start, 0.5);
+            // Тишина или щелчок - остаются окна субтитров
+            assert!(speech_window(&SubtitleCue { start: 4.0, end: 6.5, ..SubtitleCue::default() }, &spans).is_none());
+        }
+
+        #[test]
+        fn test_cues_stay_out_of_music_only_passages() {
+            let spans = [CueWindow { start: 1.0, end: 5.0 }, CueWindow { start: 6.0, end: 8.0 }, CueWindow { start: 20.0, end: 24.0 }];
+            let regions = non_speech(&spans, 4.0);
+            assert_eq!(regions, vec![CueWindow { start: 8.0, end: 20.0 }, CueWindow { start: 24.0, end: f32::INFINITY }]);
+            assert!(non_speech(&[], 4.0).is_empty());
+
+            let cue = |start, end| SubtitleCue { start, end, ..SubtitleCue::default() };
+            assert_eq!(outside_non_speech(&cue(6.5, 10.0), &regions), Some(CueWindow { start: 6.5, end: 8.0 }));
+            assert_eq!(outside_non_speech(&cue(18.0, 22.0), &regions), Some(CueWindow { start: 20.0, end: 22.0 }));
+            assert_eq!(outside_non_speech(&cue(12.0, 15.0), &regions), None);
+        }
+    }
+}
+
+/// Модуль для работы с Demucs через командную строку
+pub mod demucs {
+    use super::{TtsError, Result};
+    use log::{info, warn, error};
+    use serde::{Deserialize, Serialize};
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use once_cell::sync::Lazy;
+    use std::sync::RwLock;
+    use futures::future::BoxFuture;
+    use tokio::sync::mpsc::Sender;
+
+    #[derive(Debug)]
+    pub enum DemucsSeparationProgress {
+        Started,
+        LoadingModel,
+        Processing { progress: f32 },
+        Converting,
+        Finished,
+        Error(String),
+    }
+
+    /// Устройство, на котором Demucs разделяет дорожки
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum ComputeDevice {
+        /// Выбор самого Demucs: CUDA, если она есть, иначе процессор
+        #[default]
+        Auto,
+        Cuda,
+        /// Apple Silicon
+        Mps,
+        Cpu,
+    }
+
+    impl ComputeDevice {
+        /// Значение `-d` для Demucs, None — не передавать
+        fn arg(self) -> Option<&'static str> {
+            match self {
+                ComputeDevice::Auto => None,
+                ComputeDevice::Cuda => Some("cuda"),
+                ComputeDevice::Mps => Some("mps"),
+                ComputeDevice::Cpu => Some("cpu"),
+            }
+        }
+
+        fn is_gpu(self) -> bool {
+            matches!(self, ComputeDevice::Cuda | ComputeDevice::Mps)
+        }
+
+        /// Сборка audio-separator под устройство: onnxruntime-gpu для CUDA,
+        /// обычный onnxruntime для остальных (на Apple Silicon он идет через CoreML)
+        fn audio_separator_package(self) -> &'static str {
+            match self {
+                ComputeDevice::Cuda => "audio-separator[gpu]",
+                _ => "audio-separator[cpu]",
+            }
+        }
+    }
+
+    /// Модель разделения голоса и фона
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum SeparationModel {
+        /// Demucs htdemucs: лучшее качество, медленный на процессоре
+        #[default]
+        Demucs,
+        /// ONNX-модель MDX-Net из UVR: в несколько раз быстрее на процессоре
+        MdxNet,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct DemucsConfig {
+        pub device: ComputeDevice,
+        pub model: SeparationModel,
+    }
+
+    static CONFIG: Lazy<RwLock<DemucsConfig>> = Lazy::new(|| RwLock::new(DemucsConfig::default()));
+
+    /// Настройки, с которыми запускается Demucs
+    pub fn current() -> DemucsConfig {
+        CONFIG.read().map(|config| config.clone()).unwrap_or_default()
+    }
+
+    pub fn apply(config: DemucsConfig) {
+        info!("Demucs работает на устройстве {:?}", config.device);
+        if let Ok(mut current) = CONFIG.write() {
+            *current = config;
+        }
+    }
+
+    /// Устройство и его доступность на этой машине
+    #[derive(Debug, Clone, Serialize)]
+    pub struct ComputeBackend {
+        pub device: ComputeDevice,
+        pub available: bool,
+        /// Название видеокарты, если PyTorch его сообщает
+        pub name: Option<String>,
+    }
+
+    const PROBE_SCRIPT: &str = r#"
+import json
+import torch
+cuda = torch.cuda.is_available()
+mps = getattr(torch.backends, "mps", None) is not None and torch.backends.mps.is_available()
+print(json.dumps({"cuda": cuda, "cuda_name": torch.cuda.get_device_name(0) if cuda else None, "mps": mps}))
+"#;
+
+    #[derive(Debug, Default, Deserialize)]
+    struct Probe {
+        cuda: bool,
+        cuda_name: Option<String>,
+        mps: bool,
+    }
+
+    fn backends_from_probe(probe: &Probe) -> Vec<ComputeBackend> {
+        vec![
+            ComputeBackend { device: ComputeDevice::Cuda, available: probe.cuda, name: probe.cuda_name.clone() },
+            ComputeBackend { device: ComputeDevice::Mps, available: probe.mps, name: None },
+            ComputeBackend { device: ComputeDevice::Cpu, available: true, name: None },
+        ]
+    }
+
+    /// Какие устройства PyTorch видит на этой машине. Без PyTorch доступен
+    /// только процессор.
+    pub async fn detect_backends() -> Vec<ComputeBackend> {
+        let output = tokio::process::Command::new("python3")
+            .args(["-c", PROBE_SCRIPT])
+            .output()
+            .await;
+        let probe = match output {
+            Ok(out) if out.status.success() => {
+                serde_json::from_slice(out.stdout.trim_ascii()).unwrap_or_else(|e| {
+                    warn!("Не удалось разобрать ответ проверки устройств: {}", e);
+                    Probe::default()
+                })
+            }
+            Ok(out) => {
+                warn!("PyTorch недоступен: {}", String::from_utf8_lossy(&out.stderr).trim());
+                Probe::default()
+            }
+            Err(e) => {
+                warn!("Не удалось запустить python3 для проверки устройств: {}", e);
+                Probe::default()
+            }
+        };
+        backends_from_probe(&probe)
+    }
+
+    /// Модель Demucs, которой разделяются дорожки
+    const MODEL: &str = "htdemucs";
+    /// Модель MDX-Net; audio-separator скачивает ее при первом запуске
+    const MDX_MODEL: &str = "UVR-MDX-NET-Inst_HQ_3.onnx";
+    const MANIFEST: &str = "stems.json";
+
+    /// Голос и фон, разделенные моделью
+    #[derive(Debug, Clone)]
+    pub struct SeparatedStems {
+        pub vocals: PathBuf,
+        pub instrumental: PathBuf,
+    }
+
+    impl SeparatedStems {
+        /// Файл дорожки по имени стема Demucs: `vocals` или `no_vocals`
+        fn path(&self, stem: &str) -> &Path {
+            if stem == "vocals" { &self.vocals } else { &self.instrumental }
+        }
+    }
+
+    /// Способ разделения аудио на голос и фон
+    pub trait SeparationBackend: Send + Sync {
+        /// Записывается в кэш: дорожки разных моделей не подменяют друг друга
+        fn id(&self) -> &'static str;
+
+        /// Разделяет `input_path`, файлы дорожек пишутся в `temp_dir`
+        fn separate<'a>(
+            &'a self,
+            input_path: &'a Path,
+            temp_dir: &'a Path,
+            progress_sender: &'a Option<Sender<DemucsSeparationProgress>>,
+        ) -> BoxFuture<'a, Result<SeparatedStems>>;
+    }
+
+    pub struct Demucs;
+
+    impl SeparationBackend for Demucs {
+        fn id(&self) -> &'static str {
+            MODEL
+        }
+
+        fn separate<'a>(
+            &'a self,
+            input_path: &'a Path,
+            temp_dir: &'a Path,
+            progress_sender: &'a Option<Sender<DemucsSeparationProgress>>,
+        ) -> BoxFuture<'a, Result<SeparatedStems>> {
+            Box::pin(run_demucs(input_path, temp_dir, progress_sender))
+        }
+    }
+
+    /// MDX-Net через audio-separator (onnxruntime)
+    pub struct MdxNet;
+
+    impl SeparationBackend for MdxNet {
+        fn id(&self) -> &'static str {
+            MDX_MODEL
+        }
+
+        fn separate<'a>(
+            &'a self,
+            input_path: &'a Path,
+            temp_dir: &'a Path,
+            progress_sender: &'a Option<Sender<DemucsSeparationProgress>>,
+        ) -> BoxFuture<'a, Result<SeparatedStems>> {
+            Box::pin(run_mdx(input_path, temp_dir, progress_sender))
+        }
+    }
+
+    /// Способ разделения выбранной модели
+    pub fn backend(model: SeparationModel) -> Box<dyn SeparationBackend> {
+        match model {
+            SeparationModel::Demucs => Box::new(Demucs),
+            SeparationModel::MdxNet => Box::new(MdxNet),
+        }
+    }
+    /// Каталог кэша рядом с временной папкой задачи
+    pub const STEMS_DIR: &str = "videonova_stems";
+
+    /// Описание записи кэша: по нему проверяется, что файлы дописаны и не подменены.
+    /// Исходник проверять незачем, ключ записи — хеш его содержимого
+    #[derive(Debug, Serialize, Deserialize)]
+    struct StemManifest {
+        model: String,
+        /// Размер каждой дорожки в байтах
+        stems: HashMap<String, u64>,
+    }
+
+    /// Кэш разделенных дорожек. Разделение дорогое, а нужно несколько раз на
+    /// одно и то же аудио: при повторных попытках, для каждого языка и для
+    /// подгонки под артикуляцию. Ключ — SHA-256 содержимого исходного аудио.
+    #[derive(Debug, Clone)]
+    pub struct StemCache {
+        root: PathBuf,
+    }
+
+    impl StemCache {
+        pub fn new(root: impl Into<PathBuf>) -> Self {
+            Self { root: root.into() }
         }
 
-        // Находим файл с инструментальной дорожкой
-        let input_filename = input_path.as_ref().file_stem()
-            .and_then(|s| s.to_str())
-            .ok_or_else(|| TtsError::AudioProcessingError("Некорректный путь к входному файлу".to_string()))?;
+        /// Кэш рядом с временной папкой `videonova_temp`, в которой лежит `path`;
+        /// рядом с самим файлом, если он не во временной папке
+        pub fn beside(path: &Path) -> Self {
+            let temp_dir = path.ancestors().find(|dir| dir.file_name().is_some_and(|name| name == "videonova_temp"));
+            let base = match temp_dir.and_then(Path::parent) {
+                Some(base) => base,
+                None => path.parent().unwrap_or_else(|| Path::new(".")),
+            };
+            Self::new(base.join(STEMS_DIR))
+        }
 
-        let instrumental_path = temp_dir.path()
-            .join("htdemucs")
-            .join(input_filename)
-            .join("no_vocals.mp3");
+        pub fn root(&self) -> &Path {
+            &self.root
+        }
 
-        if !instrumental_path.exists() {
-            let error_msg = "Не найден файл с инструментальной дорожкой после обработки Demucs".to_string();
-            error!("{}", error_msg);
-            send_progress(&progress_sender, DemucsSeparationProgress::Error(error_msg.clone())).await;
-            return Err(TtsError::AudioProcessingError(error_msg));
+        /// Ключ аудиофайла: SHA-256 его содержимого. Файл читается блоками,
+        /// многочасовое аудио не загружается в память целиком
+        pub async fn key(input_path: &Path) -> Result<String> {
+            use tokio::io::AsyncReadExt;
+            let mut file = tokio::fs::File::open(input_path).await.map_err(TtsError::IoError)?;
+            let mut hasher = Sha256::new();
+            let mut block = vec![0u8; 1 << 20];
+            loop {
+                let read = file.read(&mut block).await.map_err(TtsError::IoError)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&block[..read]);
+            }
+            Ok(hex::encode(hasher.finalize()))
+        }
+
+        /// Дорожка `stem` из кэша, если запись цела и сделана моделью `model`
+        pub async fn lookup(&self, key: &str, model: &str, stem: &str) -> Option<PathBuf> {
+            let dir = self.root.join(key);
+            let manifest = tokio::fs::read_to_string(dir.join(MANIFEST)).await.ok()?;
+            let manifest: StemManifest = serde_json::from_str(&manifest).ok()?;
+            if manifest.model != model {
+                return None;
+            }
+            let path = dir.join(format!("{}.mp3", stem));
+            let size = tokio::fs::metadata(&path).await.ok()?.len();
+            match manifest.stems.get(stem) {
+                Some(expected) if *expected == size && size > 0 => Some(path),
+                _ => {
+                    warn!("Запись кэша дорожек {} повреждена, разделяем заново", key);
+                    None
+                }
+            }
+        }
+
+        /// Сохраняет дорожки, разделенные моделью `model`
+        pub async fn store(&self, key: &str, model: &str, separated: &SeparatedStems) -> Result<()> {
+            let dir = self.root.join(key);
+            tokio::fs::create_dir_all(&dir).await.map_err(TtsError::IoError)?;
+            // Запись другой модели перестает считаться готовой, пока файлы заменяются
+            let _ = tokio::fs::remove_file(dir.join(MANIFEST)).await;
+            let mut stems = HashMap::new();
+            for stem in ["vocals", "no_vocals"] {
+                let target = dir.join(format!("{}.mp3", stem));
+                let size = tokio::fs::copy(separated.path(stem), target).await.map_err(TtsError::IoError)?;
+                stems.insert(stem.to_string(), size);
+            }
+            let manifest = StemManifest { model: model.to_string(), stems };
+            let json = serde_json::to_string_pretty(&manifest)
+                .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка записи кэша дорожек: {}", e)))?;
+            // Описание пишется последним: запись без него не считается готовой
+            tokio::fs::write(dir.join(MANIFEST), json).await.map_err(TtsError::IoError)?;
+            info!("Дорожки сохранены в кэш: {}", dir.display());
+            Ok(())
         }
+    }
+
+    /// Удаляет вокал из аудиофайла с помощью Demucs
+    pub async fn remove_vocals<P: AsRef<Path>>(
+        input_path: P,
+        output_path: P,
+        progress_sender: Option<Sender<DemucsSeparationProgress>>,
+        debug_dir: Option<P>,
+        cache: Option<&StemCache>,
+    ) -> Result<()> {
+        separate(input_path.as_ref(), output_path.as_ref(), "no_vocals", progress_sender, debug_dir.as_ref().map(|dir| dir.as_ref()), cache).await
+    }
+
+    /// Выделяет из аудиофайла только голос (например, как образец для клонирования)
+    pub async fn extract_vocals<P: AsRef<Path>>(input_path: P, output_path: P, cache: Option<&StemCache>) -> Result<()> {
+        separate(input_path.as_ref(), output_path.as_ref(), "vocals", None, None, cache).await
+    }
+
+    /// Разделяет файл на голос и остальное и сохраняет дорожку `stem` в WAV.
+    /// С кэшем Demucs запускается, только если дорожек этого аудио в нем нет.
+    async fn separate(
+        input_path: &Path,
+        output_path: &Path,
+        stem: &str,
+        progress_sender: Option<Sender<DemucsSeparationProgress>>,
+        debug_dir: Option<&Path>,
+        cache: Option<&StemCache>,
+    ) -> Result<()> {
+        let key = match cache {
+            Some(_) => match StemCache::key(input_path).await {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    warn!("Не удалось вычислить ключ кэша дорожек: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let backend = backend(current().model);
+        let cached = match (cache, &key) {
+            (Some(cache), Some(key)) => cache.lookup(key, backend.id(), stem).await,
+            _ => None,
+        };
+
+        // Временная директория нужна до конца конвертации
+        let temp_dir = tempfile::tempdir()
+            .map_err(TtsError::IoError)?;
+
+        let stem_path = match cached {
+            Some(path) => {
+                info!("Дорожка {} взята из кэша: {}", stem, path.display());
+                send_progress(&progress_sender, DemucsSeparationProgress::Started).await;
+                path
+            }
+            None => {
+                let separated = backend.separate(input_path, temp_dir.path(), &progress_sender).await?;
+                if let (Some(cache), Some(key)) = (cache, &key)
+                    && let Err(e) = cache.store(key, backend.id(), &separated).await
+                {
+                    warn!("Не удалось сохранить дорожки в кэш: {}", e);
+                }
+                separated.path(stem).to_path_buf()
+            }
+        };
 
         // Отправляем статус конвертации
         send_progress(&progress_sender, DemucsSeparationProgress::Converting).await;
 
         // Конвертируем результат в нужный формат с помощью FFmpeg
-        let output = tokio::process::Command::new("ffmpeg")
-            .args(&[
+        let output = tokio::process::Command::new(crate::utils::tools::ffmpeg())
+            .args([
                 "-y",                     // Перезаписывать выходной файл
-                "-i", instrumental_path.to_str().unwrap(),
+                "-i", stem_path.to_str().unwrap(),
                 "-acodec", "pcm_s16le",   // 16-bit PCM
                 "-ar", "44100",           // 44.1 кГц
-                output_path.as_ref().to_str().unwrap()
+                output_path.to_str().unwrap()
             ])
             .output()
             .await
@@ -613,19 +3045,284 @@ pub mod demucs {
 
         // Если указана debug_dir, сохраняем копию для отладки
         if let Some(debug_dir) = debug_dir {
-            let debug_path = Path::new(debug_dir.as_ref()).join("instrumental_debug.wav");
-            if let Err(e) = tokio::fs::copy(output_path.as_ref(), &debug_path).await {
+            let debug_path = debug_dir.join("instrumental_debug.wav");
+            if let Err(e) = tokio::fs::copy(output_path, &debug_path).await {
                 warn!("Не удалось создать отладочную копию инструментальной дорожки: {}", e);
             } else {
                 info!("Создана отладочная копия инструментальной дорожки: {}", debug_path.display());
             }
         }
 
-        info!("Вокал успешно удален с помощью Demucs: {}", output_path.as_ref().display());
+        info!("Дорожка {} выделена с помощью {}: {}", stem, backend.id(), output_path.display());
         send_progress(&progress_sender, DemucsSeparationProgress::Finished).await;
         Ok(())
     }
 
+    /// Запускает Demucs и возвращает пути к MP3 дорожек в `temp_dir`
+    async fn run_demucs(
+        input_path: &Path,
+        temp_dir: &Path,
+        progress_sender: &Option<Sender<DemucsSeparationProgress>>,
+    ) -> Result<SeparatedStems> {
+        // Проверяем установку Demucs
+        ensure_demucs_installed().await?;
+
+        info!("Разделение голоса с помощью Demucs: {}", input_path.display());
+        
+        // Отправляем статус начала работы
+        send_progress(progress_sender, DemucsSeparationProgress::Started).await;
+
+        // Отправляем статус загрузки модели
+        send_progress(progress_sender, DemucsSeparationProgress::LoadingModel).await;
+
+        let device = current().device;
+        if let Err(e) = demucs_on_device(input_path, temp_dir, device, progress_sender).await {
+            // Выбранная видеокарта может быть недоступна (нет CUDA, старый драйвер):
+            // такое разделение повторяется на процессоре, а не срывает озвучку
+            if !device.is_gpu() {
+                send_progress(progress_sender, DemucsSeparationProgress::Error(e.to_string())).await;
+                return Err(e);
+            }
+            warn!("Demucs не смог работать на {:?} ({}), повторяем на процессоре", device, e);
+            if let Err(e) = demucs_on_device(input_path, temp_dir, ComputeDevice::Cpu, progress_sender).await {
+                send_progress(progress_sender, DemucsSeparationProgress::Error(e.to_string())).await;
+                return Err(e);
+            }
+        }
+
+        // Находим файл с инструментальной дорожкой
+        let input_filename = input_path.file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| TtsError::AudioProcessingError("Некорректный путь к входному файлу".to_string()))?;
+
+        let separated_dir = temp_dir.join(MODEL).join(input_filename);
+        let stems = SeparatedStems {
+            vocals: separated_dir.join("vocals.mp3"),
+            instrumental: separated_dir.join("no_vocals.mp3"),
+        };
+
+        for path in [&stems.vocals, &stems.instrumental] {
+            if !path.exists() {
+                let error_msg = format!("Не найден файл {} после обработки Demucs", path.display());
+                error!("{}", error_msg);
+                send_progress(progress_sender, DemucsSeparationProgress::Error(error_msg.clone())).await;
+                return Err(TtsError::AudioProcessingError(error_msg));
+            }
+        }
+
+        Ok(stems)
+    }
+
+    /// Разделяет дорожки моделью MDX-Net. audio-separator называет файлы
+    /// `<имя>_(Vocals)_<модель>.mp3` и `<имя>_(Instrumental)_<модель>.mp3`.
+    async fn run_mdx(
+        input_path: &Path,
+        temp_dir: &Path,
+        progress_sender: &Option<Sender<DemucsSeparationProgress>>,
+    ) -> Result<SeparatedStems> {
+        let device = current().device;
+        ensure_audio_separator_installed(device).await?;
+
+        info!("Разделение голоса с помощью MDX-Net: {}", input_path.display());
+        send_progress(progress_sender, DemucsSeparationProgress::Started).await;
+        send_progress(progress_sender, DemucsSeparationProgress::LoadingModel).await;
+
+        if let Err(e) = mdx_on_device(input_path, temp_dir, device, progress_sender).await {
+            // Как и у Demucs, недоступная видеокарта не срывает озвучку
+            if !device.is_gpu() {
+                send_progress(progress_sender, DemucsSeparationProgress::Error(e.to_string())).await;
+                return Err(e);
+            }
+            warn!("audio-separator не смог работать на {:?} ({}), повторяем на процессоре", device, e);
+            if let Err(e) = mdx_on_device(input_path, temp_dir, ComputeDevice::Cpu, progress_sender).await {
+                send_progress(progress_sender, DemucsSeparationProgress::Error(e.to_string())).await;
+                return Err(e);
+            }
+        }
+
+        let find = |marker: &str| -> Option<PathBuf> {
+            std::fs::read_dir(temp_dir).ok()?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .find(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.contains(marker)))
+        };
+        match (find("(Vocals)"), find("(Instrumental)")) {
+            (Some(vocals), Some(instrumental)) => Ok(SeparatedStems { vocals, instrumental }),
+            _ => {
+                let error_msg = "Не найдены дорожки после обработки MDX-Net".to_string();
+                error!("{}", error_msg);
+                send_progress(progress_sender, DemucsSeparationProgress::Error(error_msg.clone())).await;
+                Err(TtsError::AudioProcessingError(error_msg))
+            }
+        }
+    }
+
+    /// Один запуск audio-separator на устройстве `device`. Своего флага
+    /// устройства у него нет: CUDA и MPS он находит сам, поэтому процессор
+    /// выбирается скрытием видеокарт.
+    async fn mdx_on_device(
+        input_path: &Path,
+        temp_dir: &Path,
+        device: ComputeDevice,
+        progress_sender: &Option<Sender<DemucsSeparationProgress>>,
+    ) -> Result<()> {
+        let mut command = tokio::process::Command::new("audio-separator");
+        command
+            .arg(input_path)
+            .args(["--model_filename", MDX_MODEL, "--output_format", "MP3", "--output_dir"])
+            .arg(temp_dir);
+        match device {
+            ComputeDevice::Cpu => {
+                command.env("CUDA_VISIBLE_DEVICES", "");
+            }
+            ComputeDevice::Mps => {
+                command.env("PYTORCH_ENABLE_MPS_FALLBACK", "1");
+            }
+            ComputeDevice::Auto | ComputeDevice::Cuda => {}
+        }
+        let mut child = command
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка запуска audio-separator: {}", e)))?;
+
+        // tqdm пишет прогресс в stderr строками вида " 45%|████ | 9/20"
+        let stderr = child.stderr.take().unwrap();
+        let progress_sender_clone = progress_sender.clone();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncBufReadExt, BufReader};
+            let mut lines = BufReader::new(stderr).split(b'\r');
+            while let Ok(Some(line)) = lines.next_segment().await {
+                let line = String::from_utf8_lossy(&line);
+                if let Some(progress) = parse_tqdm_progress(&line) {
+                    send_progress(&progress_sender_clone, DemucsSeparationProgress::Processing { progress }).await;
+                }
+                info!("audio-separator output: {}", line.trim());
+            }
+        });
+
+        let status = child.wait()
+            .await
+            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка выполнения audio-separator: {}", e)))?;
+        if !status.success() {
+            let error_msg = format!("audio-separator завершился с ошибкой: {}", status);
+            error!("{}", error_msg);
+            return Err(TtsError::AudioProcessingError(error_msg));
+        }
+        Ok(())
+    }
+
+    /// Доля из строки прогресса tqdm, например " 45%|████ | 9/20"
+    fn parse_tqdm_progress(line: &str) -> Option<f32> {
+        let (percent, _) = line.split_once("%|")?;
+        let value = percent.rsplit(|c: char| !c.is_ascii_digit()).next()?.parse::<f32>().ok()?;
+        Some(value / 100.0)
+    }
+
+    /// Устанавливает audio-separator с onnxruntime для MDX-Net под устройство
+    /// `device`, если его нет. Для CUDA нужен еще и onnxruntime-gpu.
+    pub async fn ensure_audio_separator_installed(device: ComputeDevice) -> Result<()> {
+        let mut required = vec!["audio-separator"];
+        if device == ComputeDevice::Cuda {
+            required.push("onnxruntime-gpu");
+        }
+        let installed = tokio::process::Command::new("pip")
+            .arg("show")
+            .args(&required)
+            .output()
+            .await
+            .is_ok_and(|out| out.status.success());
+        if installed {
+            return Ok(());
+        }
+
+        let package = device.audio_separator_package();
+        info!("{} не установлен, начинаем установку...", package);
+        let output = tokio::process::Command::new("pip")
+            .args(["install", package])
+            .output()
+            .await
+            .map_err(|e| TtsError::Other(anyhow::anyhow!("Ошибка установки audio-separator: {}", e)))?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(TtsError::Other(anyhow::anyhow!("Не удалось установить audio-separator: {}", error)));
+        }
+        info!("audio-separator успешно установлен");
+        Ok(())
+    }
+
+    /// Один запуск Demucs на устройстве `device`
+    async fn demucs_on_device(
+        input_path: &Path,
+        temp_dir: &Path,
+        device: ComputeDevice,
+        progress_sender: &Option<Sender<DemucsSeparationProgress>>,
+    ) -> Result<()> {
+        // Создаем канал для передачи прогресса из потока чтения вывода
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel(32);
+        let progress_sender_clone = progress_sender.clone();
+
+        // Запускаем Demucs с выводом прогресса
+        let mut command = tokio::process::Command::new("demucs");
+        command.args([
+            "--two-stems=vocals",  // Разделяем только на вокал и остальное
+            "-n", MODEL,           // Используем лучшую модель
+            "--mp3",               // Выходной формат MP3 для экономии места
+            "-o", temp_dir.to_str().unwrap(),
+        ]);
+        if let Some(device) = device.arg() {
+            command.args(["-d", device]);
+        }
+        if device == ComputeDevice::Mps {
+            // Операции, которых нет в MPS, выполняются на процессоре
+            command.env("PYTORCH_ENABLE_MPS_FALLBACK", "1");
+        }
+        let mut child = command
+            .arg(input_path)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка запуска Demucs: {}", e)))?;
+
+        // Читаем вывод в реальном времени для отслеживания прогресса
+        let stderr = child.stderr.take().unwrap();
+        
+        // Запускаем задачу для чтения вывода
+        tokio::spawn(async move {
+            use tokio::io::{BufReader, AsyncBufReadExt};
+            let reader = BufReader::new(stderr);
+            let mut lines = reader.lines();
+            
+            while let Ok(Some(line)) = lines.next_line().await {
+                // Анализируем вывод Demucs для определения прогресса
+                if let Some(progress) = parse_demucs_progress(&line) {
+                    let _ = progress_tx.send(progress).await;
+                }
+                // Логируем все строки для отладки
+                info!("Demucs output: {}", line);
+            }
+        });
+
+        // Обрабатываем прогресс
+        tokio::spawn(async move {
+            while let Some(progress) = progress_rx.recv().await {
+                send_progress(&progress_sender_clone, 
+                    DemucsSeparationProgress::Processing { progress }).await;
+            }
+        });
+
+        // Ждем завершения процесса
+        let status = child.wait()
+            .await
+            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка выполнения Demucs: {}", e)))?;
+
+        if !status.success() {
+            let error_msg = format!("Demucs завершился с ошибкой: {}", status);
+            error!("{}", error_msg);
+            return Err(TtsError::AudioProcessingError(error_msg));
+        }
+        Ok(())
+    }
+
     // Вспомогательная функция для отправки прогресса
     async fn send_progress(sender: &Option<Sender<DemucsSeparationProgress>>, progress: DemucsSeparationProgress) {
         if let Some(tx) = sender {
@@ -636,12 +3333,11 @@ pub mod demucs {
     // Функция для парсинга вывода Demucs и определения прогресса
     fn parse_demucs_progress(line: &str) -> Option<f32> {
         // Пример строки: "Processing: 45%"
-        if let Some(pos) = line.find("Processing:") {
-            if let Some(percent) = line[pos..].split('%').next() {
-                if let Ok(value) = percent.trim_start_matches("Processing:").trim().parse::<f32>() {
-                    return Some(value / 100.0);
-                }
-            }
+        if let Some(pos) = line.find("Processing:")
+            && let Some(percent) = line[pos..].split('%').next()
+            && let Ok(value) = percent.trim_start_matches("Processing:").trim().parse::<f32>()
+        {
+            return Some(value / 100.0);
         }
         None
     }
@@ -649,7 +3345,7 @@ pub mod demucs {
     /// Проверяет, установлен ли Demucs через pip
     pub async fn is_demucs_installed() -> bool {
         let output = tokio::process::Command::new("pip")
-            .args(&["show", "demucs"])
+            .args(["show", "demucs"])
             .output()
             .await;
             
@@ -666,7 +3362,7 @@ pub mod demucs {
         // Обновляем pip до последней версии
         info!("Обновление pip...");
         let output = tokio::process::Command::new("pip")
-            .args(&["install", "--upgrade", "pip"])
+            .args(["install", "--upgrade", "pip"])
             .output()
             .await
             .map_err(|e| TtsError::Other(anyhow::anyhow!("Ошибка обновления pip: {}", e)))?;
@@ -698,7 +3394,7 @@ pub mod demucs {
         for dep in base_deps.iter() {
             info!("Установка зависимости: {}", dep);
             let output = tokio::process::Command::new("pip")
-                .args(&["install", "--verbose", dep])
+                .args(["install", "--verbose", dep])
                 .output()
                 .await
                 .map_err(|e| TtsError::Other(anyhow::anyhow!("Ошибка установки {}: {}", dep, e)))?;
@@ -713,7 +3409,7 @@ pub mod demucs {
         // Устанавливаем pyAudioAnalysis
         info!("Установка pyAudioAnalysis...");
         let output = tokio::process::Command::new("pip")
-            .args(&[
+            .args([
                 "install",
                 "--verbose",
                 "--no-cache-dir",  // Игнорируем кэш pip
@@ -733,7 +3429,7 @@ pub mod demucs {
         // Проверяем установку pyAudioAnalysis
         info!("Проверка установки pyAudioAnalysis...");
         let output = tokio::process::Command::new("python3")
-            .args(&[
+            .args([
                 "-c",
                 "from pyAudioAnalysis import ShortTermFeatures; print('pyAudioAnalysis успешно импортирован')"
             ])
@@ -750,7 +3446,7 @@ pub mod demucs {
         // Устанавливаем Demucs
         info!("Установка Demucs...");
         let output = tokio::process::Command::new("pip")
-            .args(&[
+            .args([
                 "install",
                 "--verbose",
                 "demucs==4.0.1"
@@ -778,12 +3474,68 @@ pub mod demucs {
         }
         Ok(())
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_stem_cache_serves_only_complete_entries() {
+            let work = tempfile::tempdir().unwrap();
+            let temp = work.path().join("videonova_temp").join("tts");
+            std::fs::create_dir_all(&temp).unwrap();
+            let cache = StemCache::beside(&temp.join("talk_tts.wav"));
+            assert_eq!(cache.root(), work.path().join(STEMS_DIR));
+
+            let source = work.path().join("talk.wav");
+            std::fs::write(&source, b"original audio").unwrap();
+            let separated = work.path().join("separated");
+            std::fs::create_dir_all(&separated).unwrap();
+            std::fs::write(separated.join("vocals.mp3"), b"voice").unwrap();
+            std::fs::write(separated.join("no_vocals.mp3"), b"music").unwrap();
+
+            let stems = SeparatedStems {
+                vocals: separated.join("vocals.mp3"),
+                instrumental: separated.join("no_vocals.mp3"),
+            };
+
+            let key = StemCache::key(&source).await.unwrap();
+            assert!(cache.lookup(&key, MODEL, "vocals").await.is_none());
+            cache.store(&key, MODEL, &stems).await.unwrap();
+            assert_eq!(key, hex::encode(Sha256::digest(b"original audio")));
+            assert!(cache.lookup(&key, MODEL, "vocals").await.is_some());
+            // Дорожки другой модели не подставляются
+            assert!(cache.lookup(&key, MDX_MODEL, "vocals").await.is_none());
+
+            // Оборванная копия не выдается за готовую дорожку
+            std::fs::write(cache.root().join(&key).join("no_vocals.mp3"), b"mu").unwrap();
+            assert!(cache.lookup(&key, MODEL, "no_vocals").await.is_none());
+        }
+
+        #[test]
+        fn test_cpu_is_always_available() {
+            let probe: Probe = serde_json::from_str(r#"{"cuda": false, "cuda_name": null, "mps": true}"#).unwrap();
+            let backends = backends_from_probe(&probe);
+            let available: Vec<_> = backends.iter().filter(|b| b.available).map(|b| b.device).collect();
+            assert_eq!(available, vec![ComputeDevice::Mps, ComputeDevice::Cpu]);
+            assert!(backends_from_probe(&Probe::default()).iter().any(|b| b.device == ComputeDevice::Cpu && b.available));
+            assert_eq!(serde_json::from_str::<DemucsConfig>("{}").unwrap().device, ComputeDevice::Auto);
+        }
+
+        #[test]
+        fn test_parse_tqdm_progress() {
+            assert_eq!(parse_tqdm_progress(" 45%|████▌     | 9/20 [00:04<00:05]"), Some(0.45));
+            assert_eq!(parse_tqdm_progress("100%|██████████| 20/20"), Some(1.0));
+            assert_eq!(parse_tqdm_progress("Loading model UVR-MDX-NET"), None);
+        }
+    }
 }
 
 /// Модуль для аудио-обработки: декодирование, time-stretching, анализ громкости и кодирование.
 pub mod audio {
-    use super::{Result, TtsError, AudioProcessingConfig};
-    use rubato::{SincFixedIn, FftFixedIn, Resampler};
+    use super::{loudness, Result, TtsError, AudioProcessingConfig};
+    use crate::utils::audio_probe::AudioFormat;
+    use rubato::{FftFixedIn, Resampler};
     use log::{info, warn, error, debug};
     use std::path::Path;
     use tokio::sync::mpsc::Sender;
@@ -807,11 +3559,11 @@ pub mod audio {
         
         // Создаем временный файл для MP3-данных
         let mut temp_file = tempfile::NamedTempFile::new()
-            .map_err(|e| TtsError::IoError(e))?;
+            .map_err(TtsError::IoError)?;
         
         // Записываем MP3 данные во временный файл
         std::io::Write::write_all(&mut temp_file, data)
-            .map_err(|e| TtsError::IoError(e))?;
+            .map_err(TtsError::IoError)?;
         
         // Получаем путь к временному файлу
         let temp_path = temp_file.path();
@@ -850,13 +3602,13 @@ pub mod audio {
         let temp_wav = tempfile::Builder::new()
             .suffix(".wav")
             .tempfile()
-            .map_err(|e| TtsError::IoError(e))?;
+            .map_err(TtsError::IoError)?;
         let temp_wav_path = temp_wav.path().to_str()
             .ok_or_else(|| TtsError::AudioProcessingError("Не удалось получить путь к временному файлу".to_string()))?;
         
         // Конвертируем аудио в WAV с помощью ffmpeg с улучшенными параметрами
-        let output = Command::new("ffmpeg")
-            .args(&[
+        let output = Command::new(crate::utils::tools::ffmpeg())
+            .args([
                 "-v", "warning",          // Уровень логирования
                 "-stats",                 // Показывать прогресс
                 "-i", path.as_ref().to_str().unwrap(),
@@ -879,7 +3631,7 @@ pub mod audio {
         
         // Проверяем размер полученного WAV файла
         let metadata = std::fs::metadata(temp_wav_path)
-            .map_err(|e| TtsError::IoError(e))?;
+            .map_err(TtsError::IoError)?;
         
         if metadata.len() < 44 {
             error!("Слишком маленький WAV файл после декодирования {} (размер: {} байт)", path.as_ref().display(), metadata.len());
@@ -939,24 +3691,46 @@ pub mod audio {
 
     /// Кодирует вектор f32-сэмплов (моно) в WAV-формат.
     pub fn encode_wav(samples: &[f32], sample_rate: u32, output_path: &str) -> Result<()> {
+        encode_wav_channels(samples, sample_rate, 1, output_path)
+    }
+
+    /// Кодирует f32-сэмплы с заданным числом каналов (для стерео - чередующиеся L/R) в WAV-формат.
+    pub fn encode_wav_channels(samples: &[f32], sample_rate: u32, channels: u16, output_path: &str) -> Result<()> {
         let spec = hound::WavSpec {
-            channels: 1,
+            channels,
             sample_rate,
             bits_per_sample: 16,
             sample_format: hound::SampleFormat::Int,
         };
         let mut writer = hound::WavWriter::create(output_path, spec)
-            .map_err(|e| TtsError::WavEncodingError(e))?;
+            .map_err(TtsError::WavEncodingError)?;
         for &sample in samples {
             let s = (sample * i16::MAX as f32) as i16;
             writer.write_sample(s)
-                .map_err(|e| TtsError::WavEncodingError(e))?;
+                .map_err(TtsError::WavEncodingError)?;
         }
         writer.finalize()
-            .map_err(|e| TtsError::WavEncodingError(e))?;
+            .map_err(TtsError::WavEncodingError)?;
         Ok(())
     }
 
+    /// Кодирует моно f32-сэмплы в 16-битный WAV в памяти.
+    pub fn encode_wav_bytes(samples: &[f32], sample_rate: u32) -> Result<Vec<u8>> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut cursor, spec)?;
+        for &sample in samples {
+            writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+        }
+        writer.finalize()?;
+        Ok(cursor.into_inner())
+    }
+
     /// Вычисляет длительность аудио по количеству сэмплов и частоте дискретизации.
     pub fn duration_in_seconds(num_samples: usize, sample_rate: u32) -> f32 {
         num_samples as f32 / sample_rate as f32
@@ -964,12 +3738,12 @@ pub mod audio {
 
     /// Применяет time-stretching к аудио для корректировки длительности.
     ///
-    /// Если actual_duration > target_duration, вычисляется коэффициент ускорения:
-    /// speed_factor = actual_duration / target_duration (с ограничением сверху),
-    /// затем SoundTouch обрабатывает аудио чтобы итоговая длительность приблизилась к target_duration,
-    /// сохраняя при этом высоту тона.
-    ///
-    /// Если actual_duration < target_duration, просто добавляем тишину.
+    /// Коэффициент темпа speed_factor = actual_duration / target_duration ограничивается
+    /// `config.min_tempo..=config.max_tempo` (см. [`fit_tempo`]), затем WSOLA меняет
+    /// длительность, сохраняя высоту тона (если `config.stretch` не отдает часть изменения rate).
+    /// Речь короче окна замедляется в этих пределах и дополняется тишиной. Речь, которой
+    /// не хватает и максимального ускорения, не обрезается: возвращенное использованное
+    /// время больше окна, и последовательная сборка таймлайна сдвигает следующие реплики.
     pub fn adjust_duration(
         input: &[f32],
         actual_duration: f32,
@@ -1015,7 +3789,7 @@ pub mod audio {
         }
 
         // Если аудио слишком длинное, пробуем использовать дополнительное время
-        if actual_duration > target_duration {
+        let extended_target = if actual_duration > target_duration {
             // Вычисляем, сколько дополнительного времени мы можем использовать
             // Оставляем минимум 1 секунду до следующего cue
             let extra_time_to_use = if available_extra_time > 1.0 {
@@ -1025,124 +3799,86 @@ pub mod audio {
             } else {
                 0.0
             };
+            info!("Используем дополнительное время: {:.3}s, новая целевая длительность: {:.3}s",
+                  extra_time_to_use, target_duration + extra_time_to_use);
+            target_duration + extra_time_to_use
+        } else {
+            target_duration
+        };
 
-            let extended_target = target_duration + extra_time_to_use;
-            let speed_factor = actual_duration / extended_target;
+        let speed_factor = fit_tempo(actual_duration, extended_target, config.min_tempo, config.max_tempo);
+        // Речь, не уложившаяся даже с максимальным ускорением, не обрезается:
+        // фрагмент выходит за окно, и таймлайн сдвигает следующие реплики
+        let stretched_duration = actual_duration / speed_factor;
+        if stretched_duration > extended_target + 0.01 {
+            warn!("Требуется ускорение {:.2}, ограничиваем до {:.2}: фрагмент длиннее окна на {:.3}s, следующие реплики сдвинутся",
+                  actual_duration / extended_target, speed_factor, stretched_duration - extended_target);
+        }
+        let used_duration = stretched_duration.max(extended_target);
+        let used_samples = (used_duration * sample_rate as f32).round() as usize;
 
-            info!("Используем дополнительное время: {:.3}s, новая целевая длительность: {:.3}s, коэффициент ускорения: {:.3}", 
-                  extra_time_to_use, extended_target, speed_factor);
+        // Незначительное изменение темпа не стоит обработки
+        if (speed_factor - 1.0).abs() < 0.01 {
+            return Ok((fit_length(input.to_vec(), used_samples), used_duration));
+        }
 
-            // Защита от слишком агрессивного ускорения - ограничиваем до 2.0 для лучшей разборчивости
-            let adjusted_speed_factor = if speed_factor > 2.0 {
-                warn!("Очень высокий коэффициент ускорения ({:.2}), ограничиваем до 2.0", speed_factor);
-                2.0
-            } else {
-                speed_factor
-            };
+        info!("Изменение темпа фрагмента: коэффициент {:.3}, {:.3}s -> {:.3}s", speed_factor, actual_duration, stretched_duration);
+        let processed = stretch_samples(input, sample_rate, speed_factor, config)?;
+        let result_duration = processed.len() as f32 / sample_rate as f32;
+        if (result_duration - stretched_duration).abs() > 0.1 {
+            warn!("Результат обработки имеет неожиданную длительность: {:.3}s вместо {:.3}s",
+                  result_duration, stretched_duration);
+        }
+        Ok((fit_length(processed, used_samples), used_duration))
+    }
 
-            // Используем SoundTouch для изменения скорости с сохранением высоты тона
-            match super::soundtouch::process_with_soundtouch(input, sample_rate, adjusted_speed_factor) {
-                Ok(processed) => {
-                    info!("Итоговое аудио после изменения скорости с сохранением тона через SoundTouch: {} сэмплов, длительность ~{:.3}s",
-                          processed.len(), processed.len() as f32 / sample_rate as f32);
-                    
-                    // Проверим что результат не пустой
-                    if processed.is_empty() {
-                        warn!("SoundTouch вернул пустой результат! Используем оригинальное аудио с обрезкой.");
-                        let target_samples = (extended_target * sample_rate as f32).round() as usize;
-                        return Ok((input.iter().take(target_samples).cloned().collect(), extended_target));
-                    }
-                    
-                    // Проверяем, что длительность соответствует ожидаемой
-                    let result_duration = processed.len() as f32 / sample_rate as f32;
-                    let target_samples = (extended_target * sample_rate as f32).round() as usize;
-                    
-                    if (result_duration - extended_target).abs() > 0.1 {
-                        warn!("Результат обработки имеет неожиданную длительность: {:.3}s вместо {:.3}s", 
-                              result_duration, extended_target);
-                        
-                        // Корректируем длину, если нужно
-                        let mut adjusted_result = processed;
-                        if adjusted_result.len() > target_samples {
-                            adjusted_result.truncate(target_samples);
-                        } else if adjusted_result.len() < target_samples {
-                            adjusted_result.extend(vec![0.0; target_samples - adjusted_result.len()]);
-                        }
-                        
-                        Ok((adjusted_result, extended_target))
-                    } else {
-                        Ok((processed, extended_target))
-                    }
-                },
-                Err(e) => {
-                    error!("Ошибка при обработке аудио через SoundTouch: {}", e);
-                    
-                    // Предлагаем альтернативу в случае ошибки - попробуем использовать Rubato
-                    warn!("Пробуем использовать резервный метод time-stretching (Rubato FFT)");
-                    
-                    // Используем FftFixedIn для изменения длительности с сохранением высоты тона
-                    let mut resampler = match rubato::FftFixedIn::<f32>::new(
-                        sample_rate as usize,                 // Исходная частота дискретизации
-                        (sample_rate as f64 / adjusted_speed_factor as f64) as usize, // Целевая частота дискретизации
-                        input.len(),                          // Размер входного буфера
-                        config.window_size / config.hop_size, // Количество подблоков для обработки
-                        1                                     // Количество каналов (моно)
-                    ) {
-                        Ok(r) => r,
-                        Err(e) => {
-                            error!("Ошибка создания FFT-ресемплера: {}", e);
-                            return Err(TtsError::TimeStretchingError(format!("Ошибка создания FFT-ресемплера: {}", e)));
-                        }
-                    };
-                    
-                    // Подготавливаем входные данные
-                    let input_frames = vec![input.to_vec()];
-                    
-                    // Обрабатываем аудио с сохранением высоты тона
-                    let output_frames = match resampler.process(&input_frames, None) {
-                        Ok(p) => p,
-                        Err(e) => {
-                            error!("Ошибка обработки аудио через Rubato: {}", e);
-                            // Безопасная альтернатива - просто обрезаем аудио
-                            let target_samples = (extended_target * sample_rate as f32).round() as usize;
-                            return Ok((input.iter().take(target_samples).cloned().collect(), extended_target));
-                        }
-                    };
-                    
-                    // Получаем результат (первый и единственный канал)
-                    let result = output_frames[0].clone();
-                    
-                    info!("Итоговое аудио после изменения скорости с сохранением тона через Rubato: {} сэмплов, длительность ~{:.3}s",
-                          result.len(), result.len() as f32 / sample_rate as f32);
-                    
-                    // Проверяем, что длительность соответствует ожидаемой
-                    let result_duration = result.len() as f32 / sample_rate as f32;
-                    let target_samples = (extended_target * sample_rate as f32).round() as usize;
-                    
-                    if (result_duration - extended_target).abs() > 0.1 {
-                        // Корректируем длину, если нужно
-                        let mut adjusted_result = result;
-                        if adjusted_result.len() > target_samples {
-                            adjusted_result.truncate(target_samples);
-                        } else if adjusted_result.len() < target_samples {
-                            adjusted_result.extend(vec![0.0; target_samples - adjusted_result.len()]);
-                        }
-                        
-                        Ok((adjusted_result, extended_target))
-                    } else {
-                        Ok((result, extended_target))
-                    }
-                }
-            }
-        } else {
-            // Если аудио короче целевого - просто добавляем тишину
-            let target_samples = (target_duration * sample_rate as f32).round() as usize;
-            let mut output = input.to_vec();
-            if output.len() < target_samples {
-                output.extend(vec![0.0; target_samples - output.len()]);
+    /// Коэффициент tempo, подгоняющий речь длительностью `actual` к окну `target`
+    /// в пределах `min_tempo..=max_tempo`. Больше 1.0 - ускорение, меньше - замедление.
+    pub fn fit_tempo(actual: f32, target: f32, min_tempo: f32, max_tempo: f32) -> f32 {
+        if actual <= 0.0 || target <= 0.0 {
+            return 1.0;
+        }
+        (actual / target).clamp(min_tempo.min(1.0), max_tempo.max(1.0))
+    }
+
+    /// Обрезает или дополняет тишиной до `samples` сэмплов
+    fn fit_length(mut audio: Vec<f32>, samples: usize) -> Vec<f32> {
+        audio.resize(samples, 0.0);
+        audio
+    }
+
+    /// Меняет темп в `speed_factor` раз через WSOLA, при ошибке - через Rubato
+    fn stretch_samples(input: &[f32], sample_rate: u32, speed_factor: f32, config: &AudioProcessingConfig) -> Result<Vec<f32>> {
+        // Тон сохраняется, если rate не задействован
+        match super::timestretch::process(input, sample_rate, speed_factor, &config.stretch) {
+            Ok(processed) if !processed.is_empty() => {
+                info!("Итоговое аудио после изменения скорости через WSOLA: {} сэмплов, длительность ~{:.3}s",
+                      processed.len(), processed.len() as f32 / sample_rate as f32);
+                return Ok(processed);
             }
-            Ok((output, target_duration))
+            Ok(_) => warn!("WSOLA вернул пустой результат!"),
+            Err(e) => error!("Ошибка при изменении скорости через WSOLA: {}", e),
         }
+
+        // Предлагаем альтернативу в случае ошибки - попробуем использовать Rubato
+        warn!("Пробуем использовать резервный метод time-stretching (Rubato FFT)");
+        let mut resampler = rubato::FftFixedIn::<f32>::new(
+            sample_rate as usize,                           // Исходная частота дискретизации
+            (sample_rate as f64 / speed_factor as f64) as usize, // Целевая частота дискретизации
+            input.len(),                                    // Размер входного буфера
+            config.window_size / config.hop_size,           // Количество подблоков для обработки
+            1                                               // Количество каналов (моно)
+        )
+        .map_err(|e| TtsError::TimeStretchingError(format!("Ошибка создания FFT-ресемплера: {}", e)))?;
+
+        let input_frames = vec![input.to_vec()];
+        let mut output_frames = resampler
+            .process(&input_frames, None)
+            .map_err(|e| TtsError::TimeStretchingError(format!("Ошибка обработки аудио через Rubato: {}", e)))?;
+        let result = output_frames.swap_remove(0);
+        info!("Итоговое аудио после изменения скорости через Rubato: {} сэмплов, длительность ~{:.3}s",
+              result.len(), result.len() as f32 / sample_rate as f32);
+        Ok(result)
     }
 
     /// Применяет короткие fade-in и fade-out (в миллисекундах) к аудиофрагменту для сглаживания границ.
@@ -1182,11 +3918,12 @@ pub mod audio {
         output_path: P,
         progress_sender: Option<Sender<super::demucs::DemucsSeparationProgress>>,
         debug_dir: Option<P>,
+        cache: Option<&super::demucs::StemCache>,
     ) -> Result<()> {
         debug!("Удаление голоса из аудио: {}", input_path.as_ref().display());
         
         // Сначала пробуем использовать Demucs
-        match super::demucs::remove_vocals(&input_path, &output_path, progress_sender, debug_dir.as_ref()).await {
+        match super::demucs::remove_vocals(&input_path, &output_path, progress_sender, debug_dir.as_ref(), cache).await {
             Ok(_) => {
                 info!("Успешно удален голос с помощью Demucs");
                 return Ok(());
@@ -1195,112 +3932,860 @@ pub mod audio {
                 warn!("Не удалось использовать Demucs ({}), переключаемся на базовый метод через FFmpeg", e);
             }
         }
-        
-        // Fallback на базовый метод через FFmpeg
-        let output = tokio::process::Command::new("ffmpeg")
-            .args(&[
-                "-y",                     // Перезаписывать выходной файл
-                "-i", input_path.as_ref().to_str().unwrap(),
-                "-af", "pan=stereo|c0=c0-c1|c1=c1-c0,volume=2.0", // Удаление центрального канала
-                "-acodec", "pcm_s16le",   // 16-bit PCM
-                "-ar", "44100",           // 44.1 кГц
-                output_path.as_ref().to_str().unwrap()
-            ])
-            .output()
-            .await
-            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка запуска ffmpeg: {}", e)))?;
+        
+        // Fallback на базовый метод через FFmpeg
+        let output = tokio::process::Command::new(crate::utils::tools::ffmpeg())
+            .args([
+                "-y",                     // Перезаписывать выходной файл
+                "-i", input_path.as_ref().to_str().unwrap(),
+                "-af", "pan=stereo|c0=c0-c1|c1=c1-c0,volume=2.0", // Удаление центрального канала
+                "-acodec", "pcm_s16le",   // 16-bit PCM
+                "-ar", "44100",           // 44.1 кГц
+                output_path.as_ref().to_str().unwrap()
+            ])
+            .output()
+            .await
+            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка запуска ffmpeg: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!("Ошибка FFmpeg при удалении голоса: {}", stderr);
+            return Err(TtsError::AudioProcessingError(format!("Ошибка FFmpeg: {}", stderr)));
+        }
+
+        info!("Голос успешно удален из аудио (базовый метод): {}", output_path.as_ref().display());
+        Ok(())
+    }
+
+    /// Показания измерителя уровня одной шины микса
+    #[derive(Debug, Clone, serde::Serialize)]
+    pub struct BusMeter {
+        pub rms_db: f32,
+        pub peak_db: f32,
+    }
+
+    impl BusMeter {
+        pub fn measure(samples: &[f32]) -> Self {
+            let peak = samples.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+            Self {
+                rms_db: amplitude_to_db(compute_rms(samples)),
+                peak_db: amplitude_to_db(peak),
+            }
+        }
+    }
+
+    /// Отчет о микшировании для QC: уровни шин после применения усиления
+    #[derive(Debug, Clone, serde::Serialize)]
+    pub struct MixReport {
+        pub voice: BusMeter,
+        pub instrumental: BusMeter,
+        pub master: BusMeter,
+        /// Среднее усиление шины голоса после gain riding, дБ
+        pub voice_gain_db: f32,
+        /// Итоговое усиление мастер-шины для попадания в целевой уровень, дБ
+        pub master_gain_db: f32,
+        /// Частота дискретизации проекта, в которой сведены все шины
+        pub project_sample_rate: u32,
+        /// Формат оригинальной дорожки, по которому выбрана частота проекта
+        pub original_format: Option<AudioFormat>,
+        /// Шины, приведенные к частоте проекта
+        pub conversions: Vec<BusConversion>,
+        /// Нормализация громкости микса, если уровень оригинала неизвестен
+        pub loudness: Option<loudness::NormalizationReport>,
+    }
+
+    /// Приведение одной шины к частоте проекта
+    #[derive(Debug, Clone, serde::Serialize, PartialEq)]
+    pub struct BusConversion {
+        pub bus: String,
+        pub from_sample_rate: u32,
+        pub to_sample_rate: u32,
+    }
+
+    /// Выбирает частоту проекта перед микшированием. Решает оригинальная дорожка:
+    /// микс заменяет ее звук и должен совпадать с ней по частоте. Без оригинала или
+    /// с необычной частотой остается частота TTS.
+    pub fn negotiate_sample_rate(original: Option<&AudioFormat>, tts_rate: u32) -> u32 {
+        match original {
+            Some(format) if (16_000..=96_000).contains(&format.sample_rate) => format.sample_rate,
+            _ => tts_rate,
+        }
+    }
+
+    /// Меняет частоту дискретизации без изменения длительности и высоты тона
+    /// (для стерео - чередующиеся L/R)
+    pub fn resample(samples: &[f32], channels: u16, from_rate: u32, to_rate: u32) -> Result<Vec<f32>> {
+        if from_rate == to_rate || samples.is_empty() {
+            return Ok(samples.to_vec());
+        }
+        let channels = channels.max(1) as usize;
+        let frames = samples.len() / channels;
+        let mut resampler = FftFixedIn::<f32>::new(from_rate as usize, to_rate as usize, 1024, 2, channels)
+            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка создания ресемплера: {}", e)))?;
+        let planar: Vec<Vec<f32>> = (0..channels)
+            .map(|channel| samples.iter().skip(channel).step_by(channels).copied().collect())
+            .collect();
+
+        let mut output: Vec<Vec<f32>> = vec![Vec::new(); channels];
+        let mut position = 0;
+        while position < frames {
+            let needed = resampler.input_frames_next();
+            let end = (position + needed).min(frames);
+            let chunk: Vec<&[f32]> = planar.iter().map(|channel| &channel[position..end]).collect();
+            let processed = if end - position == needed {
+                resampler.process(&chunk, None)
+            } else {
+                resampler.process_partial(Some(&chunk), None)
+            }
+            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка ресемплинга: {}", e)))?;
+            for (out, channel) in output.iter_mut().zip(processed) {
+                out.extend(channel);
+            }
+            position = end;
+        }
+
+        // Ресемплер задерживает сигнал: дочитываем хвост и отбрасываем задержку
+        let delay = resampler.output_delay();
+        let expected = (frames as u64 * to_rate as u64 / from_rate as u64) as usize;
+        while output[0].len() < delay + expected {
+            let tail = resampler
+                .process_partial::<Vec<f32>>(None, None)
+                .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка ресемплинга: {}", e)))?;
+            if tail[0].is_empty() {
+                break;
+            }
+            for (out, channel) in output.iter_mut().zip(tail) {
+                out.extend(channel);
+            }
+        }
+        let mut interleaved = Vec::with_capacity(expected * channels);
+        for frame in 0..expected {
+            for channel in &output {
+                interleaved.push(channel.get(delay + frame).copied().unwrap_or(0.0));
+            }
+        }
+        Ok(interleaved)
+    }
+
+    pub fn amplitude_to_db(value: f32) -> f32 {
+        if value <= 1e-9 { -180.0 } else { 20.0 * value.log10() }
+    }
+
+    fn db_to_amplitude(db: f32) -> f32 {
+        10f32.powf(db / 20.0)
+    }
+
+    /// Итоговое аудио синхронизатора в памяти (для стерео - чередующиеся L/R)
+    #[derive(Debug, Clone)]
+    pub struct RenderedAudio {
+        pub samples: Vec<f32>,
+        pub sample_rate: u32,
+        pub channels: u16,
+    }
+
+    /// Кривая усиления инструментальной шины для дакинга, по значению на сэмпл голоса.
+    ///
+    /// Речь ищется по RMS голосовой шины блоками по 10 мс. Каждый блок речи начинает
+    /// приглушение на время атаки раньше, чтобы фон успел опуститься к началу фразы,
+    /// и держит его еще HOLD_MS после себя, чтобы фон не поднимался в паузах между
+    /// словами. Переходы сглаживаются однополюсным фильтром с постоянными времени
+    /// атаки и восстановления.
+    pub fn ducking_gains(voice: &[f32], sample_rate: u32, channels: u16, config: &AudioProcessingConfig) -> Vec<f32> {
+        const BLOCK_MS: f32 = 10.0;
+        const HOLD_MS: f32 = 150.0;
+
+        let channels = channels.max(1) as usize;
+        let block = ((sample_rate as f32 * BLOCK_MS / 1000.0) as usize).max(1) * channels;
+        let ms_to_blocks = |ms: f32| (ms.max(0.0) / BLOCK_MS).round() as usize;
+        let speech: Vec<bool> = voice
+            .chunks(block)
+            .map(|chunk| amplitude_to_db(compute_rms(chunk)) > config.duck_threshold_db)
+            .collect();
+
+        let lookahead = ms_to_blocks(config.duck_attack_ms);
+        let hold = ms_to_blocks(HOLD_MS);
+        let mut active = vec![false; speech.len()];
+        for (i, _) in speech.iter().enumerate().filter(|(_, is_speech)| **is_speech) {
+            let from = i.saturating_sub(lookahead);
+            let to = (i + hold).min(speech.len() - 1);
+            active[from..=to].iter_mut().for_each(|a| *a = true);
+        }
+
+        let coefficient = |ms: f32| {
+            if ms <= 0.0 { 1.0 } else { 1.0 - (-1000.0 / (ms * sample_rate.max(1) as f32)).exp() }
+        };
+        let attack = coefficient(config.duck_attack_ms);
+        let release = coefficient(config.duck_release_ms);
+        let ducked = db_to_amplitude(-config.duck_depth_db.abs());
+
+        let frames = voice.len() / channels;
+        let mut gains = Vec::with_capacity(frames * channels);
+        let mut gain = 1.0f32;
+        for frame in 0..frames {
+            let target = if active[frame * channels / block] { ducked } else { 1.0 };
+            gain += (target - gain) * if target < gain { attack } else { release };
+            gains.extend(std::iter::repeat_n(gain, channels));
+        }
+        gains
+    }
+
+    /// Сводит шину голоса с инструментальной шиной.
+    ///
+    /// Фон играет на уровне, заданном `voice_to_instrumental_ratio` и `instrumental_boost`.
+    /// Пока звучит речь, он приглушается относительно этого уровня на `duck_depth_db`
+    /// (см. [`ducking_gains`]), с нулевой глубиной дакинга остается постоянным.
+    ///
+    /// Громкость голоса "ведется" относительно инструментала по окнам ~400 мс, так что
+    /// голос всегда остается на `voice_over_background_db` выше фона. Целевой уровень
+    /// громкости применяется уже к суммарному миксу (по RMS оригинала, если он известен,
+    /// иначе к цели громкости EBU R128), поэтому голос не "качает" относительно музыки.
+    ///
+    /// Голос может быть стерео (чередующиеся L/R), моно инструментал в этом случае дублируется в оба канала.
+    pub fn mix_buses(
+        voice: &[f32],
+        instrumental: &[f32],
+        sample_rate: u32,
+        channels: u16,
+        config: &AudioProcessingConfig,
+        target_rms: Option<f32>,
+    ) -> (Vec<f32>, MixReport) {
+        const MAX_RIDE_DB: f32 = 12.0;
+        const SILENCE_DB: f32 = -50.0;
+
+        let ducking = config.duck_depth_db > 0.0;
+        let instrumental_gain = (1.0 - config.voice_to_instrumental_ratio) * config.instrumental_boost;
+        let upmixed: Vec<f32>;
+        let instrumental = if channels > 1 {
+            upmixed = instrumental.iter().flat_map(|&s| std::iter::repeat_n(s, channels as usize)).collect();
+            &upmixed[..]
+        } else {
+            instrumental
+        };
+        let duck = if ducking { ducking_gains(voice, sample_rate, channels, config) } else { Vec::new() };
+        let instrumental_bus: Vec<f32> = instrumental
+            .iter()
+            .enumerate()
+            .map(|(i, s)| s * instrumental_gain * duck.get(i).copied().unwrap_or(1.0))
+            .collect();
+        let window = ((sample_rate as f32 * 0.4) as usize * channels as usize).max(1);
+        let max_len = voice.len().max(instrumental.len());
+        let window_count = max_len.div_ceil(window);
+
+        // Усиление голоса для каждого окна
+        let mut gains = Vec::with_capacity(window_count);
+        let mut current_db = 0.0f32;
+        for w in 0..window_count {
+            let start = w * window;
+            let voice_rms = compute_rms(&voice[start.min(voice.len())..(start + window).min(voice.len())]);
+            let bed_rms = compute_rms(&instrumental_bus[start.min(instrumental_bus.len())..(start + window).min(instrumental_bus.len())]);
+            let voice_db = amplitude_to_db(voice_rms);
+
+            // В паузах речи сохраняем предыдущее усиление
+            if voice_db > SILENCE_DB {
+                let bed_db = amplitude_to_db(bed_rms).max(SILENCE_DB);
+                let wanted_db = (bed_db + config.voice_over_background_db - voice_db).clamp(-MAX_RIDE_DB, MAX_RIDE_DB);
+                // Сглаживание, чтобы избежать резких скачков громкости
+                current_db += (wanted_db - current_db) * 0.5;
+            }
+            gains.push(current_db);
+        }
+
+        let mut voice_bus = Vec::with_capacity(voice.len());
+        let mut gain_sum_db = 0.0f32;
+        for (i, &sample) in voice.iter().enumerate() {
+            // Линейная интерполяция усиления между центрами окон
+            let position = i as f32 / window as f32 - 0.5;
+            let left = position.floor().max(0.0) as usize;
+            let right = (left + 1).min(gains.len().saturating_sub(1));
+            let t = (position - left as f32).clamp(0.0, 1.0);
+            let gain_db = gains.get(left).copied().unwrap_or(0.0) * (1.0 - t) + gains.get(right).copied().unwrap_or(0.0) * t;
+            gain_sum_db += gain_db;
+            voice_bus.push(sample * db_to_amplitude(gain_db));
+        }
+
+        // Суммируем шины
+        let mut mixed = vec![0.0f32; max_len];
+        for (i, sample) in mixed.iter_mut().enumerate() {
+            *sample = voice_bus.get(i).copied().unwrap_or(0.0) + instrumental_bus.get(i).copied().unwrap_or(0.0);
+        }
+
+        // Целевой уровень применяется к сумме
+        let mixed_rms = compute_rms(&mixed);
+        let mixed_peak = mixed.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+        let mut loudness_report = None;
+        let master_gain = match target_rms {
+            Some(target) if target > 0.0 && mixed_rms > 0.0 => {
+                // Не допускаем клиппинга после усиления
+                let gain = (target / mixed_rms).min(0.98 / mixed_peak);
+                for sample in mixed.iter_mut() {
+                    *sample *= gain;
+                }
+                gain
+            }
+            _ => {
+                let report = loudness::normalize(&mut mixed, sample_rate, channels, &config.loudness);
+                loudness_report = Some(report);
+                db_to_amplitude(report.gain_db)
+            }
+        };
+
+        let report = MixReport {
+            voice: BusMeter::measure(&voice_bus),
+            instrumental: BusMeter::measure(&instrumental_bus),
+            master: BusMeter::measure(&mixed),
+            voice_gain_db: if voice.is_empty() { 0.0 } else { gain_sum_db / voice.len() as f32 },
+            master_gain_db: amplitude_to_db(master_gain),
+            project_sample_rate: sample_rate,
+            original_format: None,
+            conversions: Vec::new(),
+            loudness: loudness_report,
+        };
+        (mixed, report)
+    }
+
+    /// Определяет пол голоса в аудиофайле
+    /// Возвращает true для мужского голоса, false для женского
+    pub async fn detect_voice_gender<P: AsRef<Path>>(audio_path: P) -> Result<bool> {
+        use std::process::Command;
+        use std::str::from_utf8;
+
+        info!("Определение пола голоса в аудио: {}", audio_path.as_ref().display());
+
+        // Путь к скрипту относительно директории с исполняемым файлом
+        let script_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/utils/tts/voice_gender_detector.py");
+
+        // Проверяем существование скрипта
+        if !script_path.exists() {
+            error!("Скрипт определения пола голоса не найден: {}", script_path.display());
+            return Err(TtsError::AudioProcessingError(
+                format!("Скрипт определения пола голоса не найден: {}", script_path.display())
+            ));
+        }
+
+        // Запускаем Python скрипт
+        let output = Command::new("python3")
+            .args([
+                script_path.to_str().unwrap(),
+                audio_path.as_ref().to_str().unwrap(),
+            ])
+            .output()
+            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка запуска Python скрипта: {}", e)))?;
+
+        // Всегда логируем stderr для отладки
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.is_empty() {
+            debug!("Python script stderr output:\n{}", stderr);
+        }
+
+        if !output.status.success() {
+            return Err(TtsError::AudioProcessingError(format!("Ошибка анализа голоса: {}", stderr)));
+        }
+
+        let result = from_utf8(&output.stdout)
+            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка чтения результата: {}", e)))?
+            .trim();
+
+        match result {
+            "male" => {
+                info!("Определен мужской голос");
+                Ok(true)
+            },
+            "female" => {
+                info!("Определен женский голос");
+                Ok(false)
+            },
+            _ => {
+                if let Some(message) = result.strip_prefix("error: ") {
+                    Err(TtsError::AudioProcessingError(format!("Ошибка в Python скрипте: {}", message)))
+                } else {
+                    Err(TtsError::AudioProcessingError(format!("Неожиданный результат анализа: {}", result)))
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_fit_tempo_stays_within_limits() {
+            assert!((fit_tempo(3.0, 2.5, 0.85, 1.25) - 1.2).abs() < 1e-6);
+            // Не укладывается: ограничено, остаток сдвинет следующие реплики
+            assert_eq!(fit_tempo(4.0, 2.0, 0.85, 1.25), 1.25);
+            assert!((fit_tempo(1.8, 2.0, 0.85, 1.25) - 0.9).abs() < 1e-6);
+            assert_eq!(fit_tempo(1.0, 2.0, 0.85, 1.25), 0.85);
+            // Без замедления короткая речь только дополняется тишиной
+            assert_eq!(fit_tempo(1.0, 2.0, 1.0, 1.25), 1.0);
+        }
+
+        #[test]
+        fn test_background_ducks_only_under_speech() {
+            // 1 с тишины, 1 с речи, 1 с тишины при 1 кГц
+            let voice: Vec<f32> = (0..3000)
+                .map(|i| if (1000..2000).contains(&i) { 0.5 * (i as f32 * 0.3).sin() } else { 0.0 })
+                .collect();
+            let config = AudioProcessingConfig::default();
+            let gains = ducking_gains(&voice, 1000, 1, &config);
+            assert_eq!(gains.len(), voice.len());
+
+            let ducked = db_to_amplitude(-config.duck_depth_db);
+            assert!((gains[500] - 1.0).abs() < 1e-6);
+            // Фон начинает опускаться до начала фразы
+            assert!(gains[990] < 0.8);
+            assert!((gains[1500] - ducked).abs() < 0.01);
+            assert!(gains[2900] > 0.8);
+        }
+    }
+}
+
+/// Готовые получатели обновлений прогресса.
+///
+/// Помимо канала `SyncConfig::progress_sender` приложение может подписать на
+/// прогресс любое число наблюдателей через [`progress::ProgressObservers`],
+/// в том числе во время работы синхронизации. Здесь же лежат типовые
+/// реализации, чтобы их не приходилось писать в каждом приложении: канал
+/// tokio, JSONL-файл, webhook и обертка над замыканием с ограничением частоты
+/// вызовов.
+pub mod progress {
+    use super::ProgressUpdate;
+    use log::warn;
+    use std::future::Future;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tokio::io::AsyncWriteExt;
+    use tokio::sync::mpsc;
+    use tokio::task::JoinHandle;
+
+    /// Получатель обновлений прогресса. Вызывается синхронно из цикла
+    /// синхронизации, поэтому не должен блокироваться надолго.
+    pub trait ProgressReporter: Send + Sync {
+        fn report(&self, update: &ProgressUpdate);
+    }
+
+    /// Идентификатор подписки, по которому наблюдателя можно удалить
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct ObserverId(u64);
+
+    /// Набор наблюдателей прогресса
+    #[derive(Default)]
+    pub struct ProgressObservers {
+        next_id: AtomicU64,
+        observers: Mutex<Vec<(ObserverId, Arc<dyn ProgressReporter>)>>,
+    }
+
+    impl ProgressObservers {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Подписывает наблюдателя и возвращает идентификатор подписки
+        pub fn register(&self, reporter: Arc<dyn ProgressReporter>) -> ObserverId {
+            let id = ObserverId(self.next_id.fetch_add(1, Ordering::Relaxed));
+            if let Ok(mut observers) = self.observers.lock() {
+                observers.push((id, reporter));
+            }
+            id
+        }
+
+        /// Отписывает наблюдателя. Возвращает false, если подписки уже нет.
+        pub fn remove(&self, id: ObserverId) -> bool {
+            let Ok(mut observers) = self.observers.lock() else {
+                return false;
+            };
+            let before = observers.len();
+            observers.retain(|(observer_id, _)| *observer_id != id);
+            observers.len() != before
+        }
+
+        pub fn len(&self) -> usize {
+            self.observers.lock().map(|observers| observers.len()).unwrap_or(0)
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// Передает обновление всем наблюдателям в порядке подписки
+        pub fn notify(&self, update: &ProgressUpdate) {
+            // Копируем список, чтобы наблюдатель мог отписаться прямо из report()
+            let observers: Vec<Arc<dyn ProgressReporter>> = match self.observers.lock() {
+                Ok(observers) => observers.iter().map(|(_, reporter)| reporter.clone()).collect(),
+                Err(_) => return,
+            };
+            for reporter in observers {
+                reporter.report(update);
+            }
+        }
+    }
+
+    /// Ключевые этапы, которые не отбрасываются при ограничении частоты
+    fn is_milestone(update: &ProgressUpdate) -> bool {
+        !matches!(update, ProgressUpdate::TTSGeneration { .. } | ProgressUpdate::ProcessingFragment { .. })
+    }
+
+    /// Ограничение частоты: частые обновления пропускаются не чаще интервала,
+    /// ключевые этапы — всегда
+    #[derive(Default)]
+    struct Throttle {
+        min_interval: Option<Duration>,
+        last_delivered: Mutex<Option<Instant>>,
+    }
+
+    impl Throttle {
+        fn admits(&self, update: &ProgressUpdate) -> bool {
+            let (Some(interval), false) = (self.min_interval, is_milestone(update)) else {
+                return true;
+            };
+            let Ok(mut last) = self.last_delivered.lock() else { return false };
+            let now = Instant::now();
+            if last.is_some_and(|at| now.duration_since(at) < interval) {
+                return false;
+            }
+            *last = Some(now);
+            true
+        }
+    }
+
+    /// Строка JSONL или тело webhook: `{"timestamp_ms": ..., "update": {"type": ...}}`
+    fn timestamped(update: &ProgressUpdate) -> serde_json::Value {
+        let timestamp_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        serde_json::json!({ "timestamp_ms": timestamp_ms, "update": update })
+    }
+
+    /// Фоновая задача, которой репортер передает обновления. report()
+    /// вызывается синхронно из цикла синхронизации, поэтому ввод-вывод
+    /// выполняется здесь, по порядку поступления.
+    struct Worker {
+        sender: Mutex<Option<mpsc::UnboundedSender<serde_json::Value>>>,
+        task: Mutex<Option<JoinHandle<()>>>,
+    }
+
+    impl Worker {
+        /// Должен вызываться внутри runtime tokio
+        fn spawn<F, Fut>(run: F) -> Self
+        where
+            F: FnOnce(mpsc::UnboundedReceiver<serde_json::Value>) -> Fut,
+            Fut: Future<Output = ()> + Send + 'static,
+        {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            Self { sender: Mutex::new(Some(sender)), task: Mutex::new(Some(tokio::spawn(run(receiver)))) }
+        }
+
+        fn send(&self, value: serde_json::Value) {
+            if let Some(sender) = self.sender.lock().ok().as_ref().and_then(|sender| sender.as_ref()) {
+                let _ = sender.send(value);
+            }
+        }
+
+        /// Перестает принимать обновления и дожидается обработки уже принятых
+        async fn close(&self) {
+            self.sender.lock().ok().and_then(|mut sender| sender.take());
+            let task = self.task.lock().ok().and_then(|mut task| task.take());
+            if let Some(task) = task {
+                let _ = task.await;
+            }
+        }
+    }
+
+    /// Пересылает обновления в канал tokio. Если получатель не успевает и
+    /// канал заполнен, обновление отбрасывается, а не блокирует синхронизацию.
+    pub struct ChannelReporter {
+        sender: mpsc::Sender<ProgressUpdate>,
+    }
+
+    impl ChannelReporter {
+        pub fn new(sender: mpsc::Sender<ProgressUpdate>) -> Self {
+            Self { sender }
+        }
+    }
+
+    impl ProgressReporter for ChannelReporter {
+        fn report(&self, update: &ProgressUpdate) {
+            if let Err(mpsc::error::TrySendError::Full(update)) = self.sender.try_send(update.clone()) {
+                warn!("Канал прогресса переполнен, обновление пропущено: {:?}", update);
+            }
+        }
+    }
+
+    /// Дописывает каждое обновление строкой JSON в файл
+    /// (`{"timestamp_ms": ..., "update": {"type": ...}}`). Запись идет в
+    /// фоновой задаче tokio, цикл синхронизации ее не ждет.
+    pub struct JsonlFileReporter {
+        worker: Worker,
+    }
+
+    impl JsonlFileReporter {
+        /// Открывает файл на дозапись, создавая его при необходимости
+        pub async fn create(path: &Path) -> std::io::Result<Self> {
+            let file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+            let worker = Worker::spawn(|mut lines| async move {
+                let mut writer = tokio::io::BufWriter::new(file);
+                while let Some(line) = lines.recv().await {
+                    // Сбрасываем буфер после каждой строки, чтобы файл можно было читать по ходу работы
+                    let written = async {
+                        writer.write_all(format!("{}\n", line).as_bytes()).await?;
+                        writer.flush().await
+                    };
+                    if let Err(e) = written.await {
+                        warn!("Не удалось записать прогресс в файл: {}", e);
+                    }
+                }
+            });
+            Ok(Self { worker })
+        }
+
+        /// Дописывает уже полученные обновления; после этого новые не принимаются
+        pub async fn close(&self) {
+            self.worker.close().await;
+        }
+    }
+
+    impl ProgressReporter for JsonlFileReporter {
+        fn report(&self, update: &ProgressUpdate) {
+            self.worker.send(timestamped(update));
+        }
+    }
+
+    /// Отправляет обновления POST-запросом с JSON (`{"timestamp_ms": ...,
+    /// "update": {...}}`) на URL, например в CI или чат-бот. Запросы уходят
+    /// по очереди из фоновой задачи; частые обновления по умолчанию
+    /// отправляются не чаще раза в секунду, ключевые этапы — всегда.
+    /// Ошибки доставки только логируются.
+    pub struct WebhookReporter {
+        worker: Worker,
+        throttle: Throttle,
+    }
+
+    impl WebhookReporter {
+        /// Должен создаваться внутри runtime tokio
+        pub fn new(url: impl Into<String>) -> Self {
+            let url = url.into();
+            let worker = Worker::spawn(|mut updates| async move {
+                let client = reqwest::Client::builder()
+                    .timeout(Duration::from_secs(10))
+                    .build()
+                    .unwrap_or_default();
+                while let Some(update) = updates.recv().await {
+                    let sent = client.post(&url).json(&update).send().await.and_then(|r| r.error_for_status());
+                    if let Err(e) = sent {
+                        warn!("Не удалось отправить прогресс на webhook {}: {}", url, e);
+                    }
+                }
+            });
+            Self {
+                worker,
+                throttle: Throttle { min_interval: Some(Duration::from_secs(1)), ..Default::default() },
+            }
+        }
+
+        /// Отправлять частые обновления не чаще одного раза за `interval`
+        /// (`Duration::ZERO` — все)
+        pub fn with_throttle(mut self, interval: Duration) -> Self {
+            self.throttle.min_interval = Some(interval).filter(|interval| !interval.is_zero());
+            self
+        }
+
+        /// Дожидается отправки уже полученных обновлений; после этого новые не принимаются
+        pub async fn close(&self) {
+            self.worker.close().await;
+        }
+    }
+
+    impl ProgressReporter for WebhookReporter {
+        fn report(&self, update: &ProgressUpdate) {
+            if self.throttle.admits(update) {
+                self.worker.send(timestamped(update));
+            }
+        }
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            error!("Ошибка FFmpeg при удалении голоса: {}", stderr);
-            return Err(TtsError::AudioProcessingError(format!("Ошибка FFmpeg: {}", stderr)));
+    /// Вызывает замыкание на каждое обновление. С ограничением частоты
+    /// частые обновления (генерация и обработка фрагментов) доставляются не
+    /// чаще заданного интервала, ключевые этапы — всегда.
+    pub struct CallbackReporter<F: Fn(&ProgressUpdate) + Send + Sync> {
+        callback: F,
+        throttle: Throttle,
+    }
+
+    impl<F: Fn(&ProgressUpdate) + Send + Sync> CallbackReporter<F> {
+        pub fn new(callback: F) -> Self {
+            Self { callback, throttle: Throttle::default() }
         }
 
-        info!("Голос успешно удален из аудио (базовый метод): {}", output_path.as_ref().display());
-        Ok(())
+        /// Доставлять частые обновления не чаще одного раза за `interval`
+        pub fn with_throttle(mut self, interval: Duration) -> Self {
+            self.throttle.min_interval = Some(interval);
+            self
+        }
     }
 
-    /// Микширует две аудиодорожки с заданным соотношением
-    pub fn mix_audio_tracks(voice: &[f32], instrumental: &[f32], voice_ratio: f32, instrumental_boost: f32) -> Vec<f32> {
-        let voice_gain = voice_ratio;
-        let instrumental_gain = (1.0 - voice_ratio) * instrumental_boost;
-        
-        let max_len = voice.len().max(instrumental.len());
-        let mut mixed = Vec::with_capacity(max_len);
-        
-        for i in 0..max_len {
-            let voice_sample = if i < voice.len() { voice[i] } else { 0.0 };
-            let instrumental_sample = if i < instrumental.len() { instrumental[i] } else { 0.0 };
-            
-            // Микшируем с учетом усиления инструментальной дорожки
-            let mixed_sample = voice_sample * voice_gain + instrumental_sample * instrumental_gain;
-            
-            // Предотвращаем клиппинг
-            mixed.push(mixed_sample.clamp(-1.0, 1.0));
+    impl<F: Fn(&ProgressUpdate) + Send + Sync> ProgressReporter for CallbackReporter<F> {
+        fn report(&self, update: &ProgressUpdate) {
+            if self.throttle.admits(update) {
+                (self.callback)(update);
+            }
         }
-        
-        mixed
     }
 
-    /// Определяет пол голоса в аудиофайле
-    /// Возвращает true для мужского голоса, false для женского
-    pub async fn detect_voice_gender<P: AsRef<Path>>(audio_path: P) -> Result<bool> {
-        use std::process::Command;
-        use std::str::from_utf8;
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::sync::atomic::AtomicUsize;
 
-        info!("Определение пола голоса в аудио: {}", audio_path.as_ref().display());
+        struct Counter(AtomicUsize);
 
-        // Путь к скрипту относительно директории с исполняемым файлом
-        let script_path = Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("src/utils/tts/voice_gender_detector.py");
+        impl ProgressReporter for Counter {
+            fn report(&self, _update: &ProgressUpdate) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
 
-        // Проверяем существование скрипта
-        if !script_path.exists() {
-            error!("Скрипт определения пола голоса не найден: {}", script_path.display());
-            return Err(TtsError::AudioProcessingError(
-                format!("Скрипт определения пола голоса не найден: {}", script_path.display())
-            ));
+        fn fragment(index: usize) -> ProgressUpdate {
+            ProgressUpdate::TTSGeneration { current: index, total: 10 }
         }
 
-        // Запускаем Python скрипт
-        let output = Command::new("python3")
-            .args(&[
-                script_path.to_str().unwrap(),
-                audio_path.as_ref().to_str().unwrap(),
-            ])
-            .output()
-            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка запуска Python скрипта: {}", e)))?;
+        #[test]
+        fn test_register_and_remove_observers() {
+            let observers = ProgressObservers::new();
+            let first = Arc::new(Counter(AtomicUsize::new(0)));
+            let second = Arc::new(Counter(AtomicUsize::new(0)));
+            let first_id = observers.register(first.clone());
+            let second_id = observers.register(second.clone());
+            assert_ne!(first_id, second_id);
+            assert_eq!(observers.len(), 2);
 
-        // Всегда логируем stderr для отладки
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        if !stderr.is_empty() {
-            debug!("Python script stderr output:\n{}", stderr);
+            observers.notify(&ProgressUpdate::Started);
+            assert!(observers.remove(first_id));
+            assert!(!observers.remove(first_id));
+            observers.notify(&ProgressUpdate::Finished);
+
+            assert_eq!(first.0.load(Ordering::SeqCst), 1);
+            assert_eq!(second.0.load(Ordering::SeqCst), 2);
+            assert!(observers.remove(second_id));
+            assert!(observers.is_empty());
         }
 
-        if !output.status.success() {
-            return Err(TtsError::AudioProcessingError(format!("Ошибка анализа голоса: {}", stderr)));
+        #[test]
+        fn test_observer_can_unsubscribe_itself() {
+            let observers = Arc::new(ProgressObservers::new());
+            let id = Arc::new(Mutex::new(None));
+            let reporter = {
+                let observers = observers.clone();
+                let id = id.clone();
+                CallbackReporter::new(move |_: &ProgressUpdate| {
+                    if let Some(id) = id.lock().unwrap().take() {
+                        observers.remove(id);
+                    }
+                })
+            };
+            *id.lock().unwrap() = Some(observers.register(Arc::new(reporter)));
+
+            observers.notify(&ProgressUpdate::Started);
+            assert!(observers.is_empty());
         }
 
-        let result = from_utf8(&output.stdout)
-            .map_err(|e| TtsError::AudioProcessingError(format!("Ошибка чтения результата: {}", e)))?
-            .trim();
+        #[test]
+        fn test_throttled_callback_keeps_milestones() {
+            let delivered = Arc::new(Mutex::new(Vec::new()));
+            let reporter = {
+                let delivered = delivered.clone();
+                CallbackReporter::new(move |update: &ProgressUpdate| {
+                    delivered.lock().unwrap().push(format!("{:?}", update));
+                })
+                .with_throttle(Duration::from_secs(60))
+            };
 
-        match result {
-            "male" => {
-                info!("Определен мужской голос");
-                Ok(true)
-            },
-            "female" => {
-                info!("Определен женский голос");
-                Ok(false)
-            },
-            _ => {
-                if result.starts_with("error: ") {
-                    Err(TtsError::AudioProcessingError(format!("Ошибка в Python скрипте: {}", &result[7..])))
-                } else {
-                    Err(TtsError::AudioProcessingError(format!("Неожиданный результат анализа: {}", result)))
-                }
+            reporter.report(&ProgressUpdate::Started);
+            for i in 1..=5 {
+                reporter.report(&fragment(i));
+            }
+            reporter.report(&ProgressUpdate::Finished);
+
+            let delivered = delivered.lock().unwrap();
+            assert_eq!(delivered.len(), 3);
+            assert!(delivered[1].contains("current: 1"));
+        }
+
+        #[test]
+        fn test_channel_reporter_drops_when_full() {
+            let (tx, mut rx) = mpsc::channel(1);
+            let reporter = ChannelReporter::new(tx);
+            reporter.report(&fragment(1));
+            reporter.report(&fragment(2));
+            assert!(matches!(rx.try_recv(), Ok(ProgressUpdate::TTSGeneration { current: 1, .. })));
+            assert!(rx.try_recv().is_err());
+        }
+
+        #[tokio::test]
+        async fn test_jsonl_file_reporter() {
+            let file = tempfile::NamedTempFile::new().unwrap();
+            let reporter = JsonlFileReporter::create(file.path()).await.unwrap();
+            reporter.report(&ProgressUpdate::ParsedVTT { total: 3 });
+            reporter.report(&ProgressUpdate::Finished);
+            reporter.close().await;
+
+            let content = std::fs::read_to_string(file.path()).unwrap();
+            let lines: Vec<serde_json::Value> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+            assert_eq!(lines.len(), 2);
+            assert_eq!(lines[0]["update"]["type"], "parsed_vtt");
+            assert_eq!(lines[0]["update"]["total"], 3);
+            assert_eq!(lines[1]["update"]["type"], "finished");
+        }
+
+        #[tokio::test]
+        async fn test_webhook_reporter_posts_throttled_updates() {
+            use tokio::io::AsyncReadExt;
+
+            // Отвечает на каждый запрос и закрывает соединение, тела запросов собирает
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/progress", listener.local_addr().unwrap());
+            let bodies = Arc::new(Mutex::new(Vec::new()));
+            let server = {
+                let bodies = bodies.clone();
+                tokio::spawn(async move {
+                    loop {
+                        let (mut stream, _) = listener.accept().await.unwrap();
+                        let mut request = Vec::new();
+                        let mut buf = [0u8; 4096];
+                        let body = loop {
+                            let read = stream.read(&mut buf).await.unwrap();
+                            request.extend_from_slice(&buf[..read]);
+                            let text = String::from_utf8_lossy(&request).to_string();
+                            let Some((head, body)) = text.split_once("\r\n\r\n") else { continue };
+                            let length = head
+                                .lines()
+                                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                                .unwrap_or(0);
+                            if body.len() >= length || read == 0 {
+                                break body.to_string();
+                            }
+                        };
+                        bodies.lock().unwrap().push(serde_json::from_str::<serde_json::Value>(&body).unwrap());
+                        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").await;
+                    }
+                })
+            };
+
+            let reporter = WebhookReporter::new(url).with_throttle(Duration::from_secs(60));
+            reporter.report(&ProgressUpdate::Started);
+            for i in 1..=5 {
+                reporter.report(&fragment(i));
             }
+            reporter.report(&ProgressUpdate::Finished);
+            reporter.close().await;
+            server.abort();
+
+            let bodies = bodies.lock().unwrap();
+            let types: Vec<&str> = bodies.iter().map(|body| body["update"]["type"].as_str().unwrap()).collect();
+            assert_eq!(types, ["started", "tts_generation", "finished"]);
+            assert_eq!(bodies[1]["update"]["current"], 1);
         }
     }
 }
@@ -1308,19 +4793,23 @@ pub mod audio {
 /// Основной API библиотеки.
 pub mod synchronizer {
     use super::*;
-    use futures::future::join_all;
     use tokio::sync::mpsc::Sender;
-    use std::path::Path;
+    use tokio::sync::oneshot;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
     use log::{debug, info, error, warn};
 
     /// Структура одного аудиофрагмента
     pub struct AudioFragment {
+        /// Номер реплики, которую озвучивает фрагмент
+        pub cue_index: usize,
         pub samples: Vec<f32>,
         pub sample_rate: u32,
         pub text: String,
         pub start_time: f32,
         pub end_time: f32,
         pub next_cue_start: Option<f32>,  // время начала следующего cue, если есть
+        pub speaker: Option<String>,
     }
 
     /// Параметры для определения проблемных сегментов
@@ -1407,22 +4896,51 @@ pub mod synchronizer {
         results
     }
     
+    /// Озвучка реплики, записанная пользователем (MP3 моно с частотой TTS).
+    /// Реплика находится по времени начала.
+    #[derive(Debug, Clone)]
+    pub struct HumanNarration {
+        pub cue_start: f32,
+        pub path: PathBuf,
+    }
+
     /// Общая конфигурация для синхронизации
     pub struct SyncConfig<'a> {
         /// API ключ для OpenAI.
         pub api_key: &'a str,
         /// Путь к VTT-файлу с субтитрами.
         pub vtt_path: &'a Path,
+        /// Кодировка VTT-файла, выбранная пользователем; если не задана, определяется автоматически.
+        pub subtitle_encoding: Option<&'a str>,
         /// Путь для сохранения итогового WAV-файла.
         pub output_wav: &'a Path,
         /// Опциональный путь к исходному аудиофайлу для нормализации громкости (mp3, m4a и т.д.).
         pub original_audio_path: Option<&'a Path>,
         /// Опциональный канал для отправки обновлений прогресса.
         pub progress_sender: Option<Sender<ProgressUpdate>>,
+        /// Дополнительные наблюдатели прогресса; их можно подписывать и отписывать во время работы.
+        pub observers: Option<Arc<progress::ProgressObservers>>,
         /// Конфигурация TTS API.
         pub tts_config: TtsConfig,
         /// Конфигурация аудио-обработки.
         pub audio_config: AudioProcessingConfig,
+        /// Если задан, итоговое аудио передается сюда (например, в stdin ffmpeg при слиянии),
+        /// а не записывается в `output_wav`. Полноразмерные отладочные WAV при этом не сохраняются.
+        pub stream_to: Option<oneshot::Sender<audio::RenderedAudio>>,
+        /// Источник речи для реплик; по умолчанию OpenAI TTS с `tts_config`.
+        pub speech_provider: Option<Arc<dyn provider::SpeechProvider>>,
+        /// Если задан, сюда записываются реплики с фактически произнесенным текстом
+        /// (для speech-to-speech это и есть перевод).
+        pub spoken_vtt_path: Option<&'a Path>,
+        /// Реплики с озвучкой пользователя: TTS для них не вызывается, а записи
+        /// проходят ту же подгонку длительности и громкости
+        pub narration: Vec<HumanNarration>,
+        /// Если задан, сюда записываются субтитры, перенесенные туда, где фактически
+        /// звучит озвучка (когда речь сдвинула реплики). Иначе файл удаляется.
+        pub retimed_vtt_path: Option<&'a Path>,
+        /// Готовая инструментальная дорожка оригинала (например, от озвучки того же
+        /// видео на другой язык); если задана, разделение не запускается
+        pub instrumental_path: Option<&'a Path>,
     }
 
     impl<'a> SyncConfig<'a> {
@@ -1436,18 +4954,65 @@ pub mod synchronizer {
             Self {
                 api_key,
                 vtt_path,
+                subtitle_encoding: None,
                 output_wav,
                 original_audio_path: None,
                 progress_sender: None,
+                observers: None,
                 tts_config: TtsConfig::default(),
                 audio_config: AudioProcessingConfig::default(),
+                stream_to: None,
+                speech_provider: None,
+                spoken_vtt_path: None,
+                narration: Vec::new(),
+                retimed_vtt_path: None,
+                instrumental_path: None,
             }
         }
     }
 
-    /// Отправляет сообщение о прогрессе, если канал присутствует.
-    async fn send_progress(sender: &Option<Sender<ProgressUpdate>>, update: ProgressUpdate) {
-        if let Some(tx) = sender {
+    /// Приводит громкость записей пользователя к средней громкости фрагментов TTS,
+    /// чтобы голоса не скакали при смене источника речи
+    fn match_narration_loudness(fragments: &mut [AudioFragment], human: &[bool]) {
+        let tts_levels: Vec<f32> = fragments.iter().zip(human)
+            .filter(|(_, human)| !**human)
+            .map(|(fragment, _)| audio::compute_rms(&fragment.samples))
+            .filter(|rms| *rms > 1e-4)
+            .collect();
+        if tts_levels.is_empty() || tts_levels.len() == fragments.len() {
+            return;
+        }
+        let target_rms = tts_levels.iter().sum::<f32>() / tts_levels.len() as f32;
+        for (fragment, _) in fragments.iter_mut().zip(human).filter(|(_, human)| **human) {
+            let rms = audio::compute_rms(&fragment.samples);
+            if rms <= 1e-4 {
+                continue;
+            }
+            // Не усиливаем больше чем на 20 дБ, чтобы не вытягивать шум тихой записи
+            let gain = (target_rms / rms).min(10.0);
+            for sample in fragment.samples.iter_mut() {
+                *sample = (*sample * gain).clamp(-1.0, 1.0);
+            }
+            debug!("Громкость записи пользователя \"{}\": умножена на {:.2}", fragment.text, gain);
+        }
+    }
+
+    /// Оповещает наблюдателей и отправляет сообщение о прогрессе, если канал присутствует.
+    /// Имя MP3-чанка реплики без расширения: номер и текст без спецсимволов
+    pub fn chunk_name(index: usize, text: &str) -> String {
+        let sanitized_text = text.chars()
+            .map(|c| if c.is_alphanumeric() || c == ' ' { c } else { '_' })
+            .collect::<String>()
+            .trim()
+            .to_string();
+        format!("chunk_{:03}_{}", index, sanitized_text)
+    }
+
+    async fn send_progress(config: &SyncConfig<'_>, update: ProgressUpdate) {
+        if let Some(observers) = &config.observers {
+            observers.notify(&update);
+        }
+        if let Some(tx) = &config.progress_sender {
             let _ = tx.send(update).await;
         }
     }
@@ -1457,8 +5022,8 @@ pub mod synchronizer {
     /// - Генерация аудио через TTS API
     /// - Декодирование, корректировка длительности, применение fade‑in/fade‑out для каждого аудиофрагмента
     /// - Склейка фрагментов, нормализация громкости (если указан оригинальный аудиофайл), запись итогового аудио в WAV.
-    pub async fn process_sync(config: SyncConfig<'_>) -> Result<()> {
-        send_progress(&config.progress_sender, ProgressUpdate::Started).await;
+    pub async fn process_sync(mut config: SyncConfig<'_>) -> Result<()> {
+        send_progress(&config, ProgressUpdate::Started).await;
 
         // Проверяем установку Demucs и его зависимостей (включая pyAudioAnalysis)
         info!("Проверка установки Demucs и зависимостей...");
@@ -1468,16 +5033,6 @@ pub mod synchronizer {
         }
         info!("Demucs и зависимости установлены успешно");
 
-        // Сначала проверяем, установлен ли SoundTouch, и устанавливаем его при необходимости
-        info!("Проверка установки SoundTouch перед началом TTS обработки");
-        match super::soundtouch::ensure_soundtouch_installed() {
-            Ok(_) => info!("SoundTouch доступен, приступаем к TTS обработке"),
-            Err(e) => {
-                error!("Не удалось обеспечить наличие SoundTouch: {}", e);
-                return Err(e);
-            }
-        }
-
         // Используем конфигурацию TTS как есть, без определения пола голоса
         let tts_config = config.tts_config.clone();
         info!("Используется голос {} для TTS", tts_config.voice);
@@ -1490,13 +5045,10 @@ pub mod synchronizer {
         tokio::spawn(async move {
             while let Some(progress) = demucs_rx.recv().await {
                 use super::demucs::DemucsSeparationProgress::*;
-                let (status, progress_value) = match progress {
-                    Started => ("Удаление вокала".to_string(), 0.0),
-                    LoadingModel => ("Удаление вокала".to_string(), 10.0),
-                    Processing { progress } => (format!("Удаление вокала"), 10.0 + progress * 80.0),
-                    Converting => ("Удаление вокала".to_string(), 90.0),
-                    Finished => ("Удаление вокала завершено".to_string(), 100.0),
-                    Error(ref msg) => (format!("Ошибка при удалении вокала: {}", msg), 0.0),
+                let status = match progress {
+                    Started | LoadingModel | Processing { .. } | Converting => "Удаление вокала".to_string(),
+                    Finished => "Удаление вокала завершено".to_string(),
+                    Error(ref msg) => format!("Ошибка при удалении вокала: {}", msg),
                 };
 
                 // For Demucs processing, we want to integrate it better with the overall TTS flow
@@ -1526,14 +5078,135 @@ pub mod synchronizer {
         });
 
         // 1. Парсинг VTT
-        send_progress(&config.progress_sender, ProgressUpdate::ParsingVTT).await;
-        let mut cues = vtt::parse_vtt(&config.vtt_path)?;
+        send_progress(&config, ProgressUpdate::ParsingVTT).await;
+        let mut cues = vtt::parse_vtt_with_encoding(config.vtt_path, config.subtitle_encoding)?;
         if cues.is_empty() {
             return Err(TtsError::VttParsingError("VTT-файл не содержит субтитров".to_string()));
         }
+
+        let overlap_policy = config.audio_config.overlap_policy;
+        // Номера исходных реплик для каждой озвучиваемой, для отчета оптимизатора
+        let mut cue_sources: Vec<Vec<usize>> = (0..cues.len()).map(|i| vec![i]).collect();
+        if overlap_policy == timeline::OverlapPolicy::Merge {
+            let (sources, decisions) = timeline::merge_overlapping_cues(&mut cues);
+            let merged = cue_sources.len() - sources.len();
+            if merged > 0 {
+                info!("Объединено {} пересекающихся реплик", merged);
+            }
+            cue_sources = sources;
+            for decision in decisions {
+                send_progress(&config, ProgressUpdate::CueDecision { decision }).await;
+            }
+        }
+        // Субтитры переписываются, если в них нужен произнесенный текст (speech-to-speech)
+        // или новые тайминги (Retime); исходные реплики берутся до перераспределения времени
+        let retime_drift = match config.audio_config.fit_strategy {
+            timeline::FitStrategy::Retime { max_drift } => Some(max_drift),
+            timeline::FitStrategy::Stretch => None,
+        };
+        let output_vtt_path = config.spoken_vtt_path.or(retime_drift.map(|_| config.vtt_path));
+        let subtitle_cues = output_vtt_path.map(|_| cues.clone());
+
+        // Реплики одного предложения озвучиваются вместе, субтитры остаются как были.
+        // Произнесенный текст speech-to-speech по репликам уже не разделить.
+        let display_cues = cues.clone();
+        let mut sentence_groups: Vec<Vec<usize>> = (0..cues.len()).map(|i| vec![i]).collect();
+        let sentence_merging = config.audio_config.sentence_merging;
+        if sentence_merging.enabled && config.spoken_vtt_path.is_none() {
+            let fixed: Vec<bool> = cues.iter()
+                .map(|cue| config.narration.iter().any(|narration| (narration.cue_start - cue.start).abs() < 0.05))
+                .collect();
+            let (groups, decisions) = timeline::merge_sentences(&mut cues, &sentence_merging, &fixed);
+            if groups.len() < sentence_groups.len() {
+                info!("Реплики объединены в предложения: {} -> {}", sentence_groups.len(), groups.len());
+            }
+            for mut decision in decisions {
+                decision.source_cues = decision.source_cues.iter().flat_map(|&i| cue_sources[i].clone()).collect();
+                send_progress(&config, ProgressUpdate::CueDecision { decision }).await;
+            }
+            cue_sources = groups.iter().map(|group| group.iter().flat_map(|&i| cue_sources[i].clone()).collect()).collect();
+            sentence_groups = groups;
+        }
+
+        // Говорящие нужны для панорамы, а сами теги TTS зачитывать не должен
+        let speakers: Vec<Option<String>> = cues.iter()
+            .map(|cue| cue.speaker.clone().or_else(|| timeline::speaker_of(&cue.text)))
+            .collect();
+        for cue in cues.iter_mut() {
+            cue.text = timeline::strip_speaker_tags(&cue.text);
+        }
+        // Записи пользователя сопоставляем до перераспределения времени между репликами
+        let narrated: Vec<Option<PathBuf>> = cues.iter().map(|cue| {
+            config.narration.iter()
+                .find(|narration| (narration.cue_start - cue.start).abs() < 0.05)
+                .map(|narration| narration.path.clone())
+        }).collect();
+        let narrated_count = narrated.iter().filter(|path| path.is_some()).count();
+        if narrated_count > 0 {
+            info!("Реплик с озвучкой пользователя: {} из {}", narrated_count, cues.len());
+        }
+
+        // Подгонка под артикуляцию: окна реплик сужаются до речи в дорожке голоса оригинала.
+        // Субтитры под озвучку строятся по исходным окнам, смещение начала запоминается.
+        let subtitle_source: Vec<SubtitleCue> = display_cues.iter()
+            .map(|cue| SubtitleCue { text: timeline::strip_speaker_tags(&cue.text), ..cue.clone() })
+            .collect();
+        let mut lip_sync_offsets = vec![0.0f32; cues.len()];
+        // Реплики, целиком попавшие в проигрыш без речи, не озвучиваются
+        let mut in_music = vec![false; cues.len()];
+        let voice_activity = config.audio_config.voice_activity;
+        // Дорожки оригинала разделяются один раз на все шаги и повторные попытки
+        let stem_cache = super::demucs::StemCache::beside(config.output_wav);
+        match (config.audio_config.lip_sync || voice_activity.enabled, config.original_audio_path) {
+            (true, Some(orig_path)) => match vad::speech_in_vocals(orig_path, Some(&stem_cache)).await {
+                Ok(spans) => {
+                    if config.audio_config.lip_sync {
+                        let mut tightened = 0;
+                        for (cue, offset) in cues.iter_mut().zip(lip_sync_offsets.iter_mut()) {
+                            if let Some(window) = vad::speech_window(cue, &spans) {
+                                if window.start - cue.start > 0.01 || cue.end - window.end > 0.01 {
+                                    tightened += 1;
+                                }
+                                *offset = window.start - cue.start;
+                                cue.start = window.start;
+                                cue.end = window.end;
+                            }
+                        }
+                        info!("Подгонка под артикуляцию: окна {} из {} реплик сужены до фактической речи", tightened, cues.len());
+                    }
+                    if voice_activity.enabled {
+                        // Озвучка пользователя остается там, куда он ее записал
+                        let regions = vad::non_speech(&spans, voice_activity.min_gap);
+                        let mut clipped = 0;
+                        for (i, cue) in cues.iter_mut().enumerate().filter(|(i, _)| narrated[*i].is_none()) {
+                            match vad::outside_non_speech(cue, &regions) {
+                                Some(window) if window.start > cue.start || window.end < cue.end => {
+                                    clipped += 1;
+                                    lip_sync_offsets[i] += window.start - cue.start;
+                                    cue.start = window.start;
+                                    cue.end = window.end;
+                                }
+                                Some(_) => {}
+                                None => in_music[i] = true,
+                            }
+                        }
+                        info!(
+                            "Проигрышей без речи: {}, окна {} реплик обрезаны по ним, {} реплик не озвучиваются",
+                            regions.len(), clipped, in_music.iter().filter(|muted| **muted).count()
+                        );
+                    }
+                }
+                Err(e) => warn!("Не удалось найти речь в дорожке голоса: {}. Используем окна субтитров.", e),
+            },
+            (true, None) => warn!("Поиск речи в оригинале требует исходное аудио. Используем окна субтитров."),
+            (false, _) => {}
+        }
         
         // Анализируем субтитры на наличие проблемных сегментов
-        let analysis_config = SegmentAnalysisConfig::default();
+        let analysis_config = SegmentAnalysisConfig {
+            max_speed_factor: config.audio_config.max_tempo,
+            ..SegmentAnalysisConfig::default()
+        };
         let segment_analysis = analyze_segments(&cues, &analysis_config);
         
         // Логируем информацию о проблемах сегментах
@@ -1624,7 +5297,6 @@ pub mod synchronizer {
                                 
                                 // Обновляем карту свободного времени
                                 free_time_map[idx - 1] -= borrow_amount;
-                                borrowed_time += borrow_amount;
                                 
                                 info!("Сегмент #{}: заимствовано {:.2}s у предыдущего сегмента", idx, borrow_amount);
                             }
@@ -1645,43 +5317,152 @@ pub mod synchronizer {
         
         if !debug_dir.exists() {
             std::fs::create_dir_all(&debug_dir)
-                .map_err(|e| TtsError::IoError(e))?;
+                .map_err(TtsError::IoError)?;
             info!("Создана директория для отладочных MP3-файлов: {}", debug_dir.display());
         }
 
-        // 2. Генерация TTS для каждой реплики параллельно
-        let tts_futures = cues.iter().enumerate().map(|(i, cue)| {
-            let api_key = config.api_key;
+        // 2. Генерация речи для каждой реплики параллельно
+        let speech_provider: Arc<dyn provider::SpeechProvider> = match &config.speech_provider {
+            Some(speech_provider) => speech_provider.clone(),
+            None => Arc::new(provider::OpenAiTts::new(config.api_key, tts_config.clone())),
+        };
+        info!("Источник речи: {}", speech_provider.name());
+
+        // Исходная речь каждой реплики в WAV, если провайдер переводит речь в речь
+        let source_clips: Vec<Option<Vec<u8>>> = if speech_provider.needs_source_audio() {
+            let original = config.original_audio_path
+                .ok_or_else(|| TtsError::ConfigError("Для speech-to-speech нужен исходный аудиофайл".to_string()))?;
+            let (samples, sample_rate) = audio::decode_audio_file(original)?;
+            cues.iter().map(|cue| {
+                let start = ((cue.start.max(0.0) * sample_rate as f32) as usize).min(samples.len());
+                let end = ((cue.end.max(0.0) * sample_rate as f32) as usize).clamp(start, samples.len());
+                audio::encode_wav_bytes(&samples[start..end], sample_rate).ok()
+            }).collect()
+        } else {
+            vec![None; cues.len()]
+        };
+
+        // Без ограничения все запросы уходят сразу
+        let concurrency = crate::utils::advanced_config::current().concurrency;
+        let limit = match concurrency.tts_requests {
+            0 => cues.len().max(1),
+            limit => limit,
+        };
+        let (chunk_observers, chunk_sender) = (config.observers.clone(), config.progress_sender.clone());
+        // Запросы в минуту ограничиваются отдельно от числа одновременных
+        let rate_limit = match concurrency.tts_requests_per_minute {
+            0 => None,
+            per_minute => Some(Arc::new(crate::utils::rate_limit::TokenBucket::per_minute(per_minute, limit))),
+        };
+        // Синтез идет в отдельных задачах, чтобы обработка готового фрагмента
+        // не задерживала запросы в полете. Результаты приходят по одному каналу
+        // на реплику, задачи отменяются вместе с JoinSet при выходе с ошибкой.
+        let request_slots = Arc::new(tokio::sync::Semaphore::new(limit));
+        let mut tts_tasks = tokio::task::JoinSet::new();
+        let mut tts_results = Vec::with_capacity(cues.len());
+        for (i, (cue, source_clip)) in cues.iter().zip(source_clips).enumerate() {
+            let (result_tx, result_rx) = oneshot::channel();
+            tts_results.push(result_rx);
             let text = cue.text.clone();
-            let tts_config = &tts_config;
-            async move {
-                let res = tts::generate_tts(api_key, &text, tts_config).await;
-                (i, res)
-            }
-        });
-        let tts_results = join_all(tts_futures).await;
+            let speech_provider = speech_provider.clone();
+            let chunk_path = debug_dir.join(format!("{}.mp3", chunk_name(i, &text)));
+            // Текст, отличающийся от реплики (например, перевод от speech-to-speech), хранится рядом с чанком
+            let spoken_path = chunk_path.with_extension("txt");
+            let narration_path = narrated[i].clone();
+            let muted = in_music[i];
+            let speaker = speakers[i].clone();
+            let observers = chunk_observers.clone();
+            let progress_sender = chunk_sender.clone();
+            let rate_limit = rate_limit.clone();
+            let request_slots = request_slots.clone();
+            let synthesis = async move {
+                if muted {
+                    return Ok((Vec::new(), text));
+                }
+                // Запись пользователя не кэшируется как чанк TTS, чтобы после ее
+                // удаления реплика снова озвучивалась синтезом
+                if let Some(narration_path) = narration_path {
+                    return tokio::fs::read(&narration_path).await
+                        .map(|bytes| (bytes, text))
+                        .map_err(TtsError::IoError);
+                }
+                // Фрагменты, сохраненные до остановки (например, из-за исчерпанной
+                // квоты OpenAI), повторно не запрашиваем
+                if let Ok(bytes) = tokio::fs::read(&chunk_path).await
+                    && bytes.len() >= 100
+                {
+                    info!("Используем ранее сохраненный MP3-чанк №{}: {}", i, chunk_path.display());
+                    let spoken = tokio::fs::read_to_string(&spoken_path).await.unwrap_or(text);
+                    return Ok((bytes, spoken));
+                }
+                // Части аудио приходят чаще, чем их стоит ждать: канал не блокируется
+                let on_chunk = move |bytes: usize| {
+                    let update = ProgressUpdate::StreamingChunk { index: i, bytes };
+                    if let Some(observers) = &observers {
+                        observers.notify(&update);
+                    }
+                    if let Some(tx) = &progress_sender {
+                        let _ = tx.try_send(update);
+                    }
+                };
+                let request = provider::SpeechRequest {
+                    text: &text,
+                    source_audio: source_clip.as_deref(),
+                    speaker: speaker.as_deref(),
+                    on_chunk: Some(&on_chunk),
+                };
+                // Семафор не закрывается, пока живы задачи
+                let _slot = request_slots.acquire_owned().await
+                    .map_err(|e| TtsError::Other(anyhow::anyhow!(e)))?;
+                if let Some(rate_limit) = &rate_limit {
+                    rate_limit.acquire().await;
+                }
+                let res = speech_provider.synthesize(&request).await;
+                // Сохраняем сразу, чтобы готовые фрагменты пережили ошибку в соседних
+                if let Ok((bytes, spoken)) = &res {
+                    if let Err(e) = tokio::fs::write(&chunk_path, bytes).await {
+                        warn!("Не удалось сохранить MP3-чанк №{}: {}", i, e);
+                    }
+                    if spoken != &text {
+                        let _ = tokio::fs::write(&spoken_path, spoken).await;
+                    }
+                }
+                res
+            };
+            tts_tasks.spawn(async move {
+                let _ = result_tx.send(synthesis.await);
+            });
+        }
+        let mut tts_results = tts_results.into_iter();
         let mut audio_fragments = Vec::new();
+        // Для каждого фрагмента: записан ли он пользователем
+        let mut human_fragments = Vec::new();
+        let mut spoken_texts = Vec::with_capacity(cues.len());
+        // Окна реплик после Retime и конец последнего из них
+        let mut retimed_windows: Vec<(f32, f32)> = cues.iter().map(|cue| (cue.start, cue.end)).collect();
+        let mut retimed_end = 0.0f32;
 
         // 3. Обработка каждого аудиофрагмента
-        for (i, (cue, tts_result)) in cues.iter().zip(tts_results.into_iter()).enumerate() {
-            send_progress(&config.progress_sender, ProgressUpdate::TTSGeneration { current: i + 1, total: cues.len() }).await;
+        for (i, cue) in cues.iter().enumerate() {
+            // Результаты забираются по порядку, и фрагмент обрабатывается, как
+            // только готов, пока следующие еще синтезируются
+            let Some(tts_result) = tts_results.next() else { break };
+            let tts_result = tts_result.await
+                .map_err(|_| TtsError::Other(anyhow::anyhow!("Задача синтеза реплики №{} прервалась", i)))?;
+            send_progress(&config, ProgressUpdate::TTSGeneration { current: i + 1, total: cues.len() }).await;
             
             // Обрабатываем результат генерации TTS
-            let (audio_bytes, text) = tts_result.1?;
-            
-            // Сохраняем MP3-чанк на диск для отладки
-            let sanitized_text = text.chars()
-                .map(|c| if c.is_alphanumeric() || c == ' ' { c } else { '_' })
-                .collect::<String>()
-                .trim()
-                .to_string();
+            let (audio_bytes, text) = tts_result?;
+            spoken_texts.push(text.clone());
+            if in_music[i] {
+                info!("Реплика №{} целиком в проигрыше без речи, не озвучиваем: {}", i, text);
+                continue;
+            }
             
-            let chunk_name = format!("chunk_{:03}_{}", i, sanitized_text);
+            // MP3-чанк уже сохранен на диск при генерации
+            let chunk_name = chunk_name(i, &cue.text);
             let chunk_path = debug_dir.join(format!("{}.mp3", chunk_name));
-            std::fs::write(&chunk_path, &audio_bytes)
-                .map_err(|e| TtsError::IoError(e))?;
-            
-            info!("Сохранен MP3-чанк №{}: {} байт, путь: {}", i, audio_bytes.len(), chunk_path.display());
+            info!("MP3-чанк №{}: {} байт, путь: {}", i, audio_bytes.len(), chunk_path.display());
             
             // Проверяем размер аудио-чанка
             if audio_bytes.len() < 100 {
@@ -1690,10 +5471,16 @@ pub mod synchronizer {
                 let error_path = debug_dir.join(format!("{}_ERROR_TOO_SMALL.txt", chunk_name));
                 let error_info = format!("Слишком маленький размер MP3: {} байт\nТекст: {}", audio_bytes.len(), text);
                 std::fs::write(error_path, error_info)
-                    .map_err(|e| TtsError::IoError(e))?;
+                    .map_err(TtsError::IoError)?;
                 continue;
             }
             
+            // Длительность по заголовку MP3, чтобы заметить обрезанное декодирование
+            let header_duration = crate::utils::audio_probe::duration_from_bytes(&audio_bytes);
+            if header_duration.is_none() {
+                warn!("Не удалось прочитать заголовок MP3-чанка №{}, пробуем декодировать как есть", i);
+            }
+
             // Продолжаем обычную обработку
             let decode_result = audio::decode_mp3(&audio_bytes);
             let (pcm, sample_rate) = match decode_result {
@@ -1705,7 +5492,7 @@ pub mod synchronizer {
                     let error_info = format!("Ошибка декодирования: {}\nРазмер чанка: {} байт\nТекст: {}", 
                                            e, audio_bytes.len(), text);
                     std::fs::write(placeholder_path, error_info)
-                        .map_err(|e| TtsError::IoError(e))?;
+                        .map_err(TtsError::IoError)?;
                         
                     // Пропускаем этот фрагмент и продолжаем со следующим
                     continue;
@@ -1718,7 +5505,7 @@ pub mod synchronizer {
                 let error_path = debug_dir.join(format!("{}_ERROR_EMPTY_PCM.txt", chunk_name));
                 let error_info = format!("Пустое декодированное аудио\nРазмер MP3: {} байт\nТекст: {}", audio_bytes.len(), text);
                 std::fs::write(error_path, error_info)
-                    .map_err(|e| TtsError::IoError(e))?;
+                    .map_err(TtsError::IoError)?;
                 continue;
             }
             
@@ -1731,22 +5518,43 @@ pub mod synchronizer {
                 let warning_info = format!("Низкий уровень аудио: {:.6}\nРазмер MP3: {} байт\nТекст: {}", 
                                          max_amplitude, audio_bytes.len(), text);
                 std::fs::write(warning_path, warning_info)
-                    .map_err(|e| TtsError::IoError(e))?;
+                    .map_err(TtsError::IoError)?;
             }
             
             let actual_duration = audio::duration_in_seconds(pcm.len(), sample_rate);
-            let target_duration = cue.end - cue.start;
-            
-            // Получаем время начала следующего cue для расчета доступного дополнительного времени
             let next_cue_start = if i < cues.len() - 1 {
                 Some(cues[i + 1].start)
             } else {
                 None
             };
-
-            let available_extra_time = match next_cue_start {
-                Some(next_start) => next_start - cue.end,
-                None => 0.0,
+            // При Retime окно реплики подстраивается под речь, и время после него уже учтено
+            let (cue_start, cue_end) = match retime_drift {
+                Some(max_drift) => {
+                    let window = timeline::retime_window(cue, actual_duration, retimed_end, next_cue_start, max_drift);
+                    if (window.0 - cue.start).abs() > 0.01 || (window.1 - cue.end).abs() > 0.01 {
+                        info!("Retime реплики №{}: {:.2}-{:.2}s -> {:.2}-{:.2}s", i, cue.start, cue.end, window.0, window.1);
+                    }
+                    let sources = cue_sources.get(i).cloned().unwrap_or_else(|| vec![i]);
+                    if let Some(decision) = timeline::CueDecision::retime(sources, cue, window, actual_duration, retimed_end) {
+                        send_progress(&config, ProgressUpdate::CueDecision { decision }).await;
+                    }
+                    retimed_end = window.1;
+                    retimed_windows[i] = window;
+                    window
+                }
+                None => (cue.start, cue.end),
+            };
+            let target_duration = cue_end - cue_start;
+            if let Some(expected) = header_duration
+                && (actual_duration as f64) < expected * 0.8
+            {
+                warn!("MP3-чанк №{} декодирован не полностью: {:.3}s из {:.3}s. Текст: {}", i, actual_duration, expected, text);
+            }
+            
+            // Доступное дополнительное время до начала следующего cue
+            let available_extra_time = match (retime_drift, next_cue_start) {
+                (None, Some(next_start)) => next_start - cue.end,
+                _ => 0.0,
             };
             
             // Сохраняем WAV после декодирования для отладки
@@ -1756,7 +5564,7 @@ pub mod synchronizer {
             }
             
             send_progress(
-                &config.progress_sender,
+                &config,
                 ProgressUpdate::ProcessingFragment {
                     index: i + 1,
                     total: cues.len(),
@@ -1806,10 +5614,23 @@ pub mod synchronizer {
                 warn!("Пустой результат корректировки длительности для чанка №{}. Пропускаем фрагмент.", i);
                 let error_path = debug_dir.join(format!("{}_ERROR_EMPTY_ADJUSTED.txt", chunk_name));
                 std::fs::write(error_path, "Пустой результат корректировки длительности")
-                    .map_err(|e| TtsError::IoError(e))?;
+                    .map_err(TtsError::IoError)?;
                 continue;
             }
             
+            send_progress(
+                &config,
+                ProgressUpdate::SegmentTiming {
+                    index: i,
+                    start: cue.start,
+                    end: cue.end,
+                    text: cue.text.clone(),
+                    generated_duration: actual_duration,
+                    fitted_duration: audio::duration_in_seconds(adjusted.len(), sample_rate),
+                },
+            )
+            .await;
+
             // Сохраняем WAV после коррекции длительности для отладки
             let adjusted_wav_path = debug_dir.join(format!("{}_adjusted.wav", chunk_name));
             if let Err(e) = audio::encode_wav(&adjusted, sample_rate, adjusted_wav_path.to_str().unwrap()) {
@@ -1818,78 +5639,229 @@ pub mod synchronizer {
             
             // Создаем фрагмент с метаданными
             let fragment = AudioFragment {
+                cue_index: i,
                 samples: adjusted,
                 sample_rate,
                 text: text.clone(),
-                start_time: cue.start,
-                end_time: cue.start + used_duration,
+                start_time: cue_start,
+                end_time: cue_start + used_duration,
                 next_cue_start,
+                speaker: speakers[i].clone(),
             };
             
             // Добавляем фрагмент в итоговый набор
             info!("Успешно обработан чанк №{}: итоговая длина {} сэмплов, использованное время {:.3}s", 
                   i, fragment.samples.len(), used_duration);
             audio_fragments.push(fragment);
+            human_fragments.push(narrated[i].is_some());
+        }
+        drop(tts_results);
+        match_narration_loudness(&mut audio_fragments, &human_fragments);
+
+        if let (Some(path), Some(mut subtitle_cues)) = (output_vtt_path, subtitle_cues) {
+            if config.spoken_vtt_path.is_some() {
+                for (cue, text) in subtitle_cues.iter_mut().zip(spoken_texts) {
+                    cue.text = text;
+                }
+            }
+            if retime_drift.is_some() {
+                let windows: Vec<timeline::CueWindow> = sentence_groups.iter().zip(&retimed_windows)
+                    .flat_map(|(group, (start, end))| {
+                        let members = &display_cues[group[0]..=group[group.len() - 1]];
+                        timeline::spread_window(members, timeline::CueWindow { start: *start, end: *end })
+                    })
+                    .collect();
+                for (cue, window) in subtitle_cues.iter_mut().zip(windows) {
+                    cue.start = window.start;
+                    cue.end = window.end;
+                }
+            }
+            std::fs::write(path, vtt::write_vtt_str(&subtitle_cues))?;
+            info!("Субтитры с обновленными репликами сохранены: {}", path.display());
         }
 
         // 4. Склейка аудиофрагментов с учетом временных меток
-        send_progress(&config.progress_sender, ProgressUpdate::MergingFragments).await;
+        send_progress(&config, ProgressUpdate::MergingFragments).await;
         if audio_fragments.is_empty() {
             return Err(TtsError::AudioProcessingError("Нет аудиофрагментов для склейки".to_string()));
         }
         
-        let sample_rate = audio_fragments[0].sample_rate;
-        let mut final_audio = Vec::new();
-        let mut current_time = 0.0;
+        let mut sample_rate = audio_fragments[0].sample_rate;
         
         // Создаем информационный файл о каждом фрагменте
         let fragments_info_path = debug_dir.join("fragments_info.txt");
         let mut fragments_info = String::new();
-        fragments_info.push_str("Информация об аудиофрагментах:\n\n");
+        fragments_info.push_str(&format!("Информация об аудиофрагментах (политика пересечений: {:?}):\n\n", overlap_policy));
         
         for fragment in audio_fragments.iter() {
-            // Добавляем тишину, если есть пробел до начала текущего фрагмента
-            if fragment.start_time > current_time {
-                let silence_duration = fragment.start_time - current_time;
-                let silence_samples = (silence_duration * sample_rate as f32).round() as usize;
-                final_audio.extend(vec![0.0; silence_samples]);
-                info!("Добавлено {:.3}s тишины перед фрагментом, начинающимся в {:.3}s", 
-                      silence_duration, fragment.start_time);
-            }
-            
-            // Добавляем сам фрагмент
-            final_audio.extend_from_slice(&fragment.samples);
-            current_time = fragment.end_time;
-            
-            // Добавляем информацию о фрагменте
             let frag_info = format!(
-                "Фрагмент: start={:.3}s, end={:.3}s, duration={:.3}s, samples={}, text: {}\n",
+                "Фрагмент: start={:.3}s, end={:.3}s, duration={:.3}s, samples={}, speaker: {}, text: {}\n",
                 fragment.start_time,
                 fragment.end_time,
                 fragment.end_time - fragment.start_time,
                 fragment.samples.len(),
+                fragment.speaker.as_deref().unwrap_or("-"),
                 fragment.text
             );
             fragments_info.push_str(&frag_info);
         }
+
+        let timeline_fragments: Vec<timeline::TimelineFragment> = audio_fragments
+            .iter()
+            .map(|f| timeline::TimelineFragment {
+                samples: &f.samples,
+                start_time: f.start_time,
+                speaker: f.speaker.as_deref(),
+            })
+            .collect();
+        let (mut final_audio, channels) = timeline::assemble(&timeline_fragments, sample_rate, overlap_policy);
+
+        // Субтитры переезжают вслед за речью, которую не удалось уложить в реплики
+        if let Some(path) = config.retimed_vtt_path {
+            let mut subtitle_cues = subtitle_source;
+            let mut placed = vec![None; subtitle_cues.len()];
+            let windows = timeline::placements(&timeline_fragments, sample_rate, overlap_policy);
+            for (fragment, window) in audio_fragments.iter().zip(windows) {
+                let offset = lip_sync_offsets[fragment.cue_index];
+                let window = timeline::CueWindow { start: window.start - offset, end: window.end - offset };
+                let group = &sentence_groups[fragment.cue_index];
+                // Текст объединенного предложения по репликам не разделить, он остается исходным
+                if group.len() == 1 {
+                    subtitle_cues[group[0]].text = fragment.text.clone();
+                }
+                let members = &display_cues[group[0]..=group[group.len() - 1]];
+                for (index, member_window) in group.iter().zip(timeline::spread_window(members, window)) {
+                    placed[*index] = Some(member_window);
+                }
+            }
+            let (retimed, moved) = timeline::retime_subtitles_to_audio(&subtitle_cues, &placed);
+            if moved > 0 {
+                std::fs::write(path, vtt::write_vtt_str(&retimed))?;
+                info!("Субтитры под фактическую озвучку ({} перенесенных реплик) сохранены: {}", moved, path.display());
+            } else if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
         
         std::fs::write(fragments_info_path, fragments_info)
-            .map_err(|e| TtsError::IoError(e))?;
+            .map_err(TtsError::IoError)?;
+
+        // В потоковом режиме места на диске мало, полноразмерные отладочные WAV не пишем
+        let keep_full_debug = config.stream_to.is_none();
 
         // Сохраняем сырой склеенный аудиофайл перед нормализацией
         let merged_wav_path = debug_dir.join("merged_raw.wav");
-        if let Err(e) = audio::encode_wav(&final_audio, sample_rate, merged_wav_path.to_str().unwrap()) {
+        if !keep_full_debug {
+            debug!("Потоковый режим: пропускаем сохранение {}", merged_wav_path.display());
+        } else if let Err(e) = audio::encode_wav_channels(&final_audio, sample_rate, channels, merged_wav_path.to_str().unwrap()) {
             warn!("Не удалось сохранить сырой склеенный WAV: {}", e);
         }
 
-        // 5. Нормализация громкости.
+        // 5. Микширование с инструментальной дорожкой оригинала.
+        // Нормализация в этом случае применяется к суммарному миксу, а не к TTS отдельно.
+        let mut mixed_with_background = false;
+        if let (true, Some(orig_path)) = (config.audio_config.mix_with_background, config.original_audio_path) {
+            let (instrumental_path, separated) = match config.instrumental_path {
+                Some(path) => {
+                    info!("Используется готовая инструментальная дорожка {}", path.display());
+                    (path.to_path_buf(), Ok(()))
+                }
+                None => {
+                    info!("Создание инструментальной версии из оригинального аудио...");
+                    let path = debug_dir.join("instrumental.wav");
+                    let separated = super::audio::remove_vocals(orig_path, &path, Some(demucs_tx), Some(&debug_dir), Some(&stem_cache)).await;
+                    (path, separated)
+                }
+            };
+
+            if let Err(e) = separated {
+                warn!("Не удалось создать инструментальную дорожку: {}. Продолжаем без нее.", e);
+            } else {
+                match audio::decode_audio_file(&instrumental_path) {
+                    Ok((instrumental_audio, instrumental_rate)) => {
+                        // Формат проекта определяется оригинальной дорожкой, все шины приводятся к нему
+                        let original_format = match crate::utils::audio_probe::audio_format(orig_path).await {
+                            Ok(format) => Some(format),
+                            Err(e) => {
+                                warn!("Не удалось определить формат оригинальной дорожки: {}", e);
+                                None
+                            }
+                        };
+                        let project_rate = audio::negotiate_sample_rate(original_format.as_ref(), sample_rate);
+                        info!("Частота проекта: {} Hz (оригинал: {:?})", project_rate, original_format);
+
+                        let mut conversions = Vec::new();
+                        let mut convert = |bus: &str, samples: &[f32], bus_channels: u16, from_rate: u32| -> Result<Vec<f32>> {
+                            if from_rate == project_rate {
+                                return Ok(samples.to_vec());
+                            }
+                            info!("Шина {}: {} Hz -> {} Hz", bus, from_rate, project_rate);
+                            conversions.push(audio::BusConversion {
+                                bus: bus.to_string(),
+                                from_sample_rate: from_rate,
+                                to_sample_rate: project_rate,
+                            });
+                            audio::resample(samples, bus_channels, from_rate, project_rate)
+                        };
+                        let converted = convert("voice", &final_audio, channels, sample_rate)
+                            .and_then(|voice| Ok((voice, convert("instrumental", &instrumental_audio, 1, instrumental_rate)?)));
+
+                        match converted {
+                            Err(e) => warn!("Не удалось привести шины к частоте проекта: {}. Пропускаем микширование.", e),
+                            Ok((voice_bus, instrumental_audio)) => {
+                                final_audio = voice_bus;
+                                sample_rate = project_rate;
+                                send_progress(&config, ProgressUpdate::Normalizing { using_original: true }).await;
+                                info!("Микширование TTS с инструментальной дорожкой...");
+
+                                // Уровень оригинала служит целевым уровнем для суммарного микса
+                                let target_rms = match audio::decode_audio_file(orig_path) {
+                                    Ok((orig_samples, _)) if !orig_samples.is_empty() => Some(audio::compute_rms(&orig_samples)),
+                                    _ => None,
+                                };
+
+                                let (mixed, mut report) = audio::mix_buses(
+                                    &final_audio,
+                                    &instrumental_audio,
+                                    sample_rate,
+                                    channels,
+                                    &config.audio_config,
+                                    target_rms,
+                                );
+                                report.original_format = original_format;
+                                report.conversions = conversions;
+                                info!("Микширование: голос {:.1} dB RMS, инструментал {:.1} dB RMS, мастер {:.1} dB RMS / {:.1} dB peak",
+                                      report.voice.rms_db, report.instrumental.rms_db, report.master.rms_db, report.master.peak_db);
+
+                                // QC-отчет ложится рядом с фрагментами, запись задачи сохраняет его вместе с ними
+                                match serde_json::to_string_pretty(&report) {
+                                    Ok(json) => {
+                                        if let Err(e) = std::fs::write(debug_dir.join("mix_report.json"), json) {
+                                            warn!("Не удалось сохранить отчет о микшировании: {}", e);
+                                        }
+                                    }
+                                    Err(e) => warn!("Не удалось сериализовать отчет о микшировании: {}", e),
+                                }
+
+                                final_audio = mixed;
+                                mixed_with_background = true;
+                            }
+                        }
+                    },
+                    Err(e) => warn!("Не удалось декодировать инструментальную дорожку: {}. Продолжаем без микширования.", e),
+                }
+            }
+        }
+
+        // 6. Нормализация громкости (только без микширования).
         // Если указан путь к исходному аудио, анализируем его уровень и приводим итоговое аудио к такому же уровню.
-        let using_original = config.original_audio_path.is_some();
-        send_progress(&config.progress_sender, ProgressUpdate::Normalizing { using_original }).await;
-        
-        let mut normalization_applied = false;
+        let mut normalization_applied = mixed_with_background;
+        if !mixed_with_background {
+            let using_original = config.original_audio_path.is_some();
+            send_progress(&config, ProgressUpdate::Normalizing { using_original }).await;
+        }
         
-        if let Some(orig_path) = config.original_audio_path {
+        if let (false, Some(orig_path)) = (normalization_applied, config.original_audio_path) {
             // Декодируем исходное аудио с помощью улучшенной функции
             match audio::decode_audio_file(orig_path) {
                 Ok((orig_samples, _)) => {
@@ -1910,7 +5882,9 @@ pub mod synchronizer {
                             
                             // Сохраняем нормализованный аудиофайл
                             let norm_orig_wav_path = debug_dir.join("normalized_by_original.wav");
-                            if let Err(e) = audio::encode_wav(&final_audio, sample_rate, norm_orig_wav_path.to_str().unwrap()) {
+                            if keep_full_debug
+                                && let Err(e) = audio::encode_wav_channels(&final_audio, sample_rate, channels, norm_orig_wav_path.to_str().unwrap())
+                            {
                                 warn!("Не удалось сохранить нормализованный WAV (по оригиналу): {}", e);
                             }
                         } else {
@@ -1926,21 +5900,18 @@ pub mod synchronizer {
         }
         
         // Если нормализация по оригинальному аудио не была выполнена, 
-        // используем стандартную нормализацию к целевому уровню
+        // нормализуем громкость к цели EBU R128
         if !normalization_applied {
-            // Стандартная нормализация к заданному целевому уровню
             let max_amp = final_audio.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
             if max_amp > 0.0 {
-                let norm_factor = config.audio_config.target_peak_level / max_amp;
-                info!("Используем стандартную нормализацию: max_amp = {:.6}, коэффициент = {:.6}", max_amp, norm_factor);
-                for s in final_audio.iter_mut() {
-                    *s *= norm_factor;
-                }
-                normalization_applied = true;
+                let report = loudness::normalize(&mut final_audio, sample_rate, channels, &config.audio_config.loudness);
+                info!("Нормализация к {:.1} LUFS: усиление {:.1} дБ", config.audio_config.loudness.integrated_lufs, report.gain_db);
                 
                 // Сохраняем нормализованный аудиофайл
                 let norm_std_wav_path = debug_dir.join("normalized_standard.wav");
-                if let Err(e) = audio::encode_wav(&final_audio, sample_rate, norm_std_wav_path.to_str().unwrap()) {
+                if keep_full_debug
+                    && let Err(e) = audio::encode_wav_channels(&final_audio, sample_rate, channels, norm_std_wav_path.to_str().unwrap())
+                {
                     warn!("Не удалось сохранить нормализованный WAV (стандартный): {}", e);
                 }
             } else {
@@ -1962,18 +5933,30 @@ pub mod synchronizer {
 
         // Сохраняем финальное аудио перед кодированием для отладки
         let final_debug_wav_path = debug_dir.join("final_before_encoding.wav");
-        if let Err(e) = audio::encode_wav(&final_audio, sample_rate, final_debug_wav_path.to_str().unwrap()) {
+        if !keep_full_debug {
+            debug!("Потоковый режим: пропускаем сохранение {}", final_debug_wav_path.display());
+        } else if let Err(e) = audio::encode_wav_channels(&final_audio, sample_rate, channels, final_debug_wav_path.to_str().unwrap()) {
             warn!("Не удалось сохранить финальный WAV для отладки: {}", e);
         } else {
             info!("Сохранен финальный WAV для отладки: {}", final_debug_wav_path.display());
         }
 
-        // 6. Кодирование финального аудио в WAV.
-        send_progress(&config.progress_sender, ProgressUpdate::Encoding).await;
+        // 7. Кодирование финального аудио в WAV или передача его потребителю без записи на диск.
+        send_progress(&config, ProgressUpdate::Encoding).await;
+        if let Some(stream_to) = config.stream_to.take() {
+            info!("Передача итогового аудио без записи на диск. Сэмплов: {}, частота: {} Гц, каналов: {}",
+                  final_audio.len(), sample_rate, channels);
+            stream_to
+                .send(audio::RenderedAudio { samples: final_audio, sample_rate, channels })
+                .map_err(|_| TtsError::AudioProcessingError("Получатель итогового аудио закрыт".to_string()))?;
+            send_progress(&config, ProgressUpdate::Finished).await;
+            return Ok(());
+        }
+
         info!("Кодирование финального аудио в WAV. Сэмплов: {}, частота: {} Гц, макс.амплитуда: {:.6}", 
               final_audio.len(), sample_rate, max_amp_final);
         
-        match audio::encode_wav(&final_audio, sample_rate, config.output_wav.to_str().unwrap()) {
+        match audio::encode_wav_channels(&final_audio, sample_rate, channels, config.output_wav.to_str().unwrap()) {
             Ok(_) => {
                 info!("Успешно закодирован WAV-файл: {}", config.output_wav.display());
             },
@@ -2007,61 +5990,13 @@ pub mod synchronizer {
             info!("Создана копия итогового файла: {}", final_copy_path.display());
         }
 
-        send_progress(&config.progress_sender, ProgressUpdate::Finished).await;
+        send_progress(&config, ProgressUpdate::Finished).await;
         println!(
             "Итоговой аудиофайл записан: {} (размер: {} байт)",
             config.output_wav.display(),
             output_metadata.len()
         );
 
-        // Если есть оригинальное аудио, создаем инструментальную версию и микшируем
-        if let Some(orig_path) = config.original_audio_path {
-            info!("Создание инструментальной версии из оригинального аудио...");
-            
-            // Создаем временный файл для инструментальной дорожки
-            let instrumental_path = debug_dir.join("instrumental.wav");
-            
-            // Удаляем вокал из оригинального аудио
-            if let Err(e) = super::audio::remove_vocals(orig_path, &instrumental_path, Some(demucs_tx), Some(&debug_dir)).await {
-                warn!("Не удалось создать инструментальную дорожку: {}. Продолжаем без нее.", e);
-            } else {
-                // Декодируем инструментальную дорожку
-                match audio::decode_audio_file(&instrumental_path) {
-                    Ok((instrumental_audio, instrumental_rate)) => {
-                        if instrumental_rate != sample_rate {
-                            warn!("Частота дискретизации инструментальной дорожки ({} Hz) отличается от TTS ({} Hz). Пропускаем микширование.", 
-                                  instrumental_rate, sample_rate);
-                        } else {
-                            info!("Микширование TTS с инструментальной дорожкой...");
-                            
-                            // Микшируем дорожки
-                            final_audio = audio::mix_audio_tracks(
-                                &final_audio,
-                                &instrumental_audio,
-                                config.audio_config.voice_to_instrumental_ratio,
-                                config.audio_config.instrumental_boost
-                            );
-                            
-                            // Сохраняем микшированную версию для отладки
-                            let mixed_debug_path = debug_dir.join("final_mixed.wav");
-                            if let Err(e) = audio::encode_wav(&final_audio, sample_rate, mixed_debug_path.to_str().unwrap()) {
-                                warn!("Не удалось сохранить микшированный WAV для отладки: {}", e);
-                            }
-
-                            // Сохраняем финальный микшированный результат
-                            info!("Сохранение финального микшированного аудио...");
-                            if let Err(e) = audio::encode_wav(&final_audio, sample_rate, config.output_wav.to_str().unwrap()) {
-                                error!("Ошибка при сохранении финального микшированного WAV: {}", e);
-                                return Err(e.into());
-                            }
-                            info!("Финальное микшированное аудио успешно сохранено: {}", config.output_wav.display());
-                        }
-                    },
-                    Err(e) => warn!("Не удалось декодировать инструментальную дорожку: {}. Продолжаем без микширования.", e),
-                }
-            }
-        }
-
         Ok(())
     }
 }
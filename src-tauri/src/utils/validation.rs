@@ -0,0 +1,158 @@
+//! Validation of command input.
+//!
+//! Commands that take a request struct check it up front and report every
+//! problem at once as a `CommandError::Validation` with the name of the
+//! offending field, so the frontend can show each message next to its input.
+//! Failures after validation keep the plain message they always had.
+
+use serde::Serialize;
+use std::path::Path;
+
+/// A problem with one input field
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FieldError {
+    /// Field name as in the request, e.g. "target_language"
+    pub field: String,
+    pub message: String,
+}
+
+/// Error returned by commands with validated input
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CommandError {
+    #[error("Invalid input: {}", describe(.errors))]
+    Validation { errors: Vec<FieldError> },
+    #[error("{message}")]
+    Failed { message: String },
+}
+
+fn describe(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("{}: {}", error.field, error.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::Failed { message }
+    }
+}
+
+/// Request structs that can check themselves
+pub trait Validate {
+    fn validate(&self) -> Result<(), CommandError>;
+}
+
+/// ISO 639-1 language codes
+const ISO_639_1: &[&str] = &[
+    "aa", "ab", "ae", "af", "ak", "am", "an", "ar", "as", "av", "ay", "az", "ba", "be", "bg", "bh", "bi", "bm", "bn",
+    "bo", "br", "bs", "ca", "ce", "ch", "co", "cr", "cs", "cu", "cv", "cy", "da", "de", "dv", "dz", "ee", "el", "en",
+    "eo", "es", "et", "eu", "fa", "ff", "fi", "fj", "fo", "fr", "fy", "ga", "gd", "gl", "gn", "gu", "gv", "ha", "he",
+    "hi", "ho", "hr", "ht", "hu", "hy", "hz", "ia", "id", "ie", "ig", "ii", "ik", "io", "is", "it", "iu", "ja", "jv",
+    "ka", "kg", "ki", "kj", "kk", "kl", "km", "kn", "ko", "kr", "ks", "ku", "kv", "kw", "ky", "la", "lb", "lg", "li",
+    "ln", "lo", "lt", "lu", "lv", "mg", "mh", "mi", "mk", "ml", "mn", "mr", "ms", "mt", "my", "na", "nb", "nd", "ne",
+    "ng", "nl", "nn", "no", "nr", "nv", "ny", "oc", "oj", "om", "or", "os", "pa", "pi", "pl", "ps", "pt", "qu", "rm",
+    "rn", "ro", "ru", "rw", "sa", "sc", "sd", "se", "sg", "si", "sk", "sl", "sm", "sn", "so", "sq", "sr", "ss", "st",
+    "su", "sv", "sw", "ta", "te", "tg", "th", "ti", "tk", "tl", "tn", "to", "tr", "ts", "tt", "tw", "ty", "ug", "uk",
+    "ur", "uz", "ve", "vi", "vo", "wa", "wo", "xh", "yi", "yo", "za", "zh", "zu",
+];
+
+/// Whether a code is an ISO 639-1 language, optionally with a region or script
+/// subtag like "pt-BR" or "zh-Hans"
+pub fn is_language_code(code: &str) -> bool {
+    let mut parts = code.split(['-', '_']);
+    let primary = parts.next().unwrap_or_default();
+    ISO_639_1.contains(&primary)
+        && parts.all(|subtag| (2..=4).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Collects the field errors of a request
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an error unless the condition holds
+    pub fn check(&mut self, field: &str, ok: bool, message: &str) -> &mut Self {
+        if !ok {
+            self.errors.push(FieldError { field: field.to_string(), message: message.to_string() });
+        }
+        self
+    }
+
+    pub fn not_empty(&mut self, field: &str, value: &str) -> &mut Self {
+        self.check(field, !value.trim().is_empty(), "Must not be empty")
+    }
+
+    /// An absolute http(s) URL
+    pub fn url(&mut self, field: &str, value: &str) -> &mut Self {
+        let valid = url::Url::parse(value.trim())
+            .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some());
+        self.check(field, valid, "Must be an http or https link")
+    }
+
+    pub fn language(&mut self, field: &str, code: &str) -> &mut Self {
+        self.check(field, is_language_code(code), "Unknown language code")
+    }
+
+    /// A directory that exists, or can be created because its parent exists
+    pub fn output_dir(&mut self, field: &str, path: &str) -> &mut Self {
+        let path = Path::new(path);
+        let usable = path.is_dir() || (!path.exists() && path.parent().is_some_and(Path::is_dir));
+        self.check(field, !path.as_os_str().is_empty() && usable, "Folder does not exist")
+    }
+
+    pub fn existing_file(&mut self, field: &str, path: &str) -> &mut Self {
+        self.check(field, Path::new(path).is_file(), "File does not exist")
+    }
+
+    pub fn finish(&mut self) -> Result<(), CommandError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(CommandError::Validation { errors: std::mem::take(&mut self.errors) })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_codes_follow_iso_639_1() {
+        assert!(is_language_code("en"));
+        assert!(is_language_code("pt-BR"));
+        assert!(is_language_code("zh-Hans"));
+        assert!(!is_language_code("english"));
+        assert!(!is_language_code("xx"));
+        assert!(!is_language_code("en-"));
+    }
+
+    #[test]
+    fn errors_are_collected_per_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("a").join("b");
+        let result = Validator::new()
+            .url("url", "https://www.youtube.com/watch?v=abc")
+            .url("link", "youtube.com/watch")
+            .language("target_language", "ru")
+            .output_dir("output_path", &dir.path().join("new").to_string_lossy())
+            .output_dir("other_path", &missing.to_string_lossy())
+            .finish();
+        let Err(CommandError::Validation { errors }) = result else { panic!("expected validation errors") };
+        let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+        assert_eq!(fields, vec!["link", "other_path"]);
+
+        let json = serde_json::to_value(CommandError::Validation { errors }).unwrap();
+        assert_eq!(json["kind"], "validation");
+        assert_eq!(json["errors"][0]["field"], "link");
+    }
+}
@@ -0,0 +1,128 @@
+//! Favorite and recently used voices, per speech engine.
+//!
+//! Every engine has its own voice names, so preferences are kept per engine
+//! id in the settings store. Voices the user previews or dubs with go to the
+//! front of the recent list; favorites are toggled explicitly. The voice
+//! picker lists favorites first, then recents, then the remaining voices in
+//! the engine's own order.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::utils::settings;
+use crate::utils::tts::tts::provider::EngineCapabilities;
+
+const SETTINGS_KEY: &str = "voice-preferences";
+/// Length of the recent list of each engine
+const MAX_RECENT: usize = 8;
+
+/// Preferences of one engine
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct EngineVoices {
+    pub favorites: Vec<String>,
+    /// Most recent first
+    pub recent: Vec<String>,
+}
+
+impl EngineVoices {
+    fn set_favorite(&mut self, voice: &str, favorite: bool) {
+        self.favorites.retain(|existing| existing != voice);
+        if favorite {
+            self.favorites.push(voice.to_string());
+        }
+    }
+
+    fn record_use(&mut self, voice: &str) {
+        self.recent.retain(|existing| existing != voice);
+        self.recent.insert(0, voice.to_string());
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// `voices` reordered for the picker, unknown names are dropped
+    fn order(&self, voices: &[String]) -> Vec<String> {
+        let mut ordered: Vec<String> = Vec::with_capacity(voices.len());
+        for voice in self.favorites.iter().chain(&self.recent).chain(voices) {
+            if voices.contains(voice) && !ordered.contains(voice) {
+                ordered.push(voice.clone());
+            }
+        }
+        ordered
+    }
+}
+
+/// Preferences keyed by engine id
+pub type VoicePreferences = HashMap<String, EngineVoices>;
+
+/// An engine with its voices ordered for the picker
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineVoiceChoice {
+    #[serde(flatten)]
+    pub engine: EngineCapabilities,
+    pub favorites: Vec<String>,
+    pub recent: Vec<String>,
+}
+
+pub fn load(app_handle: &tauri::AppHandle) -> VoicePreferences {
+    settings::get(app_handle, SETTINGS_KEY).unwrap_or_default()
+}
+
+/// Mark or unmark a voice as favorite, returns the engine's preferences
+pub async fn set_favorite(app_handle: &tauri::AppHandle, engine: &str, voice: &str, favorite: bool) -> Result<EngineVoices> {
+    let preferences: VoicePreferences = settings::update(app_handle, SETTINGS_KEY, |preferences: &mut VoicePreferences| {
+        preferences.entry(engine.to_string()).or_default().set_favorite(voice, favorite);
+    })
+    .await?;
+    Ok(preferences.get(engine).cloned().unwrap_or_default())
+}
+
+/// Put a voice the user previewed or dubbed with at the front of the recents
+pub async fn record_use(app_handle: &tauri::AppHandle, engine: &str, voice: &str) -> Result<()> {
+    settings::update(app_handle, SETTINGS_KEY, |preferences: &mut VoicePreferences| {
+        preferences.entry(engine.to_string()).or_default().record_use(voice);
+    })
+    .await?;
+    Ok(())
+}
+
+/// Engines with their voices ordered by the user's preferences
+pub fn with_preferences(engines: Vec<EngineCapabilities>, preferences: &VoicePreferences) -> Vec<EngineVoiceChoice> {
+    engines
+        .into_iter()
+        .map(|mut engine| {
+            let prefs = preferences.get(&engine.id).cloned().unwrap_or_default();
+            engine.voices = prefs.order(&engine.voices);
+            // Voices the engine no longer offers are hidden, not forgotten
+            let favorites = prefs.favorites.iter().filter(|voice| engine.voices.contains(voice)).cloned().collect();
+            let recent = prefs.recent.iter().filter(|voice| engine.voices.contains(voice)).cloned().collect();
+            EngineVoiceChoice { engine, favorites, recent }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picker_order() {
+        let voices: Vec<String> = ["alloy", "ash", "coral", "echo", "sage"].iter().map(|v| v.to_string()).collect();
+        let mut prefs = EngineVoices::default();
+        prefs.record_use("echo");
+        prefs.record_use("ash");
+        prefs.record_use("echo");
+        prefs.set_favorite("sage", true);
+        prefs.set_favorite("retired", true);
+        assert_eq!(prefs.recent, vec!["echo", "ash"]);
+        assert_eq!(prefs.order(&voices), vec!["sage", "echo", "ash", "alloy", "coral"]);
+
+        prefs.set_favorite("sage", false);
+        assert_eq!(prefs.order(&voices)[0], "echo");
+        for voice in ["a", "b", "c", "d", "e", "f", "g", "h", "i"] {
+            prefs.record_use(voice);
+        }
+        assert_eq!(prefs.recent.len(), MAX_RECENT);
+        assert_eq!(prefs.recent[0], "i");
+    }
+}
@@ -15,9 +15,13 @@ use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio_util::sync::CancellationToken;
 use tauri::Emitter;
 use tauri::Manager;
-use tauri_plugin_store::StoreExt;
 
+use super::advanced_config;
+use super::chapters::{self, Chapter};
+use super::cookies::{self, CookieError, CookieSource};
+use super::subtitles::{self, SubtitleSource};
 use super::tools::get_tool_path;
+use super::settings;
 use crate::utils::common::{sanitize_filename, check_file_exists_and_valid};
 
 // Structure for storing YouTube cookies
@@ -26,6 +30,18 @@ pub struct YoutubeCookies {
     pub browser: String,
     pub last_used: String, // ISO timestamp
     pub valid: bool,
+    /// Imported cookies.txt, used instead of the browser when set
+    #[serde(default)]
+    pub cookies_file: Option<PathBuf>,
+}
+
+impl YoutubeCookies {
+    pub fn source(&self) -> CookieSource {
+        match &self.cookies_file {
+            Some(path) => CookieSource::File(path.clone()),
+            None => CookieSource::Browser(self.browser.clone()),
+        }
+    }
 }
 
 // Cookie manager for YouTube
@@ -33,9 +49,8 @@ pub struct YoutubeCookieManager {}
 
 impl YoutubeCookieManager {
     // Save cookies to the store
-    pub async fn save_cookies(app_handle: &tauri::AppHandle, browser: &str, valid: bool) -> Result<()> {
-        info!("Saving YouTube cookies from browser: {}", browser);
-        let store = app_handle.store(".settings.dat")?;
+    pub async fn save_cookies(app_handle: &tauri::AppHandle, source: &CookieSource, valid: bool) -> Result<()> {
+        info!("Saving YouTube cookies from: {}", source.name());
         
         // Get current time as ISO string
         let now = std::time::SystemTime::now();
@@ -44,18 +59,16 @@ impl YoutubeCookieManager {
         let timestamp = format!("{}", datetime.as_secs());
         
         let cookies = YoutubeCookies {
-            browser: browser.to_string(),
+            browser: source.name(),
             last_used: timestamp,
             valid,
+            cookies_file: match source {
+                CookieSource::File(path) => Some(path.clone()),
+                _ => None,
+            },
         };
         
-        // Convert to JSON value
-        let json_value = serde_json::to_value(&cookies)
-            .map_err(|e| anyhow!("Failed to serialize YouTube cookies: {}", e))?;
-        
-        store.set("youtube-cookies", json_value);
-        
-        store.save()
+        settings::set(app_handle, "youtube-cookies", &cookies).await
             .map_err(|e| anyhow!("Failed to persist YouTube cookies: {}", e))?;
         
         debug!("YouTube cookies saved successfully");
@@ -65,24 +78,7 @@ impl YoutubeCookieManager {
     // Load cookies from the store
     pub async fn load_cookies(app_handle: &tauri::AppHandle) -> Result<Option<YoutubeCookies>> {
         debug!("Loading YouTube cookies from store");
-        let store = app_handle.store(".settings.dat")?;
-        
-        // Get the value as a JSON value from the store
-        let json_value = store.get("youtube-cookies");
-        
-        // Convert from JSON value to our type if it exists
-        let cookies = match json_value {
-            Some(value) => {
-                match serde_json::from_value::<YoutubeCookies>(value) {
-                    Ok(cookies) => Some(cookies),
-                    Err(e) => {
-                        error!("Failed to deserialize YouTube cookies: {}", e);
-                        None
-                    }
-                }
-            },
-            None => None,
-        };
+        let cookies: Option<YoutubeCookies> = settings::get(app_handle, "youtube-cookies");
         
         if let Some(cookies) = &cookies {
             debug!("Found cookies from browser: {}, last used: {}", 
@@ -97,19 +93,15 @@ impl YoutubeCookieManager {
     // Mark cookies as invalid
     pub async fn invalidate_cookies(app_handle: &tauri::AppHandle) -> Result<()> {
         debug!("Invalidating YouTube cookies");
-        if let Ok(Some(mut cookies)) = Self::load_cookies(app_handle).await {
-            cookies.valid = false;
-            
-            let store = app_handle.store(".settings.dat")?;
-            
-            // Convert to JSON value
-            let json_value = serde_json::to_value(&cookies)
-                .map_err(|e| anyhow!("Failed to serialize YouTube cookies: {}", e))?;
-            
-            store.set("youtube-cookies", json_value);
-            
-            store.save()
-                .map_err(|e| anyhow!("Failed to persist YouTube cookie changes: {}", e))?;
+        if let Ok(Some(_)) = Self::load_cookies(app_handle).await {
+            // Update in place so a concurrent save_cookies isn't overwritten with stale data
+            settings::update(app_handle, "youtube-cookies", |cookies: &mut Option<YoutubeCookies>| {
+                if let Some(cookies) = cookies {
+                    cookies.valid = false;
+                }
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to persist YouTube cookie changes: {}", e))?;
             
             debug!("YouTube cookies marked as invalid");
         }
@@ -120,6 +112,8 @@ impl YoutubeCookieManager {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VideoInfo {
+    #[serde(default)]
+    pub id: String,                    // YouTube video id
     pub title: String,
     pub duration: f64,
     pub url: String,
@@ -127,6 +121,10 @@ pub struct VideoInfo {
     pub description: String,
     pub language: Option<String>,      // Язык видео
     pub original_language: Option<String>, // Оригинальный язык видео
+    #[serde(default)]
+    pub subtitles: Vec<SubtitleSource>,    // Готовые субтитры, которые можно использовать вместо Whisper
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,            // Главы видео
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -169,9 +167,39 @@ async fn show_keychain_info_dialog(window: &tauri::Window) {
 pub async fn download_video(
     url: &str,
     output_dir: &PathBuf,
+    section: Option<(f64, f64)>,
     progress_sender: Option<mpsc::Sender<DownloadProgress>>,
     cancellation_token: CancellationToken,
     window: &tauri::Window,
+) -> Result<DownloadResult> {
+    // Get video info first to get the title
+    info!("Fetching video information...");
+    let video_info = get_video_info(url, window).await?;
+    download_video_with_info(url, &video_info, output_dir, section, progress_sender, cancellation_token).await
+}
+
+/// yt-dlp arguments limiting a download to a start/end span in seconds.
+/// Keyframes are forced at the cuts so the clip starts where asked.
+fn section_args(section: Option<(f64, f64)>) -> Vec<String> {
+    match section {
+        Some((start, end)) => vec![
+            "--download-sections".to_string(),
+            format!("*{:.3}-{:.3}", start, end),
+            "--force-keyframes-at-cuts".to_string(),
+        ],
+        None => Vec::new(),
+    }
+}
+
+/// Download a video whose info is already known, without the app window.
+/// With a section only that span is downloaded.
+pub async fn download_video_with_info(
+    url: &str,
+    video_info: &VideoInfo,
+    output_dir: &PathBuf,
+    section: Option<(f64, f64)>,
+    progress_sender: Option<mpsc::Sender<DownloadProgress>>,
+    cancellation_token: CancellationToken,
 ) -> Result<DownloadResult> {
     info!("Starting video download process for URL: {}", url);
     debug!("Output directory: {}", output_dir.display());
@@ -189,11 +217,13 @@ pub async fn download_video(
         tokio::fs::create_dir_all(&temp_dir).await?;
     }
     
-    // Get video info first to get the title
-    info!("Fetching video information...");
-    let video_info = get_video_info(url, window).await?;
     let safe_title = sanitize_filename(&video_info.title);
     info!("Video title: {}", safe_title);
+    // A clip gets names of its own, so it isn't mistaken for the whole video
+    let safe_title = match section {
+        Some((start, end)) => format!("{}_{:.0}-{:.0}", safe_title, start, end),
+        None => safe_title,
+    };
 
     // Check if files already exist in temp directory
     let video_path = temp_dir.join(format!("{}_video.mp4", safe_title));
@@ -207,9 +237,9 @@ pub async fn download_video(
         });
     }
 
-    // Create cancellation token
-    let cancellation_token = CancellationToken::new();
-    let token_clone = cancellation_token.clone();
+    // Ctrl+C stops this download only, cancelling the job reaches it through the parent
+    let download_token = cancellation_token.child_token();
+    let token_clone = download_token.clone();
 
     // Setup Ctrl+C handler
     let ctrl_c_handler = tokio::spawn(async move {
@@ -247,7 +277,7 @@ pub async fn download_video(
     // Clone necessary values for tasks
     let url_clone = url.to_string();
     let ytdlp_path_clone = ytdlp_path.clone();
-    let cancellation_token_clone = cancellation_token.clone();
+    let cancellation_token_clone = download_token.clone();
 
     // Start audio download task
     info!("Starting audio download task...");
@@ -256,6 +286,7 @@ pub async fn download_video(
             &ytdlp_path_clone,
             &url_clone,
             &audio_template,
+            section,
             Some(audio_progress_tx),
             cancellation_token_clone,
             child_processes_clone,
@@ -266,7 +297,7 @@ pub async fn download_video(
     // Clone URL again for video task
     let url_clone_video = url.to_string();
     let ytdlp_path_clone_video = ytdlp_path.clone();
-    let cancellation_token_clone = cancellation_token.clone();
+    let cancellation_token_clone = download_token.clone();
     let child_processes_clone = child_processes.clone();
 
     // Start video download task
@@ -276,6 +307,7 @@ pub async fn download_video(
             &ytdlp_path_clone_video,
             &url_clone_video,
             &video_template,
+            section,
             Some(video_progress_tx),
             cancellation_token_clone,
             child_processes_clone,
@@ -285,7 +317,7 @@ pub async fn download_video(
 
     // Monitor progress from both downloads
     info!("Setting up progress monitoring...");
-    let cancellation_token_clone = cancellation_token.clone();
+    let cancellation_token_clone = download_token.clone();
     tokio::spawn(async move {
         loop {
             tokio::select! {
@@ -293,20 +325,20 @@ pub async fn download_video(
                     let mut progress = audio_progress;
                     progress.component = "audio".to_string();
                     debug!("Audio progress: {}% at {}", progress.progress, progress.speed.as_deref().unwrap_or("unknown speed"));
-                    if let Some(sender) = &progress_sender {
-                        if let Err(e) = sender.send(progress).await {
-                            error!("Failed to send audio progress: {}", e);
-                        }
+                    if let Some(sender) = &progress_sender
+                        && let Err(e) = sender.send(progress).await
+                    {
+                        error!("Failed to send audio progress: {}", e);
                     }
                 }
                 Some(video_progress) = video_progress_rx.recv() => {
                     let mut progress = video_progress;
                     progress.component = "video".to_string();
                     debug!("Video progress: {}% at {}", progress.progress, progress.speed.as_deref().unwrap_or("unknown speed"));
-                    if let Some(sender) = &progress_sender {
-                        if let Err(e) = sender.send(progress).await {
-                            error!("Failed to send video progress: {}", e);
-                        }
+                    if let Some(sender) = &progress_sender
+                        && let Err(e) = sender.send(progress).await
+                    {
+                        error!("Failed to send video progress: {}", e);
                     }
                 }
                 _ = cancellation_token_clone.cancelled() => {
@@ -323,13 +355,14 @@ pub async fn download_video(
 
     // Wait for both downloads to complete with timeout
     info!("Waiting for downloads to complete...");
-    let download_timeout = std::time::Duration::from_secs(3600); // 1 hour timeout
+    let timeout_secs = advanced_config::current().timeouts.download_secs;
+    let download_timeout = std::time::Duration::from_secs(timeout_secs);
 
     let result = tokio::select! {
         result = timeout(download_timeout, futures::future::try_join(audio_task, video_task)) => {
-            result.map_err(|_| anyhow!("Download timeout exceeded (1 hour)"))??
+            result.map_err(|_| anyhow!("Download timeout exceeded ({} seconds)", timeout_secs))??
         }
-        _ = cancellation_token.cancelled() => {
+        _ = download_token.cancelled() => {
             warn!("Download cancelled by user");
             // Cleanup child processes
             let mut processes = child_processes.lock().await;
@@ -406,10 +439,8 @@ pub async fn download_video(
             // List all files in the output directory for debugging
             error!("Files in output directory:");
             if let Ok(entries) = std::fs::read_dir(output_dir) {
-                for entry in entries {
-                    if let Ok(entry) = entry {
-                        error!("  {}", entry.path().display());
-                    }
+                for entry in entries.flatten() {
+                    error!("  {}", entry.path().display());
                 }
             }
             
@@ -440,7 +471,8 @@ pub async fn download_video(
 async fn download_audio(
     ytdlp_path: &PathBuf,
     url: &str,
-    output_template: &PathBuf,
+    output_template: &Path,
+    section: Option<(f64, f64)>,
     progress_sender: Option<mpsc::Sender<DownloadProgress>>,
     cancellation_token: CancellationToken,
     child_processes: Arc<Mutex<Vec<Child>>>,
@@ -479,6 +511,7 @@ async fn download_audio(
         .arg("--no-warnings")
         .arg("--no-mtime") // Don't use the media file timestamp
         .arg("--restrict-filenames") // Restrict filenames to only ASCII characters
+        .args(section_args(section))
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
@@ -497,7 +530,8 @@ async fn download_audio(
 async fn download_video_only(
     ytdlp_path: &PathBuf,
     url: &str,
-    output_template: &PathBuf,
+    output_template: &Path,
+    section: Option<(f64, f64)>,
     progress_sender: Option<mpsc::Sender<DownloadProgress>>,
     cancellation_token: CancellationToken,
     child_processes: Arc<Mutex<Vec<Child>>>,
@@ -533,6 +567,7 @@ async fn download_video_only(
         .arg("--no-warnings")
         .arg("--no-mtime") // Don't use the media file timestamp
         .arg("--restrict-filenames") // Restrict filenames to only ASCII characters
+        .args(section_args(section))
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
@@ -553,7 +588,7 @@ async fn process_download(
     progress_sender: Option<mpsc::Sender<DownloadProgress>>,
     cancellation_token: CancellationToken,
     child_processes: Arc<Mutex<Vec<Child>>>,
-    expected_file_path: &PathBuf,  // The exact file path we expect
+    expected_file_path: &Path,  // The exact file path we expect
 ) -> Result<PathBuf> {
     debug!("Starting download process with command: {:?}", command);
     info!("Will look for output file at: {}", expected_file_path.display());
@@ -623,10 +658,10 @@ async fn process_download(
                 if let Some(progress) = parse_progress(&line) {
                     last_progress_time = std::time::Instant::now();
 
-                    if let Some(sender) = &progress_sender {
-                        if let Err(e) = sender.send(progress).await {
-                            error!("Failed to send progress: {}", e);
-                        }
+                    if let Some(sender) = &progress_sender
+                        && let Err(e) = sender.send(progress).await
+                    {
+                        error!("Failed to send progress: {}", e);
                     }
                 }
 
@@ -662,7 +697,7 @@ async fn process_download(
     // Check if the expected file exists
     if check_file_exists_and_valid(expected_file_path).await {
         info!("Found expected file: {}", expected_file_path.display());
-        return Ok(expected_file_path.clone());
+        return Ok(expected_file_path.to_path_buf());
     }
 
     // If the file doesn't exist, try to find it in the parent directory by its extension
@@ -698,90 +733,221 @@ pub async fn get_video_info(url: &str, window: &tauri::Window) -> Result<VideoIn
     let app_handle = window.app_handle();
     
     // Try to use cached cookies first
-    if let Ok(Some(cookies)) = YoutubeCookieManager::load_cookies(&app_handle).await {
-        if cookies.valid {
-            info!("Using cached cookies from {} browser", cookies.browser);
-            
-            // Try with cached browser cookies
-            let result = try_get_video_info(&ytdlp_path, url, &cookies.browser).await;
-            
-            if let Ok(video_info) = result {
-                // Cookies still valid, return the result
-                return Ok(video_info);
-            } else {
-                // Cookies no longer valid, invalidate them
-                warn!("Cached cookies from {} have expired, invalidating", cookies.browser);
-                let _ = YoutubeCookieManager::invalidate_cookies(&app_handle).await;
+    let mut cached_source = None;
+    if let Ok(Some(cookies)) = YoutubeCookieManager::load_cookies(app_handle).await
+        && cookies.valid
+    {
+        let source = cookies.source();
+        info!("Using cached cookies from {}", source.name());
+
+        match try_get_video_info(&ytdlp_path, url, &source).await {
+            Ok(video_info) => return Ok(video_info),
+            // Problems with the video itself won't go away with other cookies
+            Err(e) if e.downcast_ref::<VideoUnavailable>().is_some() => return Err(e),
+            Err(e) => {
+                warn!("Cached cookies from {} no longer work, invalidating: {}", source.name(), e);
+                let _ = YoutubeCookieManager::invalidate_cookies(app_handle).await;
             }
         }
+        cached_source = Some(source);
     }
-    
-    // If we get here, we need to try with fresh browser cookies
-    let mut tried_browsers = Vec::new();
+
+    // Browsers with a profile on this machine, then no cookies at all
+    let mut strategies = cookies::browser_strategies(app_handle);
+    strategies.retain(|source| Some(source) != cached_source.as_ref());
+    strategies.push(CookieSource::Anonymous);
+
     let mut showed_keychain_info = false;
-    
-    // Try up to 3 times with increasing delays
-    for attempt in 1..=3 {
-        info!("Attempt {} to get video info with fresh browser cookies", attempt);
-
-        // Try different browsers in sequence
-        let browsers = if attempt == 1 {
-            let mut browsers = vec!["chrome", "firefox"];
-            #[cfg(target_os = "macos")]
-            browsers.push("safari");
-            browsers
-        } else {
-            vec!["chrome"]  // On retry attempts, just use Chrome
-        };
+    let mut failures: Vec<(String, String)> = Vec::new();
+    let mut locked = Vec::new();
+
+    for source in &strategies {
+        // Explain the Keychain prompt before the first browser that triggers it
+        if cookies::needs_keychain_prompt(source) && !showed_keychain_info {
+            show_keychain_info_dialog(window).await;
+            showed_keychain_info = true;
+            // Небольшая пауза, чтобы пользователь успел прочитать сообщение
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        }
 
-        for browser in browsers {
-            if tried_browsers.contains(&browser) {
-                continue;
-            }
-            tried_browsers.push(browser);
-            
-            // Show keychain access info dialog before first browser attempt
-            if !showed_keychain_info {
-                show_keychain_info_dialog(window).await;
-                showed_keychain_info = true;
-                // Небольшая пауза, чтобы пользователь успел прочитать сообщение
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-            }
-            
-            info!("Trying with fresh {} cookies...", browser);
-            let result = try_get_video_info(&ytdlp_path, url, browser).await;
-            
-            if let Ok(video_info) = result {
-                // Cookies worked, save them for future use
-                info!("Successfully retrieved video info with {} cookies, saving for future use", browser);
-                let _ = YoutubeCookieManager::save_cookies(&app_handle, browser, true).await;
+        info!("Trying to get video info with {}...", source.name());
+        match try_get_video_info(&ytdlp_path, url, source).await {
+            Ok(video_info) => {
+                if source != &CookieSource::Anonymous {
+                    info!("Successfully retrieved video info with {} cookies, saving for future use", source.name());
+                    let _ = YoutubeCookieManager::save_cookies(app_handle, source, true).await;
+                }
                 return Ok(video_info);
             }
+            Err(e) => {
+                if e.downcast_ref::<VideoUnavailable>().is_some() {
+                    return Err(e);
+                }
+                if let Some(CookieError::ProfileLocked { .. }) = e.downcast_ref::<CookieError>() {
+                    locked.push(source.clone());
+                }
+                failures.push((source.name(), e.to_string()));
+            }
         }
+    }
 
-        if attempt < 3 {
-            let delay = std::time::Duration::from_secs(attempt as u64);
-            warn!("Retrying in {} seconds...", delay.as_secs());
-            std::thread::sleep(delay);
-            continue;
+    // A locked database is usually a browser that's still running, give the user a moment to close it
+    for source in &locked {
+        warn!("{} cookie database was locked, retrying once", source.name());
+        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        if let Ok(video_info) = try_get_video_info(&ytdlp_path, url, source).await {
+            let _ = YoutubeCookieManager::save_cookies(app_handle, source, true).await;
+            return Ok(video_info);
         }
     }
 
-    // If we get here, all attempts with all browsers failed
-    let tried_browsers_str = tried_browsers.join(", ");
+    // Nothing worked: offer the cookies.txt import, which works the same on every OS
+    let _ = window.emit("cookies-import-suggested", json!({ "failures": failures.iter().map(|(source, error)| json!({
+        "source": source,
+        "error": error,
+    })).collect::<Vec<_>>() }));
+
+    let details = failures
+        .iter()
+        .map(|(source, error)| format!("- {}: {}", source, error.lines().next().unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join("\n");
     Err(anyhow!(
         "Не удалось получить информацию о видео. YouTube требует авторизацию.\n\n\
+        Попытки:\n{}\n\n\
         Пожалуйста:\n\
-        1. Войдите в свой аккаунт YouTube в одном из браузеров ({}).\n\
-        2. Откройте YouTube и просмотрите любое видео для обновления cookies.\n\
-        3. Попробуйте снова.\n\n\
-        Если проблема сохраняется, попробуйте использовать другой браузер.",
-        tried_browsers_str
+        1. Войдите в свой аккаунт YouTube в одном из браузеров и закройте его.\n\
+        2. Попробуйте снова.\n\n\
+        Если браузерные cookies недоступны, экспортируйте cookies.txt (например, расширением \
+        «Get cookies.txt LOCALLY») и импортируйте его в приложение.",
+        details
     ))
 }
 
-/// Helper function to attempt to get video info with a specific browser's cookies
-async fn try_get_video_info(ytdlp_path: &PathBuf, url: &str, browser: &str) -> Result<VideoInfo> {
+/// Video info with one given cookie source, for callers without an app handle
+pub async fn get_video_info_with(url: &str, source: &CookieSource) -> Result<VideoInfo> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(anyhow!("Invalid URL format. URL must start with http:// or https://"));
+    }
+    let ytdlp_path = get_tool_path("yt-dlp").ok_or_else(|| anyhow!("yt-dlp not found"))?;
+    try_get_video_info(&ytdlp_path, url, source).await
+}
+
+/// Failure caused by the video itself, other cookies won't help
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct VideoUnavailable(String);
+
+/// Import a cookies.txt export and use it for all further requests
+pub async fn import_cookies_file(app_handle: &tauri::AppHandle, path: &Path) -> Result<()> {
+    let imported = cookies::import_file(app_handle, path).await?;
+    YoutubeCookieManager::save_cookies(app_handle, &CookieSource::File(imported), true).await
+}
+
+/// Helper function to attempt to get video info with the given cookies
+/// An upload listed on a channel page
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChannelUpload {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    /// Name of the channel, when yt-dlp reports it
+    pub channel: Option<String>,
+    /// Upload date as YYYYMMDD, flat listings often leave it out
+    pub upload_date: Option<String>,
+}
+
+/// Uploads tab of a channel URL. Channel home pages list featured content and
+/// shorts mixed together, so `/videos` is appended unless a tab is already given.
+pub fn uploads_url(channel_url: &str) -> String {
+    let trimmed = channel_url.trim().trim_end_matches('/');
+    let is_channel = trimmed.contains("/@") || trimmed.contains("/channel/") || trimmed.contains("/c/") || trimmed.contains("/user/");
+    let has_tab = ["/videos", "/streams", "/shorts", "/playlists", "/featured"]
+        .iter()
+        .any(|tab| trimmed.ends_with(tab));
+    if is_channel && !has_tab {
+        format!("{}/videos", trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// List the newest uploads of a channel or playlist, newest first
+pub async fn list_channel_uploads(
+    app_handle: &tauri::AppHandle,
+    channel_url: &str,
+    limit: usize,
+) -> Result<Vec<ChannelUpload>> {
+    if !channel_url.starts_with("http://") && !channel_url.starts_with("https://") {
+        return Err(anyhow!("Invalid URL format. URL must start with http:// or https://"));
+    }
+    let ytdlp_path = get_tool_path("yt-dlp")
+        .ok_or_else(|| anyhow!("yt-dlp not found. Please ensure it is installed correctly."))?;
+
+    // Public channels list fine without cookies, cached ones help with age-gated content
+    let source = match YoutubeCookieManager::load_cookies(app_handle).await {
+        Ok(Some(cookies)) if cookies.valid => cookies.source(),
+        _ => CookieSource::Anonymous,
+    };
+
+    let url = uploads_url(channel_url);
+    info!("Listing up to {} uploads of {} using {} cookies", limit, url, source.name());
+    let mut command = Command::new(&ytdlp_path);
+    command
+        .arg(&url)
+        .arg("--flat-playlist")
+        .arg("--dump-single-json")
+        .arg("--playlist-end")
+        .arg(limit.max(1).to_string())
+        .arg("--no-warnings")
+        .arg("--ignore-config")
+        .args(source.ytdlp_args())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    debug!("Executing command: {:?}", command);
+
+    let output = command.output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("Failed to list channel uploads: {}", stderr.trim()));
+    }
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow!("Failed to parse JSON from yt-dlp: {}", e))?;
+
+    let channel = info["channel"]
+        .as_str()
+        .or_else(|| info["uploader"].as_str())
+        .map(str::to_string);
+    let uploads = info["entries"]
+        .as_array()
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| {
+                    let id = entry["id"].as_str()?.to_string();
+                    let url = entry["url"]
+                        .as_str()
+                        .filter(|url| url.starts_with("http"))
+                        .map(str::to_string)
+                        .unwrap_or_else(|| format!("https://www.youtube.com/watch?v={}", id));
+                    Some(ChannelUpload {
+                        title: entry["title"].as_str().unwrap_or(&id).to_string(),
+                        url,
+                        channel: entry["channel"].as_str().map(str::to_string).or_else(|| channel.clone()),
+                        upload_date: entry["upload_date"].as_str().map(str::to_string),
+                        id,
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    info!("Found {} uploads on {}", uploads.len(), url);
+    Ok(uploads)
+}
+
+async fn try_get_video_info(ytdlp_path: &PathBuf, url: &str, source: &CookieSource) -> Result<VideoInfo> {
+    let browser = source.name();
     info!("Trying to get video info using {} cookies", browser);
     
     let mut command = Command::new(ytdlp_path);
@@ -792,8 +958,7 @@ async fn try_get_video_info(ytdlp_path: &PathBuf, url: &str, browser: &str) -> R
         .arg("--no-warnings")
         .arg("--ignore-config")
         .arg("--no-check-certificates")
-        .arg("--cookies-from-browser")
-        .arg(browser)
+        .args(source.ytdlp_args())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
@@ -841,15 +1006,20 @@ async fn try_get_video_info(ytdlp_path: &PathBuf, url: &str, browser: &str) -> R
                     }
                 };
 
+                let id = info["id"].as_str().unwrap_or("").to_string();
                 let thumbnail = info["thumbnail"].as_str().unwrap_or("").to_string();
                 let description = info["description"].as_str().unwrap_or("").to_string();
                 let language = info["language"].as_str().map(|s| s.to_string());
                 let original_language = info["original_language"].as_str().map(|s| s.to_string());
+                let mut subtitles = subtitles::from_ytdlp_info(&info);
+                subtitles.extend(subtitles::from_description(&description));
+                let chapters = chapters::from_ytdlp_info(&info);
 
                 info!("Successfully retrieved video info for: {}", title);
                 debug!("Video duration: {}s", duration);
 
-                return Ok(VideoInfo {
+                Ok(VideoInfo {
+                    id,
                     title,
                     duration,
                     url: url.to_string(),
@@ -857,26 +1027,31 @@ async fn try_get_video_info(ytdlp_path: &PathBuf, url: &str, browser: &str) -> R
                     description,
                     language,
                     original_language,
-                });
+                    subtitles,
+                    chapters,
+                })
             } else {
                 let stderr = String::from_utf8_lossy(&browser_output.stderr);
                 error!("Failed with {} cookies: {}", browser, stderr);
 
                 // Check for specific error conditions
                 if stderr.contains("Video unavailable") {
-                    return Err(anyhow!("Видео недоступно. Возможно оно приватное или было удалено."));
+                    return Err(VideoUnavailable("Видео недоступно. Возможно оно приватное или было удалено.".to_string()).into());
                 } else if stderr.contains("This video is not available in your country") {
-                    return Err(anyhow!("Это видео недоступно в вашей стране."));
+                    return Err(VideoUnavailable("Это видео недоступно в вашей стране.".to_string()).into());
                 } else if stderr.contains("Sign in to confirm your age") {
                     return Err(anyhow!("Видео имеет возрастные ограничения. Пожалуйста, войдите в свой аккаунт YouTube в браузере."));
                 }
+                if let Some(cookie_error) = cookies::classify_failure(source, &stderr) {
+                    return Err(cookie_error.into());
+                }
                 
-                return Err(anyhow!("Failed to get video info: {}", stderr));
+                Err(anyhow!("Failed to get video info: {}", stderr))
             }
         }
         Err(e) => {
             error!("Error trying {} cookies: {}", browser, e);
-            return Err(anyhow!("Error trying {} cookies: {}", browser, e));
+            Err(anyhow!("Error trying {} cookies: {}", browser, e))
         }
     }
 }
@@ -942,15 +1117,15 @@ async fn find_newest_file_by_extension(dir: &Path, extension: &str) -> Result<Pa
                 let path = entry.path();
                 
                 // Only consider files with the expected extension
-                if let Some(ext) = path.extension() {
-                    if ext.to_string_lossy().to_lowercase() == extension.to_lowercase() {
-                        match entry.metadata().await {
-                            Ok(metadata) => {
-                                info!("Found file with matching extension: {}", path.display());
-                                matching_files.push((path, metadata));
-                            },
-                            Err(e) => warn!("Failed to get metadata for {}: {}", path.display(), e)
-                        }
+                if let Some(ext) = path.extension()
+                    && ext.to_string_lossy().to_lowercase() == extension.to_lowercase()
+                {
+                    match entry.metadata().await {
+                        Ok(metadata) => {
+                            info!("Found file with matching extension: {}", path.display());
+                            matching_files.push((path, metadata));
+                        },
+                        Err(e) => warn!("Failed to get metadata for {}: {}", path.display(), e)
                     }
                 }
             }